@@ -0,0 +1,191 @@
+//! Benchmarks encoding, decoding, and parsing a full-body frame: 55 bones & 52 blend shapes, packed into bundles the
+//! same way as `VMCSocket::send_pose`.
+//!
+//! Run with `cargo bench --bench codec`.
+
+use std::hint::black_box;
+
+use criterion::{BatchSize, Criterion, Throughput, criterion_group, criterion_main};
+use vmc::{OSCPacket, Quat, VMCPose, VMCTime, Vec3, osc};
+
+const BONES: [&str; 55] = [
+	"Hips",
+	"LeftUpperLeg",
+	"RightUpperLeg",
+	"LeftLowerLeg",
+	"RightLowerLeg",
+	"LeftFoot",
+	"RightFoot",
+	"Spine",
+	"Chest",
+	"UpperChest",
+	"Neck",
+	"Head",
+	"LeftShoulder",
+	"RightShoulder",
+	"LeftUpperArm",
+	"RightUpperArm",
+	"LeftLowerArm",
+	"RightLowerArm",
+	"LeftHand",
+	"RightHand",
+	"LeftToes",
+	"RightToes",
+	"LeftEye",
+	"RightEye",
+	"Jaw",
+	"LeftThumbProximal",
+	"LeftThumbIntermediate",
+	"LeftThumbDistal",
+	"LeftIndexProximal",
+	"LeftIndexIntermediate",
+	"LeftIndexDistal",
+	"LeftMiddleProximal",
+	"LeftMiddleIntermediate",
+	"LeftMiddleDistal",
+	"LeftRingProximal",
+	"LeftRingIntermediate",
+	"LeftRingDistal",
+	"LeftLittleProximal",
+	"LeftLittleIntermediate",
+	"LeftLittleDistal",
+	"RightThumbProximal",
+	"RightThumbIntermediate",
+	"RightThumbDistal",
+	"RightIndexProximal",
+	"RightIndexIntermediate",
+	"RightIndexDistal",
+	"RightMiddleProximal",
+	"RightMiddleIntermediate",
+	"RightMiddleDistal",
+	"RightRingProximal",
+	"RightRingIntermediate",
+	"RightRingDistal",
+	"RightLittleProximal",
+	"RightLittleIntermediate",
+	"RightLittleDistal"
+];
+
+/// ARKit blend shapes, as sent by "perfect sync" face trackers.
+const BLENDSHAPES: [&str; 52] = [
+	"EyeBlinkLeft",
+	"EyeLookDownLeft",
+	"EyeLookInLeft",
+	"EyeLookOutLeft",
+	"EyeLookUpLeft",
+	"EyeSquintLeft",
+	"EyeWideLeft",
+	"EyeBlinkRight",
+	"EyeLookDownRight",
+	"EyeLookInRight",
+	"EyeLookOutRight",
+	"EyeLookUpRight",
+	"EyeSquintRight",
+	"EyeWideRight",
+	"JawForward",
+	"JawLeft",
+	"JawRight",
+	"JawOpen",
+	"MouthClose",
+	"MouthFunnel",
+	"MouthPucker",
+	"MouthLeft",
+	"MouthRight",
+	"MouthSmileLeft",
+	"MouthSmileRight",
+	"MouthFrownLeft",
+	"MouthFrownRight",
+	"MouthDimpleLeft",
+	"MouthDimpleRight",
+	"MouthStretchLeft",
+	"MouthStretchRight",
+	"MouthRollLower",
+	"MouthRollUpper",
+	"MouthShrugLower",
+	"MouthShrugUpper",
+	"MouthPressLeft",
+	"MouthPressRight",
+	"MouthLowerDownLeft",
+	"MouthLowerDownRight",
+	"MouthUpperUpLeft",
+	"MouthUpperUpRight",
+	"BrowDownLeft",
+	"BrowDownRight",
+	"BrowInnerUp",
+	"BrowOuterUpLeft",
+	"BrowOuterUpRight",
+	"CheekPuff",
+	"CheekSquintLeft",
+	"CheekSquintRight",
+	"NoseSneerLeft",
+	"NoseSneerRight",
+	"TongueOut"
+];
+
+fn frame() -> Vec<OSCPacket> {
+	let mut pose = VMCPose::new();
+	for (i, bone) in BONES.into_iter().enumerate() {
+		let t = i as f32 / BONES.len() as f32;
+		pose.set_bone(bone, Vec3::new(t, 1.0 - t, 0.5 * t), Quat::from_rotation_y(t));
+	}
+	for (i, key) in BLENDSHAPES.into_iter().enumerate() {
+		pose.set_blendshape(key, i as f32 / BLENDSHAPES.len() as f32);
+	}
+	pose.time = Some(VMCTime::new(1.0));
+	pose.to_packets()
+}
+
+fn bench_codec(c: &mut Criterion) {
+	let packets = frame();
+	let encoded: Vec<Vec<u8>> = packets.iter().map(|packet| osc::encode(packet).unwrap()).collect();
+
+	let mut group = c.benchmark_group("frame");
+	group.throughput(Throughput::Bytes(encoded.iter().map(Vec::len).sum::<usize>() as u64));
+
+	group.bench_function("encode", |b| {
+		b.iter(|| {
+			for packet in &packets {
+				black_box(osc::encode(black_box(packet)).unwrap());
+			}
+		})
+	});
+	group.bench_function("encode_into", |b| {
+		let mut buf = Vec::with_capacity(osc::MTU);
+		b.iter(|| {
+			for packet in &packets {
+				buf.clear();
+				black_box(osc::encode_into(black_box(packet), &mut buf).unwrap());
+			}
+		})
+	});
+	group.bench_function("decode", |b| {
+		b.iter(|| {
+			for bytes in &encoded {
+				black_box(osc::decode_udp(black_box(bytes)).unwrap());
+			}
+		})
+	});
+	group.bench_function("parse", |b| {
+		b.iter_batched(
+			|| packets.clone(),
+			|packets| {
+				for packet in packets {
+					black_box(vmc::parse(packet).unwrap());
+				}
+			},
+			BatchSize::SmallInput
+		)
+	});
+	group.bench_function("decode_parse", |b| {
+		b.iter(|| {
+			for bytes in &encoded {
+				let (_, packet) = osc::decode_udp(black_box(bytes)).unwrap();
+				black_box(vmc::parse(packet).unwrap());
+			}
+		})
+	});
+	group.finish();
+}
+
+criterion_group!(benches, bench_codec);
+criterion_main!(benches);
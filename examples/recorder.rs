@@ -1,68 +1,57 @@
-use std::sync::{
-	Arc, RwLock,
-	atomic::{AtomicBool, Ordering}
+use std::{
+	fs::File,
+	io::BufWriter,
+	sync::{Arc, Mutex}
 };
 
 use console::Term;
 use futures_util::StreamExt;
-use serde::Serialize;
-use vmc::{VMCMessage, VMCResult};
-
-#[derive(Default, Serialize)]
-struct MessageBundle {
-	time_delta: f32,
-	messages: Vec<VMCMessage>
-}
+use vmc::{
+	VMCResult,
+	record::{Metadata, Recorder}
+};
 
 #[tokio::main]
 async fn main() -> VMCResult<()> {
 	let mut socket = vmc::marionette!("127.0.0.1:39539").await?;
 
+	let mut recorder = Recorder::create("out.vmcr", Metadata::new().with(Metadata::RECORDER, "vmc recorder example"))?;
+	recorder.pause();
+	let recorder: Arc<Mutex<Option<Recorder<BufWriter<File>>>>> = Arc::new(Mutex::new(Some(recorder)));
+	println!("Press any key to start/pause recording, Ctrl+C to finish");
+
+	let _recorder = Arc::clone(&recorder);
 	tokio::spawn(async move {
 		tokio::signal::ctrl_c().await.unwrap();
+		if let Some(recorder) = _recorder.lock().unwrap().take() {
+			let frames = recorder.frame_count();
+			recorder.finish().unwrap();
+			println!("Saved {frames} frames to out.vmcr");
+		}
 		std::process::exit(0);
 	});
 
-	let packet_buffer = Arc::new(RwLock::new(Vec::new()));
-	let mut current_packet = MessageBundle::default();
-	let active = Arc::new(AtomicBool::new(false));
-
-	let _packet_buffer = Arc::clone(&packet_buffer);
-	let _active = Arc::clone(&active);
+	let _recorder = Arc::clone(&recorder);
 	std::thread::spawn(move || {
 		let term = Term::stdout();
 		while term.read_char().is_ok() {
-			let active = _active.load(Ordering::Relaxed);
-			if active {
-				let mut packet_buffer = _packet_buffer.write().unwrap();
-				let buf = &packet_buffer[1..];
-				std::fs::write("out.vmc", rmp_serde::to_vec(buf).unwrap()).unwrap();
-				packet_buffer.clear();
-				println!("Stopped");
-			} else {
-				println!("Started");
+			if let Some(recorder) = _recorder.lock().unwrap().as_mut() {
+				if recorder.is_paused() {
+					recorder.resume();
+					println!("Recording");
+				} else {
+					recorder.pause();
+					println!("Paused ({:.1}s recorded)", recorder.elapsed().as_secs_f32());
+				}
 			}
-			_active.store(!active, Ordering::Relaxed);
 		}
 	});
 
-	while let Some(packet) = socket.next().await {
-		let (packet, _) = packet?;
-		for message in vmc::parse(packet)? {
-			if active.load(Ordering::Relaxed) {
-				match message {
-					VMCMessage::Time(t) => {
-						{
-							let mut packet_buffer = packet_buffer.write().unwrap();
-							packet_buffer.push(current_packet);
-						}
-						current_packet = MessageBundle::default();
-
-						current_packet.time_delta = t.0;
-					}
-					message => current_packet.messages.push(message)
-				}
-			}
+	let mut messages = socket.messages();
+	while let Some(message) = messages.next().await {
+		let (message, _) = message?;
+		if let Some(recorder) = recorder.lock().unwrap().as_mut() {
+			recorder.record(message)?;
 		}
 	}
 
@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use vmc::osc;
+
+fuzz_target!(|data: &[u8]| {
+	if let Ok((_, packet)) = osc::decode_udp(data) {
+		// anything we can decode, we should be able to encode again
+		let _ = osc::encode(&packet);
+	}
+	let _ = osc::decode_tcp_vec(data);
+	let _ = osc::debug_dump(data);
+});
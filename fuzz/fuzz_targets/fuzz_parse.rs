@@ -0,0 +1,19 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use vmc::{IntoOSCPacket, VMCAvatarState, osc};
+
+fuzz_target!(|data: &[u8]| {
+	let Ok((_, packet)) = osc::decode_udp(data) else {
+		return;
+	};
+	let Ok(messages) = vmc::parse(packet) else {
+		return;
+	};
+	let mut avatar = VMCAvatarState::new();
+	for message in messages {
+		let _ = osc::encode(&message.clone().into_osc_packet());
+		avatar.apply(message);
+	}
+	let _ = avatar.to_pose().to_messages();
+});
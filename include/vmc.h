@@ -0,0 +1,81 @@
+/*
+ * C API for the `vmc` crate, built with:
+ *
+ *     cargo rustc --release --features ffi --crate-type cdylib
+ *
+ * Functions which can fail return 0 on success and -1 on failure; see vmc_last_error().
+ * A socket must not be used from multiple threads at the same time.
+ */
+
+#ifndef VMC_H
+#define VMC_H
+
+#include <stdbool.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct VMCSocket VMCSocket;
+
+typedef enum VMCMessageKind {
+	/* /VMC/Ext/Root/Pos; uses position & rotation. */
+	VMC_MESSAGE_ROOT = 0,
+	/* /VMC/Ext/Bone/Pos; uses name, position, & rotation. */
+	VMC_MESSAGE_BONE = 1,
+	/* /VMC/Ext/{Hmd,Con,Tra}/Pos; uses name (the joint), position, rotation, device, & local. */
+	VMC_MESSAGE_DEVICE = 2,
+	/* /VMC/Ext/Blend/Val; uses name & value. */
+	VMC_MESSAGE_BLENDSHAPE = 3,
+	/* /VMC/Ext/Blend/Apply */
+	VMC_MESSAGE_APPLY_BLENDSHAPES = 4,
+	/* /VMC/Ext/OK; uses model_state. */
+	VMC_MESSAGE_STATE = 5,
+	/* /VMC/Ext/T; uses value. */
+	VMC_MESSAGE_TIME = 6
+} VMCMessageKind;
+
+/* A received message. Fields not used by the message's kind are zeroed. */
+typedef struct VMCMessage {
+	VMCMessageKind kind;
+	/* Valid until the next call to vmc_poll_message() or vmc_socket_free(). */
+	const char *name;
+	/* x, y, z */
+	float position[3];
+	/* x, y, z, w */
+	float rotation[4];
+	/* The blend shape value or time. */
+	float value;
+	/* 0 for an HMD, 1 for a controller, 2 for a tracker. */
+	int32_t device;
+	bool local;
+	/* 0 if the model is not loaded, 1 if it is. */
+	int32_t model_state;
+} VMCMessage;
+
+/* Returns a description of the last error on the calling thread, or NULL. */
+const char *vmc_last_error(void);
+
+/* Binds a socket to an address, e.g. "0.0.0.0:39539". Returns NULL on failure. */
+VMCSocket *vmc_socket_bind(const char *addr);
+/* Connects a socket to the address packets are sent to. */
+int32_t vmc_socket_connect(VMCSocket *socket, const char *addr);
+/* Closes & frees a socket. Passing NULL does nothing. */
+void vmc_socket_free(VMCSocket *socket);
+
+int32_t vmc_send_root(VMCSocket *socket, const float position[3], const float rotation[4]);
+int32_t vmc_send_bone(VMCSocket *socket, const char *bone, const float position[3], const float rotation[4]);
+int32_t vmc_send_blendshape(VMCSocket *socket, const char *key, float value);
+int32_t vmc_send_apply_blendshapes(VMCSocket *socket);
+int32_t vmc_send_time(VMCSocket *socket, float time);
+
+/* Receives the next message without blocking. Returns 1 if a message was written to `out`, 0 if none is available,
+ * and -1 on failure. */
+int32_t vmc_poll_message(VMCSocket *socket, VMCMessage *out);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
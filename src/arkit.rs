@@ -0,0 +1,308 @@
+//! Mapping of ARKit face tracking blend shapes to VRM blend shapes.
+//!
+//! Face trackers built on ARKit (and compatible trackers like MediaPipe's Face Landmarker) output 52 blend shapes.
+//! Avatars set up for "perfect sync" have blend shapes with the same names, so the values can be passed through
+//! directly; other VRM avatars only have the [standard VRM blend shapes](crate::VMCStandardVRMBlendShape), so ARKit
+//! values have to be combined into those. [`BlendShapeMapper`] does either (or both), using [`VRM_MAPPING`] by default.
+//!
+//! # Examples
+//!
+//! ```
+//! use vmc::{VMCStandardVRMBlendShape, arkit::BlendShapeMapper};
+//!
+//! let mapper =
+//! 	BlendShapeMapper::vrm().with_mapping(VMCStandardVRMBlendShape::A, [("JawOpen", 0.8), ("MouthFunnel", 0.2)]);
+//! let values = mapper.map([("jawOpen", 0.5), ("eyeBlink_L", 1.0)]);
+//! assert_eq!(values.get("A"), Some(&0.4));
+//! assert_eq!(values.get("Blink_L"), Some(&1.0));
+//! ```
+
+use std::collections::HashMap;
+
+use crate::{VMCPose as Pose, VMCStandardVRMBlendShape as StandardVRMBlendShape, ifacialmocap::perfect_sync_name, message::BlendShape, name::Name};
+
+/// The 52 ARKit blend shapes, by their "perfect sync" names.
+pub const BLENDSHAPES: [&str; 52] = [
+	"EyeBlinkLeft",
+	"EyeLookDownLeft",
+	"EyeLookInLeft",
+	"EyeLookOutLeft",
+	"EyeLookUpLeft",
+	"EyeSquintLeft",
+	"EyeWideLeft",
+	"EyeBlinkRight",
+	"EyeLookDownRight",
+	"EyeLookInRight",
+	"EyeLookOutRight",
+	"EyeLookUpRight",
+	"EyeSquintRight",
+	"EyeWideRight",
+	"JawForward",
+	"JawLeft",
+	"JawRight",
+	"JawOpen",
+	"MouthClose",
+	"MouthFunnel",
+	"MouthPucker",
+	"MouthLeft",
+	"MouthRight",
+	"MouthSmileLeft",
+	"MouthSmileRight",
+	"MouthFrownLeft",
+	"MouthFrownRight",
+	"MouthDimpleLeft",
+	"MouthDimpleRight",
+	"MouthStretchLeft",
+	"MouthStretchRight",
+	"MouthRollLower",
+	"MouthRollUpper",
+	"MouthShrugLower",
+	"MouthShrugUpper",
+	"MouthPressLeft",
+	"MouthPressRight",
+	"MouthLowerDownLeft",
+	"MouthLowerDownRight",
+	"MouthUpperUpLeft",
+	"MouthUpperUpRight",
+	"BrowDownLeft",
+	"BrowDownRight",
+	"BrowInnerUp",
+	"BrowOuterUpLeft",
+	"BrowOuterUpRight",
+	"CheekPuff",
+	"CheekSquintLeft",
+	"CheekSquintRight",
+	"NoseSneerLeft",
+	"NoseSneerRight",
+	"TongueOut"
+];
+
+/// The default mapping of ARKit blend shapes to standard VRM blend shapes. Each VRM blend shape is the weighted sum of
+/// its ARKit sources, clamped to `0..=1`.
+pub const VRM_MAPPING: &[(StandardVRMBlendShape, &[(&str, f32)])] = &[
+	(StandardVRMBlendShape::A, &[("JawOpen", 1.0)]),
+	(StandardVRMBlendShape::I, &[("MouthStretchLeft", 0.5), ("MouthStretchRight", 0.5)]),
+	(StandardVRMBlendShape::U, &[("MouthPucker", 1.0)]),
+	(StandardVRMBlendShape::E, &[("MouthLowerDownLeft", 0.5), ("MouthLowerDownRight", 0.5)]),
+	(StandardVRMBlendShape::O, &[("MouthFunnel", 1.0)]),
+	(StandardVRMBlendShape::Blink, &[("EyeBlinkLeft", 0.5), ("EyeBlinkRight", 0.5)]),
+	(StandardVRMBlendShape::BlinkL, &[("EyeBlinkLeft", 1.0)]),
+	(StandardVRMBlendShape::BlinkR, &[("EyeBlinkRight", 1.0)]),
+	(StandardVRMBlendShape::Joy, &[("MouthSmileLeft", 0.5), ("MouthSmileRight", 0.5)]),
+	(StandardVRMBlendShape::Angry, &[("BrowDownLeft", 0.5), ("BrowDownRight", 0.5)]),
+	(StandardVRMBlendShape::Sorrow, &[("BrowInnerUp", 0.5), ("MouthFrownLeft", 0.25), ("MouthFrownRight", 0.25)]),
+	(StandardVRMBlendShape::Fun, &[("CheekSquintLeft", 0.5), ("CheekSquintRight", 0.5)]),
+	(StandardVRMBlendShape::LookUp, &[("EyeLookUpLeft", 0.5), ("EyeLookUpRight", 0.5)]),
+	(StandardVRMBlendShape::LookDown, &[("EyeLookDownLeft", 0.5), ("EyeLookDownRight", 0.5)]),
+	// looking to the left turns the left eye outwards and the right eye inwards
+	(StandardVRMBlendShape::LookLeft, &[("EyeLookOutLeft", 0.5), ("EyeLookInRight", 0.5)]),
+	(StandardVRMBlendShape::LookRight, &[("EyeLookInLeft", 0.5), ("EyeLookOutRight", 0.5)])
+];
+
+/// Returns the "perfect sync" name of an ARKit blend shape given in any of the common spellings (`eyeBlink_L`,
+/// `eyeBlinkLeft`, or `EyeBlinkLeft`), or `None` if it isn't an ARKit blend shape.
+pub fn canonical_name(name: &str) -> Option<&'static str> {
+	let name = perfect_sync_name(name);
+	BLENDSHAPES.into_iter().find(|shape| *shape == name)
+}
+
+/// Maps ARKit blend shape values to the blend shapes of an avatar.
+///
+/// A mapper has a set of target blend shapes, each computed as the weighted sum of source blend shapes, and can also
+/// pass ARKit blend shapes through under their "perfect sync" names. Source names of ARKit blend shapes are matched in
+/// any spelling accepted by [`canonical_name`]; other source names must match exactly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlendShapeMapper {
+	mappings: Vec<(Name, Vec<(Name, f32)>)>,
+	passthrough: bool
+}
+
+impl Default for BlendShapeMapper {
+	fn default() -> Self {
+		Self::vrm()
+	}
+}
+
+impl BlendShapeMapper {
+	/// Creates a mapper with no mappings, which outputs nothing.
+	pub fn new() -> Self {
+		Self {
+			mappings: Vec::new(),
+			passthrough: false
+		}
+	}
+
+	/// Creates a mapper which maps ARKit blend shapes to standard VRM blend shapes using [`VRM_MAPPING`].
+	pub fn vrm() -> Self {
+		VRM_MAPPING
+			.iter()
+			.fold(Self::new(), |mapper, (target, sources)| mapper.with_mapping(*target, sources.iter().copied()))
+	}
+
+	/// Creates a mapper which only passes ARKit blend shapes through under their "perfect sync" names.
+	pub fn perfect_sync() -> Self {
+		Self::new().with_passthrough(true)
+	}
+
+	/// Sets whether ARKit blend shapes are also output under their "perfect sync" names.
+	pub fn with_passthrough(mut self, passthrough: bool) -> Self {
+		self.passthrough = passthrough;
+		self
+	}
+
+	/// Sets the sources of a target blend shape, replacing its existing mapping if there is one.
+	pub fn with_mapping<S: Into<Name>>(mut self, target: impl Into<Name>, sources: impl IntoIterator<Item = (S, f32)>) -> Self {
+		let target = target.into();
+		let sources = sources
+			.into_iter()
+			.map(|(source, weight)| {
+				let source = source.into();
+				(canonical_name(&source).map_or(source, Name::from_static), weight)
+			})
+			.collect();
+		match self.mappings.iter_mut().find(|(existing, _)| *existing == target) {
+			Some((_, existing)) => *existing = sources,
+			None => self.mappings.push((target, sources))
+		}
+		self
+	}
+
+	/// Removes the mapping of a target blend shape.
+	pub fn without_mapping(mut self, target: impl AsRef<str>) -> Self {
+		self.mappings.retain(|(existing, _)| existing.as_str() != target.as_ref());
+		self
+	}
+
+	/// Maps source blend shape values to target values.
+	///
+	/// Targets are only included if at least one of their sources is present.
+	pub fn map<K: AsRef<str>>(&self, values: impl IntoIterator<Item = (K, f32)>) -> HashMap<Name, f32> {
+		let values: HashMap<Name, f32> = values
+			.into_iter()
+			.map(|(key, value)| {
+				let key = key.as_ref();
+				(canonical_name(key).map_or_else(|| Name::new(key), Name::from_static), value)
+			})
+			.collect();
+
+		let mut mapped = HashMap::with_capacity(self.mappings.len());
+		if self.passthrough {
+			mapped.extend(
+				values
+					.iter()
+					.filter(|(key, _)| canonical_name(key).is_some())
+					.map(|(key, value)| (key.clone(), *value))
+			);
+		}
+		for (target, sources) in &self.mappings {
+			let mut present = false;
+			let mut sum = 0.0;
+			for (source, weight) in sources {
+				if let Some(value) = values.get(source.as_str()) {
+					present = true;
+					sum += value * weight;
+				}
+			}
+			if present {
+				mapped.insert(target.clone(), sum.clamp(0.0, 1.0));
+			}
+		}
+		mapped
+	}
+
+	/// Maps source values into [`BlendShape`] messages.
+	pub fn map_messages<K: AsRef<str>>(&self, values: impl IntoIterator<Item = (K, f32)>) -> Vec<BlendShape> {
+		self.map(values).into_iter().map(|(key, value)| BlendShape::new(key, value)).collect()
+	}
+
+	/// Replaces the blend shapes of a pose with their mapped values.
+	pub fn apply(&self, pose: &mut Pose) {
+		pose.blendshapes = self.map(pose.blendshapes.iter().map(|(key, value)| (key.as_str(), *value)));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_blendshape_mapper() {
+		assert_eq!(canonical_name("mouthSmile_R"), Some("MouthSmileRight"));
+		assert_eq!(canonical_name("eyeLookInLeft"), Some("EyeLookInLeft"));
+		assert_eq!(canonical_name("Joy"), None);
+
+		let values = [("mouthSmile_L", 1.0), ("mouthSmile_R", 0.5), ("eyeLookOut_L", 1.0), ("Custom", 0.25)];
+		let mapped = BlendShapeMapper::vrm().map(values);
+		assert_eq!(mapped.get("Joy"), Some(&0.75));
+		assert_eq!(mapped.get("LookLeft"), Some(&0.5));
+		assert_eq!(mapped.get("Blink_L"), None);
+		assert!(!mapped.contains_key("MouthSmileLeft"));
+
+		let mapped = BlendShapeMapper::perfect_sync().with_mapping("Extra", [("Custom", 2.0)]).map(values);
+		assert_eq!(mapped.get("MouthSmileLeft"), Some(&1.0));
+		assert_eq!(mapped.get("Extra"), Some(&0.5));
+		assert!(!mapped.contains_key("Custom"));
+
+		let mut pose = Pose::new();
+		pose.set_blendshape("jawOpen", 1.0);
+		BlendShapeMapper::vrm().without_mapping("A").apply(&mut pose);
+		assert!(pose.blendshapes.is_empty());
+	}
+
+	#[test]
+	fn test_canonical_names() {
+		for shape in BLENDSHAPES {
+			assert_eq!(canonical_name(shape), Some(shape));
+			// the camelCase spelling sent by ARKit
+			let mut camel = shape[..1].to_lowercase() + &shape[1..];
+			assert_eq!(canonical_name(&camel), Some(shape));
+			// ...and iFacialMocap's `_L`/`_R` suffixes
+			if let Some(base) = camel.strip_suffix("Left") {
+				camel = format!("{base}_L");
+			} else if let Some(base) = camel.strip_suffix("Right") {
+				camel = format!("{base}_R");
+			}
+			assert_eq!(canonical_name(&camel), Some(shape));
+		}
+		// names are matched case-sensitively, apart from the first letter
+		assert_eq!(canonical_name("EYEBLINKLEFT"), None);
+		assert_eq!(canonical_name("eyeblinkleft"), None);
+		assert_eq!(canonical_name(""), None);
+		assert_eq!(canonical_name("_L"), None);
+	}
+
+	#[test]
+	fn test_mapper_edge_cases() {
+		// nothing in, nothing out; targets without any sources present are omitted rather than set to 0
+		assert!(BlendShapeMapper::vrm().map::<&str>([]).is_empty());
+		assert!(BlendShapeMapper::new().map([("jawOpen", 1.0)]).is_empty());
+		assert!(BlendShapeMapper::vrm().map([("Custom", 1.0)]).is_empty());
+
+		// sums are clamped, including with negative weights
+		let mapper = BlendShapeMapper::new()
+			.with_mapping("Over", [("JawOpen", 1.0), ("MouthFunnel", 1.0)])
+			.with_mapping("Under", [("JawOpen", -1.0)]);
+		let mapped = mapper.map([("jawOpen", 0.75), ("mouthFunnel", 0.75)]);
+		assert_eq!(mapped.get("Over"), Some(&1.0));
+		assert_eq!(mapped.get("Under"), Some(&0.0));
+
+		// the same blend shape given in two spellings is one source; the last value wins
+		let mapped = BlendShapeMapper::vrm().map([("eyeBlink_L", 0.2), ("EyeBlinkLeft", 0.6)]);
+		assert_eq!(mapped.get("Blink_L"), Some(&0.6));
+
+		// replacing a mapping keeps a single entry for the target
+		let mapper = BlendShapeMapper::vrm().with_mapping(StandardVRMBlendShape::A, [("mouthFunnel", 1.0)]);
+		assert_eq!(mapper.mappings.iter().filter(|(target, _)| target == "A").count(), 1);
+		let mapped = mapper.map([("jawOpen", 1.0)]);
+		assert_eq!(mapped.get("A"), None);
+
+		// a mapping whose target is an ARKit name overrides the passed-through value
+		let mapper = BlendShapeMapper::perfect_sync().with_mapping("JawOpen", [("JawOpen", 0.5)]);
+		assert_eq!(mapper.map([("jawOpen", 1.0)]).get("JawOpen"), Some(&0.5));
+
+		let messages = BlendShapeMapper::vrm().map_messages([("eyeBlink_L", 1.0), ("eyeBlink_R", 1.0)]);
+		let mut keys: Vec<_> = messages.iter().map(|shape| (shape.key.as_str(), shape.value)).collect();
+		keys.sort_by(|a, b| a.0.cmp(b.0));
+		assert_eq!(keys, [("Blink", 1.0), ("Blink_L", 1.0), ("Blink_R", 1.0)]);
+	}
+}
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+
+use crate::{
+	blendshape::BlendShapeBuffer,
+	message::{BoneTransform, DeviceTransform, DeviceType, RootTransform, State, Time, VMCMessage},
+	name::Name,
+	pose::Pose
+};
+
+/// Tracks the current state of an avatar by applying incoming [`VMCMessage`]s.
+///
+/// This takes care of the bookkeeping a marionette would usually have to do by hand: the latest root, bone, and device
+/// transforms are stored, blend shapes are buffered until an [`ApplyBlendShapes`](VMCMessage::ApplyBlendShapes)
+/// message is received, and the latest state & time messages are kept.
+///
+/// # Examples
+///
+/// ```no_run
+/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+/// use futures_util::StreamExt;
+/// use vmc::{VMCAvatarState, VMCMessage, VMCStandardVRM0Bone};
+///
+/// let mut socket = vmc::marionette!().await?;
+/// let mut messages = socket.messages();
+/// let mut avatar = VMCAvatarState::new();
+/// while let Some(message) = messages.next().await {
+/// 	let (message, _) = message?;
+/// 	let is_frame_end = matches!(message, VMCMessage::Time(_));
+/// 	avatar.apply(message);
+/// 	if is_frame_end {
+/// 		if let Some(head) = avatar.bone(VMCStandardVRM0Bone::Head) {
+/// 			println!("head rotation: {:?}", head.rotation);
+/// 		}
+/// 		println!("joy: {:?}", avatar.blendshape("Joy"));
+/// 	}
+/// }
+/// # Ok(()) }) }
+/// ```
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::system::Resource))]
+pub struct AvatarState {
+	root: Option<RootTransform>,
+	bones: HashMap<Name, BoneTransform>,
+	devices: HashMap<(DeviceType, String), DeviceTransform>,
+	blendshapes: BlendShapeBuffer,
+	state: Option<State>,
+	time: Option<f32>
+}
+
+impl AvatarState {
+	/// Creates a new, empty avatar state.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Updates the avatar state with a single message.
+	pub fn apply(&mut self, message: VMCMessage) {
+		match message {
+			VMCMessage::RootTransform(transform) => self.root = Some(transform),
+			VMCMessage::BoneTransform(transform) => {
+				self.bones.insert(transform.bone.clone(), transform);
+			}
+			VMCMessage::DeviceTransform(transform) => {
+				self.devices.insert((transform.device, transform.joint.clone()), transform);
+			}
+			VMCMessage::BlendShape(blend) => self.blendshapes.set(blend.key, blend.value),
+			VMCMessage::ApplyBlendShapes => self.blendshapes.apply(),
+			VMCMessage::State(state) => self.state = Some(state),
+			VMCMessage::Time(time) => self.time = Some(time.0)
+		}
+	}
+
+	/// Returns the latest root transform, if one has been received.
+	pub fn root(&self) -> Option<&RootTransform> {
+		self.root.as_ref()
+	}
+
+	/// Returns the latest transform of the given bone, if one has been received.
+	///
+	/// `bone` can be either a [`StandardVRM0Bone`](crate::VMCStandardVRM0Bone) or the name of a bone.
+	pub fn bone(&self, bone: impl AsRef<str>) -> Option<&BoneTransform> {
+		self.bones.get(bone.as_ref())
+	}
+
+	/// Returns an iterator over the latest transforms of all bones received so far.
+	pub fn bones(&self) -> impl Iterator<Item = &BoneTransform> {
+		self.bones.values()
+	}
+
+	/// Returns the latest transform of the given device, if one has been received.
+	pub fn device(&self, device: DeviceType, joint: impl AsRef<str>) -> Option<&DeviceTransform> {
+		self.devices.get(&(device, joint.as_ref().to_string()))
+	}
+
+	/// Returns an iterator over the latest transforms of all devices received so far.
+	pub fn devices(&self) -> impl Iterator<Item = &DeviceTransform> {
+		self.devices.values()
+	}
+
+	/// Returns the applied value of the given blend shape, if one has been received.
+	///
+	/// `key` can be either a [`StandardVRMBlendShape`](crate::VMCStandardVRMBlendShape) or the name of a blend shape.
+	/// Values only become visible here once an [`ApplyBlendShapes`](VMCMessage::ApplyBlendShapes) message is received.
+	pub fn blendshape(&self, key: impl AsRef<str>) -> Option<f32> {
+		self.blendshapes.get(key)
+	}
+
+	/// Returns the avatar's blend shape buffer, containing both applied & pending values.
+	pub fn blendshapes(&self) -> &BlendShapeBuffer {
+		&self.blendshapes
+	}
+
+	/// Returns the latest state message, containing model, calibration, & tracking status, if one has been received.
+	pub fn state(&self) -> Option<&State> {
+		self.state.as_ref()
+	}
+
+	/// Returns the value of the latest [`Time`](crate::VMCTime) message, if one has been received.
+	pub fn time(&self) -> Option<f32> {
+		self.time
+	}
+
+	/// Returns a snapshot of the avatar's current pose, containing the root & bone transforms and applied blend shapes.
+	pub fn to_pose(&self) -> Pose {
+		Pose {
+			root: self.root.clone(),
+			bones: self.bones.clone(),
+			blendshapes: self.blendshapes.iter().map(|(key, value)| (Name::new(key), value)).collect(),
+			state: self.state.clone(),
+			time: self.time.map(Time)
+		}
+	}
+
+	/// Resets the avatar state, discarding all received data.
+	pub fn clear(&mut self) {
+		*self = Self::default();
+	}
+}
+
+impl Extend<VMCMessage> for AvatarState {
+	fn extend<T: IntoIterator<Item = VMCMessage>>(&mut self, iter: T) {
+		for message in iter {
+			self.apply(message);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use glam::{Quat, Vec3A};
+
+	use super::AvatarState;
+	use crate::message::{ApplyBlendShapes, BlendShape, BoneTransform, StandardVRM0Bone, StandardVRMBlendShape, Time, VMCMessage};
+
+	#[test]
+	fn test_avatar_state() {
+		let mut avatar = AvatarState::new();
+		avatar.extend([
+			VMCMessage::from(BoneTransform::new(StandardVRM0Bone::Head, Vec3A::ZERO, Quat::IDENTITY)),
+			BoneTransform::new(StandardVRM0Bone::Head, Vec3A::X, Quat::IDENTITY).into(),
+			BlendShape::new(StandardVRMBlendShape::Joy, 0.5).into()
+		]);
+		assert_eq!(avatar.bone(StandardVRM0Bone::Head).map(|b| b.position), Some(Vec3A::X));
+		assert_eq!(avatar.bone("Neck"), None);
+		assert_eq!(avatar.blendshape(StandardVRMBlendShape::Joy), None);
+		assert_eq!(avatar.blendshapes().iter_pending().count(), 1);
+
+		avatar.extend([VMCMessage::from(ApplyBlendShapes), Time(1.5).into()]);
+		assert_eq!(avatar.blendshape(StandardVRMBlendShape::Joy), Some(0.5));
+		assert_eq!(avatar.blendshapes().iter_pending().count(), 0);
+		assert_eq!(avatar.time(), Some(1.5));
+	}
+}
@@ -0,0 +1,356 @@
+//! [Bevy](https://bevyengine.org) integration, enabled with the `bevy` feature.
+//!
+//! [`VMCPlugin`] binds a VMC socket and drives it from a background thread, so the app doesn't need to run an async
+//! runtime. Received messages are applied to the [`VMCAvatarState`](crate::VMCAvatarState) resource and emitted as
+//! [`MessageReceived`] events. If the plugin is given a target, the transforms of all entities with a [`Bone`]
+//! component are sent to it at the end of each frame.
+//!
+//! This module targets Bevy 0.15, which shares this crate's version of `glam`, so transforms convert without copying
+//! between math types.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use bevy_app::{App, Update};
+//! use bevy_ecs::prelude::*;
+//! use vmc::{
+//! 	VMCAvatarState, VMCStandardVRM0Bone,
+//! 	bevy::{Bone, VMCPlugin}
+//! };
+//!
+//! fn print_head(avatar: Res<VMCAvatarState>) {
+//! 	if let Some(head) = avatar.bone(VMCStandardVRM0Bone::Head) {
+//! 		println!("head rotation: {:?}", head.rotation);
+//! 	}
+//! }
+//!
+//! App::new()
+//! 	.add_plugins(VMCPlugin::new("0.0.0.0:39539".parse().unwrap()))
+//! 	.add_systems(Update, print_head)
+//! 	.run();
+//! ```
+
+use std::{
+	future::{Future, poll_fn},
+	net::{self, SocketAddr},
+	pin::pin,
+	sync::Arc,
+	task::Poll,
+	thread
+};
+
+use bevy_app::{App, Plugin, PostUpdate, PreUpdate};
+use bevy_ecs::prelude::*;
+use bevy_transform::components::Transform;
+use crossbeam_queue::ArrayQueue;
+
+use crate::{
+	IntoOSCPacket, OSCPacket, VMCMessage, VMCQueuedSender, VMCSocket,
+	avatar::AvatarState,
+	message::{RootTransform, parse},
+	name::Name,
+	pose::Pose
+};
+
+/// The maximum number of received messages buffered between frames. If the app falls behind, the oldest messages are
+/// dropped.
+const INCOMING_CAPACITY: usize = 4096;
+/// The maximum number of packets queued for sending.
+const OUTGOING_CAPACITY: usize = 256;
+
+/// Binds a VMC socket and connects it to the ECS.
+///
+/// The plugin inserts the [`Connection`] & [`VMCAvatarState`](crate::VMCAvatarState) resources, adds the
+/// [`MessageReceived`] event, and runs [`receive_messages`] in [`PreUpdate`]. If a [target](VMCPlugin::with_target) is
+/// set, [`send_skeleton`] runs in [`PostUpdate`].
+#[derive(Debug, Clone)]
+pub struct VMCPlugin {
+	addr: SocketAddr,
+	target: Option<SocketAddr>
+}
+
+impl VMCPlugin {
+	/// Creates a plugin which receives on the given address.
+	pub fn new(addr: SocketAddr) -> Self {
+		Self { addr, target: None }
+	}
+
+	/// Sends this app's skeleton to the given address every frame.
+	pub fn with_target(mut self, target: SocketAddr) -> Self {
+		self.target = Some(target);
+		self
+	}
+}
+
+impl Plugin for VMCPlugin {
+	/// # Panics
+	///
+	/// Panics if the socket can't be bound or connected to the target.
+	fn build(&self, app: &mut App) {
+		let connection = Connection::open(self.addr, self.target).expect("failed to open VMC socket");
+		app.insert_resource(connection)
+			.init_resource::<AvatarState>()
+			.add_event::<MessageReceived>()
+			.add_systems(PreUpdate, receive_messages);
+		if self.target.is_some() {
+			app.add_systems(PostUpdate, send_skeleton);
+		}
+	}
+}
+
+/// The socket opened by [`VMCPlugin`].
+///
+/// The socket is closed, and its background thread stops, when this resource is removed.
+#[derive(Debug, Resource)]
+pub struct Connection {
+	incoming: Arc<ArrayQueue<(VMCMessage, SocketAddr)>>,
+	sender: VMCQueuedSender,
+	local_addr: SocketAddr,
+	target: Option<SocketAddr>
+}
+
+impl Connection {
+	fn open(addr: SocketAddr, target: Option<SocketAddr>) -> crate::VMCResult<Self> {
+		let socket = net::UdpSocket::bind(addr)?;
+		if let Some(target) = target {
+			socket.connect(target)?;
+		}
+		socket.set_nonblocking(true)?;
+		let local_addr = socket.local_addr()?;
+
+		let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+		let socket = {
+			let _guard = runtime.enter();
+			VMCSocket::new(tokio::net::UdpSocket::from_std(socket)?)
+		};
+		let (sender, task) = VMCQueuedSender::new(socket.sender(), OUTGOING_CAPACITY);
+		let incoming = Arc::new(ArrayQueue::new(INCOMING_CAPACITY));
+
+		let queue = Arc::clone(&incoming);
+		thread::Builder::new().name("vmc".to_string()).spawn(move || {
+			runtime.block_on(async move {
+				let mut receive = pin!(receive(socket, queue));
+				let mut send = pin!(task.run());
+				// the send task completes once the `Connection` (and thus its queued sender) is dropped
+				poll_fn(|cx| match send.as_mut().poll(cx) {
+					Poll::Ready(()) => Poll::Ready(()),
+					Poll::Pending => receive.as_mut().poll(cx)
+				})
+				.await
+			})
+		})?;
+
+		Ok(Self { incoming, sender, local_addr, target })
+	}
+
+	/// Queues a packet to be sent to the target, without waiting. See [`VMCQueuedSender::send`].
+	// the packet is handed back as-is, like `VMCQueuedSender::send` does, rather than boxed
+	#[allow(clippy::result_large_err)]
+	pub fn send<P: IntoOSCPacket>(&self, packet: P) -> Result<(), OSCPacket> {
+		self.sender.send(packet)
+	}
+
+	/// Returns the queued sender used to send packets to the target, e.g. to check its statistics.
+	pub fn sender(&self) -> &VMCQueuedSender {
+		&self.sender
+	}
+
+	/// Returns the local address that the socket is bound to.
+	pub fn local_addr(&self) -> SocketAddr {
+		self.local_addr
+	}
+
+	/// Returns the address packets are sent to, if any.
+	pub fn target(&self) -> Option<SocketAddr> {
+		self.target
+	}
+}
+
+async fn receive(mut socket: VMCSocket, incoming: Arc<ArrayQueue<(VMCMessage, SocketAddr)>>) {
+	loop {
+		// malformed packets & unknown messages are skipped, like `VMCRelay::run` does
+		let Ok((packet, peer)) = socket.recv_from().await else {
+			continue;
+		};
+		let Ok(messages) = parse(packet) else {
+			continue;
+		};
+		for message in messages {
+			incoming.force_push((message, peer));
+		}
+	}
+}
+
+/// Sent for each message received by the [`Connection`].
+#[derive(Debug, Clone, Event)]
+pub struct MessageReceived {
+	/// The received message.
+	pub message: VMCMessage,
+	/// The address of the peer that sent the message.
+	pub peer: SocketAddr
+}
+
+/// Marks an entity as a bone of the skeleton sent by [`send_skeleton`]. The bone's local [`Transform`] is sent.
+#[derive(Debug, Clone, PartialEq, Eq, Component)]
+pub struct Bone(pub Name);
+
+impl Bone {
+	/// Creates a bone component.
+	///
+	/// `bone` can be either a [`StandardVRM0Bone`](crate::VMCStandardVRM0Bone) or the name of a bone.
+	pub fn new(bone: impl Into<Name>) -> Self {
+		Self(bone.into())
+	}
+}
+
+/// Marks the entity whose [`Transform`] is sent as the root transform by [`send_skeleton`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Component)]
+pub struct Root;
+
+/// Applies messages received since the last frame to the [`VMCAvatarState`](crate::VMCAvatarState) resource and emits
+/// them as [`MessageReceived`] events.
+pub fn receive_messages(connection: Res<Connection>, mut avatar: ResMut<AvatarState>, mut events: EventWriter<MessageReceived>) {
+	while let Some((message, peer)) = connection.incoming.pop() {
+		avatar.apply(message.clone());
+		events.send(MessageReceived { message, peer });
+	}
+}
+
+/// Sends the [`Root`] transform and the transforms of all [`Bone`]s as a single frame, packed into bundles.
+///
+/// Nothing is sent if there are no bones or root. Packets which can't be queued are dropped; see
+/// [`VMCQueuedSender::stats`] for counters.
+pub fn send_skeleton(connection: Res<Connection>, root: Query<&Transform, With<Root>>, bones: Query<(&Bone, &Transform)>) {
+	let mut pose = Pose::new();
+	if let Ok(root) = root.get_single() {
+		pose.root = Some(RootTransform::new(root.translation, root.rotation));
+	}
+	for (bone, transform) in &bones {
+		pose.set_bone(&bone.0, transform.translation, transform.rotation);
+	}
+	if pose.root.is_none() && pose.bones.is_empty() {
+		return;
+	}
+	for packet in pose.to_packets() {
+		let _ = connection.send(packet);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::{Duration, Instant};
+
+	use super::*;
+	use crate::{
+		Quat, VMCBlendShape, VMCStandardVRM0Bone, Vec3,
+		message::{ApplyBlendShapes, Time, parse_datagram},
+		osc::{self, OSCMessage}
+	};
+
+	#[test]
+	fn test_plugin() {
+		let mut marionette = App::new();
+		marionette.add_plugins(VMCPlugin::new("127.0.0.1:0".parse().unwrap()));
+		let addr = marionette.world().resource::<Connection>().local_addr();
+
+		let mut performer = App::new();
+		performer.add_plugins(VMCPlugin::new("127.0.0.1:0".parse().unwrap()).with_target(addr));
+		performer.world_mut().spawn((Root, Transform::from_xyz(0.0, 1.0, 0.0)));
+		performer
+			.world_mut()
+			.spawn((Bone::new(VMCStandardVRM0Bone::Head), Transform::from_rotation(Quat::from_rotation_y(0.5))));
+		performer.update();
+
+		let start = Instant::now();
+		loop {
+			marionette.update();
+			let avatar = marionette.world().resource::<AvatarState>();
+			if let Some(head) = avatar.bone(VMCStandardVRM0Bone::Head) {
+				assert_eq!(head.rotation, Quat::from_rotation_y(0.5));
+				assert_eq!(avatar.root().map(|root| Vec3::from(root.position)), Some(Vec3::Y));
+				break;
+			}
+			assert!(start.elapsed() < Duration::from_secs(5), "timed out waiting for the skeleton");
+			thread::sleep(Duration::from_millis(1));
+		}
+	}
+
+	#[test]
+	#[should_panic = "failed to open VMC socket"]
+	fn test_plugin_bind_error() {
+		let taken = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+		App::new().add_plugins(VMCPlugin::new(taken.local_addr().unwrap()));
+	}
+
+	#[test]
+	fn test_receive_skips_invalid() {
+		let mut marionette = App::new();
+		marionette.add_plugins(VMCPlugin::new("127.0.0.1:0".parse().unwrap()));
+		let addr = marionette.world().resource::<Connection>().local_addr();
+
+		// malformed packets & packets with unknown messages are skipped without stopping the receiver
+		let performer = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+		performer.send_to(b"garbage", addr).unwrap();
+		performer
+			.send_to(&osc::encode(&OSCMessage::new("/Unknown", ()).into_osc_packet()).unwrap(), addr)
+			.unwrap();
+		let frame = vec![VMCBlendShape::new("Joy", 1.0).into_osc_packet(), ApplyBlendShapes.into_osc_packet(), Time::new(2.0).into_osc_packet()];
+		performer.send_to(&osc::encode(&frame.into_osc_packet()).unwrap(), addr).unwrap();
+
+		let mut received = Vec::new();
+		let start = Instant::now();
+		while received.len() < 3 {
+			marionette.update();
+			received.extend(marionette.world_mut().resource_mut::<Events<MessageReceived>>().drain());
+			assert!(start.elapsed() < Duration::from_secs(5), "timed out waiting for messages");
+			thread::sleep(Duration::from_millis(1));
+		}
+		assert_eq!(received.len(), 3);
+		assert!(received.iter().all(|event| event.peer == performer.local_addr().unwrap()));
+		assert!(matches!(&received[2].message, VMCMessage::Time(time) if time.0 == 2.0));
+		let avatar = marionette.world().resource::<AvatarState>();
+		assert_eq!((avatar.blendshape("Joy"), avatar.time()), (Some(1.0), Some(2.0)));
+	}
+
+	#[test]
+	fn test_send_skeleton_edge_cases() {
+		let receiver = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+		receiver.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+		let mut performer = App::new();
+		performer.add_plugins(VMCPlugin::new("127.0.0.1:0".parse().unwrap()).with_target(receiver.local_addr().unwrap()));
+
+		// nothing is sent without a root or bones
+		performer.update();
+		assert_eq!(performer.world().resource::<Connection>().sender().stats().queued(), 0);
+
+		// bones can have custom names
+		performer.world_mut().spawn((Bone::new("Tail"), Transform::from_xyz(0.0, 0.0, -0.5)));
+		performer.update();
+		let mut buf = [0; 1536];
+		let len = receiver.recv(&mut buf).unwrap();
+		let messages = parse_datagram(&buf[..len]).unwrap();
+		assert!(
+			messages
+				.iter()
+				.any(|message| matches!(message, VMCMessage::BoneTransform(bone) if bone.bone == "Tail")),
+			"{messages:?}"
+		);
+
+		// the socket is closed once the connection is removed
+		let addr = performer.world().resource::<Connection>().local_addr();
+		performer.world_mut().remove_resource::<Connection>();
+		let start = Instant::now();
+		while net::UdpSocket::bind(addr).is_err() {
+			assert!(start.elapsed() < Duration::from_secs(5), "timed out waiting for the socket to close");
+			thread::sleep(Duration::from_millis(1));
+		}
+
+		// without a target, the skeleton isn't sent
+		let mut marionette = App::new();
+		marionette.add_plugins(VMCPlugin::new("127.0.0.1:0".parse().unwrap()));
+		marionette.world_mut().spawn((Root, Transform::default()));
+		marionette.update();
+		let connection = marionette.world().resource::<Connection>();
+		assert_eq!((connection.target(), connection.sender().stats().queued()), (None, 0));
+	}
+}
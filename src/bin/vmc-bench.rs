@@ -0,0 +1,140 @@
+//! Measures latency, jitter & packet loss between two hosts, to diagnose laggy avatars.
+//!
+//! Run `vmc-bench echo` on one host and `vmc-bench probe --target <host>:39539` on the other to measure round trip, or
+//! `vmc-bench listen` on the receiving host to measure one way, including the [`Time`](vmc::VMCTime) messages sent by
+//! any performer:
+//!
+//! ```sh
+//! vmc-bench echo --bind 0.0.0.0:39539
+//! vmc-bench probe --target 192.168.1.20:39539 --rate 60
+//! ```
+
+use std::{io, net::SocketAddr, time::Duration};
+
+use clap::{Parser, Subcommand};
+use vmc::{OSCPacket, VMCError, VMCResult, VMCSocket, osc::OSCMessage, probe};
+
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Args {
+	#[command(subcommand)]
+	command: Command
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+	/// Sends probes to a host and measures their round trip if it echoes them.
+	Probe {
+		/// The host to send probes to.
+		#[arg(short, long)]
+		target: SocketAddr,
+		/// The address to send from & receive echoes on.
+		#[arg(short, long, default_value = "0.0.0.0:0")]
+		bind: SocketAddr,
+		/// The number of probes to send per second.
+		#[arg(short, long, default_value_t = 60)]
+		rate: u32,
+		/// Stop after this many seconds.
+		#[arg(short, long, value_name = "SECONDS")]
+		duration: Option<u64>,
+		/// Send `/VMC/Ext/T` messages instead of probes, for measuring one way with `listen`.
+		#[arg(long)]
+		time: bool
+	},
+	/// Reflects probes back to their sender.
+	Echo {
+		/// The address to listen on.
+		#[arg(short, long, default_value = "0.0.0.0:39539")]
+		bind: SocketAddr
+	},
+	/// Measures probes & `/VMC/Ext/T` messages one way as they arrive.
+	Listen {
+		/// The address to listen on.
+		#[arg(short, long, default_value = "0.0.0.0:39539")]
+		bind: SocketAddr
+	}
+}
+
+#[tokio::main]
+async fn main() -> VMCResult<()> {
+	match Args::parse().command {
+		Command::Probe { target, bind, rate, duration, time } => {
+			let mut socket = VMCSocket::bind(bind).await?;
+			socket.connect(target).await?;
+			eprintln!("probing {target} from {}", socket.local_addr()?);
+			measure(&mut socket, Some((rate.max(1), time)), duration.map(Duration::from_secs)).await
+		}
+		Command::Echo { bind } => {
+			let mut socket = VMCSocket::bind(bind).await?;
+			eprintln!("echoing probes on {}", socket.local_addr()?);
+			loop {
+				let (packet, peer) = socket.recv_from().await?;
+				for message in messages(packet) {
+					if let Some(echo) = probe::echo(&message) {
+						socket.send_to(echo, peer).await?;
+					}
+				}
+			}
+		}
+		Command::Listen { bind } => {
+			let mut socket = VMCSocket::bind(bind).await?;
+			eprintln!("listening on {}", socket.local_addr()?);
+			measure(&mut socket, None, None).await
+		}
+	}
+}
+
+/// Measures received messages, optionally sending probes at the given rate, and prints statistics every second.
+async fn measure(socket: &mut VMCSocket, send: Option<(u32, bool)>, duration: Option<Duration>) -> VMCResult<()> {
+	let mut probe = probe::LinkProbe::new();
+	let mut send_interval = tokio::time::interval(Duration::from_secs(1) / send.map_or(1, |(rate, _)| rate));
+	let mut report_interval = tokio::time::interval(Duration::from_secs(1));
+	report_interval.tick().await;
+	let deadline = tokio::time::sleep(duration.unwrap_or(Duration::MAX / 4));
+	tokio::pin!(deadline);
+	loop {
+		tokio::select! {
+			res = socket.recv_timestamped() => {
+				let Some((packet, _, timestamp)) = refused_ok(res)? else {
+					continue;
+				};
+				for message in messages(packet) {
+					probe.handle(&message, timestamp.received);
+				}
+			}
+			_ = send_interval.tick(), if send.is_some() => {
+				let res = if send.is_some_and(|(_, time)| time) {
+					socket.send(probe.probe_time()).await
+				} else {
+					socket.send(probe.probe()).await
+				};
+				refused_ok(res)?;
+			}
+			_ = report_interval.tick() => {
+				let stats = probe.take_stats();
+				if stats.samples() > 0 {
+					println!("{stats}");
+				} else if send.is_some_and(|(_, time)| !time) {
+					println!("no echoes received; is `vmc-bench echo` running on the target?");
+				}
+			}
+			_ = &mut deadline => return Ok(())
+		}
+	}
+}
+
+/// Ignores errors caused by the target not listening (yet), which are reported by some later send or receive.
+fn refused_ok<T>(res: VMCResult<T>) -> VMCResult<Option<T>> {
+	match res {
+		Ok(value) => Ok(Some(value)),
+		Err(VMCError::Io(e)) if e.kind() == io::ErrorKind::ConnectionRefused => Ok(None),
+		Err(e) => Err(e)
+	}
+}
+
+fn messages(packet: OSCPacket) -> Vec<OSCMessage> {
+	match packet {
+		OSCPacket::Message(message) => vec![message],
+		OSCPacket::Bundle(bundle) => bundle.content.into_iter().flat_map(messages).collect()
+	}
+}
@@ -0,0 +1,88 @@
+//! Plays back a recording made with `vmc-record` (or any other tool using the recording format) to a marionette.
+//!
+//! ```sh
+//! vmc-play take1.vmcr --target 127.0.0.1:39539 --speed 0.5 --trim 5..20 --loop
+//! ```
+
+use std::{
+	io::{Cursor, Read, Seek},
+	net::SocketAddr,
+	ops::Range,
+	path::PathBuf,
+	time::Duration
+};
+
+use clap::Parser;
+use vmc::{
+	VMCResult, VMCSocket,
+	record::{Player, RecordingReader}
+};
+
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Args {
+	/// The recording file to play.
+	input: PathBuf,
+	/// The marionette to send to.
+	#[arg(short, long, default_value = "127.0.0.1:39539")]
+	target: SocketAddr,
+	/// Restart from the beginning once the end of the recording is reached.
+	#[arg(short, long = "loop")]
+	looping: bool,
+	/// The playback speed multiplier, e.g. `2` to play at double speed.
+	#[arg(short, long, default_value_t = 1.0, value_parser = parse_speed)]
+	speed: f64,
+	/// Only play part of the recording, as `START..END` in seconds; either end may be omitted, e.g. `5..` or `..20`.
+	#[arg(long, value_name = "RANGE", value_parser = parse_range)]
+	trim: Option<Range<Duration>>
+}
+
+fn parse_speed(s: &str) -> Result<f64, String> {
+	match s.parse::<f64>() {
+		Ok(speed) if speed.is_finite() && speed > 0.0 => Ok(speed),
+		Ok(_) => Err("speed must be positive".to_string()),
+		Err(e) => Err(e.to_string())
+	}
+}
+
+fn parse_range(s: &str) -> Result<Range<Duration>, String> {
+	let (start, end) = s.split_once("..").ok_or("expected a range like `5..20`")?;
+	let parse = |value: &str, default: Duration| -> Result<Duration, String> {
+		match value.trim() {
+			"" => Ok(default),
+			value => Duration::try_from_secs_f64(value.parse::<f64>().map_err(|e| e.to_string())?).map_err(|e| e.to_string())
+		}
+	};
+	let range = parse(start, Duration::ZERO)?..parse(end, Duration::MAX)?;
+	if range.is_empty() {
+		return Err("the end of the range must be after the start".to_string());
+	}
+	Ok(range)
+}
+
+#[tokio::main]
+async fn main() -> VMCResult<()> {
+	let args = Args::parse();
+
+	let mut reader = RecordingReader::open(&args.input)?;
+	if let Some(range) = args.trim.clone() {
+		// trimming carries over the avatar state from before the start of the range, which seeking wouldn't
+		let trimmed = reader.trim(range, Cursor::new(Vec::new()))?;
+		return play(RecordingReader::new(Cursor::new(trimmed.into_inner()))?, &args).await;
+	}
+	play(reader, &args).await
+}
+
+async fn play<R: Read + Seek>(reader: RecordingReader<R>, args: &Args) -> VMCResult<()> {
+	let socket = VMCSocket::bind("0.0.0.0:0").await?;
+	socket.connect(args.target).await?;
+
+	eprintln!("playing {} frames ({:.1}s) to {}", reader.len(), reader.duration().as_secs_f32(), args.target);
+	let mut player = Player::new(reader, socket.sender());
+	player.set_speed(args.speed);
+	player.set_looping(args.looping);
+	tokio::select! {
+		res = player.play() => res,
+		_ = tokio::signal::ctrl_c() => Ok(())
+	}
+}
@@ -0,0 +1,66 @@
+//! Forwards VMC packets received on one port to multiple targets, e.g. to share a single tracker between several
+//! applications.
+//!
+//! ```sh
+//! vmc-proxy --listen 0.0.0.0:39539 --forward 127.0.0.1:39540 --forward 127.0.0.1:39541 --deny "/VMC/Ext/Blend/*"
+//! ```
+//!
+//! Unlike generic OSC repeaters, bundles are forwarded with their structure & timetags intact.
+
+use std::net::{IpAddr, SocketAddr};
+
+use clap::Parser;
+use vmc::{VMCRelay, VMCResult, osc::Matcher};
+
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Args {
+	/// The address to receive packets on.
+	#[arg(short, long, default_value = "0.0.0.0:39539")]
+	listen: SocketAddr,
+	/// An address to forward packets to. Can be given multiple times.
+	#[arg(short = 'f', long = "forward", value_name = "ADDR", required = true)]
+	targets: Vec<SocketAddr>,
+	/// Only forward messages whose address matches one of these OSC patterns. Can be given multiple times.
+	#[arg(long, value_name = "PATTERN")]
+	allow: Vec<String>,
+	/// Don't forward messages whose address matches one of these OSC patterns. Can be given multiple times.
+	#[arg(long, value_name = "PATTERN")]
+	deny: Vec<String>,
+	/// Only forward packets sent from this IP address. Can be given multiple times.
+	#[arg(long, value_name = "IP")]
+	allow_peer: Vec<IpAddr>,
+	/// Wrap forwarded messages into pass-through messages, e.g. `/VMC/Ext/Bone/Pos` becomes `/VMC/Thru/Ext/Bone/Pos`.
+	#[arg(long)]
+	thru: bool
+}
+
+fn matchers(patterns: &[String]) -> VMCResult<Vec<Matcher>> {
+	Ok(patterns.iter().map(|pattern| Matcher::new(pattern)).collect::<Result<_, _>>()?)
+}
+
+#[tokio::main]
+async fn main() -> VMCResult<()> {
+	let args = Args::parse();
+	let (allow, deny) = (matchers(&args.allow)?, matchers(&args.deny)?);
+
+	let mut relay = VMCRelay::bind(args.listen).await?;
+	if !allow.is_empty() {
+		relay = relay.with_filter(move |message| allow.iter().any(|matcher| matcher.matches(message)));
+	}
+	if !deny.is_empty() {
+		relay = relay.with_filter(move |message| !deny.iter().any(|matcher| matcher.matches(message)));
+	}
+	if args.thru {
+		relay = relay.with_thru_wrapping();
+	}
+	for peer in args.allow_peer {
+		relay.receiver_mut().allow_peer(peer);
+	}
+	for target in &args.targets {
+		relay.add_target(target).await?;
+	}
+
+	eprintln!("forwarding {} to {}", relay.local_addr()?, args.targets.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "));
+	relay.run().await
+}
@@ -0,0 +1,106 @@
+//! Records the VMC messages received on a port to a recording file until interrupted with Ctrl+C.
+//!
+//! ```sh
+//! vmc-record take1.vmcr --bind 0.0.0.0:39539 --performer Alice --duration 60
+//! ```
+//!
+//! Recordings can be played back with `vmc-play`.
+
+use std::{fs::File, io::BufWriter, net::SocketAddr, path::PathBuf, time::Duration};
+
+use clap::{Parser, ValueEnum};
+use vmc::{
+	VMCResult, VMCSocket,
+	record::{Compression, DeltaConfig, Metadata, Recorder}
+};
+
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Args {
+	/// The recording file to write; replaced if it already exists.
+	output: PathBuf,
+	/// The address to listen on.
+	#[arg(short, long, default_value = "0.0.0.0:39539")]
+	bind: SocketAddr,
+	/// Stop recording after this many seconds.
+	#[arg(short, long, value_name = "SECONDS")]
+	duration: Option<f64>,
+	/// How frames are compressed.
+	#[arg(short, long, default_value = "lz4")]
+	compression: CompressionArg,
+	/// The name of the performer, stored in the recording's metadata.
+	#[arg(long)]
+	performer: Option<String>,
+	/// The name of the avatar, stored in the recording's metadata.
+	#[arg(long)]
+	avatar: Option<String>
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum CompressionArg {
+	None,
+	Lz4,
+	/// Lossless delta encoding.
+	Delta,
+	/// Quantized delta encoding; smallest, but not bit-exact.
+	DeltaLossy
+}
+
+impl From<CompressionArg> for Compression {
+	fn from(arg: CompressionArg) -> Self {
+		match arg {
+			CompressionArg::None => Compression::None,
+			CompressionArg::Lz4 => Compression::Lz4,
+			CompressionArg::Delta => Compression::Delta(DeltaConfig::lossless()),
+			CompressionArg::DeltaLossy => Compression::Delta(DeltaConfig::lossy())
+		}
+	}
+}
+
+#[tokio::main]
+async fn main() -> VMCResult<()> {
+	let args = Args::parse();
+
+	let mut metadata = Metadata::new().with(Metadata::RECORDER, concat!("vmc-record ", env!("CARGO_PKG_VERSION")));
+	if let Some(performer) = args.performer {
+		metadata.insert(Metadata::PERFORMER, performer);
+	}
+	if let Some(avatar) = args.avatar {
+		metadata = metadata.with_avatar(avatar);
+	}
+	let mut recorder = Recorder::with_compression(BufWriter::new(File::create(&args.output)?), metadata, args.compression.into())?;
+
+	let mut socket = VMCSocket::bind(args.bind).await?;
+	eprintln!("recording {} to {}, press Ctrl+C to stop", socket.local_addr()?, args.output.display());
+
+	let deadline = args
+		.duration
+		.map(|duration| tokio::time::Instant::now() + Duration::from_secs_f64(duration));
+	let mut status = tokio::time::interval(Duration::from_secs(1));
+	loop {
+		tokio::select! {
+			res = socket.recv_message() => {
+				for message in res? {
+					recorder.record(message)?;
+				}
+			}
+			_ = status.tick() => {
+				eprint!("\r{:.1}s, {} frames, {:.1} KiB", recorder.elapsed().as_secs_f32(), recorder.frame_count(), recorder.bytes_written() as f64 / 1024.0);
+			}
+			_ = sleep_until(deadline) => break,
+			_ = tokio::signal::ctrl_c() => break
+		}
+	}
+
+	let (frames, elapsed) = (recorder.frame_count(), recorder.elapsed());
+	recorder.finish()?;
+	eprintln!("\nsaved {frames} frames ({:.1}s) to {}", elapsed.as_secs_f32(), args.output.display());
+	Ok(())
+}
+
+async fn sleep_until(deadline: Option<tokio::time::Instant>) {
+	match deadline {
+		Some(deadline) => tokio::time::sleep_until(deadline).await,
+		None => std::future::pending().await
+	}
+}
@@ -0,0 +1,183 @@
+//! Sends VMC messages from the command line.
+//!
+//! ```sh
+//! vmc-send blend Joy 1.0
+//! vmc-send bone Head --rot 0,0.259,0,0.966
+//! vmc-send --target 192.168.1.20:39539 script take.ndjson
+//! ```
+//!
+//! Scripts contain one JSON message per line, in the format used by the `serde` feature (e.g.
+//! `{"BlendShape":{"key":"Joy","value":1.0}}` or `"ApplyBlendShapes"`), or `{"sleep":0.5}` to wait for a number of
+//! seconds. Empty lines and lines starting with `#` are ignored.
+
+use std::{
+	fs,
+	io::{self, Read},
+	net::SocketAddr,
+	path::PathBuf,
+	time::Duration
+};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Deserialize;
+use vmc::{
+	Quat, VMCApplyBlendShapes, VMCBlendShape, VMCBoneTransform, VMCError, VMCMessage, VMCModelState, VMCResult, VMCRootTransform, VMCSocket, VMCState, VMCTime,
+	Vec3
+};
+
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Args {
+	/// The marionette to send to.
+	#[arg(short, long, default_value = "127.0.0.1:39539")]
+	target: SocketAddr,
+	#[command(subcommand)]
+	command: Command
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+	/// Sends a blend shape value, followed by an apply message.
+	Blend {
+		key: String,
+		value: f32,
+		/// Don't send an apply message.
+		#[arg(long)]
+		no_apply: bool
+	},
+	/// Applies blend shape values.
+	Apply,
+	/// Sends a bone transform.
+	Bone {
+		bone: String,
+		#[command(flatten)]
+		transform: Transform
+	},
+	/// Sends the root transform.
+	Root {
+		#[command(flatten)]
+		transform: Transform
+	},
+	/// Sends the model state.
+	State { state: ModelState },
+	/// Sends the time; defaults to the time since the program started.
+	Time { time: Option<f32> },
+	/// Sends the messages in a script file, or standard input if the path is `-`.
+	Script {
+		path: PathBuf,
+		/// Replay the script this many times; 0 loops forever.
+		#[arg(long, default_value_t = 1)]
+		repeat: u32
+	}
+}
+
+#[derive(Debug, clap::Args)]
+struct Transform {
+	/// The position, as `x,y,z`.
+	#[arg(long, value_parser = parse_floats::<3>, default_value = "0,0,0")]
+	pos: [f32; 3],
+	/// The rotation quaternion, as `x,y,z,w`.
+	#[arg(long, value_parser = parse_floats::<4>, conflicts_with = "euler")]
+	rot: Option<[f32; 4]>,
+	/// The rotation as Euler angles in degrees, as `x,y,z`, applied in Unity's Z, X, Y order.
+	#[arg(long, value_parser = parse_floats::<3>)]
+	euler: Option<[f32; 3]>
+}
+
+impl Transform {
+	fn position(&self) -> Vec3 {
+		Vec3::from_array(self.pos)
+	}
+
+	fn rotation(&self) -> Quat {
+		match (self.rot, self.euler) {
+			(Some(rot), _) => Quat::from_array(rot).normalize(),
+			(None, Some([x, y, z])) => Quat::from_euler(vmc::EulerRot::YXZ, y.to_radians(), x.to_radians(), z.to_radians()),
+			(None, None) => Quat::IDENTITY
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ModelState {
+	Loaded,
+	NotLoaded
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Line {
+	Sleep { sleep: f64 },
+	Message(VMCMessage)
+}
+
+fn parse_floats<const N: usize>(s: &str) -> Result<[f32; N], String> {
+	let values = s
+		.split(',')
+		.map(|value| value.trim().parse::<f32>().map_err(|e| e.to_string()))
+		.collect::<Result<Vec<_>, _>>()?;
+	values
+		.try_into()
+		.map_err(|values: Vec<f32>| format!("expected {N} comma-separated values, got {}", values.len()))
+}
+
+fn read_script(path: &PathBuf) -> VMCResult<Vec<Line>> {
+	let source = if path.as_os_str() == "-" {
+		let mut source = String::new();
+		io::stdin().read_to_string(&mut source)?;
+		source
+	} else {
+		fs::read_to_string(path)?
+	};
+	source
+		.lines()
+		.enumerate()
+		.filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+		.map(|(i, line)| serde_json::from_str(line).map_err(|e| VMCError::Io(io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {e}", i + 1)))))
+		.collect()
+}
+
+#[tokio::main]
+async fn main() -> VMCResult<()> {
+	let args = Args::parse();
+	let socket = VMCSocket::bind("0.0.0.0:0").await?;
+	socket.connect(args.target).await?;
+
+	match args.command {
+		Command::Blend { key, value, no_apply } => {
+			socket.send(VMCBlendShape::new(key, value)).await?;
+			if !no_apply {
+				socket.send(VMCApplyBlendShapes).await?;
+			}
+		}
+		Command::Apply => socket.send(VMCApplyBlendShapes).await?,
+		Command::Bone { bone, transform } => {
+			socket
+				.send(VMCBoneTransform::new(bone, transform.position(), transform.rotation()))
+				.await?
+		}
+		Command::Root { transform } => socket.send(VMCRootTransform::new(transform.position(), transform.rotation())).await?,
+		Command::State { state } => {
+			let state = match state {
+				ModelState::Loaded => VMCModelState::Loaded,
+				ModelState::NotLoaded => VMCModelState::NotLoaded
+			};
+			socket.send(VMCState::new(state)).await?
+		}
+		Command::Time { time } => socket.send(time.map_or_else(VMCTime::elapsed, VMCTime::new)).await?,
+		Command::Script { path, repeat } => {
+			let script = read_script(&path)?;
+			let mut iteration = 0;
+			while repeat == 0 || iteration < repeat {
+				for line in &script {
+					match line {
+						Line::Sleep { sleep } => tokio::time::sleep(Duration::from_secs_f64(*sleep)).await,
+						Line::Message(message) => socket.send(message.clone()).await?
+					}
+				}
+				iteration += 1;
+			}
+		}
+	}
+	Ok(())
+}
@@ -0,0 +1,157 @@
+//! Prints the VMC messages received on a port, like `tcpdump` for VMC.
+//!
+//! ```sh
+//! cargo run --features cli --bin vmc-sniff -- 0.0.0.0:39539 --type bone --address "/VMC/Ext/Bone/*" --stats
+//! ```
+
+use std::{collections::BTreeMap, net::SocketAddr, time::Duration};
+
+use clap::{Parser, ValueEnum};
+use vmc::{
+	OSCPacket, VMCMessage, VMCResult, VMCSocket,
+	osc::{self, Matcher, OSCMessage}
+};
+
+/// Receives VMC messages and prints them.
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Args {
+	/// The address to listen on.
+	#[arg(default_value = "0.0.0.0:39539")]
+	bind: SocketAddr,
+	/// Only show messages whose address matches this OSC pattern. Can be given multiple times.
+	#[arg(short, long = "address", value_name = "PATTERN")]
+	addresses: Vec<String>,
+	/// Only show messages of this type. Can be given multiple times.
+	#[arg(short, long = "type", value_name = "TYPE")]
+	types: Vec<Kind>,
+	/// Print message & byte rates every second.
+	#[arg(short, long)]
+	stats: bool,
+	/// Don't print messages; useful with `--stats`.
+	#[arg(short, long)]
+	quiet: bool
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum Kind {
+	Root,
+	Bone,
+	Device,
+	Blend,
+	Apply,
+	State,
+	Time,
+	/// Any message which isn't a standard VMC message.
+	Other
+}
+
+impl Kind {
+	fn of(addr: &str) -> Self {
+		match addr {
+			"/VMC/Ext/Root/Pos" => Kind::Root,
+			"/VMC/Ext/Bone/Pos" => Kind::Bone,
+			"/VMC/Ext/Blend/Val" => Kind::Blend,
+			"/VMC/Ext/Blend/Apply" => Kind::Apply,
+			"/VMC/Ext/OK" => Kind::State,
+			"/VMC/Ext/T" => Kind::Time,
+			_ if ["/VMC/Ext/Hmd/", "/VMC/Ext/Con/", "/VMC/Ext/Tra/"]
+				.iter()
+				.any(|prefix| addr.starts_with(prefix)) =>
+			{
+				Kind::Device
+			}
+			_ => Kind::Other
+		}
+	}
+}
+
+#[derive(Debug, Default)]
+struct Stats {
+	packets: u64,
+	bytes: u64,
+	messages: BTreeMap<String, u64>
+}
+
+#[tokio::main]
+async fn main() -> VMCResult<()> {
+	let args = Args::parse();
+	let matchers = args
+		.addresses
+		.iter()
+		.map(|pattern| Matcher::new(pattern))
+		.collect::<Result<Vec<_>, _>>()?;
+
+	let mut socket = VMCSocket::bind(args.bind).await?;
+	eprintln!("listening on {}", socket.local_addr()?);
+
+	let mut stats = Stats::default();
+	let mut interval = tokio::time::interval(Duration::from_secs(1));
+	interval.tick().await;
+	loop {
+		tokio::select! {
+			res = socket.recv_raw() => {
+				let (datagram, peer) = res?;
+				stats.packets += 1;
+				stats.bytes += datagram.len() as u64;
+				let packet = match osc::decode_udp(&datagram) {
+					Ok((_, packet)) => packet,
+					Err(e) => {
+						if !args.quiet {
+							println!("{peer} malformed packet: {e}\n{}", osc::debug_dump(&datagram));
+						}
+						continue;
+					}
+				};
+
+				for message in packet.into_messages() {
+					let kind = Kind::of(&message.addr);
+					if (!args.types.is_empty() && !args.types.contains(&kind)) || (!matchers.is_empty() && !matchers.iter().any(|matcher| matcher.matches(&message))) {
+						continue;
+					}
+					*stats.messages.entry(message.addr.clone()).or_default() += 1;
+					if !args.quiet {
+						print_message(peer, message);
+					}
+				}
+			}
+			_ = interval.tick(), if args.stats => {
+				let total: u64 = stats.messages.values().sum();
+				println!("-- {} packets/s, {} messages/s, {:.1} KiB/s", stats.packets, total, stats.bytes as f64 / 1024.0);
+				for (addr, count) in &stats.messages {
+					println!("   {count:>6}  {addr}");
+				}
+				stats = Stats::default();
+			}
+		}
+	}
+}
+
+fn print_message(peer: SocketAddr, message: OSCMessage) {
+	let parsed = vmc::parse(OSCPacket::Message(message.clone())).ok().and_then(|mut parsed| parsed.pop());
+	match parsed {
+		Some(VMCMessage::RootTransform(root)) => println!("{peer} root       pos {} rot {}", root.position, root.rotation),
+		Some(VMCMessage::BoneTransform(bone)) => println!("{peer} bone       {:<24} pos {} rot {}", bone.bone.as_str(), bone.position, bone.rotation),
+		Some(VMCMessage::DeviceTransform(device)) => {
+			println!(
+				"{peer} device     {:<24} pos {} rot {} ({}{})",
+				device.joint,
+				device.position,
+				device.rotation,
+				device.device,
+				if device.local { ", local" } else { "" }
+			)
+		}
+		Some(VMCMessage::BlendShape(blend)) => println!("{peer} blend      {:<24} {:.3}", blend.key.as_str(), blend.value),
+		Some(VMCMessage::ApplyBlendShapes) => println!("{peer} apply"),
+		Some(VMCMessage::State(state)) => println!("{peer} state      {:?}", state),
+		Some(VMCMessage::Time(time)) => println!("{peer} time       {}", time.0),
+		// unknown messages, or standard addresses with unexpected args
+		None => {
+			let dump = osc::encode(&OSCPacket::Message(message.clone()))
+				.map(|bytes| osc::debug_dump(&bytes))
+				.unwrap_or_default();
+			println!("{peer} {message}\n{dump}");
+		}
+	}
+}
@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use crate::{
+	message::{BlendShape, VMCMessage},
+	name::Name
+};
+
+/// A double buffer for blend shape values, mirroring the semantics of the VMC protocol.
+///
+/// [`BlendShape`] messages are accumulated into a pending buffer, which is merged into the applied snapshot when an
+/// [`ApplyBlendShapes`](crate::VMCApplyBlendShapes) message is received. Blend shapes which were not updated since the
+/// last apply keep their previous value.
+///
+/// # Examples
+///
+/// ```
+/// use vmc::{VMCApplyBlendShapes, VMCBlendShape, VMCBlendShapeBuffer, VMCMessage, VMCStandardVRMBlendShape};
+///
+/// let mut buffer = VMCBlendShapeBuffer::new();
+/// buffer.update(&VMCBlendShape::new(VMCStandardVRMBlendShape::Joy, 1.0).into());
+/// assert_eq!(buffer.get(VMCStandardVRMBlendShape::Joy), None);
+///
+/// assert!(buffer.update(&VMCApplyBlendShapes.into()));
+/// assert_eq!(buffer.get(VMCStandardVRMBlendShape::Joy), Some(1.0));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BlendShapeBuffer {
+	pending: HashMap<Name, f32>,
+	applied: HashMap<Name, f32>
+}
+
+impl BlendShapeBuffer {
+	/// Creates a new, empty buffer.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Updates the buffer with a message. [`BlendShape`] messages are added to the pending buffer, and
+	/// [`ApplyBlendShapes`](VMCMessage::ApplyBlendShapes) messages [apply](BlendShapeBuffer::apply) it; other messages
+	/// are ignored.
+	///
+	/// Returns `true` if the pending values were applied.
+	pub fn update(&mut self, message: &VMCMessage) -> bool {
+		match message {
+			VMCMessage::BlendShape(blend) => {
+				self.set(blend.key.clone(), blend.value);
+				false
+			}
+			VMCMessage::ApplyBlendShapes => {
+				self.apply();
+				true
+			}
+			_ => false
+		}
+	}
+
+	/// Sets the pending value of a blend shape.
+	pub fn set(&mut self, key: impl Into<Name>, value: f32) {
+		self.pending.insert(key.into(), value);
+	}
+
+	/// Merges all pending values into the applied snapshot, clearing the pending buffer.
+	pub fn apply(&mut self) {
+		self.applied.extend(self.pending.drain());
+	}
+
+	/// Returns the applied value of a blend shape.
+	///
+	/// `key` can be either a [`StandardVRMBlendShape`](crate::VMCStandardVRMBlendShape) or the name of a blend shape.
+	pub fn get(&self, key: impl AsRef<str>) -> Option<f32> {
+		self.applied.get(key.as_ref()).copied()
+	}
+
+	/// Returns the pending value of a blend shape, if it has been updated since the last apply.
+	pub fn get_pending(&self, key: impl AsRef<str>) -> Option<f32> {
+		self.pending.get(key.as_ref()).copied()
+	}
+
+	/// Returns an iterator over the applied values of all blend shapes.
+	pub fn iter(&self) -> impl Iterator<Item = (&str, f32)> {
+		self.applied.iter().map(|(key, value)| (key.as_str(), *value))
+	}
+
+	/// Returns an iterator over all pending values.
+	pub fn iter_pending(&self) -> impl Iterator<Item = (&str, f32)> {
+		self.pending.iter().map(|(key, value)| (key.as_str(), *value))
+	}
+
+	/// Returns an iterator over the pending values that differ from the applied snapshot, i.e. the blend shapes that
+	/// will change on the next apply, along with their previously applied value (if any).
+	pub fn changes(&self) -> impl Iterator<Item = (&str, Option<f32>, f32)> {
+		self.pending.iter().filter_map(|(key, &value)| {
+			let previous = self.applied.get(key).copied();
+			(previous != Some(value)).then_some((key.as_str(), previous, value))
+		})
+	}
+
+	/// Returns an iterator over the blend shapes whose applied value differs between `previous` and this buffer, along
+	/// with their value in `previous` (if any) and in this buffer.
+	///
+	/// Blend shapes which are not present in this buffer are not included.
+	pub fn diff<'a>(&'a self, previous: &'a BlendShapeBuffer) -> impl Iterator<Item = (&'a str, Option<f32>, f32)> {
+		self.applied.iter().filter_map(|(key, &value)| {
+			let previous = previous.applied.get(key).copied();
+			(previous != Some(value)).then_some((key.as_str(), previous, value))
+		})
+	}
+
+	/// Returns `true` if no blend shapes have been applied.
+	pub fn is_empty(&self) -> bool {
+		self.applied.is_empty()
+	}
+
+	/// Clears both the pending and applied values.
+	pub fn clear(&mut self) {
+		self.pending.clear();
+		self.applied.clear();
+	}
+}
+
+impl Extend<BlendShape> for BlendShapeBuffer {
+	fn extend<T: IntoIterator<Item = BlendShape>>(&mut self, iter: T) {
+		for blend in iter {
+			self.set(blend.key, blend.value);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::BlendShapeBuffer;
+	use crate::message::{BlendShape, StandardVRMBlendShape, VMCMessage};
+
+	#[test]
+	fn test_blendshape_buffer() {
+		let mut buffer = BlendShapeBuffer::new();
+		buffer.extend([BlendShape::new(StandardVRMBlendShape::Joy, 1.0), BlendShape::new(StandardVRMBlendShape::A, 0.5)]);
+		assert!(buffer.is_empty());
+		assert_eq!(buffer.get_pending(StandardVRMBlendShape::A), Some(0.5));
+		assert!(buffer.update(&VMCMessage::ApplyBlendShapes));
+		let previous = buffer.clone();
+
+		buffer.set("Joy", 1.0);
+		buffer.set("A", 0.25);
+		let changes: Vec<_> = buffer.changes().collect();
+		assert_eq!(changes, [("A", Some(0.5), 0.25)]);
+
+		buffer.apply();
+		assert_eq!(buffer.iter_pending().count(), 0);
+		assert_eq!(buffer.get(StandardVRMBlendShape::Joy), Some(1.0));
+		assert_eq!(buffer.get(StandardVRMBlendShape::A), Some(0.25));
+		let diff: Vec<_> = buffer.diff(&previous).collect();
+		assert_eq!(diff, [("A", Some(0.5), 0.25)]);
+	}
+}
@@ -0,0 +1,195 @@
+//! A synchronous VMC socket, for applications that don't use an async runtime.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # fn main() -> vmc::VMCResult<()> {
+//! use vmc::{VMCMessage, blocking::VMCSocket};
+//!
+//! let mut socket = VMCSocket::bind("127.0.0.1:39539")?;
+//! loop {
+//! 	for message in socket.recv_message()? {
+//! 		if let VMCMessage::BoneTransform(transform) = message {
+//! 			println!("{:?}", transform);
+//! 		}
+//! 	}
+//! }
+//! # }
+//! ```
+
+use std::{
+	io,
+	net::{Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket},
+	time::Duration
+};
+
+use socket2::SockRef;
+
+use crate::{
+	IntoOSCPacket, OSCPacket, VMCMessage, VMCResult, osc, parse,
+	socket::{check_broadcast, check_len, map_broadcast_err}
+};
+
+/// A blocking UDP socket to send and receive VMC messages.
+///
+/// This mirrors the API of the async [`VMCSocket`](crate::VMCSocket), but is built on [`std::net::UdpSocket`].
+#[derive(Debug)]
+pub struct VMCSocket {
+	socket: UdpSocket,
+	buf: Box<[u8]>
+}
+
+impl VMCSocket {
+	/// Creates a new VMC socket from a [`std::net::UdpSocket`].
+	pub fn new(socket: UdpSocket) -> Self {
+		Self {
+			socket,
+			buf: vec![0; 1024 * 64].into_boxed_slice()
+		}
+	}
+
+	/// Creates a VMC socket bound to the given address.
+	///
+	/// Binding with a port number of 0 will request that the OS assigns a port to this socket.
+	pub fn bind<A: ToSocketAddrs>(addr: A) -> VMCResult<Self> {
+		Ok(Self::new(UdpSocket::bind(addr)?))
+	}
+
+	/// Connects the socket to a remote address.
+	///
+	/// See [`crate::VMCSocket::connect`].
+	///
+	/// # Examples
+	///
+	/// ```no_run
+	/// # fn main() -> vmc::VMCResult<()> {
+	/// use vmc::{VMCBlendShape, VMCStandardVRMBlendShape, blocking::VMCSocket};
+	///
+	/// let socket = VMCSocket::bind("127.0.0.1:0")?;
+	/// socket.connect("127.0.0.1:39539")?;
+	/// socket.send(VMCBlendShape::new(VMCStandardVRMBlendShape::Joy, 1.0))?;
+	/// # Ok(()) }
+	/// ```
+	pub fn connect<A: ToSocketAddrs>(&self, addrs: A) -> VMCResult<()> {
+		let mut last_err = None;
+		for addr in addrs.to_socket_addrs()? {
+			check_broadcast(SockRef::from(&self.socket), addr)?;
+			match self.socket.connect(addr) {
+				Ok(()) => return Ok(()),
+				Err(e) => last_err = Some(map_broadcast_err(SockRef::from(&self.socket), addr, e))
+			}
+		}
+		Err(last_err.unwrap_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "could not resolve to any address").into()))
+	}
+
+	/// Sets the value of the `SO_BROADCAST` option for this socket.
+	///
+	/// See [`crate::VMCSocket::set_broadcast`].
+	pub fn set_broadcast(&self, on: bool) -> VMCResult<()> {
+		self.socket.set_broadcast(on)?;
+		Ok(())
+	}
+
+	/// Gets the value of the `SO_BROADCAST` option for this socket.
+	pub fn broadcast(&self) -> VMCResult<bool> {
+		Ok(self.socket.broadcast()?)
+	}
+
+	/// Joins an IPv4 multicast group. See [`crate::VMCSocket::join_multicast_v4`].
+	pub fn join_multicast_v4(&self, multiaddr: Ipv4Addr, interface: Ipv4Addr) -> VMCResult<()> {
+		self.socket.join_multicast_v4(&multiaddr, &interface)?;
+		Ok(())
+	}
+
+	/// Joins an IPv6 multicast group. See [`crate::VMCSocket::join_multicast_v6`].
+	pub fn join_multicast_v6(&self, multiaddr: &Ipv6Addr, interface: u32) -> VMCResult<()> {
+		self.socket.join_multicast_v6(multiaddr, interface)?;
+		Ok(())
+	}
+
+	/// Sets the read timeout of the socket. If `None`, [`recv`](VMCSocket::recv) and friends block indefinitely.
+	///
+	/// When the timeout elapses, receiving returns an I/O error of kind [`io::ErrorKind::WouldBlock`] or
+	/// [`io::ErrorKind::TimedOut`], depending on the platform.
+	pub fn set_read_timeout(&self, timeout: Option<Duration>) -> VMCResult<()> {
+		self.socket.set_read_timeout(timeout)?;
+		Ok(())
+	}
+
+	/// Moves the socket into or out of non-blocking mode.
+	///
+	/// In non-blocking mode, receiving returns an I/O error of kind [`io::ErrorKind::WouldBlock`] if no packet is
+	/// available, which is useful for polling the socket once per frame in a game loop.
+	pub fn set_nonblocking(&self, nonblocking: bool) -> VMCResult<()> {
+		self.socket.set_nonblocking(nonblocking)?;
+		Ok(())
+	}
+
+	/// Sends an OSC packet on the socket to the given address.
+	pub fn send_to<A: ToSocketAddrs, P: IntoOSCPacket>(&self, packet: P, addrs: A) -> VMCResult<()> {
+		let buf = osc::encode(&packet.into_osc_packet())?;
+		let addr = match addrs.to_socket_addrs()?.next() {
+			Some(addr) => addr,
+			None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "no addresses to send data to").into())
+		};
+		check_broadcast(SockRef::from(&self.socket), addr)?;
+		let n = self
+			.socket
+			.send_to(&buf[..], addr)
+			.map_err(|e| map_broadcast_err(SockRef::from(&self.socket), addr, e))?;
+		check_len(&buf[..], n)
+	}
+
+	/// Sends an OSC packet on the socket to the remote address to which it is connected.
+	pub fn send<P: IntoOSCPacket>(&self, packet: P) -> VMCResult<()> {
+		let buf = osc::encode(&packet.into_osc_packet())?;
+		let n = self.socket.send(&buf[..])?;
+		check_len(&buf[..], n)
+	}
+
+	/// Receives a single OSC packet on the socket.
+	pub fn recv(&mut self) -> VMCResult<OSCPacket> {
+		self.recv_from().map(|(packet, _)| packet)
+	}
+
+	/// Receives a single OSC packet on the socket, returning the packet and the address of the peer that sent it.
+	pub fn recv_from(&mut self) -> VMCResult<(OSCPacket, SocketAddr)> {
+		let (n, addr) = self.socket.recv_from(&mut self.buf)?;
+		let (_, packet) = osc::decode_udp(&self.buf[..n])?;
+		Ok((packet, addr))
+	}
+
+	/// Receives a single OSC packet on the socket and [parses](crate::parse) it into its contained [`VMCMessage`]s.
+	pub fn recv_message(&mut self) -> VMCResult<Vec<VMCMessage>> {
+		parse(self.recv()?)
+	}
+
+	/// Get a reference to the underlying [`UdpSocket`].
+	pub fn socket(&self) -> &UdpSocket {
+		&self.socket
+	}
+
+	/// Returns the local address that this socket is bound to.
+	pub fn local_addr(&self) -> VMCResult<SocketAddr> {
+		Ok(self.socket.local_addr()?)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::VMCSocket;
+	use crate::{VMCMessage, VMCResult, VMCTime};
+
+	#[test]
+	fn test_blocking_roundtrip() -> VMCResult<()> {
+		let mut receiver = VMCSocket::bind("127.0.0.1:0")?;
+		let sender = VMCSocket::bind("127.0.0.1:0")?;
+		sender.connect(receiver.local_addr()?)?;
+		sender.send(VMCTime(4.0))?;
+
+		let (packet, addr) = receiver.recv_from()?;
+		assert_eq!(addr, sender.local_addr()?);
+		assert!(matches!(crate::parse(packet)?[..], [VMCMessage::Time(VMCTime(t))] if t == 4.0));
+		Ok(())
+	}
+}
@@ -0,0 +1,461 @@
+//! Import of [BVH](https://research.cs.wisc.edu/graphics/Courses/cs-838-1999/Jeff/BVH.html) motion capture files.
+//!
+//! A [`Bvh`] file can be converted into VMC [`Frame`](crate::VMCFrame)s of [`BoneTransform`](crate::VMCBoneTransform)
+//! and [`Time`](crate::VMCTime) messages, allowing motion from existing mocap libraries to drive any VMC marionette.
+//! BVH joints are mapped to [`StandardVRM0Bone`]s by name through [`BvhOptions`], which recognizes the joint names
+//! used by common tools (e.g. Mixamo, MotionBuilder, and the CMU motion capture database) by default.
+//!
+//! Motion is converted from the right-handed coordinate system used by BVH to the left-handed Unity coordinate system
+//! used by VMC. Joints that do not map to a bone are folded into their nearest mapped descendants, so their motion is
+//! preserved.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+//! use futures_util::StreamExt;
+//! use vmc::bvh::{Bvh, BvhOptions};
+//!
+//! let bvh = Bvh::open("walk.bvh")?;
+//! let socket = vmc::performer!("127.0.0.1:39539").await?;
+//! let mut messages = bvh.stream(&BvhOptions::default());
+//! while let Some(message) = messages.next().await {
+//! 	socket.send(message).await?;
+//! }
+//! # Ok(()) }) }
+//! ```
+
+use std::{
+	collections::{HashMap, VecDeque},
+	fs,
+	iter::Peekable,
+	path::Path,
+	pin::Pin,
+	str::SplitWhitespace,
+	task::{Context, Poll, ready},
+	time::Duration
+};
+
+use futures_core::Stream;
+use glam::{Quat, Vec3A};
+use tokio::time::Interval;
+
+use crate::{VMCBoneTransform, VMCError, VMCFrame, VMCMessage, VMCResult, VMCStandardVRM0Bone as StandardVRM0Bone, VMCTime};
+
+/// A channel of motion data for a [`Joint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+	XPosition,
+	YPosition,
+	ZPosition,
+	XRotation,
+	YRotation,
+	ZRotation
+}
+
+impl Channel {
+	fn parse(name: &str) -> Option<Self> {
+		match name {
+			"Xposition" => Some(Channel::XPosition),
+			"Yposition" => Some(Channel::YPosition),
+			"Zposition" => Some(Channel::ZPosition),
+			"Xrotation" => Some(Channel::XRotation),
+			"Yrotation" => Some(Channel::YRotation),
+			"Zrotation" => Some(Channel::ZRotation),
+			_ => None
+		}
+	}
+}
+
+/// A joint in the hierarchy of a [`Bvh`] file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Joint {
+	/// The name of the joint.
+	pub name: String,
+	/// The index of the parent joint, or `None` for the root joint.
+	pub parent: Option<usize>,
+	/// The offset of the joint from its parent in the rest pose.
+	pub offset: Vec3A,
+	/// The channels of motion data for this joint, in the order they appear in each frame.
+	pub channels: Vec<Channel>
+}
+
+/// Options controlling how a [`Bvh`] file is converted to VMC messages.
+#[derive(Debug, Clone)]
+pub struct BvhOptions {
+	bones: HashMap<String, StandardVRM0Bone>,
+	scale: f32
+}
+
+impl Default for BvhOptions {
+	fn default() -> Self {
+		use StandardVRM0Bone::*;
+
+		let mut bones = HashMap::new();
+		let mut map = |names: &[&str], bone: StandardVRM0Bone| {
+			for name in names {
+				bones.insert(name.to_string(), bone);
+			}
+		};
+		map(&["Hips", "hip", "Hip"], Hips);
+		map(&["Spine", "abdomen", "Spine1"], Spine);
+		map(&["Chest", "chest", "Spine2"], Chest);
+		map(&["UpperChest", "Spine3"], UpperChest);
+		map(&["Neck", "neck", "Neck1"], Neck);
+		map(&["Head", "head"], Head);
+		map(&["LeftShoulder", "LeftCollar", "lCollar"], LeftShoulder);
+		map(&["RightShoulder", "RightCollar", "rCollar"], RightShoulder);
+		map(&["LeftUpperArm", "LeftArm", "LeftUpArm", "lShldr"], LeftUpperArm);
+		map(&["RightUpperArm", "RightArm", "RightUpArm", "rShldr"], RightUpperArm);
+		map(&["LeftLowerArm", "LeftForeArm", "LeftLowArm", "lForeArm"], LeftLowerArm);
+		map(&["RightLowerArm", "RightForeArm", "RightLowArm", "rForeArm"], RightLowerArm);
+		map(&["LeftHand", "lHand"], LeftHand);
+		map(&["RightHand", "rHand"], RightHand);
+		map(&["LeftUpperLeg", "LeftUpLeg", "LeftHip", "lThigh"], LeftUpperLeg);
+		map(&["RightUpperLeg", "RightUpLeg", "RightHip", "rThigh"], RightUpperLeg);
+		map(&["LeftLowerLeg", "LeftLeg", "LeftKnee", "lShin"], LeftLowerLeg);
+		map(&["RightLowerLeg", "RightLeg", "RightKnee", "rShin"], RightLowerLeg);
+		map(&["LeftFoot", "LeftAnkle", "lFoot"], LeftFoot);
+		map(&["RightFoot", "RightAnkle", "rFoot"], RightFoot);
+		map(&["LeftToes", "LeftToeBase", "LeftToe"], LeftToes);
+		map(&["RightToes", "RightToeBase", "RightToe"], RightToes);
+
+		// Mixamo-style finger names, e.g. `LeftHandIndex1`
+		let fingers = [
+			("Thumb", [LeftThumbProximal, LeftThumbIntermediate, LeftThumbDistal], [RightThumbProximal, RightThumbIntermediate, RightThumbDistal]),
+			("Index", [LeftIndexProximal, LeftIndexIntermediate, LeftIndexDistal], [RightIndexProximal, RightIndexIntermediate, RightIndexDistal]),
+			("Middle", [LeftMiddleProximal, LeftMiddleIntermediate, LeftMiddleDistal], [RightMiddleProximal, RightMiddleIntermediate, RightMiddleDistal]),
+			("Ring", [LeftRingProximal, LeftRingIntermediate, LeftRingDistal], [RightRingProximal, RightRingIntermediate, RightRingDistal]),
+			("Pinky", [LeftLittleProximal, LeftLittleIntermediate, LeftLittleDistal], [RightLittleProximal, RightLittleIntermediate, RightLittleDistal])
+		];
+		for (finger, left, right) in fingers {
+			for (i, (left, right)) in left.into_iter().zip(right).enumerate() {
+				bones.insert(format!("LeftHand{finger}{}", i + 1), left);
+				bones.insert(format!("RightHand{finger}{}", i + 1), right);
+			}
+		}
+
+		Self { bones, scale: 0.01 }
+	}
+}
+
+impl BvhOptions {
+	/// Creates options with the default joint name mapping and a scale of `0.01`, converting from centimeters (the
+	/// unit used by most BVH files) to meters.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Maps the joint named `joint` to `bone`, overriding the default mapping.
+	pub fn with_bone(mut self, joint: impl Into<String>, bone: StandardVRM0Bone) -> Self {
+		self.bones.insert(joint.into(), bone);
+		self
+	}
+
+	/// Sets the factor by which positions are multiplied to convert them to meters.
+	pub fn with_scale(mut self, scale: f32) -> Self {
+		self.scale = scale;
+		self
+	}
+
+	/// Returns the bone a joint is mapped to.
+	///
+	/// Namespace prefixes like `mixamorig:` are ignored. Joints named after a [`StandardVRM0Bone`] are mapped to that
+	/// bone unless overridden.
+	pub fn bone(&self, joint: &str) -> Option<StandardVRM0Bone> {
+		let name = joint.rsplit(':').next().unwrap_or(joint);
+		self.bones
+			.get(joint)
+			.or_else(|| self.bones.get(name))
+			.copied()
+			.or_else(|| name.parse().ok())
+	}
+}
+
+/// A parsed BVH file.
+#[derive(Debug, Clone)]
+pub struct Bvh {
+	joints: Vec<Joint>,
+	channel_offsets: Vec<usize>,
+	channel_count: usize,
+	frame_time: Duration,
+	motion: Vec<f32>
+}
+
+impl Bvh {
+	/// Reads and parses the BVH file at the given path.
+	pub fn open(path: impl AsRef<Path>) -> VMCResult<Self> {
+		Self::parse(&fs::read_to_string(path)?)
+	}
+
+	/// Parses a BVH file.
+	pub fn parse(source: &str) -> VMCResult<Self> {
+		let mut parser = Parser {
+			tokens: source.split_whitespace().peekable()
+		};
+		parser.expect("HIERARCHY")?;
+		parser.expect("ROOT")?;
+		let mut joints = Vec::new();
+		parser.joint(&mut joints, None)?;
+
+		let mut channel_offsets = Vec::with_capacity(joints.len());
+		let mut channel_count = 0;
+		for joint in &joints {
+			channel_offsets.push(channel_count);
+			channel_count += joint.channels.len();
+		}
+
+		parser.expect("MOTION")?;
+		parser.expect("Frames:")?;
+		let frames: usize = parser.parse()?;
+		parser.expect("Frame")?;
+		parser.expect("Time:")?;
+		let frame_time: f64 = parser.parse()?;
+		if !(frame_time > 0.0 && frame_time.is_finite()) {
+			return Err(VMCError::InvalidBvh(format!("invalid frame time {frame_time}")));
+		}
+
+		let len = frames
+			.checked_mul(channel_count)
+			.ok_or_else(|| VMCError::InvalidBvh("too many frames".to_string()))?;
+		let mut motion = Vec::with_capacity(len.min(1 << 24));
+		for _ in 0..len {
+			motion.push(parser.parse()?);
+		}
+
+		Ok(Self {
+			joints,
+			channel_offsets,
+			channel_count,
+			frame_time: Duration::from_secs_f64(frame_time),
+			motion
+		})
+	}
+
+	/// Returns the joints of the hierarchy. Parents always come before their children.
+	pub fn joints(&self) -> &[Joint] {
+		&self.joints
+	}
+
+	/// Returns the number of frames of motion.
+	pub fn frame_count(&self) -> usize {
+		self.motion.len().checked_div(self.channel_count).unwrap_or(0)
+	}
+
+	/// Returns the duration of a single frame.
+	pub fn frame_time(&self) -> Duration {
+		self.frame_time
+	}
+
+	/// Returns the raw channel values of a frame, in the order of [`Bvh::joints`] and their [`Joint::channels`].
+	pub fn frame_values(&self, index: usize) -> Option<&[f32]> {
+		self.motion.get(index * self.channel_count..(index + 1) * self.channel_count)
+	}
+
+	/// Converts a frame of motion into VMC messages.
+	///
+	/// The frame contains a [`BoneTransform`](crate::VMCBoneTransform) for every mapped joint, and its time is the
+	/// frame's offset from the start of the motion.
+	pub fn frame(&self, index: usize, options: &BvhOptions) -> Option<VMCFrame> {
+		let values = self.frame_values(index)?;
+
+		// transform from the nearest mapped ancestor of each joint, to fold unmapped joints into their children
+		let mut carried = Vec::with_capacity(self.joints.len());
+		let mut messages = Vec::new();
+		for (joint, &channel_offset) in self.joints.iter().zip(&self.channel_offsets) {
+			let (mut position, mut rotation) = (joint.offset, Quat::IDENTITY);
+			for (channel, &value) in joint.channels.iter().zip(&values[channel_offset..]) {
+				match channel {
+					Channel::XPosition => position.x = value,
+					Channel::YPosition => position.y = value,
+					Channel::ZPosition => position.z = value,
+					Channel::XRotation => rotation *= Quat::from_rotation_x(value.to_radians()),
+					Channel::YRotation => rotation *= Quat::from_rotation_y(value.to_radians()),
+					Channel::ZRotation => rotation *= Quat::from_rotation_z(value.to_radians())
+				}
+			}
+			// mirror along X to convert from right-handed to left-handed coordinates
+			let position = Vec3A::new(-position.x, position.y, position.z) * options.scale;
+			let rotation = Quat::from_xyzw(rotation.x, -rotation.y, -rotation.z, rotation.w);
+
+			let (parent_position, parent_rotation) = joint.parent.map_or((Vec3A::ZERO, Quat::IDENTITY), |parent| carried[parent]);
+			let position = parent_position + parent_rotation * position;
+			let rotation = parent_rotation * rotation;
+			match options.bone(&joint.name) {
+				Some(bone) => {
+					messages.push(VMCMessage::BoneTransform(VMCBoneTransform::new(bone, position, rotation)));
+					carried.push((Vec3A::ZERO, Quat::IDENTITY));
+				}
+				None => carried.push((position, rotation))
+			}
+		}
+
+		Some(VMCFrame {
+			time: VMCTime::new((self.frame_time.as_secs_f64() * index as f64) as f32),
+			messages
+		})
+	}
+
+	/// Returns an iterator over all frames of motion converted into VMC messages. See [`Bvh::frame`].
+	pub fn frames<'a>(&'a self, options: &'a BvhOptions) -> impl Iterator<Item = VMCFrame> + 'a {
+		(0..self.frame_count()).filter_map(|index| self.frame(index, options))
+	}
+
+	/// Returns a stream of the messages of all frames, emitted in real time at the file's frame rate.
+	pub fn stream(&self, options: &BvhOptions) -> BvhStream {
+		BvhStream {
+			frames: self.frames(options).collect::<Vec<_>>().into_iter(),
+			pending: VecDeque::new(),
+			period: self.frame_time,
+			interval: None
+		}
+	}
+}
+
+/// A stream of messages converted from a [`Bvh`] file, created by [`Bvh::stream`].
+///
+/// Each frame's [`BoneTransform`](crate::VMCBoneTransform)s are yielded at once, followed by its
+/// [`Time`](crate::VMCTime) message; frames are spaced by the file's frame time.
+#[derive(Debug)]
+pub struct BvhStream {
+	frames: std::vec::IntoIter<VMCFrame>,
+	pending: VecDeque<VMCMessage>,
+	period: Duration,
+	interval: Option<Interval>
+}
+
+impl Stream for BvhStream {
+	type Item = VMCMessage;
+
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		if let Some(message) = self.pending.pop_front() {
+			return Poll::Ready(Some(message));
+		}
+		if self.frames.len() == 0 {
+			return Poll::Ready(None);
+		}
+
+		let period = self.period;
+		ready!(self.interval.get_or_insert_with(|| tokio::time::interval(period)).poll_tick(cx));
+		let Some(frame) = self.frames.next() else {
+			return Poll::Ready(None);
+		};
+		self.pending.extend(frame.messages);
+		self.pending.push_back(VMCMessage::Time(frame.time));
+		Poll::Ready(self.pending.pop_front())
+	}
+}
+
+struct Parser<'a> {
+	tokens: Peekable<SplitWhitespace<'a>>
+}
+
+impl<'a> Parser<'a> {
+	fn next(&mut self) -> VMCResult<&'a str> {
+		self.tokens
+			.next()
+			.ok_or_else(|| VMCError::InvalidBvh("unexpected end of file".to_string()))
+	}
+
+	fn expect(&mut self, expected: &str) -> VMCResult<()> {
+		match self.next()? {
+			token if token == expected => Ok(()),
+			token => Err(VMCError::InvalidBvh(format!("expected '{expected}', found '{token}'")))
+		}
+	}
+
+	fn parse<T: std::str::FromStr>(&mut self) -> VMCResult<T> {
+		let token = self.next()?;
+		token.parse().map_err(|_| VMCError::InvalidBvh(format!("invalid number '{token}'")))
+	}
+
+	fn offset(&mut self) -> VMCResult<Vec3A> {
+		self.expect("OFFSET")?;
+		Ok(Vec3A::new(self.parse()?, self.parse()?, self.parse()?))
+	}
+
+	fn joint(&mut self, joints: &mut Vec<Joint>, parent: Option<usize>) -> VMCResult<()> {
+		let name = self.next()?.to_string();
+		self.expect("{")?;
+		let offset = self.offset()?;
+		let mut channels = Vec::new();
+		if self.tokens.peek() == Some(&"CHANNELS") {
+			self.next()?;
+			let count: usize = self.parse()?;
+			for _ in 0..count {
+				let channel = self.next()?;
+				channels.push(Channel::parse(channel).ok_or_else(|| VMCError::InvalidBvh(format!("unknown channel '{channel}'")))?);
+			}
+		}
+
+		let index = joints.len();
+		joints.push(Joint { name, parent, offset, channels });
+		loop {
+			match self.next()? {
+				"JOINT" => self.joint(joints, Some(index))?,
+				"End" => {
+					self.expect("Site")?;
+					self.expect("{")?;
+					self.offset()?;
+					self.expect("}")?;
+				}
+				"}" => return Ok(()),
+				token => return Err(VMCError::InvalidBvh(format!("unexpected '{token}' in joint")))
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use approx::assert_relative_eq;
+
+	use super::*;
+
+	const BVH: &str = "HIERARCHY
+ROOT Hips
+{
+	OFFSET 0.0 0.0 0.0
+	CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+	JOINT LeftHipJoint
+	{
+		OFFSET 0.0 0.0 0.0
+		CHANNELS 3 Zrotation Xrotation Yrotation
+		JOINT mixamorig:LeftUpLeg
+		{
+			OFFSET 10.0 -5.0 0.0
+			CHANNELS 3 Zrotation Xrotation Yrotation
+			End Site
+			{
+				OFFSET 0.0 -40.0 0.0
+			}
+		}
+	}
+}
+MOTION
+Frames: 2
+Frame Time: 0.0333333
+0.0 90.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0
+10.0 90.0 0.0 0.0 0.0 0.0 0.0 90.0 0.0 0.0 0.0 0.0
+";
+
+	#[test]
+	fn test_bvh_frames() -> VMCResult<()> {
+		let bvh = Bvh::parse(BVH)?;
+		assert_eq!(bvh.joints().len(), 3);
+		assert_eq!(bvh.frame_count(), 2);
+
+		let frames: Vec<_> = bvh.frames(&BvhOptions::default()).collect();
+		assert_relative_eq!(frames[1].time.0, 0.0333333);
+		let [VMCMessage::BoneTransform(hips), VMCMessage::BoneTransform(leg)] = &frames[1].messages[..] else {
+			panic!("expected hips & leg transforms");
+		};
+		assert_eq!(hips.bone, "Hips");
+		assert_relative_eq!(hips.position, Vec3A::new(-0.1, 0.9, 0.0));
+		assert_eq!(leg.bone, "LeftUpperLeg");
+		assert_relative_eq!(leg.position, Vec3A::new(-0.1, 0.0, -0.05), epsilon = 1e-6);
+		// the unmapped `LeftHipJoint`'s rotation is folded into the leg
+		assert_relative_eq!(leg.rotation, Quat::from_rotation_x(90f32.to_radians()), epsilon = 1e-6);
+		Ok(())
+	}
+}
@@ -0,0 +1,581 @@
+//! Zero-configuration discovery of VMC applications on the local network via mDNS/DNS-SD, so users don't have to type
+//! IP addresses into their tracking apps.
+//!
+//! An [`Advertiser`] announces an application under the `_vmc._udp` service type (and `_osc._udp`, which generic OSC
+//! tools browse for) and answers queries for it; [`browse`] finds advertised applications and returns their addresses.
+//! Each advertisement carries the application's [role](VMCRole) in a `role=performer` or `role=marionette` TXT entry.
+//!
+//! Only IPv4 is supported. Advertisers listen on the standard mDNS port 5353, sharing it with any other responder on
+//! the machine.
+//!
+//! This module is only available with the `discovery` feature.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+//! use std::time::Duration;
+//!
+//! use vmc::{VMCRole, discovery};
+//!
+//! // on the marionette
+//! let marionette = vmc::marionette!(bind_any).await?;
+//! let advertiser = discovery::Advertiser::new("My Avatar", marionette.local_addr()?.port(), VMCRole::Marionette);
+//! tokio::spawn(advertiser.run());
+//!
+//! // on the performer
+//! let marionettes = discovery::browse(Some(VMCRole::Marionette), Duration::from_secs(1)).await?;
+//! if let Some(addr) = marionettes.first().and_then(|peer| peer.addrs.first()) {
+//! 	let performer = vmc::performer!(*addr).await?;
+//! }
+//! # Ok(()) }) }
+//! ```
+
+use std::{
+	collections::HashSet,
+	io,
+	net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4},
+	time::{Duration, SystemTime, UNIX_EPOCH}
+};
+
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::{
+	net::UdpSocket,
+	time::{Instant, timeout_at}
+};
+
+use crate::{VMCResult, VMCRole};
+
+mod dns;
+
+use self::dns::{DomainName, Message, Question, Record, RecordData};
+
+/// The DNS-SD service type of VMC applications.
+pub const SERVICE_TYPE: &str = "_vmc._udp.local.";
+/// The DNS-SD service type of OSC applications, which advertisers also register under.
+pub const OSC_SERVICE_TYPE: &str = "_osc._udp.local.";
+
+const MDNS_ADDR: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(224, 0, 0, 251), 5353);
+/// The TTL of advertised records in seconds, as recommended by RFC 6762 for records which don't contain host names.
+const TTL: u32 = 4500;
+/// The TTL of address & SRV records, which should expire sooner if the host changes networks.
+const HOST_TTL: u32 = 120;
+
+impl VMCRole {
+	fn txt(&self) -> &'static str {
+		match self {
+			VMCRole::Performer => "role=performer",
+			VMCRole::Marionette => "role=marionette"
+		}
+	}
+}
+
+/// Advertises a VMC application on the local network; see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Advertiser {
+	instance: String,
+	port: u16,
+	role: VMCRole,
+	host: DomainName,
+	addr: Option<Ipv4Addr>
+}
+
+impl Advertiser {
+	/// Creates an advertiser for an application listening on `port`, under a human-readable instance name like
+	/// `"My Avatar"`, which browsers display to the user.
+	///
+	/// The address advertised is that of the interface used to reach the mDNS group, which is usually the primary
+	/// network interface; browsers fall back to the address responses are received from if it can't be determined.
+	pub fn new(instance: impl Into<String>, port: u16, role: VMCRole) -> Self {
+		let instance = instance.into();
+		let host: String = instance.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '-' }).collect();
+		Self {
+			host: DomainName::new("local").prepend(format!("{host}-vmc")),
+			instance,
+			port,
+			role,
+			addr: local_addr()
+		}
+	}
+
+	/// Sets the IPv4 address advertised for the application.
+	pub fn with_addr(mut self, addr: Ipv4Addr) -> Self {
+		self.addr = Some(addr);
+		self
+	}
+
+	/// Answers queries for the advertised application until an error occurs, announcing it when started. Spawn the
+	/// returned future on the runtime to keep advertising in the background; dropping it announces that the
+	/// application has left, so browsers forget it immediately.
+	pub async fn run(self) -> VMCResult<()> {
+		let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+		socket.set_reuse_address(true)?;
+		#[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos", target_os = "cygwin", target_os = "nuttx"))))]
+		socket.set_reuse_port(true)?;
+		socket.set_multicast_loop_v4(true)?;
+		socket.set_multicast_ttl_v4(255)?;
+		socket.set_nonblocking(true)?;
+		socket.bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, MDNS_ADDR.port())).into())?;
+		socket.join_multicast_v4(MDNS_ADDR.ip(), &Ipv4Addr::UNSPECIFIED)?;
+		let socket = UdpSocket::from_std(socket.into())?;
+
+		let announcement = Message {
+			response: true,
+			records: self.records(None),
+			..Default::default()
+		}
+		.encode();
+		socket.send_to(&announcement, MDNS_ADDR).await?;
+		let _goodbye = Goodbye {
+			socket: &socket,
+			packet: Message {
+				response: true,
+				records: self.records(Some(0)),
+				..Default::default()
+			}
+			.encode()
+		};
+
+		// RFC 6762 asks for a second announcement a second after the first, in case it was lost
+		let mut reannounce = Some(Instant::now() + Duration::from_secs(1));
+		let mut buf = vec![0; 9000];
+		loop {
+			let received = match reannounce {
+				Some(at) => match timeout_at(at, socket.recv_from(&mut buf)).await {
+					Ok(received) => received,
+					Err(_) => {
+						socket.send_to(&announcement, MDNS_ADDR).await?;
+						reannounce = None;
+						continue;
+					}
+				},
+				None => socket.recv_from(&mut buf).await
+			};
+			let (len, peer) = match received {
+				Ok(received) => received,
+				// ICMP errors from earlier unicast responses surface here on some platforms
+				Err(e) if e.kind() == io::ErrorKind::ConnectionReset => continue,
+				Err(e) => return Err(e.into())
+			};
+			let Some(query) = Message::decode(&buf[..len]).filter(|message| !message.response) else {
+				continue;
+			};
+			// queries not sent from the mDNS port come from simple resolvers, which expect a unicast reply
+			let legacy = peer.port() != MDNS_ADDR.port();
+			if let Some(response) = self.respond(&query, legacy) {
+				let target = if legacy { peer } else { MDNS_ADDR.into() };
+				socket.send_to(&response.encode(), target).await?;
+			}
+		}
+	}
+
+	fn services(&self) -> impl Iterator<Item = (DomainName, DomainName)> + '_ {
+		[SERVICE_TYPE, OSC_SERVICE_TYPE].into_iter().map(|service| {
+			let service = DomainName::new(service);
+			(service.prepend(self.instance.clone()), service)
+		})
+	}
+
+	/// Returns all records of the advertisement, with the given TTL instead of the default.
+	fn records(&self, ttl: Option<u32>) -> Vec<Record> {
+		let mut records = Vec::new();
+		for (instance, service) in self.services() {
+			records.push(Record::new(service, ttl.unwrap_or(TTL), RecordData::Ptr(instance.clone())));
+			records.extend(self.instance_records(instance, ttl));
+		}
+		records.extend(self.addr_record(ttl));
+		records
+	}
+
+	fn instance_records(&self, instance: DomainName, ttl: Option<u32>) -> [Record; 2] {
+		[
+			Record::new(
+				instance.clone(),
+				ttl.unwrap_or(HOST_TTL),
+				RecordData::Srv {
+					port: self.port,
+					target: self.host.clone()
+				}
+			),
+			Record::new(instance, ttl.unwrap_or(TTL), RecordData::Txt(vec!["txtvers=1".to_string(), self.role.txt().to_string()]))
+		]
+	}
+
+	fn addr_record(&self, ttl: Option<u32>) -> Option<Record> {
+		self.addr
+			.map(|addr| Record::new(self.host.clone(), ttl.unwrap_or(HOST_TTL), RecordData::A(addr)))
+	}
+
+	/// Builds the response to a query, or `None` if it asks about none of our records.
+	fn respond(&self, query: &Message, legacy: bool) -> Option<Message> {
+		let mut records = Vec::new();
+		for question in &query.questions {
+			let any = question.kind == dns::TYPE_ANY;
+			for (instance, service) in self.services() {
+				if question.name == service && (any || question.kind == dns::TYPE_PTR) {
+					records.push(Record::new(service, TTL, RecordData::Ptr(instance.clone())));
+					records.extend(self.instance_records(instance, None));
+					records.extend(self.addr_record(None));
+				} else if question.name == instance {
+					records.extend(
+						self.instance_records(instance, None)
+							.into_iter()
+							.filter(|record| any || record.kind() == question.kind)
+					);
+					records.extend(self.addr_record(None));
+				}
+			}
+			if question.name == self.host && (any || question.kind == dns::TYPE_A) {
+				records.extend(self.addr_record(None));
+			}
+		}
+		if records.is_empty() {
+			return None;
+		}
+		let mut deduplicated: Vec<Record> = Vec::with_capacity(records.len());
+		for record in records {
+			if !deduplicated.contains(&record) {
+				deduplicated.push(record);
+			}
+		}
+		Some(Message {
+			id: if legacy { query.id } else { 0 },
+			response: true,
+			questions: if legacy { query.questions.clone() } else { Vec::new() },
+			records: deduplicated
+		})
+	}
+}
+
+/// Announces that the advertisement is gone when [`Advertiser::run`] stops.
+struct Goodbye<'s> {
+	socket: &'s UdpSocket,
+	packet: Vec<u8>
+}
+
+impl Drop for Goodbye<'_> {
+	fn drop(&mut self) {
+		let _ = self.socket.try_send_to(&self.packet, MDNS_ADDR.into());
+	}
+}
+
+/// Returns the address of the interface used to reach the mDNS group.
+fn local_addr() -> Option<Ipv4Addr> {
+	let socket = std::net::UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+	socket.connect(MDNS_ADDR).ok()?;
+	match socket.local_addr().ok()?.ip() {
+		IpAddr::V4(ip) if !ip.is_unspecified() => Some(ip),
+		_ => None
+	}
+}
+
+/// A VMC application found by [`browse`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Peer {
+	/// The human-readable instance name of the application.
+	pub name: String,
+	/// The role of the application, if it advertised one.
+	pub role: Option<VMCRole>,
+	/// The candidate addresses of the application, in order of preference.
+	pub addrs: Vec<SocketAddr>
+}
+
+/// Browses the local network for VMC applications for `duration`, returning every application which responded.
+///
+/// If `role` is given, applications advertising a different role are skipped; applications which didn't advertise a
+/// role are always included.
+pub async fn browse(role: Option<VMCRole>, duration: Duration) -> VMCResult<Vec<Peer>> {
+	let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+	let id = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.subsec_nanos() as u16);
+	let query = Message {
+		id,
+		questions: vec![Question {
+			name: DomainName::new(SERVICE_TYPE),
+			kind: dns::TYPE_PTR
+		}],
+		..Default::default()
+	};
+	socket.send_to(&query.encode(), MDNS_ADDR).await?;
+
+	let deadline = Instant::now() + duration;
+	let mut peers: Vec<Peer> = Vec::new();
+	let mut buf = vec![0; 9000];
+	while let Ok(received) = timeout_at(deadline, socket.recv_from(&mut buf)).await {
+		let Ok((len, source)) = received else {
+			continue;
+		};
+		let Some(response) = Message::decode(&buf[..len]).filter(|message| message.response) else {
+			continue;
+		};
+		for peer in resolve(&response, source.ip()) {
+			if role.is_some() && peer.role.is_some() && peer.role != role {
+				continue;
+			}
+			match peers.iter_mut().find(|known| known.name == peer.name) {
+				Some(known) => {
+					for addr in peer.addrs {
+						if !known.addrs.contains(&addr) {
+							known.addrs.push(addr);
+						}
+					}
+				}
+				None => peers.push(peer)
+			}
+		}
+	}
+	Ok(peers)
+}
+
+/// Extracts the VMC applications described by a response, using `source` as the address of those without address
+/// records.
+fn resolve(response: &Message, source: IpAddr) -> Vec<Peer> {
+	let service = DomainName::new(SERVICE_TYPE);
+	let mut seen = HashSet::new();
+	let mut peers = Vec::new();
+	for record in &response.records {
+		let RecordData::Ptr(instance) = &record.data else {
+			continue;
+		};
+		if record.name != service || record.ttl == 0 || !seen.insert(instance.to_string().to_ascii_lowercase()) {
+			continue;
+		}
+		let records = || response.records.iter().filter(|record| record.name == *instance);
+		let Some((port, host)) = records().find_map(|record| match &record.data {
+			RecordData::Srv { port, target } => Some((*port, target)),
+			_ => None
+		}) else {
+			continue;
+		};
+		let role = records()
+			.filter_map(|record| match &record.data {
+				RecordData::Txt(entries) => Some(entries),
+				_ => None
+			})
+			.flatten()
+			.find_map(|entry| match entry.strip_prefix("role=")? {
+				"performer" => Some(VMCRole::Performer),
+				"marionette" => Some(VMCRole::Marionette),
+				_ => None
+			});
+		let mut addrs: Vec<SocketAddr> = response
+			.records
+			.iter()
+			.filter(|record| record.name == *host)
+			.filter_map(|record| match record.data {
+				RecordData::A(ip) => Some(SocketAddr::from((ip, port))),
+				RecordData::Aaaa(ip) => Some(SocketAddr::from((ip, port))),
+				_ => None
+			})
+			.collect();
+		if !addrs.contains(&SocketAddr::new(source, port)) {
+			addrs.push(SocketAddr::new(source, port));
+		}
+		peers.push(Peer {
+			name: instance.first().unwrap_or_default().to_string(),
+			role,
+			addrs
+		});
+	}
+	peers
+}
+
+#[cfg(test)]
+mod tests {
+	use std::net::Ipv6Addr;
+
+	use super::*;
+
+	#[test]
+	fn test_discovery() {
+		let advertiser = Advertiser::new("My Avatar", 39539, VMCRole::Marionette).with_addr(Ipv4Addr::new(192, 168, 1, 20));
+		let query = Message::decode(
+			&Message {
+				id: 7,
+				questions: vec![Question {
+					name: DomainName::new("_VMC._udp.local"),
+					kind: dns::TYPE_PTR
+				}],
+				..Default::default()
+			}
+			.encode()
+		)
+		.unwrap();
+		assert!(advertiser.respond(&Message::default(), false).is_none());
+
+		let response = advertiser.respond(&query, true).unwrap();
+		assert_eq!(response.id, 7);
+		let response = Message::decode(&response.encode()).unwrap();
+		let peers = resolve(&response, IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+		assert_eq!(
+			peers,
+			[Peer {
+				name: "My Avatar".to_string(),
+				role: Some(VMCRole::Marionette),
+				addrs: vec![SocketAddr::from(([192, 168, 1, 20], 39539)), SocketAddr::from(([10, 0, 0, 1], 39539))]
+			}]
+		);
+
+		// compressed names are followed
+		let mut packet = Message {
+			response: true,
+			records: vec![Record::new(DomainName::new(SERVICE_TYPE), TTL, RecordData::Other(0))],
+			..Default::default()
+		}
+		.encode();
+		packet.extend_from_slice(&[0xc0, 12, 0, 12, 0, 1, 0, 0, 0, 10, 0, 5, 2, b'h', b'i', 0xc0, 12]);
+		packet[7] = 2;
+		let message = Message::decode(&packet).unwrap();
+		assert_eq!(message.records[1].name, DomainName::new(SERVICE_TYPE));
+		assert_eq!(message.records[1].data, RecordData::Ptr(DomainName::new(SERVICE_TYPE).prepend("hi")));
+		// but not forever
+		assert!(Message::decode(&[0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0xc0, 12, 0, 12, 0, 1]).is_none());
+	}
+
+	#[test]
+	fn test_dns_edge_cases() {
+		// instance names may contain dots; overlong labels & TXT entries are truncated
+		let long = "x".repeat(300);
+		let message = Message {
+			response: true,
+			records: vec![
+				Record::new(DomainName::new(SERVICE_TYPE).prepend("v1.2 Avatar"), TTL, RecordData::Txt(vec![long.clone()])),
+				Record::new(DomainName::new(SERVICE_TYPE).prepend(long.clone()), TTL, RecordData::Txt(Vec::new())),
+				Record::new(DomainName::new("host.local"), TTL, RecordData::Aaaa(Ipv6Addr::LOCALHOST)),
+			],
+			..Default::default()
+		};
+		let decoded = Message::decode(&message.encode()).unwrap();
+		assert_eq!(decoded.records[0].name.first(), Some("v1.2 Avatar"));
+		assert_eq!(decoded.records[0].data, RecordData::Txt(vec!["x".repeat(255)]));
+		assert_eq!(decoded.records[1].name.first(), Some(&*"x".repeat(63)));
+		assert_eq!(decoded.records[1].data, RecordData::Txt(Vec::new()));
+		assert_eq!(decoded.records[2], message.records[2]);
+
+		// questions of other classes are skipped
+		let mut packet = Message {
+			questions: vec![Question {
+				name: DomainName::new(SERVICE_TYPE),
+				kind: dns::TYPE_PTR
+			}],
+			..Default::default()
+		}
+		.encode();
+		let len = packet.len();
+		packet[len - 1] = 3;
+		assert_eq!(Message::decode(&packet).unwrap().questions, []);
+		// ...but the unicast-response bit is ignored
+		packet[len - 2..].copy_from_slice(&[0x80, 1]);
+		assert_eq!(Message::decode(&packet).unwrap().questions.len(), 1);
+
+		// truncated & malformed packets are rejected
+		let packet = message.encode();
+		for len in [0, 11, 12, packet.len() - 1] {
+			assert!(Message::decode(&packet[..len]).is_none(), "{len}");
+		}
+		// record count larger than the records present
+		let mut truncated = packet.clone();
+		truncated[7] += 1;
+		assert!(Message::decode(&truncated).is_none());
+		// reserved label type
+		assert!(Message::decode(&[0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0x40, 0, 0, 12, 0, 1]).is_none());
+		// pointer past the end of the packet
+		assert!(Message::decode(&[0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0xc0, 0xff, 0, 12, 0, 1]).is_none());
+		// address record of the wrong length
+		let mut packet = Message {
+			records: vec![Record::new(DomainName::default(), TTL, RecordData::A(Ipv4Addr::LOCALHOST))],
+			..Default::default()
+		}
+		.encode();
+		packet[12 + 1 + 9] = 3;
+		packet.pop();
+		assert!(Message::decode(&packet).is_none());
+		// TXT entry longer than its record
+		let mut packet = Message {
+			records: vec![Record::new(DomainName::default(), TTL, RecordData::Txt(vec!["role".to_string()]))],
+			..Default::default()
+		}
+		.encode();
+		packet[12 + 1 + 10] = 5;
+		assert!(Message::decode(&packet).is_none());
+	}
+
+	#[test]
+	fn test_advertiser_respond() {
+		let advertiser = Advertiser::new("My Avatar", 39539, VMCRole::Performer).with_addr(Ipv4Addr::new(192, 168, 1, 20));
+		let query = |questions: &[(&str, u16)]| Message {
+			id: 7,
+			questions: questions
+				.iter()
+				.map(|(name, kind)| Question {
+					name: DomainName::new(name),
+					kind: *kind
+				})
+				.collect(),
+			..Default::default()
+		};
+		let kinds = |response: Message| response.records.iter().map(Record::kind).collect::<Vec<_>>();
+
+		// multicast responses carry no ID or questions; asking for both services returns each record once
+		let response = advertiser
+			.respond(&query(&[(SERVICE_TYPE, dns::TYPE_PTR), (OSC_SERVICE_TYPE, dns::TYPE_ANY)]), false)
+			.unwrap();
+		assert_eq!((response.id, response.questions.len()), (0, 0));
+		assert_eq!(kinds(response), [dns::TYPE_PTR, dns::TYPE_SRV, dns::TYPE_TXT, dns::TYPE_A, dns::TYPE_PTR, dns::TYPE_SRV, dns::TYPE_TXT]);
+
+		// questions about the instance only return the records of the requested type, plus the address
+		let instance = DomainName::new(SERVICE_TYPE).prepend("my avatar").to_string();
+		assert_eq!(kinds(advertiser.respond(&query(&[(&instance, dns::TYPE_TXT)]), true).unwrap()), [dns::TYPE_TXT, dns::TYPE_A]);
+		assert_eq!(kinds(advertiser.respond(&query(&[(&instance, dns::TYPE_ANY)]), true).unwrap()), [dns::TYPE_SRV, dns::TYPE_TXT, dns::TYPE_A]);
+		assert_eq!(kinds(advertiser.respond(&query(&[("my-avatar-vmc.local", dns::TYPE_A)]), true).unwrap()), [dns::TYPE_A]);
+
+		// unrelated questions, or other record types of the services & host, aren't answered
+		assert!(advertiser.respond(&query(&[("_http._tcp.local", dns::TYPE_PTR)]), true).is_none());
+		assert!(advertiser.respond(&query(&[(SERVICE_TYPE, dns::TYPE_TXT)]), true).is_none());
+		assert!(advertiser.respond(&query(&[("My-Avatar-vmc.local", dns::TYPE_AAAA)]), true).is_none());
+
+		// without a known address, no address record is advertised
+		let mut advertiser = advertiser;
+		advertiser.addr = None;
+		assert!(advertiser.respond(&query(&[("my-avatar-vmc.local", dns::TYPE_A)]), true).is_none());
+		assert_eq!(kinds(advertiser.respond(&query(&[(SERVICE_TYPE, dns::TYPE_PTR)]), true).unwrap()), [dns::TYPE_PTR, dns::TYPE_SRV, dns::TYPE_TXT]);
+	}
+
+	#[test]
+	fn test_resolve_edge_cases() {
+		let source = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+		let service = DomainName::new(SERVICE_TYPE);
+		let instance = |name: &str| service.prepend(name);
+		let host = DomainName::new("host.local");
+		let srv = |name: &str, port| Record::new(instance(name), HOST_TTL, RecordData::Srv { port, target: host.clone() });
+		let response = Message {
+			response: true,
+			records: vec![
+				// leaving
+				Record::new(service.clone(), 0, RecordData::Ptr(instance("Gone"))),
+				srv("Gone", 1),
+				// another service
+				Record::new(DomainName::new(OSC_SERVICE_TYPE), TTL, RecordData::Ptr(instance("OSC"))),
+				srv("OSC", 2),
+				// no SRV record
+				Record::new(service.clone(), TTL, RecordData::Ptr(instance("Portless"))),
+				// duplicate PTR records differing in case; unknown role
+				Record::new(service.clone(), TTL, RecordData::Ptr(instance("Avatar"))),
+				Record::new(service.clone(), TTL, RecordData::Ptr(instance("AVATAR"))),
+				srv("avatar", 39539),
+				Record::new(instance("Avatar"), TTL, RecordData::Txt(vec!["role=director".to_string()])),
+				Record::new(host.clone(), HOST_TTL, RecordData::Aaaa(Ipv6Addr::LOCALHOST)),
+				Record::new(host.clone(), HOST_TTL, RecordData::A(Ipv4Addr::new(10, 0, 0, 1))),
+			],
+			..Default::default()
+		};
+		assert_eq!(
+			resolve(&response, source),
+			[Peer {
+				name: "Avatar".to_string(),
+				role: None,
+				addrs: vec![SocketAddr::from((Ipv6Addr::LOCALHOST, 39539)), SocketAddr::from((source, 39539))]
+			}]
+		);
+		assert!(resolve(&Message::default(), source).is_empty());
+	}
+}
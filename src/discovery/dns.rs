@@ -0,0 +1,273 @@
+//! A minimal DNS message codec, covering the subset of records used by DNS-SD over mDNS.
+
+use std::{
+	fmt,
+	net::{Ipv4Addr, Ipv6Addr}
+};
+
+pub(super) const TYPE_A: u16 = 1;
+pub(super) const TYPE_PTR: u16 = 12;
+pub(super) const TYPE_TXT: u16 = 16;
+pub(super) const TYPE_AAAA: u16 = 28;
+pub(super) const TYPE_SRV: u16 = 33;
+pub(super) const TYPE_ANY: u16 = 255;
+
+const CLASS_IN: u16 = 1;
+/// The top bit of the class is the cache-flush bit in records, and the unicast-response bit in questions.
+const CLASS_MASK: u16 = 0x7fff;
+const FLAG_RESPONSE: u16 = 0x8000;
+const FLAG_AUTHORITATIVE: u16 = 0x0400;
+
+/// The maximum number of compression pointers followed in a single name, so malicious packets can't loop forever.
+const MAX_JUMPS: usize = 16;
+
+/// A domain name as a list of labels. Labels may contain dots, as DNS-SD instance names can; comparisons ignore ASCII
+/// case.
+#[derive(Debug, Clone, Default)]
+pub(super) struct DomainName(Vec<String>);
+
+impl DomainName {
+	/// Parses a dot-separated name. A trailing dot is optional.
+	pub fn new(name: &str) -> Self {
+		Self(name.split('.').filter(|label| !label.is_empty()).map(str::to_string).collect())
+	}
+
+	/// Returns this name with a label prepended, e.g. an instance name to a service type.
+	pub fn prepend(&self, label: impl Into<String>) -> Self {
+		let mut labels = vec![label.into()];
+		labels.extend(self.0.iter().cloned());
+		Self(labels)
+	}
+
+	/// Returns the first label of the name.
+	pub fn first(&self) -> Option<&str> {
+		self.0.first().map(String::as_str)
+	}
+
+	fn encode(&self, buf: &mut Vec<u8>) {
+		for label in &self.0 {
+			let label = &label.as_bytes()[..label.len().min(63)];
+			buf.push(label.len() as u8);
+			buf.extend_from_slice(label);
+		}
+		buf.push(0);
+	}
+
+	fn decode(packet: &[u8], mut pos: usize) -> Option<(Self, usize)> {
+		let mut labels = Vec::new();
+		let mut end = None;
+		let mut jumps = 0;
+		loop {
+			let len = *packet.get(pos)? as usize;
+			match len & 0xc0 {
+				0x00 if len == 0 => {
+					pos += 1;
+					break;
+				}
+				0x00 => {
+					let label = packet.get(pos + 1..pos + 1 + len)?;
+					labels.push(String::from_utf8_lossy(label).into_owned());
+					pos += 1 + len;
+				}
+				0xc0 => {
+					jumps += 1;
+					if jumps > MAX_JUMPS {
+						return None;
+					}
+					end.get_or_insert(pos + 2);
+					pos = (len & 0x3f) << 8 | *packet.get(pos + 1)? as usize;
+				}
+				_ => return None
+			}
+		}
+		Some((Self(labels), end.unwrap_or(pos)))
+	}
+}
+
+impl PartialEq for DomainName {
+	fn eq(&self, other: &Self) -> bool {
+		self.0.len() == other.0.len() && self.0.iter().zip(&other.0).all(|(a, b)| a.eq_ignore_ascii_case(b))
+	}
+}
+
+impl fmt::Display for DomainName {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for label in &self.0 {
+			write!(f, "{label}.")?;
+		}
+		Ok(())
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Question {
+	pub name: DomainName,
+	pub kind: u16
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum RecordData {
+	A(Ipv4Addr),
+	Aaaa(Ipv6Addr),
+	Ptr(DomainName),
+	Srv { port: u16, target: DomainName },
+	Txt(Vec<String>),
+	Other(u16)
+}
+
+impl RecordData {
+	fn kind(&self) -> u16 {
+		match self {
+			RecordData::A(_) => TYPE_A,
+			RecordData::Aaaa(_) => TYPE_AAAA,
+			RecordData::Ptr(_) => TYPE_PTR,
+			RecordData::Srv { .. } => TYPE_SRV,
+			RecordData::Txt(_) => TYPE_TXT,
+			RecordData::Other(kind) => *kind
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Record {
+	pub name: DomainName,
+	pub ttl: u32,
+	pub data: RecordData
+}
+
+impl Record {
+	pub fn new(name: DomainName, ttl: u32, data: RecordData) -> Self {
+		Self { name, ttl, data }
+	}
+
+	pub fn kind(&self) -> u16 {
+		self.data.kind()
+	}
+
+	fn encode(&self, buf: &mut Vec<u8>) {
+		self.name.encode(buf);
+		buf.extend_from_slice(&self.kind().to_be_bytes());
+		buf.extend_from_slice(&CLASS_IN.to_be_bytes());
+		buf.extend_from_slice(&self.ttl.to_be_bytes());
+		let len_pos = buf.len();
+		buf.extend_from_slice(&[0, 0]);
+		match &self.data {
+			RecordData::A(ip) => buf.extend_from_slice(&ip.octets()),
+			RecordData::Aaaa(ip) => buf.extend_from_slice(&ip.octets()),
+			RecordData::Ptr(name) => name.encode(buf),
+			RecordData::Srv { port, target } => {
+				// priority & weight
+				buf.extend_from_slice(&[0, 0, 0, 0]);
+				buf.extend_from_slice(&port.to_be_bytes());
+				target.encode(buf);
+			}
+			RecordData::Txt(entries) => {
+				for entry in entries {
+					let entry = &entry.as_bytes()[..entry.len().min(255)];
+					buf.push(entry.len() as u8);
+					buf.extend_from_slice(entry);
+				}
+				if entries.is_empty() {
+					buf.push(0);
+				}
+			}
+			RecordData::Other(_) => {}
+		}
+		let len = (buf.len() - len_pos - 2) as u16;
+		buf[len_pos..len_pos + 2].copy_from_slice(&len.to_be_bytes());
+	}
+
+	fn decode(packet: &[u8], pos: usize) -> Option<(Self, usize)> {
+		let (name, pos) = DomainName::decode(packet, pos)?;
+		let header = packet.get(pos..pos + 10)?;
+		let kind = u16::from_be_bytes([header[0], header[1]]);
+		let ttl = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+		let len = u16::from_be_bytes([header[8], header[9]]) as usize;
+		let start = pos + 10;
+		let rdata = packet.get(start..start + len)?;
+		let data = match kind {
+			TYPE_A => RecordData::A(<[u8; 4]>::try_from(rdata).ok()?.into()),
+			TYPE_AAAA => RecordData::Aaaa(<[u8; 16]>::try_from(rdata).ok()?.into()),
+			TYPE_PTR => RecordData::Ptr(DomainName::decode(packet, start)?.0),
+			TYPE_SRV => RecordData::Srv {
+				port: u16::from_be_bytes([*rdata.get(4)?, *rdata.get(5)?]),
+				target: DomainName::decode(packet, start + 6)?.0
+			},
+			TYPE_TXT => {
+				let mut entries = Vec::new();
+				let mut rest = rdata;
+				while let Some((&len, tail)) = rest.split_first() {
+					let entry = tail.get(..len as usize)?;
+					if !entry.is_empty() {
+						entries.push(String::from_utf8_lossy(entry).into_owned());
+					}
+					rest = &tail[len as usize..];
+				}
+				RecordData::Txt(entries)
+			}
+			kind => RecordData::Other(kind)
+		};
+		Some((Self { name, ttl, data }, start + len))
+	}
+}
+
+/// A DNS message. Answer, authority, & additional records are all kept in `records`, since mDNS doesn't distinguish
+/// them in practice.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(super) struct Message {
+	pub id: u16,
+	pub response: bool,
+	pub questions: Vec<Question>,
+	pub records: Vec<Record>
+}
+
+impl Message {
+	pub fn encode(&self) -> Vec<u8> {
+		let mut buf = Vec::with_capacity(512);
+		let flags = if self.response { FLAG_RESPONSE | FLAG_AUTHORITATIVE } else { 0 };
+		buf.extend_from_slice(&self.id.to_be_bytes());
+		buf.extend_from_slice(&flags.to_be_bytes());
+		buf.extend_from_slice(&(self.questions.len() as u16).to_be_bytes());
+		buf.extend_from_slice(&(self.records.len() as u16).to_be_bytes());
+		// no authority or additional records
+		buf.extend_from_slice(&[0, 0, 0, 0]);
+		for question in &self.questions {
+			question.name.encode(&mut buf);
+			buf.extend_from_slice(&question.kind.to_be_bytes());
+			buf.extend_from_slice(&CLASS_IN.to_be_bytes());
+		}
+		for record in &self.records {
+			record.encode(&mut buf);
+		}
+		buf
+	}
+
+	/// Decodes a message, returning `None` if it is malformed or isn't of the internet class.
+	pub fn decode(packet: &[u8]) -> Option<Self> {
+		let header = packet.get(..12)?;
+		let count = |i: usize| u16::from_be_bytes([header[i], header[i + 1]]) as usize;
+		let mut message = Message {
+			id: count(0) as u16,
+			response: count(2) as u16 & FLAG_RESPONSE != 0,
+			..Default::default()
+		};
+		let mut pos = 12;
+		for _ in 0..count(4) {
+			let (name, next) = DomainName::decode(packet, pos)?;
+			let fields = packet.get(next..next + 4)?;
+			if u16::from_be_bytes([fields[2], fields[3]]) & CLASS_MASK == CLASS_IN {
+				message.questions.push(Question {
+					name,
+					kind: u16::from_be_bytes([fields[0], fields[1]])
+				});
+			}
+			pos = next + 4;
+		}
+		for _ in 0..count(6) + count(8) + count(10) {
+			let (record, next) = Record::decode(packet, pos)?;
+			message.records.push(record);
+			pos = next;
+		}
+		Some(message)
+	}
+}
@@ -0,0 +1,358 @@
+//! [egui](https://github.com/emilk/egui) widgets for inspecting an avatar, enabled with the `egui` feature.
+//!
+//! [`AvatarPanel`] is a drop-in diagnostics panel showing everything known about a
+//! [`VMCAvatarState`](crate::VMCAvatarState): [`StatusLights`] for the model, calibration & tracking state, a
+//! [`BoneList`] with the live transforms of all bones & devices, and [`BlendShapeBars`] for the applied blend shape
+//! values. Each part can also be used on its own.
+//!
+//! This module targets egui 0.31. Widgets only draw the state they're given, so the app should request a repaint
+//! whenever new messages are applied.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use vmc::{VMCAvatarState, egui::AvatarPanel};
+//!
+//! fn show(ctx: &egui::Context, avatar: &VMCAvatarState) {
+//! 	egui::SidePanel::right("vmc").show(ctx, |ui| {
+//! 		egui::ScrollArea::vertical().show(ui, |ui| ui.add(AvatarPanel::new(avatar)));
+//! 	});
+//! }
+//! ```
+
+use egui::{CollapsingHeader, Color32, Grid, ProgressBar, Response, Sense, Ui, Widget, vec2};
+use glam::{EulerRot, Quat, Vec3A};
+
+use crate::{
+	avatar::AvatarState,
+	message::{CalibrationState, ModelState, TrackingState}
+};
+
+const GOOD: Color32 = Color32::from_rgb(80, 200, 100);
+const PENDING: Color32 = Color32::from_rgb(230, 180, 50);
+const BAD: Color32 = Color32::from_rgb(220, 70, 70);
+const UNKNOWN: Color32 = Color32::GRAY;
+
+/// Shows the model, calibration & tracking state of an avatar as colored lights, followed by the latest time.
+///
+/// Lights are gray until a state message has been received.
+#[derive(Debug, Clone, Copy)]
+pub struct StatusLights<'a> {
+	avatar: &'a AvatarState
+}
+
+impl<'a> StatusLights<'a> {
+	/// Creates status lights for the given avatar.
+	pub fn new(avatar: &'a AvatarState) -> Self {
+		Self { avatar }
+	}
+}
+
+impl Widget for StatusLights<'_> {
+	fn ui(self, ui: &mut Ui) -> Response {
+		let state = self.avatar.state();
+		let model = match state.map(|state| state.model_state) {
+			Some(ModelState::Loaded) => GOOD,
+			Some(ModelState::NotLoaded) => BAD,
+			None => UNKNOWN
+		};
+		let calibration = match state.and_then(|state| state.calibration_state) {
+			Some((_, CalibrationState::Calibrated)) => GOOD,
+			Some((_, CalibrationState::Calibrating | CalibrationState::WaitingForCalibration)) => PENDING,
+			Some((_, CalibrationState::Uncalibrated)) => BAD,
+			None => UNKNOWN
+		};
+		let tracking = match state.and_then(|state| state.tracking_state) {
+			Some(TrackingState::Good) => GOOD,
+			Some(TrackingState::Poor) => PENDING,
+			None => UNKNOWN
+		};
+
+		ui.horizontal(|ui| {
+			for (label, color) in [("Model", model), ("Calibration", calibration), ("Tracking", tracking)] {
+				light(ui, color);
+				ui.label(label);
+				ui.add_space(6.0);
+			}
+			match self.avatar.time() {
+				Some(time) => ui.monospace(format!("T {time:.2}")),
+				None => ui.weak("T -")
+			};
+		})
+		.response
+	}
+}
+
+fn light(ui: &mut Ui, color: Color32) {
+	let size = ui.text_style_height(&egui::TextStyle::Body) * 0.7;
+	let (rect, _) = ui.allocate_exact_size(vec2(size, size), Sense::hover());
+	ui.painter().circle_filled(rect.center(), size / 2.0, color);
+}
+
+/// Shows the live position & rotation of the root, all bones, and all devices of an avatar in a table.
+///
+/// Rotations are shown as Euler angles in degrees, in the same Y, X, Z order as Unity. Bones are sorted by name.
+#[derive(Debug, Clone, Copy)]
+pub struct BoneList<'a> {
+	avatar: &'a AvatarState
+}
+
+impl<'a> BoneList<'a> {
+	/// Creates a bone list for the given avatar.
+	pub fn new(avatar: &'a AvatarState) -> Self {
+		Self { avatar }
+	}
+}
+
+impl Widget for BoneList<'_> {
+	fn ui(self, ui: &mut Ui) -> Response {
+		let mut bones: Vec<_> = self.avatar.bones().collect();
+		bones.sort_unstable_by(|a, b| a.bone.cmp(&b.bone));
+		let mut devices: Vec<_> = self.avatar.devices().collect();
+		devices.sort_unstable_by(|a, b| (a.device as u8, &a.joint).cmp(&(b.device as u8, &b.joint)));
+
+		Grid::new("vmc_bone_list")
+			.num_columns(3)
+			.striped(true)
+			.show(ui, |ui| {
+				ui.strong("Name");
+				ui.strong("Position");
+				ui.strong("Rotation");
+				ui.end_row();
+
+				if let Some(root) = self.avatar.root() {
+					transform_row(ui, "Root", root.position, root.rotation);
+				}
+				for bone in bones {
+					transform_row(ui, bone.bone.as_str(), bone.position, bone.rotation);
+				}
+				for device in devices {
+					transform_row(ui, &format!("{} {}", device.device, device.joint), device.position, device.rotation);
+				}
+			})
+			.response
+	}
+}
+
+fn transform_row(ui: &mut Ui, name: &str, position: Vec3A, rotation: Quat) {
+	let (y, x, z) = rotation.to_euler(EulerRot::YXZ);
+	ui.label(name);
+	ui.monospace(format!("{:>7.3} {:>7.3} {:>7.3}", position.x, position.y, position.z));
+	ui.monospace(format!("{:>7.1} {:>7.1} {:>7.1}", x.to_degrees(), y.to_degrees(), z.to_degrees()));
+	ui.end_row();
+}
+
+/// Shows the applied blend shape values of an avatar as bars, sorted by name.
+#[derive(Debug, Clone, Copy)]
+pub struct BlendShapeBars<'a> {
+	avatar: &'a AvatarState,
+	hide_zero: bool
+}
+
+impl<'a> BlendShapeBars<'a> {
+	/// Creates blend shape bars for the given avatar.
+	pub fn new(avatar: &'a AvatarState) -> Self {
+		Self { avatar, hide_zero: false }
+	}
+
+	/// Sets whether blend shapes with a value of `0` are hidden, which is useful for avatars with many "perfect sync"
+	/// blend shapes.
+	pub fn with_hide_zero(mut self, hide_zero: bool) -> Self {
+		self.hide_zero = hide_zero;
+		self
+	}
+}
+
+impl Widget for BlendShapeBars<'_> {
+	fn ui(self, ui: &mut Ui) -> Response {
+		let mut blendshapes: Vec<_> = self
+			.avatar
+			.blendshapes()
+			.iter()
+			.filter(|(_, value)| !self.hide_zero || *value != 0.0)
+			.collect();
+		blendshapes.sort_unstable_by(|a, b| a.0.cmp(b.0));
+
+		Grid::new("vmc_blendshape_bars")
+			.num_columns(2)
+			.show(ui, |ui| {
+				for (key, value) in blendshapes {
+					ui.label(key);
+					ui.add(ProgressBar::new(value.clamp(0.0, 1.0)).desired_width(120.0).text(format!("{value:.2}")));
+					ui.end_row();
+				}
+			})
+			.response
+	}
+}
+
+/// A diagnostics panel combining [`StatusLights`], a [`BoneList`], and [`BlendShapeBars`] for an avatar, with the
+/// bone list & blend shapes in collapsible sections.
+#[derive(Debug, Clone, Copy)]
+pub struct AvatarPanel<'a> {
+	avatar: &'a AvatarState
+}
+
+impl<'a> AvatarPanel<'a> {
+	/// Creates a diagnostics panel for the given avatar.
+	pub fn new(avatar: &'a AvatarState) -> Self {
+		Self { avatar }
+	}
+}
+
+impl Widget for AvatarPanel<'_> {
+	fn ui(self, ui: &mut Ui) -> Response {
+		ui.vertical(|ui| {
+			ui.add(StatusLights::new(self.avatar));
+			CollapsingHeader::new(format!("Bones ({})", self.avatar.bones().count()))
+				.id_salt("vmc_bones")
+				.default_open(true)
+				.show(ui, |ui| ui.add(BoneList::new(self.avatar)));
+			CollapsingHeader::new(format!("Blend shapes ({})", self.avatar.blendshapes().iter().count()))
+				.id_salt("vmc_blendshapes")
+				.default_open(true)
+				.show(ui, |ui| ui.add(BlendShapeBars::new(self.avatar)));
+		})
+		.response
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		VMCBlendShape, VMCBoneTransform, VMCCalibrationMode, VMCCalibrationState, VMCMessage, VMCModelState, VMCStandardVRM0Bone, VMCState, VMCTime,
+		VMCTrackingState
+	};
+
+	#[test]
+	fn test_avatar_panel() {
+		let mut avatar = AvatarState::new();
+		avatar.extend([
+			VMCMessage::from(VMCState::new(VMCModelState::Loaded)),
+			VMCBoneTransform::new(VMCStandardVRM0Bone::Head, Vec3A::Y, Quat::IDENTITY).into(),
+			VMCBlendShape::new("Joy", 0.5).into(),
+			VMCMessage::ApplyBlendShapes,
+			VMCTime::new(1.0).into()
+		]);
+
+		let ctx = egui::Context::default();
+		let mut shapes = 0;
+		for _ in 0..2 {
+			let output = ctx.run(Default::default(), |ctx| {
+				egui::CentralPanel::default().show(ctx, |ui| ui.add(AvatarPanel::new(&avatar)));
+			});
+			shapes = output.shapes.len();
+		}
+		assert!(shapes > 0);
+	}
+
+	/// Renders a widget twice (so that layout has settled) and returns the shapes painted by the second frame.
+	fn render(add: impl Fn(&mut Ui)) -> Vec<egui::Shape> {
+		fn flatten(shape: egui::Shape, shapes: &mut Vec<egui::Shape>) {
+			match shape {
+				egui::Shape::Vec(nested) => nested.into_iter().for_each(|shape| flatten(shape, shapes)),
+				shape => shapes.push(shape)
+			}
+		}
+
+		let ctx = egui::Context::default();
+		let mut shapes = Vec::new();
+		for _ in 0..2 {
+			let output = ctx.run(Default::default(), |ctx| {
+				egui::CentralPanel::default().show(ctx, |ui| add(ui));
+			});
+			shapes.clear();
+			output.shapes.into_iter().for_each(|clipped| flatten(clipped.shape, &mut shapes));
+		}
+		shapes
+	}
+
+	fn texts(shapes: &[egui::Shape]) -> Vec<String> {
+		shapes
+			.iter()
+			.filter_map(|shape| match shape {
+				egui::Shape::Text(text) => Some(text.galley.text().to_string()),
+				_ => None
+			})
+			.collect()
+	}
+
+	fn lights(shapes: &[egui::Shape]) -> Vec<Color32> {
+		shapes
+			.iter()
+			.filter_map(|shape| match shape {
+				egui::Shape::Circle(circle) => Some(circle.fill),
+				_ => None
+			})
+			.collect()
+	}
+
+	#[test]
+	fn test_status_lights() {
+		let mut avatar = AvatarState::new();
+		let shapes = render(|ui| {
+			ui.add(StatusLights::new(&avatar));
+		});
+		assert_eq!(lights(&shapes), [UNKNOWN; 3]);
+		assert!(texts(&shapes).contains(&"T -".to_string()));
+
+		for (state, expected) in [
+			(VMCState::new(VMCModelState::NotLoaded), [BAD, UNKNOWN, UNKNOWN]),
+			(VMCState::new_calibration(VMCModelState::Loaded, VMCCalibrationMode::Normal, VMCCalibrationState::Calibrating), [GOOD, PENDING, UNKNOWN]),
+			(
+				VMCState::new_tracking(VMCModelState::Loaded, VMCCalibrationMode::Normal, VMCCalibrationState::Uncalibrated, VMCTrackingState::Poor),
+				[GOOD, BAD, PENDING]
+			),
+			(
+				VMCState::new_tracking(VMCModelState::Loaded, VMCCalibrationMode::MixedRealityHand, VMCCalibrationState::Calibrated, VMCTrackingState::Good),
+				[GOOD, GOOD, GOOD]
+			)
+		] {
+			avatar.apply(state.into());
+			let shapes = render(|ui| {
+				ui.add(StatusLights::new(&avatar));
+			});
+			assert_eq!(lights(&shapes), expected);
+		}
+	}
+
+	#[test]
+	fn test_widgets_edge_cases() {
+		// an empty avatar only shows headers
+		let avatar = AvatarState::new();
+		let headers = texts(&render(|ui| {
+			ui.add(AvatarPanel::new(&avatar));
+		}));
+		assert!(headers.contains(&"Bones (0)".to_string()) && headers.contains(&"Blend shapes (0)".to_string()), "{headers:?}");
+
+		// non-finite & out of range values are drawn without panicking; bones & blend shapes are sorted by name
+		let mut avatar = AvatarState::new();
+		avatar.extend([
+			VMCBoneTransform::new(VMCStandardVRM0Bone::Neck, Vec3A::NAN, Quat::from_xyzw(f32::NAN, 0.0, 0.0, 1.0)).into(),
+			VMCBoneTransform::new(VMCStandardVRM0Bone::Head, Vec3A::splat(f32::INFINITY), Quat::IDENTITY).into(),
+			VMCMessage::from(VMCBlendShape::new("Sorrow", f32::NAN)),
+			VMCBlendShape::new("Joy", 2.0).into(),
+			VMCBlendShape::new("Fun", -1.0).into(),
+			VMCBlendShape::new("Angry", 0.0).into(),
+			VMCMessage::ApplyBlendShapes
+		]);
+		let bones = texts(&render(|ui| {
+			ui.add(BoneList::new(&avatar));
+		}));
+		let position = |name: &str| bones.iter().position(|text| text == name).unwrap();
+		assert!(position("Head") < position("Neck"));
+		assert!(bones.iter().any(|text| text.contains("NaN")) && bones.iter().any(|text| text.contains("inf")), "{bones:?}");
+
+		let blendshapes = texts(&render(|ui| {
+			ui.add(BlendShapeBars::new(&avatar));
+		}));
+		let keys = ["Angry", "Fun", "Joy", "Sorrow"];
+		assert_eq!(blendshapes.iter().filter(|text| keys.contains(&text.as_str())).collect::<Vec<_>>(), keys);
+		assert!(["NaN", "2.00", "-1.00"].iter().all(|value| blendshapes.contains(&value.to_string())), "{blendshapes:?}");
+		let blendshapes = texts(&render(|ui| {
+			ui.add(BlendShapeBars::new(&avatar).with_hide_zero(true));
+		}));
+		assert!(!blendshapes.contains(&"Angry".to_string()) && blendshapes.contains(&"Sorrow".to_string()));
+	}
+}
@@ -1,12 +1,15 @@
 use std::{
 	error::Error,
 	fmt,
-	io::{self}
+	io::{self},
+	net::SocketAddr,
+	ops::RangeInclusive
 };
 
 use crate::{OSCType, osc};
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum VMCError {
 	Io(io::Error),
 	Osc(osc::OSCError),
@@ -16,7 +19,14 @@ pub enum VMCError {
 	UnknownModelState(i32),
 	UnknownCalibrationState(i32),
 	UnknownCalibrationMode(i32),
-	UnknownTrackingState(i32)
+	UnknownTrackingState(i32),
+	BroadcastNotEnabled(SocketAddr),
+	InvalidRecording(String),
+	RecordingSizeLimit(u64),
+	InvalidBvh(String),
+	InvalidFaceData(String),
+	InvalidLandmarks(String),
+	PortsInUse(RangeInclusive<u16>)
 }
 
 impl fmt::Display for VMCError {
@@ -30,7 +40,14 @@ impl fmt::Display for VMCError {
 			VMCError::UnknownModelState(state) => write!(f, "unknown model state: {state}"),
 			VMCError::UnknownCalibrationState(state) => write!(f, "unknown calibration state: {state}"),
 			VMCError::UnknownCalibrationMode(mode) => write!(f, "unknown calibration mode: {mode}"),
-			VMCError::UnknownTrackingState(state) => write!(f, "unknown tracking state: {state}")
+			VMCError::UnknownTrackingState(state) => write!(f, "unknown tracking state: {state}"),
+			VMCError::BroadcastNotEnabled(addr) => write!(f, "cannot send to broadcast address {addr} without enabling broadcast on the socket"),
+			VMCError::InvalidRecording(reason) => write!(f, "invalid recording: {reason}"),
+			VMCError::RecordingSizeLimit(limit) => write!(f, "recording would exceed size limit of {limit} bytes"),
+			VMCError::InvalidBvh(reason) => write!(f, "invalid BVH: {reason}"),
+			VMCError::InvalidFaceData(reason) => write!(f, "invalid face tracking data: {reason}"),
+			VMCError::InvalidLandmarks(reason) => write!(f, "invalid landmarks: {reason}"),
+			VMCError::PortsInUse(ports) => write!(f, "all ports from {} to {} are in use", ports.start(), ports.end())
 		}
 	}
 }
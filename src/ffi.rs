@@ -0,0 +1,490 @@
+//! A C API, enabled with the `ffi` feature, so that native plugins (e.g. for Unity or Unreal) and C/C++ tools can use
+//! this implementation of the protocol.
+//!
+//! Build a shared library with:
+//!
+//! ```sh
+//! cargo rustc --release --features ffi --crate-type cdylib
+//! ```
+//!
+//! The matching header is [`include/vmc.h`](https://github.com/pykeio/vmc/blob/main/include/vmc.h).
+//!
+//! All functions are synchronous: each socket drives its own single-threaded runtime, sends complete immediately (UDP
+//! sends don't wait for the receiver), and [`vmc_poll_message`] never blocks. A socket must not be used from multiple
+//! threads at the same time.
+//!
+//! Functions which can fail return `0` on success and `-1` on failure; a description of the last error on the calling
+//! thread can be retrieved with [`vmc_last_error`].
+
+use std::{
+	cell::RefCell,
+	collections::VecDeque,
+	ffi::{CStr, CString, c_char},
+	future::poll_fn,
+	pin::Pin,
+	ptr,
+	task::Poll
+};
+
+use futures_core::Stream;
+use tokio::runtime::Runtime;
+
+use crate::{
+	IntoOSCPacket, Quat, VMCError, VMCMessage, VMCResult, VMCSocket, Vec3A,
+	message::{ApplyBlendShapes, BlendShape, BoneTransform, DeviceType, RootTransform, Time, parse}
+};
+
+thread_local! {
+	static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(error: impl ToString) {
+	// interior NUL bytes can't be represented in a C string
+	let message = CString::new(error.to_string().replace('\0', " ")).unwrap_or_default();
+	LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+fn status(res: VMCResult<()>) -> i32 {
+	match res {
+		Ok(()) => 0,
+		Err(e) => {
+			set_last_error(e);
+			-1
+		}
+	}
+}
+
+/// Returns a description of the last error that occurred on the calling thread, or null if no error has occurred.
+///
+/// The string is valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn vmc_last_error() -> *const c_char {
+	LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+/// A VMC socket, created by [`vmc_socket_bind`] and freed by [`vmc_socket_free`].
+pub struct Socket {
+	runtime: Runtime,
+	socket: VMCSocket,
+	pending: VecDeque<VMCMessage>,
+	/// The name of the last message returned by [`vmc_poll_message`].
+	name: CString
+}
+
+impl Socket {
+	fn send(&self, packet: impl IntoOSCPacket) -> i32 {
+		status(self.runtime.block_on(self.socket.send(packet)))
+	}
+}
+
+unsafe fn str_arg<'a>(s: *const c_char) -> VMCResult<&'a str> {
+	if s.is_null() {
+		return Err(VMCError::Io(std::io::Error::new(std::io::ErrorKind::InvalidInput, "string argument is null")));
+	}
+	CStr::from_ptr(s)
+		.to_str()
+		.map_err(|_| VMCError::Io(std::io::Error::new(std::io::ErrorKind::InvalidInput, "string argument is not valid UTF-8")))
+}
+
+/// Binds a socket to the given address, e.g. `"0.0.0.0:39539"`. Returns null on failure.
+///
+/// # Safety
+///
+/// `addr` must be a valid, NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vmc_socket_bind(addr: *const c_char) -> *mut Socket {
+	let res = (|| {
+		let addr = str_arg(addr)?;
+		let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+		let socket = runtime.block_on(VMCSocket::bind(addr))?;
+		Ok::<_, VMCError>(Socket {
+			runtime,
+			socket,
+			pending: VecDeque::new(),
+			name: CString::default()
+		})
+	})();
+	match res {
+		Ok(socket) => Box::into_raw(Box::new(socket)),
+		Err(e) => {
+			set_last_error(e);
+			ptr::null_mut()
+		}
+	}
+}
+
+/// Connects a socket to the given address, which packets are then sent to.
+///
+/// # Safety
+///
+/// `socket` must be a socket returned by [`vmc_socket_bind`], and `addr` must be a valid, NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vmc_socket_connect(socket: *mut Socket, addr: *const c_char) -> i32 {
+	let socket = &*socket;
+	status(str_arg(addr).and_then(|addr| socket.runtime.block_on(socket.socket.connect(addr))))
+}
+
+/// Closes & frees a socket. Passing null does nothing.
+///
+/// # Safety
+///
+/// `socket` must be null or a socket returned by [`vmc_socket_bind`] which hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn vmc_socket_free(socket: *mut Socket) {
+	if !socket.is_null() {
+		drop(Box::from_raw(socket));
+	}
+}
+
+/// Sends the root transform. `position` points to 3 floats (x, y, z), and `rotation` to a quaternion of 4 floats
+/// (x, y, z, w).
+///
+/// # Safety
+///
+/// `socket` must be a connected socket returned by [`vmc_socket_bind`], and `position` & `rotation` must point to 3 &
+/// 4 floats respectively.
+#[no_mangle]
+pub unsafe extern "C" fn vmc_send_root(socket: *mut Socket, position: *const f32, rotation: *const f32) -> i32 {
+	let (position, rotation) = transform_args(position, rotation);
+	(*socket).send(RootTransform::new(position, rotation))
+}
+
+/// Sends a bone transform. See [`vmc_send_root`] for the layout of `position` & `rotation`.
+///
+/// # Safety
+///
+/// `socket` must be a connected socket returned by [`vmc_socket_bind`], `bone` must be a valid, NUL-terminated string,
+/// and `position` & `rotation` must point to 3 & 4 floats respectively.
+#[no_mangle]
+pub unsafe extern "C" fn vmc_send_bone(socket: *mut Socket, bone: *const c_char, position: *const f32, rotation: *const f32) -> i32 {
+	let (position, rotation) = transform_args(position, rotation);
+	match str_arg(bone) {
+		Ok(bone) => (*socket).send(BoneTransform::new(bone, position, rotation)),
+		Err(e) => status(Err(e))
+	}
+}
+
+unsafe fn transform_args(position: *const f32, rotation: *const f32) -> (Vec3A, Quat) {
+	(Vec3A::from_array(*position.cast::<[f32; 3]>()), Quat::from_array(*rotation.cast::<[f32; 4]>()))
+}
+
+/// Sends a blend shape value. Blend shapes are applied by the receiver once [`vmc_send_apply_blendshapes`] is sent.
+///
+/// # Safety
+///
+/// `socket` must be a connected socket returned by [`vmc_socket_bind`], and `key` must be a valid, NUL-terminated
+/// string.
+#[no_mangle]
+pub unsafe extern "C" fn vmc_send_blendshape(socket: *mut Socket, key: *const c_char, value: f32) -> i32 {
+	match str_arg(key) {
+		Ok(key) => (*socket).send(BlendShape::new(key, value)),
+		Err(e) => status(Err(e))
+	}
+}
+
+/// Applies all blend shape values sent since the last call.
+///
+/// # Safety
+///
+/// `socket` must be a connected socket returned by [`vmc_socket_bind`].
+#[no_mangle]
+pub unsafe extern "C" fn vmc_send_apply_blendshapes(socket: *mut Socket) -> i32 {
+	(*socket).send(ApplyBlendShapes)
+}
+
+/// Sends the time, which marks the end of a frame.
+///
+/// # Safety
+///
+/// `socket` must be a connected socket returned by [`vmc_socket_bind`].
+#[no_mangle]
+pub unsafe extern "C" fn vmc_send_time(socket: *mut Socket, time: f32) -> i32 {
+	(*socket).send(Time::new(time))
+}
+
+/// The kind of a [`Message`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageKind {
+	/// `/VMC/Ext/Root/Pos`; uses `position` & `rotation`.
+	Root = 0,
+	/// `/VMC/Ext/Bone/Pos`; uses `name`, `position`, & `rotation`.
+	Bone = 1,
+	/// `/VMC/Ext/{Hmd,Con,Tra}/Pos`; uses `name` (the joint), `position`, `rotation`, `device`, & `local`.
+	Device = 2,
+	/// `/VMC/Ext/Blend/Val`; uses `name` & `value`.
+	BlendShape = 3,
+	/// `/VMC/Ext/Blend/Apply`.
+	ApplyBlendShapes = 4,
+	/// `/VMC/Ext/OK`; uses `model_state`.
+	State = 5,
+	/// `/VMC/Ext/T`; uses `value`.
+	Time = 6
+}
+
+/// A received message, filled in by [`vmc_poll_message`]. Fields not used by the message's kind are zeroed.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Message {
+	pub kind: MessageKind,
+	/// The bone, blend shape, or device joint name. Valid until the next call to [`vmc_poll_message`] or
+	/// [`vmc_socket_free`].
+	pub name: *const c_char,
+	/// x, y, z
+	pub position: [f32; 3],
+	/// x, y, z, w
+	pub rotation: [f32; 4],
+	/// The blend shape value or time.
+	pub value: f32,
+	/// 0 for an HMD, 1 for a controller, 2 for a tracker.
+	pub device: i32,
+	/// Whether a device's position is in raw device scale rather than avatar scale.
+	pub local: bool,
+	/// 0 if the model is not loaded, 1 if it is.
+	pub model_state: i32
+}
+
+/// Receives the next message without blocking, writing it to `out`.
+///
+/// Returns `1` if a message was received, `0` if none is available, and `-1` on failure. Packets containing unknown
+/// messages fail to parse and are discarded.
+///
+/// # Safety
+///
+/// `socket` must be a socket returned by [`vmc_socket_bind`], and `out` must point to a writable [`Message`].
+#[no_mangle]
+pub unsafe extern "C" fn vmc_poll_message(socket: *mut Socket, out: *mut Message) -> i32 {
+	let socket = &mut *socket;
+	let message = loop {
+		if let Some(message) = socket.pending.pop_front() {
+			break message;
+		}
+		let inner = &mut socket.socket;
+		let next = socket.runtime.block_on(async {
+			match poll_fn(|cx| Poll::Ready(Pin::new(&mut *inner).poll_next(cx))).await {
+				Poll::Pending => {
+					// readiness is only picked up when the runtime polls its I/O driver, which yielding does
+					tokio::task::yield_now().await;
+					poll_fn(|cx| Poll::Ready(Pin::new(&mut *inner).poll_next(cx))).await
+				}
+				ready => ready
+			}
+		});
+		match next {
+			Poll::Ready(Some(Ok((packet, _)))) => match parse(packet) {
+				Ok(messages) => socket.pending.extend(messages),
+				Err(e) => return status(Err(e))
+			},
+			Poll::Ready(Some(Err(e))) => return status(Err(e)),
+			Poll::Ready(None) | Poll::Pending => return 0
+		}
+	};
+
+	let mut result = Message {
+		kind: MessageKind::Time,
+		name: ptr::null(),
+		position: [0.0; 3],
+		rotation: [0.0; 4],
+		value: 0.0,
+		device: 0,
+		local: false,
+		model_state: 0
+	};
+	let mut name = None;
+	match message {
+		VMCMessage::RootTransform(transform) => {
+			result.kind = MessageKind::Root;
+			result.position = transform.position.to_array();
+			result.rotation = transform.rotation.to_array();
+		}
+		VMCMessage::BoneTransform(transform) => {
+			result.kind = MessageKind::Bone;
+			result.position = transform.position.to_array();
+			result.rotation = transform.rotation.to_array();
+			name = Some(transform.bone.to_string());
+		}
+		VMCMessage::DeviceTransform(transform) => {
+			result.kind = MessageKind::Device;
+			result.position = transform.position.to_array();
+			result.rotation = transform.rotation.to_array();
+			result.device = match transform.device {
+				DeviceType::HMD => 0,
+				DeviceType::Controller => 1,
+				DeviceType::Tracker => 2
+			};
+			result.local = transform.local;
+			name = Some(transform.joint);
+		}
+		VMCMessage::BlendShape(blend) => {
+			result.kind = MessageKind::BlendShape;
+			result.value = blend.value;
+			name = Some(blend.key.to_string());
+		}
+		VMCMessage::ApplyBlendShapes => result.kind = MessageKind::ApplyBlendShapes,
+		VMCMessage::State(state) => {
+			result.kind = MessageKind::State;
+			result.model_state = state.model_state as i32;
+		}
+		VMCMessage::Time(time) => result.value = time.0
+	}
+	if let Some(name) = name {
+		socket.name = CString::new(name.replace('\0', "")).unwrap_or_default();
+		result.name = socket.name.as_ptr();
+	}
+	*out = result;
+	1
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{
+		mem::MaybeUninit,
+		thread,
+		time::{Duration, Instant}
+	};
+
+	use super::*;
+	use crate::{
+		message::{DeviceTransform, ModelState, State},
+		osc::OSCMessage
+	};
+
+	fn c(s: &[u8]) -> *const c_char {
+		CStr::from_bytes_with_nul(s).unwrap().as_ptr()
+	}
+
+	unsafe fn poll(socket: *mut Socket) -> Message {
+		let mut message = MaybeUninit::<Message>::uninit();
+		let start = Instant::now();
+		while vmc_poll_message(socket, message.as_mut_ptr()) == 0 {
+			assert!(start.elapsed() < Duration::from_secs(5), "timed out waiting for message");
+			thread::sleep(Duration::from_millis(1));
+		}
+		message.assume_init()
+	}
+
+	unsafe fn last_error() -> String {
+		CStr::from_ptr(vmc_last_error()).to_string_lossy().into_owned()
+	}
+
+	#[test]
+	fn test_ffi_roundtrip() {
+		unsafe {
+			let marionette = vmc_socket_bind(c(b"127.0.0.1:0\0"));
+			assert!(!marionette.is_null());
+			let addr = CString::new((*marionette).socket.local_addr().unwrap().to_string()).unwrap();
+
+			let performer = vmc_socket_bind(c(b"127.0.0.1:0\0"));
+			assert_eq!(vmc_socket_connect(performer, addr.as_ptr()), 0);
+			let rotation = Quat::from_rotation_y(0.5).to_array();
+			assert_eq!(vmc_send_bone(performer, c(b"Head\0"), [0.0, 1.5, 0.0].as_ptr(), rotation.as_ptr()), 0);
+			assert_eq!(vmc_send_bone(performer, ptr::null(), [0.0; 3].as_ptr(), rotation.as_ptr()), -1);
+			assert!(!vmc_last_error().is_null());
+
+			let message = poll(marionette);
+			assert_eq!(message.kind, MessageKind::Bone);
+			assert_eq!(CStr::from_ptr(message.name).to_str(), Ok("Head"));
+			assert_eq!(message.position, [0.0, 1.5, 0.0]);
+			assert_eq!(message.rotation, rotation);
+
+			vmc_socket_free(performer);
+			vmc_socket_free(marionette);
+		}
+	}
+
+	#[test]
+	fn test_ffi_errors() {
+		// errors are per thread
+		thread::spawn(|| assert!(vmc_last_error().is_null())).join().unwrap();
+		unsafe {
+			assert!(vmc_socket_bind(ptr::null()).is_null());
+			assert_eq!(last_error(), "socket error: string argument is null");
+			assert!(vmc_socket_bind(c(b"\xff\0")).is_null());
+			assert_eq!(last_error(), "socket error: string argument is not valid UTF-8");
+			assert!(vmc_socket_bind(c(b"not an address\0")).is_null());
+			vmc_socket_free(ptr::null_mut());
+
+			let socket = vmc_socket_bind(c(b"127.0.0.1:0\0"));
+			assert!(!socket.is_null());
+			// unconnected sockets have nowhere to send to
+			assert_eq!(vmc_send_time(socket, 1.0), -1);
+			assert_eq!(vmc_socket_connect(socket, c(b"127.0.0.1\0")), -1);
+			assert_eq!(vmc_socket_connect(socket, ptr::null()), -1);
+			assert_eq!(vmc_socket_connect(socket, c(b"127.0.0.1:9\0")), 0);
+			assert_eq!(vmc_send_blendshape(socket, c(b"\xff\0"), 1.0), -1);
+			assert_eq!(last_error(), "socket error: string argument is not valid UTF-8");
+			// nothing received
+			let mut message = MaybeUninit::<Message>::uninit();
+			assert_eq!(vmc_poll_message(socket, message.as_mut_ptr()), 0);
+			vmc_socket_free(socket);
+		}
+
+		// interior NUL bytes in errors are replaced
+		set_last_error("a\0b");
+		assert_eq!(unsafe { last_error() }, "a b");
+	}
+
+	#[test]
+	fn test_ffi_poll_messages() {
+		unsafe {
+			let marionette = vmc_socket_bind(c(b"127.0.0.1:0\0"));
+			let addr = CString::new((*marionette).socket.local_addr().unwrap().to_string()).unwrap();
+			let performer = vmc_socket_bind(c(b"127.0.0.1:0\0"));
+			assert_eq!(vmc_socket_connect(performer, addr.as_ptr()), 0);
+
+			// packets with unknown messages fail & are discarded
+			assert_eq!((*performer).send(OSCMessage::new("/Unknown", ())), 0);
+			let mut message = MaybeUninit::<Message>::uninit();
+			let start = Instant::now();
+			while vmc_poll_message(marionette, message.as_mut_ptr()) != -1 {
+				assert!(start.elapsed() < Duration::from_secs(5), "timed out waiting for message");
+				thread::sleep(Duration::from_millis(1));
+			}
+
+			// messages of a bundle are returned one at a time, with unused fields zeroed
+			let rotation = Quat::from_rotation_x(0.5);
+			assert_eq!(
+				(*performer).send(vec![
+					RootTransform::new(Vec3A::X, rotation).into_osc_packet(),
+					DeviceTransform::new(DeviceType::Controller, "LeftHand", Vec3A::Y, rotation, true).into_osc_packet(),
+					BlendShape::new("Joy", 0.5).into_osc_packet(),
+					ApplyBlendShapes.into_osc_packet(),
+					State::new(ModelState::Loaded).into_osc_packet(),
+				]),
+				0
+			);
+			assert_eq!(vmc_send_time(performer, 2.5), 0);
+
+			let root = poll(marionette);
+			assert_eq!(root.kind, MessageKind::Root);
+			assert!(root.name.is_null());
+			assert_eq!((root.position, root.rotation), ([1.0, 0.0, 0.0], rotation.to_array()));
+
+			let device = poll(marionette);
+			assert_eq!(device.kind, MessageKind::Device);
+			assert_eq!(CStr::from_ptr(device.name).to_str(), Ok("LeftHand"));
+			assert_eq!((device.position, device.device, device.local), ([0.0, 1.0, 0.0], 1, true));
+
+			let blendshape = poll(marionette);
+			assert_eq!(blendshape.kind, MessageKind::BlendShape);
+			assert_eq!(CStr::from_ptr(blendshape.name).to_str(), Ok("Joy"));
+			assert_eq!((blendshape.value, blendshape.position, blendshape.rotation), (0.5, [0.0; 3], [0.0; 4]));
+
+			let apply = poll(marionette);
+			assert_eq!(apply.kind, MessageKind::ApplyBlendShapes);
+			assert!(apply.name.is_null());
+
+			let state = poll(marionette);
+			assert_eq!((state.kind, state.model_state), (MessageKind::State, 1));
+
+			let time = poll(marionette);
+			assert_eq!((time.kind, time.value, time.device, time.local), (MessageKind::Time, 2.5, 0, false));
+
+			let mut message = MaybeUninit::<Message>::uninit();
+			assert_eq!(vmc_poll_message(marionette, message.as_mut_ptr()), 0);
+
+			vmc_socket_free(performer);
+			vmc_socket_free(marionette);
+		}
+	}
+}
@@ -0,0 +1,356 @@
+//! Smoothing filters for tracking data.
+//!
+//! Tracking data from webcam-based trackers is often jittery. This module implements the
+//! [One-Euro filter](https://gery.casiez.net/1euro/), which heavily smooths slow movements to remove jitter while
+//! keeping latency low for fast movements.
+//!
+//! A [`FilterBank`] holds a filter per bone, device, and blend shape channel, and can be used either standalone via
+//! [`FilterBank::filter`] or as a stream adapter via [`FilterBank::smooth`].
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+//! use futures_util::StreamExt;
+//! use vmc::{
+//! 	VMCAvatarState, VMCStandardVRM0Bone,
+//! 	filter::{FilterBank, OneEuroConfig}
+//! };
+//!
+//! let mut socket = vmc::marionette!().await?;
+//! let bank = FilterBank::new()
+//! 	.with_rotation_config(OneEuroConfig::new(1.0, 0.5))
+//! 	// the head is more prone to jitter, smooth it more heavily
+//! 	.with_bone_config(VMCStandardVRM0Bone::Head, OneEuroConfig::new(0.3, 0.2));
+//!
+//! let mut avatar = VMCAvatarState::new();
+//! let mut messages = bank.smooth(socket.messages());
+//! while let Some(message) = messages.next().await {
+//! 	let (message, _) = message?;
+//! 	avatar.apply(message);
+//! }
+//! # Ok(()) }) }
+//! ```
+
+use std::{
+	collections::HashMap,
+	pin::Pin,
+	task::{Context, Poll, ready},
+	time::Instant
+};
+
+use futures_core::Stream;
+use glam::{Quat, Vec3A};
+
+use crate::{
+	VMCResult,
+	message::{BlendShape, BoneTransform, DeviceTransform, RootTransform, VMCMessage},
+	name::Name
+};
+
+/// A value which can be smoothed by a [`OneEuroFilter`].
+pub trait Smoothable: Copy {
+	/// Returns the distance between two values, used to estimate the speed of change.
+	fn distance(self, other: Self) -> f32;
+
+	/// Interpolates between two values.
+	fn interpolate(self, other: Self, t: f32) -> Self;
+}
+
+impl Smoothable for f32 {
+	fn distance(self, other: Self) -> f32 {
+		(other - self).abs()
+	}
+
+	fn interpolate(self, other: Self, t: f32) -> Self {
+		self + (other - self) * t
+	}
+}
+
+impl Smoothable for Vec3A {
+	fn distance(self, other: Self) -> f32 {
+		Vec3A::distance(self, other)
+	}
+
+	fn interpolate(self, other: Self, t: f32) -> Self {
+		self.lerp(other, t)
+	}
+}
+
+impl Smoothable for Quat {
+	fn distance(self, other: Self) -> f32 {
+		self.angle_between(other)
+	}
+
+	fn interpolate(self, other: Self, t: f32) -> Self {
+		self.slerp(other, t)
+	}
+}
+
+/// Parameters for a [`OneEuroFilter`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OneEuroConfig {
+	/// The minimum cutoff frequency in Hz. Lower values reduce jitter at low speeds, but increase lag.
+	pub min_cutoff: f32,
+	/// The speed coefficient. Higher values reduce lag during fast movements, but let more jitter through.
+	pub beta: f32,
+	/// The cutoff frequency in Hz used to smooth the estimated speed.
+	pub d_cutoff: f32
+}
+
+impl OneEuroConfig {
+	/// Creates a new config with the given minimum cutoff frequency and speed coefficient.
+	pub const fn new(min_cutoff: f32, beta: f32) -> Self {
+		Self { min_cutoff, beta, d_cutoff: 1.0 }
+	}
+}
+
+impl Default for OneEuroConfig {
+	fn default() -> Self {
+		Self::new(1.0, 0.0)
+	}
+}
+
+/// A [One-Euro filter](https://gery.casiez.net/1euro/) for a single channel.
+#[derive(Debug, Clone)]
+pub struct OneEuroFilter<T> {
+	config: OneEuroConfig,
+	state: Option<(T, f32, Instant)>
+}
+
+impl<T: Smoothable> OneEuroFilter<T> {
+	/// Creates a new filter with the given parameters.
+	pub fn new(config: OneEuroConfig) -> Self {
+		Self { config, state: None }
+	}
+
+	/// Returns the parameters of this filter.
+	pub fn config(&self) -> &OneEuroConfig {
+		&self.config
+	}
+
+	/// Changes the parameters of this filter without resetting it.
+	pub fn set_config(&mut self, config: OneEuroConfig) {
+		self.config = config;
+	}
+
+	/// Filters a value sampled at the given time, returning the smoothed value.
+	pub fn filter(&mut self, value: T, time: Instant) -> T {
+		let Some((prev, prev_speed, prev_time)) = self.state else {
+			self.state = Some((value, 0.0, time));
+			return value;
+		};
+		let dt = time.saturating_duration_since(prev_time).as_secs_f32();
+		if dt <= 0.0 {
+			return prev;
+		}
+
+		let speed = prev.distance(value) / dt;
+		let speed = prev_speed + (speed - prev_speed) * alpha(self.config.d_cutoff, dt);
+		let cutoff = self.config.min_cutoff + self.config.beta * speed;
+		let filtered = prev.interpolate(value, alpha(cutoff, dt));
+		self.state = Some((filtered, speed, time));
+		filtered
+	}
+
+	/// Resets the filter, so that the next value is passed through as-is.
+	pub fn reset(&mut self) {
+		self.state = None;
+	}
+}
+
+fn alpha(cutoff: f32, dt: f32) -> f32 {
+	let tau = 1.0 / (2.0 * std::f32::consts::PI * cutoff);
+	1.0 / (1.0 + tau / dt)
+}
+
+#[derive(Debug, Clone)]
+struct TransformFilter {
+	position: OneEuroFilter<Vec3A>,
+	rotation: OneEuroFilter<Quat>
+}
+
+impl TransformFilter {
+	fn filter(&mut self, position: &mut Vec3A, rotation: &mut Quat, time: Instant) {
+		*position = self.position.filter(*position, time);
+		*rotation = self.rotation.filter(*rotation, time);
+	}
+}
+
+/// A bank of [`OneEuroFilter`]s, with separate filters for each bone, device, and blend shape.
+///
+/// Filters are created on demand as new channels are seen. Parameters can be configured separately for positions,
+/// rotations, and blend shapes, and overridden for individual bones & blend shapes.
+#[derive(Debug, Clone, Default)]
+pub struct FilterBank {
+	position_config: OneEuroConfig,
+	rotation_config: OneEuroConfig,
+	blendshape_config: OneEuroConfig,
+	bone_configs: HashMap<Name, OneEuroConfig>,
+	blendshape_configs: HashMap<Name, OneEuroConfig>,
+	root: Option<TransformFilter>,
+	bones: HashMap<Name, TransformFilter>,
+	devices: HashMap<String, TransformFilter>,
+	blendshapes: HashMap<Name, OneEuroFilter<f32>>
+}
+
+impl FilterBank {
+	/// Creates a new filter bank with the default parameters.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Sets the parameters used to filter the position of all transforms.
+	pub fn with_position_config(mut self, config: OneEuroConfig) -> Self {
+		self.position_config = config;
+		self
+	}
+
+	/// Sets the parameters used to filter the rotation of all transforms.
+	pub fn with_rotation_config(mut self, config: OneEuroConfig) -> Self {
+		self.rotation_config = config;
+		self
+	}
+
+	/// Sets the parameters used to filter all blend shapes.
+	pub fn with_blendshape_config(mut self, config: OneEuroConfig) -> Self {
+		self.blendshape_config = config;
+		self
+	}
+
+	/// Overrides the parameters used to filter both the position & rotation of a specific bone.
+	///
+	/// `bone` can be either a [`StandardVRM0Bone`](crate::VMCStandardVRM0Bone) or the name of a bone.
+	pub fn with_bone_config(mut self, bone: impl Into<Name>, config: OneEuroConfig) -> Self {
+		self.bone_configs.insert(bone.into(), config);
+		self
+	}
+
+	/// Overrides the parameters used to filter a specific blend shape.
+	///
+	/// `key` can be either a [`StandardVRMBlendShape`](crate::VMCStandardVRMBlendShape) or the name of a blend shape.
+	pub fn with_blendshape_key_config(mut self, key: impl Into<Name>, config: OneEuroConfig) -> Self {
+		self.blendshape_configs.insert(key.into(), config);
+		self
+	}
+
+	/// Filters a message received at the given time. Messages other than transforms & blend shapes are returned as-is.
+	pub fn filter(&mut self, mut message: VMCMessage, time: Instant) -> VMCMessage {
+		match &mut message {
+			VMCMessage::RootTransform(RootTransform { position, rotation, .. }) => {
+				let (position_config, rotation_config) = (self.position_config, self.rotation_config);
+				self.root
+					.get_or_insert_with(|| TransformFilter {
+						position: OneEuroFilter::new(position_config),
+						rotation: OneEuroFilter::new(rotation_config)
+					})
+					.filter(position, rotation, time);
+			}
+			VMCMessage::BoneTransform(BoneTransform { bone, position, rotation }) => {
+				let override_config = self.bone_configs.get(bone).copied();
+				let (position_config, rotation_config) = (self.position_config, self.rotation_config);
+				self.bones
+					.entry(bone.clone())
+					.or_insert_with(|| TransformFilter {
+						position: OneEuroFilter::new(override_config.unwrap_or(position_config)),
+						rotation: OneEuroFilter::new(override_config.unwrap_or(rotation_config))
+					})
+					.filter(position, rotation, time);
+			}
+			VMCMessage::DeviceTransform(DeviceTransform { joint, position, rotation, .. }) => {
+				let (position_config, rotation_config) = (self.position_config, self.rotation_config);
+				self.devices
+					.entry(joint.clone())
+					.or_insert_with(|| TransformFilter {
+						position: OneEuroFilter::new(position_config),
+						rotation: OneEuroFilter::new(rotation_config)
+					})
+					.filter(position, rotation, time);
+			}
+			VMCMessage::BlendShape(BlendShape { key, value }) => {
+				let config = self.blendshape_configs.get(key).copied().unwrap_or(self.blendshape_config);
+				*value = self
+					.blendshapes
+					.entry(key.clone())
+					.or_insert_with(|| OneEuroFilter::new(config))
+					.filter(*value, time);
+			}
+			_ => {}
+		}
+		message
+	}
+
+	/// Resets all filters, discarding their state but keeping the configured parameters.
+	pub fn reset(&mut self) {
+		self.root = None;
+		self.bones.clear();
+		self.devices.clear();
+		self.blendshapes.clear();
+	}
+
+	/// Wraps a stream of messages, such as [`VMCSocket::messages`](crate::VMCSocket::messages), filtering each message
+	/// as it is received.
+	pub fn smooth<S>(self, stream: S) -> Smoothed<S> {
+		Smoothed { stream, bank: self }
+	}
+}
+
+/// A stream adapter which filters messages through a [`FilterBank`], created by [`FilterBank::smooth`].
+#[derive(Debug)]
+pub struct Smoothed<S> {
+	stream: S,
+	bank: FilterBank
+}
+
+impl<S> Smoothed<S> {
+	/// Get a reference to the filter bank.
+	pub fn bank(&self) -> &FilterBank {
+		&self.bank
+	}
+
+	/// Get a mutable reference to the filter bank.
+	pub fn bank_mut(&mut self) -> &mut FilterBank {
+		&mut self.bank
+	}
+
+	/// Consumes this adapter, returning the inner stream.
+	pub fn into_inner(self) -> S {
+		self.stream
+	}
+}
+
+impl<S, A> Stream for Smoothed<S>
+where
+	S: Stream<Item = VMCResult<(VMCMessage, A)>> + Unpin,
+	A: Unpin
+{
+	type Item = VMCResult<(VMCMessage, A)>;
+
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		let item = ready!(Pin::new(&mut self.stream).poll_next(cx));
+		let now = Instant::now();
+		Poll::Ready(item.map(|res| res.map(|(message, addr)| (self.bank.filter(message, now), addr))))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::{Duration, Instant};
+
+	use super::{OneEuroConfig, OneEuroFilter};
+
+	#[test]
+	fn test_one_euro_smooths_jitter() {
+		let mut filter = OneEuroFilter::new(OneEuroConfig::new(1.0, 0.0));
+		let start = Instant::now();
+		let mut max_deviation = 0.0f32;
+		for i in 0..120 {
+			let noise = if i % 2 == 0 { 0.1 } else { -0.1 };
+			let value = filter.filter(1.0 + noise, start + Duration::from_millis(i * 16));
+			if i > 60 {
+				max_deviation = max_deviation.max((value - 1.0).abs());
+			}
+		}
+		assert!(max_deviation < 0.05, "{max_deviation}");
+	}
+}
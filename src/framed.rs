@@ -0,0 +1,117 @@
+use std::{
+	io,
+	pin::Pin,
+	task::{Context, Poll, ready}
+};
+
+use tokio::io::{AsyncRead, ReadBuf};
+
+use crate::{
+	OSCPacket, VMCResult,
+	osc::{self, EncodeOptions, OSCResult, StreamDecoder},
+	slip::SlipDecoder
+};
+
+const READ_CHUNK_SIZE: usize = 1024 * 16;
+
+/// Splits a byte stream into OSC packets.
+pub(crate) trait FrameDecoder {
+	fn push(&mut self, data: &[u8]);
+
+	fn next_packet(&mut self) -> OSCResult<Option<OSCPacket>>;
+
+	fn has_partial(&self) -> bool;
+}
+
+impl FrameDecoder for StreamDecoder {
+	fn push(&mut self, data: &[u8]) {
+		StreamDecoder::push(self, data)
+	}
+
+	fn next_packet(&mut self) -> OSCResult<Option<OSCPacket>> {
+		StreamDecoder::next_packet(self)
+	}
+
+	fn has_partial(&self) -> bool {
+		StreamDecoder::has_partial(self)
+	}
+}
+
+impl FrameDecoder for SlipDecoder {
+	fn push(&mut self, data: &[u8]) {
+		SlipDecoder::push(self, data)
+	}
+
+	fn next_packet(&mut self) -> OSCResult<Option<OSCPacket>> {
+		SlipDecoder::next_packet(self)
+	}
+
+	fn has_partial(&self) -> bool {
+		SlipDecoder::has_partial(self)
+	}
+}
+
+/// Sanitizes a packet according to `options` and appends it to `buf` with a big-endian `int32` size prefix.
+pub(crate) fn encode_length_prefixed(packet: &OSCPacket, options: &EncodeOptions, buf: &mut Vec<u8>) -> OSCResult<()> {
+	let packet = options.prepare(packet)?;
+	let start = buf.len();
+	buf.reserve(4 + osc::encoded_size(&packet));
+	buf.extend_from_slice(&[0; 4]);
+	// NOTE: The Output implementation for Vec<u8> can't actually produce an error!
+	let len = osc::encode_into(&packet, buf).expect("Failed to write encoded packet into Vec");
+	buf[start..start + 4].copy_from_slice(&(len as u32).to_be_bytes());
+	Ok(())
+}
+
+/// Reads OSC packets from an [`AsyncRead`] using a [`FrameDecoder`].
+pub(crate) struct FramedRead<R, D> {
+	reader: R,
+	decoder: D,
+	chunk: Box<[u8]>,
+	eof: bool
+}
+
+impl<R: AsyncRead + Unpin, D: FrameDecoder> FramedRead<R, D> {
+	pub fn new(reader: R, decoder: D) -> Self {
+		Self {
+			reader,
+			decoder,
+			chunk: vec![0; READ_CHUNK_SIZE].into_boxed_slice(),
+			eof: false
+		}
+	}
+
+	pub fn get_ref(&self) -> &R {
+		&self.reader
+	}
+
+	pub fn poll_next_packet(&mut self, cx: &mut Context<'_>) -> Poll<Option<VMCResult<OSCPacket>>> {
+		loop {
+			if self.eof {
+				return Poll::Ready(None);
+			}
+
+			match self.decoder.next_packet() {
+				Ok(Some(packet)) => return Poll::Ready(Some(Ok(packet))),
+				Ok(None) => {}
+				Err(e) => return Poll::Ready(Some(Err(e.into())))
+			}
+
+			let mut buf = ReadBuf::new(&mut self.chunk);
+			if let Err(e) = ready!(Pin::new(&mut self.reader).poll_read(cx, &mut buf)) {
+				return Poll::Ready(Some(Err(e.into())));
+			}
+			let filled = buf.filled();
+			if filled.is_empty() {
+				// end of stream
+				self.eof = true;
+				return if self.decoder.has_partial() {
+					Poll::Ready(Some(Err(io::Error::new(io::ErrorKind::UnexpectedEof, "stream closed mid-packet").into())))
+				} else {
+					Poll::Ready(None)
+				};
+			}
+			self.decoder.push(filled);
+		}
+	}
+}
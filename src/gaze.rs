@@ -0,0 +1,242 @@
+//! Conversion of gaze directions into eye blend shapes & eye bone rotations.
+//!
+//! Eye trackers (and face trackers with eye tracking) report where the performer is looking as a direction or as a
+//! point on the screen. [`GazeMapper`] turns either into the [`LookUp`](StandardVRMBlendShape::LookUp),
+//! [`LookDown`](StandardVRMBlendShape::LookDown), [`LookLeft`](StandardVRMBlendShape::LookLeft), &
+//! [`LookRight`](StandardVRMBlendShape::LookRight) blend shapes used by VRM avatars whose eyes are driven by blend
+//! shapes, and/or rotations of the [`LeftEye`](StandardVRM0Bone::LeftEye) & [`RightEye`](StandardVRM0Bone::RightEye)
+//! bones used by avatars with eye bones.
+//!
+//! Directions are relative to the head, in Unity's coordinate system like all VMC data: +Z is forward, +Y is up, and
+//! +X is the avatar's right.
+//!
+//! # Examples
+//!
+//! ```
+//! use vmc::{VMCPose, VMCStandardVRMBlendShape, Vec2, gaze::GazeMapper};
+//!
+//! let mapper = GazeMapper::new().with_yaw_range(20f32.to_radians());
+//! let mut pose = VMCPose::new();
+//! // the performer looks at a point 10cm right of the center of a screen 60cm away
+//! mapper.apply(GazeMapper::screen_direction(Vec2::new(0.1, 0.0), 0.6), &mut pose);
+//! assert!(pose.blendshape(VMCStandardVRMBlendShape::LookRight).unwrap() > 0.4);
+//! assert_eq!(pose.blendshape(VMCStandardVRMBlendShape::LookLeft), Some(0.0));
+//! ```
+
+use glam::{EulerRot, Quat, Vec2, Vec3, Vec3A};
+
+use crate::{VMCPose as Pose, VMCStandardVRM0Bone as StandardVRM0Bone, VMCStandardVRMBlendShape as StandardVRMBlendShape};
+
+/// Converts gaze directions into eye blend shapes & eye bone rotations; see the [module documentation](self).
+///
+/// The range limits set how far the eyes can turn: gaze beyond them is clamped, and the look blend shapes reach `1.0`
+/// at the limits. By default, both blend shapes & eye bones are written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GazeMapper {
+	max_yaw: f32,
+	max_up: f32,
+	max_down: f32,
+	blendshapes: bool,
+	bones: bool
+}
+
+impl Default for GazeMapper {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl GazeMapper {
+	/// Creates a mapper with the default range limits of 30° to either side, 20° up, and 25° down.
+	pub fn new() -> Self {
+		Self {
+			max_yaw: 30f32.to_radians(),
+			max_up: 20f32.to_radians(),
+			max_down: 25f32.to_radians(),
+			blendshapes: true,
+			bones: true
+		}
+	}
+
+	/// Sets how far the eyes can turn to either side, in radians.
+	///
+	/// # Panics
+	///
+	/// Panics if `max_yaw` is not positive.
+	pub fn with_yaw_range(mut self, max_yaw: f32) -> Self {
+		assert!(max_yaw > 0.0, "gaze range must be positive");
+		self.max_yaw = max_yaw;
+		self
+	}
+
+	/// Sets how far the eyes can turn up & down, in radians.
+	///
+	/// # Panics
+	///
+	/// Panics if `max_up` or `max_down` is not positive.
+	pub fn with_pitch_range(mut self, max_up: f32, max_down: f32) -> Self {
+		assert!(max_up > 0.0 && max_down > 0.0, "gaze range must be positive");
+		self.max_up = max_up;
+		self.max_down = max_down;
+		self
+	}
+
+	/// Sets whether [`GazeMapper::apply`] writes the look blend shapes.
+	pub fn with_blendshapes(mut self, blendshapes: bool) -> Self {
+		self.blendshapes = blendshapes;
+		self
+	}
+
+	/// Sets whether [`GazeMapper::apply`] writes the eye bone rotations.
+	pub fn with_bones(mut self, bones: bool) -> Self {
+		self.bones = bones;
+		self
+	}
+
+	/// Returns the direction from the performer's eyes to a point on a screen in front of them.
+	///
+	/// `point` is relative to the center of the screen, with +X to the right and +Y up as seen by the performer, in the
+	/// same units as `distance`, the distance from the eyes to the screen. Since the performer faces the screen,
+	/// looking at its right side makes the avatar look to its right; negate `point.x` to mirror the avatar instead.
+	pub fn screen_direction(point: Vec2, distance: f32) -> Vec3 {
+		Vec3::new(point.x, point.y, distance)
+	}
+
+	/// Returns the yaw (positive to the right) & pitch (positive upwards) of a gaze direction in radians, clamped to
+	/// the range limits. A zero or non-finite direction, e.g. from a tracker which lost the eyes, looks straight ahead.
+	pub fn angles(&self, direction: impl Into<Vec3>) -> (f32, f32) {
+		let direction = direction.into();
+		if direction == Vec3::ZERO || !direction.is_finite() {
+			return (0.0, 0.0);
+		}
+		let yaw = direction.x.atan2(direction.z);
+		let pitch = direction.y.atan2(Vec2::new(direction.x, direction.z).length());
+		(yaw.clamp(-self.max_yaw, self.max_yaw), pitch.clamp(-self.max_down, self.max_up))
+	}
+
+	/// Returns the weights of the look blend shapes for a gaze direction. Opposing blend shapes are never both nonzero.
+	pub fn blendshapes(&self, direction: impl Into<Vec3>) -> [(StandardVRMBlendShape, f32); 4] {
+		let (yaw, pitch) = self.angles(direction);
+		[
+			(StandardVRMBlendShape::LookUp, (pitch / self.max_up).max(0.0)),
+			(StandardVRMBlendShape::LookDown, (-pitch / self.max_down).max(0.0)),
+			(StandardVRMBlendShape::LookLeft, (-yaw / self.max_yaw).max(0.0)),
+			(StandardVRMBlendShape::LookRight, (yaw / self.max_yaw).max(0.0))
+		]
+	}
+
+	/// Returns the local rotation of the eye bones for a gaze direction.
+	pub fn eye_rotation(&self, direction: impl Into<Vec3>) -> Quat {
+		let (yaw, pitch) = self.angles(direction);
+		// a positive rotation around X turns +Z downwards
+		Quat::from_euler(EulerRot::YXZ, yaw, -pitch, 0.0)
+	}
+
+	/// Writes the look blend shapes and/or eye bone rotations for a gaze direction into a pose.
+	///
+	/// Positions of eye bones already in the pose are kept; otherwise, they are set to zero.
+	pub fn apply(&self, direction: impl Into<Vec3>, pose: &mut Pose) {
+		let direction = direction.into();
+		if self.blendshapes {
+			for (key, value) in self.blendshapes(direction) {
+				pose.set_blendshape(key, value);
+			}
+		}
+		if self.bones {
+			let rotation = self.eye_rotation(direction);
+			for bone in [StandardVRM0Bone::LeftEye, StandardVRM0Bone::RightEye] {
+				let position = pose.bone(bone).map_or(Vec3A::ZERO, |transform| transform.position);
+				pose.set_bone(bone, position, rotation);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use approx::assert_relative_eq;
+
+	use super::*;
+
+	#[test]
+	fn test_gaze_mapper() {
+		let mapper = GazeMapper::new()
+			.with_yaw_range(45f32.to_radians())
+			.with_pitch_range(45f32.to_radians(), 30f32.to_radians());
+
+		// up & to the avatar's left
+		let direction = Vec3::new(-1.0, 1.0, 1.0);
+		let (yaw, pitch) = mapper.angles(direction);
+		assert_relative_eq!(yaw, -45f32.to_radians());
+		assert_relative_eq!(pitch, 35.26439f32.to_radians(), epsilon = 1e-5);
+		let weights = mapper.blendshapes(direction);
+		assert_eq!(weights[0].0, StandardVRMBlendShape::LookUp);
+		assert_relative_eq!(weights[0].1, 35.26439 / 45.0, epsilon = 1e-5);
+		assert_eq!(weights[1].1, 0.0);
+		assert_relative_eq!(weights[2].1, 1.0);
+		assert_eq!(weights[3].1, 0.0);
+
+		// the rotated eye looks along the direction
+		let forward = mapper.eye_rotation(direction) * Vec3::Z;
+		assert_relative_eq!(forward, direction.normalize(), epsilon = 1e-5);
+
+		// far down is clamped
+		let (_, pitch) = mapper.angles(Vec3::new(0.0, -1.0, 0.1));
+		assert_relative_eq!(pitch, -30f32.to_radians());
+		assert_eq!(mapper.angles(Vec3::ZERO), (0.0, 0.0));
+
+		let mut pose = Pose::new();
+		pose.set_bone(StandardVRM0Bone::LeftEye, Vec3A::new(0.03, 0.05, 0.02), Quat::IDENTITY);
+		mapper.with_blendshapes(false).apply(direction, &mut pose);
+		assert!(pose.blendshapes.is_empty());
+		assert_eq!(pose.bone(StandardVRM0Bone::LeftEye).unwrap().position, Vec3A::new(0.03, 0.05, 0.02));
+		assert_eq!(pose.bone(StandardVRM0Bone::RightEye).unwrap().position, Vec3A::ZERO);
+	}
+
+	#[test]
+	fn test_gaze_edge_cases() {
+		let mapper = GazeMapper::new();
+		let (max_yaw, max_up, max_down) = (30f32.to_radians(), 20f32.to_radians(), 25f32.to_radians());
+
+		// looking backwards or straight up/down is clamped to the limits instead of wrapping around
+		assert_eq!(mapper.angles(Vec3::new(0.1, 0.0, -1.0)), (max_yaw, 0.0));
+		assert_eq!(mapper.angles(Vec3::new(-0.1, 0.0, -1.0)), (-max_yaw, 0.0));
+		assert_eq!(mapper.angles(Vec3::Y), (0.0, max_up));
+		assert_eq!(mapper.angles(Vec3::NEG_Y), (0.0, -max_down));
+		let weights = mapper.blendshapes(Vec3::new(-1.0, -1.0, -1.0));
+		assert_eq!(weights.map(|(_, weight)| weight), [0.0, 1.0, 1.0, 0.0]);
+
+		// only the direction matters, not its length
+		let ((yaw, pitch), (small_yaw, small_pitch)) = (mapper.angles(Vec3::new(1.0, 2.0, 10.0)), mapper.angles(Vec3::new(1.0, 2.0, 10.0) * 1e-6));
+		assert_relative_eq!(yaw, small_yaw);
+		assert_relative_eq!(pitch, small_pitch);
+
+		// non-finite directions look straight ahead, like a zero direction
+		for direction in [Vec3::NAN, Vec3::new(f32::INFINITY, 0.0, 1.0), Vec3::new(0.0, 0.0, f32::NEG_INFINITY)] {
+			assert_eq!(mapper.angles(direction), (0.0, 0.0));
+			assert_eq!(mapper.eye_rotation(direction), Quat::IDENTITY);
+			assert!(mapper.blendshapes(direction).iter().all(|(_, weight)| *weight == 0.0));
+		}
+
+		let mut pose = Pose::new();
+		mapper.with_bones(false).apply(Vec3::X, &mut pose);
+		assert!(pose.bones.is_empty());
+		assert_eq!(pose.blendshape(StandardVRMBlendShape::LookRight), Some(1.0));
+		// with both disabled, nothing is written
+		let mut pose = Pose::new();
+		mapper.with_bones(false).with_blendshapes(false).apply(Vec3::X, &mut pose);
+		assert_eq!(pose, Pose::new());
+	}
+
+	#[test]
+	#[should_panic = "gaze range must be positive"]
+	fn test_zero_yaw_range() {
+		let _ = GazeMapper::new().with_yaw_range(0.0);
+	}
+
+	#[test]
+	#[should_panic = "gaze range must be positive"]
+	fn test_nan_pitch_range() {
+		let _ = GazeMapper::new().with_pitch_range(f32::NAN, 1.0);
+	}
+}
@@ -0,0 +1,368 @@
+//! Procedural idle motion, to keep avatars alive when tracking drops out.
+//!
+//! [`IdleMotion`] generates subtle breathing of the chest & shoulders and a slow sway of the spine, neck, & head from
+//! layered sine waves of incommensurate frequencies, so the motion never visibly repeats. It can be layered onto a pose
+//! with [`IdleMotion::apply`], or turned into an [`IdleStream`] of messages and merged under live tracking data with a
+//! low-priority [`Merge`](crate::stream::Merge) source: while the tracker sends a bone, its data takes precedence, and
+//! once it stops for longer than the [priority timeout](crate::stream::ConflictPolicy::Priority), the idle motion takes
+//! over.
+//!
+//! Rotations are relative to the rest pose of each bone, in Unity's coordinate system like all VMC data.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+//! use std::time::Duration;
+//!
+//! use futures_util::StreamExt;
+//! use vmc::{
+//! 	idle::IdleMotion,
+//! 	stream::{ConflictPolicy, Merge}
+//! };
+//!
+//! let mut socket = vmc::marionette!().await?;
+//! let mut merged = Merge::new(ConflictPolicy::Priority { timeout: Duration::from_millis(250) })
+//! 	.with_source(socket.messages().boxed(), 1)
+//! 	.with_source(IdleMotion::new().stream(60.0).boxed(), 0)
+//! 	// the idle stream never stops, so use it as the clock
+//! 	.with_clock(1);
+//! while let Some(message) = merged.next().await {
+//! 	let (message, _) = message?;
+//! 	println!("{message:?}");
+//! }
+//! # Ok(()) }) }
+//! ```
+
+use std::{
+	collections::VecDeque,
+	f32::consts::TAU,
+	net::{Ipv4Addr, SocketAddr},
+	pin::Pin,
+	task::{Context, Poll, ready},
+	time::Duration
+};
+
+use futures_core::Stream;
+use glam::{EulerRot, Quat, Vec3A};
+use tokio::time::{Instant, Interval, MissedTickBehavior};
+
+use crate::{VMCMessage, VMCPose as Pose, VMCResult, VMCStandardVRM0Bone as StandardVRM0Bone, VMCTime as Time};
+
+/// Relative frequencies & weights of the sine waves summed into each noise channel. The frequencies are roughly
+/// incommensurate so the sum doesn't repeat.
+const OCTAVES: [(f32, f32); 3] = [(1.0, 0.6), (2.31, 0.3), (5.17, 0.1)];
+
+/// Base frequency of the sway in Hz.
+const SWAY_FREQUENCY: f32 = 0.11;
+
+/// Noise channels, one per axis of each swaying bone.
+#[derive(Debug, Clone, Copy)]
+enum Channel {
+	SpineYaw,
+	SpineRoll,
+	NeckPitch,
+	NeckYaw,
+	HeadPitch,
+	HeadYaw,
+	HeadRoll
+}
+
+const CHANNELS: usize = 7;
+
+/// Generates idle breathing & sway motion; see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdleMotion {
+	amplitude: f32,
+	breathing_rate: f32,
+	phases: [[f32; 3]; CHANNELS]
+}
+
+impl Default for IdleMotion {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl IdleMotion {
+	/// Creates an idle motion generator with an amplitude of `1.0`, a breathing rate of 14 breaths per minute, and a
+	/// seed of `0`.
+	pub fn new() -> Self {
+		Self {
+			amplitude: 1.0,
+			breathing_rate: 14.0,
+			phases: [[0.0; 3]; CHANNELS]
+		}
+		.with_seed(0)
+	}
+
+	/// Scales all motion; `1.0` is subtle, `0.0` disables the motion.
+	pub fn with_amplitude(mut self, amplitude: f32) -> Self {
+		self.amplitude = amplitude;
+		self
+	}
+
+	/// Sets the breathing rate in breaths per minute.
+	pub fn with_breathing_rate(mut self, breaths_per_minute: f32) -> Self {
+		self.breathing_rate = breaths_per_minute;
+		self
+	}
+
+	/// Sets the seed of the sway, so that multiple avatars idling side by side don't move in unison.
+	pub fn with_seed(mut self, seed: u64) -> Self {
+		let mut state = seed;
+		for phases in &mut self.phases {
+			for phase in phases {
+				*phase = (splitmix64(&mut state) >> 40) as f32 / (1u64 << 24) as f32 * TAU;
+			}
+		}
+		self
+	}
+
+	fn noise(&self, channel: Channel, t: f32) -> f32 {
+		let phases = &self.phases[channel as usize];
+		OCTAVES
+			.iter()
+			.zip(phases)
+			.map(|((frequency, weight), phase)| (TAU * SWAY_FREQUENCY * frequency * t + phase).sin() * weight)
+			.sum()
+	}
+
+	/// Returns the idle rotation of each animated bone at time `t` since the start of the motion.
+	pub fn rotations(&self, t: Duration) -> [(StandardVRM0Bone, Quat); 7] {
+		let t = t.as_secs_f32();
+		let scale = self.amplitude.to_radians();
+		// inhaling is quicker than exhaling; raising the sine to a power keeps the lungs empty for longer
+		let breath = ((TAU * self.breathing_rate / 60.0 * t).sin() * 0.5 + 0.5).powf(1.5) * scale;
+		let noise = |channel| self.noise(channel, t) * scale;
+		let euler = |pitch: f32, yaw: f32, roll: f32| Quat::from_euler(EulerRot::YXZ, yaw, pitch, roll);
+
+		// the chest leans back slightly & the shoulders rise as the lungs fill
+		[
+			(StandardVRM0Bone::Spine, euler(0.0, noise(Channel::SpineYaw) * 1.5, noise(Channel::SpineRoll) * 1.0)),
+			(StandardVRM0Bone::Chest, euler(-breath * 1.2, 0.0, 0.0)),
+			(StandardVRM0Bone::UpperChest, euler(-breath * 0.6, 0.0, 0.0)),
+			(StandardVRM0Bone::LeftShoulder, euler(0.0, 0.0, -breath * 1.5)),
+			(StandardVRM0Bone::RightShoulder, euler(0.0, 0.0, breath * 1.5)),
+			(StandardVRM0Bone::Neck, euler(noise(Channel::NeckPitch) * 1.0 + breath * 0.6, noise(Channel::NeckYaw) * 1.5, 0.0)),
+			(StandardVRM0Bone::Head, euler(noise(Channel::HeadPitch) * 2.0 + breath * 0.6, noise(Channel::HeadYaw) * 3.0, noise(Channel::HeadRoll) * 1.5))
+		]
+	}
+
+	/// Layers the idle motion at time `t` onto a pose, rotating each animated bone from its current rotation.
+	///
+	/// Bones missing from the pose are added at the rest pose; positions of bones already in the pose are kept.
+	pub fn apply(&self, t: Duration, pose: &mut Pose) {
+		for (bone, rotation) in self.rotations(t) {
+			let (position, base) = pose
+				.bone(bone)
+				.map_or((Vec3A::ZERO, Quat::IDENTITY), |transform| (transform.position, transform.rotation));
+			pose.set_bone(bone, position, base * rotation);
+		}
+	}
+
+	/// Returns a pose containing only the idle motion at time `t`.
+	pub fn pose(&self, t: Duration) -> Pose {
+		let mut pose = Pose::new();
+		self.apply(t, &mut pose);
+		pose.time = Some(Time::new(t.as_secs_f32()));
+		pose
+	}
+
+	/// Turns this generator into a stream of messages emitting a pose at `rate` Hz. See [`IdleStream`].
+	///
+	/// # Panics
+	///
+	/// Panics if `rate` is not positive & finite.
+	pub fn stream(self, rate: f64) -> IdleStream {
+		IdleStream::new(self, rate)
+	}
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+	*state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+	let mut z = *state;
+	z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+	z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+	z ^ (z >> 31)
+}
+
+/// A never-ending stream of idle motion messages, created by [`IdleMotion::stream`].
+///
+/// Each tick yields the bone transforms of [`IdleMotion::pose`] followed by a [`Time`] message with the
+/// time since the stream was first polled, in the same item type as [`Messages`](crate::VMCMessages) so it can be
+/// [merged](crate::stream::Merge) with live data. Messages are paired with the [address](IdleStream::with_addr)
+/// `0.0.0.0:0` by default. If ticks are missed because the stream isn't polled in time, they are skipped rather than
+/// emitted in a burst.
+#[derive(Debug)]
+pub struct IdleStream {
+	motion: IdleMotion,
+	period: Duration,
+	addr: SocketAddr,
+	interval: Option<Interval>,
+	start: Option<Instant>,
+	pending: VecDeque<VMCMessage>
+}
+
+impl IdleStream {
+	/// Creates a stream emitting the idle motion at `rate` Hz.
+	///
+	/// # Panics
+	///
+	/// Panics if `rate` is not positive & finite.
+	pub fn new(motion: IdleMotion, rate: f64) -> Self {
+		assert!(rate > 0.0 && rate.is_finite(), "idle rate must be positive");
+		Self {
+			motion,
+			period: Duration::from_secs_f64(1.0 / rate),
+			addr: (Ipv4Addr::UNSPECIFIED, 0).into(),
+			interval: None,
+			start: None,
+			pending: VecDeque::new()
+		}
+	}
+
+	/// Sets the address messages are paired with.
+	pub fn with_addr(mut self, addr: SocketAddr) -> Self {
+		self.addr = addr;
+		self
+	}
+
+	/// Get a reference to the idle motion generator.
+	pub fn motion(&self) -> &IdleMotion {
+		&self.motion
+	}
+
+	/// Get a mutable reference to the idle motion generator, e.g. to fade the motion in or out.
+	pub fn motion_mut(&mut self) -> &mut IdleMotion {
+		&mut self.motion
+	}
+}
+
+impl Stream for IdleStream {
+	type Item = VMCResult<(VMCMessage, SocketAddr)>;
+
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		if let Some(message) = self.pending.pop_front() {
+			return Poll::Ready(Some(Ok((message, self.addr))));
+		}
+
+		let period = self.period;
+		let interval = self.interval.get_or_insert_with(|| {
+			let mut interval = tokio::time::interval(period);
+			interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+			interval
+		});
+		let now = ready!(interval.poll_tick(cx));
+		let start = *self.start.get_or_insert(now);
+		let messages = self.motion.pose(now.duration_since(start)).to_messages();
+		self.pending.extend(messages);
+		let message = self.pending.pop_front().expect("pose has a time message");
+		Poll::Ready(Some(Ok((message, self.addr))))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use futures_util::StreamExt;
+
+	use super::*;
+
+	#[tokio::test]
+	async fn test_idle_motion() {
+		let motion = IdleMotion::new();
+		let t = Duration::from_millis(1234);
+		assert_eq!(motion.rotations(t), IdleMotion::new().rotations(t));
+		assert_ne!(motion.rotations(t), IdleMotion::new().with_seed(1).rotations(t));
+		assert!(
+			IdleMotion::new()
+				.with_amplitude(0.0)
+				.rotations(t)
+				.iter()
+				.all(|(_, rotation)| *rotation == Quat::IDENTITY)
+		);
+
+		// the motion stays subtle
+		for ms in (0..20_000).step_by(50) {
+			for (_, rotation) in motion.rotations(Duration::from_millis(ms)) {
+				assert!(rotation.angle_between(Quat::IDENTITY) < 6f32.to_radians());
+			}
+		}
+
+		let mut pose = Pose::new();
+		pose.set_bone(StandardVRM0Bone::Head, Vec3A::Y, Quat::from_rotation_y(1.0));
+		motion.apply(t, &mut pose);
+		let head = pose.bone(StandardVRM0Bone::Head).unwrap();
+		assert_eq!(head.position, Vec3A::Y);
+		assert!(head.rotation.angle_between(Quat::from_rotation_y(1.0)) < 6f32.to_radians());
+		assert_eq!(pose.bones.len(), 7);
+
+		let messages: Vec<_> = motion.stream(1000.0).take(16).collect().await;
+		assert!(matches!(messages[7], Ok((VMCMessage::Time(_), _))));
+		assert!(matches!(messages[15], Ok((VMCMessage::Time(_), addr)) if addr.port() == 0));
+	}
+
+	#[test]
+	fn test_idle_edge_cases() {
+		// long sessions stay well-behaved despite the precision of f32 time
+		let motion = IdleMotion::new().with_seed(u64::MAX);
+		for t in [Duration::ZERO, Duration::from_secs(10 * 3600), Duration::from_secs(u32::MAX as u64)] {
+			for (_, rotation) in motion.rotations(t) {
+				assert!(rotation.is_finite() && rotation.is_normalized());
+				assert!(rotation.angle_between(Quat::IDENTITY) < 6f32.to_radians());
+			}
+		}
+
+		// no breathing leaves the chest still, but the sway continues
+		let still = IdleMotion::new().with_breathing_rate(0.0);
+		let rotations = |t| still.rotations(Duration::from_millis(t));
+		assert_eq!(rotations(0)[1], rotations(2500)[1]);
+		assert_ne!(rotations(0)[6], rotations(2500)[6]);
+
+		let pose = IdleMotion::new().pose(Duration::from_millis(1500));
+		assert_eq!(pose.time, Some(Time::new(1.5)));
+		assert!(pose.blendshapes.is_empty() && pose.root.is_none());
+	}
+
+	#[test]
+	#[should_panic = "idle rate must be positive"]
+	fn test_zero_rate() {
+		let _ = IdleMotion::new().stream(0.0);
+	}
+
+	#[test]
+	#[should_panic = "idle rate must be positive"]
+	fn test_infinite_rate() {
+		let _ = IdleMotion::new().stream(f64::INFINITY);
+	}
+
+	#[tokio::test]
+	async fn test_idle_stream_skips_missed_ticks() {
+		async fn next_time(stream: &mut IdleStream) -> f32 {
+			loop {
+				if let (VMCMessage::Time(Time(time)), addr) = stream.next().await.unwrap().unwrap() {
+					assert_eq!(addr, SocketAddr::from(([127, 0, 0, 1], 39539)));
+					return time;
+				}
+			}
+		}
+
+		let mut stream = IdleMotion::new().stream(1000.0).with_addr(([127, 0, 0, 1], 39539).into());
+		assert_eq!(next_time(&mut stream).await, 0.0);
+		// stop polling for a while; missed ticks aren't emitted in a burst, so time jumps ahead within a couple of frames
+		tokio::time::sleep(Duration::from_millis(50)).await;
+		let mut time = 0.0;
+		for _ in 0..3 {
+			time = next_time(&mut stream).await;
+		}
+		assert!(time >= 0.045, "{time}");
+
+		// the motion can be changed while streaming, e.g. to fade it out
+		*stream.motion_mut() = stream.motion().clone().with_amplitude(0.0);
+		next_time(&mut stream).await;
+		for _ in 0..7 {
+			let (message, _) = stream.next().await.unwrap().unwrap();
+			assert!(matches!(message, VMCMessage::BoneTransform(transform) if transform.rotation == Quat::IDENTITY));
+		}
+	}
+}
@@ -0,0 +1,294 @@
+//! A receiver for the [iFacialMocap](https://www.ifacialmocap.com/) & [Facemotion3d](https://www.facemotion3d.info/)
+//! face tracking protocol.
+//!
+//! These iOS apps stream ARKit face tracking over UDP in a plain-text format rather than OSC. [`FaceData`] parses it
+//! and converts it into VMC messages: ARKit blend shapes become [`BlendShape`](crate::VMCBlendShape)s using the
+//! "perfect sync" names VMC applications expect (e.g. `eyeBlink_L` becomes `EyeBlinkLeft`), and head & eye rotations
+//! become [`BoneTransform`](crate::VMCBoneTransform)s, so that iPhone face tracking can be fed through the same
+//! pipeline as any other performer, or re-broadcast as VMC.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+//! use vmc::ifacialmocap::Receiver;
+//!
+//! let mut receiver = Receiver::bind(("0.0.0.0", vmc::ifacialmocap::PORT)).await?;
+//! receiver.request("192.168.1.42".parse().unwrap()).await?;
+//!
+//! let socket = vmc::performer!("127.0.0.1:39539").await?;
+//! loop {
+//! 	let (face, _) = receiver.recv().await?;
+//! 	socket.send_batch(face.to_messages()).await?;
+//! }
+//! # Ok(()) }) }
+//! ```
+
+use std::{
+	io,
+	net::{IpAddr, SocketAddr}
+};
+
+use glam::{EulerRot, Quat, Vec3A};
+use tokio::net::{ToSocketAddrs, UdpSocket};
+
+use crate::{VMCBlendShape, VMCBoneTransform, VMCError, VMCMessage, VMCResult, VMCStandardVRM0Bone as StandardVRM0Bone};
+
+/// The port iFacialMocap sends to and listens for requests on.
+pub const PORT: u16 = 49983;
+/// The port Facemotion3d sends to and listens for requests on.
+pub const FACEMOTION3D_PORT: u16 = 49993;
+
+/// The message which asks iFacialMocap to start streaming to the sender's address.
+pub const REQUEST: &[u8] = b"iFacialMocap_sahuasouryya9218sauhuiayeta91555dy3719";
+/// The message which asks Facemotion3d to start streaming to the sender's address.
+pub const FACEMOTION3D_REQUEST: &[u8] = b"FACEMOTION3D_OtherStreaming";
+
+/// A single frame of face tracking data.
+///
+/// Rotations are Euler angles in degrees, in the Unity convention used by both apps (applied in Z, X, Y order).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FaceData {
+	/// ARKit blend shape names (e.g. `jawOpen`, `eyeBlink_L`) and their values, from 0 to 1.
+	pub blendshapes: Vec<(String, f32)>,
+	/// The rotation of the head.
+	pub head_rotation: Option<Vec3A>,
+	/// The position of the head relative to the phone's camera.
+	pub head_position: Option<Vec3A>,
+	/// The rotation of the left eye.
+	pub left_eye: Option<Vec3A>,
+	/// The rotation of the right eye.
+	pub right_eye: Option<Vec3A>
+}
+
+impl FaceData {
+	/// Parses a packet of face tracking data.
+	///
+	/// Both the original (`name-value`) & v2 (`name&value`) blend shape formats are accepted. Unknown entries are
+	/// skipped.
+	pub fn parse(data: &str) -> VMCResult<Self> {
+		let mut face = FaceData::default();
+		for entry in data.trim().split('|').filter(|entry| !entry.is_empty()) {
+			// Facemotion3d separates blend shapes from transforms with `=`
+			let entry = entry.trim_start_matches('=');
+			if let Some((name, values)) = entry.split_once('#') {
+				let values = parse_floats(values)?;
+				match (name, values.as_slice()) {
+					("head", &[rx, ry, rz, px, py, pz]) => {
+						face.head_rotation = Some(Vec3A::new(rx, ry, rz));
+						face.head_position = Some(Vec3A::new(px, py, pz));
+					}
+					("leftEye", &[x, y, z]) => face.left_eye = Some(Vec3A::new(x, y, z)),
+					("rightEye", &[x, y, z]) => face.right_eye = Some(Vec3A::new(x, y, z)),
+					("head" | "leftEye" | "rightEye", _) => return Err(VMCError::InvalidFaceData(format!("wrong number of values for {name}"))),
+					_ => {}
+				}
+			} else if let Some((name, value)) = entry.rsplit_once('&').or_else(|| entry.rsplit_once('-')) {
+				let value: f32 = value
+					.parse()
+					.map_err(|_| VMCError::InvalidFaceData(format!("invalid value for blend shape {name}: {value}")))?;
+				face.blendshapes.push((name.to_string(), value / 100.0));
+			}
+		}
+		Ok(face)
+	}
+
+	/// Converts the face data into VMC messages.
+	///
+	/// Blend shapes are renamed with [`perfect_sync_name`] and followed by an
+	/// [`ApplyBlendShapes`](crate::VMCApplyBlendShapes) message. The head & eye rotations are sent as transforms of the
+	/// [`Head`](StandardVRM0Bone::Head), [`LeftEye`](StandardVRM0Bone::LeftEye), &
+	/// [`RightEye`](StandardVRM0Bone::RightEye) bones; the head position is sent with the head's rotation.
+	pub fn to_messages(&self) -> Vec<VMCMessage> {
+		let mut messages = Vec::with_capacity(self.blendshapes.len() + 4);
+		for (name, value) in &self.blendshapes {
+			messages.push(VMCMessage::BlendShape(VMCBlendShape::new(perfect_sync_name(name).as_str(), *value)));
+		}
+		if !self.blendshapes.is_empty() {
+			messages.push(VMCMessage::ApplyBlendShapes);
+		}
+		let bones = [
+			(StandardVRM0Bone::Head, self.head_rotation, self.head_position),
+			(StandardVRM0Bone::LeftEye, self.left_eye, None),
+			(StandardVRM0Bone::RightEye, self.right_eye, None)
+		];
+		for (bone, rotation, position) in bones {
+			if let Some(rotation) = rotation {
+				messages.push(VMCMessage::BoneTransform(VMCBoneTransform::new(bone, position.unwrap_or(Vec3A::ZERO), euler_to_quat(rotation))));
+			}
+		}
+		messages
+	}
+}
+
+fn parse_floats(values: &str) -> VMCResult<Vec<f32>> {
+	values
+		.split(',')
+		.map(|value| {
+			value
+				.trim()
+				.parse()
+				.map_err(|_| VMCError::InvalidFaceData(format!("invalid number: {value}")))
+		})
+		.collect()
+}
+
+fn euler_to_quat(degrees: Vec3A) -> Quat {
+	Quat::from_euler(EulerRot::YXZ, degrees.y.to_radians(), degrees.x.to_radians(), degrees.z.to_radians())
+}
+
+/// Converts an ARKit blend shape name, as sent by iFacialMocap, into the "perfect sync" name used by VMC applications.
+///
+/// The first letter is capitalized and the `_L`/`_R` suffix is spelled out, so `eyeBlink_L` becomes `EyeBlinkLeft` and
+/// `jawOpen` becomes `JawOpen`.
+///
+/// ```
+/// assert_eq!(vmc::ifacialmocap::perfect_sync_name("mouthSmile_R"), "MouthSmileRight");
+/// ```
+pub fn perfect_sync_name(name: &str) -> String {
+	let (base, side) = if let Some(base) = name.strip_suffix("_L") {
+		(base, "Left")
+	} else if let Some(base) = name.strip_suffix("_R") {
+		(base, "Right")
+	} else {
+		(name, "")
+	};
+	let mut chars = base.chars();
+	let mut out = String::with_capacity(name.len() + 4);
+	if let Some(first) = chars.next() {
+		out.extend(first.to_uppercase());
+	}
+	out.push_str(chars.as_str());
+	out.push_str(side);
+	out
+}
+
+/// Receives face tracking data from iFacialMocap or Facemotion3d.
+///
+/// The apps only stream to a computer after receiving a [request](Receiver::request), and they reply to the port the
+/// request came from, so the receiver should be bound to [`PORT`] (or [`FACEMOTION3D_PORT`]).
+#[derive(Debug)]
+pub struct Receiver {
+	socket: UdpSocket,
+	buf: Vec<u8>
+}
+
+impl Receiver {
+	/// Binds a receiver to the given address.
+	pub async fn bind<A: ToSocketAddrs>(addr: A) -> VMCResult<Self> {
+		Ok(Self::new(UdpSocket::bind(addr).await?))
+	}
+
+	/// Creates a receiver from an existing socket.
+	pub fn new(socket: UdpSocket) -> Self {
+		Self { socket, buf: vec![0; 8192] }
+	}
+
+	/// Asks the iFacialMocap app on the given phone to start streaming to this receiver.
+	pub async fn request(&self, phone: IpAddr) -> VMCResult<()> {
+		self.socket.send_to(REQUEST, (phone, PORT)).await?;
+		Ok(())
+	}
+
+	/// Asks the Facemotion3d app on the given phone to start streaming to this receiver.
+	pub async fn request_facemotion3d(&self, phone: IpAddr) -> VMCResult<()> {
+		self.socket.send_to(FACEMOTION3D_REQUEST, (phone, FACEMOTION3D_PORT)).await?;
+		Ok(())
+	}
+
+	/// Receives the next frame of face tracking data, along with the address of the phone which sent it.
+	pub async fn recv(&mut self) -> VMCResult<(FaceData, SocketAddr)> {
+		let (len, peer) = self.socket.recv_from(&mut self.buf).await?;
+		let data = std::str::from_utf8(&self.buf[..len]).map_err(|e| VMCError::Io(io::Error::new(io::ErrorKind::InvalidData, e)))?;
+		Ok((FaceData::parse(data)?, peer))
+	}
+
+	/// Returns the local address that the receiver is bound to.
+	pub fn local_addr(&self) -> VMCResult<SocketAddr> {
+		Ok(self.socket.local_addr()?)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse_face_data() -> VMCResult<()> {
+		let face = FaceData::parse("mouthSmile_R-50|eyeBlink_L-100|jawOpen-0|head#-10.0,20.0,0.0,0.01,-0.02,-0.5|rightEye#5.0,-2.5,0.0|leftEye#5.0,2.5,0.0|")?;
+		assert_eq!(face.blendshapes, vec![("mouthSmile_R".to_string(), 0.5), ("eyeBlink_L".to_string(), 1.0), ("jawOpen".to_string(), 0.0)]);
+		assert_eq!(face.head_position, Some(Vec3A::new(0.01, -0.02, -0.5)));
+		assert_eq!(FaceData::parse("mouthSmile_R&50|=head#-10.0,20.0,0.0,0.01,-0.02,-0.5")?.head_rotation, face.head_rotation);
+		assert!(FaceData::parse("leftEye#1.0,2.0").is_err());
+
+		let messages = face.to_messages();
+		assert_eq!(messages.len(), 7);
+		assert!(matches!(&messages[1], VMCMessage::BlendShape(shape) if shape.key.as_str() == "EyeBlinkLeft" && shape.value == 1.0));
+		assert!(matches!(messages[3], VMCMessage::ApplyBlendShapes));
+		let VMCMessage::BoneTransform(head) = &messages[4] else {
+			panic!("expected head transform")
+		};
+		assert_eq!(head.bone.as_str(), "Head");
+		assert!(
+			head.rotation
+				.abs_diff_eq(Quat::from_rotation_y(20f32.to_radians()) * Quat::from_rotation_x(-10f32.to_radians()), 1e-6)
+		);
+		Ok(())
+	}
+
+	#[test]
+	fn test_invalid_face_data() {
+		let message = |data: &str| match FaceData::parse(data) {
+			Err(VMCError::InvalidFaceData(message)) => message,
+			result => panic!("expected InvalidFaceData for {data:?}, got {result:?}")
+		};
+		assert_eq!(message("jawOpen-50|head#1.0,2.0,3.0"), "wrong number of values for head");
+		assert_eq!(message("rightEye#1.0,2.0,3.0,4.0"), "wrong number of values for rightEye");
+		assert_eq!(message("leftEye#1.0,up,3.0"), "invalid number: up");
+		assert_eq!(message("jawOpen&wide"), "invalid value for blend shape jawOpen: wide");
+		// a packet cut off mid-entry
+		assert_eq!(message("mouthSmile_R-50|jawOpen-"), "invalid value for blend shape jawOpen: ");
+		assert_eq!(message("head#-10.0,20.0,0.0,0.01,"), "invalid number: ");
+	}
+
+	#[test]
+	fn test_parse_edge_cases() -> VMCResult<()> {
+		// an empty packet, e.g. before the face is found
+		for data in ["", "|", " \n", "=|"] {
+			let face = FaceData::parse(data)?;
+			assert_eq!(face, FaceData::default());
+			assert!(face.to_messages().is_empty());
+		}
+
+		// unknown transforms and entries without a value are skipped
+		let face = FaceData::parse("hapihapi#1.0,2.0|garbage|jawOpen-25|rightEye#0,0,0")?;
+		assert_eq!(face.blendshapes, vec![("jawOpen".to_string(), 0.25)]);
+		assert_eq!(face.right_eye, Some(Vec3A::ZERO));
+		assert_eq!(face.head_rotation, None);
+		// without blend shapes, no ApplyBlendShapes message is sent
+		let messages = FaceData::parse("rightEye#0,0,0")?.to_messages();
+		assert!(matches!(&messages[..], [VMCMessage::BoneTransform(eye)] if eye.bone.as_str() == "RightEye"));
+
+		assert_eq!(perfect_sync_name(""), "");
+		assert_eq!(perfect_sync_name("_L"), "Left");
+		assert_eq!(perfect_sync_name("tongueOut"), "TongueOut");
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_recv_malformed() -> VMCResult<()> {
+		let mut receiver = Receiver::bind("127.0.0.1:0").await?;
+		let phone = UdpSocket::bind("127.0.0.1:0").await?;
+		phone.send_to(&[0xff, 0xfe, b'|'], receiver.local_addr()?).await?;
+		phone.send_to(b"head#1.0", receiver.local_addr()?).await?;
+		phone.send_to(b"jawOpen-50|", receiver.local_addr()?).await?;
+
+		assert!(matches!(receiver.recv().await, Err(VMCError::Io(err)) if err.kind() == io::ErrorKind::InvalidData));
+		assert!(matches!(receiver.recv().await, Err(VMCError::InvalidFaceData(_))));
+		// malformed packets don't affect the next one
+		let (face, peer) = receiver.recv().await?;
+		assert_eq!(peer, phone.local_addr()?);
+		assert_eq!(face.blendshapes, vec![("jawOpen".to_string(), 0.5)]);
+		Ok(())
+	}
+}
@@ -0,0 +1,317 @@
+//! Inverse kinematics, for turning tracker positions into bone rotations.
+//!
+//! [`TwoBoneIk`] analytically solves a chain of two bones, such as an arm (upper arm, lower arm, hand) or a leg (upper
+//! leg, lower leg, foot), so that its end reaches a target like the position of a controller or tracker from a
+//! [`DeviceTransform`](crate::VMCDeviceTransform). Since a two-bone chain can reach a target in infinitely many ways, a
+//! pole position chooses the direction the elbow or knee points towards.
+//!
+//! [`BodyIk`] builds on it to produce a complete [`VMCPose`](crate::VMCPose) from 3-point (HMD & controllers) or
+//! 6-point (with waist & feet trackers) tracking, estimating the hips & spine from the head when they aren't tracked.
+//!
+//! Chains are described by their rest pose. VMC bone rotations are relative to the rest pose of a normalized VRM
+//! humanoid, in which every bone has an identity rotation, so the rest offsets of all bones are in the same model
+//! space: +X is the avatar's right, +Y is up, and +Z is forward, as in Unity.
+//!
+//! # Examples
+//!
+//! ```
+//! use vmc::{
+//! 	Quat, VMCPose, VMCStandardVRM0Bone, Vec3A,
+//! 	ik::{Limb, TwoBoneIk}
+//! };
+//!
+//! // a left arm pointing along -X in the T-pose
+//! let arm = TwoBoneIk::for_limb(Limb::LeftArm, Vec3A::new(-0.28, 0.0, 0.0), Vec3A::new(-0.25, 0.0, 0.0));
+//! let shoulder = Vec3A::new(-0.18, 1.4, 0.0);
+//! let controller = Vec3A::new(-0.3, 1.2, 0.35);
+//! let solution = arm.solve(shoulder, Quat::IDENTITY, controller, None);
+//! assert!(solution.reached);
+//!
+//! let mut pose = VMCPose::new();
+//! solution.apply(Limb::LeftArm, &mut pose);
+//! assert!(pose.bone(VMCStandardVRM0Bone::LeftLowerArm).is_some());
+//! ```
+
+use glam::{Mat3A, Quat, Vec3A};
+
+use crate::{VMCPose as Pose, VMCStandardVRM0Bone as StandardVRM0Bone};
+
+mod body;
+
+pub use self::body::{BodyIk, BodyTargets, Skeleton, Target, TrackerRole};
+
+/// A limb which can be solved by [`TwoBoneIk`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Limb {
+	LeftArm,
+	RightArm,
+	LeftLeg,
+	RightLeg
+}
+
+impl Limb {
+	/// Returns the upper, lower, and end bones of the limb, e.g. the upper arm, lower arm, and hand.
+	pub fn bones(self) -> [StandardVRM0Bone; 3] {
+		match self {
+			Limb::LeftArm => [StandardVRM0Bone::LeftUpperArm, StandardVRM0Bone::LeftLowerArm, StandardVRM0Bone::LeftHand],
+			Limb::RightArm => [StandardVRM0Bone::RightUpperArm, StandardVRM0Bone::RightLowerArm, StandardVRM0Bone::RightHand],
+			Limb::LeftLeg => [StandardVRM0Bone::LeftUpperLeg, StandardVRM0Bone::LeftLowerLeg, StandardVRM0Bone::LeftFoot],
+			Limb::RightLeg => [StandardVRM0Bone::RightUpperLeg, StandardVRM0Bone::RightLowerLeg, StandardVRM0Bone::RightFoot]
+		}
+	}
+
+	/// Returns the direction the middle joint of the limb moves in when the limb bends from its rest pose: backwards
+	/// for elbows, and forwards for knees.
+	pub fn bend(self) -> Vec3A {
+		match self {
+			Limb::LeftArm | Limb::RightArm => Vec3A::NEG_Z,
+			Limb::LeftLeg | Limb::RightLeg => Vec3A::Z
+		}
+	}
+}
+
+/// An analytic two-bone IK solver; see the [module documentation](self).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TwoBoneIk {
+	upper: Vec3A,
+	lower: Vec3A,
+	bend: Vec3A
+}
+
+impl TwoBoneIk {
+	/// Creates a solver for a chain with the given rest offsets from the root joint to the middle joint (`upper`) and
+	/// from the middle joint to the end (`lower`), in model space.
+	///
+	/// The chain bends in the direction of `bend` when no pole is given; see [`Limb::bend`].
+	///
+	/// # Panics
+	///
+	/// Panics if either offset has zero length.
+	pub fn new(upper: impl Into<Vec3A>, lower: impl Into<Vec3A>, bend: impl Into<Vec3A>) -> Self {
+		let (upper, lower) = (upper.into(), lower.into());
+		assert!(upper.length_squared() > 0.0 && lower.length_squared() > 0.0, "bones must have a length");
+		Self { upper, lower, bend: bend.into() }
+	}
+
+	/// Creates a solver for a limb with the given rest offsets, bending in the direction of [`Limb::bend`].
+	pub fn for_limb(limb: Limb, upper: impl Into<Vec3A>, lower: impl Into<Vec3A>) -> Self {
+		Self::new(upper, lower, limb.bend())
+	}
+
+	/// Returns the rest offset from the root joint to the middle joint.
+	pub fn upper(&self) -> Vec3A {
+		self.upper
+	}
+
+	/// Returns the rest offset from the middle joint to the end.
+	pub fn lower(&self) -> Vec3A {
+		self.lower
+	}
+
+	/// Returns the length of the chain when fully extended.
+	pub fn length(&self) -> f32 {
+		self.upper.length() + self.lower.length()
+	}
+
+	/// Solves the chain so that its end reaches `target`.
+	///
+	/// `root` is the position of the root joint (the shoulder or hip), and `parent_rotation` is the model space
+	/// rotation of the bone it is attached to, used to make the resulting rotations local. If `pole` is given, the
+	/// middle joint points towards it; otherwise, the chain bends in its bend direction as rotated by the parent.
+	/// Targets out of reach are approached by fully extending the chain towards them; targets too close to the root for
+	/// bones of unequal length to reach are approached by fully folding the chain.
+	pub fn solve(&self, root: impl Into<Vec3A>, parent_rotation: Quat, target: impl Into<Vec3A>, pole: Option<Vec3A>) -> TwoBoneSolution {
+		let (root, target) = (root.into(), target.into());
+		let (a, b) = (self.upper.length(), self.lower.length());
+		let rest_line = (self.upper + self.lower).try_normalize().unwrap_or(self.upper / a);
+		let rest_bend = orthogonal(self.bend, rest_line);
+
+		let offset = target - root;
+		let distance = offset.length();
+		let dir = offset.try_normalize().unwrap_or(parent_rotation * rest_line);
+		// the direction the middle joint should move in, perpendicular to the line from the root to the target
+		let default_bend = || orthogonal(Quat::from_rotation_arc(rest_line.into(), dir.into()) * (parent_rotation * rest_bend), dir);
+		let bend = pole
+			.and_then(|pole| (pole - root).reject_from_normalized(dir).try_normalize())
+			.unwrap_or_else(default_bend);
+
+		// law of cosines; keep a little slack so the chain never becomes degenerate
+		let d = distance.clamp((a - b).abs() + 1e-4, a + b - 1e-4).max(1e-4);
+		let cos_root = ((a * a + d * d - b * b) / (2.0 * a * d)).clamp(-1.0, 1.0);
+		let mid = root + (dir * cos_root + bend * (1.0 - cos_root * cos_root).sqrt()) * a;
+		let end = root + dir * d;
+
+		// align the rest chain & its bend plane with the target direction & bend, then swing each bone into place
+		let align = frame(dir, bend) * frame(rest_line, rest_bend).transpose();
+		let align = Quat::from_mat3a(&align);
+		let upper_world = swing(align * self.upper, mid - root) * align;
+		let lower_world = swing(upper_world * self.lower, end - mid) * upper_world;
+
+		TwoBoneSolution {
+			upper: (parent_rotation.inverse() * upper_world).normalize(),
+			lower: (upper_world.inverse() * lower_world).normalize(),
+			upper_world,
+			lower_world,
+			mid,
+			end,
+			reached: ((a - b).abs()..=a + b).contains(&distance)
+		}
+	}
+}
+
+/// Returns the component of `v` perpendicular to the unit vector `axis`, normalized, or any perpendicular direction if
+/// there is none.
+fn orthogonal(v: Vec3A, axis: Vec3A) -> Vec3A {
+	v.reject_from_normalized(axis)
+		.try_normalize()
+		.unwrap_or_else(|| axis.any_orthonormal_vector())
+}
+
+/// Returns the rotation matrix of the orthonormal frame with `forward` as X & `up` as Y, which must be perpendicular.
+fn frame(forward: Vec3A, up: Vec3A) -> Mat3A {
+	Mat3A::from_cols(forward, up, forward.cross(up))
+}
+
+/// Returns the shortest rotation from the direction of `from` to the direction of `to`.
+fn swing(from: Vec3A, to: Vec3A) -> Quat {
+	match (from.try_normalize(), to.try_normalize()) {
+		(Some(from), Some(to)) => Quat::from_rotation_arc(from.into(), to.into()),
+		_ => Quat::IDENTITY
+	}
+}
+
+/// The result of [`TwoBoneIk::solve`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TwoBoneSolution {
+	/// The local rotation of the upper bone, relative to its parent.
+	pub upper: Quat,
+	/// The local rotation of the lower bone, relative to the upper bone.
+	pub lower: Quat,
+	/// The model space rotation of the upper bone.
+	pub upper_world: Quat,
+	/// The model space rotation of the lower bone.
+	pub lower_world: Quat,
+	/// The position of the middle joint.
+	pub mid: Vec3A,
+	/// The position of the end of the chain, which differs from the target if it is out of reach.
+	pub end: Vec3A,
+	/// Whether the target is within reach, i.e. neither further from the root than the length of the chain nor closer
+	/// than the difference of the bone lengths.
+	pub reached: bool
+}
+
+impl TwoBoneSolution {
+	/// Returns the local rotation of the end bone (the hand or foot) for the given model space rotation, such as the
+	/// rotation of a controller or tracker.
+	pub fn end_rotation(&self, rotation: Quat) -> Quat {
+		(self.lower_world.inverse() * rotation).normalize()
+	}
+
+	/// Writes the rotations of the upper & lower bones of a limb into a pose.
+	///
+	/// Positions of bones already in the pose are kept; otherwise, they are set to zero.
+	pub fn apply(&self, limb: Limb, pose: &mut Pose) {
+		let [upper, lower, _] = limb.bones();
+		for (bone, rotation) in [(upper, self.upper), (lower, self.lower)] {
+			let position = pose.bone(bone).map_or(Vec3A::ZERO, |transform| transform.position);
+			pose.set_bone(bone, position, rotation);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use approx::assert_relative_eq;
+
+	use super::*;
+
+	#[test]
+	fn test_two_bone_ik() {
+		let arm = TwoBoneIk::for_limb(Limb::RightArm, Vec3A::new(0.3, 0.0, 0.0), Vec3A::new(0.25, 0.0, 0.0));
+		let root = Vec3A::new(0.2, 1.4, 0.0);
+		let parent = Quat::from_rotation_y(0.2);
+
+		// forward kinematics with the local rotations must reach the target
+		let fk = |solution: &TwoBoneSolution| {
+			let upper = parent * solution.upper;
+			let mid = root + upper * arm.upper();
+			(mid, mid + upper * solution.lower * arm.lower())
+		};
+
+		let target = Vec3A::new(0.35, 1.2, 0.3);
+		let solution = arm.solve(root, parent, target, None);
+		assert!(solution.reached);
+		let (mid, end) = fk(&solution);
+		assert_relative_eq!(mid, solution.mid, epsilon = 1e-4);
+		assert_relative_eq!(end, target, epsilon = 1e-3);
+		// without a pole, the elbow points backwards
+		assert!(mid.z < (root.z + target.z) / 2.0);
+
+		// the pole moves the elbow
+		let pole = Vec3A::new(0.3, 2.0, 0.0);
+		let solution = arm.solve(root, parent, target, Some(pole));
+		let (mid, end) = fk(&solution);
+		assert_relative_eq!(end, target, epsilon = 1e-3);
+		assert!(mid.y > 1.4);
+
+		// out of reach, the arm extends towards the target
+		let target = Vec3A::new(2.0, 1.4, 0.0);
+		let solution = arm.solve(root, parent, target, None);
+		assert!(!solution.reached);
+		let (_, end) = fk(&solution);
+		assert_relative_eq!(end, root + Vec3A::X * arm.length(), epsilon = 1e-3);
+
+		let hand = Quat::from_rotation_z(0.5);
+		assert_relative_eq!(solution.lower_world * solution.end_rotation(hand), hand, epsilon = 1e-5);
+
+		let mut pose = Pose::new();
+		pose.set_bone(StandardVRM0Bone::RightUpperArm, Vec3A::X, Quat::IDENTITY);
+		solution.apply(Limb::RightArm, &mut pose);
+		assert_eq!(pose.bone(StandardVRM0Bone::RightUpperArm).unwrap().position, Vec3A::X);
+		assert_eq!(pose.bone(StandardVRM0Bone::RightLowerArm).unwrap().rotation, solution.lower);
+	}
+
+	#[test]
+	fn test_two_bone_ik_edge_cases() {
+		let leg = TwoBoneIk::for_limb(Limb::LeftLeg, Vec3A::new(0.0, -0.45, 0.0), Vec3A::new(0.0, -0.35, 0.0));
+		let root = Vec3A::new(-0.1, 0.9, 0.0);
+		let is_finite = |solution: &TwoBoneSolution| solution.upper.is_finite() && solution.lower.is_finite() && solution.mid.is_finite();
+
+		// a target at the root folds the chain as far as it goes, without NaNs
+		let solution = leg.solve(root, Quat::IDENTITY, root, None);
+		assert!(is_finite(&solution) && !solution.reached);
+		assert_relative_eq!(solution.end.distance(root), 0.1, epsilon = 1e-3);
+		assert_relative_eq!(solution.mid.distance(root), 0.45, epsilon = 1e-4);
+
+		// closer than the difference of the bone lengths can't be reached either
+		let solution = leg.solve(root, Quat::IDENTITY, root + Vec3A::NEG_Y * 0.05, None);
+		assert!(!solution.reached);
+		assert_relative_eq!(solution.end, root + Vec3A::NEG_Y * 0.1, epsilon = 1e-3);
+
+		// exactly at full extension is still reached
+		let target = root + Vec3A::NEG_Y * leg.length();
+		let solution = leg.solve(root, Quat::IDENTITY, target, None);
+		assert!(solution.reached && is_finite(&solution));
+		assert_relative_eq!(solution.end, target, epsilon = 1e-3);
+		// the knee bends forwards, even if only slightly
+		assert!(solution.mid.z >= 0.0);
+
+		// a pole on the line from the root to the target says nothing about the bend, so the default is used
+		let target = root + Vec3A::new(0.0, -0.6, 0.2);
+		let default = leg.solve(root, Quat::IDENTITY, target, None);
+		let collinear = leg.solve(root, Quat::IDENTITY, target, Some(root + (target - root) * 2.0));
+		assert_relative_eq!(collinear.mid, default.mid, epsilon = 1e-5);
+
+		// pointing the chain opposite to its rest direction doesn't flip through a degenerate rotation
+		let target = root + Vec3A::Y * 0.7;
+		let solution = leg.solve(root, Quat::IDENTITY, target, None);
+		assert!(solution.reached && is_finite(&solution));
+		assert_relative_eq!(solution.end, target, epsilon = 1e-3);
+	}
+
+	#[test]
+	#[should_panic = "bones must have a length"]
+	fn test_zero_length_bone() {
+		TwoBoneIk::for_limb(Limb::RightArm, Vec3A::X, Vec3A::ZERO);
+	}
+}
@@ -0,0 +1,711 @@
+use std::str::FromStr;
+
+use glam::{Quat, Vec3A};
+
+use super::{Limb, TwoBoneIk, swing};
+use crate::{
+	VMCDeviceTransform as DeviceTransform, VMCDeviceType as DeviceType, VMCPose as Pose, VMCStandardVRM0Bone as StandardVRM0Bone, retarget::vrm0_parent
+};
+
+/// The bones posed by [`BodyIk`], ordered so that every bone comes after its parent.
+const JOINTS: [StandardVRM0Bone; 22] = [
+	StandardVRM0Bone::Hips,
+	StandardVRM0Bone::Spine,
+	StandardVRM0Bone::Chest,
+	StandardVRM0Bone::UpperChest,
+	StandardVRM0Bone::Neck,
+	StandardVRM0Bone::Head,
+	StandardVRM0Bone::LeftShoulder,
+	StandardVRM0Bone::LeftUpperArm,
+	StandardVRM0Bone::LeftLowerArm,
+	StandardVRM0Bone::LeftHand,
+	StandardVRM0Bone::RightShoulder,
+	StandardVRM0Bone::RightUpperArm,
+	StandardVRM0Bone::RightLowerArm,
+	StandardVRM0Bone::RightHand,
+	StandardVRM0Bone::LeftUpperLeg,
+	StandardVRM0Bone::LeftLowerLeg,
+	StandardVRM0Bone::LeftFoot,
+	StandardVRM0Bone::LeftToes,
+	StandardVRM0Bone::RightUpperLeg,
+	StandardVRM0Bone::RightLowerLeg,
+	StandardVRM0Bone::RightFoot,
+	StandardVRM0Bone::RightToes
+];
+
+/// The height of the humanoid of [`Pose::t_pose`].
+const HUMANOID_HEIGHT: f32 = 1.7;
+
+const SPINE: [usize; 5] = [1, 2, 3, 4, 5];
+const ARMS: [(Limb, TrackerRole, usize); 2] = [(Limb::LeftArm, TrackerRole::LeftHand, 6), (Limb::RightArm, TrackerRole::RightHand, 10)];
+const LEGS: [(Limb, TrackerRole, usize); 2] = [(Limb::LeftLeg, TrackerRole::LeftFoot, 14), (Limb::RightLeg, TrackerRole::RightFoot, 18)];
+
+fn index(bone: StandardVRM0Bone) -> Option<usize> {
+	JOINTS.iter().position(|joint| *joint == bone)
+}
+
+fn parent(index: usize) -> usize {
+	vrm0_parent(JOINTS[index].as_str())
+		.and_then(|parent| StandardVRM0Bone::from_str(parent).ok())
+		.and_then(self::index)
+		.unwrap_or(0)
+}
+
+/// The rest pose of the body bones solved by [`BodyIk`]: the model space position of each joint in T-pose, with the
+/// feet on the floor at a height of zero.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Skeleton {
+	joints: [Vec3A; 22],
+	eyes: Vec3A
+}
+
+impl Default for Skeleton {
+	fn default() -> Self {
+		Self::humanoid(HUMANOID_HEIGHT)
+	}
+}
+
+impl Skeleton {
+	/// Creates a skeleton with the proportions of [`Pose::t_pose`], scaled to a humanoid of the given height in meters.
+	pub fn humanoid(height: f32) -> Self {
+		Self::from_pose(&Pose::t_pose()).scaled(height / HUMANOID_HEIGHT)
+	}
+
+	/// Creates a skeleton from a rest pose, such as the bone positions sent by a marionette for its avatar.
+	///
+	/// The position of `Hips` is taken as relative to the floor, and all other positions as relative to their parent,
+	/// like the bone positions of VMC messages. Bones missing from the pose keep their position relative to their
+	/// parent in [`Pose::t_pose`], and the eyes are placed between the eye bones.
+	pub fn from_pose(pose: &Pose) -> Self {
+		let t_pose = Pose::t_pose();
+		let rest = |bone: StandardVRM0Bone| t_pose.bone(bone).expect("T-pose has all body bones").position;
+		let mut skeleton = Self {
+			joints: [Vec3A::ZERO; 22],
+			eyes: Vec3A::ZERO
+		};
+		let mut present = [false; 22];
+		for (i, bone) in JOINTS.into_iter().enumerate() {
+			skeleton.joints[i] = match (pose.bone(bone), i) {
+				(Some(transform), 0) => transform.position,
+				(None, 0) => rest(bone),
+				(transform, _) => {
+					// offsets in the pose are relative to the nearest ancestor the avatar actually has
+					let mut ancestor = parent(i);
+					while ancestor != 0 && !present[ancestor] {
+						ancestor = parent(ancestor);
+					}
+					match transform {
+						Some(transform) => skeleton.joints[ancestor] + transform.position,
+						None => skeleton.joints[parent(i)] + rest(bone)
+					}
+				}
+			};
+			present[i] = pose.bone(bone).is_some();
+		}
+
+		let eye = |bone| pose.bone(bone).map_or_else(|| rest(bone), |transform| transform.position);
+		skeleton.eyes = skeleton.joints[5] + (eye(StandardVRM0Bone::LeftEye) + eye(StandardVRM0Bone::RightEye)) / 2.0;
+		skeleton
+	}
+
+	/// Sets the rest position of a joint.
+	///
+	/// # Panics
+	///
+	/// Panics if `bone` is not one of the body bones solved by [`BodyIk`]; fingers, eyes, & the jaw are not.
+	pub fn with_joint(mut self, bone: StandardVRM0Bone, position: impl Into<Vec3A>) -> Self {
+		let index = index(bone).expect("bone is not part of the skeleton");
+		self.joints[index] = position.into();
+		self
+	}
+
+	/// Sets the rest position of the point between the eyes, where the HMD is worn.
+	pub fn with_eyes(mut self, position: impl Into<Vec3A>) -> Self {
+		self.eyes = position.into();
+		self
+	}
+
+	/// Returns the rest position of a joint, or `None` if it is not one of the body bones.
+	pub fn joint(&self, bone: StandardVRM0Bone) -> Option<Vec3A> {
+		index(bone).map(|index| self.joints[index])
+	}
+
+	/// Returns the rest position of the point between the eyes.
+	pub fn eyes(&self) -> Vec3A {
+		self.eyes
+	}
+
+	/// Returns a copy of this skeleton uniformly scaled about the floor.
+	pub fn scaled(&self, scale: f32) -> Self {
+		Self {
+			joints: self.joints.map(|joint| joint * scale),
+			eyes: self.eyes * scale
+		}
+	}
+
+	fn offset(&self, index: usize) -> Vec3A {
+		self.joints[index] - self.joints[parent(index)]
+	}
+
+	fn two_bone(&self, limb: Limb, upper: usize) -> TwoBoneIk {
+		TwoBoneIk::for_limb(limb, self.offset(upper + 1), self.offset(upper + 2))
+	}
+}
+
+/// The body part a tracked device is attached to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TrackerRole {
+	/// The HMD, at the point between the eyes.
+	Head,
+	LeftHand,
+	RightHand,
+	/// A waist tracker.
+	Hips,
+	LeftFoot,
+	RightFoot
+}
+
+impl TrackerRole {
+	/// All roles, in order.
+	pub const ALL: [TrackerRole; 6] = [
+		TrackerRole::Head,
+		TrackerRole::LeftHand,
+		TrackerRole::RightHand,
+		TrackerRole::Hips,
+		TrackerRole::LeftFoot,
+		TrackerRole::RightFoot
+	];
+
+	/// Returns the bone driven by this role.
+	pub fn bone(self) -> StandardVRM0Bone {
+		match self {
+			TrackerRole::Head => StandardVRM0Bone::Head,
+			TrackerRole::LeftHand => StandardVRM0Bone::LeftHand,
+			TrackerRole::RightHand => StandardVRM0Bone::RightHand,
+			TrackerRole::Hips => StandardVRM0Bone::Hips,
+			TrackerRole::LeftFoot => StandardVRM0Bone::LeftFoot,
+			TrackerRole::RightFoot => StandardVRM0Bone::RightFoot
+		}
+	}
+}
+
+/// The position & rotation of a tracked device, or an offset between a device & the bone it drives.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Target {
+	pub position: Vec3A,
+	pub rotation: Quat
+}
+
+impl Target {
+	/// Creates a new target.
+	pub fn new(position: impl Into<Vec3A>, rotation: impl Into<Quat>) -> Self {
+		Self {
+			position: position.into(),
+			rotation: rotation.into()
+		}
+	}
+
+	/// Applies a local offset to this target.
+	pub fn then(&self, offset: &Target) -> Target {
+		Target::new(self.position + self.rotation * offset.position, self.rotation * offset.rotation)
+	}
+
+	/// Returns the local offset from this target to `other`, such that `self.then(&self.offset_to(other)) == other`.
+	pub fn offset_to(&self, other: &Target) -> Target {
+		let inverse = self.rotation.inverse();
+		Target::new(inverse * (other.position - self.position), inverse * other.rotation)
+	}
+}
+
+impl From<&DeviceTransform> for Target {
+	fn from(transform: &DeviceTransform) -> Self {
+		Target::new(transform.position, transform.rotation)
+	}
+}
+
+/// The tracked devices used by [`BodyIk`], by role. The HMD is always present.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BodyTargets {
+	targets: [Option<Target>; 6]
+}
+
+impl BodyTargets {
+	/// Creates targets with only the HMD.
+	pub fn new(head: Target) -> Self {
+		let mut targets = [None; 6];
+		targets[TrackerRole::Head as usize] = Some(head);
+		Self { targets }
+	}
+
+	/// Assigns roles to device transforms, returning `None` if there is no HMD.
+	///
+	/// The first HMD is the head. Controllers are assigned to the hands by which side of the HMD they are on. A single
+	/// tracker is assigned to the hips, and two trackers to the feet; with three or more, the highest tracker is
+	/// assigned to the hips and the two lowest to the feet. Stand upright when roles are first assigned, and keep the
+	/// assignment by [serial](DeviceTransform::joint) afterwards.
+	pub fn from_devices<'a>(devices: impl IntoIterator<Item = &'a DeviceTransform>) -> Option<Self> {
+		let devices: Vec<_> = devices.into_iter().collect();
+		let head = Target::from(*devices.iter().find(|device| device.device == DeviceType::HMD)?);
+		let mut targets = Self::new(head);
+		let right = head.rotation * Vec3A::X;
+		let side = |device: &&DeviceTransform| (device.position - head.position).dot(right);
+		let assign_sides = |targets: &mut Self, mut devices: Vec<&DeviceTransform>, left: TrackerRole, right: TrackerRole| {
+			devices.sort_by(|a, b| side(a).total_cmp(&side(b)));
+			match devices[..] {
+				[single] => targets.set(if side(&single) < 0.0 { left } else { right }, Some(single.into())),
+				[first, .., last] => {
+					targets.set(left, Some(first.into()));
+					targets.set(right, Some(last.into()));
+				}
+				[] => {}
+			}
+		};
+
+		let controllers = devices.iter().copied().filter(|device| device.device == DeviceType::Controller).collect();
+		assign_sides(&mut targets, controllers, TrackerRole::LeftHand, TrackerRole::RightHand);
+
+		let mut trackers: Vec<_> = devices.iter().copied().filter(|device| device.device == DeviceType::Tracker).collect();
+		trackers.sort_by(|a, b| b.position.y.total_cmp(&a.position.y));
+		match trackers.len() {
+			1 => targets.set(TrackerRole::Hips, Some(trackers[0].into())),
+			2 => assign_sides(&mut targets, trackers, TrackerRole::LeftFoot, TrackerRole::RightFoot),
+			n if n >= 3 => {
+				targets.set(TrackerRole::Hips, Some(trackers[0].into()));
+				assign_sides(&mut targets, trackers[n - 2..].to_vec(), TrackerRole::LeftFoot, TrackerRole::RightFoot);
+			}
+			_ => {}
+		}
+		Some(targets)
+	}
+
+	/// Sets the target of a role, returning the modified targets.
+	pub fn with(mut self, role: TrackerRole, target: Target) -> Self {
+		self.set(role, Some(target));
+		self
+	}
+
+	/// Sets or removes the target of a role. The head can't be removed.
+	pub fn set(&mut self, role: TrackerRole, target: Option<Target>) {
+		if role != TrackerRole::Head || target.is_some() {
+			self.targets[role as usize] = target;
+		}
+	}
+
+	/// Returns the target of a role.
+	pub fn get(&self, role: TrackerRole) -> Option<Target> {
+		self.targets[role as usize]
+	}
+
+	/// Returns the HMD target.
+	pub fn head(&self) -> Target {
+		self.targets[TrackerRole::Head as usize].expect("head is always present")
+	}
+}
+
+/// Returns the rotation about Y towards which a rotation faces, using its up direction when it faces straight up or
+/// down.
+fn heading(rotation: Quat) -> Quat {
+	let forward = rotation * Vec3A::Z;
+	let mut horizontal = Vec3A::new(forward.x, 0.0, forward.z);
+	if horizontal.length_squared() < 1e-6 {
+		let up = rotation * Vec3A::Y * -forward.y.signum();
+		horizontal = Vec3A::new(up.x, 0.0, up.z);
+	}
+	Quat::from_rotation_y(horizontal.x.atan2(horizontal.z))
+}
+
+/// A full-body IK solver producing a complete pose from 3-point (HMD & controllers) or 6-point (with waist & feet
+/// trackers) tracking.
+///
+/// Each frame, the solver
+/// 1. places the head at the HMD,
+/// 2. estimates the hips below the head when there is no waist tracker, leaning the upper body with the head,
+/// 3. fits the spine between the hips & head with [FABRIK](http://www.andreasaristidou.com/FABRIK.html), twisting it
+///    gradually from the hips to the head,
+/// 4. solves the arms & legs with [`TwoBoneIk`], letting hands without a controller hang down and planting feet without
+///    a tracker below the hips, and
+/// 5. keeps the feet above the floor.
+///
+/// Targets are in the same space as the resulting pose, in which the floor is at the [floor
+/// height](BodyIk::with_floor). Without [calibration](BodyIk::calibrate), the head target is taken to be between the
+/// eyes, and all other targets are taken to be at their joint with the rest orientation of the bone; real devices are
+/// worn at an offset, so calibrate while the performer stands in T-pose before solving.
+///
+/// # Examples
+///
+/// ```
+/// use vmc::{
+/// 	Quat, VMCStandardVRM0Bone, Vec3A,
+/// 	ik::{BodyIk, BodyTargets, Skeleton, Target, TrackerRole}
+/// };
+///
+/// let mut ik = BodyIk::new(Skeleton::humanoid(1.75));
+/// let hmd = Target::new(Vec3A::new(0.0, 1.65, 0.05), Quat::IDENTITY);
+/// let targets = BodyTargets::new(hmd)
+/// 	.with(TrackerRole::LeftHand, Target::new(Vec3A::new(-0.3, 1.1, 0.3), Quat::IDENTITY))
+/// 	.with(TrackerRole::RightHand, Target::new(Vec3A::new(0.3, 1.1, 0.3), Quat::IDENTITY));
+/// let pose = ik.solve(&targets);
+/// assert!(pose.bone(VMCStandardVRM0Bone::Hips).unwrap().position.y > 0.8);
+/// assert!(pose.bone(VMCStandardVRM0Bone::RightLowerLeg).is_some());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct BodyIk {
+	skeleton: Skeleton,
+	floor: f32,
+	lean: f32,
+	iterations: usize,
+	offsets: [Option<Target>; 6]
+}
+
+impl Default for BodyIk {
+	fn default() -> Self {
+		Self::new(Skeleton::default())
+	}
+}
+
+impl BodyIk {
+	/// Creates a solver for a skeleton, with the floor at a height of zero.
+	pub fn new(skeleton: Skeleton) -> Self {
+		Self {
+			skeleton,
+			floor: 0.0,
+			lean: 0.5,
+			iterations: 10,
+			offsets: [None; 6]
+		}
+	}
+
+	/// Sets the height of the floor.
+	pub fn with_floor(mut self, floor: f32) -> Self {
+		self.floor = floor;
+		self
+	}
+
+	/// Sets how much the upper body leans with the pitch & roll of the head when there is no waist tracker, from `0.0`
+	/// (the hips stay directly below the head) to `1.0`. Defaults to `0.5`.
+	pub fn with_lean(mut self, lean: f32) -> Self {
+		self.lean = lean.clamp(0.0, 1.0);
+		self
+	}
+
+	/// Sets the number of FABRIK iterations used to fit the spine. Defaults to 10.
+	pub fn with_iterations(mut self, iterations: usize) -> Self {
+		self.iterations = iterations;
+		self
+	}
+
+	/// Returns the skeleton, which is scaled by [`BodyIk::calibrate`].
+	pub fn skeleton(&self) -> &Skeleton {
+		&self.skeleton
+	}
+
+	/// Calibrates the solver while the performer stands upright in T-pose, looking straight ahead.
+	///
+	/// The skeleton is scaled so that its eyes are at the height of the HMD, and the offset between each device & the
+	/// bone it drives is recorded, so that devices can be worn in any orientation.
+	pub fn calibrate(&mut self, targets: &BodyTargets) {
+		let head = targets.head();
+		let scale = (head.position.y - self.floor) / self.skeleton.eyes.y;
+		if scale.is_finite() && scale > 0.0 {
+			self.skeleton = self.skeleton.scaled(scale);
+		}
+
+		// where the rest skeleton stands, facing the same way as the performer
+		let facing = heading(head.rotation);
+		let eyes = self.skeleton.eyes;
+		let origin = Vec3A::new(head.position.x, self.floor, head.position.z) - facing * Vec3A::new(eyes.x, 0.0, eyes.z);
+		for role in TrackerRole::ALL {
+			self.offsets[role as usize] = targets.get(role).map(|target| {
+				let joint = self.skeleton.joint(role.bone()).expect("roles drive body bones");
+				target.offset_to(&Target::new(origin + facing * joint, facing))
+			});
+		}
+	}
+
+	/// Clears the calibration offsets. The skeleton stays scaled.
+	pub fn reset_calibration(&mut self) {
+		self.offsets = [None; 6];
+	}
+
+	/// Returns the joint position & rotation driven by a role's target, if present.
+	fn target(&self, targets: &BodyTargets, role: TrackerRole) -> Option<Target> {
+		let target = targets.get(role)?;
+		Some(match self.offsets[role as usize] {
+			Some(offset) => target.then(&offset),
+			None if role == TrackerRole::Head => {
+				let skeleton = &self.skeleton;
+				target.then(&Target::new(skeleton.joints[5] - skeleton.eyes, Quat::IDENTITY))
+			}
+			None => target
+		})
+	}
+
+	/// Solves a full-body pose reaching the targets as closely as possible. See [`BodyIk`].
+	///
+	/// The pose contains all bones of the [`Skeleton`]. `Hips` is positioned in the space of the targets; all other
+	/// bones are positioned at their rest offset from their parent.
+	pub fn solve(&self, targets: &BodyTargets) -> Pose {
+		let skeleton = &self.skeleton;
+		let head = self.target(targets, TrackerRole::Head).expect("head is always present");
+		let mut positions = [Vec3A::ZERO; 22];
+		let mut rotations = [Quat::IDENTITY; 22];
+
+		// hips
+		let (hips, hips_rotation) = match self.target(targets, TrackerRole::Hips) {
+			Some(target) => (target.position, target.rotation),
+			None => {
+				let facing = heading(head.rotation);
+				let torso = facing * Quat::IDENTITY.slerp(facing.inverse() * head.rotation, self.lean);
+				(head.position + torso * (skeleton.joints[0] - skeleton.joints[5]), facing)
+			}
+		};
+		// never crouch lower than the knees allow
+		let min_hips = self.floor + (skeleton.joints[0].y - skeleton.joints[14].y) + skeleton.joints[16].y;
+		positions[0] = Vec3A::new(hips.x, hips.y.max(min_hips), hips.z);
+		rotations[0] = hips_rotation;
+
+		// spine, twisting gradually from the hips to the head
+		let twist = |i: usize| hips_rotation.slerp(head.rotation, i as f32 / (SPINE.len() - 1) as f32);
+		positions[SPINE[0]] = positions[0] + hips_rotation * skeleton.offset(SPINE[0]);
+		for (i, window) in SPINE.windows(2).enumerate() {
+			positions[window[1]] = positions[window[0]] + twist(i) * skeleton.offset(window[1]);
+		}
+		self.fabrik(&mut positions, head.position);
+		for (i, window) in SPINE.windows(2).enumerate() {
+			let twist = twist(i);
+			rotations[window[0]] = swing(twist * skeleton.offset(window[1]), positions[window[1]] - positions[window[0]]) * twist;
+		}
+		rotations[5] = head.rotation;
+
+		// arms
+		for (limb, role, shoulder) in ARMS {
+			let chest = parent(shoulder);
+			rotations[shoulder] = rotations[chest];
+			positions[shoulder] = positions[chest] + rotations[chest] * skeleton.offset(shoulder);
+			let root = positions[shoulder] + rotations[shoulder] * skeleton.offset(shoulder + 1);
+			let ik = skeleton.two_bone(limb, shoulder + 1);
+			let target = self.target(targets, role);
+			let goal = target.map(|target| target.position).unwrap_or_else(|| {
+				let outwards = ik.lower().normalize() * 0.15;
+				root + (Vec3A::NEG_Y + rotations[chest] * outwards).normalize() * ik.length() * 0.98
+			});
+			let solution = ik.solve(root, rotations[shoulder], goal, None);
+			rotations[shoulder + 1] = solution.upper_world;
+			rotations[shoulder + 2] = solution.lower_world;
+			rotations[shoulder + 3] = target.map_or(solution.lower_world, |target| target.rotation);
+		}
+
+		// legs
+		for (limb, role, upper) in LEGS {
+			let root = positions[0] + rotations[0] * skeleton.offset(upper);
+			let ik = skeleton.two_bone(limb, upper);
+			let ankle = self.floor + skeleton.joints[upper + 2].y;
+			let target = self.target(targets, role);
+			let mut goal = target.map_or(Vec3A::new(root.x, ankle, root.z), |target| target.position);
+			goal.y = goal.y.max(ankle);
+			let solution = ik.solve(root, rotations[0], goal, None);
+			rotations[upper] = solution.upper_world;
+			rotations[upper + 1] = solution.lower_world;
+			let foot = target.map_or(heading(hips_rotation), |target| target.rotation);
+			rotations[upper + 2] = foot;
+			rotations[upper + 3] = foot;
+		}
+
+		let mut pose = Pose::new();
+		for (i, bone) in JOINTS.into_iter().enumerate() {
+			if i == 0 {
+				pose.set_bone(bone, positions[0], rotations[0]);
+			} else {
+				let local = rotations[parent(i)].inverse() * rotations[i];
+				pose.set_bone(bone, skeleton.offset(i), local.normalize());
+			}
+		}
+		pose
+	}
+
+	/// Fits the spine joints between the fixed spine root & the head with FABRIK.
+	fn fabrik(&self, positions: &mut [Vec3A; 22], head: Vec3A) {
+		let lengths = SPINE.map(|i| self.skeleton.offset(i).length());
+		let root = positions[SPINE[0]];
+		for _ in 0..self.iterations {
+			positions[SPINE[SPINE.len() - 1]] = head;
+			for i in (0..SPINE.len() - 1).rev() {
+				let (joint, next) = (SPINE[i], SPINE[i + 1]);
+				let dir = (positions[joint] - positions[next]).try_normalize().unwrap_or(Vec3A::NEG_Y);
+				positions[joint] = positions[next] + dir * lengths[i + 1];
+			}
+			positions[SPINE[0]] = root;
+			for i in 0..SPINE.len() - 1 {
+				let (joint, next) = (SPINE[i], SPINE[i + 1]);
+				let dir = (positions[next] - positions[joint]).try_normalize().unwrap_or(Vec3A::Y);
+				positions[next] = positions[joint] + dir * lengths[i + 1];
+			}
+			if positions[SPINE[SPINE.len() - 1]].distance_squared(head) < 1e-8 {
+				break;
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use approx::assert_relative_eq;
+
+	use super::*;
+
+	/// Returns the model space positions of all joints of a solved pose.
+	fn forward(pose: &Pose) -> [Vec3A; 22] {
+		let mut positions = [Vec3A::ZERO; 22];
+		let mut rotations = [Quat::IDENTITY; 22];
+		for (i, bone) in JOINTS.into_iter().enumerate() {
+			let transform = pose.bone(bone).unwrap();
+			if i == 0 {
+				positions[0] = transform.position;
+				rotations[0] = transform.rotation;
+			} else {
+				let parent = parent(i);
+				positions[i] = positions[parent] + rotations[parent] * transform.position;
+				rotations[i] = rotations[parent] * transform.rotation;
+			}
+		}
+		positions
+	}
+
+	#[test]
+	fn test_body_ik() {
+		let skeleton = Skeleton::default();
+		let joint = |bone| skeleton.joint(bone).unwrap();
+		let device = |device, position: Vec3A| DeviceTransform::new(device, "", position, Quat::IDENTITY, false);
+		let devices = [
+			device(DeviceType::Tracker, joint(StandardVRM0Bone::RightFoot)),
+			device(DeviceType::Controller, joint(StandardVRM0Bone::RightHand)),
+			device(DeviceType::HMD, skeleton.eyes()),
+			device(DeviceType::Controller, joint(StandardVRM0Bone::LeftHand)),
+			device(DeviceType::Tracker, joint(StandardVRM0Bone::Hips)),
+			device(DeviceType::Tracker, joint(StandardVRM0Bone::LeftFoot))
+		];
+		let targets = BodyTargets::from_devices(&devices).unwrap();
+		assert_eq!(targets.get(TrackerRole::LeftHand).unwrap().position, joint(StandardVRM0Bone::LeftHand));
+		assert_eq!(targets.get(TrackerRole::RightFoot).unwrap().position, joint(StandardVRM0Bone::RightFoot));
+		assert_eq!(targets.get(TrackerRole::Hips).unwrap().position, joint(StandardVRM0Bone::Hips));
+
+		// in T-pose, the solved pose is the rest pose, except for the slack that keeps fully extended limbs from being
+		// degenerate
+		let mut ik = BodyIk::new(skeleton.clone());
+		ik.calibrate(&targets);
+		assert_eq!(ik.skeleton(), &skeleton);
+		let positions = forward(&ik.solve(&targets));
+		for (position, rest) in positions.iter().zip(skeleton.joints) {
+			assert_relative_eq!(*position, rest, epsilon = 1e-2);
+		}
+
+		// 3-point tracking while crouching & reaching forward
+		let targets = BodyTargets::new(Target::new(skeleton.eyes() - Vec3A::Y * 0.3, Quat::IDENTITY))
+			.with(TrackerRole::LeftHand, Target::new(Vec3A::new(-0.2, 1.0, 0.4), Quat::IDENTITY))
+			.with(TrackerRole::RightHand, Target::new(Vec3A::new(0.2, 1.0, 0.4), Quat::IDENTITY));
+		let pose = ik.solve(&targets);
+		let positions = forward(&pose);
+		assert_relative_eq!(positions[5], joint(StandardVRM0Bone::Head) - Vec3A::Y * 0.3, epsilon = 1e-3);
+		assert_relative_eq!(positions[9], Vec3A::new(-0.2, 1.0, 0.4), epsilon = 1e-3);
+		assert_relative_eq!(positions[13], Vec3A::new(0.2, 1.0, 0.4), epsilon = 1e-3);
+		// the feet stay planted & the knees bend forwards
+		assert_relative_eq!(positions[16].y, joint(StandardVRM0Bone::LeftFoot).y, epsilon = 1e-3);
+		assert!(positions[15].z > positions[16].z + 0.05);
+
+		// feet are kept above the floor
+		let targets = targets.with(TrackerRole::RightFoot, Target::new(Vec3A::new(0.1, -0.2, 0.0), Quat::IDENTITY));
+		assert!(forward(&ik.solve(&targets))[20].y > 0.05);
+	}
+
+	#[test]
+	fn test_assign_devices() {
+		let device = |device, position: Vec3A| DeviceTransform::new(device, "", position, Quat::IDENTITY, false);
+		let hmd = device(DeviceType::HMD, Vec3A::new(0.0, 1.6, 0.0));
+		assert!(BodyTargets::from_devices(&[device(DeviceType::Controller, Vec3A::ZERO)]).is_none());
+
+		let targets = BodyTargets::from_devices(std::slice::from_ref(&hmd)).unwrap();
+		assert!(TrackerRole::ALL[1..].iter().all(|role| targets.get(*role).is_none()));
+
+		// a single controller is assigned by which side of the HMD it's on, and a single tracker goes to the hips
+		let targets = BodyTargets::from_devices(&[
+			device(DeviceType::Controller, Vec3A::new(-0.3, 1.0, 0.2)),
+			hmd.clone(),
+			device(DeviceType::Tracker, Vec3A::new(0.0, 0.1, 0.0))
+		])
+		.unwrap();
+		assert!(targets.get(TrackerRole::LeftHand).is_some() && targets.get(TrackerRole::RightHand).is_none());
+		assert_eq!(targets.get(TrackerRole::Hips).unwrap().position, Vec3A::new(0.0, 0.1, 0.0));
+		// ...but with the HMD turned around, its left is the other way
+		let turned = DeviceTransform::new(DeviceType::HMD, "", hmd.position, Quat::from_rotation_y(std::f32::consts::PI), false);
+		let targets = BodyTargets::from_devices(&[turned, device(DeviceType::Controller, Vec3A::new(-0.3, 1.0, 0.2))]).unwrap();
+		assert!(targets.get(TrackerRole::RightHand).is_some());
+
+		// with four trackers, the highest is the hips and the two lowest are the feet
+		let targets = BodyTargets::from_devices(&[
+			device(DeviceType::Tracker, Vec3A::new(0.1, 0.05, 0.0)),
+			device(DeviceType::Tracker, Vec3A::new(0.0, 1.0, 0.0)),
+			device(DeviceType::Tracker, Vec3A::new(0.0, 0.5, 0.1)),
+			hmd,
+			device(DeviceType::Tracker, Vec3A::new(-0.1, 0.08, 0.0))
+		])
+		.unwrap();
+		assert_eq!(targets.get(TrackerRole::Hips).unwrap().position.y, 1.0);
+		assert_eq!(targets.get(TrackerRole::LeftFoot).unwrap().position.x, -0.1);
+		assert_eq!(targets.get(TrackerRole::RightFoot).unwrap().position.x, 0.1);
+
+		// the head can't be removed
+		let mut targets = targets;
+		targets.set(TrackerRole::Head, None);
+		targets.set(TrackerRole::Hips, None);
+		assert_eq!(targets.head().position.y, 1.6);
+		assert!(targets.get(TrackerRole::Hips).is_none());
+	}
+
+	#[test]
+	fn test_body_ik_edge_cases() {
+		let skeleton = Skeleton::default();
+		// bones missing from the rest pose are filled in from the T-pose
+		assert_eq!(Skeleton::from_pose(&Pose::new()), skeleton);
+		assert_eq!(skeleton.joint(StandardVRM0Bone::LeftEye), None);
+		let is_finite = |pose: &Pose| {
+			pose.bones
+				.values()
+				.all(|transform| transform.position.is_finite() && transform.rotation.is_finite())
+		};
+
+		// an HMD at or below the floor (e.g. lying on it before tracking starts) doesn't collapse the skeleton
+		let mut ik = BodyIk::new(skeleton.clone());
+		ik.calibrate(&BodyTargets::new(Target::new(Vec3A::ZERO, Quat::IDENTITY)));
+		assert_eq!(ik.skeleton(), &skeleton);
+		ik.reset_calibration();
+
+		// looking straight down, crouched far lower than the legs allow
+		let down = Quat::from_rotation_x(std::f32::consts::FRAC_PI_2);
+		let targets = BodyTargets::new(Target::new(Vec3A::new(0.0, 0.3, 0.0), down));
+		let pose = ik.solve(&targets);
+		assert!(is_finite(&pose));
+		assert_eq!(pose.bones.len(), JOINTS.len());
+		let positions = forward(&pose);
+		assert!(positions[0].y >= skeleton.joints[0].y - skeleton.joints[14].y + skeleton.joints[16].y - 1e-4);
+		assert!(positions[16].y >= skeleton.joints[16].y - 1e-3);
+
+		// without FABRIK iterations, the spine just follows the twist, but the pose is still valid
+		let pose = BodyIk::new(skeleton.clone())
+			.with_iterations(0)
+			.with_lean(5.0)
+			.solve(&BodyTargets::new(Target::new(skeleton.eyes() + Vec3A::Z * 0.3, Quat::from_rotation_x(0.5))));
+		assert!(is_finite(&pose));
+
+		let target = Target::new(Vec3A::new(1.0, 2.0, 3.0), Quat::from_rotation_y(1.0));
+		let other = Target::new(Vec3A::new(-1.0, 0.5, 0.0), Quat::from_rotation_x(0.3));
+		let roundtrip = target.then(&target.offset_to(&other));
+		assert_relative_eq!(roundtrip.position, other.position, epsilon = 1e-5);
+		assert_relative_eq!(roundtrip.rotation, other.rotation, epsilon = 1e-5);
+	}
+
+	#[test]
+	#[should_panic = "bone is not part of the skeleton"]
+	fn test_with_finger_joint() {
+		let _ = Skeleton::default().with_joint(StandardVRM0Bone::LeftIndexProximal, Vec3A::ZERO);
+	}
+}
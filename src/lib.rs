@@ -33,18 +33,17 @@
 //! #[tokio::main]
 //! async fn main() -> VMCResult<()> {
 //! 	let mut socket = vmc::marionette!("127.0.0.1:39539").await?;
-//! 	while let Some(packet) = socket.next().await {
-//! 		let (packet, _) = packet?;
-//! 		for message in vmc::parse(packet)? {
-//! 			match message {
-//! 				VMCMessage::BoneTransform(transform) => {
-//! 					println!(
-//! 						"\tTransform bone: {} (pos {:?}; rot {:?})",
-//! 						transform.bone, transform.position, transform.rotation
-//! 					)
-//! 				}
-//! 				_ => {}
+//! 	let mut messages = socket.messages();
+//! 	while let Some(message) = messages.next().await {
+//! 		let (message, _) = message?;
+//! 		match message {
+//! 			VMCMessage::BoneTransform(transform) => {
+//! 				println!(
+//! 					"\tTransform bone: {} (pos {:?}; rot {:?})",
+//! 					transform.bone, transform.position, transform.rotation
+//! 				)
 //! 			}
+//! 			_ => {}
 //! 		}
 //! 	}
 //!
@@ -60,202 +59,86 @@
 
 use std::{
 	io,
-	net::SocketAddr,
-	pin::Pin,
-	sync::Arc,
-	task::{Context, Poll}
+	net::{IpAddr, Ipv4Addr}
 };
 
-use futures_core::Stream;
-use tokio::net::{ToSocketAddrs, UdpSocket};
+use tokio::net::ToSocketAddrs;
 
+pub mod arkit;
+mod avatar;
+#[cfg(feature = "bevy")]
+pub mod bevy;
+mod blendshape;
+pub mod blocking;
+pub mod bvh;
+#[cfg(feature = "discovery")]
+pub mod discovery;
+#[cfg(feature = "egui")]
+pub mod egui;
 mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod filter;
+mod framed;
+pub mod gaze;
+pub mod idle;
+pub mod ifacialmocap;
+pub mod ik;
+pub mod lipsync;
+pub mod lookat;
+pub mod mediapipe;
 pub mod message;
+pub mod middleware;
+#[cfg(feature = "nalgebra")]
+mod nalgebra;
+mod name;
+pub mod openseeface;
 pub mod osc;
+mod pose;
+pub mod probe;
+pub mod record;
+mod relay;
+pub mod retarget;
+pub mod router;
+pub mod slip;
+mod socket;
+pub mod spring;
+pub mod stream;
+mod tcp;
 mod udp;
 
-pub use glam::{EulerRot, Quat, Vec3, Vec3A};
+pub use glam::{Affine3A, EulerRot, Mat4, Quat, Vec2, Vec3, Vec3A};
 
-use self::udp::UDPSocketStream;
 pub use self::{
+	avatar::AvatarState as VMCAvatarState,
+	blendshape::BlendShapeBuffer as VMCBlendShapeBuffer,
 	error::{VMCError, VMCResult},
 	message::{
 		ApplyBlendShapes as VMCApplyBlendShapes, BlendShape as VMCBlendShape, BoneTransform as VMCBoneTransform, CalibrationMode as VMCCalibrationMode,
 		CalibrationState as VMCCalibrationState, DeviceTransform as VMCDeviceTransform, DeviceType as VMCDeviceType, ModelState as VMCModelState,
-		RootTransform as VMCRootTransform, StandardVRM0Bone as VMCStandardVRM0Bone, StandardVRMBlendShape as VMCStandardVRMBlendShape, State as VMCState,
-		Time as VMCTime, TrackingState as VMCTrackingState, VMCMessage, parse
+		OptionString as VMCOptionString, ReceiveEnable as VMCReceiveEnable, RequestInfo as VMCRequestInfo, RootTransform as VMCRootTransform,
+		StandardVRM0Bone as VMCStandardVRM0Bone, StandardVRMBlendShape as VMCStandardVRMBlendShape, State as VMCState, Time as VMCTime,
+		TrackingState as VMCTrackingState, VMCMessage, parse, parse_datagram, parse_datagram_with_limits
 	},
-	osc::{IntoOSCArgs, IntoOSCMessage, IntoOSCPacket, OSCPacket, OSCType}
+	name::Name as VMCName,
+	osc::{FromOSCArgs, IntoOSCArgs, IntoOSCMessage, IntoOSCPacket, OSCPacket, OSCType},
+	pose::Pose as VMCPose,
+	relay::VMCRelay,
+	slip::VMCSlipStream,
+	socket::{
+		VMCBundledSender, VMCKeepalive, VMCKeepaliveTask, VMCPeerStatus, VMCQueuedSender, VMCReceiver, VMCRecvTimestamp, VMCRole, VMCSendQueueStats,
+		VMCSendTask, VMCSender, VMCSocket, VMCSocketBuilder, VMCSocketStats, VMCThrottledSender
+	},
+	stream::{Frame as VMCFrame, Frames as VMCFrames, Messages as VMCMessages},
+	tcp::{VMCTcpListener, VMCTcpSocket}
 };
 
-/// A UDP socket to send and receive VMC messages.
-#[derive(Debug)]
-pub struct VMCSocket {
-	socket: UDPSocketStream
-}
-
-impl VMCSocket {
-	/// Creates a new OSC socket from a [`tokio::net::UdpSocket`].
-	pub fn new(socket: UdpSocket) -> Self {
-		let socket = UDPSocketStream::new(socket);
-		Self { socket }
-	}
-
-	/// Creates an VMC socket from the given address.
-	///
-	/// Binding with a port number of 0 will request that the OS assigns a port to this socket.
-	/// The port allocated can be queried via [`local_addr`] method.
-	///
-	/// [`local_addr`]: #method.local_addr
-	pub async fn bind<A: ToSocketAddrs>(addr: A) -> VMCResult<Self> {
-		let socket = UdpSocket::bind(addr).await?;
-		Ok(Self::new(socket))
-	}
-
-	/// Connects the UDP socket to a remote address.
-	///
-	/// When connected, only messages from this address will be received and the [`send`] method
-	/// will use the specified address for sending.
-	///
-	/// [`send`]: #method.send
-	///
-	/// # Examples
-	///
-	/// ```no_run
-	/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
-	/// use vmc::VMCSocket;
-	///
-	/// let socket = VMCSocket::bind("127.0.0.1:0").await?;
-	/// socket.connect("127.0.0.1:8080").await?;
-	/// # Ok(()) }) }
-	/// ```
-	pub async fn connect<A: ToSocketAddrs>(&self, addrs: A) -> VMCResult<()> {
-		self.socket().connect(addrs).await?;
-		Ok(())
-	}
-
-	/// Sends an OSC packet on the socket to the given address.
-	///
-	/// # Examples
-	///
-	/// ```no_run
-	/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
-	/// use vmc::{VMCBlendShape, VMCSocket, VMCStandardVRMBlendShape};
-	///
-	/// let socket = VMCSocket::bind("127.0.0.1:0").await?;
-	/// let addr = "127.0.0.1:39539";
-	/// let message = VMCBlendShape::new(VMCStandardVRMBlendShape::Joy, 1.0);
-	/// socket.send_to(message, &addr).await?;
-	/// # Ok(()) }) }
-	/// ```
-	pub async fn send_to<A: ToSocketAddrs, P: IntoOSCPacket>(&self, packet: P, addrs: A) -> VMCResult<()> {
-		let buf = self::osc::encode(&packet.into_osc_packet())?;
-		let n = self.socket().send_to(&buf[..], addrs).await?;
-		check_len(&buf[..], n)
-	}
-
-	/// Sends a packet on the socket to the remote address to which it is connected.
-	///
-	/// The [`connect`] method will connect this socket to a remote address.
-	/// This method will fail if the socket is not connected.
-	///
-	/// [`connect`]: #method.connect
-	///
-	/// # Examples
-	///
-	/// ```no_run
-	/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
-	/// use vmc::{VMCBlendShape, VMCSocket, VMCStandardVRMBlendShape};
-	///
-	/// let socket = VMCSocket::bind("127.0.0.1:2434").await?;
-	/// socket.connect("127.0.0.1:39539").await?;
-	/// socket.send(VMCBlendShape::new(VMCStandardVRMBlendShape::Joy, 1.0)).await?;
-	/// #
-	/// # Ok(()) }) }
-	/// ```
-	pub async fn send<P: IntoOSCPacket>(&self, packet: P) -> VMCResult<()> {
-		let buf = self::osc::encode(&packet.into_osc_packet())?;
-		let n = self.socket().send(&buf[..]).await?;
-		check_len(&buf[..], n)
-	}
-
-	/// Create a standalone sender for this socket.
-	///
-	/// The sender can be moved to other threads or tasks.
-	pub fn sender(&self) -> VMCSender {
-		VMCSender::new(self.socket.clone_inner())
-	}
-
-	/// Get a reference to the underling [`UdpSocket`].
-	pub fn socket(&self) -> &UdpSocket {
-		self.socket.get_ref()
-	}
-
-	/// Returns the local address that this socket is bound to.
-	///
-	/// This can be useful, for example, when binding to port 0 to figure out which port was
-	/// actually bound.
-	pub fn local_addr(&self) -> VMCResult<SocketAddr> {
-		let addr = self.socket().local_addr()?;
-		Ok(addr)
-	}
-}
-
-impl Stream for VMCSocket {
-	type Item = VMCResult<(OSCPacket, SocketAddr)>;
-	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-		let packet = match Pin::new(&mut self.socket).poll_next(cx) {
-			Poll::Ready(packet) => packet,
-			Poll::Pending => return Poll::Pending
-		};
-		let message = packet.map(|packet| match packet {
-			Err(err) => Err(err.into()),
-			Ok((buf, peer_addr)) => self::osc::decode_udp(&buf[..]).map_err(|e| e.into()).map(|p| (p.1, peer_addr))
-		});
-		Poll::Ready(message)
-	}
-}
-
-/// A sender to send messages over a VMC socket.
-///
-/// See [`VMCSocket::sender`].
-#[derive(Clone, Debug)]
-pub struct VMCSender {
-	socket: Arc<UdpSocket>
-}
-
-impl VMCSender {
-	fn new(socket: Arc<UdpSocket>) -> Self {
-		Self { socket }
-	}
-
-	/// Sends a VMC packet on the socket to the given address.
-	///
-	/// See [`VMCSocket::send_to`].
-	pub async fn send_to<A: ToSocketAddrs, P: IntoOSCPacket>(&self, packet: P, addrs: A) -> VMCResult<()> {
-		let buf = self::osc::encode(&packet.into_osc_packet())?;
-		let n = self.socket().send_to(&buf[..], addrs).await?;
-		check_len(&buf[..], n)
-	}
-
-	/// Sends a VMC packet on the connected socket.
-	///
-	/// See [`VMCSocket::send`].
-	pub async fn send<P: IntoOSCPacket>(&self, packet: P) -> VMCResult<()> {
-		let buf = self::osc::encode(&packet.into_osc_packet())?;
-		let n = self.socket().send(&buf[..]).await?;
-		check_len(&buf[..], n)
-	}
-
-	/// Get a reference to the underling [`UdpSocket`].
-	pub fn socket(&self) -> &UdpSocket {
-		&self.socket
-	}
-}
-
 /// Creates a new VMC Performer. Performers process tracking, motion, and IK, and send bone transforms and other
 /// information to a [`marionette`].
 ///
+/// For addresses configured at runtime, pre-bound sockets, or further socket options, use [`VMCSocket::builder`] with
+/// [`VMCRole::Performer`] instead.
+///
 /// The default usage of this usage will automatically select a free port to bind to, and send information to port 39539
 /// (default VMC receiver port) on the local machine.
 ///
@@ -274,38 +157,94 @@ impl VMCSender {
 /// let performer = vmc::performer!("127.13.72.16:2434", bind_port = 39540).await?;
 /// # Ok(()) }) }
 /// ```
+///
+/// Performers bind to `127.0.0.1` when sending to the local machine, and to the unspecified address (`0.0.0.0`, or `::`
+/// for IPv6 send addresses) otherwise, so that packets can reach marionettes on the local network. The `bind_any` &
+/// `bind_any_v6` options always bind to `0.0.0.0` & `::` respectively:
+/// ```no_run
+/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+/// // binds to 0.0.0.0 automatically
+/// let performer = vmc::performer!("192.168.1.193:39539").await?;
+/// // binds to [::]
+/// let performer = vmc::performer!("[fe80::1%2]:39539").await?;
+/// let performer = vmc::performer!("[::1]:39539", bind_any_v6).await?;
+/// # Ok(()) }) }
+/// ```
+///
+/// Performers can also send to a broadcast address, so that every marionette on the local network receives the
+/// performer's stream. This enables `SO_BROADCAST` on the socket and binds to `0.0.0.0` (unless a `bind` address is
+/// given) so that packets can leave the local machine:
+/// ```no_run
+/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+/// let performer = vmc::performer!("255.255.255.255:39539", broadcast = true).await?;
+/// // subnet broadcast addresses work too
+/// let performer = vmc::performer!("192.168.1.255:39539", broadcast = true).await?;
+/// # Ok(()) }) }
+/// ```
+///
+/// VMC is usually transported over UDP, but TCP can be used instead with the `transport = tcp` option, in which case a
+/// [`VMCTcpSocket`] connected to the given address is returned:
+/// ```no_run
+/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+/// // connects to 127.0.0.1:39539
+/// let performer = vmc::performer!(transport = tcp).await?;
+/// // customize address & port
+/// let performer = vmc::performer!("127.13.72.16:2434", transport = tcp).await?;
+/// # Ok(()) }) }
+/// ```
 #[macro_export]
 macro_rules! performer {
 	() => {
-		$crate::_create_performer("127.0.0.1:0", "127.0.0.1:39539")
+		$crate::_create_performer("127.0.0.1:39539", |builder| builder)
+	};
+	(transport = tcp) => {
+		$crate::VMCTcpSocket::connect("127.0.0.1:39539")
 	};
 	(bind = $bind:expr) => {
-		$crate::_create_performer($bind, "127.0.0.1:39539")
+		$crate::_create_performer("127.0.0.1:39539", |builder| builder.bind($bind))
 	};
 	(bind_port = $bind_port:expr) => {
-		$crate::_create_performer(format!("127.0.0.1:{}", $bind_port), "127.0.0.1:39539")
+		$crate::_create_performer("127.0.0.1:39539", |builder| builder.bind_port($bind_port))
 	};
 	($addr:expr) => {
-		$crate::_create_performer("127.0.0.1:0", $addr)
+		$crate::_create_performer($addr, |builder| builder)
+	};
+	($addr:expr, transport = tcp) => {
+		$crate::VMCTcpSocket::connect($addr)
 	};
 	($addr:expr, bind = $bind:expr) => {
-		$crate::_create_performer($bind, $addr)
+		$crate::_create_performer($addr, |builder| builder.bind($bind))
 	};
 	($addr:expr, bind_port = $bind_port:expr) => {
-		$crate::_create_performer(format!("127.0.0.1:{}", $bind_port), $addr)
+		$crate::_create_performer($addr, |builder| builder.bind_port($bind_port))
+	};
+	($addr:expr, bind_any) => {
+		$crate::_create_performer($addr, |builder| builder.bind_any())
+	};
+	($addr:expr, bind_any_v6) => {
+		$crate::_create_performer($addr, |builder| builder.bind_any_v6())
+	};
+	($addr:expr, broadcast = $broadcast:expr) => {
+		$crate::_create_performer($addr, |builder| builder.broadcast($broadcast))
+	};
+	($addr:expr, bind = $bind:expr, broadcast = $broadcast:expr) => {
+		$crate::_create_performer($addr, |builder| builder.bind($bind).broadcast($broadcast))
 	};
 }
 
 #[doc(hidden)]
-pub async fn _create_performer(bind: impl ToSocketAddrs, addr: impl ToSocketAddrs) -> VMCResult<VMCSocket> {
-	let socket = VMCSocket::bind(bind).await?;
-	socket.connect(addr).await?;
-	Ok(socket)
+pub async fn _create_performer(addr: impl ToSocketAddrs, configure: impl FnOnce(VMCSocketBuilder) -> VMCSocketBuilder) -> VMCResult<VMCSocket> {
+	// resolve asynchronously; the builder would block
+	let addrs: Vec<_> = tokio::net::lookup_host(addr).await?.collect();
+	configure(VMCSocket::builder().role(VMCRole::Performer).target(&addrs[..])).build().await
 }
 
 /// Creates a new VMC Marionette. Marionettes receive motion data from a [`performer`] and render the avatar to a
 /// screen.
 ///
+/// For addresses configured at runtime, pre-bound sockets, or further socket options, use [`VMCSocket::builder`] with
+/// [`VMCRole::Marionette`] instead.
+///
 /// The default usage of this usage will automatically bind to port 39539, the default VMC port.
 ///
 /// The binding address can also be customized:
@@ -315,6 +254,47 @@ pub async fn _create_performer(bind: impl ToSocketAddrs, addr: impl ToSocketAddr
 /// let marionette = vmc::marionette!().await?;
 /// // customize bind address/port
 /// let marionette = vmc::marionette!("192.168.1.193:2434").await?;
+/// let marionette = vmc::marionette!(bind = "192.168.1.193:2434").await?;
+/// # Ok(()) }) }
+/// ```
+///
+/// Note that by default, marionettes only receive packets sent from the local machine. To receive from other machines,
+/// such as a tracking app on a phone, bind to the unspecified address with the `bind_any` (`0.0.0.0:39539`) or
+/// `bind_any_v6` (`[::]:39539`) options, or give an unspecified address with a custom port:
+/// ```no_run
+/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+/// // binds to 0.0.0.0:39539
+/// let marionette = vmc::marionette!(bind_any).await?;
+/// // binds to [::]:39539
+/// let marionette = vmc::marionette!(bind_any_v6).await?;
+/// // binds to 0.0.0.0:2434
+/// let marionette = vmc::marionette!(bind = "0.0.0.0:2434").await?;
+/// # Ok(()) }) }
+/// ```
+///
+/// Marionettes can also receive from a multicast group, allowing multiple machines (or multiple marionettes on the
+/// same machine) to receive one performer's stream. This binds to the unspecified address (`0.0.0.0` or `::`) with
+/// address reuse enabled, and joins the group on the default interface:
+/// ```no_run
+/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+/// // binds to 0.0.0.0:39539 and joins 239.0.0.1
+/// let marionette = vmc::marionette!(multicast = "239.0.0.1").await?;
+/// // customize bind address/port
+/// let marionette = vmc::marionette!("0.0.0.0:2434", multicast = "239.0.0.1").await?;
+/// # Ok(()) }) }
+/// ```
+///
+/// With the `transport = tcp` option, a [`VMCTcpListener`] is returned instead, which can accept TCP connections from
+/// performers:
+/// ```no_run
+/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+/// // listens on 127.0.0.1:39539
+/// let marionette = vmc::marionette!(transport = tcp).await?;
+/// // listens on 0.0.0.0:39539
+/// let marionette = vmc::marionette!(bind_any, transport = tcp).await?;
+/// // customize bind address/port
+/// let marionette = vmc::marionette!("192.168.1.193:2434", transport = tcp).await?;
+/// let (socket, _) = marionette.accept().await?;
 /// # Ok(()) }) }
 /// ```
 #[macro_export]
@@ -322,9 +302,42 @@ macro_rules! marionette {
 	() => {
 		$crate::_create_marionette("127.0.0.1:39539")
 	};
+	(transport = tcp) => {
+		$crate::VMCTcpListener::bind("127.0.0.1:39539")
+	};
+	(multicast = $group:expr) => {
+		$crate::_create_multicast_marionette(None::<&str>, $group)
+	};
+	(bind_any) => {
+		$crate::_create_any_marionette(false)
+	};
+	(bind_any, transport = tcp) => {
+		$crate::VMCTcpListener::bind("0.0.0.0:39539")
+	};
+	(bind_any_v6) => {
+		$crate::_create_any_marionette(true)
+	};
+	(bind_any_v6, transport = tcp) => {
+		$crate::VMCTcpListener::bind("[::]:39539")
+	};
+	(bind = $addr:expr) => {
+		$crate::_create_marionette($addr)
+	};
+	(bind = $addr:expr, transport = tcp) => {
+		$crate::VMCTcpListener::bind($addr)
+	};
+	(bind = $addr:expr, multicast = $group:expr) => {
+		$crate::_create_multicast_marionette(Some($addr), $group)
+	};
 	($addr:expr) => {
 		$crate::_create_marionette($addr)
 	};
+	($addr:expr, transport = tcp) => {
+		$crate::VMCTcpListener::bind($addr)
+	};
+	($addr:expr, multicast = $group:expr) => {
+		$crate::_create_multicast_marionette(Some($addr), $group)
+	};
 }
 
 #[doc(hidden)]
@@ -333,10 +346,25 @@ pub async fn _create_marionette(addr: impl ToSocketAddrs) -> VMCResult<VMCSocket
 	Ok(socket)
 }
 
-fn check_len(buf: &[u8], len: usize) -> VMCResult<()> {
-	if len != buf.len() {
-		Err(io::Error::new(io::ErrorKind::Interrupted, "UDP packet not fully sent").into())
-	} else {
-		Ok(())
+#[doc(hidden)]
+pub async fn _create_any_marionette(v6: bool) -> VMCResult<VMCSocket> {
+	let builder = VMCSocket::builder().role(VMCRole::Marionette);
+	if v6 { builder.bind_any_v6() } else { builder.bind_any() }.build().await
+}
+
+#[doc(hidden)]
+pub async fn _create_multicast_marionette(addr: Option<impl std::net::ToSocketAddrs>, group: impl ToString) -> VMCResult<VMCSocket> {
+	let group: IpAddr = group
+		.to_string()
+		.parse()
+		.map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid multicast group address"))?;
+	let mut builder = VMCSocket::builder().role(VMCRole::Marionette);
+	if let Some(addr) = addr {
+		builder = builder.bind(addr);
 	}
+	let builder = match group {
+		IpAddr::V4(group) => builder.join_multicast_v4(group, Ipv4Addr::UNSPECIFIED),
+		IpAddr::V6(group) => builder.join_multicast_v6(group, 0)
+	};
+	builder.reuse_address(true).reuse_port(true).build().await
 }
@@ -0,0 +1,417 @@
+//! Conversion of audio-derived visemes or formants into the VRM mouth blend shapes.
+//!
+//! VRM avatars animate speech with the five vowel blend shapes [`A`](StandardVRMBlendShape::A),
+//! [`I`](StandardVRMBlendShape::I), [`U`](StandardVRMBlendShape::U), [`E`](StandardVRMBlendShape::E), &
+//! [`O`](StandardVRMBlendShape::O). [`LipSync`] derives their weights from either of the two common kinds of audio
+//! analysis:
+//!
+//! - **Visemes**, as output by viseme classifiers like Oculus Lipsync, are mapped onto the vowels through
+//!   [`VISEME_MAPPING`].
+//! - **Formants**, the first two resonant frequencies of the voice, are matched against the typical formants of each
+//!   vowel, so lip sync can be driven by a simple LPC analysis without a trained model.
+//!
+//! Raw per-frame weights flicker, so they are smoothed with separate attack & release times, and blended with the
+//! previous shape (co-articulation) so that the mouth moves through transitions instead of snapping between shapes.
+//! The result can be layered onto any pose with [`LipSync::apply`].
+//!
+//! # Examples
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use vmc::{
+//! 	VMCPose, VMCStandardVRMBlendShape,
+//! 	lipsync::{LipSync, Viseme}
+//! };
+//!
+//! let mut lipsync = LipSync::new();
+//! let mut pose = VMCPose::new();
+//! for _ in 0..10 {
+//! 	lipsync.update_visemes([(Viseme::Aa, 0.9), (Viseme::Sil, 0.1)], Duration::from_millis(16));
+//! }
+//! lipsync.apply(&mut pose);
+//! assert!(pose.blendshape(VMCStandardVRMBlendShape::A).unwrap() > 0.8);
+//! ```
+
+use std::{fmt, str::FromStr, time::Duration};
+
+use crate::{VMCPose as Pose, VMCStandardVRMBlendShape as StandardVRMBlendShape};
+
+/// The vowel blend shapes, in the order used for weight arrays throughout this module.
+pub const VOWELS: [StandardVRMBlendShape; 5] = [
+	StandardVRMBlendShape::A,
+	StandardVRMBlendShape::I,
+	StandardVRMBlendShape::U,
+	StandardVRMBlendShape::E,
+	StandardVRMBlendShape::O
+];
+
+/// A viseme of the 15-viseme set used by Oculus Lipsync and compatible classifiers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Viseme {
+	/// Silence.
+	Sil,
+	/// `p`, `b`, `m`
+	PP,
+	/// `f`, `v`
+	FF,
+	/// `th`
+	TH,
+	/// `t`, `d`
+	DD,
+	/// `k`, `g`
+	Kk,
+	/// `ch`, `j`, `sh`
+	CH,
+	/// `s`, `z`
+	SS,
+	/// `n`, `l`
+	Nn,
+	/// `r`
+	RR,
+	/// `a` as in "car"
+	Aa,
+	/// `e` as in "bed"
+	E,
+	/// `i` as in "tip"
+	Ih,
+	/// `o` as in "toe"
+	Oh,
+	/// `u` as in "book"
+	Ou
+}
+
+impl Viseme {
+	/// All visemes, in the order classifiers usually output them.
+	pub const ALL: [Viseme; 15] = [
+		Viseme::Sil,
+		Viseme::PP,
+		Viseme::FF,
+		Viseme::TH,
+		Viseme::DD,
+		Viseme::Kk,
+		Viseme::CH,
+		Viseme::SS,
+		Viseme::Nn,
+		Viseme::RR,
+		Viseme::Aa,
+		Viseme::E,
+		Viseme::Ih,
+		Viseme::Oh,
+		Viseme::Ou
+	];
+
+	/// Returns the conventional name of the viseme, e.g. `aa` or `PP`.
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Viseme::Sil => "sil",
+			Viseme::PP => "PP",
+			Viseme::FF => "FF",
+			Viseme::TH => "TH",
+			Viseme::DD => "DD",
+			Viseme::Kk => "kk",
+			Viseme::CH => "CH",
+			Viseme::SS => "SS",
+			Viseme::Nn => "nn",
+			Viseme::RR => "RR",
+			Viseme::Aa => "aa",
+			Viseme::E => "E",
+			Viseme::Ih => "ih",
+			Viseme::Oh => "oh",
+			Viseme::Ou => "ou"
+		}
+	}
+}
+
+impl fmt::Display for Viseme {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
+impl FromStr for Viseme {
+	type Err = ();
+
+	/// Parses a viseme by its conventional name, ignoring case and an optional `viseme_` prefix.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let s = s.strip_prefix("viseme_").unwrap_or(s);
+		Viseme::ALL.into_iter().find(|viseme| viseme.as_str().eq_ignore_ascii_case(s)).ok_or(())
+	}
+}
+
+/// The default weights of the vowel blend shapes for each viseme, in the order of [`VOWELS`].
+///
+/// Vowel visemes map directly onto their blend shape. Consonants only slightly open the mouth in the shape they're
+/// usually articulated with; closed-lip consonants & silence close it.
+pub const VISEME_MAPPING: [(Viseme, [f32; 5]); 15] = [
+	(Viseme::Sil, [0.0, 0.0, 0.0, 0.0, 0.0]),
+	(Viseme::PP, [0.0, 0.0, 0.0, 0.0, 0.0]),
+	(Viseme::FF, [0.0, 0.2, 0.0, 0.2, 0.0]),
+	(Viseme::TH, [0.1, 0.0, 0.0, 0.3, 0.0]),
+	(Viseme::DD, [0.2, 0.0, 0.0, 0.3, 0.0]),
+	(Viseme::Kk, [0.3, 0.0, 0.0, 0.2, 0.0]),
+	(Viseme::CH, [0.0, 0.4, 0.3, 0.0, 0.0]),
+	(Viseme::SS, [0.0, 0.5, 0.0, 0.0, 0.0]),
+	(Viseme::Nn, [0.1, 0.0, 0.0, 0.3, 0.0]),
+	(Viseme::RR, [0.0, 0.0, 0.2, 0.0, 0.3]),
+	(Viseme::Aa, [1.0, 0.0, 0.0, 0.0, 0.0]),
+	(Viseme::E, [0.0, 0.0, 0.0, 1.0, 0.0]),
+	(Viseme::Ih, [0.0, 1.0, 0.0, 0.0, 0.0]),
+	(Viseme::Oh, [0.0, 0.0, 0.0, 0.0, 1.0]),
+	(Viseme::Ou, [0.0, 0.0, 1.0, 0.0, 0.0])
+];
+
+/// Typical first & second formants of each vowel in Hz, in the order of [`VOWELS`], for an adult voice.
+pub const FORMANTS: [(f32, f32); 5] = [(800.0, 1200.0), (300.0, 2300.0), (350.0, 1300.0), (500.0, 1900.0), (500.0, 850.0)];
+
+/// Converts visemes or formants into smoothed vowel blend shape weights; see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq)]
+pub struct LipSync {
+	attack: Duration,
+	release: Duration,
+	coarticulation: f32,
+	gain: f32,
+	formant_scale: f32,
+	previous_target: [f32; 5],
+	weights: [f32; 5]
+}
+
+impl Default for LipSync {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl LipSync {
+	/// Creates a new lip sync converter with an attack time of 30ms, a release time of 80ms, and a co-articulation
+	/// factor of 0.3.
+	pub fn new() -> Self {
+		Self {
+			attack: Duration::from_millis(30),
+			release: Duration::from_millis(80),
+			coarticulation: 0.3,
+			gain: 1.0,
+			formant_scale: 1.0,
+			previous_target: [0.0; 5],
+			weights: [0.0; 5]
+		}
+	}
+
+	/// Sets how quickly weights rise towards their target, as the time constant of an exponential approach. Zero
+	/// disables smoothing.
+	pub fn with_attack(mut self, attack: Duration) -> Self {
+		self.attack = attack;
+		self
+	}
+
+	/// Sets how quickly weights fall towards their target, as the time constant of an exponential approach. A longer
+	/// release than attack keeps the mouth from closing between syllables.
+	pub fn with_release(mut self, release: Duration) -> Self {
+		self.release = release;
+		self
+	}
+
+	/// Sets how much of the previous update's shape is blended into each new shape, from `0.0` (none) to `1.0`.
+	pub fn with_coarticulation(mut self, coarticulation: f32) -> Self {
+		self.coarticulation = coarticulation.clamp(0.0, 1.0);
+		self
+	}
+
+	/// Sets a multiplier for all weights, e.g. to exaggerate mouth movement for a quiet voice.
+	pub fn with_gain(mut self, gain: f32) -> Self {
+		self.gain = gain;
+		self
+	}
+
+	/// Scales the reference [`FORMANTS`] to match the voice; formants of children's voices are roughly 1.3 times as
+	/// high as those of adults.
+	pub fn with_formant_scale(mut self, scale: f32) -> Self {
+		self.formant_scale = scale;
+		self
+	}
+
+	/// Updates the weights from viseme amplitudes, `dt` after the previous update.
+	///
+	/// Amplitudes are mapped through [`VISEME_MAPPING`] and summed; they usually add up to 1.
+	pub fn update_visemes(&mut self, visemes: impl IntoIterator<Item = (Viseme, f32)>, dt: Duration) -> [f32; 5] {
+		let mut target = [0.0; 5];
+		for (viseme, amplitude) in visemes {
+			let (_, weights) = VISEME_MAPPING[viseme as usize];
+			for (target, weight) in target.iter_mut().zip(weights) {
+				*target += weight * amplitude;
+			}
+		}
+		self.update(target, dt)
+	}
+
+	/// Updates the weights from the first two formants of the voice in Hz and its loudness (from `0.0` for silence to
+	/// `1.0`), `dt` after the previous update.
+	///
+	/// Each vowel is weighted by how close the formants are to its reference formants, and the weights are scaled by
+	/// the loudness.
+	pub fn update_formants(&mut self, f1: f32, f2: f32, loudness: f32, dt: Duration) -> [f32; 5] {
+		// compare on a logarithmic scale, which is closer to how vowels are perceived
+		let distances = FORMANTS.map(|(r1, r2)| {
+			let d1 = (f1 / (r1 * self.formant_scale)).ln();
+			let d2 = (f2 / (r2 * self.formant_scale)).ln();
+			d1 * d1 + d2 * d2
+		});
+		// softmax over the negative distances, sharp enough that a clear vowel dominates
+		let min = distances.iter().copied().fold(f32::INFINITY, f32::min);
+		let scores = distances.map(|distance| (-(distance - min) * 20.0).exp());
+		let total: f32 = scores.iter().sum();
+		let loudness = if total.is_finite() { loudness.clamp(0.0, 1.0) } else { 0.0 };
+		self.update(scores.map(|score| score / total * loudness), dt)
+	}
+
+	/// Updates the weights towards target vowel weights in the order of [`VOWELS`], `dt` after the previous update,
+	/// returning the new weights.
+	///
+	/// The targets are blended with the previous targets by the co-articulation factor, scaled by the gain, and
+	/// normalized if they add up to more than 1, so that combined shapes don't overdrive the mouth.
+	pub fn update(&mut self, target: [f32; 5], dt: Duration) -> [f32; 5] {
+		let target = target.map(|weight| if weight.is_finite() { weight.max(0.0) } else { 0.0 });
+		let mut blended = [0.0; 5];
+		for i in 0..5 {
+			blended[i] = (target[i] * (1.0 - self.coarticulation) + self.previous_target[i] * self.coarticulation) * self.gain;
+		}
+		self.previous_target = target;
+		let total: f32 = blended.iter().sum();
+		if total > 1.0 {
+			blended = blended.map(|weight| weight / total);
+		}
+
+		for (weight, target) in self.weights.iter_mut().zip(blended) {
+			let tau = if target > *weight { self.attack } else { self.release };
+			let t = if tau.is_zero() { 1.0 } else { 1.0 - (-dt.as_secs_f32() / tau.as_secs_f32()).exp() };
+			*weight += (target - *weight) * t;
+		}
+		self.weights
+	}
+
+	/// Returns the current weights of the vowel blend shapes.
+	pub fn weights(&self) -> [(StandardVRMBlendShape, f32); 5] {
+		let mut weights = VOWELS.map(|vowel| (vowel, 0.0));
+		for ((_, weight), value) in weights.iter_mut().zip(self.weights) {
+			*weight = value;
+		}
+		weights
+	}
+
+	/// Writes the current weights of the vowel blend shapes into a pose, replacing their previous values.
+	pub fn apply(&self, pose: &mut Pose) {
+		for (vowel, weight) in self.weights() {
+			pose.set_blendshape(vowel, weight);
+		}
+	}
+
+	/// Resets the weights to a closed mouth.
+	pub fn reset(&mut self) {
+		self.previous_target = [0.0; 5];
+		self.weights = [0.0; 5];
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use approx::assert_relative_eq;
+
+	use super::*;
+
+	#[test]
+	fn test_lipsync() {
+		assert!(VISEME_MAPPING.iter().enumerate().all(|(i, (viseme, _))| *viseme as usize == i));
+		assert_eq!("viseme_aa".parse(), Ok(Viseme::Aa));
+		assert_eq!("PP".parse(), Ok(Viseme::PP));
+		assert_eq!("x".parse::<Viseme>(), Err(()));
+
+		let frame = Duration::from_millis(16);
+		let mut lipsync = LipSync::new();
+		for _ in 0..20 {
+			lipsync.update_visemes([(Viseme::Aa, 1.0)], frame);
+		}
+		assert!(lipsync.weights()[0].1 > 0.99);
+
+		// switching vowels passes through a blend of both
+		let [a, _, u, ..] = lipsync.update_visemes([(Viseme::Ou, 1.0)], frame);
+		assert!(a > 0.3 && u > 0.1);
+		for _ in 0..40 {
+			lipsync.update_visemes([(Viseme::Ou, 1.0)], frame);
+		}
+		let [a, _, u, ..] = lipsync.update_visemes([(Viseme::Ou, 1.0)], frame);
+		assert!(a < 0.01 && u > 0.99);
+
+		// formants of an "i"
+		let mut lipsync = LipSync::new().with_attack(Duration::ZERO).with_coarticulation(0.0);
+		let [a, i, u, e, o] = lipsync.update_formants(320.0, 2250.0, 0.8, frame);
+		assert!(i > 0.7 && i <= 0.8);
+		assert!([a, u, e, o].iter().all(|weight| *weight < 0.1));
+		// silence releases the mouth
+		assert_relative_eq!(lipsync.update_formants(320.0, 2250.0, 0.0, frame)[1], i * (-0.016f32 / 0.08).exp(), epsilon = 1e-6);
+	}
+
+	#[test]
+	fn test_viseme_names() {
+		for viseme in Viseme::ALL {
+			assert_eq!(viseme.to_string().parse(), Ok(viseme));
+			assert_eq!(viseme.as_str().to_uppercase().parse(), Ok(viseme));
+			assert_eq!(format!("viseme_{viseme}").parse(), Ok(viseme));
+		}
+		assert_eq!("viseme_".parse::<Viseme>(), Err(()));
+		assert_eq!("".parse::<Viseme>(), Err(()));
+		// the prefix is case-sensitive, like in Oculus Lipsync's blend shape names
+		assert_eq!("VISEME_aa".parse::<Viseme>(), Err(()));
+	}
+
+	#[test]
+	fn test_invalid_input() {
+		let frame = Duration::from_millis(16);
+		let mut lipsync = LipSync::new().with_attack(Duration::ZERO).with_coarticulation(0.0);
+
+		// NaN & negative amplitudes are treated as silence rather than poisoning the smoothed weights
+		assert_eq!(lipsync.update_visemes([(Viseme::Aa, f32::NAN), (Viseme::Oh, -1.0)], frame), [0.0; 5]);
+		assert_eq!(lipsync.update([f32::INFINITY, f32::NEG_INFINITY, f32::NAN, -0.5, 0.0], frame), [0.0; 5]);
+		// as are formants which can't be measured, e.g. during silence
+		for (f1, f2) in [(0.0, 0.0), (-300.0, 2300.0), (f32::NAN, 1200.0), (800.0, f32::INFINITY)] {
+			let weights = lipsync.update_formants(f1, f2, 1.0, frame);
+			assert!(weights.iter().all(|weight| *weight == 0.0), "{f1} {f2}: {weights:?}");
+		}
+		// loudness is clamped
+		let weights = lipsync.update_formants(800.0, 1200.0, 5.0, frame);
+		assert!(weights.iter().sum::<f32>() <= 1.0 + 1e-6);
+	}
+
+	#[test]
+	fn test_smoothing_edge_cases() {
+		let mut lipsync = LipSync::new();
+		// no time has passed, so nothing moves
+		assert_eq!(lipsync.update([1.0, 0.0, 0.0, 0.0, 0.0], Duration::ZERO), [0.0; 5]);
+
+		// gain can't overdrive the mouth: combined weights are normalized
+		let mut lipsync = LipSync::new()
+			.with_attack(Duration::ZERO)
+			.with_release(Duration::ZERO)
+			.with_coarticulation(0.0)
+			.with_gain(3.0);
+		let weights = lipsync.update([0.5, 0.0, 0.0, 0.5, 0.0], Duration::from_millis(16));
+		assert_relative_eq!(weights[0], 0.5);
+		assert_relative_eq!(weights[3], 0.5);
+		// without smoothing, the weights follow the target immediately in both directions
+		assert_eq!(lipsync.update([0.0; 5], Duration::from_millis(16)), [0.0; 5]);
+
+		// co-articulation is clamped to 0..=1; at 1, the shape never changes from the previous target
+		let mut lipsync = LipSync::new().with_attack(Duration::ZERO).with_coarticulation(2.0);
+		assert_eq!(lipsync.update([1.0, 0.0, 0.0, 0.0, 0.0], Duration::from_millis(16)), [0.0; 5]);
+
+		let mut pose = Pose::new();
+		let mut lipsync = LipSync::new().with_attack(Duration::ZERO);
+		lipsync.update_visemes([(Viseme::Aa, 1.0)], Duration::from_millis(16));
+		lipsync.reset();
+		assert_eq!(lipsync.update([0.0; 5], Duration::from_millis(16)), [0.0; 5]);
+		// every vowel is written, even when closed, so stale values from a previous frame are overwritten
+		pose.set_blendshape(StandardVRMBlendShape::O, 1.0);
+		lipsync.apply(&mut pose);
+		assert_eq!(pose.blendshapes.len(), 5);
+		assert_eq!(pose.blendshape(StandardVRMBlendShape::O), Some(0.0));
+	}
+}
@@ -0,0 +1,318 @@
+//! A look-at constraint turning the neck, head, & eyes towards a target, e.g. for "look at camera" toggles.
+//!
+//! [`LookAt`] measures the yaw & pitch from the avatar's eyes to the target relative to its upper body, then
+//! distributes them over the neck & head by their weights, clamped to their range limits. The eyes cover whatever
+//! remains, through a [`GazeMapper`] which writes eye bone rotations and/or look blend shapes. An overall weight fades
+//! the constraint in & out over the tracked pose.
+//!
+//! Targets are in the space of the pose: the space its root transform is in, or the space of the hips if the pose has
+//! no root.
+//!
+//! # Examples
+//!
+//! ```
+//! use vmc::{Quat, VMCPose, VMCStandardVRM0Bone, Vec3A, lookat::LookAt};
+//!
+//! let mut pose = VMCPose::new();
+//! pose.set_bone(VMCStandardVRM0Bone::Hips, Vec3A::new(0.0, 1.0, 0.0), Quat::IDENTITY);
+//! pose.set_bone(VMCStandardVRM0Bone::Neck, Vec3A::new(0.0, 0.45, 0.0), Quat::IDENTITY);
+//! pose.set_bone(VMCStandardVRM0Bone::Head, Vec3A::new(0.0, 0.1, 0.0), Quat::IDENTITY);
+//!
+//! // a camera up & to the avatar's right
+//! let camera = Vec3A::new(1.0, 2.0, 1.5);
+//! LookAt::new().apply(camera, &mut pose);
+//! let head = pose.bone(VMCStandardVRM0Bone::Head).unwrap().rotation;
+//! assert!((head * Vec3A::Z).x > 0.0);
+//! ```
+
+use glam::{EulerRot, Quat, Vec3A};
+
+use crate::{
+	VMCBoneTransform as BoneTransform, VMCPose as Pose, VMCStandardVRM0Bone as StandardVRM0Bone, VMCStandardVRMBlendShape as StandardVRMBlendShape,
+	gaze::GazeMapper
+};
+
+const EYES: [StandardVRM0Bone; 2] = [StandardVRM0Bone::LeftEye, StandardVRM0Bone::RightEye];
+const LOOK: [StandardVRMBlendShape; 4] = [
+	StandardVRMBlendShape::LookUp,
+	StandardVRMBlendShape::LookDown,
+	StandardVRMBlendShape::LookLeft,
+	StandardVRMBlendShape::LookRight
+];
+
+/// How far a bone can turn to either side, up, & down, in radians.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Limits {
+	yaw: f32,
+	up: f32,
+	down: f32
+}
+
+impl Limits {
+	fn new(yaw: f32, up: f32, down: f32) -> Self {
+		assert!(yaw >= 0.0 && up >= 0.0 && down >= 0.0, "look-at range must not be negative");
+		Self { yaw, up, down }
+	}
+
+	fn clamp(&self, yaw: f32, pitch: f32) -> (f32, f32) {
+		(yaw.clamp(-self.yaw, self.yaw), pitch.clamp(-self.down, self.up))
+	}
+}
+
+/// Turns the neck, head, & eyes of a pose towards a target; see the [module documentation](self).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LookAt {
+	neck: (f32, Limits),
+	head: (f32, Limits),
+	gaze: GazeMapper,
+	weight: f32
+}
+
+impl Default for LookAt {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl LookAt {
+	/// Creates a look-at constraint with the neck taking 30% of the rotation (up to 30° to either side & 20° up or
+	/// down), the head taking 50% (up to 45° to either side & 30° up or down), and the eyes covering the rest with the
+	/// default [`GazeMapper`].
+	pub fn new() -> Self {
+		Self {
+			neck: (0.3, Limits::new(30f32.to_radians(), 20f32.to_radians(), 20f32.to_radians())),
+			head: (0.5, Limits::new(45f32.to_radians(), 30f32.to_radians(), 30f32.to_radians())),
+			gaze: GazeMapper::new(),
+			weight: 1.0
+		}
+	}
+
+	/// Sets the share of the rotation taken by the neck, and its range limits in radians. Without a neck bone in the
+	/// pose, the head takes the neck's share too.
+	///
+	/// # Panics
+	///
+	/// Panics if any limit is negative.
+	pub fn with_neck(mut self, weight: f32, max_yaw: f32, max_up: f32, max_down: f32) -> Self {
+		self.neck = (weight, Limits::new(max_yaw, max_up, max_down));
+		self
+	}
+
+	/// Sets the share of the rotation taken by the head, and its range limits in radians.
+	///
+	/// # Panics
+	///
+	/// Panics if any limit is negative.
+	pub fn with_head(mut self, weight: f32, max_yaw: f32, max_up: f32, max_down: f32) -> Self {
+		self.head = (weight, Limits::new(max_yaw, max_up, max_down));
+		self
+	}
+
+	/// Sets the gaze mapper used for the eyes, which sets their range limits and whether eye bones and/or look blend
+	/// shapes are written.
+	pub fn with_gaze(mut self, gaze: GazeMapper) -> Self {
+		self.gaze = gaze;
+		self
+	}
+
+	/// Sets how strongly the constraint overrides the tracked pose, from `0.0` (not at all) to `1.0`. Animate this to
+	/// fade a "look at camera" toggle in & out.
+	pub fn with_weight(mut self, weight: f32) -> Self {
+		self.weight = weight.clamp(0.0, 1.0);
+		self
+	}
+
+	/// Computes the bone transforms turning the neck, head, & eye bones of a pose towards `target`, without modifying
+	/// the pose.
+	///
+	/// Eye bones are only included if the [gaze mapper](LookAt::with_gaze) writes bones. Returns no transforms if the
+	/// pose has no head bone.
+	pub fn solve(&self, target: impl Into<Vec3A>, pose: &Pose) -> Vec<BoneTransform> {
+		let mut solved = pose.clone();
+		self.apply(target, &mut solved);
+		[StandardVRM0Bone::Neck, StandardVRM0Bone::Head, StandardVRM0Bone::LeftEye, StandardVRM0Bone::RightEye]
+			.into_iter()
+			.filter_map(|bone| solved.bones.remove(bone.as_str()).filter(|transform| pose.bone(bone) != Some(transform)))
+			.collect()
+	}
+
+	/// Turns the neck, head, & eyes of a pose towards `target`, writing eye bones and/or look blend shapes as
+	/// configured by the [gaze mapper](LookAt::with_gaze). Positions are kept.
+	///
+	/// Does nothing if the pose has no head bone, or if the target is at the eyes or isn't finite.
+	pub fn apply(&self, target: impl Into<Vec3A>, pose: &mut Pose) {
+		let target = target.into();
+		if self.weight == 0.0 {
+			return;
+		}
+		let Some((head_position, _)) = pose.world_transform(StandardVRM0Bone::Head) else {
+			return;
+		};
+		let eyes = match (pose.world_transform(StandardVRM0Bone::LeftEye), pose.world_transform(StandardVRM0Bone::RightEye)) {
+			(Some((left, _)), Some((right, _))) => (left + right) / 2.0,
+			_ => head_position
+		};
+
+		// the frame of the upper body, which the neck (or the head, without a neck) turns relative to
+		let neck = pose.bone(StandardVRM0Bone::Neck).filter(|_| self.neck.0 > 0.0).cloned();
+		let first = if neck.is_some() { StandardVRM0Bone::Neck } else { StandardVRM0Bone::Head };
+		let (_, first_world) = pose.world_transform(first).expect("bone is present");
+		let body = first_world * pose.bone(first).expect("bone is present").rotation.inverse();
+
+		let direction = body.inverse() * (target - eyes);
+		// a target at the eyes has no direction, and a non-finite one (e.g. an untracked camera) would poison the pose
+		if direction == Vec3A::ZERO || !direction.is_finite() {
+			return;
+		}
+		let yaw = direction.x.atan2(direction.z);
+		let pitch = direction.y.atan2((direction.x * direction.x + direction.z * direction.z).sqrt());
+		// a positive rotation around X turns +Z downwards
+		let turn = |(yaw, pitch): (f32, f32)| Quat::from_euler(EulerRot::YXZ, yaw, -pitch, 0.0);
+		let blend = |current: Quat, rotation: Quat| current.slerp(rotation, self.weight).normalize();
+
+		let mut head_frame = body;
+		let head_weight = match neck {
+			Some(neck) => {
+				let rotation = blend(neck.rotation, turn(self.neck.1.clamp(yaw * self.neck.0, pitch * self.neck.0)));
+				pose.set_bone(StandardVRM0Bone::Neck, neck.position, rotation);
+				head_frame *= rotation;
+				self.head.0
+			}
+			None => self.head.0 + self.neck.0
+		};
+		let head = pose.bone(StandardVRM0Bone::Head).expect("head is present").clone();
+		let rotation = blend(head.rotation, turn(self.head.1.clamp(yaw * head_weight, pitch * head_weight)));
+		pose.set_bone(StandardVRM0Bone::Head, head.position, rotation);
+		head_frame *= rotation;
+
+		// the eyes cover the rest, measured exactly from the turned head, and blend from their tracked state
+		let eye_bones = EYES.map(|bone| pose.bone(bone).map(|transform| transform.rotation));
+		let look = LOOK.map(|key| pose.blendshape(key));
+		self.gaze.apply(head_frame.inverse() * (target - eyes), pose);
+		if self.weight < 1.0 {
+			for (bone, tracked) in EYES.into_iter().zip(eye_bones) {
+				if let (Some(tracked), Some(transform)) = (tracked, pose.bones.get_mut(bone.as_str())) {
+					transform.rotation = blend(tracked, transform.rotation);
+				}
+			}
+			for (key, tracked) in LOOK.into_iter().zip(look) {
+				if let Some(value) = pose.blendshapes.get_mut(key.as_str()) {
+					let tracked = tracked.unwrap_or(0.0);
+					*value = tracked + (*value - tracked) * self.weight;
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use approx::assert_relative_eq;
+
+	use super::*;
+
+	#[test]
+	fn test_look_at() {
+		let mut pose = Pose::new();
+		pose.set_bone(StandardVRM0Bone::Hips, Vec3A::Y, Quat::from_rotation_y(0.3));
+		pose.set_bone(StandardVRM0Bone::Neck, Vec3A::Y * 0.45, Quat::IDENTITY);
+		pose.set_bone(StandardVRM0Bone::Head, Vec3A::Y * 0.1, Quat::IDENTITY);
+		pose.set_bone(StandardVRM0Bone::LeftEye, Vec3A::new(-0.03, 0.07, 0.08), Quat::IDENTITY);
+		pose.set_bone(StandardVRM0Bone::RightEye, Vec3A::new(0.03, 0.07, 0.08), Quat::IDENTITY);
+		let (eyes, _) = pose.world_transform(StandardVRM0Bone::LeftEye).unwrap();
+		let eyes = eyes + Quat::from_rotation_y(0.3) * Vec3A::X * 0.03;
+
+		// a target within range is looked at exactly
+		let target = eyes + Quat::from_rotation_y(0.3) * Vec3A::new(0.5, 0.2, 1.0);
+		let mut looking = pose.clone();
+		LookAt::new().apply(target, &mut looking);
+		let (_, head) = looking.world_transform(StandardVRM0Bone::Head).unwrap();
+		let eye = looking.bone(StandardVRM0Bone::LeftEye).unwrap().rotation;
+		assert_relative_eq!(head * eye * Vec3A::Z, (target - eyes).normalize(), epsilon = 1e-4);
+		assert!(looking.blendshape(StandardVRMBlendShape::LookRight).unwrap() > 0.0);
+		// the neck takes less of the rotation than the head
+		let neck = looking.bone(StandardVRM0Bone::Neck).unwrap().rotation;
+		let head = looking.bone(StandardVRM0Bone::Head).unwrap().rotation;
+		assert!(neck.angle_between(Quat::IDENTITY) < head.angle_between(Quat::IDENTITY));
+
+		// a target behind the avatar is clamped
+		let behind = LookAt::new()
+			.with_gaze(GazeMapper::new().with_blendshapes(false))
+			.solve(eyes - Vec3A::Z, &pose);
+		assert_eq!(behind.len(), 4);
+		let (yaw, ..) = behind[1].rotation.to_euler(EulerRot::YXZ);
+		assert!(yaw.abs() <= 45f32.to_radians() + 1e-5);
+
+		// with no weight, the pose is unchanged
+		let mut unchanged = pose.clone();
+		LookAt::new().with_weight(0.0).apply(target, &mut unchanged);
+		assert_eq!(unchanged, pose);
+
+		assert!(LookAt::new().solve(target, &Pose::new()).is_empty());
+	}
+
+	fn rest_pose(neck: bool) -> Pose {
+		let mut pose = Pose::new();
+		pose.set_bone(StandardVRM0Bone::Hips, Vec3A::Y, Quat::IDENTITY);
+		if neck {
+			pose.set_bone(StandardVRM0Bone::Neck, Vec3A::Y * 0.45, Quat::IDENTITY);
+		}
+		pose.set_bone(StandardVRM0Bone::Head, Vec3A::Y * if neck { 0.1 } else { 0.55 }, Quat::IDENTITY);
+		pose
+	}
+
+	#[test]
+	fn test_look_at_edge_cases() {
+		let pose = rest_pose(true);
+		let (eyes, _) = pose.world_transform(StandardVRM0Bone::Head).unwrap();
+
+		// targets without a direction leave the pose untouched
+		for target in [eyes, Vec3A::NAN, Vec3A::new(f32::INFINITY, 1.0, 1.0)] {
+			let mut unchanged = pose.clone();
+			LookAt::new().apply(target, &mut unchanged);
+			assert_eq!(unchanged, pose);
+			assert!(LookAt::new().solve(target, &pose).is_empty());
+		}
+
+		// without a neck, the head takes the neck's share, up to its own limits
+		let target = eyes + Vec3A::new(0.2, 0.0, 1.0);
+		let yaw = |pose: &Pose, bone| pose.bone(bone).unwrap().rotation.to_euler(EulerRot::YXZ).0;
+		let mut no_neck = rest_pose(false);
+		LookAt::new().apply(target, &mut no_neck);
+		assert!(no_neck.bone(StandardVRM0Bone::Neck).is_none());
+		assert_relative_eq!(yaw(&no_neck, StandardVRM0Bone::Head), 0.2f32.atan2(1.0) * 0.8, epsilon = 1e-5);
+		// a neck with no share is treated the same, and left as it was
+		let mut still_neck = pose.clone();
+		LookAt::new().with_neck(0.0, 1.0, 1.0, 1.0).apply(target, &mut still_neck);
+		assert_eq!(still_neck.bone(StandardVRM0Bone::Neck), pose.bone(StandardVRM0Bone::Neck));
+		assert_relative_eq!(yaw(&still_neck, StandardVRM0Bone::Head), 0.2f32.atan2(1.0) * 0.5, epsilon = 1e-5);
+		// zero range limits lock a bone in place
+		let mut locked = pose.clone();
+		LookAt::new().with_head(0.5, 0.0, 0.0, 0.0).apply(target, &mut locked);
+		assert_eq!(locked.bone(StandardVRM0Bone::Head).unwrap().rotation, Quat::IDENTITY);
+
+		// partial weights blend from the tracked pose, including the look blend shapes
+		let mut tracked = pose.clone();
+		tracked.set_blendshape(StandardVRMBlendShape::LookLeft, 1.0);
+		let mut full = tracked.clone();
+		LookAt::new().apply(target, &mut full);
+		let mut half = tracked.clone();
+		LookAt::new().with_weight(0.5).apply(target, &mut half);
+		assert_relative_eq!(yaw(&half, StandardVRM0Bone::Head), yaw(&full, StandardVRM0Bone::Head) / 2.0, epsilon = 1e-5);
+		assert_relative_eq!(half.blendshape(StandardVRMBlendShape::LookLeft).unwrap(), 0.5);
+		// weights above 1 are clamped rather than overshooting
+		let mut over = tracked.clone();
+		LookAt::new().with_weight(3.0).apply(target, &mut over);
+		assert_eq!(over, full);
+
+		// eye bones are only solved when the gaze mapper writes them
+		let solved = LookAt::new().with_gaze(GazeMapper::new().with_bones(false)).solve(target, &pose);
+		assert_eq!(solved.iter().map(|transform| transform.bone.as_str()).collect::<Vec<_>>(), ["Neck", "Head"]);
+	}
+
+	#[test]
+	#[should_panic = "look-at range must not be negative"]
+	fn test_negative_range() {
+		let _ = LookAt::new().with_neck(0.3, -1.0, 0.0, 0.0);
+	}
+}
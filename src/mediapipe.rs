@@ -0,0 +1,410 @@
+//! Conversion of [MediaPipe](https://ai.google.dev/edge/mediapipe/solutions/guide) landmarks into VMC poses.
+//!
+//! [`Converter`] takes the landmark arrays produced by MediaPipe's Pose, Hands, and Face Mesh models and writes
+//! approximate bone rotations & blend shapes into a [`Pose`], which can then be sent to a marionette.
+//! Landmarks are given in MediaPipe's coordinate system (X right, Y down, in image space or world space) without
+//! mirroring, so the tracked person's left side drives the avatar's left side.
+//!
+//! Bone rotations are computed from the direction of each limb segment, so they don't include twist, and are only as
+//! accurate as the landmarks. Calibrating the converter while the person holds the avatar's rest pose (a T-pose with
+//! flat, palm-down hands and a neutral face) removes most of the remaining offset.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+//! use vmc::{VMCPose, Vec3, mediapipe::Converter};
+//!
+//! # fn world_landmarks() -> Vec<Vec3> { vec![Vec3::ZERO; 33] }
+//! let socket = vmc::performer!("127.0.0.1:39539").await?;
+//! let mut converter = Converter::new();
+//! // while the person is in a T-pose:
+//! converter.calibrate_pose(&world_landmarks())?;
+//!
+//! loop {
+//! 	let mut pose = VMCPose::new();
+//! 	converter.apply_pose(&world_landmarks(), &mut pose)?;
+//! 	socket.send_pose(&pose).await?;
+//! }
+//! # Ok(()) }) }
+//! ```
+
+use std::collections::HashMap;
+
+use glam::{Mat3, Quat, Vec3, Vec3A};
+
+use crate::{
+	VMCError, VMCPose as Pose, VMCResult, VMCStandardVRM0Bone as StandardVRM0Bone, VMCStandardVRMBlendShape as StandardVRMBlendShape,
+	ifacialmocap::perfect_sync_name
+};
+
+/// The number of landmarks produced by MediaPipe Pose.
+pub const POSE_LANDMARKS: usize = 33;
+/// The number of landmarks produced by MediaPipe Hands for each hand.
+pub const HAND_LANDMARKS: usize = 21;
+/// The number of landmarks produced by MediaPipe Face Mesh, not including the optional iris landmarks.
+pub const FACE_LANDMARKS: usize = 468;
+
+/// The ratio of eye height to width at which an eye is considered closed, relative to its calibrated open ratio.
+const EYE_CLOSED: f32 = 0.4;
+/// The increase in the ratio of mouth height to width between a closed and fully open mouth.
+const MOUTH_RANGE: f32 = 0.6;
+
+/// A hand tracked by MediaPipe Hands.
+///
+/// MediaPipe's handedness labels assume a mirrored image; make sure this refers to the person's actual hand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Hand {
+	Left,
+	Right
+}
+
+impl Hand {
+	/// The direction of this hand's arm in the rest pose.
+	fn rest(self) -> Vec3 {
+		match self {
+			Hand::Left => Vec3::X,
+			Hand::Right => Vec3::NEG_X
+		}
+	}
+
+	/// The thumb, index, middle, ring, & little finger bones, from the base of each finger.
+	fn fingers(self) -> [[StandardVRM0Bone; 3]; 5] {
+		use StandardVRM0Bone::*;
+		match self {
+			Hand::Left => [
+				[LeftThumbProximal, LeftThumbIntermediate, LeftThumbDistal],
+				[LeftIndexProximal, LeftIndexIntermediate, LeftIndexDistal],
+				[LeftMiddleProximal, LeftMiddleIntermediate, LeftMiddleDistal],
+				[LeftRingProximal, LeftRingIntermediate, LeftRingDistal],
+				[LeftLittleProximal, LeftLittleIntermediate, LeftLittleDistal]
+			],
+			Hand::Right => [
+				[RightThumbProximal, RightThumbIntermediate, RightThumbDistal],
+				[RightIndexProximal, RightIndexIntermediate, RightIndexDistal],
+				[RightMiddleProximal, RightMiddleIntermediate, RightMiddleDistal],
+				[RightRingProximal, RightRingIntermediate, RightRingDistal],
+				[RightLittleProximal, RightLittleIntermediate, RightLittleDistal]
+			]
+		}
+	}
+}
+
+/// Converts MediaPipe landmarks into bone rotations & blend shapes. See the [module documentation](self).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Converter {
+	offsets: HashMap<StandardVRM0Bone, Quat>,
+	/// The ratio of eye height to width of the left & right eyes when open.
+	eye_open: [f32; 2],
+	/// The ratio of mouth height to width when closed.
+	mouth_closed: f32
+}
+
+impl Default for Converter {
+	fn default() -> Self {
+		Self {
+			offsets: HashMap::new(),
+			eye_open: [0.3; 2],
+			mouth_closed: 0.0
+		}
+	}
+}
+
+impl Converter {
+	/// Creates an uncalibrated converter.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Sets the calibration offset of a bone, which is removed from the bone's rotation after conversion.
+	pub fn with_offset(mut self, bone: StandardVRM0Bone, offset: Quat) -> Self {
+		self.offsets.insert(bone, offset);
+		self
+	}
+
+	/// Returns the calibration offset of a bone.
+	pub fn offset(&self, bone: StandardVRM0Bone) -> Quat {
+		self.offsets.get(&bone).copied().unwrap_or(Quat::IDENTITY)
+	}
+
+	/// Calibrates the body bones from MediaPipe Pose landmarks of the person in a T-pose.
+	pub fn calibrate_pose(&mut self, landmarks: &[Vec3]) -> VMCResult<()> {
+		self.offsets.extend(pose_rotations(landmarks)?);
+		Ok(())
+	}
+
+	/// Calibrates the finger bones of a hand from MediaPipe Hands landmarks of the hand held flat, palm down, with the
+	/// fingers extended.
+	pub fn calibrate_hand(&mut self, hand: Hand, landmarks: &[Vec3]) -> VMCResult<()> {
+		self.offsets.extend(hand_rotations(hand, landmarks)?);
+		Ok(())
+	}
+
+	/// Calibrates blend shapes & the head rotation from MediaPipe Face Mesh landmarks of a neutral face with open eyes
+	/// and a closed mouth, looking at the camera.
+	pub fn calibrate_face(&mut self, landmarks: &[Vec3]) -> VMCResult<()> {
+		let face = FaceMetrics::new(landmarks)?;
+		self.eye_open = face.eyes;
+		self.mouth_closed = face.mouth;
+		self.offsets.insert(StandardVRM0Bone::Head, face.head);
+		Ok(())
+	}
+
+	/// Writes the rotations of the hips, spine, arms, legs, & feet computed from MediaPipe Pose landmarks into `pose`.
+	///
+	/// `landmarks` must contain all [`POSE_LANDMARKS`]; world landmarks give the best results.
+	pub fn apply_pose(&self, landmarks: &[Vec3], pose: &mut Pose) -> VMCResult<()> {
+		self.apply_rotations(pose_rotations(landmarks)?, pose);
+		Ok(())
+	}
+
+	/// Writes the rotations of a hand's finger bones computed from MediaPipe Hands landmarks into `pose`.
+	///
+	/// `landmarks` must contain all [`HAND_LANDMARKS`]. Finger rotations are relative to the hand, so they can be
+	/// combined with [`Converter::apply_pose`].
+	pub fn apply_hand(&self, hand: Hand, landmarks: &[Vec3], pose: &mut Pose) -> VMCResult<()> {
+		self.apply_rotations(hand_rotations(hand, landmarks)?, pose);
+		Ok(())
+	}
+
+	/// Writes the [`Blink_L`](StandardVRMBlendShape::BlinkL), [`Blink_R`](StandardVRMBlendShape::BlinkR), &
+	/// [`A`](StandardVRMBlendShape::A) blend shapes and the [`Head`](StandardVRM0Bone::Head) rotation computed from
+	/// MediaPipe Face Mesh landmarks into `pose`.
+	///
+	/// `landmarks` must contain at least [`FACE_LANDMARKS`]. The head rotation is relative to the camera.
+	pub fn apply_face(&self, landmarks: &[Vec3], pose: &mut Pose) -> VMCResult<()> {
+		let face = FaceMetrics::new(landmarks)?;
+		let blink = |eye: usize| 1.0 - ((face.eyes[eye] / self.eye_open[eye] - EYE_CLOSED) / (1.0 - EYE_CLOSED)).clamp(0.0, 1.0);
+		pose.set_blendshape(StandardVRMBlendShape::BlinkL, blink(0));
+		pose.set_blendshape(StandardVRMBlendShape::BlinkR, blink(1));
+		pose.set_blendshape(StandardVRMBlendShape::A, ((face.mouth - self.mouth_closed) / MOUTH_RANGE).clamp(0.0, 1.0));
+		self.apply_rotations([(StandardVRM0Bone::Head, face.head)], pose);
+		Ok(())
+	}
+
+	fn apply_rotations(&self, rotations: impl IntoIterator<Item = (StandardVRM0Bone, Quat)>, pose: &mut Pose) {
+		for (bone, rotation) in rotations {
+			pose.set_bone(bone, Vec3A::ZERO, (rotation * self.offset(bone).inverse()).normalize());
+		}
+	}
+}
+
+/// Writes the blend shape scores of MediaPipe's Face Landmarker (e.g. `eyeBlinkLeft`) into `pose` using their
+/// "perfect sync" names (e.g. `EyeBlinkLeft`). The `_neutral` score is skipped.
+pub fn apply_face_blendshapes<S: AsRef<str>>(scores: impl IntoIterator<Item = (S, f32)>, pose: &mut Pose) {
+	for (name, score) in scores {
+		let name = name.as_ref();
+		if name != "_neutral" {
+			pose.set_blendshape(perfect_sync_name(name).as_str(), score);
+		}
+	}
+}
+
+fn check_len(landmarks: &[Vec3], expected: usize) -> VMCResult<()> {
+	if landmarks.len() < expected {
+		return Err(VMCError::InvalidLandmarks(format!("expected {expected} landmarks, got {}", landmarks.len())));
+	}
+	Ok(())
+}
+
+/// Converts a landmark from MediaPipe's coordinate system (Y down) to Unity's (Y up).
+fn point(landmarks: &[Vec3], index: usize) -> Vec3 {
+	let landmark = landmarks[index];
+	Vec3::new(landmark.x, -landmark.y, landmark.z)
+}
+
+/// Returns the rotation which turns `rest` to point from `from` towards `to`.
+fn swing(rest: Vec3, from: Vec3, to: Vec3) -> Quat {
+	match (to - from).try_normalize() {
+		Some(direction) => Quat::from_rotation_arc(rest, direction),
+		None => Quat::IDENTITY
+	}
+}
+
+/// Returns the rotation which maps +X to `right` and +Y to (approximately) `up`.
+fn basis(right: Vec3, up: Vec3) -> Quat {
+	let (Some(x), Some(z)) = (right.try_normalize(), right.cross(up).try_normalize()) else {
+		return Quat::IDENTITY;
+	};
+	Quat::from_mat3(&Mat3::from_cols(x, z.cross(x), z)).normalize()
+}
+
+/// Converts a chain of landmarks into local bone rotations. Each segment is a bone, the direction it points in the
+/// rest pose, and the landmarks at its start & end.
+fn chain(parent: Quat, segments: &[(StandardVRM0Bone, Vec3, Vec3, Vec3)], rotations: &mut Vec<(StandardVRM0Bone, Quat)>) {
+	let mut parent = parent;
+	for &(bone, rest, from, to) in segments {
+		let global = swing(rest, from, to);
+		rotations.push((bone, parent.inverse() * global));
+		parent = global;
+	}
+}
+
+fn pose_rotations(landmarks: &[Vec3]) -> VMCResult<Vec<(StandardVRM0Bone, Quat)>> {
+	use StandardVRM0Bone::*;
+
+	check_len(landmarks, POSE_LANDMARKS)?;
+	let p = |index| point(landmarks, index);
+	let (left_shoulder, right_shoulder, left_elbow, right_elbow, left_wrist, right_wrist) = (p(11), p(12), p(13), p(14), p(15), p(16));
+	let (left_hip, right_hip, left_knee, right_knee, left_ankle, right_ankle) = (p(23), p(24), p(25), p(26), p(27), p(28));
+	let (left_toe, right_toe) = (p(31), p(32));
+
+	let up = (left_shoulder + right_shoulder - left_hip - right_hip) / 2.0;
+	let hips = basis(left_hip - right_hip, up);
+	let chest = basis(left_shoulder - right_shoulder, up);
+	let mut rotations = vec![(Hips, hips), (Spine, hips.inverse() * chest)];
+	chain(chest, &[(LeftUpperArm, Vec3::X, left_shoulder, left_elbow), (LeftLowerArm, Vec3::X, left_elbow, left_wrist)], &mut rotations);
+	chain(chest, &[(RightUpperArm, Vec3::NEG_X, right_shoulder, right_elbow), (RightLowerArm, Vec3::NEG_X, right_elbow, right_wrist)], &mut rotations);
+	for (leg, (hip, knee, ankle, toe)) in [
+		([LeftUpperLeg, LeftLowerLeg, LeftFoot], (left_hip, left_knee, left_ankle, left_toe)),
+		([RightUpperLeg, RightLowerLeg, RightFoot], (right_hip, right_knee, right_ankle, right_toe))
+	] {
+		// the avatar faces -Z, so toes point towards -Z in the rest pose
+		chain(hips, &[(leg[0], Vec3::NEG_Y, hip, knee), (leg[1], Vec3::NEG_Y, knee, ankle), (leg[2], Vec3::NEG_Z, ankle, toe)], &mut rotations);
+	}
+	Ok(rotations)
+}
+
+fn hand_rotations(hand: Hand, landmarks: &[Vec3]) -> VMCResult<Vec<(StandardVRM0Bone, Quat)>> {
+	check_len(landmarks, HAND_LANDMARKS)?;
+	let p = |index| point(landmarks, index);
+	let rest = hand.rest();
+
+	// with the palm down, the index finger is in front of the little finger (towards -Z)
+	let right = (p(9) - p(0)) * rest.x;
+	let palm = basis(right, (p(17) - p(5)).cross(right));
+	let mut rotations = Vec::with_capacity(15);
+	for (bones, base) in hand.fingers().into_iter().zip([1, 5, 9, 13, 17]) {
+		let segments = [0, 1, 2].map(|i| (bones[i], rest, p(base + i), p(base + i + 1)));
+		chain(palm, &segments, &mut rotations);
+	}
+	Ok(rotations)
+}
+
+/// Measurements of a face from Face Mesh landmarks.
+struct FaceMetrics {
+	/// The ratio of eye height to width of the left & right eyes.
+	eyes: [f32; 2],
+	/// The ratio of mouth height to width.
+	mouth: f32,
+	head: Quat
+}
+
+impl FaceMetrics {
+	fn new(landmarks: &[Vec3]) -> VMCResult<Self> {
+		check_len(landmarks, FACE_LANDMARKS)?;
+		let p = |index| point(landmarks, index);
+		let ratio = |top, bottom, left, right| p(top).distance(p(bottom)) / p(left).distance(p(right)).max(f32::EPSILON);
+		Ok(Self {
+			eyes: [ratio(386, 374, 362, 263), ratio(159, 145, 33, 133)],
+			mouth: ratio(13, 14, 61, 291),
+			// from the outer corner of the right eye to the left eye, and from the chin to the forehead
+			head: basis(p(263) - p(33), p(10) - p(152))
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_pose_conversion() -> VMCResult<()> {
+		// a T-pose in MediaPipe coordinates, with the left arm raised straight up
+		let mut landmarks = vec![Vec3::ZERO; POSE_LANDMARKS];
+		for (index, point) in [
+			(11, Vec3::new(0.2, -0.5, 0.0)),
+			(12, Vec3::new(-0.2, -0.5, 0.0)),
+			(13, Vec3::new(0.2, -0.8, 0.0)),
+			(14, Vec3::new(-0.5, -0.5, 0.0)),
+			(15, Vec3::new(0.2, -1.1, 0.0)),
+			(16, Vec3::new(-0.8, -0.5, 0.0)),
+			(23, Vec3::new(0.1, 0.0, 0.0)),
+			(24, Vec3::new(-0.1, 0.0, 0.0)),
+			(25, Vec3::new(0.1, 0.4, 0.0)),
+			(26, Vec3::new(-0.1, 0.4, 0.0)),
+			(27, Vec3::new(0.1, 0.8, 0.0)),
+			(28, Vec3::new(-0.1, 0.8, 0.0)),
+			(31, Vec3::new(0.1, 0.8, -0.1)),
+			(32, Vec3::new(-0.1, 0.8, -0.1))
+		] {
+			landmarks[index] = point;
+		}
+
+		let mut pose = Pose::new();
+		Converter::new().apply_pose(&landmarks, &mut pose)?;
+		let rotation = |bone: StandardVRM0Bone| pose.bone(bone).unwrap().rotation;
+		assert!(rotation(StandardVRM0Bone::Hips).abs_diff_eq(Quat::IDENTITY, 1e-6));
+		assert!(rotation(StandardVRM0Bone::RightUpperArm).abs_diff_eq(Quat::IDENTITY, 1e-6));
+		assert!(rotation(StandardVRM0Bone::LeftFoot).abs_diff_eq(Quat::IDENTITY, 1e-6));
+		assert!((rotation(StandardVRM0Bone::LeftUpperArm) * Vec3::X).abs_diff_eq(Vec3::Y, 1e-6));
+		assert!(rotation(StandardVRM0Bone::LeftLowerArm).abs_diff_eq(Quat::IDENTITY, 1e-6));
+
+		// calibrating in this pose makes it the rest pose
+		let mut converter = Converter::new();
+		converter.calibrate_pose(&landmarks)?;
+		converter.apply_pose(&landmarks, &mut pose)?;
+		assert!(
+			pose.bone(StandardVRM0Bone::LeftUpperArm)
+				.unwrap()
+				.rotation
+				.abs_diff_eq(Quat::IDENTITY, 1e-6)
+		);
+
+		assert!(Converter::new().apply_hand(Hand::Left, &landmarks[..20], &mut pose).is_err());
+		Ok(())
+	}
+
+	#[test]
+	fn test_invalid_landmarks() {
+		let mut converter = Converter::new();
+		let mut pose = Pose::new();
+		let short = vec![Vec3::ONE; FACE_LANDMARKS - 1];
+		let check = |result: VMCResult<()>, expected: usize, got: usize| match result {
+			Err(VMCError::InvalidLandmarks(message)) => assert_eq!(message, format!("expected {expected} landmarks, got {got}")),
+			result => panic!("expected InvalidLandmarks, got {result:?}")
+		};
+
+		check(converter.apply_pose(&short[..POSE_LANDMARKS - 1], &mut pose), POSE_LANDMARKS, POSE_LANDMARKS - 1);
+		check(converter.apply_hand(Hand::Right, &[], &mut pose), HAND_LANDMARKS, 0);
+		check(converter.apply_face(&short, &mut pose), FACE_LANDMARKS, FACE_LANDMARKS - 1);
+		check(converter.calibrate_pose(&short[..1]), POSE_LANDMARKS, 1);
+		check(converter.calibrate_hand(Hand::Left, &short[..HAND_LANDMARKS - 1]), HAND_LANDMARKS, HAND_LANDMARKS - 1);
+		check(converter.calibrate_face(&short[..POSE_LANDMARKS]), FACE_LANDMARKS, POSE_LANDMARKS);
+
+		// failed calls leave both the pose and the calibration untouched
+		assert_eq!(pose, Pose::new());
+		assert_eq!(converter.offset(StandardVRM0Bone::Hips), Quat::IDENTITY);
+		assert_eq!(converter.offset(StandardVRM0Bone::Head), Quat::IDENTITY);
+	}
+
+	#[test]
+	fn test_degenerate_landmarks() -> VMCResult<()> {
+		// landmarks that all coincide (e.g. a lost track) have no direction, so bones fall back to their rest pose
+		let mut pose = Pose::new();
+		let converter = Converter::new();
+		converter.apply_pose(&[Vec3::ZERO; POSE_LANDMARKS], &mut pose)?;
+		converter.apply_hand(Hand::Left, &[Vec3::ZERO; HAND_LANDMARKS], &mut pose)?;
+		converter.apply_face(&vec![Vec3::ZERO; FACE_LANDMARKS], &mut pose)?;
+		for transform in pose.bones.values() {
+			assert_eq!(transform.rotation, Quat::IDENTITY);
+		}
+		// a face with no measurable eyes reads as fully closed, never NaN
+		assert_eq!(pose.blendshape(StandardVRMBlendShape::BlinkL), Some(1.0));
+		assert_eq!(pose.blendshape(StandardVRMBlendShape::A), Some(0.0));
+
+		// extra landmarks, like the iris landmarks of the 478-point mesh, are ignored
+		converter.apply_face(&vec![Vec3::ZERO; FACE_LANDMARKS + 10], &mut pose)?;
+		Ok(())
+	}
+
+	#[test]
+	fn test_face_blendshapes() {
+		let mut pose = Pose::new();
+		apply_face_blendshapes([("_neutral", 0.9), ("eyeBlinkLeft", 0.5), ("jawOpen", 0.25)], &mut pose);
+		assert_eq!(pose.blendshape("EyeBlinkLeft"), Some(0.5));
+		assert_eq!(pose.blendshape("JawOpen"), Some(0.25));
+		assert_eq!(pose.blendshape("_neutral"), None);
+		assert_eq!(pose.blendshape("Neutral"), None);
+	}
+}
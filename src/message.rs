@@ -1,10 +1,18 @@
 //! Submodule for Virtual Motion Capture-specific messages.
 
-use std::{str::FromStr, sync::OnceLock, time::Instant};
+use std::{fmt, str::FromStr, sync::OnceLock, time::Instant};
 
-use glam::{Quat, Vec3A};
+use glam::{Affine3A, Mat4, Quat, Vec3A};
+use smallvec::{SmallVec, smallvec};
 
-use crate::{IntoOSCMessage, OSCPacket, OSCType, VMCError, VMCResult, osc::OSCMessage};
+use crate::{
+	IntoOSCMessage, OSCPacket, OSCType, VMCError, VMCResult,
+	name::Name,
+	osc::{
+		DecodeLimits, OSCArgs, OSCMessage,
+		decoder::{self, ArgRef}
+	}
+};
 
 /// Root Transform message (`/VMC/Ext/Root/Pos`)
 ///
@@ -20,10 +28,10 @@ pub struct RootTransform {
 
 impl RootTransform {
 	/// Creates a new root transform message.
-	pub fn new(position: impl Into<Vec3A>, rotation: Quat) -> Self {
+	pub fn new(position: impl Into<Vec3A>, rotation: impl Into<Quat>) -> Self {
 		Self {
 			position: position.into(),
-			rotation,
+			rotation: rotation.into(),
 			scale: None,
 			offset: None
 		}
@@ -31,19 +39,71 @@ impl RootTransform {
 
 	/// Creates a new root transform message with additional scale & offset parameters, which can be used to adjust the
 	/// size and position of the virtual avatar to match the physical body.
-	pub fn new_mr(position: impl Into<Vec3A>, rotation: Quat, scale: impl Into<Vec3A>, offset: impl Into<Vec3A>) -> Self {
+	pub fn new_mr(position: impl Into<Vec3A>, rotation: impl Into<Quat>, scale: impl Into<Vec3A>, offset: impl Into<Vec3A>) -> Self {
 		Self {
 			position: position.into(),
-			rotation,
+			rotation: rotation.into(),
 			scale: Some(scale.into()),
 			offset: Some(offset.into())
 		}
 	}
+
+	/// Creates a root transform from an affine transform, decomposing it into its translation, rotation, and scale.
+	///
+	/// The scale is only included if it is not `1`. Since the offset can't be distinguished from the position, the
+	/// offset is always `None`.
+	pub fn from_affine(affine: Affine3A) -> Self {
+		let (scale, rotation, position) = affine.to_scale_rotation_translation();
+		Self {
+			position: position.into(),
+			rotation,
+			scale: (!scale.abs_diff_eq(glam::Vec3::ONE, 1e-6)).then_some(scale.into()),
+			offset: None
+		}
+	}
+
+	/// Creates a root transform from a 4x4 transformation matrix. See [`RootTransform::from_affine`].
+	pub fn from_mat4(mat: Mat4) -> Self {
+		Self::from_affine(Affine3A::from_mat4(mat))
+	}
+
+	/// Converts this root transform into an affine transform.
+	///
+	/// If present, the scale is applied to the model before rotation, and the offset is subtracted from the position,
+	/// matching the behavior of common marionettes.
+	pub fn to_affine(&self) -> Affine3A {
+		let scale = self.scale.unwrap_or(Vec3A::ONE);
+		let translation = self.position - self.offset.unwrap_or(Vec3A::ZERO);
+		Affine3A::from_scale_rotation_translation(scale.into(), self.rotation, translation.into())
+	}
+
+	/// Converts this root transform into a 4x4 transformation matrix. See [`RootTransform::to_affine`].
+	pub fn to_mat4(&self) -> Mat4 {
+		Mat4::from(self.to_affine())
+	}
+
+	/// Interpolates between this transform and `other`, linearly interpolating the position (and scale & offset, if
+	/// both transforms have them) and spherically interpolating the rotation.
+	///
+	/// When `t` is `0.0`, the result is equal to `self`; when `t` is `1.0`, the result is equal to `other`.
+	pub fn lerp(&self, other: &RootTransform, t: f32) -> RootTransform {
+		let (scale, offset) = match (self.scale, self.offset, other.scale, other.offset) {
+			(Some(s0), Some(o0), Some(s1), Some(o1)) => (Some(s0.lerp(s1, t)), Some(o0.lerp(o1, t))),
+			_ if t < 0.5 => (self.scale, self.offset),
+			_ => (other.scale, other.offset)
+		};
+		RootTransform {
+			position: self.position.lerp(other.position, t),
+			rotation: self.rotation.slerp(other.rotation, t),
+			scale,
+			offset
+		}
+	}
 }
 
 impl IntoOSCMessage for RootTransform {
 	fn into_osc_message(self) -> crate::osc::OSCMessage {
-		let mut args: Vec<OSCType> = vec![
+		let mut args: OSCArgs = smallvec![
 			"root".into(),
 			self.position.x.into(),
 			self.position.y.into(),
@@ -64,7 +124,7 @@ impl IntoOSCMessage for RootTransform {
 /// Standard bones used by VRM 0.x.
 ///
 /// <https://github.com/vrm-c/vrm-specification/blob/master/specification/0.0/README.md#defined-bones>
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum StandardVRM0Bone {
 	Hips,
@@ -125,8 +185,9 @@ pub enum StandardVRM0Bone {
 	RightLittleDistal
 }
 
-impl AsRef<str> for StandardVRM0Bone {
-	fn as_ref(&self) -> &'static str {
+impl StandardVRM0Bone {
+	/// Returns the name of the bone.
+	pub const fn as_str(&self) -> &'static str {
 		match self {
 			StandardVRM0Bone::Hips => "Hips",
 			StandardVRM0Bone::LeftUpperLeg => "LeftUpperLeg",
@@ -188,9 +249,15 @@ impl AsRef<str> for StandardVRM0Bone {
 	}
 }
 
-impl ToString for StandardVRM0Bone {
-	fn to_string(&self) -> String {
-		self.as_ref().to_owned()
+impl AsRef<str> for StandardVRM0Bone {
+	fn as_ref(&self) -> &str {
+		self.as_str()
+	}
+}
+
+impl fmt::Display for StandardVRM0Bone {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.as_ref())
 	}
 }
 
@@ -287,7 +354,9 @@ impl PartialEq<StandardVRM0Bone> for String {
 #[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BoneTransform {
-	pub bone: String,
+	/// The name of the bone. This was a `String` before 0.5; [`Name`] derefs to `str` and compares equal to strings,
+	/// and `String::from(name)` converts it back.
+	pub bone: Name,
 	pub position: Vec3A,
 	pub rotation: Quat
 }
@@ -295,12 +364,44 @@ pub struct BoneTransform {
 impl BoneTransform {
 	/// Creates a new bone transform message.
 	///
-	/// `bone` is the name of the bone; see [`StandardVRM0Bone`] for standard VRM 0.x bone names.
-	pub fn new(bone: impl ToString, position: impl Into<Vec3A>, rotation: Quat) -> Self {
+	/// `bone` is the name of the bone; see [`StandardVRM0Bone`] for standard VRM 0.x bone names. With the `mint`
+	/// feature, the position & rotation can also be given as [`mint`](https://docs.rs/mint) types.
+	pub fn new(bone: impl Into<Name>, position: impl Into<Vec3A>, rotation: impl Into<Quat>) -> Self {
 		Self {
-			bone: bone.to_string(),
+			bone: bone.into(),
 			position: position.into(),
-			rotation
+			rotation: rotation.into()
+		}
+	}
+
+	/// Creates a bone transform from an affine transform. Any scale in the transform is discarded.
+	pub fn from_affine(bone: impl Into<Name>, affine: Affine3A) -> Self {
+		let (_, rotation, position) = affine.to_scale_rotation_translation();
+		Self::new(bone, position, rotation)
+	}
+
+	/// Creates a bone transform from a 4x4 transformation matrix. Any scale in the matrix is discarded.
+	pub fn from_mat4(bone: impl Into<Name>, mat: Mat4) -> Self {
+		Self::from_affine(bone, Affine3A::from_mat4(mat))
+	}
+
+	/// Converts this bone transform into an affine transform, relative to the bone's parent.
+	pub fn to_affine(&self) -> Affine3A {
+		Affine3A::from_rotation_translation(self.rotation, self.position.into())
+	}
+
+	/// Converts this bone transform into a 4x4 transformation matrix, relative to the bone's parent.
+	pub fn to_mat4(&self) -> Mat4 {
+		Mat4::from_rotation_translation(self.rotation, self.position.into())
+	}
+
+	/// Interpolates between this transform and `other`, linearly interpolating the position and spherically
+	/// interpolating the rotation. The bone name of `self` is kept.
+	pub fn lerp(&self, other: &BoneTransform, t: f32) -> BoneTransform {
+		BoneTransform {
+			bone: self.bone.clone(),
+			position: self.position.lerp(other.position, t),
+			rotation: self.rotation.slerp(other.rotation, t)
 		}
 	}
 }
@@ -315,7 +416,7 @@ impl IntoOSCMessage for BoneTransform {
 }
 
 /// The type of device used in [`DeviceTransform`] (HMD, controller, or independent tracker).
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DeviceType {
 	HMD,
@@ -333,9 +434,9 @@ impl AsRef<str> for DeviceType {
 	}
 }
 
-impl ToString for DeviceType {
-	fn to_string(&self) -> String {
-		self.as_ref().to_owned()
+impl fmt::Display for DeviceType {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.as_ref())
 	}
 }
 
@@ -368,15 +469,36 @@ impl DeviceTransform {
 	///
 	/// - `joint` is the OpenVR serial no.
 	/// - `local` determines whether the position is in raw device scale (`true`) or avatar scale (`false`).
-	pub fn new(device: DeviceType, joint: impl ToString, position: impl Into<Vec3A>, rotation: Quat, local: bool) -> Self {
+	pub fn new(device: DeviceType, joint: impl ToString, position: impl Into<Vec3A>, rotation: impl Into<Quat>, local: bool) -> Self {
 		Self {
 			device,
 			joint: joint.to_string(),
 			position: position.into(),
-			rotation,
+			rotation: rotation.into(),
 			local
 		}
 	}
+
+	/// Creates a device transform from an affine transform. Any scale in the transform is discarded.
+	pub fn from_affine(device: DeviceType, joint: impl ToString, affine: Affine3A, local: bool) -> Self {
+		let (_, rotation, position) = affine.to_scale_rotation_translation();
+		Self::new(device, joint, position, rotation, local)
+	}
+
+	/// Creates a device transform from a 4x4 transformation matrix. Any scale in the matrix is discarded.
+	pub fn from_mat4(device: DeviceType, joint: impl ToString, mat: Mat4, local: bool) -> Self {
+		Self::from_affine(device, joint, Affine3A::from_mat4(mat), local)
+	}
+
+	/// Converts this device transform into an affine transform.
+	pub fn to_affine(&self) -> Affine3A {
+		Affine3A::from_rotation_translation(self.rotation, self.position.into())
+	}
+
+	/// Converts this device transform into a 4x4 transformation matrix.
+	pub fn to_mat4(&self) -> Mat4 {
+		Mat4::from_rotation_translation(self.rotation, self.position.into())
+	}
 }
 
 impl IntoOSCMessage for DeviceTransform {
@@ -415,8 +537,9 @@ pub enum StandardVRMBlendShape {
 	BlinkR
 }
 
-impl AsRef<str> for StandardVRMBlendShape {
-	fn as_ref(&self) -> &str {
+impl StandardVRMBlendShape {
+	/// Returns the name of the blend shape.
+	pub const fn as_str(&self) -> &'static str {
 		match self {
 			StandardVRMBlendShape::Neutral => "Neutral",
 			StandardVRMBlendShape::A => "A",
@@ -439,9 +562,15 @@ impl AsRef<str> for StandardVRMBlendShape {
 	}
 }
 
-impl ToString for StandardVRMBlendShape {
-	fn to_string(&self) -> String {
-		self.as_ref().to_owned()
+impl AsRef<str> for StandardVRMBlendShape {
+	fn as_ref(&self) -> &str {
+		self.as_str()
+	}
+}
+
+impl fmt::Display for StandardVRMBlendShape {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.as_ref())
 	}
 }
 
@@ -499,7 +628,8 @@ impl PartialEq<StandardVRMBlendShape> for String {
 #[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BlendShape {
-	pub key: String,
+	/// The name of the blend shape. This was a `String` before 0.5; see [`BoneTransform::bone`].
+	pub key: Name,
 	pub value: f32
 }
 
@@ -507,8 +637,8 @@ impl BlendShape {
 	/// Creates a new blendshape message.
 	///
 	/// See [`StandardVRMBlendShape`] for standard blendshapes.
-	pub fn new(key: impl ToString, value: f32) -> Self {
-		Self { key: key.to_string(), value }
+	pub fn new(key: impl Into<Name>, value: f32) -> Self {
+		Self { key: key.into(), value }
 	}
 }
 
@@ -696,7 +826,7 @@ impl State {
 
 impl IntoOSCMessage for State {
 	fn into_osc_message(self) -> OSCMessage {
-		let mut args: Vec<OSCType> = vec![self.model_state.into()];
+		let mut args: OSCArgs = smallvec![self.model_state.into()];
 		if let Some((calibration_mode, calibration_state)) = self.calibration_state {
 			args.extend([calibration_state.into(), calibration_mode.into()]);
 			if let Some(tracking_state) = self.tracking_state {
@@ -730,6 +860,107 @@ impl IntoOSCMessage for Time {
 	}
 }
 
+/// Receive Enable message (`/VMC/Ext/Rcv`)
+///
+/// Reports whether an application receives VMC data, on which port, and optionally on which IP address, so that
+/// senders can follow it. Like other status messages which aren't part of the motion stream, it isn't returned by
+/// [`parse`]; use [`ReceiveEnable::from_osc_message`] on received messages instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReceiveEnable {
+	pub enable: bool,
+	pub port: u16,
+	pub addr: Option<String>
+}
+
+impl ReceiveEnable {
+	/// Creates a new receive enable message.
+	pub fn new(enable: bool, port: u16) -> Self {
+		Self { enable, port, addr: None }
+	}
+
+	/// Sets the IP address the application receives on.
+	pub fn with_addr(mut self, addr: impl ToString) -> Self {
+		self.addr = Some(addr.to_string());
+		self
+	}
+
+	/// Parses a receive enable message, returning `None` if the message isn't one.
+	pub fn from_osc_message(message: &OSCMessage) -> Option<Self> {
+		match message.as_tuple() {
+			("/VMC/Ext/Rcv", &[OSCType::Int(enable), OSCType::Int(port), ref rest @ ..]) => Some(Self {
+				enable: enable != 0,
+				port: port.try_into().ok()?,
+				addr: match rest {
+					[OSCType::String(addr), ..] => Some(addr.clone()),
+					_ => None
+				}
+			}),
+			_ => None
+		}
+	}
+}
+
+impl IntoOSCMessage for ReceiveEnable {
+	fn into_osc_message(self) -> OSCMessage {
+		let mut args: OSCArgs = smallvec![OSCType::Int(self.enable as i32), OSCType::Int(self.port as i32)];
+		if let Some(addr) = self.addr {
+			args.push(OSCType::String(addr));
+		}
+		OSCMessage::new("/VMC/Ext/Rcv", args)
+	}
+}
+
+/// Option String message (`/VMC/Ext/Opt`)
+///
+/// An application-defined string describing the sender's configuration. Like [`ReceiveEnable`], it isn't returned by
+/// [`parse`]; use [`OptionString::from_osc_message`] on received messages instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OptionString(pub String);
+
+impl OptionString {
+	/// Creates a new option string message.
+	pub fn new(option: impl ToString) -> Self {
+		Self(option.to_string())
+	}
+
+	/// Parses an option string message, returning `None` if the message isn't one.
+	pub fn from_osc_message(message: &OSCMessage) -> Option<Self> {
+		match message.as_tuple() {
+			("/VMC/Ext/Opt", [OSCType::String(option), ..]) => Some(Self(option.clone())),
+			_ => None
+		}
+	}
+}
+
+impl IntoOSCMessage for OptionString {
+	fn into_osc_message(self) -> OSCMessage {
+		OSCMessage::new("/VMC/Ext/Opt", (self.0,))
+	}
+}
+
+/// Request Information message (`/VMC/Ext/Set/Req`)
+///
+/// Asks the receiver to send its status messages, such as [`State`] & [`ReceiveEnable`], back. Like [`ReceiveEnable`],
+/// it isn't returned by [`parse`]; use [`RequestInfo::from_osc_message`] on received messages instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RequestInfo;
+
+impl RequestInfo {
+	/// Parses a request information message, returning `None` if the message isn't one.
+	pub fn from_osc_message(message: &OSCMessage) -> Option<Self> {
+		(message.addr == "/VMC/Ext/Set/Req").then_some(RequestInfo)
+	}
+}
+
+impl IntoOSCMessage for RequestInfo {
+	fn into_osc_message(self) -> OSCMessage {
+		OSCMessage::new("/VMC/Ext/Set/Req", ())
+	}
+}
+
 /// Contains any possible message that can be sent over VMC protocol.
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -793,224 +1024,249 @@ impl From<Time> for VMCMessage {
 	}
 }
 
-fn flatten_packet(packet: OSCPacket) -> Vec<OSCMessage> {
-	match packet {
-		OSCPacket::Bundle(bundle) => bundle.content.into_iter().flat_map(flatten_packet).collect(),
-		OSCPacket::Message(message) => vec![message]
-	}
-}
-
 /// Parses an [`OSCPacket`] into its contained [`VMCMessage`]s. This will automatically flatten message bundles and
 /// handle the parsing to different message types. Returns an error upon encountering an unimplemented packet.
 pub fn parse(osc_packet: OSCPacket) -> VMCResult<Vec<VMCMessage>> {
-	let messages = flatten_packet(osc_packet);
-	messages
+	osc_packet
+		.into_messages()
 		.into_iter()
-		.map(|msg| match msg.as_tuple() {
-			(
-				"/VMC/Ext/Root/Pos",
-				&[
-					OSCType::String(_),
-					OSCType::Float(p_x),
-					OSCType::Float(p_y),
-					OSCType::Float(p_z),
-					OSCType::Float(r_x),
-					OSCType::Float(r_y),
-					OSCType::Float(r_z),
-					OSCType::Float(r_w)
-				]
-			) => Ok(VMCMessage::RootTransform(RootTransform::new(Vec3A::new(p_x, p_y, p_z), Quat::from_array([r_x, r_y, r_z, r_w])))),
-			(
-				"/VMC/Ext/Root/Pos",
-				&[
-					OSCType::String(_),
-					OSCType::Float(p_x),
-					OSCType::Float(p_y),
-					OSCType::Float(p_z),
-					OSCType::Float(r_x),
-					OSCType::Float(r_y),
-					OSCType::Float(r_z),
-					OSCType::Float(r_w),
-					OSCType::Float(s_x),
-					OSCType::Float(s_y),
-					OSCType::Float(s_z),
-					OSCType::Float(o_x),
-					OSCType::Float(o_y),
-					OSCType::Float(o_z),
-					..
-				]
-			) => Ok(VMCMessage::RootTransform(RootTransform::new_mr(
-				Vec3A::new(p_x, p_y, p_z),
-				Quat::from_array([r_x, r_y, r_z, r_w]),
-				Vec3A::new(s_x, s_y, s_z),
-				Vec3A::new(o_x, o_y, o_z)
-			))),
-			(
-				"/VMC/Ext/Bone/Pos",
-				&[
-					OSCType::String(ref bone),
-					OSCType::Float(p_x),
-					OSCType::Float(p_y),
-					OSCType::Float(p_z),
-					OSCType::Float(r_x),
-					OSCType::Float(r_y),
-					OSCType::Float(r_z),
-					OSCType::Float(r_w)
-				]
-			) => Ok(VMCMessage::BoneTransform(BoneTransform::new(
-				StandardVRM0Bone::from_str(bone).map_err(|_| VMCError::UnknownBone(bone.to_string()))?,
-				Vec3A::new(p_x, p_y, p_z),
-				Quat::from_array([r_x, r_y, r_z, r_w])
-			))),
-			(
-				"/VMC/Ext/Hmd/Pos",
-				&[
-					OSCType::String(ref joint),
-					OSCType::Float(p_x),
-					OSCType::Float(p_y),
-					OSCType::Float(p_z),
-					OSCType::Float(r_x),
-					OSCType::Float(r_y),
-					OSCType::Float(r_z),
-					OSCType::Float(r_w),
-					..
-				]
-			) => Ok(VMCMessage::DeviceTransform(DeviceTransform::new(
-				DeviceType::HMD,
-				joint.to_owned(),
-				Vec3A::new(p_x, p_y, p_z),
-				Quat::from_array([r_x, r_y, r_z, r_w]),
-				false
-			))),
-			(
-				"/VMC/Ext/Hmd/Pos/Local",
-				&[
-					OSCType::String(ref joint),
-					OSCType::Float(p_x),
-					OSCType::Float(p_y),
-					OSCType::Float(p_z),
-					OSCType::Float(r_x),
-					OSCType::Float(r_y),
-					OSCType::Float(r_z),
-					OSCType::Float(r_w),
-					..
-				]
-			) => Ok(VMCMessage::DeviceTransform(DeviceTransform::new(
-				DeviceType::HMD,
-				joint.to_owned(),
-				Vec3A::new(p_x, p_y, p_z),
-				Quat::from_array([r_x, r_y, r_z, r_w]),
-				true
-			))),
-			(
-				"/VMC/Ext/Con/Pos",
-				&[
-					OSCType::String(ref joint),
-					OSCType::Float(p_x),
-					OSCType::Float(p_y),
-					OSCType::Float(p_z),
-					OSCType::Float(r_x),
-					OSCType::Float(r_y),
-					OSCType::Float(r_z),
-					OSCType::Float(r_w),
-					..
-				]
-			) => Ok(VMCMessage::DeviceTransform(DeviceTransform::new(
-				DeviceType::Controller,
-				joint.to_owned(),
-				Vec3A::new(p_x, p_y, p_z),
-				Quat::from_array([r_x, r_y, r_z, r_w]),
-				false
-			))),
-			(
-				"/VMC/Ext/Con/Pos/Local",
-				&[
-					OSCType::String(ref joint),
-					OSCType::Float(p_x),
-					OSCType::Float(p_y),
-					OSCType::Float(p_z),
-					OSCType::Float(r_x),
-					OSCType::Float(r_y),
-					OSCType::Float(r_z),
-					OSCType::Float(r_w),
-					..
-				]
-			) => Ok(VMCMessage::DeviceTransform(DeviceTransform::new(
-				DeviceType::Controller,
-				joint.to_owned(),
-				Vec3A::new(p_x, p_y, p_z),
-				Quat::from_array([r_x, r_y, r_z, r_w]),
-				true
-			))),
-			(
-				"/VMC/Ext/Tra/Pos",
-				&[
-					OSCType::String(ref joint),
-					OSCType::Float(p_x),
-					OSCType::Float(p_y),
-					OSCType::Float(p_z),
-					OSCType::Float(r_x),
-					OSCType::Float(r_y),
-					OSCType::Float(r_z),
-					OSCType::Float(r_w),
-					..
-				]
-			) => Ok(VMCMessage::DeviceTransform(DeviceTransform::new(
-				DeviceType::Tracker,
-				joint.to_owned(),
-				Vec3A::new(p_x, p_y, p_z),
-				Quat::from_array([r_x, r_y, r_z, r_w]),
-				false
-			))),
-			(
-				"/VMC/Ext/Tra/Pos/Local",
-				&[
-					OSCType::String(ref joint),
-					OSCType::Float(p_x),
-					OSCType::Float(p_y),
-					OSCType::Float(p_z),
-					OSCType::Float(r_x),
-					OSCType::Float(r_y),
-					OSCType::Float(r_z),
-					OSCType::Float(r_w),
-					..
-				]
-			) => Ok(VMCMessage::DeviceTransform(DeviceTransform::new(
-				DeviceType::Tracker,
-				joint.to_owned(),
-				Vec3A::new(p_x, p_y, p_z),
-				Quat::from_array([r_x, r_y, r_z, r_w]),
-				true
-			))),
-			("/VMC/Ext/Blend/Val", &[OSCType::String(ref shape), OSCType::Float(val), ..]) => Ok(VMCMessage::BlendShape(BlendShape::new(shape, val))),
-			("/VMC/Ext/Blend/Apply", &[..]) => Ok(VMCMessage::ApplyBlendShapes),
-			("/VMC/Ext/OK", &[OSCType::Int(model_state)]) => Ok(VMCMessage::State(State::new(model_state.try_into().map_err(VMCError::UnknownModelState)?))),
-			("/VMC/Ext/OK", &[OSCType::Int(model_state), OSCType::Int(calibration_state), OSCType::Int(calibration_mode)]) => {
-				Ok(VMCMessage::State(State::new_calibration(
-					model_state.try_into().map_err(VMCError::UnknownModelState)?,
-					calibration_mode.try_into().map_err(VMCError::UnknownCalibrationMode)?,
-					calibration_state.try_into().map_err(VMCError::UnknownCalibrationState)?
-				)))
-			}
-			(
-				"/VMC/Ext/OK",
-				&[
-					OSCType::Int(model_state),
-					OSCType::Int(calibration_state),
-					OSCType::Int(calibration_mode),
-					OSCType::Int(tracking_state),
-					..
-				]
-			) => Ok(VMCMessage::State(State::new_tracking(
+		.map(|OSCMessage { addr, args }| {
+			let args: SmallVec<[ArgRef; 16]> = args.into_iter().map(ArgRef::from).collect();
+			parse_message(&addr, &args)
+		})
+		.collect()
+}
+
+/// Decodes a UDP datagram & parses it into its contained [`VMCMessage`]s, like [`parse`]ing the result of
+/// [`osc::decode_udp`](crate::osc::decode_udp), but without copying strings out of the datagram first. Bone & blend
+/// shape names are looked up straight from the datagram, so parsing a standard or previously seen name doesn't
+/// allocate.
+///
+/// # Examples
+///
+/// ```
+/// use vmc::{IntoOSCPacket, VMCMessage, VMCStandardVRM0Bone, VMCBoneTransform, Quat, Vec3A, osc};
+///
+/// let datagram = osc::encode(&VMCBoneTransform::new(VMCStandardVRM0Bone::Head, Vec3A::ZERO, Quat::IDENTITY).into_osc_packet())?;
+/// let messages = vmc::parse_datagram(&datagram)?;
+/// assert!(matches!(&messages[..], [VMCMessage::BoneTransform(transform)] if transform.bone == VMCStandardVRM0Bone::Head));
+/// # Ok::<_, vmc::VMCError>(())
+/// ```
+pub fn parse_datagram(datagram: &[u8]) -> VMCResult<Vec<VMCMessage>> {
+	parse_datagram_with_limits(datagram, &DecodeLimits::default())
+}
+
+/// Like [`parse_datagram`], but with custom [`DecodeLimits`].
+pub fn parse_datagram_with_limits(datagram: &[u8], limits: &DecodeLimits) -> VMCResult<Vec<VMCMessage>> {
+	let mut messages = Vec::new();
+	let mut res = Ok(());
+	decoder::visit_udp(datagram, limits, &mut |addr, args| {
+		if res.is_ok() {
+			res = parse_message(addr, args).map(|message| messages.push(message));
+		}
+	})?;
+	res.map(|_| messages)
+}
+
+fn parse_message(addr: &str, args: &[ArgRef<'_>]) -> VMCResult<VMCMessage> {
+	match (addr, args) {
+		(
+			"/VMC/Ext/Root/Pos",
+			&[
+				ArgRef::String(_),
+				ArgRef::Float(p_x),
+				ArgRef::Float(p_y),
+				ArgRef::Float(p_z),
+				ArgRef::Float(r_x),
+				ArgRef::Float(r_y),
+				ArgRef::Float(r_z),
+				ArgRef::Float(r_w)
+			]
+		) => Ok(VMCMessage::RootTransform(RootTransform::new(Vec3A::new(p_x, p_y, p_z), Quat::from_array([r_x, r_y, r_z, r_w])))),
+		(
+			"/VMC/Ext/Root/Pos",
+			&[
+				ArgRef::String(_),
+				ArgRef::Float(p_x),
+				ArgRef::Float(p_y),
+				ArgRef::Float(p_z),
+				ArgRef::Float(r_x),
+				ArgRef::Float(r_y),
+				ArgRef::Float(r_z),
+				ArgRef::Float(r_w),
+				ArgRef::Float(s_x),
+				ArgRef::Float(s_y),
+				ArgRef::Float(s_z),
+				ArgRef::Float(o_x),
+				ArgRef::Float(o_y),
+				ArgRef::Float(o_z),
+				..
+			]
+		) => Ok(VMCMessage::RootTransform(RootTransform::new_mr(
+			Vec3A::new(p_x, p_y, p_z),
+			Quat::from_array([r_x, r_y, r_z, r_w]),
+			Vec3A::new(s_x, s_y, s_z),
+			Vec3A::new(o_x, o_y, o_z)
+		))),
+		(
+			"/VMC/Ext/Bone/Pos",
+			&[
+				ArgRef::String(ref bone),
+				ArgRef::Float(p_x),
+				ArgRef::Float(p_y),
+				ArgRef::Float(p_z),
+				ArgRef::Float(r_x),
+				ArgRef::Float(r_y),
+				ArgRef::Float(r_z),
+				ArgRef::Float(r_w)
+			]
+		) => Ok(VMCMessage::BoneTransform(BoneTransform::new(
+			// custom bones, like those simulated by `SpringBones`, are sent alongside the humanoid bones
+			&**bone,
+			Vec3A::new(p_x, p_y, p_z),
+			Quat::from_array([r_x, r_y, r_z, r_w])
+		))),
+		(
+			"/VMC/Ext/Hmd/Pos",
+			&[
+				ArgRef::String(ref joint),
+				ArgRef::Float(p_x),
+				ArgRef::Float(p_y),
+				ArgRef::Float(p_z),
+				ArgRef::Float(r_x),
+				ArgRef::Float(r_y),
+				ArgRef::Float(r_z),
+				ArgRef::Float(r_w),
+				..
+			]
+		) => Ok(VMCMessage::DeviceTransform(DeviceTransform::new(
+			DeviceType::HMD,
+			&**joint,
+			Vec3A::new(p_x, p_y, p_z),
+			Quat::from_array([r_x, r_y, r_z, r_w]),
+			false
+		))),
+		(
+			"/VMC/Ext/Hmd/Pos/Local",
+			&[
+				ArgRef::String(ref joint),
+				ArgRef::Float(p_x),
+				ArgRef::Float(p_y),
+				ArgRef::Float(p_z),
+				ArgRef::Float(r_x),
+				ArgRef::Float(r_y),
+				ArgRef::Float(r_z),
+				ArgRef::Float(r_w),
+				..
+			]
+		) => Ok(VMCMessage::DeviceTransform(DeviceTransform::new(
+			DeviceType::HMD,
+			&**joint,
+			Vec3A::new(p_x, p_y, p_z),
+			Quat::from_array([r_x, r_y, r_z, r_w]),
+			true
+		))),
+		(
+			"/VMC/Ext/Con/Pos",
+			&[
+				ArgRef::String(ref joint),
+				ArgRef::Float(p_x),
+				ArgRef::Float(p_y),
+				ArgRef::Float(p_z),
+				ArgRef::Float(r_x),
+				ArgRef::Float(r_y),
+				ArgRef::Float(r_z),
+				ArgRef::Float(r_w),
+				..
+			]
+		) => Ok(VMCMessage::DeviceTransform(DeviceTransform::new(
+			DeviceType::Controller,
+			&**joint,
+			Vec3A::new(p_x, p_y, p_z),
+			Quat::from_array([r_x, r_y, r_z, r_w]),
+			false
+		))),
+		(
+			"/VMC/Ext/Con/Pos/Local",
+			&[
+				ArgRef::String(ref joint),
+				ArgRef::Float(p_x),
+				ArgRef::Float(p_y),
+				ArgRef::Float(p_z),
+				ArgRef::Float(r_x),
+				ArgRef::Float(r_y),
+				ArgRef::Float(r_z),
+				ArgRef::Float(r_w),
+				..
+			]
+		) => Ok(VMCMessage::DeviceTransform(DeviceTransform::new(
+			DeviceType::Controller,
+			&**joint,
+			Vec3A::new(p_x, p_y, p_z),
+			Quat::from_array([r_x, r_y, r_z, r_w]),
+			true
+		))),
+		(
+			"/VMC/Ext/Tra/Pos",
+			&[
+				ArgRef::String(ref joint),
+				ArgRef::Float(p_x),
+				ArgRef::Float(p_y),
+				ArgRef::Float(p_z),
+				ArgRef::Float(r_x),
+				ArgRef::Float(r_y),
+				ArgRef::Float(r_z),
+				ArgRef::Float(r_w),
+				..
+			]
+		) => Ok(VMCMessage::DeviceTransform(DeviceTransform::new(
+			DeviceType::Tracker,
+			&**joint,
+			Vec3A::new(p_x, p_y, p_z),
+			Quat::from_array([r_x, r_y, r_z, r_w]),
+			false
+		))),
+		(
+			"/VMC/Ext/Tra/Pos/Local",
+			&[
+				ArgRef::String(ref joint),
+				ArgRef::Float(p_x),
+				ArgRef::Float(p_y),
+				ArgRef::Float(p_z),
+				ArgRef::Float(r_x),
+				ArgRef::Float(r_y),
+				ArgRef::Float(r_z),
+				ArgRef::Float(r_w),
+				..
+			]
+		) => Ok(VMCMessage::DeviceTransform(DeviceTransform::new(
+			DeviceType::Tracker,
+			&**joint,
+			Vec3A::new(p_x, p_y, p_z),
+			Quat::from_array([r_x, r_y, r_z, r_w]),
+			true
+		))),
+		("/VMC/Ext/Blend/Val", &[ArgRef::String(ref shape), ArgRef::Float(val), ..]) => Ok(VMCMessage::BlendShape(BlendShape::new(&**shape, val))),
+		("/VMC/Ext/Blend/Apply", &[..]) => Ok(VMCMessage::ApplyBlendShapes),
+		("/VMC/Ext/OK", &[ArgRef::Int(model_state)]) => Ok(VMCMessage::State(State::new(model_state.try_into().map_err(VMCError::UnknownModelState)?))),
+		("/VMC/Ext/OK", &[ArgRef::Int(model_state), ArgRef::Int(calibration_state), ArgRef::Int(calibration_mode)]) => {
+			Ok(VMCMessage::State(State::new_calibration(
+				model_state.try_into().map_err(VMCError::UnknownModelState)?,
+				calibration_mode.try_into().map_err(VMCError::UnknownCalibrationMode)?,
+				calibration_state.try_into().map_err(VMCError::UnknownCalibrationState)?
+			)))
+		}
+		("/VMC/Ext/OK", &[ArgRef::Int(model_state), ArgRef::Int(calibration_state), ArgRef::Int(calibration_mode), ArgRef::Int(tracking_state), ..]) => {
+			Ok(VMCMessage::State(State::new_tracking(
 				model_state.try_into().map_err(VMCError::UnknownModelState)?,
 				calibration_mode.try_into().map_err(VMCError::UnknownCalibrationMode)?,
 				calibration_state.try_into().map_err(VMCError::UnknownCalibrationState)?,
 				tracking_state.try_into().map_err(VMCError::UnknownTrackingState)?
-			))),
-			("/VMC/Ext/T", &[OSCType::Float(time), ..]) => Ok(VMCMessage::Time(Time::new(time))),
-			(addr, args) => Err(VMCError::UnimplementedMessage(addr.to_owned(), args.to_owned()))
-		})
-		.collect()
+			)))
+		}
+		("/VMC/Ext/T", &[ArgRef::Float(time), ..]) => Ok(VMCMessage::Time(Time::new(time))),
+		(addr, args) => Err(VMCError::UnimplementedMessage(addr.to_owned(), args.iter().cloned().map(OSCType::from).collect()))
+	}
 }
 
 #[cfg(test)]
@@ -1197,6 +1453,36 @@ mod tests {
 		Ok(())
 	}
 
+	#[test]
+	fn test_root_transform_affine() {
+		let rotation = Quat::from_rotation_y(0.5);
+		let root = RootTransform::new_mr(Vec3A::new(1.0, 2.0, 3.0), rotation, Vec3A::splat(2.0), Vec3A::new(0.0, 1.0, 0.0));
+		let affine = root.to_affine();
+		assert_relative_eq!(affine.transform_point3a(Vec3A::ZERO), Vec3A::new(1.0, 1.0, 3.0));
+		assert_relative_eq!(affine.transform_vector3a(Vec3A::X), rotation * Vec3A::new(2.0, 0.0, 0.0), epsilon = 1e-6);
+
+		let decomposed = RootTransform::from_mat4(root.to_mat4());
+		assert_relative_eq!(decomposed.position, Vec3A::new(1.0, 1.0, 3.0), epsilon = 1e-6);
+		assert_relative_eq!(decomposed.scale.unwrap(), Vec3A::splat(2.0), epsilon = 1e-6);
+		assert!(decomposed.rotation.abs_diff_eq(rotation, 1e-6));
+		assert_eq!(RootTransform::from_affine(Affine3A::IDENTITY).scale, None);
+	}
+
+	#[test]
+	#[cfg(feature = "mint")]
+	fn test_mint_conversions() {
+		let position = mint::Vector3 { x: 1.0, y: 2.0, z: 3.0 };
+		let rotation = mint::Quaternion {
+			v: mint::Vector3 { x: 0.0, y: 1.0, z: 0.0 },
+			s: 0.0
+		};
+		let transform = BoneTransform::new(StandardVRM0Bone::Head, position, rotation);
+		assert_eq!(transform.position, Vec3A::new(1.0, 2.0, 3.0));
+		assert_eq!(transform.rotation, Quat::from_xyzw(0.0, 1.0, 0.0, 0.0));
+		assert_eq!(mint::Vector3::from(transform.position), position);
+		assert_eq!(mint::Quaternion::from(transform.rotation), rotation);
+	}
+
 	#[test]
 	fn test_ignore_extra_args() -> VMCResult<()> {
 		assert!(parse(OSCPacket::Message(OSCMessage::new("/VMC/Ext/T", (7.0_f32, "hello")))).is_ok());
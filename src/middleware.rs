@@ -0,0 +1,460 @@
+//! Composable stages for inspecting & modifying traffic.
+//!
+//! A [`Middleware`] receives each message and decides what to pass on: it can forward the message as-is, transform
+//! it, drop it, or emit additional messages. Middleware is composed into a [`Pipeline`], which can be placed between a
+//! socket and the application via [`Pipeline::wrap`].
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+//! use futures_util::StreamExt;
+//! use vmc::{
+//! 	VMCMessage,
+//! 	filter::FilterBank,
+//! 	middleware::{self, Pipeline}
+//! };
+//!
+//! let mut socket = vmc::marionette!().await?;
+//! let pipeline = Pipeline::new()
+//! 	// drop device transforms
+//! 	.layer(middleware::filter(|message| !matches!(message, VMCMessage::DeviceTransform(_))))
+//! 	// smooth transforms & blend shapes
+//! 	.layer(FilterBank::new())
+//! 	.layer(middleware::inspect(|message| println!("{message:?}")));
+//!
+//! let mut messages = pipeline.wrap(socket.messages());
+//! while let Some(message) = messages.next().await {
+//! 	let (message, _) = message?;
+//! }
+//! # Ok(()) }) }
+//! ```
+
+use std::{
+	collections::{HashMap, VecDeque},
+	fmt, mem,
+	net::SocketAddr,
+	pin::Pin,
+	task::{Context, Poll, ready},
+	time::Instant
+};
+
+use futures_core::Stream;
+
+use crate::{Quat, VMCMessage, VMCResult, Vec3A, filter::FilterBank, name::Name};
+
+/// A stage of a [`Pipeline`].
+pub trait Middleware: Send {
+	/// Processes a message, pushing the messages to pass on to the next stage to `out`.
+	///
+	/// To forward the message unchanged, push it as-is; to drop it, push nothing.
+	fn process(&mut self, message: VMCMessage, out: &mut Vec<VMCMessage>);
+}
+
+/// Smooths transforms & blend shapes. See [`FilterBank::filter`].
+impl Middleware for FilterBank {
+	fn process(&mut self, message: VMCMessage, out: &mut Vec<VMCMessage>) {
+		out.push(self.filter(message, Instant::now()));
+	}
+}
+
+impl<M: Middleware + ?Sized> Middleware for Box<M> {
+	fn process(&mut self, message: VMCMessage, out: &mut Vec<VMCMessage>) {
+		(**self).process(message, out);
+	}
+}
+
+/// Middleware created from a closure. See [`from_fn`].
+#[derive(Debug, Clone)]
+pub struct FromFn<F>(F);
+
+impl<F: FnMut(VMCMessage, &mut Vec<VMCMessage>) + Send> Middleware for FromFn<F> {
+	fn process(&mut self, message: VMCMessage, out: &mut Vec<VMCMessage>) {
+		(self.0)(message, out);
+	}
+}
+
+/// Creates middleware from a closure with the same signature as [`Middleware::process`].
+///
+/// ```
+/// use vmc::{
+/// 	VMCApplyBlendShapes, VMCMessage,
+/// 	middleware::{self, Middleware}
+/// };
+///
+/// // inject an `ApplyBlendShapes` message after every blend shape
+/// let mut apply = middleware::from_fn(|message, out| {
+/// 	let is_blendshape = matches!(message, VMCMessage::BlendShape(_));
+/// 	out.push(message);
+/// 	if is_blendshape {
+/// 		out.push(VMCApplyBlendShapes.into());
+/// 	}
+/// });
+/// ```
+pub fn from_fn<F: FnMut(VMCMessage, &mut Vec<VMCMessage>) + Send>(f: F) -> FromFn<F> {
+	FromFn(f)
+}
+
+/// Middleware which observes messages without modifying them. See [`inspect`].
+#[derive(Debug, Clone)]
+pub struct Inspect<F>(F);
+
+impl<F: FnMut(&VMCMessage) + Send> Middleware for Inspect<F> {
+	fn process(&mut self, message: VMCMessage, out: &mut Vec<VMCMessage>) {
+		(self.0)(&message);
+		out.push(message);
+	}
+}
+
+/// Creates middleware which calls `f` with each message, e.g. for logging.
+pub fn inspect<F: FnMut(&VMCMessage) + Send>(f: F) -> Inspect<F> {
+	Inspect(f)
+}
+
+/// Middleware which transforms messages. See [`map`].
+#[derive(Debug, Clone)]
+pub struct Map<F>(F);
+
+impl<F: FnMut(VMCMessage) -> VMCMessage + Send> Middleware for Map<F> {
+	fn process(&mut self, message: VMCMessage, out: &mut Vec<VMCMessage>) {
+		out.push((self.0)(message));
+	}
+}
+
+/// Creates middleware which replaces each message with the result of `f`, e.g. to rename bones.
+pub fn map<F: FnMut(VMCMessage) -> VMCMessage + Send>(f: F) -> Map<F> {
+	Map(f)
+}
+
+/// Middleware which drops messages. See [`filter`].
+#[derive(Debug, Clone)]
+pub struct Filter<F>(F);
+
+impl<F: FnMut(&VMCMessage) -> bool + Send> Middleware for Filter<F> {
+	fn process(&mut self, message: VMCMessage, out: &mut Vec<VMCMessage>) {
+		if (self.0)(&message) {
+			out.push(message);
+		}
+	}
+}
+
+/// Creates middleware which only passes on messages for which `f` returns `true`.
+pub fn filter<F: FnMut(&VMCMessage) -> bool + Send>(f: F) -> Filter<F> {
+	Filter(f)
+}
+
+/// Middleware which drops bone transforms & blend shapes whose values haven't changed since they were last passed on.
+///
+/// For mostly-static poses, most of each frame is redundant; dropping unchanged values cuts bandwidth considerably.
+/// Values are compared against the last value passed on rather than the last value seen, so slow drift below the
+/// thresholds still goes through eventually. Other messages are always passed on.
+///
+/// Since receivers which join late or miss a packet would otherwise never learn about static bones, all values are
+/// passed on every [refresh interval](Dedup::with_refresh_interval) frames.
+///
+/// `Dedup` can be used for incoming messages as a [`Pipeline`] stage, or for outgoing messages via
+/// [`Dedup::retain`]:
+///
+/// ```no_run
+/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+/// # let messages: Vec<vmc::VMCMessage> = vec![];
+/// use vmc::middleware::Dedup;
+///
+/// let socket = vmc::performer!().await?;
+/// let mut dedup = Dedup::new();
+/// for message in messages {
+/// 	if dedup.retain(&message) {
+/// 		socket.send(message).await?;
+/// 	}
+/// }
+/// # Ok(()) }) }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Dedup {
+	position_epsilon: f32,
+	rotation_epsilon: f32,
+	blendshape_epsilon: f32,
+	refresh_interval: u32,
+	frames: u32,
+	bones: HashMap<Name, (Vec3A, Quat)>,
+	blendshapes: HashMap<Name, f32>
+}
+
+impl Default for Dedup {
+	fn default() -> Self {
+		Self {
+			position_epsilon: 1e-4,
+			rotation_epsilon: 1e-4,
+			blendshape_epsilon: 1e-3,
+			refresh_interval: 60,
+			frames: 0,
+			bones: HashMap::new(),
+			blendshapes: HashMap::new()
+		}
+	}
+}
+
+impl Dedup {
+	/// Creates a deduplicator with the default thresholds, refreshing all values every 60 frames.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Sets the distance, in meters, a bone must move to be passed on. Defaults to `1e-4`.
+	pub fn with_position_epsilon(mut self, epsilon: f32) -> Self {
+		self.position_epsilon = epsilon;
+		self
+	}
+
+	/// Sets the angle, in radians, a bone must rotate to be passed on. Defaults to `1e-4`.
+	pub fn with_rotation_epsilon(mut self, epsilon: f32) -> Self {
+		self.rotation_epsilon = epsilon;
+		self
+	}
+
+	/// Sets how much a blend shape's value must change to be passed on. Defaults to `1e-3`.
+	pub fn with_blendshape_epsilon(mut self, epsilon: f32) -> Self {
+		self.blendshape_epsilon = epsilon;
+		self
+	}
+
+	/// Sets the number of frames, as delimited by [`Time`](crate::VMCTime) messages, after which all values are passed
+	/// on regardless of whether they changed. `0` disables refreshing. Defaults to 60.
+	pub fn with_refresh_interval(mut self, frames: u32) -> Self {
+		self.refresh_interval = frames;
+		self
+	}
+
+	/// Returns `true` if `message` should be passed on, recording its value if so.
+	pub fn retain(&mut self, message: &VMCMessage) -> bool {
+		match message {
+			VMCMessage::BoneTransform(transform) => {
+				if let Some((position, rotation)) = self.bones.get(&transform.bone) {
+					if position.distance(transform.position) <= self.position_epsilon && rotation.angle_between(transform.rotation) <= self.rotation_epsilon {
+						return false;
+					}
+				}
+				self.bones.insert(transform.bone.clone(), (transform.position, transform.rotation));
+				true
+			}
+			VMCMessage::BlendShape(blendshape) => {
+				if let Some(value) = self.blendshapes.get(&blendshape.key) {
+					if (value - blendshape.value).abs() <= self.blendshape_epsilon {
+						return false;
+					}
+				}
+				self.blendshapes.insert(blendshape.key.clone(), blendshape.value);
+				true
+			}
+			VMCMessage::Time(_) => {
+				self.frames += 1;
+				if self.refresh_interval != 0 && self.frames >= self.refresh_interval {
+					self.reset();
+				}
+				true
+			}
+			_ => true
+		}
+	}
+
+	/// Forgets all recorded values, so that the next value of every bone & blend shape is passed on.
+	pub fn reset(&mut self) {
+		self.frames = 0;
+		self.bones.clear();
+		self.blendshapes.clear();
+	}
+}
+
+impl Middleware for Dedup {
+	fn process(&mut self, message: VMCMessage, out: &mut Vec<VMCMessage>) {
+		if self.retain(&message) {
+			out.push(message);
+		}
+	}
+}
+
+/// A sequence of [`Middleware`] stages. Messages emitted by each stage are passed to the next, in order.
+///
+/// A pipeline is itself middleware, so pipelines can be nested.
+#[derive(Default)]
+pub struct Pipeline {
+	stages: Vec<Box<dyn Middleware>>,
+	buffer: Vec<VMCMessage>
+}
+
+impl fmt::Debug for Pipeline {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Pipeline").field("stages", &self.stages.len()).finish_non_exhaustive()
+	}
+}
+
+impl Pipeline {
+	/// Creates an empty pipeline, which passes on all messages unchanged.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Appends a stage to the end of the pipeline.
+	pub fn layer<M: Middleware + 'static>(mut self, middleware: M) -> Self {
+		self.stages.push(Box::new(middleware));
+		self
+	}
+
+	/// Returns the number of stages in the pipeline.
+	pub fn len(&self) -> usize {
+		self.stages.len()
+	}
+
+	/// Returns `true` if the pipeline has no stages.
+	pub fn is_empty(&self) -> bool {
+		self.stages.is_empty()
+	}
+
+	/// Runs a message through all stages, returning the messages emitted by the last stage.
+	pub fn run(&mut self, message: VMCMessage) -> Vec<VMCMessage> {
+		let mut out = Vec::new();
+		self.process(message, &mut out);
+		out
+	}
+
+	/// Wraps a stream of messages, such as [`VMCSocket::messages`](crate::VMCSocket::messages), running each message
+	/// through the pipeline as it is received.
+	pub fn wrap<S>(self, stream: S) -> Piped<S> {
+		Piped {
+			stream,
+			pipeline: self,
+			pending: VecDeque::new()
+		}
+	}
+}
+
+impl Middleware for Pipeline {
+	fn process(&mut self, message: VMCMessage, out: &mut Vec<VMCMessage>) {
+		let mut current = mem::take(&mut self.buffer);
+		current.push(message);
+		let mut next = Vec::new();
+		for stage in &mut self.stages {
+			for message in current.drain(..) {
+				stage.process(message, &mut next);
+			}
+			mem::swap(&mut current, &mut next);
+			if current.is_empty() {
+				break;
+			}
+		}
+		out.append(&mut current);
+		// keep the allocation around for the next message
+		self.buffer = current;
+	}
+}
+
+/// A stream adapter which runs messages through a [`Pipeline`], created by [`Pipeline::wrap`].
+///
+/// Each message emitted by the pipeline is paired with the address of the peer that sent the original message.
+#[derive(Debug)]
+pub struct Piped<S> {
+	stream: S,
+	pipeline: Pipeline,
+	pending: VecDeque<(VMCMessage, SocketAddr)>
+}
+
+impl<S> Piped<S> {
+	/// Get a reference to the pipeline.
+	pub fn pipeline(&self) -> &Pipeline {
+		&self.pipeline
+	}
+
+	/// Get a mutable reference to the pipeline.
+	pub fn pipeline_mut(&mut self) -> &mut Pipeline {
+		&mut self.pipeline
+	}
+
+	/// Consumes this adapter, returning the inner stream.
+	///
+	/// Any messages emitted by the pipeline which have not yet been yielded are discarded.
+	pub fn into_inner(self) -> S {
+		self.stream
+	}
+}
+
+impl<S> Stream for Piped<S>
+where
+	S: Stream<Item = VMCResult<(VMCMessage, SocketAddr)>> + Unpin
+{
+	type Item = VMCResult<(VMCMessage, SocketAddr)>;
+
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		loop {
+			if let Some(message) = self.pending.pop_front() {
+				return Poll::Ready(Some(Ok(message)));
+			}
+
+			match ready!(Pin::new(&mut self.stream).poll_next(cx)) {
+				Some(Ok((message, addr))) => {
+					let messages = self.pipeline.run(message);
+					self.pending.extend(messages.into_iter().map(|message| (message, addr)));
+				}
+				Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+				None => return Poll::Ready(None)
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::{
+		Arc,
+		atomic::{AtomicUsize, Ordering}
+	};
+
+	use super::*;
+	use crate::{VMCApplyBlendShapes, VMCBlendShape, VMCBoneTransform, VMCStandardVRM0Bone, VMCTime};
+
+	#[test]
+	fn test_pipeline() {
+		let seen = Arc::new(AtomicUsize::new(0));
+		let mut pipeline = Pipeline::new()
+			.layer(filter(|message| !matches!(message, VMCMessage::Time(_))))
+			.layer(map(|message| match message {
+				VMCMessage::BlendShape(blendshape) => VMCBlendShape::new(blendshape.key, blendshape.value * 0.5).into(),
+				message => message
+			}))
+			.layer(from_fn(|message, out| {
+				let is_blendshape = matches!(message, VMCMessage::BlendShape(_));
+				out.push(message);
+				if is_blendshape {
+					out.push(VMCApplyBlendShapes.into());
+				}
+			}))
+			.layer(inspect({
+				let seen = Arc::clone(&seen);
+				move |_| {
+					seen.fetch_add(1, Ordering::Relaxed);
+				}
+			}));
+
+		assert!(pipeline.run(VMCTime::new(1.0).into()).is_empty());
+		let out = pipeline.run(VMCBlendShape::new("Joy", 1.0).into());
+		assert!(matches!(&out[..], [VMCMessage::BlendShape(blendshape), VMCMessage::ApplyBlendShapes] if blendshape.value == 0.5));
+		assert_eq!(seen.load(Ordering::Relaxed), 2);
+	}
+
+	#[test]
+	fn test_dedup() {
+		let mut dedup = Dedup::new().with_refresh_interval(2);
+		let head = |x: f32| VMCMessage::from(VMCBoneTransform::new(VMCStandardVRM0Bone::Head, Vec3A::X * x, Quat::IDENTITY));
+		assert!(dedup.retain(&head(0.0)));
+		assert!(!dedup.retain(&head(0.00001)));
+		assert!(dedup.retain(&head(0.1)));
+		assert!(dedup.retain(&VMCBlendShape::new("Joy", 0.5).into()));
+		assert!(!dedup.retain(&VMCBlendShape::new("Joy", 0.5).into()));
+		assert!(dedup.retain(&VMCTime::new(1.0).into()));
+		assert!(!dedup.retain(&head(0.1)));
+
+		// the refresh interval has elapsed, so everything should be passed on again
+		assert!(dedup.retain(&VMCTime::new(2.0).into()));
+		assert!(dedup.retain(&head(0.1)));
+		assert!(dedup.retain(&VMCBlendShape::new("Joy", 0.5).into()));
+	}
+}
@@ -0,0 +1,129 @@
+//! Conversions between message transforms and [`nalgebra`] types, enabled with the `nalgebra` feature.
+
+use nalgebra::{Isometry3, Point3, Quaternion, Translation3, UnitQuaternion};
+
+use crate::{
+	Quat, Vec3A,
+	message::{BoneTransform, DeviceTransform, DeviceType, RootTransform},
+	name::Name
+};
+
+fn to_point(position: Vec3A) -> Point3<f32> {
+	Point3::new(position.x, position.y, position.z)
+}
+
+fn to_unit_quaternion(rotation: Quat) -> UnitQuaternion<f32> {
+	// glam quaternions used as rotations are already normalized
+	UnitQuaternion::new_unchecked(Quaternion::new(rotation.w, rotation.x, rotation.y, rotation.z))
+}
+
+fn from_point(point: Point3<f32>) -> Vec3A {
+	Vec3A::new(point.x, point.y, point.z)
+}
+
+fn from_unit_quaternion(rotation: UnitQuaternion<f32>) -> Quat {
+	Quat::from_xyzw(rotation.i, rotation.j, rotation.k, rotation.w)
+}
+
+macro_rules! impl_nalgebra {
+	($($ty:ty),*) => {
+		$(
+			impl $ty {
+				/// Returns the position as an [`nalgebra::Point3`].
+				pub fn position_point(&self) -> Point3<f32> {
+					to_point(self.position)
+				}
+
+				/// Returns the rotation as an [`nalgebra::UnitQuaternion`].
+				pub fn rotation_unit_quaternion(&self) -> UnitQuaternion<f32> {
+					to_unit_quaternion(self.rotation)
+				}
+
+				/// Sets the position & rotation from an [`nalgebra::Isometry3`].
+				pub fn set_isometry(&mut self, isometry: &Isometry3<f32>) {
+					self.position = from_point(isometry.translation.vector.into());
+					self.rotation = from_unit_quaternion(isometry.rotation);
+				}
+			}
+
+			impl From<&$ty> for Isometry3<f32> {
+				fn from(transform: &$ty) -> Self {
+					Isometry3::from_parts(Translation3::from(transform.position_point()), transform.rotation_unit_quaternion())
+				}
+			}
+
+			impl From<$ty> for Isometry3<f32> {
+				fn from(transform: $ty) -> Self {
+					Isometry3::from(&transform)
+				}
+			}
+		)*
+	};
+}
+
+impl_nalgebra!(RootTransform, BoneTransform, DeviceTransform);
+
+impl From<Isometry3<f32>> for RootTransform {
+	fn from(isometry: Isometry3<f32>) -> Self {
+		RootTransform::new(from_point(isometry.translation.vector.into()), from_unit_quaternion(isometry.rotation))
+	}
+}
+
+impl RootTransform {
+	/// Creates a root transform from an [`nalgebra::Point3`] & [`nalgebra::UnitQuaternion`].
+	pub fn from_nalgebra(position: Point3<f32>, rotation: UnitQuaternion<f32>) -> Self {
+		RootTransform::new(from_point(position), from_unit_quaternion(rotation))
+	}
+}
+
+impl BoneTransform {
+	/// Creates a bone transform from an [`nalgebra::Point3`] & [`nalgebra::UnitQuaternion`].
+	///
+	/// `bone` is the name of the bone; see [`StandardVRM0Bone`](crate::VMCStandardVRM0Bone) for standard VRM 0.x bone
+	/// names.
+	pub fn from_nalgebra(bone: impl Into<Name>, position: Point3<f32>, rotation: UnitQuaternion<f32>) -> Self {
+		BoneTransform::new(bone, from_point(position), from_unit_quaternion(rotation))
+	}
+
+	/// Creates a bone transform from an [`nalgebra::Isometry3`].
+	pub fn from_isometry(bone: impl Into<Name>, isometry: &Isometry3<f32>) -> Self {
+		BoneTransform::from_nalgebra(bone, isometry.translation.vector.into(), isometry.rotation)
+	}
+}
+
+impl DeviceTransform {
+	/// Creates a device transform from an [`nalgebra::Point3`] & [`nalgebra::UnitQuaternion`]. See
+	/// [`DeviceTransform::new`].
+	pub fn from_nalgebra(device: DeviceType, joint: impl ToString, position: Point3<f32>, rotation: UnitQuaternion<f32>, local: bool) -> Self {
+		DeviceTransform::new(device, joint, from_point(position), from_unit_quaternion(rotation), local)
+	}
+
+	/// Creates a device transform from an [`nalgebra::Isometry3`]. See [`DeviceTransform::new`].
+	pub fn from_isometry(device: DeviceType, joint: impl ToString, isometry: &Isometry3<f32>, local: bool) -> Self {
+		DeviceTransform::from_nalgebra(device, joint, isometry.translation.vector.into(), isometry.rotation, local)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use nalgebra::Vector3;
+
+	use super::*;
+	use crate::message::StandardVRM0Bone;
+
+	#[test]
+	fn test_nalgebra_roundtrip() {
+		let rotation = Quat::from_rotation_y(0.5) * Quat::from_rotation_x(0.25);
+		let bone = BoneTransform::new(StandardVRM0Bone::Head, Vec3A::new(1.0, 2.0, 3.0), rotation);
+
+		let isometry = Isometry3::from(&bone);
+		assert_eq!(isometry.translation.vector, Vector3::new(1.0, 2.0, 3.0));
+		let expected = UnitQuaternion::from_euler_angles(0.25, 0.0, 0.0);
+		let expected = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), 0.5) * expected;
+		assert!(isometry.rotation.angle_to(&expected) < 1e-6);
+
+		assert_eq!(BoneTransform::from_isometry(StandardVRM0Bone::Head, &isometry), bone);
+		assert_eq!(RootTransform::from(isometry), RootTransform::new(bone.position, rotation));
+		assert_eq!(bone.position_point(), Point3::new(1.0, 2.0, 3.0));
+	}
+}
@@ -0,0 +1,305 @@
+use std::{
+	borrow::Borrow,
+	cmp::Ordering,
+	collections::HashMap,
+	fmt,
+	hash::{Hash, Hasher},
+	ops::Deref,
+	str::FromStr,
+	sync::{Arc, Mutex, OnceLock, PoisonError}
+};
+
+use crate::{
+	OSCType,
+	message::{StandardVRM0Bone, StandardVRMBlendShape}
+};
+
+/// The maximum number of custom names kept in the intern cache. When the cache is full, the least recently used half of
+/// it is evicted, so names which are no longer being sent make room for new ones.
+const MAX_CACHED: usize = 1024;
+
+/// An interned bone or blend shape name.
+///
+/// Standard VRM bone & blend shape names are stored as `&'static str`, and custom names are shared from a global cache
+/// of recently used names, so creating a `Name` for a name that has been seen recently doesn't allocate, and cloning
+/// one never does.
+///
+/// `Name` derefs to `str`, and compares & hashes the same as the string it holds, so maps keyed by `Name` can be
+/// looked up with a `&str`.
+///
+/// # Examples
+///
+/// ```
+/// use vmc::{VMCName, VMCStandardVRM0Bone};
+///
+/// let name = VMCName::new("Head");
+/// assert_eq!(name, VMCStandardVRM0Bone::Head);
+/// assert_eq!(name, "Head");
+///
+/// let custom = VMCName::new("Tail");
+/// assert_eq!(custom.as_str(), "Tail");
+/// ```
+#[derive(Clone)]
+pub struct Name(Repr);
+
+#[derive(Clone)]
+enum Repr {
+	Static(&'static str),
+	Shared(Arc<str>)
+}
+
+impl Name {
+	/// Creates a name, reusing a standard or recently seen name if possible.
+	pub fn new(name: &str) -> Self {
+		Self::standard(name).unwrap_or_else(|| Self::intern(name))
+	}
+
+	/// Creates a name from a static string without interning it.
+	pub const fn from_static(name: &'static str) -> Self {
+		Self(Repr::Static(name))
+	}
+
+	/// Returns the name as a string slice.
+	pub fn as_str(&self) -> &str {
+		match &self.0 {
+			Repr::Static(name) => name,
+			Repr::Shared(name) => name
+		}
+	}
+
+	fn standard(name: &str) -> Option<Self> {
+		if let Ok(bone) = StandardVRM0Bone::from_str(name) {
+			return Some(bone.into());
+		}
+		if let Ok(shape) = StandardVRMBlendShape::from_str(name) {
+			return Some(shape.into());
+		}
+		None
+	}
+
+	fn intern(name: &str) -> Self {
+		let mut cache = cache().lock().unwrap_or_else(PoisonError::into_inner);
+		Self(Repr::Shared(cache.get_or_insert(name)))
+	}
+}
+
+fn cache() -> &'static Mutex<NameCache> {
+	static CACHE: OnceLock<Mutex<NameCache>> = OnceLock::new();
+	CACHE.get_or_init(Mutex::default)
+}
+
+/// A cache of custom names which evicts the least recently used names when full.
+#[derive(Default)]
+struct NameCache {
+	names: HashMap<Arc<str>, CachedName>,
+	clock: u64
+}
+
+struct CachedName {
+	name: Arc<str>,
+	last_used: u64
+}
+
+impl NameCache {
+	fn get_or_insert(&mut self, name: &str) -> Arc<str> {
+		self.clock += 1;
+		if let Some(cached) = self.names.get_mut(name) {
+			cached.last_used = self.clock;
+			return Arc::clone(&cached.name);
+		}
+
+		if self.names.len() >= MAX_CACHED {
+			self.evict();
+		}
+		let name: Arc<str> = Arc::from(name);
+		self.names.insert(
+			Arc::clone(&name),
+			CachedName {
+				name: Arc::clone(&name),
+				last_used: self.clock
+			}
+		);
+		name
+	}
+
+	/// Evicts the least recently used half of the cache. Evicting in bulk keeps the cost of finding the least recently
+	/// used names low when many new names are received in a row.
+	fn evict(&mut self) {
+		let mut last_used: Vec<u64> = self.names.values().map(|cached| cached.last_used).collect();
+		let (_, &mut cutoff, _) = last_used.select_nth_unstable(self.names.len() / 2);
+		self.names.retain(|_, cached| cached.last_used > cutoff);
+	}
+}
+
+impl Deref for Name {
+	type Target = str;
+
+	fn deref(&self) -> &str {
+		self.as_str()
+	}
+}
+
+impl AsRef<str> for Name {
+	fn as_ref(&self) -> &str {
+		self.as_str()
+	}
+}
+
+impl Borrow<str> for Name {
+	fn borrow(&self) -> &str {
+		self.as_str()
+	}
+}
+
+impl fmt::Debug for Name {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		fmt::Debug::fmt(self.as_str(), f)
+	}
+}
+
+impl fmt::Display for Name {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
+impl PartialEq for Name {
+	fn eq(&self, other: &Self) -> bool {
+		self.as_str() == other.as_str()
+	}
+}
+impl Eq for Name {}
+
+impl PartialOrd for Name {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+impl Ord for Name {
+	fn cmp(&self, other: &Self) -> Ordering {
+		self.as_str().cmp(other.as_str())
+	}
+}
+
+impl Hash for Name {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.as_str().hash(state)
+	}
+}
+
+impl From<&str> for Name {
+	fn from(name: &str) -> Self {
+		Self::new(name)
+	}
+}
+impl From<&String> for Name {
+	fn from(name: &String) -> Self {
+		Self::new(name)
+	}
+}
+impl From<String> for Name {
+	fn from(name: String) -> Self {
+		Self::new(&name)
+	}
+}
+impl From<&Name> for Name {
+	fn from(name: &Name) -> Self {
+		name.clone()
+	}
+}
+impl From<StandardVRM0Bone> for Name {
+	fn from(bone: StandardVRM0Bone) -> Self {
+		Self::from_static(bone.as_str())
+	}
+}
+impl From<StandardVRMBlendShape> for Name {
+	fn from(shape: StandardVRMBlendShape) -> Self {
+		Self::from_static(shape.as_str())
+	}
+}
+
+impl From<Name> for String {
+	fn from(name: Name) -> Self {
+		name.as_str().to_string()
+	}
+}
+impl From<Name> for OSCType {
+	fn from(name: Name) -> Self {
+		OSCType::String(name.into())
+	}
+}
+
+macro_rules! name_eq_impl {
+	($($ty:ty),*) => {
+		$(
+			impl PartialEq<$ty> for Name {
+				fn eq(&self, other: &$ty) -> bool {
+					self.as_str() == AsRef::<str>::as_ref(other)
+				}
+			}
+			impl PartialEq<Name> for $ty {
+				fn eq(&self, other: &Name) -> bool {
+					AsRef::<str>::as_ref(self) == other.as_str()
+				}
+			}
+		)*
+	};
+}
+
+name_eq_impl!(str, &str, String, StandardVRM0Bone, StandardVRMBlendShape);
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Name {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_str(self.as_str())
+	}
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Name {
+	fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		String::deserialize(deserializer).map(Name::from)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_interning() {
+		let name = Name::new("Head");
+		assert!(matches!(name.0, Repr::Static("Head")));
+		assert!(matches!(Name::from("Blink_L".to_string()).0, Repr::Static("Blink_L")));
+
+		let (Repr::Shared(a), Repr::Shared(b)) = (Name::new("TestInterningTail").0, Name::from("TestInterningTail".to_string()).0) else {
+			panic!("custom names should be shared")
+		};
+		assert!(Arc::ptr_eq(&a, &b));
+
+		let map = std::collections::HashMap::from([(Name::new("Joy"), 1.0)]);
+		assert_eq!(map.get("Joy"), Some(&1.0));
+	}
+
+	#[test]
+	fn test_full_cache() {
+		let mut cache = NameCache::default();
+		let first = cache.get_or_insert("Custom0");
+		for i in 1..MAX_CACHED {
+			cache.get_or_insert(&format!("Custom{i}"));
+		}
+		assert_eq!(cache.names.len(), MAX_CACHED);
+
+		// using a name marks it as recently used, so it survives eviction...
+		assert!(Arc::ptr_eq(&cache.get_or_insert("Custom0"), &first));
+		let overflow = cache.get_or_insert("Overflow");
+		assert_eq!(cache.names.len(), MAX_CACHED / 2);
+		assert!(Arc::ptr_eq(&cache.get_or_insert("Custom0"), &first));
+		assert!(Arc::ptr_eq(&cache.get_or_insert("Overflow"), &overflow));
+		assert!(cache.names.contains_key(&*format!("Custom{}", MAX_CACHED - 1)));
+		// ...while the least recently used names are evicted & allocated anew when seen again
+		assert!(!cache.names.contains_key("Custom1") && !cache.names.contains_key(&*format!("Custom{}", MAX_CACHED / 2)));
+		assert_eq!(&*cache.get_or_insert("Custom1"), "Custom1");
+		assert_eq!(cache.names.len(), MAX_CACHED / 2 + 1);
+	}
+}
@@ -0,0 +1,360 @@
+//! A receiver for [OpenSeeFace](https://github.com/emilianavt/OpenSeeFace) webcam face tracking, as used by VSeeFace.
+//!
+//! OpenSeeFace's tracker sends raw tracking data over UDP in a fixed-size binary format: 2D landmarks, 3D points
+//! (including the pupils, for gaze), the head pose, and a set of expression features. [`Face`] parses it, and
+//! [`Face::to_messages`] converts it into an approximate [`Head`](crate::VMCStandardVRM0Bone::Head) rotation and
+//! standard VRM blend shapes, so webcam tracking can drive a VMC marionette directly.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+//! use vmc::openseeface::Receiver;
+//!
+//! let mut receiver = Receiver::bind(("127.0.0.1", vmc::openseeface::PORT)).await?;
+//! let socket = vmc::performer!("127.0.0.1:39539").await?;
+//! loop {
+//! 	let (faces, _) = receiver.recv().await?;
+//! 	if let Some(face) = faces.first() {
+//! 		socket.send_batch(face.to_messages()).await?;
+//! 	}
+//! }
+//! # Ok(()) }) }
+//! ```
+
+use std::net::SocketAddr;
+
+use bytes::Buf;
+use glam::{EulerRot, Quat, Vec2, Vec3, Vec3A};
+use tokio::net::{ToSocketAddrs, UdpSocket};
+
+use crate::{
+	VMCBlendShape, VMCBoneTransform, VMCError, VMCMessage, VMCResult, VMCStandardVRM0Bone as StandardVRM0Bone,
+	VMCStandardVRMBlendShape as StandardVRMBlendShape
+};
+
+/// The port OpenSeeFace sends to by default.
+pub const PORT: u16 = 11573;
+/// The number of 2D landmarks tracked for each face.
+pub const LANDMARKS: usize = 68;
+/// The number of 3D points sent for each face. The last two are the right & left pupils.
+pub const POINTS_3D: usize = 70;
+/// The size in bytes of the data for a single face. A packet contains one or more faces.
+pub const FACE_LEN: usize = 8 + 4 + 2 * 4 + 2 * 4 + 1 + 4 + 4 * 4 + 3 * 4 + 3 * 4 + LANDMARKS * 4 + LANDMARKS * 2 * 4 + POINTS_3D * 3 * 4 + 14 * 4;
+
+/// Expression features estimated by OpenSeeFace. Values are roughly centered on 0 for a neutral expression.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Features {
+	pub eye_left: f32,
+	pub eye_right: f32,
+	pub eyebrow_steepness_left: f32,
+	pub eyebrow_up_down_left: f32,
+	pub eyebrow_quirk_left: f32,
+	pub eyebrow_steepness_right: f32,
+	pub eyebrow_up_down_right: f32,
+	pub eyebrow_quirk_right: f32,
+	pub mouth_corner_up_down_left: f32,
+	pub mouth_corner_in_out_left: f32,
+	pub mouth_corner_up_down_right: f32,
+	pub mouth_corner_in_out_right: f32,
+	pub mouth_open: f32,
+	pub mouth_wide: f32
+}
+
+/// The tracking data for a single face.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Face {
+	/// The time the frame was captured, in seconds since the Unix epoch.
+	pub time: f64,
+	/// The ID of the tracked face, when tracking multiple faces.
+	pub id: i32,
+	/// The resolution of the camera image.
+	pub resolution: Vec2,
+	/// How open the right eye is, from 0 (closed) to 1 (open).
+	pub eye_open_right: f32,
+	/// How open the left eye is, from 0 (closed) to 1 (open).
+	pub eye_open_left: f32,
+	/// Whether the 3D points were fitted successfully.
+	pub success: bool,
+	/// The error of the 3D fit.
+	pub fit_error: f32,
+	/// The raw rotation of the head, in the camera's coordinate system.
+	pub rotation: Quat,
+	/// The raw rotation of the head as Euler angles in degrees (pitch, yaw, roll). The pitch is close to ±180° when
+	/// facing the camera.
+	pub euler: Vec3,
+	/// The position of the head relative to the camera.
+	pub translation: Vec3,
+	/// The confidence of each landmark.
+	pub confidence: Vec<f32>,
+	/// The 2D landmarks, in pixels.
+	pub landmarks: Vec<Vec2>,
+	/// The 3D points of the face model. The right & left pupils are at indices 68 & 69.
+	pub points_3d: Vec<Vec3>,
+	/// Expression features.
+	pub features: Features
+}
+
+impl Face {
+	/// Parses the data of a single face, which must be exactly [`FACE_LEN`] bytes long.
+	pub fn parse(mut data: &[u8]) -> VMCResult<Self> {
+		if data.len() != FACE_LEN {
+			return Err(VMCError::InvalidFaceData(format!("expected {FACE_LEN} bytes of OpenSeeFace data, got {}", data.len())));
+		}
+		let time = data.get_f64_le();
+		let id = data.get_i32_le();
+		let resolution = Vec2::new(data.get_f32_le(), data.get_f32_le());
+		let eye_open_right = data.get_f32_le();
+		let eye_open_left = data.get_f32_le();
+		let success = data.get_u8() != 0;
+		let fit_error = data.get_f32_le();
+		let rotation = Quat::from_xyzw(data.get_f32_le(), data.get_f32_le(), data.get_f32_le(), data.get_f32_le());
+		let euler = Vec3::new(data.get_f32_le(), data.get_f32_le(), data.get_f32_le());
+		let translation = Vec3::new(data.get_f32_le(), data.get_f32_le(), data.get_f32_le());
+		let confidence = (0..LANDMARKS).map(|_| data.get_f32_le()).collect();
+		let landmarks = (0..LANDMARKS)
+			.map(|_| {
+				// landmarks are sent as (y, x)
+				let y = data.get_f32_le();
+				Vec2::new(data.get_f32_le(), y)
+			})
+			.collect();
+		let points_3d = (0..POINTS_3D)
+			.map(|_| Vec3::new(data.get_f32_le(), data.get_f32_le(), data.get_f32_le()))
+			.collect();
+		let mut features = [0.0; 14];
+		for feature in &mut features {
+			*feature = data.get_f32_le();
+		}
+		let [
+			eye_left,
+			eye_right,
+			eyebrow_steepness_left,
+			eyebrow_up_down_left,
+			eyebrow_quirk_left,
+			eyebrow_steepness_right,
+			eyebrow_up_down_right,
+			eyebrow_quirk_right,
+			mouth_corner_up_down_left,
+			mouth_corner_in_out_left,
+			mouth_corner_up_down_right,
+			mouth_corner_in_out_right,
+			mouth_open,
+			mouth_wide
+		] = features;
+		Ok(Self {
+			time,
+			id,
+			resolution,
+			eye_open_right,
+			eye_open_left,
+			success,
+			fit_error,
+			rotation,
+			euler,
+			translation,
+			confidence,
+			landmarks,
+			points_3d,
+			features: Features {
+				eye_left,
+				eye_right,
+				eyebrow_steepness_left,
+				eyebrow_up_down_left,
+				eyebrow_quirk_left,
+				eyebrow_steepness_right,
+				eyebrow_up_down_right,
+				eyebrow_quirk_right,
+				mouth_corner_up_down_left,
+				mouth_corner_in_out_left,
+				mouth_corner_up_down_right,
+				mouth_corner_in_out_right,
+				mouth_open,
+				mouth_wide
+			}
+		})
+	}
+
+	/// Parses all faces in a packet.
+	pub fn parse_packet(data: &[u8]) -> VMCResult<Vec<Self>> {
+		if data.len() % FACE_LEN != 0 {
+			return Err(VMCError::InvalidFaceData(format!("OpenSeeFace packet length {} is not a multiple of {FACE_LEN}", data.len())));
+		}
+		data.chunks_exact(FACE_LEN).map(Self::parse).collect()
+	}
+
+	/// Returns the rotation of the head as seen by a mirror, with no rotation when looking straight at the camera.
+	pub fn head_rotation(&self) -> Quat {
+		let pitch = wrap_degrees(self.euler.x - 180.0);
+		Quat::from_euler(EulerRot::YXZ, -self.euler.y.to_radians(), pitch.to_radians(), self.euler.z.to_radians())
+	}
+
+	/// Converts the face into VMC messages: a [`Head`](StandardVRM0Bone::Head) transform with the
+	/// [head rotation](Face::head_rotation), followed by the [`BlinkL`](StandardVRMBlendShape::BlinkL),
+	/// [`BlinkR`](StandardVRMBlendShape::BlinkR), [`A`](StandardVRMBlendShape::A), [`I`](StandardVRMBlendShape::I), &
+	/// [`U`](StandardVRMBlendShape::U) blend shapes and an [`ApplyBlendShapes`](crate::VMCApplyBlendShapes) message.
+	///
+	/// The blend shapes are estimated directly from the raw eye openness & mouth features, without per-user
+	/// calibration.
+	pub fn to_messages(&self) -> Vec<VMCMessage> {
+		let shapes = [
+			(StandardVRMBlendShape::BlinkL, 1.0 - self.eye_open_left),
+			(StandardVRMBlendShape::BlinkR, 1.0 - self.eye_open_right),
+			(StandardVRMBlendShape::A, self.features.mouth_open),
+			(StandardVRMBlendShape::I, self.features.mouth_wide),
+			(StandardVRMBlendShape::U, -self.features.mouth_wide)
+		];
+		let mut messages = Vec::with_capacity(shapes.len() + 2);
+		messages.push(VMCMessage::BoneTransform(VMCBoneTransform::new(StandardVRM0Bone::Head, Vec3A::ZERO, self.head_rotation())));
+		for (shape, value) in shapes {
+			messages.push(VMCMessage::BlendShape(VMCBlendShape::new(shape, value.clamp(0.0, 1.0))));
+		}
+		messages.push(VMCMessage::ApplyBlendShapes);
+		messages
+	}
+}
+
+fn wrap_degrees(degrees: f32) -> f32 {
+	(degrees + 180.0).rem_euclid(360.0) - 180.0
+}
+
+/// Receives tracking data from OpenSeeFace.
+#[derive(Debug)]
+pub struct Receiver {
+	socket: UdpSocket,
+	buf: Vec<u8>
+}
+
+impl Receiver {
+	/// Binds a receiver to the given address. OpenSeeFace sends to `127.0.0.1:11573` ([`PORT`]) by default.
+	pub async fn bind<A: ToSocketAddrs>(addr: A) -> VMCResult<Self> {
+		Ok(Self::new(UdpSocket::bind(addr).await?))
+	}
+
+	/// Creates a receiver from an existing socket.
+	pub fn new(socket: UdpSocket) -> Self {
+		Self { socket, buf: vec![0; 65536] }
+	}
+
+	/// Receives the faces tracked in the next frame, along with the address of the tracker which sent them.
+	pub async fn recv(&mut self) -> VMCResult<(Vec<Face>, SocketAddr)> {
+		let (len, peer) = self.socket.recv_from(&mut self.buf).await?;
+		Ok((Face::parse_packet(&self.buf[..len])?, peer))
+	}
+
+	/// Returns the local address that the receiver is bound to.
+	pub fn local_addr(&self) -> VMCResult<SocketAddr> {
+		Ok(self.socket.local_addr()?)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use bytes::BufMut;
+
+	use super::*;
+
+	/// Encodes a face facing the camera with its head turned 10° and the given eye openness & mouth features.
+	fn face_data(id: i32, eye_open: f32, mouth_open: f32, mouth_wide: f32) -> Vec<u8> {
+		let mut data = Vec::with_capacity(FACE_LEN);
+		data.put_f64_le(1.5);
+		data.put_i32_le(id);
+		data.put_f32_le(640.0);
+		data.put_f32_le(480.0);
+		data.put_f32_le(1.0);
+		data.put_f32_le(eye_open);
+		data.put_u8(1);
+		data.put_f32_le(0.0);
+		for value in [0.0, 0.0, 0.0, 1.0, 180.0, 10.0, 0.0, 0.0, 0.0, 5.0] {
+			data.put_f32_le(value);
+		}
+		for i in 0..LANDMARKS * 3 + POINTS_3D * 3 {
+			data.put_f32_le(i as f32);
+		}
+		for i in 0..14 {
+			data.put_f32_le(match i {
+				12 => mouth_open,
+				13 => mouth_wide,
+				_ => 0.0
+			});
+		}
+		data
+	}
+
+	#[test]
+	fn test_parse_face() -> VMCResult<()> {
+		let data = [face_data(0, 0.25, 0.5, 0.0), face_data(1, 0.25, 0.5, 0.0)].concat();
+
+		let faces = Face::parse_packet(&data)?;
+		assert_eq!(faces.len(), 2);
+		assert_eq!(faces[1].id, 1);
+		assert_eq!(faces[0].landmarks[0], Vec2::new(LANDMARKS as f32 + 1.0, LANDMARKS as f32));
+		assert_eq!(faces[0].features.mouth_open, 0.5);
+		assert!(faces[0].head_rotation().abs_diff_eq(Quat::from_rotation_y(-10f32.to_radians()), 1e-6));
+		assert!(Face::parse_packet(&data[1..]).is_err());
+
+		let messages = faces[0].to_messages();
+		assert!(matches!(&messages[1], VMCMessage::BlendShape(shape) if shape.key.as_str() == "Blink_L" && shape.value == 0.75));
+		assert!(matches!(&messages[3], VMCMessage::BlendShape(shape) if shape.key.as_str() == "A" && shape.value == 0.5));
+		Ok(())
+	}
+
+	#[test]
+	fn test_invalid_face_data() {
+		let data = face_data(0, 0.25, 0.5, 0.0);
+		fn message<T: std::fmt::Debug>(result: VMCResult<T>) -> String {
+			match result {
+				Err(VMCError::InvalidFaceData(message)) => message,
+				result => panic!("expected InvalidFaceData, got {result:?}")
+			}
+		}
+
+		assert_eq!(message(Face::parse(&data[..FACE_LEN - 1])), format!("expected {FACE_LEN} bytes of OpenSeeFace data, got {}", FACE_LEN - 1));
+		assert_eq!(message(Face::parse(&[data.clone(), vec![0]].concat())), format!("expected {FACE_LEN} bytes of OpenSeeFace data, got {}", FACE_LEN + 1));
+		assert!(message(Face::parse(&[])).ends_with("got 0"));
+		// a truncated second face fails the whole packet, rather than returning only the first face
+		let truncated = [&data[..], &data[..100]].concat();
+		assert_eq!(message(Face::parse_packet(&truncated)), format!("OpenSeeFace packet length {} is not a multiple of {FACE_LEN}", FACE_LEN + 100));
+		// no faces are tracked
+		assert_eq!(Face::parse_packet(&[]).unwrap(), vec![]);
+	}
+
+	#[test]
+	fn test_messages_clamped() -> VMCResult<()> {
+		// eye openness can overshoot 1 when the eyes are opened wide, and the mouth features are signed
+		let face = Face::parse(&face_data(0, 1.3, -0.2, 1.5))?;
+		let messages = face.to_messages();
+		assert_eq!(messages.len(), 7);
+		let shape = |key: &str| {
+			messages
+				.iter()
+				.find_map(|message| match message {
+					VMCMessage::BlendShape(shape) if shape.key.as_str() == key => Some(shape.value),
+					_ => None
+				})
+				.unwrap()
+		};
+		assert_eq!((shape("Blink_L"), shape("Blink_R"), shape("A"), shape("I"), shape("U")), (0.0, 0.0, 0.0, 1.0, 0.0));
+		assert!(matches!(messages.last(), Some(VMCMessage::ApplyBlendShapes)));
+
+		// the pitch wraps around, so looking slightly down from ±180° doesn't flip the head
+		assert!((wrap_degrees(-175.0 - 180.0) - 5.0).abs() < 1e-4);
+		assert!((wrap_degrees(175.0 - 180.0) + 5.0).abs() < 1e-4);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_recv_malformed() -> VMCResult<()> {
+		let mut receiver = Receiver::bind("127.0.0.1:0").await?;
+		let sender = UdpSocket::bind("127.0.0.1:0").await?;
+		sender.send_to(b"garbage", receiver.local_addr()?).await?;
+		sender.send_to(&face_data(3, 1.0, 0.0, 0.0), receiver.local_addr()?).await?;
+
+		assert!(matches!(receiver.recv().await, Err(VMCError::InvalidFaceData(_))));
+		// a malformed datagram doesn't affect the next one
+		let (faces, peer) = receiver.recv().await?;
+		assert_eq!(peer, sender.local_addr()?);
+		assert_eq!(faces.iter().map(|face| face.id).collect::<Vec<_>>(), [3]);
+		Ok(())
+	}
+}
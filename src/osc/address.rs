@@ -0,0 +1,256 @@
+//! OSC address pattern matching.
+
+use super::{OSCError, OSCMessage, OSCResult};
+
+/// Characters which may not appear in an OSC address, other than as separators.
+const RESERVED: &[char] = &[' ', '#', '*', ',', '/', '?', '[', ']', '{', '}'];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+	Literal(char),
+	/// `?`
+	AnyChar,
+	/// `*`
+	AnySequence,
+	/// `[abc]`, `[a-z]`, `[!abc]`
+	Class {
+		negated: bool,
+		ranges: Vec<(char, char)>
+	},
+	/// `{foo,bar}`
+	Alternatives(Vec<Vec<char>>)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+	Tokens(Vec<Token>),
+	/// An empty segment produced by `//`, which matches any number of address segments.
+	Descendants
+}
+
+/// A compiled OSC address pattern, which can be matched against the address of an [`OSCMessage`].
+///
+/// Supports the OSC 1.0 wildcards `?` (any single character), `*` (any sequence of characters), `[]` (any character in
+/// the list or range, or not in it if the list starts with `!`), and `{}` (any of the comma-separated strings), as
+/// well as the OSC 1.1 path-traversing wildcard `//`, which matches any number of path segments, including none.
+/// Wildcards other than `//` never match across a `/`.
+///
+/// # Examples
+///
+/// ```
+/// use vmc::osc::Matcher;
+///
+/// let matcher = Matcher::new("/VMC/Ext/{Bone,Root}/Pos")?;
+/// assert!(matcher.match_address("/VMC/Ext/Bone/Pos"));
+/// assert!(!matcher.match_address("/VMC/Ext/Blend/Val"));
+///
+/// let matcher = Matcher::new("//Pos")?;
+/// assert!(matcher.match_address("/VMC/Ext/Root/Pos"));
+/// assert!(matcher.match_address("/VMC/Ext/Bone/Pos"));
+/// assert!(!matcher.match_address("/VMC/Ext/Tra/Pos/Local"));
+/// # Ok::<_, vmc::osc::OSCError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Matcher {
+	pattern: String,
+	segments: Vec<Segment>
+}
+
+impl Matcher {
+	/// Compiles an address pattern.
+	///
+	/// Returns [`OSCError::BadAddressPattern`] if the pattern doesn't start with `/`, has an empty segment other than a
+	/// single `//`, contains a space, `#` or misplaced `,`, or has unbalanced or invalid brackets or braces.
+	pub fn new(pattern: &str) -> OSCResult<Self> {
+		let bad = |msg: &str| OSCError::BadAddressPattern(format!("{msg}: {pattern}"));
+		let rest = pattern.strip_prefix('/').ok_or_else(|| bad("pattern must start with '/'"))?;
+		let mut segments = Vec::new();
+		for part in rest.split('/') {
+			if part.is_empty() {
+				if !matches!(segments.last(), Some(Segment::Descendants)) {
+					segments.push(Segment::Descendants);
+					continue;
+				}
+				return Err(bad("empty path segment"));
+			}
+			segments.push(Segment::Tokens(parse_segment(part).map_err(bad)?));
+		}
+		if let Some(Segment::Descendants) = segments.last() {
+			return Err(bad("pattern must not end with '/'"));
+		}
+		Ok(Self {
+			pattern: pattern.to_string(),
+			segments
+		})
+	}
+
+	/// Returns the pattern this matcher was compiled from.
+	pub fn pattern(&self) -> &str {
+		&self.pattern
+	}
+
+	/// Returns `true` if the pattern matches the given address.
+	///
+	/// The address itself is not validated; see [`verify_address`].
+	pub fn match_address(&self, address: &str) -> bool {
+		let Some(address) = address.strip_prefix('/') else {
+			return false;
+		};
+		let parts: Vec<Vec<char>> = address.split('/').map(|part| part.chars().collect()).collect();
+		match_segments(&self.segments, &parts)
+	}
+
+	/// Returns `true` if the pattern matches the address of the given message.
+	pub fn matches(&self, message: &OSCMessage) -> bool {
+		self.match_address(&message.addr)
+	}
+}
+
+/// Checks that `address` is a valid OSC address: it must start with `/`, have no empty segments, and contain none of
+/// the characters reserved for address patterns.
+pub fn verify_address(address: &str) -> OSCResult<()> {
+	let bad = |msg: &str| OSCError::BadAddress(format!("{msg}: {address}"));
+	let rest = address.strip_prefix('/').ok_or_else(|| bad("address must start with '/'"))?;
+	for part in rest.split('/') {
+		if part.is_empty() {
+			return Err(bad("empty path segment"));
+		}
+		if let Some(c) = part.chars().find(|c| RESERVED.contains(c)) {
+			return Err(bad(&format!("reserved character {c:?}")));
+		}
+	}
+	Ok(())
+}
+
+fn parse_segment(part: &str) -> Result<Vec<Token>, &'static str> {
+	let mut tokens = Vec::new();
+	let mut chars = part.chars();
+	while let Some(c) = chars.next() {
+		let token = match c {
+			'?' => Token::AnyChar,
+			'*' => {
+				// consecutive stars are equivalent to one
+				if tokens.last() == Some(&Token::AnySequence) {
+					continue;
+				}
+				Token::AnySequence
+			}
+			'[' => {
+				let mut negated = false;
+				let mut members = Vec::new();
+				loop {
+					match chars.next() {
+						Some(']') => break,
+						Some('!') if members.is_empty() && !negated => negated = true,
+						Some(c) if RESERVED.contains(&c) => return Err("invalid character in '[]'"),
+						Some(c) => members.push(c),
+						None => return Err("unclosed '['")
+					}
+				}
+				if members.is_empty() {
+					return Err("empty '[]'");
+				}
+				let mut ranges = Vec::new();
+				let mut i = 0;
+				while i < members.len() {
+					// a '-' at the start or end of the list is literal
+					if i + 2 < members.len() && members[i + 1] == '-' {
+						let (start, end) = (members[i], members[i + 2]);
+						if start > end {
+							return Err("reversed range in '[]'");
+						}
+						ranges.push((start, end));
+						i += 3;
+					} else {
+						ranges.push((members[i], members[i]));
+						i += 1;
+					}
+				}
+				Token::Class { negated, ranges }
+			}
+			'{' => {
+				let mut alternatives = vec![Vec::new()];
+				loop {
+					match chars.next() {
+						Some('}') => break,
+						Some(',') => alternatives.push(Vec::new()),
+						Some(c) if RESERVED.contains(&c) => return Err("invalid character in '{}'"),
+						Some(c) => alternatives.last_mut().unwrap().push(c),
+						None => return Err("unclosed '{'")
+					}
+				}
+				Token::Alternatives(alternatives)
+			}
+			']' | '}' => return Err("unbalanced bracket"),
+			' ' | '#' | ',' => return Err("reserved character"),
+			c => Token::Literal(c)
+		};
+		tokens.push(token);
+	}
+	Ok(tokens)
+}
+
+fn match_segments(segments: &[Segment], parts: &[Vec<char>]) -> bool {
+	match segments.split_first() {
+		None => parts.is_empty(),
+		Some((Segment::Descendants, rest)) => (0..=parts.len()).any(|skip| match_segments(rest, &parts[skip..])),
+		Some((Segment::Tokens(tokens), rest)) => match parts.split_first() {
+			Some((part, parts)) => match_tokens(tokens, part) && match_segments(rest, parts),
+			None => false
+		}
+	}
+}
+
+fn match_tokens(tokens: &[Token], s: &[char]) -> bool {
+	let Some((token, rest)) = tokens.split_first() else {
+		return s.is_empty();
+	};
+	match token {
+		Token::Literal(c) => s.first() == Some(c) && match_tokens(rest, &s[1..]),
+		Token::AnyChar => !s.is_empty() && match_tokens(rest, &s[1..]),
+		Token::AnySequence => (0..=s.len()).any(|skip| match_tokens(rest, &s[skip..])),
+		Token::Class { negated, ranges } => match s.first() {
+			Some(c) => ranges.iter().any(|(start, end)| (*start..=*end).contains(c)) != *negated && match_tokens(rest, &s[1..]),
+			None => false
+		},
+		Token::Alternatives(alternatives) => alternatives
+			.iter()
+			.any(|alternative| s.starts_with(alternative) && match_tokens(rest, &s[alternative.len()..]))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_matcher() -> OSCResult<()> {
+		let matcher = Matcher::new("/VMC/Ext/Bone/Pos")?;
+		assert!(matcher.match_address("/VMC/Ext/Bone/Pos"));
+		assert!(!matcher.match_address("/VMC/Ext/Bone/Pos/Local"));
+
+		let matcher = Matcher::new("/VMC/*/B?ne/P[!a-n]s")?;
+		assert!(matcher.match_address("/VMC/Ext/Bone/Pos"));
+		assert!(!matcher.match_address("/VMC/Ext/Bone/Pas"));
+		assert!(!matcher.match_address("/VMC/Ext/Tra/Bone/Pos"));
+
+		let matcher = Matcher::new("/VMC/Ext/{Hmd,Con,Tra}/Pos*")?;
+		assert!(matcher.match_address("/VMC/Ext/Con/Pos"));
+		assert!(!matcher.match_address("/VMC/Ext/Tra/Pos/Local"));
+		assert!(matcher.match_address("/VMC/Ext/Tra/PosLocal"));
+
+		let matcher = Matcher::new("/VMC//Pos")?;
+		assert!(matcher.match_address("/VMC/Pos"));
+		assert!(matcher.match_address("/VMC/Ext/Root/Pos"));
+		assert!(!matcher.match_address("/Other/Pos"));
+
+		for pattern in ["VMC", "/VMC/", "/VMC///Pos", "/VMC/[a-", "/VMC/{a,b", "/VMC/a]", "/VMC/a b"] {
+			assert!(matches!(Matcher::new(pattern), Err(OSCError::BadAddressPattern(_))), "{pattern}");
+		}
+
+		assert!(verify_address("/VMC/Ext/Bone/Pos").is_ok());
+		assert!(verify_address("/VMC/Ext/*").is_err());
+		assert!(verify_address("/VMC//Pos").is_err());
+		Ok(())
+	}
+}
@@ -1,24 +1,164 @@
+use std::borrow::Cow;
+
+use bytes::Bytes;
 use nom::{
 	Err, IResult, Offset,
 	bytes::complete::{take, take_till},
 	combinator::{map, map_parser, map_res},
-	multi::many0,
 	number::complete::{be_f32, be_f64, be_i32, be_i64, be_u32},
 	sequence::{terminated, tuple}
 };
+use smallvec::SmallVec;
 
 use super::{
-	OSCArray, OSCBundle, OSCColor, OSCMessage, OSCMidiMessage, OSCPacket, OSCTime, OSCType,
+	OSCArgs, OSCArray, OSCBundle, OSCColor, OSCMessage, OSCMidiMessage, OSCPacket, OSCTime, OSCType,
 	error::{OSCError, OSCResult}
 };
 
 /// Common MTU size for ethernet
 pub const MTU: usize = 1536;
 
+/// The largest UDP payload which fits in a single Ethernet frame without IP fragmentation: the 1500 byte Ethernet MTU,
+/// minus the 20 byte IPv4 header & 8 byte UDP header.
+pub const MAX_UDP_PAYLOAD: usize = 1472;
+
+/// Limits applied while decoding packets, to protect against crafted packets which would otherwise cause deep recursion
+/// or excessive allocations.
+///
+/// The default limits are far above anything sent by VMC applications, but low enough to be safe on a socket exposed to
+/// untrusted peers. Exceeding a limit fails decoding with [`OSCError::LimitExceeded`] (wrapped in
+/// [`OSCError::Decode`]).
+///
+/// # Examples
+///
+/// ```
+/// use vmc::osc::{self, DecodeLimits, OSCError};
+///
+/// let packet = osc::encode(&vmc::OSCPacket::Message(osc::OSCMessage::new("/test", (1, 2, 3))))?;
+/// let limits = DecodeLimits::default().with_max_args(2);
+/// let err = osc::decode_udp_with_limits(&packet, &limits).unwrap_err();
+/// assert!(matches!(err.inner(), OSCError::LimitExceeded(_)));
+/// # Ok::<_, OSCError>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeLimits {
+	max_depth: usize,
+	max_args: usize,
+	max_string_len: usize,
+	max_blob_len: usize
+}
+
+impl Default for DecodeLimits {
+	fn default() -> Self {
+		Self {
+			max_depth: 16,
+			max_args: 1024,
+			max_string_len: 4096,
+			max_blob_len: 65536
+		}
+	}
+}
+
+impl DecodeLimits {
+	/// Creates limits which never reject a packet. Only use this for trusted input.
+	pub fn unlimited() -> Self {
+		Self {
+			max_depth: usize::MAX,
+			max_args: usize::MAX,
+			max_string_len: usize::MAX,
+			max_blob_len: usize::MAX
+		}
+	}
+
+	/// Sets the maximum nesting depth of bundles & arrays. A message outside of any bundle has a depth of 0. Defaults
+	/// to 16.
+	pub fn with_max_depth(mut self, depth: usize) -> Self {
+		self.max_depth = depth;
+		self
+	}
+
+	/// Sets the maximum number of args in a message, including args nested in arrays. Defaults to 1024.
+	pub fn with_max_args(mut self, args: usize) -> Self {
+		self.max_args = args;
+		self
+	}
+
+	/// Sets the maximum length in bytes of an address, type tag, or string arg. Defaults to 4096.
+	pub fn with_max_string_len(mut self, len: usize) -> Self {
+		self.max_string_len = len;
+		self
+	}
+
+	/// Sets the maximum size in bytes of a blob arg. Defaults to 65536.
+	pub fn with_max_blob_len(mut self, len: usize) -> Self {
+		self.max_blob_len = len;
+		self
+	}
+
+	/// Returns the maximum nesting depth of bundles & arrays.
+	pub fn max_depth(&self) -> usize {
+		self.max_depth
+	}
+
+	/// Returns the maximum number of args in a message.
+	pub fn max_args(&self) -> usize {
+		self.max_args
+	}
+
+	/// Returns the maximum length of an address, type tag, or string arg.
+	pub fn max_string_len(&self) -> usize {
+		self.max_string_len
+	}
+
+	/// Returns the maximum size of a blob arg.
+	pub fn max_blob_len(&self) -> usize {
+		self.max_blob_len
+	}
+}
+
+/// State shared by all parsers while decoding a packet.
+#[derive(Clone, Copy)]
+struct Context<'a> {
+	original_input: &'a [u8],
+	limits: DecodeLimits
+}
+
+impl Context<'_> {
+	/// Wraps an error in [`OSCError::Decode`], recording the offset of `input` & the address of the message being
+	/// parsed. Errors which already carry an offset keep it, so the innermost parser determines the reported offset.
+	fn wrap(&self, err: nom::Err<OSCError>, input: &[u8], addr: Option<&str>) -> nom::Err<OSCError> {
+		err.map(|error| match error {
+			OSCError::Decode { offset, addr: None, error } => OSCError::Decode {
+				offset,
+				addr: addr.map(str::to_string),
+				error
+			},
+			error @ OSCError::Decode { .. } => error,
+			error => OSCError::Decode {
+				offset: self.original_input.offset(input),
+				addr: addr.map(str::to_string),
+				error: Box::new(error)
+			}
+		})
+	}
+}
+
+fn limit_exceeded<I, T>(msg: String) -> IResult<I, T, OSCError> {
+	Err(nom::Err::Failure(OSCError::LimitExceeded(msg)))
+}
+
 /// Takes a bytes slice representing a UDP packet and returns the OSC packet as well as a slice of
 /// any bytes remaining after the OSC packet.
+///
+/// The [default limits](DecodeLimits::default) are applied; see [`decode_udp_with_limits`].
 pub fn decode_udp(msg: &[u8]) -> OSCResult<(&[u8], OSCPacket)> {
-	match decode_packet(msg, msg) {
+	decode_udp_with_limits(msg, &DecodeLimits::default())
+}
+
+/// Like [`decode_udp`], but with custom [`DecodeLimits`].
+pub fn decode_udp_with_limits<'a>(msg: &'a [u8], limits: &DecodeLimits) -> OSCResult<(&'a [u8], OSCPacket)> {
+	let ctx = Context { original_input: msg, limits: *limits };
+	match decode_packet(msg, ctx, 0) {
 		Ok((remainder, osc_packet)) => Ok((remainder, osc_packet)),
 		Err(e) => match e {
 			Err::Incomplete(_) => Err(OSCError::BadPacket("Incomplete data")),
@@ -27,6 +167,14 @@ pub fn decode_udp(msg: &[u8]) -> OSCResult<(&[u8], OSCPacket)> {
 	}
 }
 
+/// Like [`decode_udp`], but takes a [`Bytes`] buffer, such as a datagram received with
+/// [`VMCSocket::recv_raw`](crate::VMCSocket::recv_raw), and returns any remaining bytes as a [`Bytes`] handle into
+/// the same buffer instead of a borrowed slice.
+pub fn decode_udp_bytes(msg: &Bytes) -> OSCResult<(Bytes, OSCPacket)> {
+	let (remainder, packet) = decode_udp(msg)?;
+	Ok((msg.slice_ref(remainder), packet))
+}
+
 /// Takes a bytes slice from a TCP stream (or any stream-based protocol) and returns the first OSC
 /// packet as well as a slice of the bytes remaining after the packet.
 ///
@@ -39,6 +187,11 @@ pub fn decode_udp(msg: &[u8]) -> OSCResult<(&[u8], OSCPacket)> {
 ///
 /// [OSC specification]: https://cnmat.org/OpenSoundControl/OSC-spec.html
 pub fn decode_tcp(msg: &[u8]) -> OSCResult<(&[u8], Option<OSCPacket>)> {
+	decode_tcp_with_limits(msg, &DecodeLimits::default())
+}
+
+/// Like [`decode_tcp`], but with custom [`DecodeLimits`].
+pub fn decode_tcp_with_limits<'a>(msg: &'a [u8], limits: &DecodeLimits) -> OSCResult<(&'a [u8], Option<OSCPacket>)> {
 	let (input, osc_packet_length) = match be_u32(msg) {
 		Ok((i, o)) => (i, o),
 		Err(e) => match e {
@@ -47,12 +200,14 @@ pub fn decode_tcp(msg: &[u8]) -> OSCResult<(&[u8], Option<OSCPacket>)> {
 		}
 	};
 
-	if osc_packet_length as usize > msg.len() {
+	if osc_packet_length as usize > input.len() {
 		return Ok((msg, None));
 	}
 
-	match decode_packet(input, msg).map(|(remainder, osc_packet)| (remainder, Some(osc_packet))) {
-		Ok((remainder, osc_packet)) => Ok((remainder, osc_packet)),
+	let (input, remainder) = input.split_at(osc_packet_length as usize);
+	let ctx = Context { original_input: msg, limits: *limits };
+	match decode_packet(input, ctx, 0) {
+		Ok((_, osc_packet)) => Ok((remainder, Some(osc_packet))),
 		Err(e) => match e {
 			Err::Incomplete(_) => Err(OSCError::BadPacket("Incomplete data")),
 			Err::Error(e) | Err::Failure(e) => Err(e)
@@ -63,10 +218,15 @@ pub fn decode_tcp(msg: &[u8]) -> OSCResult<(&[u8], Option<OSCPacket>)> {
 /// Takes a bytes slice from a TCP stream (or any stream-based protocol) and returns a vec of all
 /// OSC packets in the slice as well as a slice of the bytes remaining after the last packet.
 pub fn decode_tcp_vec(msg: &[u8]) -> OSCResult<(&[u8], Vec<OSCPacket>)> {
+	decode_tcp_vec_with_limits(msg, &DecodeLimits::default())
+}
+
+/// Like [`decode_tcp_vec`], but with custom [`DecodeLimits`].
+pub fn decode_tcp_vec_with_limits<'a>(msg: &'a [u8], limits: &DecodeLimits) -> OSCResult<(&'a [u8], Vec<OSCPacket>)> {
 	let mut input = msg;
 	let mut osc_packets = vec![];
 
-	while let (remainder, Some(osc_packet)) = decode_tcp(input)? {
+	while let (remainder, Some(osc_packet)) = decode_tcp_with_limits(input, limits)? {
 		input = remainder;
 		osc_packets.push(osc_packet);
 
@@ -78,92 +238,369 @@ pub fn decode_tcp_vec(msg: &[u8]) -> OSCResult<(&[u8], Vec<OSCPacket>)> {
 	Ok((input, osc_packets))
 }
 
-fn decode_packet<'a>(input: &'a [u8], original_input: &'a [u8]) -> IResult<&'a [u8], OSCPacket, OSCError> {
+/// The default maximum size of a packet accepted by a [`StreamDecoder`].
+const DEFAULT_MAX_PACKET_LEN: usize = 1024 * 1024;
+
+/// An incremental decoder for packets read from a stream-based transport, such as TCP, where each packet is preceded by
+/// its size as a big-endian `int32` (see [`decode_tcp`]).
+///
+/// Unlike [`decode_tcp_vec`], the caller doesn't need to hold on to partial packets: data read from the stream can be
+/// fed to the decoder in chunks of any size via [`StreamDecoder::push`], and complete packets are returned by
+/// [`StreamDecoder::next_packet`] as soon as they are available.
+///
+/// A packet which fails to decode, or which is larger than the [maximum packet
+/// size](StreamDecoder::with_max_packet_len), is skipped after returning an error, so the decoder stays in sync with
+/// the stream.
+///
+/// # Example
+///
+/// ```
+/// use vmc::osc::{OSCMessage, OSCPacket, StreamDecoder};
+///
+/// let packet = OSCPacket::Message(OSCMessage::new("/VMC/Ext/T", (1.0f32,)));
+/// let encoded = vmc::osc::encode(&packet)?;
+/// let mut bytes = (encoded.len() as u32).to_be_bytes().to_vec();
+/// bytes.extend_from_slice(&encoded);
+///
+/// let mut decoder = StreamDecoder::new();
+/// decoder.push(&bytes[..6]);
+/// assert_eq!(decoder.next_packet()?, None);
+/// decoder.push(&bytes[6..]);
+/// assert_eq!(decoder.next_packet()?, Some(packet));
+/// assert!(!decoder.has_partial());
+/// # Ok::<(), vmc::osc::OSCError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct StreamDecoder {
+	buf: Vec<u8>,
+	/// The offset of the first byte in `buf` which hasn't been decoded yet.
+	pos: usize,
+	/// The number of bytes still to be discarded from an oversized packet.
+	skip: usize,
+	limits: DecodeLimits,
+	max_packet_len: usize
+}
+
+impl Default for StreamDecoder {
+	fn default() -> Self {
+		Self {
+			buf: Vec::new(),
+			pos: 0,
+			skip: 0,
+			limits: DecodeLimits::default(),
+			max_packet_len: DEFAULT_MAX_PACKET_LEN
+		}
+	}
+}
+
+impl StreamDecoder {
+	/// Creates a new decoder with the [default limits](DecodeLimits::default).
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Sets the limits applied when decoding each packet.
+	pub fn with_limits(mut self, limits: DecodeLimits) -> Self {
+		self.limits = limits;
+		self
+	}
+
+	/// Sets the maximum size in bytes of a packet, which bounds how much data is buffered for a single packet. Defaults
+	/// to 1 MiB.
+	pub fn with_max_packet_len(mut self, len: usize) -> Self {
+		self.max_packet_len = len;
+		self
+	}
+
+	/// Returns the limits applied when decoding each packet.
+	pub fn limits(&self) -> &DecodeLimits {
+		&self.limits
+	}
+
+	/// Returns the maximum size of a packet.
+	pub fn max_packet_len(&self) -> usize {
+		self.max_packet_len
+	}
+
+	/// Feeds raw bytes from the stream into the decoder.
+	pub fn push(&mut self, mut data: &[u8]) {
+		if self.skip > 0 {
+			let n = self.skip.min(data.len());
+			self.skip -= n;
+			data = &data[n..];
+		}
+		// reclaim the space of packets which were already returned
+		if self.pos > 0 {
+			self.buf.drain(..self.pos);
+			self.pos = 0;
+		}
+		self.buf.extend_from_slice(data);
+	}
+
+	/// Decodes the next complete packet, if one is available.
+	pub fn next_packet(&mut self) -> OSCResult<Option<OSCPacket>> {
+		let data = &self.buf[self.pos..];
+		let Some(size) = data.get(..4) else {
+			return Ok(None);
+		};
+		let len = u32::from_be_bytes(size.try_into().unwrap()) as usize;
+		if len > self.max_packet_len {
+			let buffered = data.len() - 4;
+			if buffered >= len {
+				self.pos += 4 + len;
+			} else {
+				self.skip = len - buffered;
+				self.buf.clear();
+				self.pos = 0;
+			}
+			// the size prefix is at the start of the packet
+			return Err(OSCError::Decode {
+				offset: 0,
+				addr: None,
+				error: Box::new(OSCError::LimitExceeded(format!("packet larger than {} bytes", self.max_packet_len)))
+			});
+		}
+		let Some(packet) = data.get(4..4 + len) else {
+			return Ok(None);
+		};
+		let res = decode_udp_with_limits(packet, &self.limits).map(|(_, packet)| Some(packet));
+		self.pos += 4 + len;
+		res
+	}
+
+	/// Returns `true` if the decoder holds data for an incomplete packet.
+	pub fn has_partial(&self) -> bool {
+		self.skip > 0 || self.pos < self.buf.len()
+	}
+}
+
+/// An arg of a message decoded by [`visit_udp`], with strings borrowed from the datagram where possible.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ArgRef<'a> {
+	Int(i32),
+	Float(f32),
+	String(Cow<'a, str>),
+	Other(OSCType)
+}
+
+impl From<OSCType> for ArgRef<'_> {
+	fn from(arg: OSCType) -> Self {
+		match arg {
+			OSCType::Int(i) => ArgRef::Int(i),
+			OSCType::Float(f) => ArgRef::Float(f),
+			OSCType::String(s) => ArgRef::String(Cow::Owned(s)),
+			arg => ArgRef::Other(arg)
+		}
+	}
+}
+
+impl From<ArgRef<'_>> for OSCType {
+	fn from(arg: ArgRef<'_>) -> Self {
+		match arg {
+			ArgRef::Int(i) => OSCType::Int(i),
+			ArgRef::Float(f) => OSCType::Float(f),
+			ArgRef::String(s) => OSCType::String(s.into_owned()),
+			ArgRef::Other(arg) => arg
+		}
+	}
+}
+
+/// Decodes a UDP packet like [`decode_udp_with_limits`], but instead of building an [`OSCPacket`], passes the address &
+/// args of each message to `f` in order, borrowing string args from `msg` so they don't need to be copied.
+///
+/// Messages before a malformed element are still passed to `f` before the error is returned.
+pub(crate) fn visit_udp<'a>(msg: &'a [u8], limits: &DecodeLimits, f: &mut dyn FnMut(&'a str, &[ArgRef<'a>])) -> OSCResult<()> {
+	let ctx = Context { original_input: msg, limits: *limits };
+	match visit_packet(msg, ctx, 0, f) {
+		Ok(_) => Ok(()),
+		Err(e) => match e {
+			Err::Incomplete(_) => Err(OSCError::BadPacket("Incomplete data")),
+			Err::Error(e) | Err::Failure(e) => Err(e)
+		}
+	}
+}
+
+fn visit_packet<'a>(input: &'a [u8], ctx: Context<'a>, depth: usize, f: &mut dyn FnMut(&'a str, &[ArgRef<'a>])) -> IResult<&'a [u8], (), OSCError> {
+	let start = input;
 	if input.is_empty() {
-		return Err(nom::Err::Error(OSCError::BadPacket("Empty packet.")));
+		return Err(ctx.wrap(nom::Err::Error(OSCError::BadPacket("Empty packet.")), start, None));
 	}
 
-	let (input, addr) = read_osc_string(input, original_input)?;
+	let (input, addr) = read_osc_str(input, ctx).map_err(|e| ctx.wrap(e, start, None))?;
 
-	match addr.chars().next() {
-		Some('/') => decode_message(addr, input, original_input),
-		Some('#') if &addr == "#bundle" => decode_bundle(input, original_input),
-		_ => Err(nom::Err::Error(OSCError::BadPacket("Invalid message address or bundle tag")))
+	match addr.as_bytes().first() {
+		Some(b'/') => {
+			let (input, type_tags) = read_osc_str(input, ctx).map_err(|e| ctx.wrap(e, input, Some(addr)))?;
+			let (input, args) = if type_tags.len() > 1 {
+				read_osc_arg_refs(input, ctx, type_tags).map_err(|e| ctx.wrap(e, input, Some(addr)))?
+			} else {
+				(input, SmallVec::new())
+			};
+			f(addr, &args);
+			Ok((input, ()))
+		}
+		Some(b'#') if addr == "#bundle" => visit_bundle(input, ctx, depth, f).map_err(|e| ctx.wrap(e, start, None)),
+		_ => Err(ctx.wrap(nom::Err::Error(OSCError::BadPacket("Invalid message address or bundle tag")), start, None))
 	}
 }
 
-fn decode_message<'a>(addr: String, input: &'a [u8], original_input: &'a [u8]) -> IResult<&'a [u8], OSCPacket, OSCError> {
-	let (input, type_tags) = read_osc_string(input, original_input)?;
+fn visit_bundle<'a>(input: &'a [u8], ctx: Context<'a>, depth: usize, f: &mut dyn FnMut(&'a str, &[ArgRef<'a>])) -> IResult<&'a [u8], (), OSCError> {
+	if depth >= ctx.limits.max_depth {
+		return limit_exceeded(format!("bundle nesting deeper than {}", ctx.limits.max_depth));
+	}
+	let (mut input, _) = read_time_tag(input)?;
+	while !input.is_empty() {
+		let (remainder, elem_size) = be_u32(input)?;
+		let (remainder, element) =
+			take(elem_size)(remainder).map_err(|_: nom::Err<OSCError>| nom::Err::Error(OSCError::BadBundle("Bundle shorter than expected!".to_string())))?;
+		visit_packet(element, ctx, depth + 1, f)?;
+		input = remainder;
+	}
+	Ok((input, ()))
+}
 
-	if type_tags.len() > 1 {
-		let (input, args) = read_osc_args(input, original_input, type_tags)?;
-		Ok((input, OSCPacket::Message(OSCMessage { addr, args })))
-	} else {
-		Ok((input, OSCPacket::Message(OSCMessage { addr, args: vec![] })))
+/// Reads the args of a message like [`read_osc_args`], borrowing string args from the input. Messages containing arrays
+/// are rare enough that they are decoded into owned args.
+fn read_osc_arg_refs<'a>(mut input: &'a [u8], ctx: Context<'a>, raw_type_tags: &str) -> IResult<&'a [u8], SmallVec<[ArgRef<'a>; 16]>, OSCError> {
+	if raw_type_tags.contains(['[', ']']) {
+		let (input, args) = read_osc_args(input, ctx, raw_type_tags)?;
+		return Ok((input, args.into_iter().map(ArgRef::from).collect()));
+	}
+
+	// skip the leading ','
+	let type_tags = raw_type_tags.get(1..).unwrap_or_default();
+	if type_tags.len() > ctx.limits.max_args {
+		return limit_exceeded(format!("message has more than {} args", ctx.limits.max_args));
+	}
+
+	let mut args = SmallVec::with_capacity(type_tags.len());
+	for tag in type_tags.chars() {
+		let (remainder, arg) = match tag {
+			'f' => map(be_f32, ArgRef::Float)(input),
+			'i' => map(be_i32, ArgRef::Int)(input),
+			's' => read_osc_str(input, ctx).map(|(remainder, string)| (remainder, ArgRef::String(Cow::Borrowed(string)))),
+			tag => read_osc_arg(input, ctx, tag).map(|(remainder, arg)| (remainder, ArgRef::Other(arg)))
+		}
+		.map_err(|e| ctx.wrap(e, input, None))?;
+		input = remainder;
+		args.push(arg);
 	}
+	Ok((input, args))
 }
 
-fn decode_bundle<'a>(input: &'a [u8], original_input: &'a [u8]) -> IResult<&'a [u8], OSCPacket, OSCError> {
-	let (input, (timetag, content)) = tuple((read_time_tag, many0(|input| read_bundle_element(input, original_input))))(input)?;
+fn decode_packet<'a>(input: &'a [u8], ctx: Context<'a>, depth: usize) -> IResult<&'a [u8], OSCPacket, OSCError> {
+	let start = input;
+	if input.is_empty() {
+		return Err(ctx.wrap(nom::Err::Error(OSCError::BadPacket("Empty packet.")), start, None));
+	}
+
+	let (input, addr) = read_osc_str(input, ctx).map_err(|e| ctx.wrap(e, start, None))?;
+
+	match addr.as_bytes().first() {
+		Some(b'/') => decode_message(addr, input, ctx),
+		Some(b'#') if addr == "#bundle" => decode_bundle(input, ctx, depth).map_err(|e| ctx.wrap(e, start, None)),
+		_ => Err(ctx.wrap(nom::Err::Error(OSCError::BadPacket("Invalid message address or bundle tag")), start, None))
+	}
+}
+
+fn decode_message<'a>(addr: &'a str, input: &'a [u8], ctx: Context<'a>) -> IResult<&'a [u8], OSCPacket, OSCError> {
+	let (input, type_tags) = read_osc_str(input, ctx).map_err(|e| ctx.wrap(e, input, Some(addr)))?;
+
+	let (input, args) = if type_tags.len() > 1 {
+		read_osc_args(input, ctx, type_tags).map_err(|e| ctx.wrap(e, input, Some(addr)))?
+	} else {
+		(input, OSCArgs::new())
+	};
+	Ok((input, OSCPacket::Message(OSCMessage { addr: addr.to_string(), args })))
+}
+
+fn decode_bundle<'a>(input: &'a [u8], ctx: Context<'a>, depth: usize) -> IResult<&'a [u8], OSCPacket, OSCError> {
+	if depth >= ctx.limits.max_depth {
+		return limit_exceeded(format!("bundle nesting deeper than {}", ctx.limits.max_depth));
+	}
+	let (mut input, timetag) = read_time_tag(input)?;
+	let mut content = Vec::new();
+	while !input.is_empty() {
+		let (remainder, packet) = read_bundle_element(input, ctx, depth + 1)?;
+		input = remainder;
+		content.push(packet);
+	}
 
 	Ok((input, OSCPacket::Bundle(OSCBundle { timetag, content })))
 }
 
-fn read_bundle_element<'a>(input: &'a [u8], original_input: &'a [u8]) -> IResult<&'a [u8], OSCPacket, OSCError> {
+fn read_bundle_element<'a>(input: &'a [u8], ctx: Context<'a>, depth: usize) -> IResult<&'a [u8], OSCPacket, OSCError> {
 	let (input, elem_size) = be_u32(input)?;
 
 	map_parser(
 		move |input| take(elem_size)(input).map_err(|_: nom::Err<OSCError>| nom::Err::Error(OSCError::BadBundle("Bundle shorter than expected!".to_string()))),
-		|input| decode_packet(input, original_input)
+		|input| decode_packet(input, ctx, depth)
 	)(input)
 }
 
-fn read_osc_string<'a>(input: &'a [u8], original_input: &'a [u8]) -> IResult<&'a [u8], String, OSCError> {
-	map_res(terminated(take_till(|c| c == 0u8), pad_to_32_bit_boundary(original_input)), |str_buf: &'a [u8]| {
-		String::from_utf8(str_buf.into())
-			.map_err(OSCError::StringError)
-			.map(|s| s.trim_matches(0u8 as char).to_string())
-	})(input)
+/// Reads a NUL-terminated, padded string, borrowing it from the input. Addresses & type tags are only inspected, so
+/// only string arguments are copied into an owned `String`.
+fn read_osc_str<'a>(input: &'a [u8], ctx: Context<'a>) -> IResult<&'a [u8], &'a str, OSCError> {
+	let (input, str_buf) = take_till(|c| c == 0u8)(input)?;
+	if str_buf.len() > ctx.limits.max_string_len {
+		return limit_exceeded(format!("string longer than {} bytes", ctx.limits.max_string_len));
+	}
+	let (input, ()) = pad_to_32_bit_boundary(ctx.original_input)(input)?;
+	// only build the owned `FromUtf8Error` (which `OSCError` carries) once we know the string is invalid
+	let string = std::str::from_utf8(str_buf).map_err(|_| nom::Err::Error(OSCError::StringError(String::from_utf8(str_buf.to_vec()).unwrap_err())))?;
+	Ok((input, string))
 }
 
-fn read_osc_args<'a>(mut input: &'a [u8], original_input: &'a [u8], raw_type_tags: String) -> IResult<&'a [u8], Vec<OSCType>, OSCError> {
-	let type_tags: Vec<char> = raw_type_tags.chars().skip(1).collect();
+fn read_osc_args<'a>(mut input: &'a [u8], ctx: Context<'a>, raw_type_tags: &str) -> IResult<&'a [u8], OSCArgs, OSCError> {
+	// skip the leading ','
+	let mut type_tags = raw_type_tags.chars();
+	type_tags.next();
+	let type_tags = type_tags.as_str();
+	let arg_count = type_tags.chars().filter(|tag| *tag != '[' && *tag != ']').count();
+	if arg_count > ctx.limits.max_args {
+		return limit_exceeded(format!("message has more than {} args", ctx.limits.max_args));
+	}
 
-	let mut args: Vec<OSCType> = Vec::with_capacity(type_tags.len());
+	let mut args = OSCArgs::with_capacity(arg_count);
+	// the content of each array we're currently inside of, innermost last
 	let mut stack: Vec<Vec<OSCType>> = Vec::new();
-	for tag in type_tags {
-		if tag == '[' {
-			// array start: save current frame and start a new frame
-			// for the array's content
-			stack.push(args);
-			args = Vec::new();
+	for tag in type_tags.chars() {
+		let arg = if tag == '[' {
+			if stack.len() >= ctx.limits.max_depth {
+				return limit_exceeded(format!("array nesting deeper than {}", ctx.limits.max_depth));
+			}
+			// array start: start a new frame for the array's content
+			stack.push(Vec::new());
+			continue;
 		} else if tag == ']' {
 			// found the end of the current array:
 			// create array object from current frame and step one level up
-			let array = OSCType::Array(OSCArray { content: args });
 			match stack.pop() {
-				Some(stashed) => args = stashed,
+				Some(content) => OSCType::Array(OSCArray { content }),
 				None => return Err(nom::Err::Error(OSCError::BadMessage("Encountered ] outside array")))
 			}
-			args.push(array);
 		} else {
-			let input_and_arg = read_osc_arg(input, original_input, tag)?;
+			let input_and_arg = read_osc_arg(input, ctx, tag).map_err(|e| ctx.wrap(e, input, None))?;
 			input = input_and_arg.0;
-			args.push(input_and_arg.1);
+			input_and_arg.1
+		};
+		match stack.last_mut() {
+			Some(content) => content.push(arg),
+			None => args.push(arg)
 		}
 	}
 	Ok((input, args))
 }
 
-fn read_osc_arg<'a>(input: &'a [u8], original_input: &'a [u8], tag: char) -> IResult<&'a [u8], OSCType, OSCError> {
+fn read_osc_arg<'a>(input: &'a [u8], ctx: Context<'a>, tag: char) -> IResult<&'a [u8], OSCType, OSCError> {
 	match tag {
 		'f' => map(be_f32, OSCType::Float)(input),
 		'd' => map(be_f64, OSCType::Double)(input),
 		'i' => map(be_i32, OSCType::Int)(input),
 		'h' => map(be_i64, OSCType::Long)(input),
-		's' => read_osc_string(input, original_input).map(|(remainder, string)| (remainder, OSCType::String(string))),
+		's' => read_osc_str(input, ctx).map(|(remainder, string)| (remainder, OSCType::String(string.to_string()))),
 		't' => read_time_tag(input).map(|(remainder, time)| (remainder, OSCType::Time(time))),
-		'b' => read_blob(input, original_input),
+		'b' => read_blob(input, ctx),
 		'r' => read_osc_color(input),
 		'T' => Ok((input, true.into())),
 		'F' => Ok((input, false.into())),
@@ -185,10 +622,15 @@ fn read_char(input: &[u8]) -> IResult<&[u8], OSCType, OSCError> {
 	})(input)
 }
 
-fn read_blob<'a>(input: &'a [u8], original_input: &'a [u8]) -> IResult<&'a [u8], OSCType, OSCError> {
+fn read_blob<'a>(input: &'a [u8], ctx: Context<'a>) -> IResult<&'a [u8], OSCType, OSCError> {
 	let (input, size) = be_u32(input)?;
+	if size as usize > ctx.limits.max_blob_len {
+		return limit_exceeded(format!("blob larger than {} bytes", ctx.limits.max_blob_len));
+	}
 
-	map(terminated(take(size), pad_to_32_bit_boundary(original_input)), |blob| OSCType::Blob(blob.into()))(input)
+	// unlike strings, blobs aren't NUL-terminated, so there is no padding if the size is already a multiple of 4
+	let padding = (4 - size % 4) % 4;
+	map(terminated(take(size), take(padding)), |blob: &[u8]| OSCType::Blob(blob.into()))(input)
 }
 
 fn read_time_tag(input: &[u8]) -> IResult<&[u8], OSCTime, OSCError> {
@@ -224,3 +666,136 @@ fn pad_to_32_bit_boundary<'a>(original_input: &'a [u8]) -> impl Fn(&'a [u8]) ->
 		Ok((input, ()))
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::osc::{OSCBundle, encode};
+
+	#[test]
+	fn test_decode_limits() -> OSCResult<()> {
+		let mut packet = OSCPacket::Message(OSCMessage::new("/test", (1, "two", vec![3u8; 16])));
+		for _ in 0..4 {
+			packet = OSCPacket::Bundle(OSCBundle {
+				timetag: (0, 1).into(),
+				content: vec![packet]
+			});
+		}
+		let bytes = encode(&packet)?;
+		assert_eq!(decode_udp(&bytes)?.1, packet);
+
+		for limits in [
+			DecodeLimits::default().with_max_depth(3),
+			DecodeLimits::default().with_max_args(2),
+			DecodeLimits::default().with_max_string_len(2),
+			DecodeLimits::default().with_max_blob_len(15)
+		] {
+			let err = decode_udp_with_limits(&bytes, &limits).unwrap_err();
+			assert!(matches!(err.inner(), OSCError::LimitExceeded(_)), "{limits:?}");
+		}
+		assert!(decode_udp_with_limits(&bytes, &DecodeLimits::default().with_max_depth(4)).is_ok());
+
+		let nested = OSCPacket::Message(OSCMessage::new(
+			"/test",
+			vec![OSCType::Array(OSCArray {
+				content: vec![OSCType::Array(OSCArray { content: vec![OSCType::Int(1)] })]
+			})]
+		));
+		let bytes = encode(&nested)?;
+		let err = decode_udp_with_limits(&bytes, &DecodeLimits::default().with_max_depth(1)).unwrap_err();
+		assert!(matches!(err.inner(), OSCError::LimitExceeded(_)));
+		Ok(())
+	}
+
+	#[test]
+	fn test_stream_decoder() -> OSCResult<()> {
+		let packets: Vec<OSCPacket> = (0..3).map(|i| OSCPacket::Message(OSCMessage::new("/test", (i,)))).collect();
+		let mut bytes = Vec::new();
+		for packet in &packets {
+			let encoded = encode(packet)?;
+			bytes.extend_from_slice(&(encoded.len() as u32).to_be_bytes());
+			bytes.extend_from_slice(&encoded);
+		}
+
+		// fed one byte at a time, every packet is returned once it's complete
+		let mut decoder = StreamDecoder::new();
+		let mut decoded = Vec::new();
+		for byte in &bytes {
+			decoder.push(std::slice::from_ref(byte));
+			while let Some(packet) = decoder.next_packet()? {
+				decoded.push(packet);
+			}
+		}
+		assert_eq!(decoded, packets);
+		assert!(!decoder.has_partial());
+
+		// an oversized packet is skipped, even if it arrives over multiple pushes
+		let mut decoder = StreamDecoder::new().with_max_packet_len(16);
+		let mut oversized = 64u32.to_be_bytes().to_vec();
+		oversized.extend_from_slice(&[0; 64]);
+		decoder.push(&oversized[..20]);
+		let err = decoder.next_packet().unwrap_err();
+		assert!(matches!(err, OSCError::Decode { offset: 0, .. }));
+		assert!(matches!(err.inner(), OSCError::LimitExceeded(_)));
+		assert!(decoder.has_partial());
+		decoder.push(&oversized[20..]);
+		decoder.push(&bytes);
+		assert_eq!(decoder.next_packet()?, Some(packets[0].clone()));
+
+		// as is a packet that fails to decode
+		let mut decoder = StreamDecoder::new();
+		decoder.push(&[0, 0, 0, 4, b'x', 0, 0, 0]);
+		decoder.push(&bytes);
+		assert!(decoder.next_packet().is_err());
+		assert_eq!(decoder.next_packet()?, Some(packets[0].clone()));
+		Ok(())
+	}
+
+	#[test]
+	fn test_decode_args_inline() -> OSCResult<()> {
+		let bone = OSCPacket::Message(OSCMessage::new("/VMC/Ext/Bone/Pos", ("Hips", 0.0_f32, 1.0_f32, 0.0_f32, 0.0_f32, 0.0_f32, 0.0_f32, 1.0_f32)));
+		let OSCPacket::Message(message) = decode_udp(&encode(&bone)?)?.1 else {
+			panic!("expected message")
+		};
+		assert_eq!(message.args.len(), 8);
+		assert!(!message.args.spilled());
+
+		// arrays are collected into their own frame, and args after them land back in the message
+		let args = vec![
+			OSCType::Int(1),
+			OSCType::Array(OSCArray {
+				content: vec![OSCType::Int(2), OSCType::Int(3)]
+			}),
+			OSCType::Int(4),
+		];
+		let nested = OSCPacket::Message(OSCMessage::new("/test", args));
+		assert_eq!(decode_udp(&encode(&nested)?)?.1, nested);
+
+		let long = OSCPacket::Message(OSCMessage::new("/test", vec![0_i32; 12]));
+		let OSCPacket::Message(message) = decode_udp(&encode(&long)?)?.1 else {
+			panic!("expected message")
+		};
+		assert_eq!(message.args.len(), 12);
+		assert!(message.args.spilled());
+		Ok(())
+	}
+
+	#[test]
+	fn test_decode_error_context() -> OSCResult<()> {
+		let message = OSCPacket::Message(OSCMessage::new("/VMC/Ext/Bone/Pos", ("Hips", 0.0_f32, 1.0_f32)));
+		let mut bytes = encode(&OSCPacket::Bundle(OSCBundle {
+			timetag: (0, 1).into(),
+			content: vec![message.clone(), message]
+		}))?;
+		// change the type tag of the last arg of the second message to an unknown one
+		let last_tag = bytes.iter().rposition(|b| *b == b'f').unwrap();
+		bytes[last_tag] = b'x';
+
+		let err = decode_udp(&bytes).unwrap_err();
+		assert!(matches!(err.inner(), OSCError::BadArg(_)));
+		assert_eq!(err.address(), Some("/VMC/Ext/Bone/Pos"));
+		assert_eq!(err.offset(), Some(bytes.len() - 4));
+		assert_eq!(err.to_string(), format!("bad OSC argument: Type tag \"x\" is not implemented! at offset {} in /VMC/Ext/Bone/Pos", bytes.len() - 4));
+		Ok(())
+	}
+}
@@ -0,0 +1,196 @@
+//! Dispatching OSC messages to handlers by address pattern.
+
+use std::{fmt, future::poll_fn, pin::Pin};
+
+use futures_core::Stream;
+
+use super::{FromOSCArgs, Matcher, OSCError, OSCMessage, OSCPacket, OSCResult};
+
+type Handler = Box<dyn FnMut(&OSCMessage) -> OSCResult<()> + Send>;
+
+/// Dispatches OSC messages to handlers registered under [address patterns](Matcher), turning any packet stream into a
+/// general-purpose OSC server.
+///
+/// Every handler whose pattern matches a message's address is called, in the order the handlers were registered.
+/// Messages within bundles are dispatched immediately, regardless of the bundle's time tag.
+///
+/// # Examples
+///
+/// ```no_run
+/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+/// use vmc::{VMCSocket, osc::OSCDispatcher};
+///
+/// let mut socket = VMCSocket::bind("0.0.0.0:9000").await?;
+/// let mut dispatcher = OSCDispatcher::new()
+/// 	.on("/mixer/channel/[1-8]/fader", |(level,): (f32,)| println!("fader: {level}"))?
+/// 	.on("/mixer/*/name", |(name,): (String,)| println!("name: {name}"))?
+/// 	.on_message("//mute", |message| println!("{} muted", message.addr))?;
+/// dispatcher.run(&mut socket).await?;
+/// # Ok(()) }) }
+/// ```
+#[derive(Default)]
+pub struct OSCDispatcher {
+	handlers: Vec<(Matcher, Handler)>
+}
+
+impl fmt::Debug for OSCDispatcher {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("OSCDispatcher")
+			.field("patterns", &self.handlers.iter().map(|(matcher, _)| matcher.pattern()).collect::<Vec<_>>())
+			.finish_non_exhaustive()
+	}
+}
+
+impl OSCDispatcher {
+	/// Creates a dispatcher without any handlers.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Registers a handler for messages matching `pattern`, which is called with the message's args extracted as `A`.
+	///
+	/// If a matching message's args can't be extracted as `A`, the handler is not called and dispatching returns
+	/// [`OSCError::BadArg`]. Returns [`OSCError::BadAddressPattern`] if `pattern` is invalid.
+	pub fn on<A, F>(self, pattern: &str, mut handler: F) -> OSCResult<Self>
+	where
+		A: FromOSCArgs,
+		F: FnMut(A) + Send + 'static
+	{
+		self.register(
+			pattern,
+			Box::new(move |message| {
+				handler(A::from_osc_args(&message.args)?);
+				Ok(())
+			})
+		)
+	}
+
+	/// Registers a handler for messages matching `pattern`, which is called with the whole message.
+	///
+	/// Returns [`OSCError::BadAddressPattern`] if `pattern` is invalid.
+	pub fn on_message<F>(self, pattern: &str, mut handler: F) -> OSCResult<Self>
+	where
+		F: FnMut(&OSCMessage) + Send + 'static
+	{
+		self.register(
+			pattern,
+			Box::new(move |message| {
+				handler(message);
+				Ok(())
+			})
+		)
+	}
+
+	fn register(mut self, pattern: &str, handler: Handler) -> OSCResult<Self> {
+		self.handlers.push((Matcher::new(pattern)?, handler));
+		Ok(self)
+	}
+
+	/// Returns the number of registered handlers.
+	pub fn len(&self) -> usize {
+		self.handlers.len()
+	}
+
+	/// Returns `true` if no handlers are registered.
+	pub fn is_empty(&self) -> bool {
+		self.handlers.is_empty()
+	}
+
+	/// Dispatches a message to all handlers whose pattern matches its address, returning the number of matching
+	/// handlers.
+	///
+	/// Returns the first error from a handler whose args couldn't be extracted; the remaining handlers are still
+	/// called.
+	pub fn dispatch(&mut self, message: &OSCMessage) -> OSCResult<usize> {
+		let mut matched = 0;
+		let mut result = Ok(());
+		for (matcher, handler) in &mut self.handlers {
+			if matcher.matches(message) {
+				matched += 1;
+				if let Err(e) = handler(message) {
+					if result.is_ok() {
+						result = Err(e);
+					}
+				}
+			}
+		}
+		result.map(|_| matched)
+	}
+
+	/// Dispatches all messages contained in an OSC packet, returning the total number of matching handlers.
+	///
+	/// Returns the first error encountered; the remaining messages of the packet are still dispatched.
+	pub fn dispatch_packet(&mut self, packet: &OSCPacket) -> OSCResult<usize> {
+		match packet {
+			OSCPacket::Message(message) => self.dispatch(message),
+			OSCPacket::Bundle(bundle) => {
+				let mut matched = 0;
+				let mut result = Ok(());
+				for packet in &bundle.content {
+					match self.dispatch_packet(packet) {
+						Ok(n) => matched += n,
+						Err(e) => {
+							if result.is_ok() {
+								result = Err(e);
+							}
+						}
+					}
+				}
+				result.map(|_| matched)
+			}
+		}
+	}
+
+	/// Dispatches all packets received from a stream of packets, such as a [`VMCSocket`](crate::VMCSocket), until the
+	/// stream ends or an error occurs.
+	pub async fn run<S, A, E>(&mut self, mut stream: S) -> Result<(), E>
+	where
+		S: Stream<Item = Result<(OSCPacket, A), E>> + Unpin,
+		E: From<OSCError>
+	{
+		while let Some(packet) = poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await {
+			let (packet, _) = packet?;
+			self.dispatch_packet(&packet)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::{Arc, Mutex};
+
+	use super::*;
+	use crate::osc::{OSCBundle, OSCType};
+
+	#[test]
+	fn test_dispatcher() -> OSCResult<()> {
+		let faders = Arc::new(Mutex::new(Vec::new()));
+		let names = Arc::new(Mutex::new(Vec::new()));
+		let mut dispatcher = OSCDispatcher::new()
+			.on("/mixer/channel/[1-8]/fader", {
+				let faders = Arc::clone(&faders);
+				move |(level,): (f32,)| faders.lock().unwrap().push(level)
+			})?
+			.on_message("//name", {
+				let names = Arc::clone(&names);
+				move |message| names.lock().unwrap().push(message.addr.clone())
+			})?;
+
+		let matched = dispatcher.dispatch_packet(&OSCPacket::Bundle(OSCBundle {
+			timetag: (0, 1).into(),
+			content: vec![
+				OSCPacket::Message(OSCMessage::new("/mixer/channel/1/fader", (0.5_f32,))),
+				OSCPacket::Message(OSCMessage::new("/mixer/channel/9/fader", (1.0_f32,))),
+				OSCPacket::Message(OSCMessage::new("/mixer/channel/2/name", ("Vocals",))),
+			]
+		}))?;
+		assert_eq!(matched, 2);
+		assert_eq!(*faders.lock().unwrap(), [0.5]);
+		assert_eq!(*names.lock().unwrap(), ["/mixer/channel/2/name"]);
+
+		assert!(matches!(dispatcher.dispatch(&OSCMessage::new("/mixer/channel/3/fader", vec![OSCType::String("loud".into())])), Err(OSCError::BadArg(_))));
+		assert!(matches!(OSCDispatcher::new().on_message("mixer", |_| {}), Err(OSCError::BadAddressPattern(_))));
+		Ok(())
+	}
+}
@@ -0,0 +1,152 @@
+//! Human-readable formatting of OSC packets.
+//!
+//! Messages are formatted as their address, type tag string, and args, e.g. `/VMC/Ext/Blend/Val ,sf "Joy" 1.0`.
+//! Bundles are formatted as an indented tree, one packet per line; the alternate form (`{:#}`) puts everything on a
+//! single line instead, which is more suitable for logs.
+
+use std::fmt::{self, Display, Write};
+
+use super::{OSCBundle, OSCMessage, OSCPacket, OSCTime, OSCType};
+
+pub(super) fn write_type_tag<W: Write>(f: &mut W, arg: &OSCType) -> fmt::Result {
+	let tag = match arg {
+		OSCType::Int(_) => 'i',
+		OSCType::Float(_) => 'f',
+		OSCType::String(_) => 's',
+		OSCType::Blob(_) => 'b',
+		OSCType::Time(_) => 't',
+		OSCType::Long(_) => 'h',
+		OSCType::Double(_) => 'd',
+		OSCType::Char(_) => 'c',
+		OSCType::Color(_) => 'r',
+		OSCType::Midi(_) => 'm',
+		OSCType::Bool(true) => 'T',
+		OSCType::Bool(false) => 'F',
+		OSCType::Nil => 'N',
+		OSCType::Inf => 'I',
+		OSCType::Array(array) => {
+			f.write_char('[')?;
+			for arg in &array.content {
+				write_type_tag(f, arg)?;
+			}
+			']'
+		}
+	};
+	f.write_char(tag)
+}
+
+impl Display for OSCTime {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		if *self == OSCTime::IMMEDIATE {
+			f.write_str("immediately")
+		} else {
+			// the fractional part is in units of 2^-32 seconds
+			let nanos = (self.fractional as u64 * 1_000_000_000) >> 32;
+			write!(f, "{}.{:09}", self.seconds, nanos)
+		}
+	}
+}
+
+impl Display for OSCType {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			OSCType::Int(i) => write!(f, "{i}"),
+			OSCType::Float(x) => write!(f, "{x:?}"),
+			OSCType::String(s) => write!(f, "{s:?}"),
+			OSCType::Blob(blob) => write!(f, "<{} bytes>", blob.len()),
+			OSCType::Time(time) => write!(f, "{time}"),
+			OSCType::Long(i) => write!(f, "{i}"),
+			OSCType::Double(x) => write!(f, "{x:?}"),
+			OSCType::Char(c) => write!(f, "{c:?}"),
+			OSCType::Color(color) => write!(f, "#{:02x}{:02x}{:02x}{:02x}", color.red, color.green, color.blue, color.alpha),
+			OSCType::Midi(midi) => write!(f, "midi({}, {:#04x}, {}, {})", midi.port, midi.status, midi.data1, midi.data2),
+			OSCType::Bool(b) => write!(f, "{b}"),
+			OSCType::Nil => f.write_str("nil"),
+			OSCType::Inf => f.write_str("inf"),
+			OSCType::Array(array) => {
+				f.write_char('[')?;
+				for (i, arg) in array.content.iter().enumerate() {
+					if i > 0 {
+						f.write_char(' ')?;
+					}
+					write!(f, "{arg}")?;
+				}
+				f.write_char(']')
+			}
+		}
+	}
+}
+
+impl Display for OSCMessage {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{} ,", self.addr)?;
+		for arg in &self.args {
+			write_type_tag(f, arg)?;
+		}
+		for arg in &self.args {
+			write!(f, " {arg}")?;
+		}
+		Ok(())
+	}
+}
+
+fn write_bundle(f: &mut fmt::Formatter<'_>, bundle: &OSCBundle, depth: usize) -> fmt::Result {
+	write!(f, "#bundle {}", bundle.timetag)?;
+	if f.alternate() {
+		f.write_str(" {")?;
+		for (i, packet) in bundle.content.iter().enumerate() {
+			f.write_str(if i > 0 { "; " } else { " " })?;
+			write_packet(f, packet, depth + 1)?;
+		}
+		f.write_str(" }")
+	} else {
+		for packet in &bundle.content {
+			writeln!(f)?;
+			for _ in 0..=depth {
+				f.write_str("  ")?;
+			}
+			write_packet(f, packet, depth + 1)?;
+		}
+		Ok(())
+	}
+}
+
+fn write_packet(f: &mut fmt::Formatter<'_>, packet: &OSCPacket, depth: usize) -> fmt::Result {
+	match packet {
+		OSCPacket::Message(message) => Display::fmt(message, f),
+		OSCPacket::Bundle(bundle) => write_bundle(f, bundle, depth)
+	}
+}
+
+impl Display for OSCBundle {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write_bundle(f, self, 0)
+	}
+}
+
+impl Display for OSCPacket {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write_packet(f, self, 0)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{
+		IntoOSCPacket, OSCType, VMCApplyBlendShapes, VMCBlendShape,
+		osc::{OSCArray, OSCBundle, OSCMessage}
+	};
+
+	#[test]
+	fn test_display() {
+		let message = OSCMessage::new("/test", vec![OSCType::Int(1), OSCType::Bool(true), OSCType::Array(OSCArray::from_iter([1.5_f32, 2.0]))]);
+		assert_eq!(message.to_string(), "/test ,iT[ff] 1 true [1.5 2.0]");
+
+		let packet = OSCBundle::builder()
+			.push(VMCBlendShape::new("Joy", 1.0))
+			.push_bundle(OSCBundle::builder().timetag((1, 0)).push(VMCApplyBlendShapes))
+			.into_osc_packet();
+		assert_eq!(packet.to_string(), "#bundle immediately\n  /VMC/Ext/Blend/Val ,sf \"Joy\" 1.0\n  #bundle 1.000000000\n    /VMC/Ext/Blend/Apply ,");
+		assert_eq!(format!("{packet:#}"), "#bundle immediately { /VMC/Ext/Blend/Val ,sf \"Joy\" 1.0; #bundle 1.000000000 { /VMC/Ext/Blend/Apply , } }");
+	}
+}
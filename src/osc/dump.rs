@@ -0,0 +1,226 @@
+//! Annotated hexdumps of OSC packets, for debugging interoperability issues.
+
+use std::fmt::{Display, Write};
+
+use super::{OSCColor, OSCMidiMessage, OSCTime, OSCType};
+
+/// Renders a datagram as a hexdump annotated with the structure of the OSC packet it contains.
+///
+/// Each line shows the offset and bytes of a single field (an address, the type tag string, an argument, or part of a
+/// bundle) next to its decoded value; fields longer than 16 bytes continue on the following lines. Elements of bundles
+/// are indented. Unlike [`decode_udp`](super::decode_udp), this never fails: if the packet is malformed, the bytes from
+/// where decoding failed are shown with the reason, so the dump can be attached to a bug report as-is.
+///
+/// # Examples
+///
+/// ```
+/// use vmc::{IntoOSCPacket, VMCBlendShape, osc};
+///
+/// let bytes = osc::encode(&VMCBlendShape::new("Joy", 1.0).into_osc_packet()).unwrap();
+/// assert_eq!(
+/// 	osc::debug_dump(&bytes),
+/// 	"0000  2f 56 4d 43 2f 45 78 74 2f 42 6c 65 6e 64 2f 56  address \"/VMC/Ext/Blend/Val\"
+/// 0010  61 6c 00 00
+/// 0014  2c 73 66 00                                      type tags \",sf\"
+/// 0018  4a 6f 79 00                                      s \"Joy\"
+/// 001c  3f 80 00 00                                      f 1.0"
+/// );
+/// ```
+pub fn debug_dump(bytes: &[u8]) -> String {
+	let mut dump = Dump { bytes, out: String::new() };
+	dump.packet(0, bytes.len(), 0);
+	dump.out.pop();
+	dump.out
+}
+
+struct Dump<'a> {
+	bytes: &'a [u8],
+	out: String
+}
+
+type Result<T> = std::result::Result<T, (usize, String)>;
+
+impl Dump<'_> {
+	/// Writes the bytes in `start..end`, annotating the first line.
+	fn field(&mut self, start: usize, end: usize, depth: usize, annotation: impl Display) {
+		for (i, line) in self.bytes[start..end].chunks(16).enumerate() {
+			let _ = write!(self.out, "{:04x} ", start + i * 16);
+			for byte in line {
+				let _ = write!(self.out, " {byte:02x}");
+			}
+			if i == 0 {
+				let _ = write!(self.out, "{:width$}  {:indent$}{annotation}", "", "", width = (16 - line.len()) * 3, indent = depth * 2);
+			}
+			self.out.push('\n');
+		}
+	}
+
+	fn packet(&mut self, start: usize, end: usize, depth: usize) {
+		if let Err((pos, reason)) = self.try_packet(start, end, depth) {
+			if pos < end {
+				self.field(pos, end, depth, format_args!("error: {reason}"));
+			} else {
+				let _ = writeln!(self.out, "{pos:04x}  {:indent$}error: {reason}", "", indent = 48 + depth * 2);
+			}
+		}
+	}
+
+	fn try_packet(&mut self, start: usize, end: usize, depth: usize) -> Result<()> {
+		if start == end {
+			return Err((start, "empty packet".to_string()));
+		}
+		let (addr, pos) = self.string(start, end)?;
+		if addr.starts_with('/') {
+			self.field(start, pos, depth, format_args!("address {addr:?}"));
+			self.message(pos, end, depth)
+		} else if addr == "#bundle" {
+			self.field(start, pos, depth, "#bundle");
+			self.bundle(pos, end, depth)
+		} else {
+			Err((start, format!("invalid address or bundle tag {addr:?}")))
+		}
+	}
+
+	fn message(&mut self, start: usize, end: usize, depth: usize) -> Result<()> {
+		// some old implementations omit the type tag string for messages without args
+		if start == end {
+			return Ok(());
+		}
+		let (type_tags, mut pos) = self.string(start, end)?;
+		if !type_tags.starts_with(',') {
+			return Err((start, format!("type tag string {type_tags:?} doesn't start with ','")));
+		}
+		self.field(start, pos, depth, format_args!("type tags {type_tags:?}"));
+
+		for tag in type_tags.chars().skip(1) {
+			let arg_start = pos;
+			let arg = match tag {
+				'i' => OSCType::Int(i32::from_be_bytes(self.take(&mut pos, end)?)),
+				'f' => OSCType::Float(f32::from_be_bytes(self.take(&mut pos, end)?)),
+				'h' => OSCType::Long(i64::from_be_bytes(self.take(&mut pos, end)?)),
+				'd' => OSCType::Double(f64::from_be_bytes(self.take(&mut pos, end)?)),
+				't' => OSCType::Time(self.time(&mut pos, end)?),
+				'c' => {
+					let c = u32::from_be_bytes(self.take(&mut pos, end)?);
+					OSCType::Char(char::from_u32(c).ok_or_else(|| (arg_start, format!("invalid char {c:#x}")))?)
+				}
+				'r' => {
+					let [red, green, blue, alpha] = self.take(&mut pos, end)?;
+					OSCType::Color(OSCColor { red, green, blue, alpha })
+				}
+				'm' => {
+					let [port, status, data1, data2] = self.take(&mut pos, end)?;
+					OSCType::Midi(OSCMidiMessage { port, status, data1, data2 })
+				}
+				's' => {
+					let (string, next) = self.string(pos, end)?;
+					pos = next;
+					OSCType::String(string)
+				}
+				'b' => {
+					let len = u32::from_be_bytes(self.take(&mut pos, end)?) as usize;
+					self.field(arg_start, pos, depth, format_args!("b length {len}"));
+					let data_end = pos.checked_add(len).and_then(|data_end| data_end.checked_add((4 - len % 4) % 4));
+					match data_end {
+						Some(data_end) if data_end <= end => {
+							if len > 0 {
+								self.field(pos, data_end, depth, "  blob data");
+							}
+							pos = data_end;
+							continue;
+						}
+						_ => return Err((pos, format!("blob of {len} bytes exceeds the packet")))
+					}
+				}
+				// these tags have no data
+				'T' | 'F' | 'N' | 'I' | '[' | ']' => continue,
+				_ => return Err((arg_start, format!("unknown type tag '{tag}'")))
+			};
+			self.field(arg_start, pos, depth, format_args!("{tag} {arg}"));
+		}
+
+		if pos < end {
+			self.field(pos, end, depth, "trailing bytes");
+		}
+		Ok(())
+	}
+
+	fn bundle(&mut self, mut pos: usize, end: usize, depth: usize) -> Result<()> {
+		let time_start = pos;
+		let time = self.time(&mut pos, end)?;
+		self.field(time_start, pos, depth, format_args!("time tag {time}"));
+		while pos < end {
+			let size_start = pos;
+			let size = u32::from_be_bytes(self.take(&mut pos, end)?) as usize;
+			if size > end - pos {
+				return Err((size_start, format!("element of {size} bytes exceeds the bundle")));
+			}
+			self.field(size_start, pos, depth, format_args!("element size {size}"));
+			self.packet(pos, pos + size, depth + 1);
+			pos += size;
+		}
+		Ok(())
+	}
+
+	fn take<const N: usize>(&self, pos: &mut usize, end: usize) -> Result<[u8; N]> {
+		let bytes = self.bytes[*pos..end]
+			.get(..N)
+			.ok_or_else(|| (*pos, format!("expected {N} bytes, got {}", end - *pos)))?;
+		*pos += N;
+		Ok(bytes.try_into().unwrap())
+	}
+
+	fn time(&self, pos: &mut usize, end: usize) -> Result<OSCTime> {
+		let seconds = u32::from_be_bytes(self.take(pos, end)?);
+		let fractional = u32::from_be_bytes(self.take(pos, end)?);
+		Ok(OSCTime { seconds, fractional })
+	}
+
+	/// Reads a NUL-terminated, padded string starting at `start`, returning it and the position after its padding.
+	fn string(&self, start: usize, end: usize) -> Result<(String, usize)> {
+		let len = self.bytes[start..end]
+			.iter()
+			.position(|byte| *byte == 0)
+			.ok_or_else(|| (start, "unterminated string".to_string()))?;
+		let string = std::str::from_utf8(&self.bytes[start..start + len]).map_err(|e| (start, format!("invalid UTF-8 in string: {e}")))?;
+		let next = start + (len / 4 + 1) * 4;
+		if next > end {
+			return Err((start, format!("string {string:?} is missing padding")));
+		}
+		Ok((string.to_string(), next))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::debug_dump;
+	use crate::{
+		IntoOSCPacket, VMCApplyBlendShapes, VMCBlendShape,
+		osc::{OSCBundle, OSCMessage, OSCTime, encode}
+	};
+
+	#[test]
+	fn test_debug_dump() {
+		let bundle = OSCBundle {
+			timetag: OSCTime::IMMEDIATE,
+			content: vec![VMCBlendShape::new("Joy", 1.0).into_osc_packet(), VMCApplyBlendShapes.into_osc_packet()]
+		};
+		let bytes = encode(&bundle.into_osc_packet()).unwrap();
+		let dump = debug_dump(&bytes);
+		let lines: Vec<_> = dump.lines().collect();
+		assert_eq!(lines[0], "0000  23 62 75 6e 64 6c 65 00                          #bundle");
+		assert_eq!(lines[1], "0008  00 00 00 00 00 00 00 01                          time tag immediately");
+		assert_eq!(lines[2], "0010  00 00 00 20                                      element size 32");
+		assert!(lines[3].ends_with("    address \"/VMC/Ext/Blend/Val\""));
+		assert!(lines.last().unwrap().ends_with("    type tags \",\""));
+
+		// truncate the float argument of the blend shape
+		let mut bytes = encode(&OSCMessage::new("/VMC/Ext/Blend/Val", ("Joy", 1.0f32)).into_osc_packet()).unwrap();
+		bytes.truncate(bytes.len() - 2);
+		let dump = debug_dump(&bytes);
+		assert!(dump.contains("s \"Joy\"\n"));
+		assert!(dump.ends_with("001c  3f 80                                            error: expected 4 bytes, got 2"));
+
+		assert!(debug_dump(b"junk").ends_with("error: unterminated string"));
+	}
+}
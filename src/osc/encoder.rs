@@ -1,4 +1,197 @@
-use super::{OSCBundle, OSCMessage, OSCPacket, OSCTime, OSCType, error::OSCResult};
+use std::borrow::Cow;
+
+use bytes::BytesMut;
+
+use super::{
+	OSCBundle, OSCMessage, OSCPacket, OSCTime, OSCType,
+	error::{OSCError, OSCResult}
+};
+
+/// How non-finite floats (NaN and ±infinity) in `f` and `d` args are handled when encoding.
+///
+/// Tracking glitches can produce NaN positions, which many receivers don't expect; a single NaN bone position can make
+/// a whole avatar disappear.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NonFinitePolicy {
+	/// Encode non-finite floats as-is.
+	#[default]
+	PassThrough,
+	/// Replace non-finite floats with `0.0`.
+	Zero,
+	/// Fail encoding with [`OSCError::NonFiniteFloat`].
+	Reject
+}
+
+/// How strings (addresses and `s` args) which aren't valid OSC strings are handled when encoding.
+///
+/// The OSC specification only allows ASCII strings without NUL characters. A NUL character truncates the string on the
+/// receiving end and misaligns all following args, and many receivers can't decode multibyte characters, such as those
+/// in Japanese blend shape names.
+///
+/// Invalid strings are [rejected](StringPolicy::Reject) by default. Receivers which are known to decode UTF-8 can opt
+/// in to [`StringPolicy::PassThrough`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StringPolicy {
+	/// Encode strings as UTF-8, as-is. NUL characters will corrupt the packet.
+	PassThrough,
+	/// Remove NUL characters and replace non-ASCII characters with `?`.
+	Lossy,
+	/// Fail encoding with [`OSCError::InvalidString`].
+	#[default]
+	Reject
+}
+
+/// Options controlling how packets are sanitized before they are encoded by [`encode_with_options`].
+///
+/// # Examples
+///
+/// ```
+/// use vmc::osc::{EncodeOptions, NonFinitePolicy, OSCError, OSCMessage, OSCPacket, encode_with_options};
+///
+/// let packet = OSCPacket::Message(OSCMessage::new("/VMC/Ext/Blend/Val", ("Joy", f32::NAN)));
+/// let options = EncodeOptions::new().with_non_finite(NonFinitePolicy::Reject);
+/// assert!(matches!(encode_with_options(&packet, &options), Err(OSCError::NonFiniteFloat(_))));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EncodeOptions {
+	non_finite: NonFinitePolicy,
+	strings: StringPolicy
+}
+
+impl EncodeOptions {
+	/// Creates the default options, which reject strings that aren't valid OSC strings, and encode everything else
+	/// as-is.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Creates options which reject anything that isn't valid per the OSC specification.
+	pub fn strict() -> Self {
+		Self {
+			non_finite: NonFinitePolicy::Reject,
+			strings: StringPolicy::Reject
+		}
+	}
+
+	/// Sets how non-finite floats are handled. Defaults to [`NonFinitePolicy::PassThrough`].
+	pub fn with_non_finite(mut self, policy: NonFinitePolicy) -> Self {
+		self.non_finite = policy;
+		self
+	}
+
+	/// Sets how strings which contain NUL or non-ASCII characters are handled. Defaults to [`StringPolicy::Reject`].
+	pub fn with_strings(mut self, policy: StringPolicy) -> Self {
+		self.strings = policy;
+		self
+	}
+
+	/// Returns how non-finite floats are handled.
+	pub fn non_finite(&self) -> NonFinitePolicy {
+		self.non_finite
+	}
+
+	/// Returns how strings which contain NUL or non-ASCII characters are handled.
+	pub fn strings(&self) -> StringPolicy {
+		self.strings
+	}
+
+	/// Checks `packet` against these options, returning a sanitized copy if any of its args had to be changed.
+	pub fn prepare<'a>(&self, packet: &'a OSCPacket) -> OSCResult<Cow<'a, OSCPacket>> {
+		if !self.needs_rewrite(packet)? {
+			return Ok(Cow::Borrowed(packet));
+		}
+		let mut packet = packet.clone();
+		self.rewrite(&mut packet);
+		Ok(Cow::Owned(packet))
+	}
+
+	fn needs_rewrite(&self, packet: &OSCPacket) -> OSCResult<bool> {
+		match packet {
+			OSCPacket::Message(msg) => {
+				let mut rewrite = false;
+				if self.non_finite != NonFinitePolicy::PassThrough && msg.args.iter().any(has_non_finite) {
+					match self.non_finite {
+						NonFinitePolicy::Reject => return Err(OSCError::NonFiniteFloat(msg.addr.clone())),
+						_ => rewrite = true
+					}
+				}
+				if self.strings != StringPolicy::PassThrough && (!is_osc_string(&msg.addr) || msg.args.iter().any(has_invalid_string)) {
+					match self.strings {
+						StringPolicy::Reject => return Err(OSCError::InvalidString(msg.addr.clone())),
+						_ => rewrite = true
+					}
+				}
+				Ok(rewrite)
+			}
+			OSCPacket::Bundle(bundle) => {
+				let mut rewrite = false;
+				for packet in &bundle.content {
+					rewrite |= self.needs_rewrite(packet)?;
+				}
+				Ok(rewrite)
+			}
+		}
+	}
+
+	fn rewrite(&self, packet: &mut OSCPacket) {
+		match packet {
+			OSCPacket::Message(msg) => {
+				if self.non_finite == NonFinitePolicy::Zero {
+					msg.args.iter_mut().for_each(zero_non_finite);
+				}
+				if self.strings == StringPolicy::Lossy {
+					transliterate(&mut msg.addr);
+					msg.args.iter_mut().for_each(transliterate_arg);
+				}
+			}
+			OSCPacket::Bundle(bundle) => bundle.content.iter_mut().for_each(|packet| self.rewrite(packet))
+		}
+	}
+}
+
+fn has_non_finite(arg: &OSCType) -> bool {
+	match arg {
+		OSCType::Float(x) => !x.is_finite(),
+		OSCType::Double(x) => !x.is_finite(),
+		OSCType::Array(array) => array.content.iter().any(has_non_finite),
+		_ => false
+	}
+}
+
+fn zero_non_finite(arg: &mut OSCType) {
+	match arg {
+		OSCType::Float(x) if !x.is_finite() => *x = 0.0,
+		OSCType::Double(x) if !x.is_finite() => *x = 0.0,
+		OSCType::Array(array) => array.content.iter_mut().for_each(zero_non_finite),
+		_ => {}
+	}
+}
+
+fn is_osc_string(s: &str) -> bool {
+	s.bytes().all(|b| b.is_ascii() && b != 0)
+}
+
+fn has_invalid_string(arg: &OSCType) -> bool {
+	match arg {
+		OSCType::String(s) => !is_osc_string(s),
+		OSCType::Array(array) => array.content.iter().any(has_invalid_string),
+		_ => false
+	}
+}
+
+fn transliterate(s: &mut String) {
+	if !is_osc_string(s) {
+		*s = s.chars().filter(|c| *c != '\0').map(|c| if c.is_ascii() { c } else { '?' }).collect();
+	}
+}
+
+fn transliterate_arg(arg: &mut OSCType) {
+	match arg {
+		OSCType::String(s) => transliterate(s),
+		OSCType::Array(array) => array.content.iter_mut().for_each(transliterate_arg),
+		_ => {}
+	}
+}
 
 /// Takes a reference to an OSC packet and returns
 /// a byte vector on success. If the packet was invalid
@@ -11,12 +204,12 @@ use super::{OSCBundle, OSCMessage, OSCPacket, OSCTime, OSCType, error::OSCResult
 ///
 /// let packet = OSCPacket::Message(OSCMessage {
 /// 	addr: "/greet/me".to_string(),
-/// 	args: vec![OSCType::String("hi!".to_string())]
+/// 	args: vec![OSCType::String("hi!".to_string())].into()
 /// });
 /// assert!(encoder::encode(&packet).is_ok())
 /// ```
 pub fn encode(packet: &OSCPacket) -> OSCResult<Vec<u8>> {
-	let mut bytes = Vec::new();
+	let mut bytes = Vec::with_capacity(encoded_size(packet));
 
 	// NOTE: The Output implementation for Vec<u8> can't actually produce an error!
 	encode_into(packet, &mut bytes).expect("Failed to write encoded packet into Vec");
@@ -24,6 +217,84 @@ pub fn encode(packet: &OSCPacket) -> OSCResult<Vec<u8>> {
 	Ok(bytes)
 }
 
+/// Returns the exact number of bytes [`encode`] produces for the given packet, without encoding it.
+///
+/// # Example
+///
+/// ```
+/// use vmc::osc::{OSCMessage, OSCPacket, encoder};
+///
+/// let packet = OSCPacket::Message(OSCMessage::new("/greet/me", ("hi!",)));
+/// assert_eq!(encoder::encoded_size(&packet), encoder::encode(&packet).unwrap().len());
+/// ```
+pub fn encoded_size(packet: &OSCPacket) -> usize {
+	match packet {
+		OSCPacket::Message(msg) => message_size(msg),
+		// "#bundle\0" + time tag, then each element prefixed by its size
+		OSCPacket::Bundle(bundle) => 16 + bundle.content.iter().map(|packet| 4 + encoded_size(packet)).sum::<usize>()
+	}
+}
+
+fn message_size(msg: &OSCMessage) -> usize {
+	// the type tag string starts with a ',' and is NUL-terminated
+	let type_tags = 1 + msg.args.iter().map(arg_type_size).sum::<usize>() + 1;
+	pad(msg.addr.len() as u64 + 1) as usize + pad(type_tags as u64) as usize + msg.args.iter().map(arg_data_size).sum::<usize>()
+}
+
+fn arg_type_size(arg: &OSCType) -> usize {
+	match arg {
+		OSCType::Array(x) => 2 + x.content.iter().map(arg_type_size).sum::<usize>(),
+		_ => 1
+	}
+}
+
+fn arg_data_size(arg: &OSCType) -> usize {
+	match arg {
+		OSCType::Int(_) | OSCType::Float(_) | OSCType::Char(_) | OSCType::Midi(_) | OSCType::Color(_) => 4,
+		OSCType::Long(_) | OSCType::Double(_) | OSCType::Time(_) => 8,
+		OSCType::String(x) => pad(x.len() as u64 + 1) as usize,
+		OSCType::Blob(x) => 4 + pad(x.len() as u64) as usize,
+		OSCType::Bool(_) | OSCType::Nil | OSCType::Inf => 0,
+		OSCType::Array(x) => x.content.iter().map(arg_data_size).sum()
+	}
+}
+
+/// Encodes a packet into the start of a caller-provided buffer, returning the number of bytes written.
+///
+/// Returns [`OSCError::BufferTooSmall`] if the encoded packet doesn't fit into `buf`, in which case nothing is written.
+///
+/// # Example
+///
+/// ```
+/// use vmc::osc::{OSCMessage, OSCPacket, encoder};
+///
+/// let mut buf = [0u8; 64];
+/// let packet = OSCPacket::Message(OSCMessage::new("/greet/me", ("hi!",)));
+/// let len = encoder::encode_into_slice(&packet, &mut buf)?;
+/// assert_eq!(&buf[..len], &encoder::encode(&packet)?[..]);
+/// # Ok::<_, vmc::osc::OSCError>(())
+/// ```
+pub fn encode_into_slice(packet: &OSCPacket, buf: &mut [u8]) -> OSCResult<usize> {
+	let size = encoded_size(packet);
+	if size > buf.len() {
+		return Err(OSCError::BufferTooSmall(size));
+	}
+
+	let mut out = SliceOutput { buf, pos: 0 };
+	// NOTE: The Output implementation for SliceOutput can't produce an error once we know the packet fits.
+	encode_into(packet, &mut out).expect("Failed to write encoded packet into slice");
+	Ok(out.pos)
+}
+
+/// Like [`encode`], but first sanitizes the packet according to the given [`EncodeOptions`].
+///
+/// Returns an error if the packet is rejected by the options, e.g. [`OSCError::NonFiniteFloat`] if it contains a NaN
+/// and non-finite floats are [rejected](NonFinitePolicy::Reject), or [`OSCError::InvalidString`] if it contains a
+/// non-ASCII string and such strings are [rejected](StringPolicy::Reject).
+pub fn encode_with_options(packet: &OSCPacket, options: &EncodeOptions) -> OSCResult<Vec<u8>> {
+	encode(&*options.prepare(packet)?)
+}
+
 /// Takes a reference to an OSC packet and writes the
 /// encoded bytes to the given output. On success, the
 /// number of bytes written will be returned. If an error
@@ -44,7 +315,7 @@ pub fn encode(packet: &OSCPacket) -> OSCResult<Vec<u8>> {
 /// let mut bytes = Vec::new();
 /// let packet = OSCPacket::Message(OSCMessage {
 /// 	addr: "/greet/me".to_string(),
-/// 	args: vec![OSCType::String("hi!".to_string())]
+/// 	args: vec![OSCType::String("hi!".to_string())].into()
 /// });
 /// assert!(encoder::encode_into(&packet, &mut bytes).is_ok())
 /// ```
@@ -222,6 +493,7 @@ fn encode_time_tag_into<O: Output>(time: &OSCTime, out: &mut O) -> Result<usize,
 ///
 /// Implementations are currently provided for this trait for:
 /// - `Vec<u8>`: Data will be appended to the end of the Vec.
+/// - [`BytesMut`]: Data will be appended to the end of the buffer.
 /// - `WriteOutput<W>` (with feature `std`): A wrapper that allows data to be written to any type that implements
 ///   `std::io::Seek + std::io::Write`.
 pub trait Output {
@@ -274,6 +546,63 @@ impl Output for Vec<u8> {
 	}
 }
 
+impl Output for BytesMut {
+	type Err = core::convert::Infallible;
+	type Mark = (usize, usize);
+
+	#[inline]
+	fn mark(&mut self, size: usize) -> Result<Self::Mark, Self::Err> {
+		let start = self.len();
+		let end = start + size;
+
+		self.resize(end, 0);
+		Ok((start, end))
+	}
+
+	#[inline]
+	fn place(&mut self, (start, end): Self::Mark, data: &[u8]) -> Result<(), Self::Err> {
+		self[start..end].copy_from_slice(data);
+		Ok(())
+	}
+
+	#[inline]
+	fn write(&mut self, data: &[u8]) -> Result<usize, Self::Err> {
+		self.extend_from_slice(data);
+		Ok(data.len())
+	}
+}
+
+/// Writes into a slice which is known to be large enough to hold the whole packet.
+struct SliceOutput<'a> {
+	buf: &'a mut [u8],
+	pos: usize
+}
+
+impl Output for SliceOutput<'_> {
+	type Err = core::convert::Infallible;
+	type Mark = (usize, usize);
+
+	#[inline]
+	fn mark(&mut self, size: usize) -> Result<Self::Mark, Self::Err> {
+		let start = self.pos;
+		self.pos += size;
+		Ok((start, self.pos))
+	}
+
+	#[inline]
+	fn place(&mut self, (start, end): Self::Mark, data: &[u8]) -> Result<(), Self::Err> {
+		self.buf[start..end].copy_from_slice(data);
+		Ok(())
+	}
+
+	#[inline]
+	fn write(&mut self, data: &[u8]) -> Result<usize, Self::Err> {
+		self.buf[self.pos..self.pos + data.len()].copy_from_slice(data);
+		self.pos += data.len();
+		Ok(data.len())
+	}
+}
+
 /// A new type which can be used to wrap any type which
 /// implements `std::io::Seek` and `std::io::Write` to allow
 /// it to be used as an `Output`.
@@ -312,3 +641,101 @@ impl<W: std::io::Seek + std::io::Write> Output for WriteOutput<W> {
 		std::io::Write::write_all(&mut self.0, data).map(|_| data.len())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::osc::{OSCArray, decode_udp};
+
+	#[test]
+	fn test_non_finite_policy() -> OSCResult<()> {
+		let packet = OSCPacket::Bundle(OSCBundle {
+			timetag: (0, 1).into(),
+			content: vec![
+				OSCPacket::Message(OSCMessage::new("/ok", (1.0_f32,))),
+				OSCPacket::Message(OSCMessage::new("/bad", vec![OSCType::Array(OSCArray::from_iter([f32::NAN, 2.0])), OSCType::Double(f64::INFINITY)])),
+			]
+		});
+
+		let options = EncodeOptions::new();
+		assert!(matches!(options.prepare(&packet)?, Cow::Borrowed(_)));
+		let (_, decoded) = decode_udp(&encode_with_options(&packet, &options)?)?;
+		assert!(
+			matches!(&decoded, OSCPacket::Bundle(bundle) if matches!(&bundle.content[1], OSCPacket::Message(msg) if msg.args[1] == OSCType::Double(f64::INFINITY)))
+		);
+
+		let options = options.with_non_finite(NonFinitePolicy::Zero);
+		let (_, decoded) = decode_udp(&encode_with_options(&packet, &options)?)?;
+		let OSCPacket::Bundle(bundle) = decoded else {
+			panic!("expected bundle")
+		};
+		assert_eq!(bundle.content[0], OSCPacket::Message(OSCMessage::new("/ok", (1.0_f32,))));
+		assert_eq!(
+			bundle.content[1],
+			OSCPacket::Message(OSCMessage::new("/bad", vec![OSCType::Array(OSCArray::from_iter([0.0_f32, 2.0])), OSCType::Double(0.0)]))
+		);
+
+		let options = options.with_non_finite(NonFinitePolicy::Reject);
+		assert!(matches!(encode_with_options(&packet, &options), Err(OSCError::NonFiniteFloat(addr)) if addr == "/bad"));
+		Ok(())
+	}
+
+	#[test]
+	fn test_string_policy() -> OSCResult<()> {
+		let packet = OSCPacket::Message(OSCMessage::new("/VMC/Ext/Blend/Val", ("笑い\0", 1.0_f32)));
+		let lossy = EncodeOptions::new().with_strings(StringPolicy::Lossy);
+		let (_, decoded) = decode_udp(&encode_with_options(&packet, &lossy)?)?;
+		assert_eq!(decoded, OSCPacket::Message(OSCMessage::new("/VMC/Ext/Blend/Val", ("??", 1.0_f32))));
+		assert!(matches!(encode_with_options(&packet, &EncodeOptions::strict()), Err(OSCError::InvalidString(addr)) if addr == "/VMC/Ext/Blend/Val"));
+
+		// invalid strings are rejected unless passing them through is opt-in
+		assert!(matches!(encode_with_options(&packet, &EncodeOptions::new()), Err(OSCError::InvalidString(_))));
+		let pass_through = EncodeOptions::new().with_strings(StringPolicy::PassThrough);
+		assert_eq!(encode_with_options(&packet, &pass_through)?, encode(&packet)?);
+
+		let packet = OSCPacket::Message(OSCMessage::new("/VMC/Ext/Blend/Val", ("Joy", 1.0_f32)));
+		assert!(matches!(EncodeOptions::strict().prepare(&packet)?, Cow::Borrowed(_)));
+		Ok(())
+	}
+
+	#[test]
+	fn test_bytes() -> OSCResult<()> {
+		let packet = OSCPacket::Message(OSCMessage::new("/VMC/Ext/Blend/Val", ("Joy", 1.0_f32)));
+		let mut buf = BytesMut::from(&b"prefix"[..]);
+		let len = encode_into(&packet, &mut buf).unwrap();
+		assert_eq!(&buf[6..], &encode(&packet)?[..]);
+
+		let buf = buf.freeze().slice(6..);
+		assert_eq!(buf.len(), len);
+		let (remainder, decoded) = crate::osc::decode_udp_bytes(&buf)?;
+		assert_eq!(decoded, packet);
+		assert!(remainder.is_empty());
+		Ok(())
+	}
+
+	#[test]
+	fn test_encoded_size() -> OSCResult<()> {
+		let packet = OSCPacket::Bundle(OSCBundle {
+			timetag: (0, 1).into(),
+			content: vec![
+				OSCPacket::Message(OSCMessage::new("/a", ())),
+				OSCPacket::Message(OSCMessage::new("/VMC/Ext/Blend/Val", ("Joy", 1.0_f32, 2.0_f64, 3_i64, true))),
+				OSCPacket::Bundle(OSCBundle {
+					timetag: (1, 0).into(),
+					content: vec![OSCPacket::Message(OSCMessage::new(
+						"/blob",
+						vec![OSCType::Blob(vec![1, 2, 3]), OSCType::Array(OSCArray::from_iter([1, 2])), OSCType::Nil]
+					))]
+				}),
+			]
+		});
+		let bytes = encode(&packet)?;
+		assert_eq!(encoded_size(&packet), bytes.len());
+
+		let mut buf = vec![0xff; bytes.len()];
+		assert_eq!(encode_into_slice(&packet, &mut buf)?, bytes.len());
+		assert_eq!(buf, bytes);
+		assert!(matches!(encode_into_slice(&packet, &mut buf[1..]), Err(OSCError::BufferTooSmall(size)) if size == bytes.len()));
+		Ok(())
+	}
+}
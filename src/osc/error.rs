@@ -3,6 +3,20 @@ use std::{error::Error, fmt, string::FromUtf8Error};
 use nom::error::{ErrorKind, FromExternalError, ParseError};
 
 /// Represents errors returned by `decode` or `encode`.
+///
+/// Errors returned by the decoder are wrapped in [`OSCError::Decode`], which records where in the packet decoding
+/// failed. Use [`OSCError::inner`] to get the underlying error.
+///
+/// # Examples
+///
+/// ```
+/// use vmc::osc::{self, OSCError};
+///
+/// let err = osc::decode_udp(b"no/addr\0").unwrap_err();
+/// assert!(matches!(err, OSCError::Decode { offset: 0, .. }));
+/// // match on `inner()` to handle the kind of error, as decoder errors were matched directly before 0.5
+/// assert!(matches!(err.inner(), OSCError::BadPacket(_)));
+/// ```
 #[derive(Debug)]
 pub enum OSCError {
 	StringError(FromUtf8Error),
@@ -16,6 +30,24 @@ pub enum OSCError {
 	BadAddressPattern(String),
 	BadAddress(String),
 	RegexError(String),
+	LimitExceeded(String),
+	NonFiniteFloat(String),
+	InvalidString(String),
+	/// The buffer passed to [`encode_into_slice`](super::encoder::encode_into_slice) is too small; holds the required
+	/// size.
+	BufferTooSmall(usize),
+	/// An arg couldn't be converted into a Rust type because it has a different type; holds the expected & actual type
+	/// tags.
+	WrongType {
+		expected: &'static str,
+		actual: String
+	},
+	/// Decoding failed at the given byte offset into the packet, while parsing the message with the given address.
+	Decode {
+		offset: usize,
+		addr: Option<String>,
+		error: Box<OSCError>
+	},
 	Unimplemented
 }
 
@@ -33,11 +65,49 @@ impl fmt::Display for OSCError {
 			OSCError::BadAddressPattern(msg) => write!(f, "bad OSC address pattern: {}", msg),
 			OSCError::BadAddress(msg) => write!(f, "bad OSC address: {}", msg),
 			OSCError::RegexError(msg) => write!(f, "OSC address pattern regex error: {}", msg),
+			OSCError::LimitExceeded(msg) => write!(f, "decode limit exceeded: {}", msg),
+			OSCError::NonFiniteFloat(addr) => write!(f, "non-finite float argument in message to {}", addr),
+			OSCError::InvalidString(addr) => write!(f, "NUL or non-ASCII character in message to {}", addr),
+			OSCError::BufferTooSmall(size) => write!(f, "buffer too small, encoded packet needs {} bytes", size),
+			OSCError::WrongType { expected, actual } => write!(f, "expected OSC type tag {}, got {}", expected, actual),
+			OSCError::Decode { offset, addr, error } => {
+				write!(f, "{} at offset {}", error, offset)?;
+				if let Some(addr) = addr {
+					write!(f, " in {}", addr)?;
+				}
+				Ok(())
+			}
 			OSCError::Unimplemented => write!(f, "unimplemented")
 		}
 	}
 }
 
+impl OSCError {
+	/// Returns the underlying error, without the [decode context](OSCError::Decode).
+	pub fn inner(&self) -> &OSCError {
+		match self {
+			OSCError::Decode { error, .. } => error.inner(),
+			error => error
+		}
+	}
+
+	/// Returns the byte offset into the packet at which decoding failed, if known.
+	pub fn offset(&self) -> Option<usize> {
+		match self {
+			OSCError::Decode { offset, .. } => Some(*offset),
+			_ => None
+		}
+	}
+
+	/// Returns the address of the message which failed to decode, if known.
+	pub fn address(&self) -> Option<&str> {
+		match self {
+			OSCError::Decode { addr, .. } => addr.as_deref(),
+			_ => None
+		}
+	}
+}
+
 impl<I> ParseError<I> for OSCError {
 	fn from_error_kind(_input: I, kind: ErrorKind) -> Self {
 		Self::ReadError(kind)
@@ -65,6 +135,7 @@ impl Error for OSCError {
 	fn source(&self) -> Option<&(dyn Error + 'static)> {
 		match self {
 			OSCError::StringError(ref err) => Some(err),
+			OSCError::Decode { error, .. } => error.source(),
 			_ => None
 		}
 	}
@@ -7,16 +7,40 @@ use std::{
 	time::{Duration, SystemTime, UNIX_EPOCH}
 };
 
+use glam::{Quat, Vec3A};
+use smallvec::{SmallVec, smallvec};
+
+pub mod address;
 pub mod decoder;
+pub mod dispatch;
+mod display;
+mod dump;
 pub mod encoder;
 pub mod error;
+#[cfg(feature = "serde")]
+mod non_finite;
 
 pub use self::{
-	decoder::{MTU, decode_tcp, decode_tcp_vec, decode_udp},
-	encoder::{encode, encode_into, encode_string, encode_string_into},
+	address::{Matcher, verify_address},
+	decoder::{
+		DecodeLimits, MAX_UDP_PAYLOAD, MTU, StreamDecoder, decode_tcp, decode_tcp_vec, decode_tcp_vec_with_limits, decode_tcp_with_limits, decode_udp,
+		decode_udp_bytes, decode_udp_with_limits
+	},
+	dispatch::OSCDispatcher,
+	dump::debug_dump,
+	encoder::{
+		EncodeOptions, NonFinitePolicy, StringPolicy, encode, encode_into, encode_into_slice, encode_string, encode_string_into, encode_with_options,
+		encoded_size
+	},
 	error::{OSCError, OSCResult}
 };
 
+/// The args of an [`OSCMessage`].
+///
+/// Up to 8 args are stored inline without allocating, which covers every standard VMC message; messages with more
+/// args spill onto the heap. Derefs to `[OSCType]`, and can be created from a `Vec<OSCType>` with `.into()`.
+pub type OSCArgs = SmallVec<[OSCType; 8]>;
+
 /// A time tag in OSC message consists of two 32-bit integers where the first one denotes the number of seconds since
 /// 1900-01-01 and the second the fractions of a second. For details on its semantics see <http://opensoundcontrol.org/node/3/#timetags>
 ///
@@ -48,12 +72,16 @@ pub use self::{
 /// the [`UNIX_EPOCH`](std::time::UNIX_EPOCH). This allows the math used in the conversions to work
 /// on 32-bit systems which cannot represent times that far back.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OSCTime {
 	pub seconds: u32,
 	pub fractional: u32
 }
 
 impl OSCTime {
+	/// The special time tag `(0, 1)`, which means a bundle should be applied immediately upon receipt.
+	pub const IMMEDIATE: OSCTime = OSCTime { seconds: 0, fractional: 1 };
+
 	const UNIX_OFFSET: u64 = 2_208_988_800; // From RFC 5905
 	const TWO_POW_32: f64 = (u32::MAX as f64) + 1.0; // Number of bits in a `u32`
 	const ONE_OVER_TWO_POW_32: f64 = 1.0 / OSCTime::TWO_POW_32;
@@ -124,15 +152,16 @@ impl Error for OSCTimeError {}
 /// see OSC Type Tag String: [OSC Spec. 1.0](http://opensoundcontrol.org/spec-1_0)
 /// padding: zero bytes (n*4)
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum OSCType {
 	Int(i32),
-	Float(f32),
+	Float(#[cfg_attr(feature = "serde", serde(with = "non_finite"))] f32),
 	String(String),
 	Blob(Vec<u8>),
 	// use struct for time tag to avoid destructuring
 	Time(OSCTime),
 	Long(i64),
-	Double(f64),
+	Double(#[cfg_attr(feature = "serde", serde(with = "non_finite"))] f64),
 	Char(char),
 	Color(OSCColor),
 	Midi(OSCMidiMessage),
@@ -142,7 +171,7 @@ pub enum OSCType {
 	Inf
 }
 macro_rules! value_impl {
-    ($(($name:ident, $variant:ident, $ty:ty)),*) => {
+    ($(($name:ident, $variant:ident, $ty:ty, $tag:literal)),*) => {
         $(
         impl OSCType {
             #[allow(dead_code)]
@@ -158,22 +187,96 @@ macro_rules! value_impl {
                 OSCType::$variant(v)
             }
         }
+        impl TryFrom<OSCType> for $ty {
+            type Error = OSCError;
+
+            fn try_from(v: OSCType) -> Result<Self, OSCError> {
+                match v {
+                    OSCType::$variant(v) => Ok(v),
+                    v => Err(OSCError::WrongType { expected: $tag, actual: v.type_tag() })
+                }
+            }
+        }
+        impl TryFrom<&OSCType> for $ty {
+            type Error = OSCError;
+
+            fn try_from(v: &OSCType) -> Result<Self, OSCError> {
+                match v {
+                    OSCType::$variant(v) => Ok(v.clone()),
+                    v => Err(OSCError::WrongType { expected: $tag, actual: v.type_tag() })
+                }
+            }
+        }
         )*
     }
 }
 value_impl! {
-	(int, Int, i32),
-	(float, Float, f32),
-	(string, String, String),
-	(blob, Blob, Vec<u8>),
-	(array, Array, OSCArray),
-	(long, Long, i64),
-	(double, Double, f64),
-	(char, Char, char),
-	(color, Color, OSCColor),
-	(midi, Midi, OSCMidiMessage),
-	(bool, Bool, bool)
+	(int, Int, i32, "i"),
+	(float, Float, f32, "f"),
+	(string, String, String, "s"),
+	(blob, Blob, Vec<u8>, "b"),
+	(array, Array, OSCArray, "[]"),
+	(long, Long, i64, "h"),
+	(double, Double, f64, "d"),
+	(char, Char, char, "c"),
+	(color, Color, OSCColor, "r"),
+	(midi, Midi, OSCMidiMessage, "m"),
+	(bool, Bool, bool, "T or F")
+}
+macro_rules! accessor_impl {
+	($(($name:ident, $variant:ident($v:ident) => $value:expr, $ty:ty, $expected:literal, $tag:literal)),*) => {
+		impl OSCType {
+			$(
+				#[doc = concat!("Returns the value of ", $expected, " arg, or [`OSCError::WrongType`] if this arg has a different type.")]
+				pub fn $name(&self) -> OSCResult<$ty> {
+					match self {
+						OSCType::$variant($v) => Ok($value),
+						other => Err(OSCError::WrongType { expected: $tag, actual: other.type_tag() })
+					}
+				}
+			)*
+		}
+	};
+}
+accessor_impl! {
+	(as_i32, Int(v) => *v, i32, "an int32", "i"),
+	(as_f32, Float(v) => *v, f32, "a float32", "f"),
+	(as_str, String(v) => v.as_str(), &str, "a string", "s"),
+	(as_blob, Blob(v) => v.as_slice(), &[u8], "a blob", "b"),
+	(as_array, Array(v) => v, &OSCArray, "an array", "[]"),
+	(as_i64, Long(v) => *v, i64, "an int64", "h"),
+	(as_f64, Double(v) => *v, f64, "a float64", "d"),
+	(as_char, Char(v) => *v, char, "a char", "c"),
+	(as_color, Color(v) => v, &OSCColor, "a color", "r"),
+	(as_midi, Midi(v) => v, &OSCMidiMessage, "a MIDI message", "m"),
+	(as_bool, Bool(v) => *v, bool, "a bool", "T or F"),
+	(as_time, Time(v) => *v, OSCTime, "a time tag", "t")
+}
+
+impl OSCType {
+	/// Returns the type tag of this arg, e.g. `"f"` for a float32 or `"[ii]"` for an array of two int32s.
+	///
+	/// Failed conversions from `OSCType` into Rust types report the expected & actual type tags:
+	///
+	/// ```
+	/// use glam::Vec3A;
+	/// use vmc::osc::{OSCError, OSCType};
+	///
+	/// assert_eq!(f32::try_from(&OSCType::Float(1.5))?, 1.5);
+	/// assert_eq!(Vec3A::try_from(OSCType::from(Vec3A::X))?, Vec3A::X);
+	/// match i32::try_from(OSCType::from("hi")) {
+	/// 	Err(OSCError::WrongType { expected, actual }) => assert_eq!((expected, actual.as_str()), ("i", "s")),
+	/// 	_ => unreachable!()
+	/// }
+	/// # Ok::<_, OSCError>(())
+	/// ```
+	pub fn type_tag(&self) -> String {
+		let mut tag = String::new();
+		display::write_type_tag(&mut tag, self).expect("Failed to write type tag into String");
+		tag
+	}
 }
+
 impl From<(u32, u32)> for OSCType {
 	fn from(time: (u32, u32)) -> Self {
 		OSCType::Time(time.into())
@@ -196,6 +299,21 @@ impl OSCType {
 		}
 	}
 }
+impl TryFrom<OSCType> for OSCTime {
+	type Error = OSCError;
+
+	fn try_from(v: OSCType) -> Result<Self, OSCError> {
+		OSCTime::try_from(&v)
+	}
+}
+impl TryFrom<&OSCType> for OSCTime {
+	type Error = OSCError;
+
+	fn try_from(v: &OSCType) -> Result<Self, OSCError> {
+		v.as_time()
+	}
+}
+
 impl<'a> From<&'a str> for OSCType {
 	fn from(string: &'a str) -> Self {
 		OSCType::String(string.to_string())
@@ -204,6 +322,7 @@ impl<'a> From<&'a str> for OSCType {
 /// Represents the parts of a Midi message. Mainly used for
 /// tunneling midi over a network using the OSC protocol.
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OSCMidiMessage {
 	pub port: u8,
 	pub status: u8,
@@ -214,6 +333,9 @@ pub struct OSCMidiMessage {
 /// An *osc packet* can contain an *osc message* or a bundle of nested messages
 /// which is called *osc bundle*.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+// messages are large because their args are stored inline; boxing them would bring back the allocation per message
+#[allow(clippy::large_enum_variant)]
 pub enum OSCPacket {
 	Message(OSCMessage),
 	Bundle(OSCBundle)
@@ -239,6 +361,38 @@ impl OSCPacket {
 			_ => None
 		}
 	}
+
+	/// Returns all messages contained in the packet, flattening nested bundles in order.
+	///
+	/// ```
+	/// use vmc::{IntoOSCPacket, VMCApplyBlendShapes, VMCBlendShape, VMCTime, osc::OSCBundle};
+	///
+	/// let packet = OSCBundle::builder()
+	/// 	.push_bundle(OSCBundle::builder().push(VMCBlendShape::new("Joy", 1.0)).push(VMCApplyBlendShapes))
+	/// 	.push(VMCTime::new(1.0))
+	/// 	.build()
+	/// 	.into_osc_packet();
+	/// let messages = packet.into_messages();
+	/// assert_eq!(messages.len(), 3);
+	/// assert_eq!(messages[2].addr, "/VMC/Ext/T");
+	/// ```
+	pub fn into_messages(self) -> Vec<OSCMessage> {
+		fn flatten_into(packet: OSCPacket, messages: &mut Vec<OSCMessage>) {
+			match packet {
+				OSCPacket::Bundle(bundle) => {
+					messages.reserve(bundle.content.len());
+					for packet in bundle.content {
+						flatten_into(packet, messages);
+					}
+				}
+				OSCPacket::Message(message) => messages.push(message)
+			}
+		}
+
+		let mut messages = Vec::new();
+		flatten_into(self, &mut messages);
+		messages
+	}
 }
 
 /// An OSC message consists of an address and
@@ -248,14 +402,15 @@ impl OSCPacket {
 /// are used to set properties of the element to the
 /// respective values.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OSCMessage {
 	pub addr: String,
-	pub args: Vec<OSCType>
+	pub args: OSCArgs
 }
 
 impl OSCMessage {
 	/// Create a new OSCMessage from an address and args.
-	/// The args can either be specified as a `Vec<[OSCType]`, or as a tuple of regular Rust types
+	/// The args can either be specified as a `Vec<[OSCType]>`, or as a tuple of regular Rust types
 	/// that can be converted into [`OSCType`].
 	pub fn new<T>(addr: impl ToString, args: T) -> Self
 	where
@@ -266,6 +421,44 @@ impl OSCMessage {
 		OSCMessage { addr, args }
 	}
 
+	/// Creates a new OSCMessage, checking that the args match the given type tag string, e.g. `"sf"` for a string
+	/// followed by a float32. The leading `,` of the type tag string is optional.
+	///
+	/// Returns [`OSCError::BadArg`] if the args don't match.
+	///
+	/// ```
+	/// # use vmc::osc::OSCMessage;
+	/// let message = OSCMessage::with_type_tags("/VMC/Ext/Blend/Val", ",sf", ("Joy", 1.0_f32))?;
+	/// assert_eq!(message.type_tags(), "sf");
+	/// assert!(OSCMessage::with_type_tags("/VMC/Ext/Blend/Val", "sf", ("Joy", 1.0_f64)).is_err());
+	/// # Ok::<_, vmc::osc::OSCError>(())
+	/// ```
+	pub fn with_type_tags<T>(addr: impl ToString, type_tags: &str, args: T) -> OSCResult<Self>
+	where
+		T: IntoOSCArgs
+	{
+		let message = Self::new(addr, args);
+		if !message.matches_type_tags(type_tags) {
+			return Err(OSCError::BadArg(format!("{}: expected type tags {}, got {}", message.addr, type_tags, message.type_tags())));
+		}
+		Ok(message)
+	}
+
+	/// Returns the type tag string describing the message's args, without the leading `,`; e.g. `"sf"` for a
+	/// `/VMC/Ext/Blend/Val` message.
+	pub fn type_tags(&self) -> String {
+		let mut tags = String::with_capacity(self.args.len());
+		for arg in &self.args {
+			display::write_type_tag(&mut tags, arg).expect("Failed to write type tag into String");
+		}
+		tags
+	}
+
+	/// Returns `true` if the message's args match the given type tag string. The leading `,` is optional.
+	pub fn matches_type_tags(&self, type_tags: &str) -> bool {
+		self.type_tags() == type_tags.strip_prefix(',').unwrap_or(type_tags)
+	}
+
 	/// Returns `true` if the address starts with the given prefix.
 	///
 	/// Returns `false` otherwise.
@@ -291,19 +484,162 @@ impl OSCMessage {
 	pub fn as_tuple(&self) -> (&str, &[OSCType]) {
 		(self.addr.as_str(), &self.args[..])
 	}
+
+	/// Returns the arg at the given index, or [`OSCError::BadArg`] if the message has too few args.
+	///
+	/// Combined with the typed accessors on [`OSCType`], this allows reading args without matching on them:
+	///
+	/// ```
+	/// # use vmc::osc::OSCMessage;
+	/// let message = OSCMessage::new("/VMC/Ext/Blend/Val", ("Joy", 1.0_f32));
+	/// assert_eq!(message.arg(0)?.as_str()?, "Joy");
+	/// assert_eq!(message.arg(1)?.as_f32()?, 1.0);
+	/// assert!(message.arg(1)?.as_i32().is_err());
+	/// assert!(message.arg(2).is_err());
+	/// # Ok::<_, vmc::osc::OSCError>(())
+	/// ```
+	pub fn arg(&self, index: usize) -> OSCResult<&OSCType> {
+		self.args
+			.get(index)
+			.ok_or_else(|| OSCError::BadArg(format!("{}: expected at least {} args, got {}", self.addr, index + 1, self.args.len())))
+	}
+
+	/// Extracts the message's args as typed values, such as a tuple of Rust types. See [`FromOSCArgs`].
+	///
+	/// Errors are prefixed with the message's address, e.g. `/VMC/Ext/Blend/Val: arg 1 has the wrong type: i`.
+	///
+	/// ```
+	/// # use vmc::osc::OSCMessage;
+	/// let message = OSCMessage::new("/VMC/Ext/Blend/Val", ("Joy", 1.0_f32));
+	/// let (name, value): (String, f32) = message.args_as()?;
+	/// assert_eq!((name.as_str(), value), ("Joy", 1.0));
+	/// assert!(message.args_as::<(String, String)>().is_err());
+	/// # Ok::<_, vmc::osc::OSCError>(())
+	/// ```
+	pub fn args_as<A: FromOSCArgs>(&self) -> OSCResult<A> {
+		A::from_osc_args(&self.args).map_err(|e| match e {
+			OSCError::BadArg(msg) => OSCError::BadArg(format!("{}: {}", self.addr, msg)),
+			e => e
+		})
+	}
 }
 
 /// An OSC bundle contains zero or more OSC packets
 /// and a time tag. The contained packets *should* be
 /// applied at the given time tag.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OSCBundle {
 	pub timetag: OSCTime,
 	pub content: Vec<OSCPacket>
 }
 
+impl OSCBundle {
+	/// Creates an [`OSCBundleBuilder`] for assembling a bundle, which may contain nested bundles.
+	///
+	/// ```
+	/// use vmc::{
+	/// 	VMCApplyBlendShapes, VMCBlendShape, VMCTime,
+	/// 	osc::{OSCBundle, OSCTime}
+	/// };
+	///
+	/// let bundle = OSCBundle::builder()
+	/// 	.timetag(OSCTime::IMMEDIATE)
+	/// 	.push_bundle(OSCBundle::builder().push(VMCBlendShape::new("Joy", 1.0)).push(VMCApplyBlendShapes))
+	/// 	.push(VMCTime::new(1.0))
+	/// 	.build();
+	/// assert_eq!(bundle.content.len(), 2);
+	/// ```
+	pub fn builder() -> OSCBundleBuilder {
+		OSCBundleBuilder::default()
+	}
+}
+
+/// Collects packets into a bundle with the [immediate](OSCTime::IMMEDIATE) time tag.
+impl<T: IntoOSCPacket> FromIterator<T> for OSCBundle {
+	fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> OSCBundle {
+		OSCBundle {
+			timetag: OSCTime::IMMEDIATE,
+			content: iter.into_iter().map(T::into_osc_packet).collect()
+		}
+	}
+}
+
+/// A builder for [`OSCBundle`]s, created by [`OSCBundle::builder`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct OSCBundleBuilder {
+	timetag: OSCTime,
+	content: Vec<OSCPacket>
+}
+
+impl Default for OSCBundleBuilder {
+	fn default() -> Self {
+		Self {
+			timetag: OSCTime::IMMEDIATE,
+			content: Vec::new()
+		}
+	}
+}
+
+impl OSCBundleBuilder {
+	/// Sets the time tag of the bundle. Defaults to [`OSCTime::IMMEDIATE`].
+	pub fn timetag(mut self, timetag: impl Into<OSCTime>) -> Self {
+		self.timetag = timetag.into();
+		self
+	}
+
+	/// Appends a message or packet to the bundle.
+	pub fn push(mut self, packet: impl IntoOSCPacket) -> Self {
+		self.content.push(packet.into_osc_packet());
+		self
+	}
+
+	/// Appends a nested bundle, which can be given either as an [`OSCBundle`] or as another builder.
+	pub fn push_bundle(mut self, bundle: impl Into<OSCBundle>) -> Self {
+		self.content.push(OSCPacket::Bundle(bundle.into()));
+		self
+	}
+
+	/// Appends all messages or packets from an iterator to the bundle.
+	pub fn extend<T: IntoOSCPacket>(mut self, packets: impl IntoIterator<Item = T>) -> Self {
+		self.content.extend(packets.into_iter().map(T::into_osc_packet));
+		self
+	}
+
+	/// Returns the number of packets in the bundle so far.
+	pub fn len(&self) -> usize {
+		self.content.len()
+	}
+
+	/// Returns `true` if no packets have been added to the bundle.
+	pub fn is_empty(&self) -> bool {
+		self.content.is_empty()
+	}
+
+	/// Builds the bundle.
+	pub fn build(self) -> OSCBundle {
+		OSCBundle {
+			timetag: self.timetag,
+			content: self.content
+		}
+	}
+}
+
+impl From<OSCBundleBuilder> for OSCBundle {
+	fn from(builder: OSCBundleBuilder) -> Self {
+		builder.build()
+	}
+}
+
+impl IntoOSCPacket for OSCBundleBuilder {
+	fn into_osc_packet(self) -> OSCPacket {
+		OSCPacket::Bundle(self.build())
+	}
+}
+
 /// An RGBA color.
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OSCColor {
 	pub red: u8,
 	pub green: u8,
@@ -313,6 +649,7 @@ pub struct OSCColor {
 
 /// An OSCArray.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OSCArray {
 	pub content: Vec<OSCType>
 }
@@ -325,37 +662,104 @@ impl<T: Into<OSCType>> FromIterator<T> for OSCArray {
 	}
 }
 
+/// Converts a vector into an array of 3 float32s (`[fff]`).
+impl From<Vec3A> for OSCType {
+	fn from(v: Vec3A) -> Self {
+		OSCType::Array(OSCArray::from_iter(v.to_array()))
+	}
+}
+
+/// Converts a quaternion into an array of 4 float32s (`[ffff]`), in `x, y, z, w` order.
+impl From<Quat> for OSCType {
+	fn from(q: Quat) -> Self {
+		OSCType::Array(OSCArray::from_iter(q.to_array()))
+	}
+}
+
+fn float_array<const N: usize>(v: &OSCType, expected: &'static str) -> OSCResult<[f32; N]> {
+	let wrong_type = || OSCError::WrongType { expected, actual: v.type_tag() };
+	let OSCType::Array(array) = v else {
+		return Err(wrong_type());
+	};
+	if array.content.len() != N {
+		return Err(wrong_type());
+	}
+	let mut out = [0.0; N];
+	for (out, arg) in out.iter_mut().zip(&array.content) {
+		*out = arg.as_f32().map_err(|_| wrong_type())?;
+	}
+	Ok(out)
+}
+
+impl TryFrom<&OSCType> for Vec3A {
+	type Error = OSCError;
+
+	fn try_from(v: &OSCType) -> Result<Self, OSCError> {
+		float_array(v, "[fff]").map(Vec3A::from_array)
+	}
+}
+impl TryFrom<OSCType> for Vec3A {
+	type Error = OSCError;
+
+	fn try_from(v: OSCType) -> Result<Self, OSCError> {
+		Vec3A::try_from(&v)
+	}
+}
+
+impl TryFrom<&OSCType> for Quat {
+	type Error = OSCError;
+
+	fn try_from(v: &OSCType) -> Result<Self, OSCError> {
+		float_array(v, "[ffff]").map(Quat::from_array)
+	}
+}
+impl TryFrom<OSCType> for Quat {
+	type Error = OSCError;
+
+	fn try_from(v: OSCType) -> Result<Self, OSCError> {
+		Quat::try_from(&v)
+	}
+}
+
 impl From<String> for OSCMessage {
 	fn from(s: String) -> OSCMessage {
-		OSCMessage { addr: s, args: vec![] }
+		OSCMessage { addr: s, args: OSCArgs::new() }
 	}
 }
 
-impl<'a> From<&'a str> for OSCMessage {
+impl From<&str> for OSCMessage {
 	fn from(s: &str) -> OSCMessage {
-		OSCMessage { addr: s.to_string(), args: vec![] }
+		OSCMessage {
+			addr: s.to_string(),
+			args: OSCArgs::new()
+		}
 	}
 }
 
-/// Helper trait to convert types into `Vec<[OSCType]>`
+/// Helper trait to convert types into [`OSCArgs`].
 pub trait IntoOSCArgs {
 	/// Convert self to OSC args.
-	fn into_osc_args(self) -> Vec<OSCType>;
+	fn into_osc_args(self) -> OSCArgs;
 }
 
 impl<T> IntoOSCArgs for Vec<T>
 where
 	T: Into<OSCType>
 {
-	fn into_osc_args(self) -> Vec<OSCType> {
-		let args: Vec<OSCType> = self.into_iter().map(|a| a.into()).collect();
-		args
+	fn into_osc_args(self) -> OSCArgs {
+		self.into_iter().map(|a| a.into()).collect()
+	}
+}
+
+impl IntoOSCArgs for OSCArgs {
+	fn into_osc_args(self) -> OSCArgs {
+		self
 	}
 }
 
 impl IntoOSCArgs for () {
-	fn into_osc_args(self) -> Vec<OSCType> {
-		vec![]
+	fn into_osc_args(self) -> OSCArgs {
+		OSCArgs::new()
 	}
 }
 
@@ -363,8 +767,8 @@ impl<T1> IntoOSCArgs for (T1,)
 where
 	T1: Into<OSCType>
 {
-	fn into_osc_args(self) -> Vec<OSCType> {
-		vec![self.0.into()]
+	fn into_osc_args(self) -> OSCArgs {
+		smallvec![self.0.into()]
 	}
 }
 
@@ -373,8 +777,8 @@ where
 	T1: Into<OSCType>,
 	T2: Into<OSCType>
 {
-	fn into_osc_args(self) -> Vec<OSCType> {
-		vec![self.0.into(), self.1.into()]
+	fn into_osc_args(self) -> OSCArgs {
+		smallvec![self.0.into(), self.1.into()]
 	}
 }
 
@@ -384,8 +788,8 @@ where
 	T2: Into<OSCType>,
 	T3: Into<OSCType>
 {
-	fn into_osc_args(self) -> Vec<OSCType> {
-		vec![self.0.into(), self.1.into(), self.2.into()]
+	fn into_osc_args(self) -> OSCArgs {
+		smallvec![self.0.into(), self.1.into(), self.2.into()]
 	}
 }
 
@@ -396,8 +800,8 @@ where
 	T3: Into<OSCType>,
 	T4: Into<OSCType>
 {
-	fn into_osc_args(self) -> Vec<OSCType> {
-		vec![self.0.into(), self.1.into(), self.2.into(), self.3.into()]
+	fn into_osc_args(self) -> OSCArgs {
+		smallvec![self.0.into(), self.1.into(), self.2.into(), self.3.into()]
 	}
 }
 
@@ -409,8 +813,8 @@ where
 	T4: Into<OSCType>,
 	T5: Into<OSCType>
 {
-	fn into_osc_args(self) -> Vec<OSCType> {
-		vec![self.0.into(), self.1.into(), self.2.into(), self.3.into(), self.4.into()]
+	fn into_osc_args(self) -> OSCArgs {
+		smallvec![self.0.into(), self.1.into(), self.2.into(), self.3.into(), self.4.into()]
 	}
 }
 
@@ -423,8 +827,8 @@ where
 	T5: Into<OSCType>,
 	T6: Into<OSCType>
 {
-	fn into_osc_args(self) -> Vec<OSCType> {
-		vec![self.0.into(), self.1.into(), self.2.into(), self.3.into(), self.4.into(), self.5.into()]
+	fn into_osc_args(self) -> OSCArgs {
+		smallvec![self.0.into(), self.1.into(), self.2.into(), self.3.into(), self.4.into(), self.5.into()]
 	}
 }
 
@@ -438,8 +842,8 @@ where
 	T6: Into<OSCType>,
 	T7: Into<OSCType>
 {
-	fn into_osc_args(self) -> Vec<OSCType> {
-		vec![self.0.into(), self.1.into(), self.2.into(), self.3.into(), self.4.into(), self.5.into(), self.6.into()]
+	fn into_osc_args(self) -> OSCArgs {
+		smallvec![self.0.into(), self.1.into(), self.2.into(), self.3.into(), self.4.into(), self.5.into(), self.6.into()]
 	}
 }
 
@@ -454,17 +858,68 @@ where
 	T7: Into<OSCType>,
 	T8: Into<OSCType>
 {
-	fn into_osc_args(self) -> Vec<OSCType> {
-		vec![self.0.into(), self.1.into(), self.2.into(), self.3.into(), self.4.into(), self.5.into(), self.6.into(), self.7.into()]
+	fn into_osc_args(self) -> OSCArgs {
+		smallvec![self.0.into(), self.1.into(), self.2.into(), self.3.into(), self.4.into(), self.5.into(), self.6.into(), self.7.into()]
 	}
 }
 
 impl IntoOSCArgs for OSCType {
-	fn into_osc_args(self) -> Vec<OSCType> {
-		vec![self]
+	fn into_osc_args(self) -> OSCArgs {
+		smallvec![self]
+	}
+}
+
+/// Helper trait to extract typed values from OSC args; the counterpart to [`IntoOSCArgs`].
+///
+/// Implemented for tuples of types which can be converted from [`OSCType`], such as `(f32, String)`, which extract the
+/// leading args & ignore any extra args, and for `Vec<OSCType>`, which extracts all args. Use [`OSCType`] as an element
+/// type to accept an arg of any type.
+pub trait FromOSCArgs: Sized {
+	/// Extracts typed values from OSC args.
+	///
+	/// Returns [`OSCError::BadArg`] if there are too few args, or if an arg has the wrong type.
+	fn from_osc_args(args: &[OSCType]) -> OSCResult<Self>;
+}
+
+impl FromOSCArgs for Vec<OSCType> {
+	fn from_osc_args(args: &[OSCType]) -> OSCResult<Self> {
+		Ok(args.to_vec())
+	}
+}
+
+impl FromOSCArgs for () {
+	fn from_osc_args(_: &[OSCType]) -> OSCResult<Self> {
+		Ok(())
 	}
 }
 
+macro_rules! from_osc_args_impl {
+	($len:literal; $($ty:ident $index:tt),*) => {
+		impl<$($ty),*> FromOSCArgs for ($($ty,)*)
+		where
+			$($ty: TryFrom<OSCType>),*
+		{
+			fn from_osc_args(args: &[OSCType]) -> OSCResult<Self> {
+				if args.len() < $len {
+					return Err(OSCError::BadArg(format!("expected {} args, got {}", $len, args.len())));
+				}
+				Ok(($(
+					$ty::try_from(args[$index].clone()).map_err(|_| OSCError::BadArg(format!("arg {} has the wrong type: {}", $index, args[$index].type_tag())))?,
+				)*))
+			}
+		}
+	};
+}
+
+from_osc_args_impl!(1; T1 0);
+from_osc_args_impl!(2; T1 0, T2 1);
+from_osc_args_impl!(3; T1 0, T2 1, T3 2);
+from_osc_args_impl!(4; T1 0, T2 1, T3 2, T4 3);
+from_osc_args_impl!(5; T1 0, T2 1, T3 2, T4 3, T5 4);
+from_osc_args_impl!(6; T1 0, T2 1, T3 2, T4 3, T5 4, T6 5);
+from_osc_args_impl!(7; T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6);
+from_osc_args_impl!(8; T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7);
+
 /// Helper trait to convert [`OSCMessage`] and [`OSCBundle`] into [`OSCPacket`].
 pub trait IntoOSCPacket {
 	/// Convert into [`OSCPacket`].
@@ -489,6 +944,26 @@ impl IntoOSCPacket for OSCPacket {
 	}
 }
 
+/// Converts a list of messages or packets into a bundle with the [immediate](OSCTime::IMMEDIATE) time tag.
+impl<T> IntoOSCPacket for Vec<T>
+where
+	T: IntoOSCPacket
+{
+	fn into_osc_packet(self) -> OSCPacket {
+		OSCPacket::Bundle(self.into_iter().collect())
+	}
+}
+
+/// Converts an array of messages or packets into a bundle with the [immediate](OSCTime::IMMEDIATE) time tag.
+impl<T, const N: usize> IntoOSCPacket for [T; N]
+where
+	T: IntoOSCPacket
+{
+	fn into_osc_packet(self) -> OSCPacket {
+		OSCPacket::Bundle(self.into_iter().collect())
+	}
+}
+
 impl<T> IntoOSCPacket for T
 where
 	T: IntoOSCMessage
@@ -0,0 +1,185 @@
+//! Serde support for the float variants of [`OSCType`](super::OSCType).
+//!
+//! Human-readable formats like JSON have no way to represent NaN or infinity - `serde_json` writes them as `null` and
+//! then refuses to read `null` back as a float. For human-readable formats, non-finite floats are instead written as
+//! the strings `"NaN"`, `"inf"`, and `"-inf"`. Binary formats store the float as-is.
+
+use std::fmt;
+
+use serde::{
+	Deserializer, Serialize, Serializer,
+	de::{self, Visitor}
+};
+
+pub(super) fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+	T: Serialize + Copy + Into<f64>,
+	S: Serializer
+{
+	let float: f64 = (*value).into();
+	if serializer.is_human_readable() && !float.is_finite() {
+		serializer.serialize_str(if float.is_nan() {
+			"NaN"
+		} else if float.is_sign_positive() {
+			"inf"
+		} else {
+			"-inf"
+		})
+	} else {
+		value.serialize(serializer)
+	}
+}
+
+pub(super) fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+	T: Float,
+	D: Deserializer<'de>
+{
+	if deserializer.is_human_readable() {
+		deserializer.deserialize_any(FloatVisitor(std::marker::PhantomData))
+	} else {
+		T::deserialize_raw(deserializer)
+	}
+}
+
+pub(super) trait Float: Sized {
+	fn from_f64(value: f64) -> Self;
+	fn deserialize_raw<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error>;
+}
+
+impl Float for f32 {
+	fn from_f64(value: f64) -> Self {
+		value as f32
+	}
+
+	fn deserialize_raw<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		serde::Deserialize::deserialize(deserializer)
+	}
+}
+
+impl Float for f64 {
+	fn from_f64(value: f64) -> Self {
+		value
+	}
+
+	fn deserialize_raw<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		serde::Deserialize::deserialize(deserializer)
+	}
+}
+
+struct FloatVisitor<T>(std::marker::PhantomData<T>);
+
+impl<'de, T: Float> Visitor<'de> for FloatVisitor<T> {
+	type Value = T;
+
+	fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(r#"a number, "NaN", "inf", or "-inf""#)
+	}
+
+	fn visit_f64<E: de::Error>(self, v: f64) -> Result<T, E> {
+		Ok(T::from_f64(v))
+	}
+
+	fn visit_i64<E: de::Error>(self, v: i64) -> Result<T, E> {
+		Ok(T::from_f64(v as f64))
+	}
+
+	fn visit_u64<E: de::Error>(self, v: u64) -> Result<T, E> {
+		Ok(T::from_f64(v as f64))
+	}
+
+	fn visit_str<E: de::Error>(self, v: &str) -> Result<T, E> {
+		match v {
+			"NaN" => Ok(T::from_f64(f64::NAN)),
+			"inf" => Ok(T::from_f64(f64::INFINITY)),
+			"-inf" => Ok(T::from_f64(f64::NEG_INFINITY)),
+			_ => Err(E::invalid_value(de::Unexpected::Str(v), &self))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::osc::{OSCArray, OSCBundle, OSCColor, OSCMessage, OSCMidiMessage, OSCPacket, OSCTime, OSCType};
+
+	#[test]
+	fn test_serde_json_roundtrip() {
+		let packet = OSCPacket::Bundle(OSCBundle {
+			timetag: OSCTime {
+				seconds: 3_900_000_000,
+				fractional: 42
+			},
+			content: vec![
+				OSCPacket::Message(OSCMessage::new(
+					"/VMC/Ext/Test",
+					vec![
+						OSCType::Int(1),
+						OSCType::Float(0.5),
+						OSCType::String("Joy".into()),
+						OSCType::Blob(vec![0, 1, 254, 255]),
+						OSCType::Time(OSCTime { seconds: 1, fractional: u32::MAX }),
+						OSCType::Long(-7),
+						OSCType::Double(0.25),
+						OSCType::Char('x'),
+						OSCType::Color(OSCColor { red: 1, green: 2, blue: 3, alpha: 4 }),
+						OSCType::Midi(OSCMidiMessage {
+							port: 0,
+							status: 0x90,
+							data1: 60,
+							data2: 127
+						}),
+						OSCType::Bool(true),
+					]
+				)),
+				OSCPacket::Message(OSCMessage::new(
+					"/VMC/Ext/Test/Array",
+					vec![
+						OSCType::Nil,
+						OSCType::Inf,
+						OSCType::Array(OSCArray {
+							content: vec![
+								OSCType::Int(1),
+								OSCType::Array(OSCArray {
+									content: vec![OSCType::String("nested".into())]
+								}),
+							]
+						}),
+					]
+				)),
+			]
+		});
+		let json = serde_json::to_string(&packet).unwrap();
+		assert_eq!(serde_json::from_str::<OSCPacket>(&json).unwrap(), packet);
+	}
+
+	#[test]
+	fn test_serde_json_non_finite() {
+		let message = OSCMessage::new(
+			"/VMC/Ext/Test",
+			vec![
+				OSCType::Float(f32::NAN),
+				OSCType::Float(f32::INFINITY),
+				OSCType::Float(f32::NEG_INFINITY),
+				OSCType::Double(f64::NAN),
+				OSCType::Double(f64::INFINITY),
+				OSCType::Double(f64::NEG_INFINITY),
+			]
+		);
+		let json = serde_json::to_string(&message).unwrap();
+		assert!(json.contains(r#"{"Float":"NaN"}"#) && json.contains(r#"{"Double":"-inf"}"#), "{json}");
+
+		let decoded: OSCMessage = serde_json::from_str(&json).unwrap();
+		match decoded.args.as_slice() {
+			[OSCType::Float(a), OSCType::Float(b), OSCType::Float(c), OSCType::Double(d), OSCType::Double(e), OSCType::Double(f)] => {
+				assert!(a.is_nan() && d.is_nan());
+				assert_eq!((*b, *c, *e, *f), (f32::INFINITY, f32::NEG_INFINITY, f64::INFINITY, f64::NEG_INFINITY));
+			}
+			args => panic!("unexpected args: {args:?}")
+		}
+
+		// plain numbers are still accepted, including integers written without a fraction
+		let decoded: OSCType = serde_json::from_str(r#"{"Float":2}"#).unwrap();
+		assert_eq!(decoded, OSCType::Float(2.0));
+		assert!(serde_json::from_str::<OSCType>(r#"{"Double":"infinity"}"#).is_err());
+	}
+}
@@ -0,0 +1,435 @@
+use std::{collections::HashMap, str::FromStr};
+
+use glam::{Quat, Vec3A};
+
+use crate::{
+	IntoOSCMessage, OSCPacket, VMCMessage,
+	message::{BlendShape, BoneTransform, RootTransform, StandardVRM0Bone, State, Time},
+	name::Name,
+	osc::{self, OSCBundle, OSCMessage, OSCTime},
+	retarget
+};
+
+/// Local rest offsets of the left & center bones of [`Pose::t_pose`], for a 1.7m tall humanoid. Right bones mirror the
+/// left ones.
+const T_POSE: &[(StandardVRM0Bone, Vec3A)] = &[
+	(StandardVRM0Bone::Hips, Vec3A::new(0.0, 0.95, 0.0)),
+	(StandardVRM0Bone::Spine, Vec3A::new(0.0, 0.08, 0.0)),
+	(StandardVRM0Bone::Chest, Vec3A::new(0.0, 0.12, 0.0)),
+	(StandardVRM0Bone::UpperChest, Vec3A::new(0.0, 0.12, 0.0)),
+	(StandardVRM0Bone::Neck, Vec3A::new(0.0, 0.15, 0.0)),
+	(StandardVRM0Bone::Head, Vec3A::new(0.0, 0.1, 0.0)),
+	(StandardVRM0Bone::Jaw, Vec3A::new(0.0, -0.02, 0.03)),
+	(StandardVRM0Bone::LeftEye, Vec3A::new(-0.03, 0.08, 0.08)),
+	(StandardVRM0Bone::LeftUpperLeg, Vec3A::new(-0.09, -0.05, 0.0)),
+	(StandardVRM0Bone::LeftLowerLeg, Vec3A::new(0.0, -0.41, 0.0)),
+	(StandardVRM0Bone::LeftFoot, Vec3A::new(0.0, -0.41, 0.0)),
+	(StandardVRM0Bone::LeftToes, Vec3A::new(0.0, -0.06, 0.13)),
+	(StandardVRM0Bone::LeftShoulder, Vec3A::new(-0.03, 0.11, 0.0)),
+	(StandardVRM0Bone::LeftUpperArm, Vec3A::new(-0.13, 0.0, 0.0)),
+	(StandardVRM0Bone::LeftLowerArm, Vec3A::new(-0.28, 0.0, 0.0)),
+	(StandardVRM0Bone::LeftHand, Vec3A::new(-0.25, 0.0, 0.0)),
+	(StandardVRM0Bone::LeftThumbProximal, Vec3A::new(-0.02, -0.01, 0.025)),
+	(StandardVRM0Bone::LeftThumbIntermediate, Vec3A::new(-0.03, 0.0, 0.01)),
+	(StandardVRM0Bone::LeftThumbDistal, Vec3A::new(-0.025, 0.0, 0.005)),
+	(StandardVRM0Bone::LeftIndexProximal, Vec3A::new(-0.08, 0.0, 0.025)),
+	(StandardVRM0Bone::LeftIndexIntermediate, Vec3A::new(-0.04, 0.0, 0.0)),
+	(StandardVRM0Bone::LeftIndexDistal, Vec3A::new(-0.025, 0.0, 0.0)),
+	(StandardVRM0Bone::LeftMiddleProximal, Vec3A::new(-0.085, 0.0, 0.005)),
+	(StandardVRM0Bone::LeftMiddleIntermediate, Vec3A::new(-0.045, 0.0, 0.0)),
+	(StandardVRM0Bone::LeftMiddleDistal, Vec3A::new(-0.028, 0.0, 0.0)),
+	(StandardVRM0Bone::LeftRingProximal, Vec3A::new(-0.08, 0.0, -0.015)),
+	(StandardVRM0Bone::LeftRingIntermediate, Vec3A::new(-0.04, 0.0, 0.0)),
+	(StandardVRM0Bone::LeftRingDistal, Vec3A::new(-0.026, 0.0, 0.0)),
+	(StandardVRM0Bone::LeftLittleProximal, Vec3A::new(-0.07, 0.0, -0.035)),
+	(StandardVRM0Bone::LeftLittleIntermediate, Vec3A::new(-0.03, 0.0, 0.0)),
+	(StandardVRM0Bone::LeftLittleDistal, Vec3A::new(-0.02, 0.0, 0.0))
+];
+
+/// A complete avatar pose for a single frame: the root transform, bone transforms, and blend shape values.
+///
+/// Performers typically build a pose each frame and send it with [`VMCSocket::send_pose`](crate::VMCSocket::send_pose).
+///
+/// # Examples
+///
+/// ```no_run
+/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+/// use vmc::{Quat, VMCPose, VMCRootTransform, VMCStandardVRM0Bone, VMCStandardVRMBlendShape, Vec3};
+///
+/// let socket = vmc::performer!().await?;
+/// let mut pose = VMCPose::new();
+/// pose.root = Some(VMCRootTransform::new(Vec3::ZERO, Quat::IDENTITY));
+/// pose.set_bone(VMCStandardVRM0Bone::Head, Vec3::new(0.0, 0.1, 0.0), Quat::from_rotation_y(0.3));
+/// pose.set_blendshape(VMCStandardVRMBlendShape::Joy, 1.0);
+/// socket.send_pose(&pose).await?;
+/// # Ok(()) }) }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Pose {
+	/// The root transform of the avatar.
+	pub root: Option<RootTransform>,
+	/// Bone transforms, keyed by bone name.
+	pub bones: HashMap<Name, BoneTransform>,
+	/// Blend shape values, keyed by blend shape name.
+	pub blendshapes: HashMap<Name, f32>,
+	/// The model, calibration, & tracking state, which is sent along with the pose if present.
+	pub state: Option<State>,
+	/// The time value sent at the end of the pose. If `None`, [`Time::elapsed`] is used.
+	pub time: Option<Time>
+}
+
+impl Pose {
+	/// Creates a new, empty pose.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Sets the transform of a bone.
+	///
+	/// `bone` can be either a [`StandardVRM0Bone`](crate::VMCStandardVRM0Bone) or the name of a bone.
+	pub fn set_bone(&mut self, bone: impl Into<Name>, position: impl Into<Vec3A>, rotation: impl Into<Quat>) {
+		let transform = BoneTransform::new(bone, position, rotation);
+		self.bones.insert(transform.bone.clone(), transform);
+	}
+
+	/// Returns the transform of a bone.
+	pub fn bone(&self, bone: impl AsRef<str>) -> Option<&BoneTransform> {
+		self.bones.get(bone.as_ref())
+	}
+
+	/// Sets the value of a blend shape.
+	///
+	/// `key` can be either a [`StandardVRMBlendShape`](crate::VMCStandardVRMBlendShape) or the name of a blend shape.
+	pub fn set_blendshape(&mut self, key: impl Into<Name>, value: f32) {
+		self.blendshapes.insert(key.into(), value);
+	}
+
+	/// Returns the value of a blend shape.
+	pub fn blendshape(&self, key: impl AsRef<str>) -> Option<f32> {
+		self.blendshapes.get(key.as_ref()).copied()
+	}
+
+	/// Returns the standard VRM rest pose of a 1.7m tall humanoid: an identity root transform, and every
+	/// [`StandardVRM0Bone`] except `Pelvis` (which VRM doesn't define) with an identity rotation at typical
+	/// proportions.
+	///
+	/// Since VMC bone rotations are relative to the rest pose, a performer can send this pose as-is, e.g. while waiting
+	/// for tracking to start.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use vmc::{Quat, VMCPose, VMCStandardVRM0Bone};
+	///
+	/// let pose = VMCPose::t_pose();
+	/// assert_eq!(pose.bone(VMCStandardVRM0Bone::RightHand).unwrap().rotation, Quat::IDENTITY);
+	/// assert!(pose.bone(VMCStandardVRM0Bone::RightHand).unwrap().position.x > 0.0);
+	/// ```
+	pub fn t_pose() -> Pose {
+		let mut pose = Pose::new();
+		pose.root = Some(RootTransform::new(Vec3A::ZERO, Quat::IDENTITY));
+		for (bone, position) in T_POSE {
+			pose.set_bone(*bone, *position, Quat::IDENTITY);
+			if let Some(right) = bone.as_str().strip_prefix("Left") {
+				let right = StandardVRM0Bone::from_str(&format!("Right{right}")).expect("left bones have a right counterpart");
+				pose.set_bone(right, *position * Vec3A::new(-1.0, 1.0, 1.0), Quat::IDENTITY);
+			}
+		}
+		pose
+	}
+
+	/// Returns the offset from `base` to this pose, such that applying it to `base` with [`Pose::apply_offset`]
+	/// results in this pose.
+	///
+	/// The offset contains the root & bones present in both poses, with the difference of their positions and the
+	/// rotation from `base` to this pose in the bone's local space (`base.rotation.inverse() * self.rotation`), and the
+	/// difference of blend shape values present in both poses. The state & time are not included.
+	///
+	/// This is useful for calibration: capture the offset from a tracked pose to a reference pose like
+	/// [`Pose::t_pose`] while the performer holds it, then apply it to every subsequent frame.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use vmc::{Quat, VMCPose, VMCStandardVRM0Bone, Vec3A};
+	///
+	/// // the performer holds a T-pose, but the tracker reports the head slightly turned
+	/// let mut calibration = VMCPose::t_pose();
+	/// let head = calibration.bone(VMCStandardVRM0Bone::Head).unwrap().position;
+	/// calibration.set_bone(VMCStandardVRM0Bone::Head, head, Quat::from_rotation_y(0.1));
+	/// let offset = VMCPose::t_pose().diff(&calibration);
+	///
+	/// let mut frame = calibration.clone();
+	/// frame.apply_offset(&offset);
+	/// assert!(frame.bone(VMCStandardVRM0Bone::Head).unwrap().rotation.abs_diff_eq(Quat::IDENTITY, 1e-6));
+	/// ```
+	pub fn diff(&self, base: &Pose) -> Pose {
+		let root = match (&self.root, &base.root) {
+			(Some(this), Some(base)) => Some(RootTransform::new(this.position - base.position, (base.rotation.inverse() * this.rotation).normalize())),
+			_ => None
+		};
+		let bones = self
+			.bones
+			.iter()
+			.filter_map(|(name, this)| {
+				let base = base.bones.get(name)?;
+				let offset = BoneTransform::new(name.clone(), this.position - base.position, (base.rotation.inverse() * this.rotation).normalize());
+				Some((name.clone(), offset))
+			})
+			.collect();
+		let blendshapes = self
+			.blendshapes
+			.iter()
+			.filter_map(|(key, value)| Some((key.clone(), value - base.blendshapes.get(key)?)))
+			.collect();
+		Pose {
+			root,
+			bones,
+			blendshapes,
+			state: None,
+			time: None
+		}
+	}
+
+	/// Applies an offset computed by [`Pose::diff`] to this pose, adding position & blend shape differences and
+	/// multiplying each rotation by the offset rotation in the bone's local space.
+	///
+	/// Bones & blend shapes in the offset which are missing from this pose are ignored, as is the root offset if this
+	/// pose has no root transform.
+	pub fn apply_offset(&mut self, offset: &Pose) {
+		if let (Some(root), Some(offset)) = (&mut self.root, &offset.root) {
+			root.position += offset.position;
+			root.rotation = (root.rotation * offset.rotation).normalize();
+		}
+		for (name, offset) in &offset.bones {
+			if let Some(transform) = self.bones.get_mut(name) {
+				transform.position += offset.position;
+				transform.rotation = (transform.rotation * offset.rotation).normalize();
+			}
+		}
+		for (key, offset) in &offset.blendshapes {
+			if let Some(value) = self.blendshapes.get_mut(key) {
+				*value += offset;
+			}
+		}
+	}
+
+	/// Returns the model space position & rotation of a humanoid bone, composing the transforms of its ancestors and
+	/// the root, or `None` if the bone is missing. Ancestors missing from the pose are skipped.
+	pub(crate) fn world_transform(&self, bone: impl AsRef<str>) -> Option<(Vec3A, Quat)> {
+		let transform = self.bone(bone.as_ref())?;
+		let mut ancestor = retarget::vrm0_parent(bone.as_ref());
+		while let Some(name) = ancestor {
+			if let Some((position, rotation)) = self.world_transform(name) {
+				return Some((position + rotation * transform.position, rotation * transform.rotation));
+			}
+			ancestor = retarget::vrm0_parent(name);
+		}
+		Some(match &self.root {
+			Some(root) => (root.position + root.rotation * transform.position, root.rotation * transform.rotation),
+			None => (transform.position, transform.rotation)
+		})
+	}
+
+	/// Interpolates between this pose and `other`.
+	///
+	/// Bone & root transforms are interpolated with [`BoneTransform::lerp`] & [`RootTransform::lerp`], and blend shape
+	/// values are linearly interpolated. When `t` is `0.0`, the result is equal to `self`; when `t` is `1.0`, the
+	/// result is equal to `other`. This can be used to upsample a low-rate tracker to a higher render rate, or to
+	/// crossfade between two animation sources.
+	///
+	/// Bones present in only one pose are taken from that pose as-is, while blend shapes present in only one pose are
+	/// interpolated to/from `0.0`. The state is taken from whichever pose `t` is closer to.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use vmc::{Quat, VMCPose, Vec3};
+	///
+	/// let mut a = VMCPose::new();
+	/// a.set_bone("Head", Vec3::ZERO, Quat::IDENTITY);
+	/// a.set_blendshape("Joy", 0.0);
+	/// let mut b = VMCPose::new();
+	/// b.set_bone("Head", Vec3::new(0.0, 1.0, 0.0), Quat::from_rotation_y(1.0));
+	/// b.set_blendshape("Joy", 1.0);
+	///
+	/// let mid = a.lerp(&b, 0.5);
+	/// assert_eq!(mid.bone("Head").unwrap().position.y, 0.5);
+	/// assert_eq!(mid.blendshape("Joy"), Some(0.5));
+	/// ```
+	pub fn lerp(&self, other: &Pose, t: f32) -> Pose {
+		let root = match (&self.root, &other.root) {
+			(Some(a), Some(b)) => Some(a.lerp(b, t)),
+			(a, b) => a.clone().or_else(|| b.clone())
+		};
+
+		let mut bones = other.bones.clone();
+		for (name, a) in &self.bones {
+			let transform = match other.bones.get(name) {
+				Some(b) => a.lerp(b, t),
+				None => a.clone()
+			};
+			bones.insert(name.clone(), transform);
+		}
+
+		let mut blendshapes: HashMap<Name, f32> = other.blendshapes.iter().map(|(key, value)| (key.clone(), value * t)).collect();
+		for (key, a) in &self.blendshapes {
+			let b = other.blendshapes.get(key).copied().unwrap_or(0.0);
+			blendshapes.insert(key.clone(), a + (b - a) * t);
+		}
+
+		let time = match (&self.time, &other.time) {
+			(Some(a), Some(b)) => Some(Time(a.0 + (b.0 - a.0) * t)),
+			(a, b) => a.clone().or_else(|| b.clone())
+		};
+
+		Pose {
+			root,
+			bones,
+			blendshapes,
+			state: if t < 0.5 { self.state.clone() } else { other.state.clone() },
+			time
+		}
+	}
+
+	/// Converts this pose into the sequence of messages sent by [`VMCSocket::send_pose`](crate::VMCSocket::send_pose).
+	///
+	/// The root transform, bone transforms, blend shapes, [`ApplyBlendShapes`](crate::VMCApplyBlendShapes), state, and
+	/// [`Time`] messages are returned in that order.
+	pub fn to_messages(&self) -> Vec<VMCMessage> {
+		let mut messages = Vec::with_capacity(self.bones.len() + self.blendshapes.len() + 4);
+		if let Some(root) = &self.root {
+			messages.push(VMCMessage::RootTransform(root.clone()));
+		}
+		messages.extend(self.bones.values().cloned().map(VMCMessage::BoneTransform));
+		if !self.blendshapes.is_empty() {
+			messages.extend(
+				self.blendshapes
+					.iter()
+					.map(|(key, value)| VMCMessage::BlendShape(BlendShape::new(key, *value)))
+			);
+			messages.push(VMCMessage::ApplyBlendShapes);
+		}
+		if let Some(state) = &self.state {
+			messages.push(VMCMessage::State(state.clone()));
+		}
+		messages.push(VMCMessage::Time(self.time.clone().unwrap_or_else(Time::elapsed)));
+		messages
+	}
+
+	/// Encodes this pose into the sequence of packets sent by [`VMCSocket::send_pose`](crate::VMCSocket::send_pose).
+	///
+	/// The messages returned by [`Pose::to_messages`] are packed into as few bundles as possible while keeping each
+	/// packet within the [maximum UDP payload](osc::MAX_UDP_PAYLOAD). A packet which would hold only a single message,
+	/// such as the last packet if only one message is left over, is returned as a bare [`OSCPacket::Message`] rather
+	/// than a bundle.
+	pub fn to_packets(&self) -> Vec<OSCPacket> {
+		let messages = self.to_messages().into_iter().map(IntoOSCMessage::into_osc_message).collect();
+		pack_bundles(messages, osc::MAX_UDP_PAYLOAD)
+	}
+}
+
+/// Size of the `#bundle` header & time tag.
+const BUNDLE_HEADER_SIZE: usize = 16;
+
+/// Greedily packs messages into bundles, keeping each encoded bundle within `max_size` bytes where possible.
+pub(crate) fn pack_bundles(messages: Vec<OSCMessage>, max_size: usize) -> Vec<OSCPacket> {
+	fn finish(content: Vec<OSCPacket>, packets: &mut Vec<OSCPacket>) {
+		match content.len() {
+			0 => {}
+			1 => packets.extend(content),
+			_ => packets.push(OSCPacket::Bundle(OSCBundle {
+				timetag: OSCTime::from((0, 1)),
+				content
+			}))
+		}
+	}
+
+	let mut packets = Vec::new();
+	let mut content = Vec::new();
+	let mut size = BUNDLE_HEADER_SIZE;
+	for message in messages {
+		let packet = OSCPacket::Message(message);
+		let len = 4 + osc::encoded_size(&packet);
+		if !content.is_empty() && size + len > max_size {
+			finish(std::mem::take(&mut content), &mut packets);
+			size = BUNDLE_HEADER_SIZE;
+		}
+		size += len;
+		content.push(packet);
+	}
+	finish(content, &mut packets);
+	packets
+}
+
+#[cfg(test)]
+mod tests {
+	use glam::{Quat, Vec3A};
+
+	use super::Pose;
+	use crate::{
+		OSCPacket,
+		message::{StandardVRM0Bone, Time, VMCMessage, parse},
+		osc
+	};
+
+	#[test]
+	fn test_pose_packets() -> crate::VMCResult<()> {
+		let mut pose = Pose::new();
+		pose.set_bone(StandardVRM0Bone::Head, Vec3A::Y, Quat::IDENTITY);
+		for i in 0..64 {
+			pose.set_blendshape(format!("Custom{i}"), 1.0);
+		}
+		pose.time = Some(Time(2.0));
+
+		let packets = pose.to_packets();
+		assert!(packets.len() > 1);
+		for packet in &packets {
+			assert!(osc::encode(packet)?.len() <= osc::MAX_UDP_PAYLOAD);
+		}
+		// blend shapes are visited in arbitrary order, so only the last packet may end up with a single message
+		assert!(packets[..packets.len() - 1].iter().all(|packet| matches!(packet, OSCPacket::Bundle(_))));
+
+		let messages: Vec<_> = packets
+			.into_iter()
+			.map(parse)
+			.collect::<Result<Vec<_>, _>>()?
+			.into_iter()
+			.flatten()
+			.collect();
+		assert_eq!(messages.len(), 1 + 64 + 2);
+		assert!(matches!(messages[messages.len() - 2], VMCMessage::ApplyBlendShapes));
+		assert!(matches!(messages[messages.len() - 1], VMCMessage::Time(Time(t)) if t == 2.0));
+		Ok(())
+	}
+
+	#[test]
+	fn test_pose_offset() {
+		let t_pose = Pose::t_pose();
+		assert_eq!(t_pose.bones.len(), 55);
+		assert_eq!(t_pose.bone(StandardVRM0Bone::RightLittleProximal).unwrap().position, Vec3A::new(0.07, 0.0, -0.035));
+		assert!(t_pose.bone(StandardVRM0Bone::Pelvis).is_none());
+
+		let mut tracked = t_pose.clone();
+		tracked.root.as_mut().unwrap().position = Vec3A::X;
+		tracked.set_bone(StandardVRM0Bone::Chest, Vec3A::Y * 0.1, Quat::from_rotation_x(0.2));
+		tracked.set_bone("Tail", Vec3A::ZERO, Quat::IDENTITY);
+		tracked.set_blendshape("Joy", 0.3);
+		let mut base = t_pose.clone();
+		base.set_blendshape("Joy", 0.1);
+		base.set_blendshape("Angry", 0.5);
+
+		let offset = tracked.diff(&base);
+		assert_eq!(offset.root.as_ref().unwrap().position, Vec3A::X);
+		assert!(offset.bone("Tail").is_none());
+		assert!(offset.blendshape("Angry").is_none());
+		let mut applied = base.clone();
+		applied.apply_offset(&offset);
+		let chest = applied.bone(StandardVRM0Bone::Chest).unwrap();
+		assert!((chest.position - Vec3A::Y * 0.1).length() < 1e-6);
+		assert!(chest.rotation.abs_diff_eq(Quat::from_rotation_x(0.2), 1e-6));
+		assert!((applied.blendshape("Joy").unwrap() - 0.3).abs() < 1e-6);
+		assert_eq!(applied.blendshape("Angry"), Some(0.5));
+	}
+}
@@ -0,0 +1,415 @@
+//! Measurement of latency, jitter & packet loss between two hosts.
+//!
+//! A [`LinkProbe`] sends numbered, timestamped probe messages as `/VMC/Thru/Probe` pass-through messages, which VMC
+//! applications ignore. It can measure a link in two ways:
+//!
+//! - **Round trip**: the other host reflects probes back with [`echo`], and the probe measures the round-trip time of
+//!   each. The one-way delay is estimated as half of the round-trip time.
+//! - **One way**: the other host measures the probes (or the [`Time`] messages sent by any performer) as they arrive.
+//!   Since the clocks of the two hosts aren't synchronized, the absolute delay can't be known; instead, delays are
+//!   measured relative to the fastest packet seen, which is usually what matters when diagnosing lag caused by
+//!   congested networks or overloaded hosts.
+//!
+//! The `vmc-bench` binary (enabled with the `cli` feature) wraps both modes.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+//! use std::time::{Duration, Instant};
+//!
+//! use vmc::{OSCPacket, probe::LinkProbe};
+//!
+//! let mut socket = vmc::performer!("192.168.1.20:39539").await?;
+//! let mut probe = LinkProbe::new();
+//! for _ in 0..100 {
+//! 	socket.send(probe.probe()).await?;
+//! 	if let Ok(Ok(OSCPacket::Message(message))) =
+//! 		tokio::time::timeout(Duration::from_millis(100), socket.recv()).await
+//! 	{
+//! 		probe.handle(&message, Instant::now());
+//! 	}
+//! }
+//! println!("{}", probe.stats());
+//! # Ok(()) }) }
+//! ```
+
+use std::{
+	fmt,
+	time::{Duration, Instant}
+};
+
+use crate::{
+	VMCTime as Time,
+	osc::{OSCMessage, OSCType}
+};
+
+/// The address of probe messages.
+pub const ADDRESS: &str = "/VMC/Thru/Probe";
+/// The address of probe messages reflected by [`echo`].
+pub const ECHO_ADDRESS: &str = "/VMC/Thru/Probe/Echo";
+
+const TIME_ADDRESS: &str = "/VMC/Ext/T";
+
+/// A probe message, carrying its sequence number and the time it was sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Probe {
+	/// The sequence number of the probe, counting up from 0.
+	pub seq: u32,
+	/// The time the probe was sent, relative to an arbitrary point on the sender's clock.
+	pub sent: Duration
+}
+
+impl Probe {
+	/// Encodes the probe as a message, `/VMC/Thru/Probe <seq: i32> <sent: i64 microseconds>`.
+	pub fn to_message(&self) -> OSCMessage {
+		self.message(ADDRESS)
+	}
+
+	/// Encodes the probe as a reflected message, which has the same arguments as [`Probe::to_message`].
+	pub fn to_echo(&self) -> OSCMessage {
+		self.message(ECHO_ADDRESS)
+	}
+
+	fn message(&self, addr: &str) -> OSCMessage {
+		OSCMessage::new(addr, (self.seq as i32, self.sent.as_micros() as i64))
+	}
+
+	/// Parses a probe message, returning `None` if the message isn't one.
+	pub fn from_message(message: &OSCMessage) -> Option<Self> {
+		Self::parse(message, ADDRESS)
+	}
+
+	/// Parses a reflected probe message, returning `None` if the message isn't one.
+	pub fn from_echo(message: &OSCMessage) -> Option<Self> {
+		Self::parse(message, ECHO_ADDRESS)
+	}
+
+	fn parse(message: &OSCMessage, addr: &str) -> Option<Self> {
+		match (message.addr == addr, &message.args[..]) {
+			(true, [OSCType::Int(seq), OSCType::Long(sent)]) => Some(Self {
+				seq: *seq as u32,
+				sent: Duration::from_micros(u64::try_from(*sent).ok()?)
+			}),
+			_ => None
+		}
+	}
+}
+
+/// Returns the message reflecting a probe message back to its sender, or `None` if the message isn't a probe.
+pub fn echo(message: &OSCMessage) -> Option<OSCMessage> {
+	Probe::from_message(message).map(|probe| probe.to_echo())
+}
+
+/// Delay, jitter & loss statistics of a link, collected by a [`LinkProbe`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LinkStats {
+	samples: u64,
+	// one-way transit times in seconds; only relative to each other if `relative` is set
+	min: f64,
+	max: f64,
+	sum: f64,
+	jitter: f64,
+	last_transit: Option<f64>,
+	/// The lowest transit time ever seen, which relative transit times are measured against.
+	baseline: Option<f64>,
+	relative: bool,
+	first_seq: Option<u32>,
+	highest_seq: u32,
+	sequenced: u64
+}
+
+impl LinkStats {
+	/// Creates empty statistics.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	fn record(&mut self, seq: Option<u32>, transit: f64, relative: bool) {
+		self.relative = relative;
+		if relative {
+			self.baseline = Some(self.baseline.map_or(transit, |baseline| baseline.min(transit)));
+		}
+		if self.samples == 0 {
+			(self.min, self.max) = (transit, transit);
+		} else {
+			(self.min, self.max) = (self.min.min(transit), self.max.max(transit));
+		}
+		self.samples += 1;
+		self.sum += transit;
+
+		// interarrival jitter as defined by RFC 3550 § 6.4.1
+		if let Some(last) = self.last_transit {
+			self.jitter += ((transit - last).abs() - self.jitter) / 16.0;
+		}
+		self.last_transit = Some(transit);
+
+		if let Some(seq) = seq {
+			let first = *self.first_seq.get_or_insert(seq);
+			if self.sequenced == 0 || seq.wrapping_sub(first) > self.highest_seq.wrapping_sub(first) {
+				self.highest_seq = seq;
+			}
+			self.sequenced += 1;
+		}
+	}
+
+	fn offset(&self) -> f64 {
+		if self.relative { self.baseline.unwrap_or_default() } else { 0.0 }
+	}
+
+	/// Returns the number of samples collected.
+	pub fn samples(&self) -> u64 {
+		self.samples
+	}
+
+	/// Returns `true` if delays are measured relative to the fastest packet seen rather than absolutely; see the
+	/// [module documentation](self).
+	pub fn is_relative(&self) -> bool {
+		self.relative
+	}
+
+	/// Returns the lowest one-way delay, or `None` if no samples have been collected.
+	pub fn min_delay(&self) -> Option<Duration> {
+		(self.samples > 0).then(|| secs(self.min - self.offset()))
+	}
+
+	/// Returns the mean one-way delay, or `None` if no samples have been collected.
+	pub fn mean_delay(&self) -> Option<Duration> {
+		(self.samples > 0).then(|| secs(self.sum / self.samples as f64 - self.offset()))
+	}
+
+	/// Returns the highest one-way delay, or `None` if no samples have been collected.
+	pub fn max_delay(&self) -> Option<Duration> {
+		(self.samples > 0).then(|| secs(self.max - self.offset()))
+	}
+
+	/// Returns the smoothed variation in delay between consecutive packets, as defined by
+	/// [RFC 3550](https://www.rfc-editor.org/rfc/rfc3550#section-6.4.1).
+	pub fn jitter(&self) -> Duration {
+		secs(self.jitter)
+	}
+
+	/// Returns the number of probes lost (or not yet received), judging by gaps in their sequence numbers.
+	///
+	/// Samples without sequence numbers, like [`Time`] messages, aren't counted.
+	pub fn lost(&self) -> u64 {
+		match self.first_seq {
+			Some(first) => (u64::from(self.highest_seq.wrapping_sub(first)) + 1).saturating_sub(self.sequenced),
+			None => 0
+		}
+	}
+
+	/// Returns the fraction of probes lost, from `0.0` to `1.0`.
+	pub fn loss(&self) -> f64 {
+		let lost = self.lost();
+		if lost == 0 { 0.0 } else { lost as f64 / (lost + self.sequenced) as f64 }
+	}
+
+	/// Clears all samples. The baseline that relative delays are measured against is kept.
+	pub fn reset(&mut self) {
+		*self = Self {
+			baseline: self.baseline,
+			relative: self.relative,
+			..Self::default()
+		};
+	}
+}
+
+fn secs(secs: f64) -> Duration {
+	Duration::try_from_secs_f64(secs.max(0.0)).unwrap_or(Duration::MAX)
+}
+
+impl fmt::Display for LinkStats {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let ms = |delay: Option<Duration>| delay.unwrap_or_default().as_secs_f64() * 1000.0;
+		write!(
+			f,
+			"{} samples, {}delay {:.2}/{:.2}/{:.2} ms (min/mean/max), jitter {:.2} ms, {} lost ({:.1}%)",
+			self.samples,
+			if self.relative { "relative " } else { "" },
+			ms(self.min_delay()),
+			ms(self.mean_delay()),
+			ms(self.max_delay()),
+			ms(Some(self.jitter())),
+			self.lost(),
+			self.loss() * 100.0
+		)
+	}
+}
+
+/// Sends probe messages and measures the link they travel over; see the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct LinkProbe {
+	epoch: Instant,
+	next_seq: u32,
+	stats: LinkStats
+}
+
+impl Default for LinkProbe {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl LinkProbe {
+	/// Creates a new probe.
+	pub fn new() -> Self {
+		Self {
+			epoch: Instant::now(),
+			next_seq: 0,
+			stats: LinkStats::new()
+		}
+	}
+
+	/// Returns the next probe message to send, timestamped with the current time.
+	pub fn probe(&mut self) -> OSCMessage {
+		let probe = Probe {
+			seq: self.next_seq,
+			sent: self.epoch.elapsed()
+		};
+		self.next_seq = self.next_seq.wrapping_add(1);
+		probe.to_message()
+	}
+
+	/// Returns a [`Time`] message with the current time, for measuring the link one way to a host which only accepts
+	/// standard VMC messages.
+	pub fn probe_time(&self) -> Time {
+		Time::new(self.epoch.elapsed().as_secs_f32())
+	}
+
+	/// Measures a received message, returning the one-way delay of the packet if the message was a probe.
+	///
+	/// [`Time`] messages with a non-finite time are ignored.
+	///
+	/// Reflected probes sent by this probe are measured round trip. Probes from other hosts and [`Time`] messages are
+	/// measured one way, relative to the fastest packet seen. Packets should be measured as soon as possible after they
+	/// are received, e.g. using [`VMCRecvTimestamp::received`](crate::VMCRecvTimestamp::received).
+	pub fn handle(&mut self, message: &OSCMessage, received: Instant) -> Option<Duration> {
+		let now = received.saturating_duration_since(self.epoch).as_secs_f64();
+		let (seq, transit, relative) = if let Some(probe) = Probe::from_echo(message) {
+			(Some(probe.seq), (now - probe.sent.as_secs_f64()) / 2.0, false)
+		} else if let Some(probe) = Probe::from_message(message) {
+			(Some(probe.seq), now - probe.sent.as_secs_f64(), true)
+		} else if let (TIME_ADDRESS, [OSCType::Float(time)]) = (message.addr.as_str(), &message.args[..]) {
+			if !time.is_finite() {
+				return None;
+			}
+			(None, now - f64::from(*time), true)
+		} else {
+			return None;
+		};
+		self.stats.record(seq, transit, relative);
+		Some(secs(transit - self.stats.offset()))
+	}
+
+	/// Returns the statistics collected so far.
+	pub fn stats(&self) -> &LinkStats {
+		&self.stats
+	}
+
+	/// Returns the statistics collected so far and clears them, e.g. to report statistics at a regular interval.
+	pub fn take_stats(&mut self) -> LinkStats {
+		let stats = self.stats.clone();
+		self.stats.reset();
+		stats
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::IntoOSCMessage;
+
+	#[test]
+	fn test_link_probe() {
+		let mut local = LinkProbe::new();
+		let mut remote = LinkProbe::new();
+		let start = local.epoch;
+
+		let probes: Vec<_> = (0..5).map(|_| local.probe()).collect();
+		assert_eq!(Probe::from_message(&probes[3]).map(|probe| probe.seq), Some(3));
+		assert_eq!(remote.handle(&probes[0], start + Duration::from_millis(10)), Some(Duration::ZERO));
+		assert!(remote.stats().is_relative());
+
+		// echoes of probes 0, 1 & 3 return after 2, 4 & 2ms
+		for (seq, rtt) in [(0, 2), (1, 4), (3, 2)] {
+			let echo = echo(&probes[seq]).unwrap();
+			let sent = Probe::from_echo(&echo).unwrap().sent;
+			local.handle(&echo, start + sent + Duration::from_millis(rtt));
+		}
+		let stats = local.take_stats();
+		assert!(!stats.is_relative());
+		assert_eq!(stats.samples(), 3);
+		assert!((stats.min_delay().unwrap().as_secs_f64() - 0.001).abs() < 1e-6);
+		assert!((stats.max_delay().unwrap().as_secs_f64() - 0.002).abs() < 1e-6);
+		assert_eq!(stats.lost(), 1);
+		assert_eq!(stats.loss(), 0.25);
+		assert!(stats.jitter() > Duration::ZERO);
+		assert_eq!(local.stats().samples(), 0);
+
+		assert_eq!(local.handle(&OSCMessage::new("/VMC/Ext/Blend/Apply", ()), Instant::now()), None);
+	}
+
+	#[test]
+	fn test_link_probe_edge_cases() {
+		let mut probe = LinkProbe::new();
+		let start = probe.epoch;
+		let empty = probe.stats().clone();
+		assert_eq!((empty.min_delay(), empty.mean_delay(), empty.max_delay()), (None, None, None));
+		assert_eq!((empty.lost(), empty.loss()), (0, 0.0));
+		assert_eq!(empty.to_string(), "0 samples, delay 0.00/0.00/0.00 ms (min/mean/max), jitter 0.00 ms, 0 lost (0.0%)");
+
+		// malformed probes & time messages are ignored
+		for message in [
+			OSCMessage::new(ADDRESS, (1, 1.0)),
+			OSCMessage::new(ADDRESS, (1, -1_i64)),
+			OSCMessage::new(ADDRESS, (1,)),
+			OSCMessage::new(ECHO_ADDRESS, ()),
+			OSCMessage::new("/VMC/Thru/Probe/Other", (1, 1_i64)),
+			OSCMessage::new(TIME_ADDRESS, (1.0, 2.0)),
+			OSCMessage::new(TIME_ADDRESS, (1.0_f64,)),
+			OSCMessage::new(TIME_ADDRESS, (f32::NAN,)),
+			OSCMessage::new(TIME_ADDRESS, (f32::INFINITY,))
+		] {
+			assert_eq!(probe.handle(&message, start), None, "{message:?}");
+		}
+		assert_eq!(probe.stats().samples(), 0);
+
+		// packets received "before" the epoch or sent "in the future" have no delay rather than a negative one
+		let time = |secs: f32| Time::new(secs).into_osc_message();
+		assert_eq!(probe.handle(&time(1.0), start - Duration::from_secs(1)), Some(Duration::ZERO));
+		// a sender whose clock is far ahead lowers the baseline; delays relative to it saturate instead of overflowing
+		assert_eq!(probe.handle(&time(f32::MAX), start), Some(Duration::ZERO));
+		assert_eq!(probe.handle(&time(0.0), start), Some(Duration::MAX));
+		assert_eq!(probe.stats().max_delay(), Some(Duration::MAX));
+
+		// the baseline is kept across resets
+		probe.take_stats();
+		assert_eq!(probe.handle(&time(0.0), start), Some(Duration::MAX));
+	}
+
+	#[test]
+	fn test_link_stats_sequence() {
+		let mut stats = LinkStats::new();
+		// sequence numbers wrap around
+		for seq in [u32::MAX - 1, 1, u32::MAX, 2] {
+			stats.record(Some(seq), 0.001, false);
+		}
+		assert_eq!((stats.samples(), stats.lost()), (4, 1));
+		assert_eq!(stats.loss(), 0.2);
+		assert_eq!(stats.jitter(), Duration::ZERO);
+
+		// reordered & duplicate packets don't count as lost, and samples without a sequence number aren't counted at all
+		stats.record(Some(0), 0.001, false);
+		stats.record(Some(0), 0.001, false);
+		stats.record(None, 0.001, false);
+		assert_eq!((stats.samples(), stats.lost()), (7, 0));
+		assert_eq!(stats.loss(), 0.0);
+
+		stats.reset();
+		assert_eq!((stats.samples(), stats.lost()), (0, 0));
+		stats.record(Some(7), 0.003, false);
+		assert_eq!(stats.lost(), 0);
+		assert_eq!(stats.min_delay(), Some(Duration::from_millis(3)));
+	}
+}
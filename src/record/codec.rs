@@ -0,0 +1,547 @@
+use std::collections::HashMap;
+
+use super::map_eof;
+use crate::{
+	IntoOSCMessage, OSCPacket, VMCError, VMCMessage, VMCResult, VMCTime, Vec3A,
+	message::{BlendShape, BoneTransform, DeviceTransform, DeviceType, RootTransform},
+	name::Name,
+	osc, parse
+};
+
+const TAG_ROOT: u8 = 0;
+const TAG_BONE: u8 = 1;
+/// Device transforms use tags `TAG_DEVICE..TAG_DEVICE + 6`: the device type's index, plus 3 if the transform is local.
+const TAG_DEVICE: u8 = 2;
+const TAG_BLENDSHAPE: u8 = 8;
+const TAG_APPLY_BLENDSHAPES: u8 = 9;
+const TAG_TIME: u8 = 10;
+/// Any other message, stored as a length-prefixed OSC message.
+const TAG_RAW: u8 = 11;
+
+const DEVICE_TYPES: [DeviceType; 3] = [DeviceType::HMD, DeviceType::Controller, DeviceType::Tracker];
+
+/// Configuration for [`Compression::Delta`](super::Compression::Delta).
+///
+/// By default, the codec is lossless. Lossy compression quantizes positions, rotations, and blend shape values, which
+/// shrinks recordings considerably further; the default lossy configuration ([`DeltaConfig::lossy`]) is precise well
+/// beyond what is visible on an avatar.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeltaConfig {
+	position_precision: Option<f32>,
+	rotation_bits: Option<u8>,
+	blendshape_bits: Option<u8>,
+	keyframe_interval: u32
+}
+
+impl Default for DeltaConfig {
+	fn default() -> Self {
+		Self::lossless()
+	}
+}
+
+impl DeltaConfig {
+	/// Creates a configuration which preserves all values exactly.
+	pub fn lossless() -> Self {
+		Self {
+			position_precision: None,
+			rotation_bits: None,
+			blendshape_bits: None,
+			keyframe_interval: 60
+		}
+	}
+
+	/// Creates a lossy configuration, quantizing positions to 0.1mm, rotation quaternion components to 16 bits, and
+	/// blend shape values to 12 bits.
+	pub fn lossy() -> Self {
+		Self {
+			position_precision: Some(0.0001),
+			rotation_bits: Some(16),
+			blendshape_bits: Some(12),
+			keyframe_interval: 60
+		}
+	}
+
+	/// Quantizes positions to multiples of `precision` meters, or stores them losslessly if `None`.
+	///
+	/// # Panics
+	///
+	/// Panics if `precision` is not positive & finite.
+	pub fn with_position_precision(mut self, precision: Option<f32>) -> Self {
+		if let Some(precision) = precision {
+			assert!(precision > 0.0 && precision.is_finite(), "position precision must be positive");
+		}
+		self.position_precision = precision;
+		self
+	}
+
+	/// Quantizes rotation quaternion components to the given number of bits, or stores them losslessly if `None`.
+	///
+	/// # Panics
+	///
+	/// Panics if `bits` is not within `2..=24`.
+	pub fn with_rotation_bits(mut self, bits: Option<u8>) -> Self {
+		if let Some(bits) = bits {
+			assert!((2..=24).contains(&bits), "rotation bits must be within 2..=24");
+		}
+		self.rotation_bits = bits;
+		self
+	}
+
+	/// Quantizes blend shape values to the given number of bits over `0.0..=1.0`, or stores them losslessly if `None`.
+	///
+	/// # Panics
+	///
+	/// Panics if `bits` is not within `1..=24`.
+	pub fn with_blendshape_bits(mut self, bits: Option<u8>) -> Self {
+		if let Some(bits) = bits {
+			assert!((1..=24).contains(&bits), "blend shape bits must be within 1..=24");
+		}
+		self.blendshape_bits = bits;
+		self
+	}
+
+	/// Sets the number of frames between keyframes. Seeking has to decode from the nearest preceding keyframe, so
+	/// shorter intervals make seeking faster at the cost of size.
+	///
+	/// # Panics
+	///
+	/// Panics if `interval` is 0.
+	pub fn with_keyframe_interval(mut self, interval: u32) -> Self {
+		assert!(interval > 0, "keyframe interval must be positive");
+		self.keyframe_interval = interval;
+		self
+	}
+
+	/// Returns `true` if this configuration preserves all values exactly.
+	pub fn is_lossless(&self) -> bool {
+		self.position_precision.is_none() && self.rotation_bits.is_none() && self.blendshape_bits.is_none()
+	}
+
+	fn scales(&self) -> Scales {
+		Scales {
+			position: self.position_precision.map(|precision| 1.0 / precision),
+			rotation: self.rotation_bits.map(|bits| ((1u32 << (bits - 1)) - 1) as f32),
+			blendshape: self.blendshape_bits.map(|bits| ((1u32 << bits) - 1) as f32)
+		}
+	}
+
+	fn encode(&self, out: &mut Vec<u8>) {
+		out.extend_from_slice(&self.position_precision.unwrap_or(0.0).to_be_bytes());
+		out.push(self.rotation_bits.unwrap_or(0));
+		out.push(self.blendshape_bits.unwrap_or(0));
+	}
+
+	fn decode(input: &mut &[u8]) -> VMCResult<Self> {
+		let precision = f32::from_be_bytes(take(input, 4)?.try_into().unwrap());
+		let bits = take(input, 2)?;
+		let invalid = || VMCError::InvalidRecording("invalid delta codec parameters".to_string());
+		Ok(Self {
+			position_precision: if precision == 0.0 {
+				None
+			} else if precision > 0.0 && precision.is_finite() {
+				Some(precision)
+			} else {
+				return Err(invalid());
+			},
+			rotation_bits: match bits[0] {
+				0 => None,
+				bits @ 2..=24 => Some(bits),
+				_ => return Err(invalid())
+			},
+			blendshape_bits: match bits[1] {
+				0 => None,
+				bits @ 1..=24 => Some(bits),
+				_ => return Err(invalid())
+			},
+			keyframe_interval: 60
+		})
+	}
+}
+
+/// Quantization scales, or `None` for lossless channels.
+#[derive(Debug, Clone, Copy)]
+struct Scales {
+	position: Option<f32>,
+	rotation: Option<f32>,
+	blendshape: Option<f32>
+}
+
+fn encode_value(out: &mut Vec<u8>, value: f32, scale: Option<f32>, previous: &mut u32) {
+	match scale {
+		None => {
+			let bits = value.to_bits();
+			write_varint(out, (bits ^ *previous) as u64);
+			*previous = bits;
+		}
+		Some(scale) => {
+			let quantized = (value * scale).round() as i32;
+			write_varint(out, zigzag(quantized.wrapping_sub(*previous as i32)));
+			*previous = quantized as u32;
+		}
+	}
+}
+
+fn decode_value(input: &mut &[u8], scale: Option<f32>, previous: &mut u32) -> VMCResult<f32> {
+	let delta = read_varint(input)?;
+	match scale {
+		None => {
+			*previous ^= delta as u32;
+			Ok(f32::from_bits(*previous))
+		}
+		Some(scale) => {
+			let quantized = (*previous as i32).wrapping_add(unzigzag(delta));
+			*previous = quantized as u32;
+			Ok(quantized as f32 / scale)
+		}
+	}
+}
+
+fn encode_transform(out: &mut Vec<u8>, position: Vec3A, rotation: glam::Quat, scales: Scales, previous: &mut [u32; 7]) {
+	for (value, previous) in position.to_array().into_iter().zip(&mut previous[..3]) {
+		encode_value(out, value, scales.position, previous);
+	}
+	// q & -q are the same rotation, so quantized rotations can be normalized to a positive w to improve deltas
+	let rotation = if scales.rotation.is_some() && rotation.w < 0.0 { -rotation } else { rotation };
+	for (value, previous) in rotation.to_array().into_iter().zip(&mut previous[3..]) {
+		encode_value(out, value, scales.rotation, previous);
+	}
+}
+
+fn decode_transform(input: &mut &[u8], scales: Scales, previous: &mut [u32; 7]) -> VMCResult<(Vec3A, glam::Quat)> {
+	let mut values = [0.0; 7];
+	for (i, (value, previous)) in values.iter_mut().zip(previous.iter_mut()).enumerate() {
+		*value = decode_value(input, if i < 3 { scales.position } else { scales.rotation }, previous)?;
+	}
+	let position = Vec3A::new(values[0], values[1], values[2]);
+	let mut rotation = glam::Quat::from_xyzw(values[3], values[4], values[5], values[6]);
+	if scales.rotation.is_some() && rotation.length_squared() > 0.0 {
+		rotation = rotation.normalize();
+	}
+	Ok((position, rotation))
+}
+
+/// Encodes frames with the delta codec.
+#[derive(Debug)]
+pub(crate) struct DeltaEncoder {
+	config: DeltaConfig,
+	ids: HashMap<(u8, String), usize>,
+	previous: Vec<[u32; 7]>,
+	time: u32,
+	frames_since_keyframe: u32
+}
+
+impl DeltaEncoder {
+	pub fn new(config: DeltaConfig) -> Self {
+		Self {
+			config,
+			ids: HashMap::new(),
+			previous: Vec::new(),
+			time: 0,
+			frames_since_keyframe: config.keyframe_interval
+		}
+	}
+
+	/// Makes the next frame a keyframe.
+	pub fn force_keyframe(&mut self) {
+		self.frames_since_keyframe = self.config.keyframe_interval;
+	}
+
+	/// Encodes a frame into `out`, returning whether it was encoded as a keyframe.
+	pub fn encode(&mut self, time: &VMCTime, messages: &[VMCMessage], out: &mut Vec<u8>) -> bool {
+		let keyframe = self.frames_since_keyframe >= self.config.keyframe_interval;
+		if keyframe {
+			self.ids.clear();
+			self.previous.clear();
+			self.time = 0;
+			self.frames_since_keyframe = 0;
+			self.config.encode(out);
+		}
+		self.frames_since_keyframe += 1;
+
+		let scales = self.config.scales();
+		for message in messages {
+			match message {
+				VMCMessage::RootTransform(root) => {
+					let previous = self.entity(out, TAG_ROOT, "");
+					encode_transform(out, root.position, root.rotation, scales, previous);
+					out.push(root.scale.is_some() as u8 | (root.offset.is_some() as u8) << 1);
+					for value in root.scale.iter().chain(&root.offset).flat_map(|value| value.to_array()) {
+						out.extend_from_slice(&value.to_be_bytes());
+					}
+				}
+				VMCMessage::BoneTransform(transform) => {
+					let previous = self.entity(out, TAG_BONE, &transform.bone);
+					encode_transform(out, transform.position, transform.rotation, scales, previous);
+				}
+				VMCMessage::DeviceTransform(transform) => {
+					let device = DEVICE_TYPES.iter().position(|device| *device == transform.device).unwrap() as u8;
+					let previous = self.entity(out, TAG_DEVICE + device + transform.local as u8 * 3, &transform.joint);
+					encode_transform(out, transform.position, transform.rotation, scales, previous);
+				}
+				VMCMessage::BlendShape(blendshape) => {
+					let previous = self.entity(out, TAG_BLENDSHAPE, &blendshape.key);
+					encode_value(out, blendshape.value, scales.blendshape, &mut previous[0]);
+				}
+				VMCMessage::ApplyBlendShapes => out.push(TAG_APPLY_BLENDSHAPES),
+				VMCMessage::Time(time) => {
+					out.push(TAG_TIME);
+					encode_value(out, time.0, None, &mut self.time);
+				}
+				message => {
+					// NOTE: The Output implementation for Vec<u8> can't actually produce an error!
+					let bytes = osc::encode(&OSCPacket::Message(message.clone().into_osc_message())).expect("Failed to write encoded packet into Vec");
+					out.push(TAG_RAW);
+					write_varint(out, bytes.len() as u64);
+					out.extend_from_slice(&bytes);
+				}
+			}
+		}
+		out.push(TAG_TIME);
+		encode_value(out, time.0, None, &mut self.time);
+		keyframe
+	}
+
+	/// Writes the tag & ID of an entity, followed by its name if this is its first occurrence since the last keyframe,
+	/// and returns its previous values.
+	fn entity(&mut self, out: &mut Vec<u8>, tag: u8, name: &str) -> &mut [u32; 7] {
+		out.push(tag);
+		let next_id = self.ids.len();
+		let id = *self.ids.entry((tag, name.to_string())).or_insert(next_id);
+		write_varint(out, id as u64);
+		if id == next_id {
+			write_varint(out, name.len() as u64);
+			out.extend_from_slice(name.as_bytes());
+			self.previous.push([0; 7]);
+		}
+		&mut self.previous[id]
+	}
+}
+
+/// Decodes frames encoded by a [`DeltaEncoder`].
+#[derive(Debug, Default)]
+pub(crate) struct DeltaDecoder {
+	config: DeltaConfig,
+	entities: Vec<(u8, Name)>,
+	previous: Vec<[u32; 7]>,
+	time: u32
+}
+
+impl DeltaDecoder {
+	/// Returns the configuration read from the last keyframe.
+	pub fn config(&self) -> DeltaConfig {
+		self.config
+	}
+
+	/// Decodes a frame, returning its messages; the last message is always the frame's time.
+	pub fn decode(&mut self, mut input: &[u8], keyframe: bool) -> VMCResult<Vec<VMCMessage>> {
+		let input = &mut input;
+		if keyframe {
+			self.config = DeltaConfig::decode(input)?;
+			self.entities.clear();
+			self.previous.clear();
+			self.time = 0;
+		}
+
+		let scales = self.config.scales();
+		let mut messages = Vec::new();
+		while let Some(&tag) = input.first() {
+			*input = &input[1..];
+			let message = match tag {
+				TAG_ROOT => {
+					let (_, previous) = self.entity(input, tag)?;
+					let (position, rotation) = decode_transform(input, scales, previous)?;
+					let flags = take(input, 1)?[0];
+					let mut vector = |present: bool| -> VMCResult<Option<Vec3A>> {
+						if !present {
+							return Ok(None);
+						}
+						let bytes = take(input, 12)?;
+						Ok(Some(Vec3A::from_array(std::array::from_fn(|i| f32::from_be_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap())))))
+					};
+					let scale = vector(flags & 1 != 0)?;
+					let offset = vector(flags & 2 != 0)?;
+					VMCMessage::RootTransform(RootTransform { position, rotation, scale, offset })
+				}
+				TAG_BONE => {
+					let (bone, previous) = self.entity(input, tag)?;
+					let (position, rotation) = decode_transform(input, scales, previous)?;
+					VMCMessage::BoneTransform(BoneTransform { bone, position, rotation })
+				}
+				tag @ TAG_DEVICE..TAG_BLENDSHAPE => {
+					let (joint, previous) = self.entity(input, tag)?;
+					let (position, rotation) = decode_transform(input, scales, previous)?;
+					let device = DEVICE_TYPES[((tag - TAG_DEVICE) % 3) as usize];
+					VMCMessage::DeviceTransform(DeviceTransform::new(device, joint, position, rotation, tag - TAG_DEVICE >= 3))
+				}
+				TAG_BLENDSHAPE => {
+					let (key, previous) = self.entity(input, tag)?;
+					let value = decode_value(input, scales.blendshape, &mut previous[0])?;
+					VMCMessage::BlendShape(BlendShape { key, value })
+				}
+				TAG_APPLY_BLENDSHAPES => VMCMessage::ApplyBlendShapes,
+				TAG_TIME => VMCMessage::Time(VMCTime(decode_value(input, None, &mut self.time)?)),
+				TAG_RAW => {
+					let len = read_varint(input)? as usize;
+					let (_, packet) = osc::decode_udp(take(input, len)?)?;
+					messages.extend(parse(packet)?);
+					continue;
+				}
+				tag => return Err(VMCError::InvalidRecording(format!("unknown message tag {tag}")))
+			};
+			messages.push(message);
+		}
+		match messages.last() {
+			Some(VMCMessage::Time(_)) => Ok(messages),
+			_ => Err(VMCError::InvalidRecording("frame is missing its time".to_string()))
+		}
+	}
+
+	fn entity(&mut self, input: &mut &[u8], tag: u8) -> VMCResult<(Name, &mut [u32; 7])> {
+		let id = read_varint(input)? as usize;
+		if id == self.entities.len() {
+			let len = read_varint(input)? as usize;
+			let name = String::from_utf8(take(input, len)?.to_vec()).map_err(|_| VMCError::InvalidRecording("name is not valid UTF-8".to_string()))?;
+			self.entities.push((tag, name.into()));
+			self.previous.push([0; 7]);
+		}
+		match self.entities.get(id) {
+			Some((entity_tag, name)) if *entity_tag == tag => Ok((name.clone(), &mut self.previous[id])),
+			_ => Err(VMCError::InvalidRecording(format!("invalid ID {id}")))
+		}
+	}
+}
+
+fn take<'a>(input: &mut &'a [u8], len: usize) -> VMCResult<&'a [u8]> {
+	if input.len() < len {
+		return Err(VMCError::InvalidRecording("truncated frame".to_string()));
+	}
+	let (head, tail) = input.split_at(len);
+	*input = tail;
+	Ok(head)
+}
+
+fn zigzag(value: i32) -> u64 {
+	((value << 1) ^ (value >> 31)) as u32 as u64
+}
+
+fn unzigzag(value: u64) -> i32 {
+	let value = value as u32;
+	((value >> 1) as i32) ^ -((value & 1) as i32)
+}
+
+pub(crate) fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+	while value >= 0x80 {
+		out.push(value as u8 | 0x80);
+		value >>= 7;
+	}
+	out.push(value as u8);
+}
+
+pub(crate) fn read_varint(input: &mut &[u8]) -> VMCResult<u64> {
+	let mut value = 0;
+	for shift in (0..64).step_by(7) {
+		let byte = take(input, 1)?[0];
+		value |= ((byte & 0x7F) as u64) << shift;
+		if byte & 0x80 == 0 {
+			return Ok(value);
+		}
+	}
+	Err(VMCError::InvalidRecording("varint too long".to_string()))
+}
+
+/// Reads a varint from a reader, one byte at a time.
+pub(crate) fn read_varint_from<R: std::io::Read>(reader: &mut R) -> VMCResult<(u64, u64)> {
+	let mut value = 0;
+	for (i, shift) in (0..64).step_by(7).enumerate() {
+		let mut byte = [0u8];
+		reader.read_exact(&mut byte).map_err(map_eof)?;
+		value |= ((byte[0] & 0x7F) as u64) << shift;
+		if byte[0] & 0x80 == 0 {
+			return Ok((value, i as u64 + 1));
+		}
+	}
+	Err(VMCError::InvalidRecording("varint too long".to_string()))
+}
+
+// the delta codec always compresses frames with LZ4
+#[cfg(all(test, feature = "lz4"))]
+mod tests {
+	use std::{io::Cursor, time::Duration};
+
+	use glam::Quat;
+
+	use super::*;
+	use crate::{
+		VMCFrame, VMCStandardVRM0Bone, VMCState,
+		message::ModelState,
+		record::{Compression, Metadata, Recorder, RecordingReader}
+	};
+
+	fn frame(i: u32) -> Vec<VMCMessage> {
+		let t = i as f32 * 0.1;
+		vec![
+			VMCMessage::RootTransform(RootTransform::new(Vec3A::new(t, 0.0, 0.0), Quat::from_rotation_y(t))),
+			VMCMessage::BoneTransform(BoneTransform::new(VMCStandardVRM0Bone::Head, Vec3A::new(0.0, 0.1, t), Quat::from_rotation_x(-t))),
+			VMCMessage::DeviceTransform(DeviceTransform::new(DeviceType::Tracker, "LHR-1", Vec3A::Y, Quat::IDENTITY, true)),
+			VMCMessage::BlendShape(BlendShape::new("Joy", t.fract())),
+			VMCMessage::ApplyBlendShapes,
+			VMCMessage::State(VMCState::new(ModelState::Loaded)),
+		]
+	}
+
+	#[test]
+	fn test_delta_codec() -> VMCResult<()> {
+		for config in [DeltaConfig::lossless().with_keyframe_interval(4), DeltaConfig::lossy().with_keyframe_interval(4)] {
+			let mut encoder = DeltaEncoder::new(config);
+			let mut decoder = DeltaDecoder::default();
+			for i in 0..10 {
+				let messages = frame(i);
+				let mut out = Vec::new();
+				let keyframe = encoder.encode(&VMCTime::new(i as f32), &messages, &mut out);
+				assert_eq!(keyframe, i % 4 == 0);
+
+				let decoded = decoder.decode(&out, keyframe)?;
+				assert_eq!(decoder.config().is_lossless(), config.is_lossless());
+				assert!(matches!(decoded.last(), Some(VMCMessage::Time(time)) if time.0 == i as f32));
+				let encode = |message: &VMCMessage| osc::encode(&OSCPacket::Message(message.clone().into_osc_message())).unwrap();
+				for (a, b) in messages.iter().zip(&decoded) {
+					match (a, b) {
+						(VMCMessage::BoneTransform(a), VMCMessage::BoneTransform(b)) if !config.is_lossless() => {
+							assert!((a.position - b.position).abs().max_element() <= 0.00005);
+							assert!(a.rotation.angle_between(b.rotation) < 0.001);
+						}
+						(VMCMessage::BlendShape(a), VMCMessage::BlendShape(b)) if !config.is_lossless() => {
+							assert!((a.value - b.value).abs() < 0.001);
+						}
+						(VMCMessage::RootTransform(_), VMCMessage::RootTransform(_)) if !config.is_lossless() => {}
+						(a, b) => assert_eq!(encode(a), encode(b))
+					}
+				}
+			}
+		}
+
+		// random access should decode from the preceding keyframe
+		let record = |compression| -> VMCResult<Vec<u8>> {
+			let mut recorder = Recorder::with_compression(Cursor::new(Vec::new()), Metadata::new(), compression)?;
+			for i in 0..100 {
+				let frame = VMCFrame {
+					time: VMCTime::new(i as f32),
+					messages: frame(i)
+				};
+				recorder.record_frame_at(&frame, Duration::from_millis(i as u64 * 16))?;
+			}
+			Ok(recorder.finish()?.into_inner())
+		};
+		let lossy = Compression::Delta(DeltaConfig::lossy().with_keyframe_interval(30));
+		let bytes = record(lossy)?;
+		assert!(bytes.len() < record(Compression::Lz4)?.len());
+		let mut reader = RecordingReader::new(Cursor::new(bytes))?;
+		assert_eq!(reader.compression(), Compression::Delta(DeltaConfig::lossy()));
+		assert_eq!(reader.timestamp(99), Some(Duration::from_millis(99 * 16)));
+		let sequential = reader.by_ref().collect::<VMCResult<Vec<_>>>()?;
+		for i in [75, 3, 60, 61] {
+			assert_eq!(reader.frame(i).unwrap()?, sequential[i]);
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,228 @@
+use std::{
+	io::{Read, Seek, Write},
+	ops::{Bound, RangeBounds},
+	time::Duration
+};
+
+use super::{Recorder, RecordingReader};
+use crate::{VMCAvatarState, VMCMessage, VMCResult, VMCTime};
+
+/// Returns messages reproducing the complete state of the avatar, excluding the time.
+fn snapshot(state: &VMCAvatarState) -> Vec<VMCMessage> {
+	let mut messages = state.to_pose().to_messages();
+	messages.pop();
+	messages.extend(state.devices().cloned().map(VMCMessage::DeviceTransform));
+	messages
+}
+
+impl<R: Read + Seek> RecordingReader<R> {
+	/// Writes the frames within the given time range to a new recording, returning the writer.
+	///
+	/// Timestamps in the new recording are relative to the start of the range. The first frame of the new recording
+	/// contains the complete state of the avatar at that point, so values set before the start of the range (e.g. blend
+	/// shapes which are only sent when they change) are preserved. [`Time`](crate::VMCTime) messages are rewritten to
+	/// match the new timestamps.
+	///
+	/// The new recording has the same metadata & compression as this one.
+	///
+	/// # Examples
+	///
+	/// ```no_run
+	/// # fn main() -> vmc::VMCResult<()> {
+	/// use std::{fs::File, io::BufWriter, time::Duration};
+	///
+	/// use vmc::record::RecordingReader;
+	///
+	/// let mut reader = RecordingReader::open("take1.vmcr")?;
+	/// reader
+	/// 	.trim(Duration::from_secs(5)..Duration::from_secs(20), BufWriter::new(File::create("take1-trimmed.vmcr")?))?;
+	/// # Ok(()) }
+	/// ```
+	pub fn trim<W: Write>(&mut self, range: impl RangeBounds<Duration>, writer: W) -> VMCResult<W> {
+		let start = match range.start_bound() {
+			Bound::Included(start) | Bound::Excluded(start) => *start,
+			Bound::Unbounded => Duration::ZERO
+		};
+
+		let mut recorder = Recorder::with_compression(writer, self.metadata().clone(), self.compression())?;
+		let mut state = VMCAvatarState::new();
+		let mut first = true;
+		for index in 0..self.len() {
+			let timestamp = self.timestamp(index).unwrap();
+			let mut frame = self.frame(index).unwrap()?.to_frame()?;
+			if !range.contains(&timestamp) {
+				if timestamp <= start {
+					state.extend(frame.messages);
+					continue;
+				}
+				break;
+			}
+
+			if first {
+				state.extend(frame.messages);
+				frame.messages = snapshot(&state);
+				first = false;
+			}
+			let timestamp = timestamp - start;
+			frame.time = VMCTime::new(timestamp.as_secs_f32());
+			recorder.record_frame_at(&frame, timestamp)?;
+		}
+		recorder.finish()
+	}
+
+	/// Writes a copy of this recording which loops seamlessly to a new recording, returning the writer.
+	///
+	/// Over the final `blend` of the recording, the pose is blended towards the pose of the first frame, so that
+	/// playing the recording on a loop (e.g. with [`Player::set_looping`](super::Player::set_looping)) doesn't visibly
+	/// jump at the seam. The first frame of the new recording contains the complete state of the avatar.
+	/// [`Time`](crate::VMCTime) messages are rewritten to match the frames' timestamps.
+	///
+	/// The new recording has the same metadata & compression as this one.
+	pub fn make_loop<W: Write>(&mut self, blend: Duration, writer: W) -> VMCResult<W> {
+		let mut recorder = Recorder::with_compression(writer, self.metadata().clone(), self.compression())?;
+		let Some(start) = self.timestamp(0) else {
+			return recorder.finish();
+		};
+		let end = self.duration();
+		let blend = blend.min(end - start);
+		let blend_start = end - blend;
+		// the first frame follows the last one when looping, so blend such that it would be reached one frame later
+		let blend_length = blend + (end - start).checked_div(self.len() as u32 - 1).unwrap_or_default();
+
+		let mut state = VMCAvatarState::new();
+		let mut target = None;
+		for index in 0..self.len() {
+			let timestamp = self.timestamp(index).unwrap();
+			let mut frame = self.frame(index).unwrap()?.to_frame()?;
+			state.extend(frame.messages.clone());
+
+			let target = target.get_or_insert_with(|| {
+				frame.messages = snapshot(&state);
+				state.to_pose()
+			});
+			if index > 0 && !blend.is_zero() && timestamp >= blend_start {
+				let t = (timestamp - blend_start).as_secs_f32() / blend_length.as_secs_f32();
+				let mut messages = state.to_pose().lerp(target, t).to_messages();
+				messages.pop();
+				messages.extend(state.devices().cloned().map(VMCMessage::DeviceTransform));
+				frame.messages = messages;
+			}
+			frame.time = VMCTime::new(timestamp.as_secs_f32());
+			recorder.record_frame_at(&frame, timestamp)?;
+		}
+		recorder.finish()
+	}
+}
+
+/// Concatenates recordings into a new recording, returning the writer.
+///
+/// Each take starts `gap` after the last frame of the previous take. The first frame of each take contains the complete
+/// state of the avatar, so that state from a previous take doesn't leak into the next. [`Time`](crate::VMCTime)
+/// messages are rewritten to match the frames' timestamps in the new recording, so that they increase monotonically.
+///
+/// The new recording has the metadata & compression of the first take.
+///
+/// # Examples
+///
+/// ```no_run
+/// # fn main() -> vmc::VMCResult<()> {
+/// use std::{fs::File, io::BufWriter, time::Duration};
+///
+/// use vmc::record::{self, RecordingReader};
+///
+/// let mut takes = [RecordingReader::open("take1.vmcr")?, RecordingReader::open("take2.vmcr")?];
+/// record::concat(&mut takes, Duration::from_millis(16), BufWriter::new(File::create("combined.vmcr")?))?;
+/// # Ok(()) }
+/// ```
+pub fn concat<R: Read + Seek, W: Write>(takes: &mut [RecordingReader<R>], gap: Duration, writer: W) -> VMCResult<W> {
+	let (metadata, compression) = takes
+		.first()
+		.map(|take| (take.metadata().clone(), take.compression()))
+		.unwrap_or_default();
+	let mut recorder = Recorder::with_compression(writer, metadata, compression)?;
+	let mut offset = Duration::ZERO;
+	for take in takes {
+		let Some(start) = take.timestamp(0) else {
+			continue;
+		};
+		let mut state = VMCAvatarState::new();
+		for index in 0..take.len() {
+			let timestamp = take.timestamp(index).unwrap() - start + offset;
+			let mut frame = take.frame(index).unwrap()?.to_frame()?;
+			if index == 0 {
+				state.extend(frame.messages);
+				frame.messages = snapshot(&state);
+			}
+			frame.time = VMCTime::new(timestamp.as_secs_f32());
+			recorder.record_frame_at(&frame, timestamp)?;
+		}
+		offset += take.duration() - start + gap;
+	}
+	recorder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+
+	use approx::assert_relative_eq;
+
+	use super::*;
+	use crate::{
+		Quat, VMCApplyBlendShapes, VMCBlendShape, VMCBoneTransform, VMCFrame, VMCStandardVRM0Bone, Vec3A,
+		record::{Metadata, RecordedFrame}
+	};
+
+	fn recording() -> VMCResult<RecordingReader<Cursor<Vec<u8>>>> {
+		let mut recorder = Recorder::new(Cursor::new(Vec::new()), Metadata::new())?;
+		for i in 0..10 {
+			let mut messages = vec![VMCBoneTransform::new(VMCStandardVRM0Bone::Head, Vec3A::X * i as f32, Quat::IDENTITY).into()];
+			if i == 0 {
+				messages.extend([VMCBlendShape::new("Joy", 1.0).into(), VMCApplyBlendShapes.into()]);
+			}
+			let frame = VMCFrame {
+				time: VMCTime::new(100.0 + i as f32),
+				messages
+			};
+			recorder.record_frame_at(&frame, Duration::from_millis(i * 10))?;
+		}
+		RecordingReader::new(recorder.finish()?)
+	}
+
+	fn frames(bytes: Cursor<Vec<u8>>) -> VMCResult<Vec<RecordedFrame>> {
+		RecordingReader::new(bytes)?.collect()
+	}
+
+	#[test]
+	fn test_edit_recordings() -> VMCResult<()> {
+		let trimmed = frames(recording()?.trim(Duration::from_millis(30)..Duration::from_millis(60), Cursor::new(Vec::new()))?)?;
+		assert_eq!(trimmed.len(), 3);
+		assert_eq!(trimmed[0].timestamp, Duration::ZERO);
+		let mut state = VMCAvatarState::new();
+		state.extend(trimmed[0].messages()?);
+		assert_eq!(state.blendshape("Joy"), Some(1.0));
+		assert_eq!(state.bone(VMCStandardVRM0Bone::Head).unwrap().position.x, 3.0);
+		assert_relative_eq!(state.time().unwrap(), 0.0);
+
+		let mut takes = [recording()?, recording()?];
+		let combined = frames(concat(&mut takes, Duration::from_millis(10), Cursor::new(Vec::new()))?)?;
+		assert_eq!(combined.len(), 20);
+		assert_eq!(combined[10].timestamp, Duration::from_millis(100));
+		assert!(
+			combined
+				.windows(2)
+				.all(|frames| frames[0].to_frame().unwrap().time.0 < frames[1].to_frame().unwrap().time.0)
+		);
+
+		let looped = frames(recording()?.make_loop(Duration::from_millis(40), Cursor::new(Vec::new()))?)?;
+		let head_x = |frame: &RecordedFrame| {
+			let mut state = VMCAvatarState::new();
+			state.extend(frame.messages().unwrap());
+			state.bone(VMCStandardVRM0Bone::Head).unwrap().position.x
+		};
+		// blending starts at 50ms and would reach the first frame at 100ms
+		assert_relative_eq!(head_x(&looped[5]), 5.0);
+		assert_relative_eq!(head_x(&looped[9]), 9.0 * 0.2, epsilon = 1e-5);
+		Ok(())
+	}
+}
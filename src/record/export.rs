@@ -0,0 +1,156 @@
+use std::{
+	collections::BTreeSet,
+	io::{Read, Seek, Write}
+};
+
+use super::RecordingReader;
+use crate::{VMCAvatarState, VMCMessage, VMCResult, name::Name};
+
+const TRANSFORM_COLUMNS: [&str; 7] = ["pos.x", "pos.y", "pos.z", "rot.x", "rot.y", "rot.z", "rot.w"];
+
+impl<R: Read + Seek> RecordingReader<R> {
+	/// Exports the recording as comma-separated values, for analysis in tools like pandas or R.
+	///
+	/// The table has one row per frame. The columns are:
+	/// - `timestamp`: the time at which the frame was recorded, in seconds since the start of the recording.
+	/// - `time`: the value of the frame's [`Time`](crate::VMCTime) message.
+	/// - `Root.pos.x` through `Root.rot.w`: the root transform's position and rotation quaternion.
+	/// - `<bone>.pos.x` through `<bone>.rot.w` for each bone present in the recording, sorted by name.
+	/// - `blendshape.<key>` for each blend shape present in the recording, sorted by key.
+	///
+	/// Each row holds the full state of the avatar as of that frame, so values persist across frames until they are
+	/// updated. Cells are left empty until a value is first received.
+	///
+	/// # Examples
+	///
+	/// ```no_run
+	/// # fn main() -> vmc::VMCResult<()> {
+	/// use std::{fs::File, io::BufWriter};
+	///
+	/// use vmc::record::RecordingReader;
+	///
+	/// let mut reader = RecordingReader::open("take1.vmcr")?;
+	/// reader.export_csv(BufWriter::new(File::create("take1.csv")?))?;
+	/// # Ok(()) }
+	/// ```
+	pub fn export_csv<W: Write>(&mut self, writer: W) -> VMCResult<()> {
+		self.export_table(writer, ',')
+	}
+
+	/// Exports the recording as tab-separated values. See [`RecordingReader::export_csv`] for the layout of the table.
+	pub fn export_tsv<W: Write>(&mut self, writer: W) -> VMCResult<()> {
+		self.export_table(writer, '\t')
+	}
+
+	fn export_table<W: Write>(&mut self, mut writer: W, delimiter: char) -> VMCResult<()> {
+		// the columns aren't known up front, so collect them in a first pass over all frames
+		let mut bones = BTreeSet::new();
+		let mut blendshapes = BTreeSet::new();
+		for index in 0..self.len() {
+			for message in self.frame(index).unwrap()?.messages()? {
+				match message {
+					VMCMessage::BoneTransform(transform) => {
+						bones.insert(transform.bone);
+					}
+					VMCMessage::BlendShape(blendshape) => {
+						blendshapes.insert(blendshape.key);
+					}
+					_ => {}
+				}
+			}
+		}
+
+		let mut row = Vec::new();
+		row.extend(["timestamp".to_string(), "time".to_string()]);
+		for name in std::iter::once("Root").chain(bones.iter().map(Name::as_str)) {
+			row.extend(TRANSFORM_COLUMNS.iter().map(|column| format!("{name}.{column}")));
+		}
+		row.extend(blendshapes.iter().map(|key| format!("blendshape.{key}")));
+		write_row(&mut writer, &row, delimiter)?;
+
+		let mut state = VMCAvatarState::new();
+		for index in 0..self.len() {
+			let frame = self.frame(index).unwrap()?;
+			state.extend(frame.messages()?);
+
+			row.clear();
+			row.push(frame.timestamp.as_secs_f64().to_string());
+			row.push(state.time().map(|time| time.to_string()).unwrap_or_default());
+			push_transform(&mut row, state.root().map(|root| (root.position.to_array(), root.rotation.to_array())));
+			for bone in &bones {
+				push_transform(&mut row, state.bone(bone).map(|bone| (bone.position.to_array(), bone.rotation.to_array())));
+			}
+			row.extend(
+				blendshapes
+					.iter()
+					.map(|key| state.blendshape(key).map(|value| value.to_string()).unwrap_or_default())
+			);
+			write_row(&mut writer, &row, delimiter)?;
+		}
+
+		writer.flush()?;
+		Ok(())
+	}
+}
+
+fn push_transform(row: &mut Vec<String>, transform: Option<([f32; 3], [f32; 4])>) {
+	match transform {
+		Some((position, rotation)) => row.extend(position.into_iter().chain(rotation).map(|value| value.to_string())),
+		None => row.extend(TRANSFORM_COLUMNS.iter().map(|_| String::new()))
+	}
+}
+
+fn write_row<W: Write>(writer: &mut W, row: &[String], delimiter: char) -> VMCResult<()> {
+	for (i, cell) in row.iter().enumerate() {
+		if i > 0 {
+			write!(writer, "{delimiter}")?;
+		}
+		// quote cells per RFC 4180 when necessary; only names can contain special characters
+		if cell.contains([delimiter, '"', '\n', '\r']) {
+			write!(writer, "\"{}\"", cell.replace('"', "\"\""))?;
+		} else {
+			writer.write_all(cell.as_bytes())?;
+		}
+	}
+	writer.write_all(b"\n")?;
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{
+		io::Cursor,
+		time::{Duration, Instant}
+	};
+
+	use super::*;
+	use crate::{
+		Quat, VMCApplyBlendShapes, VMCBlendShape, VMCBoneTransform, VMCStandardVRM0Bone, VMCTime, Vec3A,
+		record::{Metadata, Recorder}
+	};
+
+	#[test]
+	fn test_export_csv() -> VMCResult<()> {
+		let start = Instant::now();
+		let mut recorder = Recorder::new(Cursor::new(Vec::new()), Metadata::new())?;
+		recorder.record_at(VMCBoneTransform::new(VMCStandardVRM0Bone::Head, Vec3A::Y, Quat::IDENTITY).into(), start)?;
+		recorder.record_at(VMCTime::new(1.0).into(), start)?;
+		recorder.record_at(VMCBlendShape::new("a,b", 0.5).into(), start)?;
+		recorder.record_at(VMCApplyBlendShapes.into(), start)?;
+		recorder.record_at(VMCTime::new(2.0).into(), start + Duration::from_millis(500))?;
+		let mut reader = RecordingReader::new(recorder.finish()?)?;
+
+		let mut csv = Vec::new();
+		reader.export_csv(&mut csv)?;
+		let csv = String::from_utf8(csv).unwrap();
+		let lines: Vec<_> = csv.lines().collect();
+		assert_eq!(lines.len(), 3);
+		assert!(lines[0].starts_with("timestamp,time,Root.pos.x,"));
+		assert!(lines[0].contains(",Head.pos.x,Head.pos.y,"));
+		assert!(lines[0].ends_with(",Head.rot.w,\"blendshape.a,b\""));
+		assert!(lines[1].ends_with(",,,,,,,0,1,0,0,0,0,1,"));
+		assert_eq!(lines[2].split(',').nth(1), Some("2"));
+		assert!(lines[2].ends_with(",0,1,0,0,0,0,1,0.5"));
+		Ok(())
+	}
+}
@@ -0,0 +1,361 @@
+//! Recording of VMC motion data to files.
+//!
+//! A [`Recorder`] consumes messages (for example, from [`VMCSocket::messages`](crate::VMCSocket::messages)), groups
+//! them into frames terminated by [`Time`](crate::VMCTime) messages, timestamps each frame, and writes it to a file.
+//! Recordings can be read back with a [`RecordingReader`], or played back over the network with a [`Player`].
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+//! use futures_util::StreamExt;
+//! use vmc::record::{Metadata, Recorder};
+//!
+//! let mut socket = vmc::marionette!().await?;
+//! let mut messages = socket.messages();
+//! let mut recorder = Recorder::create("take1.vmcr", Metadata::new().with(Metadata::PERFORMER, "Alice"))?;
+//! while let Some(message) = messages.next().await {
+//! 	let (message, _) = message?;
+//! 	recorder.record(message)?;
+//! 	if recorder.frame_count() >= 60 * 30 {
+//! 		break;
+//! 	}
+//! }
+//! recorder.finish()?;
+//! # Ok(()) }) }
+//! ```
+//!
+//! # Format
+//!
+//! Recordings use a simple container format so that recordings made by different tools built on this crate are
+//! interchangeable. All integers are big-endian.
+//!
+//! The file begins with a header:
+//!
+//! | Size | Field |
+//! |------|-------|
+//! | 8 | Magic bytes `VMCREC\r\n` |
+//! | 2 | Format version, currently [`FORMAT_VERSION`] |
+//! | 2 | Flags; bit 0 is set if frame payloads are LZ4-compressed, bit 1 if frames use the delta codec (see [`Compression`]). Other bits are reserved and must be 0. |
+//! | 4 | Length of the metadata in bytes |
+//! | ... | [`Metadata`] entries, each a `u16` key length, the UTF-8 key, a `u32` value length, and the UTF-8 value |
+//!
+//! The header is followed by frames, one after the other:
+//!
+//! | Size | Field |
+//! |------|-------|
+//! | 8 | Timestamp in microseconds since the start of the recording |
+//! | 4 | Length of the payload in bytes |
+//! | ... | Payload: an OSC bundle containing the messages of the frame, ending with a `/VMC/Ext/T` message. If compressed, the bundle is stored as an LZ4 block prefixed by its uncompressed length as a little-endian `u32`. |
+//!
+//! If the delta codec is used, the timestamp & length are instead LEB128 varints: the timestamp is the difference in
+//! microseconds to the previous frame's timestamp shifted left by 1, with the lowest bit set for keyframes. The payload
+//! is the delta-encoded frame, which is always LZ4-compressed.
+//!
+//! A delta-encoded frame is a sequence of messages, each starting with a tag byte. Transforms and blend shapes refer to
+//! their bone, device, or blend shape by an ID assigned in order of first appearance; the first occurrence of an ID is
+//! followed by its name. Values are encoded relative to the previous value of the same channel as LEB128 varints: in
+//! lossless mode as the XOR of their IEEE 754 bits, and in lossy mode as the zigzag-encoded difference of their
+//! quantized values. Keyframes reset all IDs and previous values, so decoding can start at any keyframe, and begin with
+//! the codec parameters: the position precision as an `f32` (`0` if lossless), then the rotation and blend shape bits
+//! as `u8`s (`0` if lossless). Messages without a dedicated tag are stored as length-prefixed OSC messages.
+//!
+//! Frame timestamps never decrease. A finished recording ends with an index of all frames, allowing readers to seek
+//! without scanning the file:
+//!
+//! | Size | Field |
+//! |------|-------|
+//! | 16 × count | For each frame, the `u64` offset of the frame from the start of the file and its `u64` timestamp |
+//! | 8 | Offset of the index from the start of the file |
+//! | 8 | Frame count |
+//! | 8 | Magic bytes `VMCINDEX` |
+//!
+//! Readers should tolerate a missing index (e.g. if the recording application crashed) by scanning the frames, and
+//! must reject files with an unknown version or unknown flags.
+
+use std::{
+	collections::BTreeMap,
+	io::{self, Read, Write}
+};
+
+use crate::{VMCError, VMCResult, message::DeviceType};
+
+mod codec;
+mod edit;
+mod export;
+mod player;
+mod reader;
+mod recorder;
+
+pub use self::{
+	codec::DeltaConfig,
+	edit::concat,
+	player::{Player, PlayerHandle},
+	reader::{RecordedFrame, RecordingReader},
+	recorder::Recorder
+};
+
+/// Magic bytes at the start of every recording file.
+pub(crate) const MAGIC: &[u8; 8] = b"VMCREC\r\n";
+/// Magic bytes at the end of the footer of a finished recording.
+pub(crate) const INDEX_MAGIC: &[u8; 8] = b"VMCINDEX";
+/// The current version of the recording format.
+pub const FORMAT_VERSION: u16 = 1;
+
+/// Header flag set when frame payloads are LZ4-compressed.
+pub(crate) const FLAG_LZ4: u16 = 1 << 0;
+/// Header flag set when frames are encoded with the delta codec.
+pub(crate) const FLAG_DELTA: u16 = 1 << 1;
+
+/// Size of the frame header: `u64` timestamp and `u32` payload length.
+pub(crate) const FRAME_HEADER_SIZE: u64 = 12;
+/// Size of an index entry: `u64` offset and `u64` timestamp.
+pub(crate) const INDEX_ENTRY_SIZE: u64 = 16;
+/// Size of the footer: `u64` index offset, `u64` frame count, and magic.
+pub(crate) const FOOTER_SIZE: u64 = 24;
+
+/// How frame payloads are compressed in a recording.
+///
+/// LZ4 compression, which [`Compression::Lz4`] & [`Compression::Delta`] use, requires the `lz4` feature (enabled by
+/// default). Without it, recordings default to [`Compression::None`], and creating or reading a compressed recording
+/// fails with [`VMCError::InvalidRecording`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Compression {
+	/// Payloads are stored as plain OSC bundles.
+	#[cfg_attr(not(feature = "lz4"), default)]
+	None,
+	/// Payloads are compressed with [LZ4](https://github.com/lz4/lz4/blob/dev/doc/lz4_Block_format.md). OSC messages
+	/// repeat the same address strings every frame, so this shrinks recordings considerably at negligible CPU cost.
+	#[cfg_attr(feature = "lz4", default)]
+	Lz4,
+	/// Frames are delta-encoded against the previous frame before being compressed with LZ4.
+	///
+	/// Instead of storing OSC messages, each bone, device & blend shape is assigned an ID, and its values are stored as
+	/// the difference to its values in the previous frame, optionally quantized (see [`DeltaConfig`]). Since motion is
+	/// continuous, this shrinks recordings several times further than [`Compression::Lz4`] alone. Every so often, a
+	/// keyframe is written which can be decoded independently, so that readers can seek without decoding the whole
+	/// recording.
+	///
+	/// When reading a recording, the configuration reflects the quantization of the first keyframe; the keyframe
+	/// interval is not stored in the recording.
+	Delta(DeltaConfig)
+}
+
+impl Compression {
+	/// Fails if frames can't be compressed this way because the `lz4` feature is disabled.
+	pub(crate) fn check_supported(self) -> VMCResult<()> {
+		#[cfg(not(feature = "lz4"))]
+		if self != Compression::None {
+			return Err(VMCError::InvalidRecording("LZ4 compression requires the `lz4` feature".to_string()));
+		}
+		Ok(())
+	}
+
+	pub(crate) fn compress(self, payload: Vec<u8>) -> Vec<u8> {
+		match self {
+			Compression::None => payload,
+			#[cfg(feature = "lz4")]
+			Compression::Lz4 | Compression::Delta(_) => lz4_flex::compress_prepend_size(&payload),
+			// recordings with LZ4 compression are rejected by `check_supported` when they are created
+			#[cfg(not(feature = "lz4"))]
+			Compression::Lz4 | Compression::Delta(_) => unreachable!("LZ4 compression requires the `lz4` feature")
+		}
+	}
+
+	pub(crate) fn decompress(self, payload: Vec<u8>) -> VMCResult<Vec<u8>> {
+		match self {
+			Compression::None => Ok(payload),
+			#[cfg(not(feature = "lz4"))]
+			Compression::Lz4 | Compression::Delta(_) => self.check_supported().map(|_| payload),
+			#[cfg(feature = "lz4")]
+			Compression::Lz4 | Compression::Delta(_) => {
+				// LZ4 can't compress better than 255:1, so anything claiming more is corrupt; checking this up front
+				// avoids allocating a huge buffer for a bogus size prefix.
+				let max_len = payload.len().saturating_mul(255);
+				match lz4_flex::block::uncompressed_size(&payload) {
+					Ok((len, _)) if len <= max_len => {}
+					_ => return Err(VMCError::InvalidRecording("corrupt compressed frame".to_string()))
+				}
+				lz4_flex::decompress_size_prepended(&payload).map_err(|_| VMCError::InvalidRecording("corrupt compressed frame".to_string()))
+			}
+		}
+	}
+
+	fn flags(self) -> u16 {
+		match self {
+			Compression::None => 0,
+			Compression::Lz4 => FLAG_LZ4,
+			Compression::Delta(_) => FLAG_LZ4 | FLAG_DELTA
+		}
+	}
+
+	pub(crate) fn is_delta(self) -> bool {
+		matches!(self, Compression::Delta(_))
+	}
+}
+
+/// Free-form key/value metadata stored in the header of a recording.
+///
+/// Any key may be used, but the well-known keys defined as associated constants should be preferred where they apply
+/// so that other tools can make sense of the metadata. Devices tracked in the recording are stored under keys of the
+/// form `device.<serial>`, with the [`DeviceType`] as the value; see [`Metadata::with_device`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+	fields: BTreeMap<String, String>
+}
+
+impl Metadata {
+	/// The name of the avatar being recorded.
+	pub const AVATAR: &'static str = "avatar";
+	/// The VRM version of the avatar being recorded, either `0` or `1`. Recordings of VRM 0.x avatars can be converted
+	/// with [`retarget`](crate::retarget).
+	pub const AVATAR_VRM_VERSION: &'static str = "avatar.vrm";
+	/// The name of the performer being recorded.
+	pub const PERFORMER: &'static str = "performer";
+	/// The application that sent the recorded data, e.g. `VirtualMotionCapture`.
+	pub const SOURCE: &'static str = "source";
+	/// The application that created the recording.
+	pub const RECORDER: &'static str = "recorder";
+
+	/// Creates empty metadata.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Adds a field to the metadata.
+	pub fn with(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+		self.insert(key, value);
+		self
+	}
+
+	/// Inserts a field into the metadata, returning the previous value of the field, if any.
+	pub fn insert(&mut self, key: impl Into<String>, value: impl Into<String>) -> Option<String> {
+		self.fields.insert(key.into(), value.into())
+	}
+
+	/// Returns the value of a field.
+	pub fn get(&self, key: &str) -> Option<&str> {
+		self.fields.get(key).map(String::as_str)
+	}
+
+	/// Removes a field from the metadata, returning its value, if any.
+	pub fn remove(&mut self, key: &str) -> Option<String> {
+		self.fields.remove(key)
+	}
+
+	/// Sets the [name of the avatar](Metadata::AVATAR).
+	pub fn with_avatar(self, name: impl Into<String>) -> Self {
+		self.with(Self::AVATAR, name)
+	}
+
+	/// Returns the [name of the avatar](Metadata::AVATAR).
+	pub fn avatar(&self) -> Option<&str> {
+		self.get(Self::AVATAR)
+	}
+
+	/// Adds a device tracked in the recording.
+	pub fn with_device(self, device: DeviceType, serial: impl AsRef<str>) -> Self {
+		self.with(format!("device.{}", serial.as_ref()), device.to_string())
+	}
+
+	/// Returns an iterator over the devices tracked in the recording and their serials.
+	pub fn devices(&self) -> impl Iterator<Item = (DeviceType, &str)> {
+		self.iter().filter_map(|(key, value)| {
+			let serial = key.strip_prefix("device.")?;
+			Some((value.parse().ok()?, serial))
+		})
+	}
+
+	/// Returns an iterator over all fields.
+	pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+		self.fields.iter().map(|(key, value)| (key.as_str(), value.as_str()))
+	}
+
+	pub(crate) fn encode(&self) -> VMCResult<Vec<u8>> {
+		let mut buf = Vec::new();
+		for (key, value) in &self.fields {
+			let key_len = u16::try_from(key.len()).map_err(|_| VMCError::InvalidRecording(format!("metadata key too long: {key}")))?;
+			let value_len = u32::try_from(value.len()).map_err(|_| VMCError::InvalidRecording(format!("metadata value too long for key {key}")))?;
+			buf.extend_from_slice(&key_len.to_be_bytes());
+			buf.extend_from_slice(key.as_bytes());
+			buf.extend_from_slice(&value_len.to_be_bytes());
+			buf.extend_from_slice(value.as_bytes());
+		}
+		Ok(buf)
+	}
+
+	pub(crate) fn decode(mut buf: &[u8]) -> VMCResult<Self> {
+		fn take<'a>(buf: &mut &'a [u8], len: usize) -> VMCResult<&'a [u8]> {
+			if buf.len() < len {
+				return Err(VMCError::InvalidRecording("truncated metadata".to_string()));
+			}
+			let (head, tail) = buf.split_at(len);
+			*buf = tail;
+			Ok(head)
+		}
+		fn string(bytes: &[u8]) -> VMCResult<String> {
+			String::from_utf8(bytes.to_vec()).map_err(|_| VMCError::InvalidRecording("metadata is not valid UTF-8".to_string()))
+		}
+
+		let mut metadata = Metadata::new();
+		while !buf.is_empty() {
+			let key_len = u16::from_be_bytes(take(&mut buf, 2)?.try_into().unwrap()) as usize;
+			let key = string(take(&mut buf, key_len)?)?;
+			let value_len = u32::from_be_bytes(take(&mut buf, 4)?.try_into().unwrap()) as usize;
+			let value = string(take(&mut buf, value_len)?)?;
+			metadata.fields.insert(key, value);
+		}
+		Ok(metadata)
+	}
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for Metadata {
+	fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+		Self {
+			fields: iter.into_iter().map(|(key, value)| (key.into(), value.into())).collect()
+		}
+	}
+}
+
+pub(crate) fn write_header<W: Write>(writer: &mut W, metadata: &Metadata, compression: Compression) -> VMCResult<u64> {
+	compression.check_supported()?;
+	let metadata = metadata.encode()?;
+	let metadata_len = u32::try_from(metadata.len()).map_err(|_| VMCError::InvalidRecording("metadata too large".to_string()))?;
+	writer.write_all(MAGIC)?;
+	writer.write_all(&FORMAT_VERSION.to_be_bytes())?;
+	writer.write_all(&compression.flags().to_be_bytes())?;
+	writer.write_all(&metadata_len.to_be_bytes())?;
+	writer.write_all(&metadata)?;
+	Ok(MAGIC.len() as u64 + 8 + metadata.len() as u64)
+}
+
+pub(crate) fn read_header<R: Read>(reader: &mut R) -> VMCResult<(Metadata, Compression, u64)> {
+	let mut header = [0u8; 16];
+	reader.read_exact(&mut header).map_err(map_eof)?;
+	if &header[..8] != MAGIC {
+		return Err(VMCError::InvalidRecording("not a VMC recording".to_string()));
+	}
+	let version = u16::from_be_bytes([header[8], header[9]]);
+	if version != FORMAT_VERSION {
+		return Err(VMCError::InvalidRecording(format!("unsupported format version {version}")));
+	}
+	let compression = match u16::from_be_bytes([header[10], header[11]]) {
+		0 => Compression::None,
+		FLAG_LZ4 => Compression::Lz4,
+		flags if flags == FLAG_LZ4 | FLAG_DELTA => Compression::Delta(DeltaConfig::default()),
+		flags => return Err(VMCError::InvalidRecording(format!("unsupported flags {flags:#06x}")))
+	};
+	compression.check_supported()?;
+	let metadata_len = u32::from_be_bytes(header[12..16].try_into().unwrap()) as usize;
+	let mut metadata = vec![0; metadata_len];
+	reader.read_exact(&mut metadata).map_err(map_eof)?;
+	Ok((Metadata::decode(&metadata)?, compression, 16 + metadata_len as u64))
+}
+
+pub(crate) fn map_eof(err: io::Error) -> VMCError {
+	if err.kind() == io::ErrorKind::UnexpectedEof {
+		VMCError::InvalidRecording("unexpected end of file".to_string())
+	} else {
+		err.into()
+	}
+}
@@ -0,0 +1,330 @@
+use std::{
+	future::{Future, poll_fn},
+	io::{Read, Seek},
+	pin::pin,
+	task::Poll,
+	time::{Duration, Instant}
+};
+
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+
+use super::RecordingReader;
+use crate::{VMCResult, VMCSender, osc, pose::pack_bundles};
+
+#[derive(Debug, Clone, Copy)]
+enum Command {
+	Pause,
+	Resume,
+	Seek(Duration),
+	SetSpeed(f64),
+	SetLooping(bool)
+}
+
+/// Plays back a recording through a [`VMCSender`], reproducing the timing between frames.
+///
+/// Each frame is scheduled relative to a fixed reference point rather than the previous frame, so timer inaccuracies
+/// do not accumulate over the course of a long recording. If sending falls behind schedule, late frames are sent
+/// immediately until playback catches up.
+///
+/// Playback can be controlled directly through the player's methods, or from another task through a
+/// [`PlayerHandle`].
+///
+/// # Examples
+///
+/// ```no_run
+/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+/// use vmc::record::{Player, RecordingReader};
+///
+/// let socket = vmc::performer!("127.0.0.1:39539").await?;
+/// let mut player = Player::new(RecordingReader::open("take1.vmcr")?, socket.sender());
+/// player.set_speed(0.5);
+/// player.set_looping(true);
+/// player.play().await?;
+/// # Ok(()) }) }
+/// ```
+#[derive(Debug)]
+pub struct Player<R> {
+	reader: RecordingReader<R>,
+	sender: VMCSender,
+	commands_tx: UnboundedSender<Command>,
+	commands_rx: UnboundedReceiver<Command>,
+	speed: f64,
+	looping: bool,
+	paused: bool,
+	/// Recording time at `anchor`.
+	base: Duration,
+	/// The instant at which playback was at `base`, or `None` if the clock is stopped.
+	anchor: Option<Instant>
+}
+
+impl<R: Read + Seek> Player<R> {
+	/// Creates a new player which sends the frames read from `reader` through `sender`.
+	pub fn new(reader: RecordingReader<R>, sender: VMCSender) -> Self {
+		let (commands_tx, commands_rx) = unbounded_channel();
+		let base = reader.timestamp(reader.position()).unwrap_or_default();
+		Self {
+			reader,
+			sender,
+			commands_tx,
+			commands_rx,
+			speed: 1.0,
+			looping: false,
+			paused: false,
+			base,
+			anchor: None
+		}
+	}
+
+	/// Returns a handle which can be used to control playback from another task.
+	pub fn handle(&self) -> PlayerHandle {
+		PlayerHandle { commands: self.commands_tx.clone() }
+	}
+
+	/// Get a reference to the underlying [`RecordingReader`].
+	pub fn reader(&self) -> &RecordingReader<R> {
+		&self.reader
+	}
+
+	/// Get a reference to the [`VMCSender`] frames are sent through.
+	pub fn sender(&self) -> &VMCSender {
+		&self.sender
+	}
+
+	/// Consumes the player, returning the underlying reader and sender.
+	pub fn into_inner(self) -> (RecordingReader<R>, VMCSender) {
+		(self.reader, self.sender)
+	}
+
+	/// Pauses playback.
+	pub fn pause(&mut self) {
+		self.base = self.position();
+		self.anchor = None;
+		self.paused = true;
+	}
+
+	/// Resumes paused playback.
+	pub fn resume(&mut self) {
+		self.paused = false;
+	}
+
+	/// Returns `true` if playback is paused.
+	pub fn is_paused(&self) -> bool {
+		self.paused
+	}
+
+	/// Jumps to the given time in the recording, returning the index of the frame that is played next: the first frame
+	/// at or after `timestamp`.
+	///
+	/// Seeking uses the recording's frame index, so it is cheap even for long recordings; delta-encoded recordings
+	/// additionally decode from the nearest preceding keyframe when the next frame is read.
+	pub fn seek(&mut self, timestamp: Duration) -> usize {
+		let index = self.reader.seek(timestamp);
+		self.base = timestamp;
+		if self.anchor.is_some() {
+			self.anchor = Some(Instant::now());
+		}
+		index
+	}
+
+	/// Sets the playback speed multiplier, e.g. `2.0` to play at double speed.
+	///
+	/// # Panics
+	///
+	/// Panics if `speed` is not a positive, finite number.
+	pub fn set_speed(&mut self, speed: f64) {
+		assert!(speed.is_finite() && speed > 0.0, "playback speed must be positive and finite");
+		self.base = self.position();
+		if self.anchor.is_some() {
+			self.anchor = Some(Instant::now());
+		}
+		self.speed = speed;
+	}
+
+	/// Returns the playback speed multiplier.
+	pub fn speed(&self) -> f64 {
+		self.speed
+	}
+
+	/// Sets whether playback should restart from the beginning once the end of the recording is reached.
+	pub fn set_looping(&mut self, looping: bool) {
+		self.looping = looping;
+	}
+
+	/// Returns `true` if playback loops.
+	pub fn is_looping(&self) -> bool {
+		self.looping
+	}
+
+	/// Returns the current playback time within the recording.
+	pub fn position(&self) -> Duration {
+		match self.anchor {
+			Some(anchor) => self.base + anchor.elapsed().mul_f64(self.speed),
+			None => self.base
+		}
+	}
+
+	fn apply(&mut self, command: Command) {
+		match command {
+			Command::Pause => self.pause(),
+			Command::Resume => self.resume(),
+			Command::Seek(timestamp) => {
+				self.seek(timestamp);
+			}
+			Command::SetSpeed(speed) => self.set_speed(speed),
+			Command::SetLooping(looping) => self.set_looping(looping)
+		}
+	}
+
+	/// Waits until the next frame is due and sends it, returning its timestamp.
+	///
+	/// Returns `None` once the end of the recording is reached, unless playback is looping. While playback is paused,
+	/// this waits until it is resumed through a [`PlayerHandle`].
+	pub async fn next_frame(&mut self) -> VMCResult<Option<Duration>> {
+		loop {
+			while let Ok(command) = self.commands_rx.try_recv() {
+				self.apply(command);
+			}
+			if self.paused {
+				// we hold a sender ourselves, so the channel can never be closed
+				if let Some(command) = self.commands_rx.recv().await {
+					self.apply(command);
+				}
+				continue;
+			}
+
+			let Some(timestamp) = self.reader.timestamp(self.reader.position()) else {
+				if self.looping && !self.reader.is_empty() {
+					self.reader.set_position(0);
+					self.base = self.reader.timestamp(0).unwrap_or_default();
+					self.anchor = Some(Instant::now());
+					continue;
+				}
+				self.base = self.reader.duration();
+				self.anchor = None;
+				return Ok(None);
+			};
+
+			let now = Instant::now();
+			let anchor = *self.anchor.get_or_insert(now);
+			let deadline = anchor + timestamp.saturating_sub(self.base).div_f64(self.speed);
+			if deadline > now {
+				if let Some(command) = wait_until(&mut self.commands_rx, deadline).await {
+					self.apply(command);
+					continue;
+				}
+			}
+
+			let Some(frame) = self.reader.next() else {
+				continue;
+			};
+			for packet in pack_bundles(frame?.packet.into_messages(), osc::MAX_UDP_PAYLOAD) {
+				self.sender.send(packet).await?;
+			}
+			return Ok(Some(timestamp));
+		}
+	}
+
+	/// Plays the recording until the end is reached. If playback is looping, this never returns unless an error occurs.
+	pub async fn play(&mut self) -> VMCResult<()> {
+		while self.next_frame().await?.is_some() {}
+		Ok(())
+	}
+}
+
+/// Waits until `deadline`, returning early if a command is received.
+async fn wait_until(commands: &mut UnboundedReceiver<Command>, deadline: Instant) -> Option<Command> {
+	let mut sleep = pin!(tokio::time::sleep_until(deadline.into()));
+	poll_fn(|cx| {
+		if let Poll::Ready(command) = commands.poll_recv(cx) {
+			return Poll::Ready(command);
+		}
+		sleep.as_mut().poll(cx).map(|_| None)
+	})
+	.await
+}
+
+/// Controls a [`Player`] from another task.
+///
+/// Commands sent through a handle are applied by the player the next time it waits for a frame. Commands sent after
+/// the player has been dropped have no effect.
+#[derive(Debug, Clone)]
+pub struct PlayerHandle {
+	commands: UnboundedSender<Command>
+}
+
+impl PlayerHandle {
+	/// Pauses playback. See [`Player::pause`].
+	pub fn pause(&self) {
+		let _ = self.commands.send(Command::Pause);
+	}
+
+	/// Resumes paused playback. See [`Player::resume`].
+	pub fn resume(&self) {
+		let _ = self.commands.send(Command::Resume);
+	}
+
+	/// Jumps to the given time in the recording. See [`Player::seek`].
+	pub fn seek(&self, timestamp: Duration) {
+		let _ = self.commands.send(Command::Seek(timestamp));
+	}
+
+	/// Sets the playback speed multiplier. See [`Player::set_speed`].
+	///
+	/// # Panics
+	///
+	/// Panics if `speed` is not a positive, finite number.
+	pub fn set_speed(&self, speed: f64) {
+		assert!(speed.is_finite() && speed > 0.0, "playback speed must be positive and finite");
+		let _ = self.commands.send(Command::SetSpeed(speed));
+	}
+
+	/// Sets whether playback loops. See [`Player::set_looping`].
+	pub fn set_looping(&self, looping: bool) {
+		let _ = self.commands.send(Command::SetLooping(looping));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+
+	use super::*;
+	use crate::{
+		VMCBlendShape, VMCMessage, VMCSocket, VMCTime,
+		record::{Metadata, Recorder}
+	};
+
+	#[tokio::test]
+	async fn test_player_timing() -> VMCResult<()> {
+		let start = Instant::now();
+		let mut recorder = Recorder::new(Cursor::new(Vec::new()), Metadata::new())?;
+		for i in 0..5 {
+			let at = start + Duration::from_millis(i * 20);
+			recorder.record_at(VMCBlendShape::new("Custom", i as f32).into(), at)?;
+			recorder.record_at(VMCTime::new(i as f32).into(), at)?;
+		}
+		let reader = RecordingReader::new(recorder.finish()?)?;
+
+		let mut marionette = VMCSocket::bind("127.0.0.1:0").await?;
+		let performer = VMCSocket::bind("127.0.0.1:0").await?;
+		performer.connect(marionette.local_addr()?).await?;
+
+		let mut player = Player::new(reader, performer.sender());
+		player.set_speed(2.0);
+		let played = Instant::now();
+		player.play().await?;
+		// 80ms of recording at double speed
+		assert!(played.elapsed() >= Duration::from_millis(35));
+
+		for i in 0..5 {
+			let messages = marionette.recv_message().await?;
+			assert!(matches!(&messages[..], [VMCMessage::BlendShape(blendshape), VMCMessage::Time(_)] if blendshape.value == i as f32));
+		}
+
+		assert_eq!(player.seek(Duration::from_millis(50)), 3);
+		assert_eq!(player.next_frame().await?, player.reader().timestamp(3));
+		let messages = marionette.recv_message().await?;
+		assert!(matches!(&messages[..], [VMCMessage::BlendShape(blendshape), VMCMessage::Time(_)] if blendshape.value == 3.0));
+		Ok(())
+	}
+}
@@ -0,0 +1,348 @@
+use std::{
+	fs::File,
+	io::{BufReader, Read, Seek, SeekFrom},
+	path::Path,
+	time::Duration
+};
+
+use super::{
+	Compression, FOOTER_SIZE, FRAME_HEADER_SIZE, INDEX_ENTRY_SIZE, INDEX_MAGIC, Metadata,
+	codec::{DeltaDecoder, read_varint_from},
+	map_eof, read_header
+};
+use crate::{
+	IntoOSCMessage, OSCPacket, VMCError, VMCFrame, VMCMessage, VMCResult, VMCTime,
+	osc::{self, OSCBundle, OSCTime}
+};
+
+/// A single frame read from a recording.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedFrame {
+	/// The time at which the frame was recorded, relative to the start of the recording.
+	pub timestamp: Duration,
+	/// The frame as a bundle of OSC messages, ending with a [`Time`](crate::VMCTime) message.
+	pub packet: OSCPacket
+}
+
+impl RecordedFrame {
+	/// [Parses](crate::parse) the messages contained in this frame.
+	pub fn messages(&self) -> VMCResult<Vec<VMCMessage>> {
+		crate::parse(self.packet.clone())
+	}
+
+	/// Parses this frame into a [`VMCFrame`].
+	///
+	/// If the frame does not end with a [`Time`](crate::VMCTime) message, the frame's timestamp in seconds is used.
+	pub fn to_frame(&self) -> VMCResult<VMCFrame> {
+		let mut messages = self.messages()?;
+		let time = match messages.pop() {
+			Some(VMCMessage::Time(time)) => time,
+			other => {
+				messages.extend(other);
+				VMCTime::new(self.timestamp.as_secs_f32())
+			}
+		};
+		Ok(VMCFrame { time, messages })
+	}
+}
+
+/// Reads frames from a recording created by a [`Recorder`](super::Recorder).
+///
+/// Frames can be read sequentially by using the reader as an [`Iterator`], or randomly via [`RecordingReader::frame`]
+/// and [`RecordingReader::seek`].
+#[derive(Debug)]
+pub struct RecordingReader<R> {
+	reader: R,
+	metadata: Metadata,
+	compression: Compression,
+	index: Vec<(u64, Duration)>,
+	cursor: usize,
+	/// For delta-encoded recordings, the decoder state after the last frame read, and that frame's index.
+	decoder: Option<(usize, DeltaDecoder)>,
+	/// Indices of the keyframes found so far in a delta-encoded recording, sorted.
+	keyframes: Vec<usize>
+}
+
+impl RecordingReader<BufReader<File>> {
+	/// Opens the recording at the given path.
+	pub fn open(path: impl AsRef<Path>) -> VMCResult<Self> {
+		Self::new(BufReader::new(File::open(path)?))
+	}
+}
+
+impl<R: Read + Seek> RecordingReader<R> {
+	/// Reads the header and frame index of a recording.
+	///
+	/// If the recording was not [finished](super::Recorder::finish), the index is rebuilt by scanning the file; a
+	/// truncated frame at the end of the file is ignored.
+	pub fn new(mut reader: R) -> VMCResult<Self> {
+		reader.rewind()?;
+		let (metadata, compression, data_offset) = read_header(&mut reader)?;
+		let index = match Self::read_index(&mut reader, data_offset, compression)? {
+			Some(index) => index,
+			None => Self::scan_index(&mut reader, data_offset, compression)?
+		};
+		let mut reader = Self {
+			reader,
+			metadata,
+			compression,
+			index,
+			cursor: 0,
+			decoder: None,
+			keyframes: Vec::new()
+		};
+		if reader.compression.is_delta() && !reader.is_empty() {
+			reader.read_frame(0)?;
+			let (_, decoder) = reader.decoder.as_ref().unwrap();
+			reader.compression = Compression::Delta(decoder.config());
+		}
+		Ok(reader)
+	}
+
+	/// Reads the header of the frame at `offset`, returning its timestamp field, payload length, and header size.
+	fn read_frame_header(reader: &mut R, offset: u64, compression: Compression) -> VMCResult<(u64, u64, u64)> {
+		reader.seek(SeekFrom::Start(offset))?;
+		if compression.is_delta() {
+			let (timestamp, timestamp_size) = read_varint_from(reader)?;
+			let (len, len_size) = read_varint_from(reader)?;
+			if len > u32::MAX as u64 {
+				return Err(VMCError::InvalidRecording("frame too large".to_string()));
+			}
+			Ok((timestamp, len, timestamp_size + len_size))
+		} else {
+			let mut header = [0u8; FRAME_HEADER_SIZE as usize];
+			reader.read_exact(&mut header).map_err(map_eof)?;
+			let timestamp = u64::from_be_bytes(header[..8].try_into().unwrap());
+			let len = u32::from_be_bytes(header[8..].try_into().unwrap()) as u64;
+			Ok((timestamp, len, FRAME_HEADER_SIZE))
+		}
+	}
+
+	fn read_index(reader: &mut R, data_offset: u64, compression: Compression) -> VMCResult<Option<Vec<(u64, Duration)>>> {
+		let end = reader.seek(SeekFrom::End(0))?;
+		if end < data_offset + FOOTER_SIZE {
+			return Ok(None);
+		}
+		reader.seek(SeekFrom::Start(end - FOOTER_SIZE))?;
+		let mut footer = [0u8; FOOTER_SIZE as usize];
+		reader.read_exact(&mut footer)?;
+		if &footer[16..] != INDEX_MAGIC {
+			return Ok(None);
+		}
+		let index_offset = u64::from_be_bytes(footer[..8].try_into().unwrap());
+		let frame_count = u64::from_be_bytes(footer[8..16].try_into().unwrap());
+		if index_offset < data_offset || frame_count.checked_mul(INDEX_ENTRY_SIZE).and_then(|len| len.checked_add(index_offset)) != Some(end - FOOTER_SIZE) {
+			return Err(VMCError::InvalidRecording("corrupt frame index".to_string()));
+		}
+
+		reader.seek(SeekFrom::Start(index_offset))?;
+		let mut entries = vec![0u8; (frame_count * INDEX_ENTRY_SIZE) as usize];
+		reader.read_exact(&mut entries)?;
+		let index = entries
+			.chunks_exact(INDEX_ENTRY_SIZE as usize)
+			.map(|entry| {
+				let offset = u64::from_be_bytes(entry[..8].try_into().unwrap());
+				let timestamp = u64::from_be_bytes(entry[8..].try_into().unwrap());
+				(offset, Duration::from_micros(timestamp))
+			})
+			.collect::<Vec<_>>();
+		// delta-encoded frame headers are at least 2 bytes long
+		let min_header_size = if compression.is_delta() { 2 } else { FRAME_HEADER_SIZE };
+		if index
+			.iter()
+			.any(|(offset, _)| *offset < data_offset || *offset + min_header_size > index_offset)
+		{
+			return Err(VMCError::InvalidRecording("corrupt frame index".to_string()));
+		}
+		Ok(Some(index))
+	}
+
+	fn scan_index(reader: &mut R, data_offset: u64, compression: Compression) -> VMCResult<Vec<(u64, Duration)>> {
+		let end = reader.seek(SeekFrom::End(0))?;
+		let mut index = Vec::new();
+		let mut offset = data_offset;
+		let mut timestamp = 0;
+		while offset < end {
+			let (timestamp_field, len, header_size) = match Self::read_frame_header(reader, offset, compression) {
+				Ok(header) => header,
+				// a truncated header is the end of an unfinished recording
+				Err(VMCError::InvalidRecording(_)) => break,
+				Err(e) => return Err(e)
+			};
+			if offset + header_size + len > end {
+				break;
+			}
+			timestamp = if compression.is_delta() { timestamp + (timestamp_field >> 1) } else { timestamp_field };
+			index.push((offset, Duration::from_micros(timestamp)));
+			offset += header_size + len;
+		}
+		Ok(index)
+	}
+
+	/// Returns the metadata stored in the header of the recording.
+	pub fn metadata(&self) -> &Metadata {
+		&self.metadata
+	}
+
+	/// Returns the [`Compression`] used for frames in the recording.
+	pub fn compression(&self) -> Compression {
+		self.compression
+	}
+
+	/// Returns the number of frames in the recording.
+	pub fn len(&self) -> usize {
+		self.index.len()
+	}
+
+	/// Returns `true` if the recording contains no frames.
+	pub fn is_empty(&self) -> bool {
+		self.index.is_empty()
+	}
+
+	/// Returns the timestamp of the last frame in the recording.
+	pub fn duration(&self) -> Duration {
+		self.index.last().map_or(Duration::ZERO, |(_, timestamp)| *timestamp)
+	}
+
+	/// Returns the timestamp of the frame at `index`, without reading the frame itself.
+	pub fn timestamp(&self, index: usize) -> Option<Duration> {
+		self.index.get(index).map(|(_, timestamp)| *timestamp)
+	}
+
+	/// Reads the frame at `index`. Returns `None` if `index` is out of bounds.
+	///
+	/// For recordings using [`Compression::Delta`], reading frames in order is fastest; reading any other frame has to
+	/// decode all frames since the preceding keyframe.
+	pub fn frame(&mut self, index: usize) -> Option<VMCResult<RecordedFrame>> {
+		if index >= self.index.len() {
+			return None;
+		}
+		Some(self.read_frame(index))
+	}
+
+	fn read_frame(&mut self, index: usize) -> VMCResult<RecordedFrame> {
+		let timestamp = self.index[index].1;
+		if !self.compression.is_delta() {
+			let (_, payload) = self.read_payload(index)?;
+			let (_, packet) = osc::decode_udp(&payload)?;
+			return Ok(RecordedFrame { timestamp, packet });
+		}
+
+		let (start, mut decoder) = match self.decoder.take() {
+			Some((last, decoder)) if last + 1 == index => (index, decoder),
+			_ => (self.find_keyframe(index)?, DeltaDecoder::default())
+		};
+		let mut messages = Vec::new();
+		for i in start..=index {
+			let (keyframe, payload) = self.read_payload(i)?;
+			messages = decoder.decode(&payload, keyframe)?;
+		}
+		self.decoder = Some((index, decoder));
+		let packet = OSCPacket::Bundle(OSCBundle {
+			timetag: OSCTime::from((0, 1)),
+			content: messages
+				.into_iter()
+				.map(|message| OSCPacket::Message(message.into_osc_message()))
+				.collect()
+		});
+		Ok(RecordedFrame { timestamp, packet })
+	}
+
+	/// Finds the last keyframe at or before the frame at `index` in a delta-encoded recording.
+	///
+	/// Keyframes are remembered once found, so scrubbing back and forth only has to read each frame header once.
+	fn find_keyframe(&mut self, index: usize) -> VMCResult<usize> {
+		let known = self.keyframes.partition_point(|keyframe| *keyframe <= index);
+		let floor = known.checked_sub(1).map(|i| self.keyframes[i]);
+		if floor == Some(index) {
+			return Ok(index);
+		}
+		for i in (floor.map_or(0, |floor| floor + 1)..=index).rev() {
+			let (timestamp_field, ..) = Self::read_frame_header(&mut self.reader, self.index[i].0, self.compression)?;
+			if timestamp_field & 1 != 0 {
+				self.keyframes.insert(known, i);
+				return Ok(i);
+			}
+		}
+		floor.ok_or_else(|| VMCError::InvalidRecording("recording does not start with a keyframe".to_string()))
+	}
+
+	/// Reads & decompresses the payload of the frame at `index`, also returning whether it is a keyframe.
+	fn read_payload(&mut self, index: usize) -> VMCResult<(bool, Vec<u8>)> {
+		let (timestamp_field, len, _) = Self::read_frame_header(&mut self.reader, self.index[index].0, self.compression)?;
+		let mut payload = vec![0; len as usize];
+		self.reader.read_exact(&mut payload).map_err(map_eof)?;
+		let payload = self.compression.decompress(payload)?;
+		Ok((self.compression.is_delta() && timestamp_field & 1 != 0, payload))
+	}
+
+	/// Returns the index of the frame that will be returned next when iterating.
+	pub fn position(&self) -> usize {
+		self.cursor
+	}
+
+	/// Moves the cursor so that the next frame returned when iterating is the frame at `index`.
+	pub fn set_position(&mut self, index: usize) {
+		self.cursor = index.min(self.index.len());
+	}
+
+	/// Moves the cursor to the first frame recorded at or after `timestamp`, returning its index.
+	pub fn seek(&mut self, timestamp: Duration) -> usize {
+		self.cursor = self.index.partition_point(|(_, t)| *t < timestamp);
+		self.cursor
+	}
+
+	/// Consumes the reader, returning the underlying reader.
+	pub fn into_inner(self) -> R {
+		self.reader
+	}
+}
+
+impl<R: Read + Seek> Iterator for RecordingReader<R> {
+	type Item = VMCResult<RecordedFrame>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let frame = self.frame(self.cursor)?;
+		self.cursor += 1;
+		Some(frame)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{io::Cursor, time::Instant};
+
+	use super::*;
+	use crate::{
+		VMCBlendShape, VMCDeviceType, VMCTime,
+		record::{Metadata, Recorder}
+	};
+
+	#[test]
+	fn test_record_roundtrip() -> VMCResult<()> {
+		let start = Instant::now();
+		let metadata = Metadata::new().with_avatar("test").with_device(VMCDeviceType::Tracker, "LHR-0001");
+		let mut recorder = Recorder::new(Cursor::new(Vec::new()), metadata.clone())?;
+		for i in 0..10 {
+			let at = start + Duration::from_millis(i * 10);
+			recorder.record_at(VMCBlendShape::new("Custom", i as f32).into(), at)?;
+			recorder.record_at(VMCTime::new(i as f32).into(), at)?;
+		}
+		let bytes = recorder.finish()?.into_inner();
+
+		let mut reader = RecordingReader::new(Cursor::new(bytes.clone()))?;
+		assert_eq!(reader.metadata(), &metadata);
+		assert_eq!(reader.metadata().devices().collect::<Vec<_>>(), [(VMCDeviceType::Tracker, "LHR-0001")]);
+		assert_eq!(reader.compression(), Compression::default());
+		assert_eq!(reader.len(), 10);
+		assert_eq!(reader.seek(Duration::from_millis(45)), 5);
+		let frame = reader.next().unwrap()?;
+		assert!(matches!(&frame.messages()?[..], [VMCMessage::BlendShape(blendshape), VMCMessage::Time(_)] if blendshape.value == 5.0));
+
+		// an unfinished recording should still be readable by scanning
+		let truncated = bytes[..bytes.len() - FOOTER_SIZE as usize - 10 * INDEX_ENTRY_SIZE as usize - 3].to_vec();
+		let reader = RecordingReader::new(Cursor::new(truncated))?;
+		assert_eq!(reader.len(), 9);
+		Ok(())
+	}
+}
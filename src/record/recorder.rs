@@ -0,0 +1,249 @@
+use std::{
+	fs::File,
+	io::{BufWriter, Write},
+	path::Path,
+	time::{Duration, Instant}
+};
+
+use super::{
+	Compression, FOOTER_SIZE, FRAME_HEADER_SIZE, INDEX_ENTRY_SIZE, INDEX_MAGIC, Metadata,
+	codec::{DeltaEncoder, write_varint},
+	write_header
+};
+use crate::{
+	IntoOSCMessage, OSCPacket, VMCError, VMCFrame, VMCMessage, VMCResult, VMCTime,
+	osc::{self, OSCBundle, OSCTime}
+};
+
+/// Writes VMC motion data to a recording.
+///
+/// Messages passed to [`Recorder::record`] are buffered until a [`Time`](crate::VMCTime) message completes the frame,
+/// at which point the frame is timestamped relative to the start of the recording and written out. Time spent
+/// [paused](Recorder::pause) is excluded from timestamps, so playback skips over pauses.
+///
+/// The frame index is only written by [`Recorder::finish`]. If a recorder is dropped without being finished, the
+/// recording is still readable, but [`RecordingReader`](super::RecordingReader) has to scan the whole file to rebuild
+/// the index when opening it.
+#[derive(Debug)]
+pub struct Recorder<W: Write> {
+	writer: W,
+	position: u64,
+	index: Vec<(u64, u64)>,
+	pending: Vec<VMCMessage>,
+	started: Instant,
+	paused_at: Option<Instant>,
+	paused_for: Duration,
+	max_size: Option<u64>,
+	compression: Compression,
+	encoder: Option<DeltaEncoder>
+}
+
+impl Recorder<BufWriter<File>> {
+	/// Creates a new recording file at the given path, replacing it if it already exists.
+	pub fn create(path: impl AsRef<Path>, metadata: Metadata) -> VMCResult<Self> {
+		Self::new(BufWriter::new(File::create(path)?), metadata)
+	}
+}
+
+impl<W: Write> Recorder<W> {
+	/// Starts a new recording, writing the header with the given metadata to `writer`.
+	///
+	/// Frames are compressed with the default [`Compression`].
+	pub fn new(writer: W, metadata: Metadata) -> VMCResult<Self> {
+		Self::with_compression(writer, metadata, Compression::default())
+	}
+
+	/// Starts a new recording with the given [`Compression`], writing the header with the given metadata to `writer`.
+	pub fn with_compression(mut writer: W, metadata: Metadata, compression: Compression) -> VMCResult<Self> {
+		let position = write_header(&mut writer, &metadata, compression)?;
+		Ok(Self {
+			writer,
+			position,
+			index: Vec::new(),
+			pending: Vec::new(),
+			started: Instant::now(),
+			paused_at: None,
+			paused_for: Duration::ZERO,
+			max_size: None,
+			compression,
+			encoder: match compression {
+				Compression::Delta(config) => Some(DeltaEncoder::new(config)),
+				_ => None
+			}
+		})
+	}
+
+	/// Limits the total size of the recording in bytes, including the index written by [`Recorder::finish`].
+	///
+	/// Once the limit is reached, recording a frame fails with [`VMCError::RecordingSizeLimit`] and the frame is
+	/// discarded; the recording itself remains valid and can still be finished.
+	pub fn set_max_size(&mut self, max_size: Option<u64>) {
+		self.max_size = max_size;
+	}
+
+	/// Returns the [`Compression`] used for frames in this recording.
+	pub fn compression(&self) -> Compression {
+		self.compression
+	}
+
+	/// Returns the size limit set by [`Recorder::set_max_size`].
+	pub fn max_size(&self) -> Option<u64> {
+		self.max_size
+	}
+
+	/// Records a message, using the current time as its timestamp.
+	pub fn record(&mut self, message: VMCMessage) -> VMCResult<()> {
+		self.record_at(message, Instant::now())
+	}
+
+	/// Records a message received at the given instant.
+	///
+	/// Messages are discarded while the recorder is paused.
+	pub fn record_at(&mut self, message: VMCMessage, at: Instant) -> VMCResult<()> {
+		if self.is_paused() {
+			return Ok(());
+		}
+		match message {
+			VMCMessage::Time(time) => {
+				let messages = std::mem::take(&mut self.pending);
+				self.write_frame(time, messages, self.timestamp_at(at))
+			}
+			message => {
+				self.pending.push(message);
+				Ok(())
+			}
+		}
+	}
+
+	/// Records a complete [`VMCFrame`], using the current time as its timestamp.
+	///
+	/// The frame is discarded if the recorder is paused.
+	pub fn record_frame(&mut self, frame: &VMCFrame) -> VMCResult<()> {
+		if self.is_paused() {
+			return Ok(());
+		}
+		self.write_frame(frame.time.clone(), frame.messages.clone(), self.elapsed())
+	}
+
+	/// Records a complete [`VMCFrame`] with an explicit timestamp relative to the start of the recording, e.g. when
+	/// writing a recording from offline data.
+	///
+	/// Unlike [`Recorder::record_frame`], the frame is written even if the recorder is paused. Timestamps must not
+	/// decrease; a timestamp earlier than that of the previous frame is clamped to it.
+	pub fn record_frame_at(&mut self, frame: &VMCFrame, timestamp: Duration) -> VMCResult<()> {
+		self.write_frame(frame.time.clone(), frame.messages.clone(), timestamp)
+	}
+
+	fn write_frame(&mut self, time: VMCTime, messages: Vec<VMCMessage>, timestamp: Duration) -> VMCResult<()> {
+		let timestamp = timestamp.as_micros() as u64;
+		// Frames received out of order (e.g. via `record_at`) must not break the ordering of the index.
+		let last_timestamp = self.index.last().map_or(0, |(_, timestamp)| *timestamp);
+		let timestamp = timestamp.max(last_timestamp);
+
+		let mut payload = Vec::new();
+		let mut header = Vec::with_capacity(FRAME_HEADER_SIZE as usize);
+		match &mut self.encoder {
+			Some(encoder) => {
+				let keyframe = encoder.encode(&time, &messages, &mut payload);
+				payload = self.compression.compress(payload);
+				write_varint(&mut header, (timestamp - last_timestamp) << 1 | keyframe as u64);
+				write_varint(&mut header, payload.len() as u64);
+			}
+			None => {
+				let packet = OSCPacket::Bundle(OSCBundle {
+					timetag: OSCTime::from((0, 1)),
+					content: messages
+						.into_iter()
+						.chain([VMCMessage::Time(time)])
+						.map(|message| OSCPacket::Message(message.into_osc_message()))
+						.collect()
+				});
+				// NOTE: The Output implementation for Vec<u8> can't actually produce an error!
+				osc::encode_into(&packet, &mut payload).expect("Failed to write encoded packet into Vec");
+				payload = self.compression.compress(payload);
+				header.extend_from_slice(&timestamp.to_be_bytes());
+				header.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+			}
+		}
+
+		let frame_size = (header.len() + payload.len()) as u64;
+		if let Some(max_size) = self.max_size {
+			let finished_size = self.position + frame_size + (self.index.len() as u64 + 1) * INDEX_ENTRY_SIZE + FOOTER_SIZE;
+			if finished_size > max_size {
+				// the encoder already assumes this frame was written, so start over from a keyframe
+				if let Some(encoder) = &mut self.encoder {
+					encoder.force_keyframe();
+				}
+				return Err(VMCError::RecordingSizeLimit(max_size));
+			}
+		}
+
+		self.writer.write_all(&header)?;
+		self.writer.write_all(&payload)?;
+		self.index.push((self.position, timestamp));
+		self.position += frame_size;
+		Ok(())
+	}
+
+	fn timestamp_at(&self, at: Instant) -> Duration {
+		at.saturating_duration_since(self.started).saturating_sub(self.paused_for)
+	}
+
+	/// Pauses the recording. Messages recorded while paused are discarded, and any partially received frame is dropped.
+	pub fn pause(&mut self) {
+		if self.paused_at.is_none() {
+			self.paused_at = Some(Instant::now());
+			self.pending.clear();
+		}
+	}
+
+	/// Resumes a paused recording.
+	pub fn resume(&mut self) {
+		if let Some(paused_at) = self.paused_at.take() {
+			self.paused_for += paused_at.elapsed();
+		}
+	}
+
+	/// Returns `true` if the recording is paused.
+	pub fn is_paused(&self) -> bool {
+		self.paused_at.is_some()
+	}
+
+	/// Returns the duration of the recording so far, excluding time spent paused.
+	pub fn elapsed(&self) -> Duration {
+		self.timestamp_at(self.paused_at.unwrap_or_else(Instant::now))
+	}
+
+	/// Returns the number of frames written so far.
+	pub fn frame_count(&self) -> usize {
+		self.index.len()
+	}
+
+	/// Returns the number of bytes written so far.
+	pub fn bytes_written(&self) -> u64 {
+		self.position
+	}
+
+	/// Get a reference to the underlying writer.
+	pub fn get_ref(&self) -> &W {
+		&self.writer
+	}
+
+	/// Stops the recording, writing the frame index and flushing the writer, and returns the underlying writer.
+	///
+	/// Messages belonging to an incomplete frame are discarded.
+	pub fn finish(mut self) -> VMCResult<W> {
+		let index_offset = self.position;
+		let mut index = Vec::with_capacity(self.index.len() * INDEX_ENTRY_SIZE as usize + FOOTER_SIZE as usize);
+		for (offset, timestamp) in &self.index {
+			index.extend_from_slice(&offset.to_be_bytes());
+			index.extend_from_slice(&timestamp.to_be_bytes());
+		}
+		index.extend_from_slice(&index_offset.to_be_bytes());
+		index.extend_from_slice(&(self.index.len() as u64).to_be_bytes());
+		index.extend_from_slice(INDEX_MAGIC);
+		self.writer.write_all(&index)?;
+		self.writer.flush()?;
+		Ok(self.writer)
+	}
+}
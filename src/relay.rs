@@ -0,0 +1,304 @@
+use std::{fmt, io, net::SocketAddr};
+
+use bytes::BytesMut;
+use tokio::net::ToSocketAddrs;
+
+use crate::{
+	OSCPacket, VMCError, VMCReceiver, VMCResult, VMCSender, VMCSocket,
+	osc::{self, OSCBundle, OSCMessage}
+};
+
+type Filter = Box<dyn Fn(&OSCMessage) -> bool + Send + Sync>;
+type Rewrite = Box<dyn Fn(&mut OSCMessage) + Send + Sync>;
+
+/// The address prefix of VMC pass-through messages.
+const THRU_PREFIX: &str = "/VMC/Thru/";
+
+/// Forwards packets received on a socket to one or more targets, e.g. to share the data from a single tracker between
+/// multiple applications.
+///
+/// Packets can optionally be filtered and rewritten on a per-message basis. Bundles keep their structure; bundles left
+/// empty by filters are dropped.
+///
+/// # Examples
+///
+/// ```no_run
+/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+/// use vmc::VMCRelay;
+///
+/// // receive from a phone tracker & forward to two applications on this machine, dropping blend shapes
+/// let mut relay = VMCRelay::bind("0.0.0.0:39539")
+/// 	.await?
+/// 	.with_filter(|message| !message.addr.starts_with("/VMC/Ext/Blend/"));
+/// relay.add_target("127.0.0.1:39540").await?;
+/// relay.add_target("127.0.0.1:39541").await?;
+/// relay.run().await?;
+/// # Ok(()) }) }
+/// ```
+pub struct VMCRelay {
+	receiver: VMCReceiver,
+	sender: VMCSender,
+	targets: Vec<SocketAddr>,
+	filters: Vec<Filter>,
+	rewrites: Vec<Rewrite>
+}
+
+impl fmt::Debug for VMCRelay {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("VMCRelay")
+			.field("receiver", &self.receiver)
+			.field("targets", &self.targets)
+			.field("filters", &self.filters.len())
+			.field("rewrites", &self.rewrites.len())
+			.finish_non_exhaustive()
+	}
+}
+
+impl VMCRelay {
+	/// Creates a relay which receives on the given socket. Packets are forwarded from the same socket.
+	pub fn new(socket: VMCSocket) -> Self {
+		let (receiver, sender) = socket.into_split();
+		Self {
+			receiver,
+			sender,
+			targets: Vec::new(),
+			filters: Vec::new(),
+			rewrites: Vec::new()
+		}
+	}
+
+	/// Creates a relay which receives on the given address.
+	///
+	/// To configure the socket further (e.g. to join a multicast group), use [`VMCSocket::builder`] and
+	/// [`VMCRelay::new`].
+	pub async fn bind<A: ToSocketAddrs>(addr: A) -> VMCResult<Self> {
+		Ok(Self::new(VMCSocket::bind(addr).await?))
+	}
+
+	/// Adds a target to forward packets to.
+	pub async fn add_target<A: ToSocketAddrs>(&mut self, addr: A) -> VMCResult<()> {
+		match tokio::net::lookup_host(addr).await?.next() {
+			Some(addr) => {
+				self.targets.push(addr);
+				Ok(())
+			}
+			None => Err(io::Error::new(io::ErrorKind::InvalidInput, "could not resolve to any address").into())
+		}
+	}
+
+	/// Removes a target, returning `true` if it was present.
+	pub fn remove_target(&mut self, addr: SocketAddr) -> bool {
+		let len = self.targets.len();
+		self.targets.retain(|target| *target != addr);
+		self.targets.len() != len
+	}
+
+	/// Returns the targets packets are forwarded to.
+	pub fn targets(&self) -> &[SocketAddr] {
+		&self.targets
+	}
+
+	/// Only forwards messages for which `filter` returns `true`. If multiple filters are added, messages must pass all
+	/// of them.
+	///
+	/// Filters are applied before rewrites, so they see the original addresses.
+	pub fn with_filter<F: Fn(&OSCMessage) -> bool + Send + Sync + 'static>(mut self, filter: F) -> Self {
+		self.filters.push(Box::new(filter));
+		self
+	}
+
+	/// Rewrites each forwarded message, e.g. to change its address. Rewrites are applied in the order they are added.
+	pub fn with_rewrite<F: Fn(&mut OSCMessage) + Send + Sync + 'static>(mut self, rewrite: F) -> Self {
+		self.rewrites.push(Box::new(rewrite));
+		self
+	}
+
+	/// Wraps forwarded messages into pass-through messages, so that e.g. `/VMC/Ext/Bone/Pos` is forwarded as
+	/// `/VMC/Thru/Ext/Bone/Pos`. Messages which are already pass-through messages are forwarded unchanged.
+	///
+	/// Receivers like VirtualMotionCapture forward pass-through messages as-is without interpreting them, which allows
+	/// data to be tunneled through them to another application.
+	pub fn with_thru_wrapping(self) -> Self {
+		self.with_rewrite(|message| {
+			if !message.addr.starts_with(THRU_PREFIX) {
+				let path = message.addr.strip_prefix("/VMC/").unwrap_or_else(|| message.addr.trim_start_matches('/'));
+				message.addr = format!("{THRU_PREFIX}{path}");
+			}
+		})
+	}
+
+	/// Receives a single packet and forwards it to all targets, returning the address of the peer that sent it.
+	///
+	/// The packet is sent to every target even if sending to one of them fails; the first error is returned.
+	pub async fn relay_next(&mut self) -> VMCResult<SocketAddr> {
+		let (buf, peer_addr) = self.receiver.recv_raw().await?;
+		let Some(packet) = self.process(self.receiver.decode(&buf)?) else {
+			return Ok(peer_addr);
+		};
+		// without filters or rewrites, the packet is unchanged, so forward the datagram as-is instead of re-encoding it
+		let buf = if self.filters.is_empty() && self.rewrites.is_empty() {
+			buf
+		} else {
+			let mut out = BytesMut::with_capacity(osc::encoded_size(&packet));
+			osc::encode_into(&packet, &mut out).expect("Failed to write encoded packet into BytesMut");
+			out.freeze()
+		};
+		let mut result = Ok(peer_addr);
+		for target in &self.targets {
+			if let Err(e) = self.sender.send_buf_to(&buf, *target).await {
+				if result.is_ok() {
+					result = Err(e);
+				}
+			}
+		}
+		result
+	}
+
+	/// Forwards packets until an error occurs. Malformed packets are skipped.
+	pub async fn run(&mut self) -> VMCResult<()> {
+		loop {
+			match self.relay_next().await {
+				Ok(_) | Err(VMCError::Osc(_)) => {}
+				Err(e) => return Err(e)
+			}
+		}
+	}
+
+	/// Applies filters & rewrites to a packet, returning `None` if nothing is left to forward.
+	fn process(&self, packet: OSCPacket) -> Option<OSCPacket> {
+		match packet {
+			OSCPacket::Message(mut message) => {
+				if !self.filters.iter().all(|filter| filter(&message)) {
+					return None;
+				}
+				for rewrite in &self.rewrites {
+					rewrite(&mut message);
+				}
+				Some(OSCPacket::Message(message))
+			}
+			OSCPacket::Bundle(bundle) => {
+				let content: Vec<_> = bundle.content.into_iter().filter_map(|packet| self.process(packet)).collect();
+				if content.is_empty() {
+					return None;
+				}
+				Some(OSCPacket::Bundle(OSCBundle { timetag: bundle.timetag, content }))
+			}
+		}
+	}
+
+	/// Returns the local address that the relay receives on.
+	pub fn local_addr(&self) -> VMCResult<SocketAddr> {
+		self.receiver.local_addr()
+	}
+
+	/// Returns a reference to the receiving half of the relay's socket, e.g. to inspect its
+	/// [statistics](VMCReceiver::stats).
+	pub fn receiver(&self) -> &VMCReceiver {
+		&self.receiver
+	}
+
+	/// Returns a mutable reference to the receiving half of the relay's socket, e.g. to
+	/// [restrict which peers are relayed](VMCReceiver::allow_peer).
+	pub fn receiver_mut(&mut self) -> &mut VMCReceiver {
+		&mut self.receiver
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use super::*;
+	use crate::{IntoOSCPacket, VMCBlendShape, VMCMessage, VMCTime, osc::OSCTime};
+
+	#[tokio::test]
+	async fn test_relay() -> VMCResult<()> {
+		let mut relay = VMCRelay::bind("127.0.0.1:0")
+			.await?
+			.with_filter(|message| !message.addr.starts_with("/VMC/Ext/Blend/"));
+		let mut plain = VMCSocket::bind("127.0.0.1:0").await?;
+		relay.add_target(plain.local_addr()?).await?;
+
+		let performer = VMCSocket::bind("127.0.0.1:0").await?;
+		performer.connect(relay.local_addr()?).await?;
+		performer
+			.send(OSCPacket::Bundle(OSCBundle {
+				timetag: OSCTime::from((0, 1)),
+				content: vec![VMCBlendShape::new("Joy", 1.0).into_osc_packet(), VMCTime::new(1.0).into_osc_packet()]
+			}))
+			.await?;
+		assert_eq!(relay.relay_next().await?, performer.local_addr()?);
+		assert!(matches!(&plain.recv_message().await?[..], [VMCMessage::Time(time)] if time.0 == 1.0));
+
+		let mut relay = relay.with_thru_wrapping();
+		performer.send(VMCTime::new(2.0)).await?;
+		relay.relay_next().await?;
+		let OSCPacket::Message(message) = plain.recv().await? else {
+			panic!("expected message")
+		};
+		assert_eq!(message.addr, "/VMC/Thru/Ext/T");
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_relay_edge_cases() -> VMCResult<()> {
+		let mut relay = VMCRelay::bind("127.0.0.1:0")
+			.await?
+			.with_filter(|message| !message.addr.starts_with("/VMC/Ext/Blend/"))
+			.with_thru_wrapping();
+		let mut plain = VMCSocket::bind("127.0.0.1:0").await?;
+		// an IPv6 target can't be reached from an IPv4 socket; the packet should still be forwarded to the other target
+		relay.add_target("[::1]:39539").await?;
+		relay.add_target(plain.local_addr()?).await?;
+		assert!(relay.add_target(&[][..]).await.is_err());
+		assert_eq!(relay.targets().len(), 2);
+
+		let performer = VMCSocket::bind("127.0.0.1:0").await?;
+		performer.connect(relay.local_addr()?).await?;
+		// a bundle left empty by filters is dropped entirely, as are empty nested bundles
+		performer
+			.send(OSCPacket::Bundle(OSCBundle {
+				timetag: OSCTime::from((0, 1)),
+				content: vec![VMCBlendShape::new("Joy", 1.0).into_osc_packet()]
+			}))
+			.await?;
+		assert_eq!(relay.relay_next().await?, performer.local_addr()?);
+		performer
+			.send(OSCPacket::Bundle(OSCBundle {
+				timetag: OSCTime::from((0, 1)),
+				content: vec![
+					vec![VMCBlendShape::new("Joy", 1.0)].into_osc_packet(),
+					OSCMessage::new("/VMC/Thru/Custom", (1,)).into_osc_packet(),
+					OSCMessage::new("/Custom", ()).into_osc_packet(),
+				]
+			}))
+			.await?;
+		assert!(relay.relay_next().await.is_err());
+		let OSCPacket::Bundle(bundle) = plain.recv().await? else {
+			panic!("expected bundle")
+		};
+		let addrs: Vec<_> = bundle
+			.content
+			.iter()
+			.map(|packet| match packet {
+				OSCPacket::Message(message) => message.addr.as_str(),
+				OSCPacket::Bundle(_) => panic!("expected message")
+			})
+			.collect();
+		assert_eq!(addrs, ["/VMC/Thru/Custom", "/VMC/Thru/Custom"]);
+
+		// malformed datagrams are an error for relay_next, but are skipped by run
+		assert!(relay.remove_target("[::1]:39539".parse().unwrap()));
+		assert!(!relay.remove_target("[::1]:39539".parse().unwrap()));
+		performer.socket().send(b"garbage").await?;
+		assert!(matches!(relay.relay_next().await, Err(VMCError::Osc(_))));
+		performer.socket().send(b"garbage").await?;
+		performer.send(VMCTime::new(1.0)).await?;
+		assert!(tokio::time::timeout(Duration::from_millis(200), relay.run()).await.is_err());
+		let OSCPacket::Message(message) = plain.recv().await? else {
+			panic!("expected message")
+		};
+		assert_eq!(message.addr, "/VMC/Thru/Ext/T");
+		Ok(())
+	}
+}
@@ -0,0 +1,306 @@
+//! Retargeting of poses between VRM 0.x and VRM 1.0 conventions.
+//!
+//! VMC messages use VRM 0.x conventions: bones & blend shapes are named in `PascalCase` (as in
+//! [`StandardVRM0Bone`](crate::VMCStandardVRM0Bone) and [`StandardVRMBlendShape`](crate::VMCStandardVRMBlendShape)),
+//! and the model faces towards -Z. VRM 1.0 uses `camelCase` names, renames the thumb bones & expressions, and faces
+//! towards +Z.
+//!
+//! [`vrm1_to_vrm0`] can be used by a VRM 1.0-based performer to produce poses suitable for sending to marionettes,
+//! and [`vrm0_to_vrm1`] can be used by a VRM 1.0-based marionette to convert received poses.
+//!
+//! # Examples
+//!
+//! ```
+//! use vmc::{Quat, VMCPose, VMCStandardVRM0Bone, Vec3, retarget};
+//!
+//! let mut pose = VMCPose::new();
+//! pose.set_bone("leftThumbMetacarpal", Vec3::ZERO, Quat::IDENTITY);
+//! pose.set_blendshape("happy", 1.0);
+//!
+//! let pose = retarget::vrm1_to_vrm0(&pose);
+//! assert!(pose.bone(VMCStandardVRM0Bone::LeftThumbProximal).is_some());
+//! assert_eq!(pose.blendshape("Joy"), Some(1.0));
+//! ```
+
+use glam::{Quat, Vec3A};
+
+use crate::{message::BoneTransform, name::Name, pose::Pose};
+
+/// Pairs of VRM 0.x & VRM 1.0 bone names.
+const BONES: &[(&str, &str)] = &[
+	("Hips", "hips"),
+	("LeftUpperLeg", "leftUpperLeg"),
+	("RightUpperLeg", "rightUpperLeg"),
+	("LeftLowerLeg", "leftLowerLeg"),
+	("RightLowerLeg", "rightLowerLeg"),
+	("LeftFoot", "leftFoot"),
+	("RightFoot", "rightFoot"),
+	("Spine", "spine"),
+	("Chest", "chest"),
+	("UpperChest", "upperChest"),
+	("Neck", "neck"),
+	("Head", "head"),
+	("LeftShoulder", "leftShoulder"),
+	("RightShoulder", "rightShoulder"),
+	("LeftUpperArm", "leftUpperArm"),
+	("RightUpperArm", "rightUpperArm"),
+	("LeftLowerArm", "leftLowerArm"),
+	("RightLowerArm", "rightLowerArm"),
+	("LeftHand", "leftHand"),
+	("RightHand", "rightHand"),
+	("LeftToes", "leftToes"),
+	("RightToes", "rightToes"),
+	("LeftEye", "leftEye"),
+	("RightEye", "rightEye"),
+	("Jaw", "jaw"),
+	("LeftThumbProximal", "leftThumbMetacarpal"),
+	("LeftThumbIntermediate", "leftThumbProximal"),
+	("LeftThumbDistal", "leftThumbDistal"),
+	("LeftIndexProximal", "leftIndexProximal"),
+	("LeftIndexIntermediate", "leftIndexIntermediate"),
+	("LeftIndexDistal", "leftIndexDistal"),
+	("LeftMiddleProximal", "leftMiddleProximal"),
+	("LeftMiddleIntermediate", "leftMiddleIntermediate"),
+	("LeftMiddleDistal", "leftMiddleDistal"),
+	("LeftRingProximal", "leftRingProximal"),
+	("LeftRingIntermediate", "leftRingIntermediate"),
+	("LeftRingDistal", "leftRingDistal"),
+	("LeftLittleProximal", "leftLittleProximal"),
+	("LeftLittleIntermediate", "leftLittleIntermediate"),
+	("LeftLittleDistal", "leftLittleDistal"),
+	("RightThumbProximal", "rightThumbMetacarpal"),
+	("RightThumbIntermediate", "rightThumbProximal"),
+	("RightThumbDistal", "rightThumbDistal"),
+	("RightIndexProximal", "rightIndexProximal"),
+	("RightIndexIntermediate", "rightIndexIntermediate"),
+	("RightIndexDistal", "rightIndexDistal"),
+	("RightMiddleProximal", "rightMiddleProximal"),
+	("RightMiddleIntermediate", "rightMiddleIntermediate"),
+	("RightMiddleDistal", "rightMiddleDistal"),
+	("RightRingProximal", "rightRingProximal"),
+	("RightRingIntermediate", "rightRingIntermediate"),
+	("RightRingDistal", "rightRingDistal"),
+	("RightLittleProximal", "rightLittleProximal"),
+	("RightLittleIntermediate", "rightLittleIntermediate"),
+	("RightLittleDistal", "rightLittleDistal")
+];
+
+/// Pairs of VRM 0.x & VRM 1.0 blend shape/expression names.
+const BLENDSHAPES: &[(&str, &str)] = &[
+	("Neutral", "neutral"),
+	("A", "aa"),
+	("I", "ih"),
+	("U", "ou"),
+	("E", "ee"),
+	("O", "oh"),
+	("Blink", "blink"),
+	("Blink_L", "blinkLeft"),
+	("Blink_R", "blinkRight"),
+	("Joy", "happy"),
+	("Angry", "angry"),
+	("Sorrow", "sad"),
+	("Fun", "relaxed"),
+	("LookUp", "lookUp"),
+	("LookDown", "lookDown"),
+	("LookLeft", "lookLeft"),
+	("LookRight", "lookRight")
+];
+
+/// Converts a VRM 0.x bone name to its VRM 1.0 equivalent.
+///
+/// Returns `None` if the bone has no VRM 1.0 equivalent (e.g. `Pelvis`) or is not a standard bone.
+pub fn bone_vrm0_to_vrm1(bone: &str) -> Option<&'static str> {
+	BONES.iter().find(|(vrm0, _)| *vrm0 == bone).map(|(_, vrm1)| *vrm1)
+}
+
+/// Converts a VRM 1.0 bone name to its VRM 0.x equivalent.
+///
+/// Returns `None` if the bone is not a standard bone.
+pub fn bone_vrm1_to_vrm0(bone: &str) -> Option<&'static str> {
+	BONES.iter().find(|(_, vrm1)| *vrm1 == bone).map(|(vrm0, _)| *vrm0)
+}
+
+/// Converts a VRM 0.x blend shape name to its VRM 1.0 expression equivalent.
+pub fn blendshape_vrm0_to_vrm1(key: &str) -> Option<&'static str> {
+	BLENDSHAPES.iter().find(|(vrm0, _)| *vrm0 == key).map(|(_, vrm1)| *vrm1)
+}
+
+/// Converts a VRM 1.0 expression name to its VRM 0.x blend shape equivalent.
+pub fn blendshape_vrm1_to_vrm0(key: &str) -> Option<&'static str> {
+	BLENDSHAPES.iter().find(|(_, vrm1)| *vrm1 == key).map(|(vrm0, _)| *vrm0)
+}
+
+/// Returns the parent of a VRM 0.x bone in the humanoid hierarchy, or `None` for `Hips` and non-standard bones.
+pub fn vrm0_parent(bone: &str) -> Option<&'static str> {
+	Some(match bone {
+		"LeftUpperLeg" => "Hips",
+		"RightUpperLeg" => "Hips",
+		"LeftLowerLeg" => "LeftUpperLeg",
+		"RightLowerLeg" => "RightUpperLeg",
+		"LeftFoot" => "LeftLowerLeg",
+		"RightFoot" => "RightLowerLeg",
+		"LeftToes" => "LeftFoot",
+		"RightToes" => "RightFoot",
+		"Pelvis" => "Hips",
+		"Spine" => "Hips",
+		"Chest" => "Spine",
+		"UpperChest" => "Chest",
+		"Neck" => "UpperChest",
+		"Head" => "Neck",
+		"LeftEye" => "Head",
+		"RightEye" => "Head",
+		"Jaw" => "Head",
+		"LeftShoulder" => "UpperChest",
+		"RightShoulder" => "UpperChest",
+		"LeftUpperArm" => "LeftShoulder",
+		"RightUpperArm" => "RightShoulder",
+		"LeftLowerArm" => "LeftUpperArm",
+		"RightLowerArm" => "RightUpperArm",
+		"LeftHand" => "LeftLowerArm",
+		"RightHand" => "RightLowerArm",
+		"LeftThumbProximal" => "LeftHand",
+		"LeftThumbIntermediate" => "LeftThumbProximal",
+		"LeftThumbDistal" => "LeftThumbIntermediate",
+		"LeftIndexProximal" => "LeftHand",
+		"LeftIndexIntermediate" => "LeftIndexProximal",
+		"LeftIndexDistal" => "LeftIndexIntermediate",
+		"LeftMiddleProximal" => "LeftHand",
+		"LeftMiddleIntermediate" => "LeftMiddleProximal",
+		"LeftMiddleDistal" => "LeftMiddleIntermediate",
+		"LeftRingProximal" => "LeftHand",
+		"LeftRingIntermediate" => "LeftRingProximal",
+		"LeftRingDistal" => "LeftRingIntermediate",
+		"LeftLittleProximal" => "LeftHand",
+		"LeftLittleIntermediate" => "LeftLittleProximal",
+		"LeftLittleDistal" => "LeftLittleIntermediate",
+		"RightThumbProximal" => "RightHand",
+		"RightThumbIntermediate" => "RightThumbProximal",
+		"RightThumbDistal" => "RightThumbIntermediate",
+		"RightIndexProximal" => "RightHand",
+		"RightIndexIntermediate" => "RightIndexProximal",
+		"RightIndexDistal" => "RightIndexIntermediate",
+		"RightMiddleProximal" => "RightHand",
+		"RightMiddleIntermediate" => "RightMiddleProximal",
+		"RightMiddleDistal" => "RightMiddleIntermediate",
+		"RightRingProximal" => "RightHand",
+		"RightRingIntermediate" => "RightRingProximal",
+		"RightRingDistal" => "RightRingIntermediate",
+		"RightLittleProximal" => "RightHand",
+		"RightLittleIntermediate" => "RightLittleProximal",
+		"RightLittleDistal" => "RightLittleIntermediate",
+		_ => return None
+	})
+}
+
+/// Converts a pose from VRM 0.x to VRM 1.0 conventions.
+///
+/// Bones without a VRM 1.0 equivalent are folded into their nearest mapped ancestor by composing their rotation onto
+/// it. Non-standard bones & blend shapes are kept as-is.
+pub fn vrm0_to_vrm1(pose: &Pose) -> Pose {
+	retarget(pose, bone_vrm0_to_vrm1, blendshape_vrm0_to_vrm1)
+}
+
+/// Converts a pose from VRM 1.0 to VRM 0.x conventions, suitable for sending over VMC.
+///
+/// Non-standard bones & expressions (e.g. `surprised`, which has no VRM 0.x equivalent) are kept as-is.
+pub fn vrm1_to_vrm0(pose: &Pose) -> Pose {
+	retarget(pose, bone_vrm1_to_vrm0, blendshape_vrm1_to_vrm0)
+}
+
+fn retarget(pose: &Pose, map_bone: fn(&str) -> Option<&'static str>, map_blendshape: fn(&str) -> Option<&'static str>) -> Pose {
+	let mut out = Pose {
+		root: pose.root.clone().map(|mut root| {
+			root.position = flip_position(root.position);
+			root.rotation = flip_rotation(root.rotation);
+			root
+		}),
+		bones: Default::default(),
+		blendshapes: Default::default(),
+		state: pose.state.clone(),
+		time: pose.time.clone()
+	};
+
+	let mut orphans = Vec::new();
+	for (name, transform) in &pose.bones {
+		let position = flip_position(transform.position);
+		let rotation = flip_rotation(transform.rotation);
+		match map_bone(name) {
+			Some(mapped) => {
+				let mapped = Name::from_static(mapped);
+				out.bones.insert(mapped.clone(), BoneTransform::new(mapped, position, rotation));
+			}
+			None => match nearest_mapped_ancestor(name, map_bone) {
+				Some(ancestor) => orphans.push((ancestor, rotation)),
+				None => {
+					out.bones.insert(name.clone(), BoneTransform::new(name, position, rotation));
+				}
+			}
+		}
+	}
+	for (ancestor, rotation) in orphans {
+		let parent = out
+			.bones
+			.entry(Name::from_static(ancestor))
+			.or_insert_with(|| BoneTransform::new(ancestor, Vec3A::ZERO, Quat::IDENTITY));
+		parent.rotation = (parent.rotation * rotation).normalize();
+	}
+
+	out.blendshapes = pose
+		.blendshapes
+		.iter()
+		.map(|(key, value)| (map_blendshape(key).map_or_else(|| key.clone(), Name::from_static), *value))
+		.collect();
+	out
+}
+
+fn nearest_mapped_ancestor(bone: &str, map_bone: fn(&str) -> Option<&'static str>) -> Option<&'static str> {
+	let mut current = vrm0_parent(bone)?;
+	loop {
+		if let Some(mapped) = map_bone(current) {
+			return Some(mapped);
+		}
+		current = vrm0_parent(current)?;
+	}
+}
+
+/// Rotates a position 180 degrees around the Y axis.
+fn flip_position(position: Vec3A) -> Vec3A {
+	Vec3A::new(-position.x, position.y, -position.z)
+}
+
+/// Conjugates a rotation by a 180 degree rotation around the Y axis, expressing it in the opposite facing convention.
+fn flip_rotation(rotation: Quat) -> Quat {
+	Quat::from_xyzw(-rotation.x, rotation.y, -rotation.z, rotation.w)
+}
+
+#[cfg(test)]
+mod tests {
+	use glam::{Quat, Vec3A};
+
+	use super::*;
+
+	#[test]
+	fn test_retarget_roundtrip() {
+		let mut pose = Pose::new();
+		pose.set_bone("Hips", Vec3A::new(0.1, 1.0, 0.2), Quat::from_rotation_x(0.3));
+		pose.set_bone("LeftThumbIntermediate", Vec3A::ZERO, Quat::from_rotation_z(0.2));
+		pose.set_bone("Pelvis", Vec3A::ZERO, Quat::from_rotation_y(0.5));
+		pose.set_blendshape("Sorrow", 0.5);
+
+		let vrm1 = vrm0_to_vrm1(&pose);
+		assert_eq!(vrm1.bones.len(), 2);
+		assert!(vrm1.bone("leftThumbProximal").is_some());
+		assert_eq!(vrm1.blendshape("sad"), Some(0.5));
+		let hips = vrm1.bone("hips").unwrap();
+		assert!(hips.position.abs_diff_eq(Vec3A::new(-0.1, 1.0, -0.2), 1e-6));
+		assert!(
+			hips.rotation
+				.abs_diff_eq(flip_rotation(Quat::from_rotation_x(0.3) * Quat::from_rotation_y(0.5)), 1e-6)
+		);
+
+		let vrm0 = vrm1_to_vrm0(&vrm1);
+		assert!(vrm0.bone("Hips").unwrap().position.abs_diff_eq(Vec3A::new(0.1, 1.0, 0.2), 1e-6));
+		assert!(vrm0.bone("LeftThumbIntermediate").is_some());
+		assert_eq!(vrm0.blendshape("Sorrow"), Some(0.5));
+	}
+}
@@ -0,0 +1,323 @@
+//! Callback-based dispatch of received messages.
+//!
+//! A [`Router`] calls handlers registered per message type or OSC address prefix, as an alternative to matching on
+//! [`VMCMessage`] in a receive loop.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+//! use vmc::router::Router;
+//!
+//! let mut socket = vmc::marionette!().await?;
+//! let mut router = Router::new()
+//! 	.on_bone(|transform| println!("{}: {:?}", transform.bone, transform.rotation))
+//! 	.on_blendshape(|blendshape| println!("{} = {}", blendshape.key, blendshape.value))
+//! 	.on_address("/VMC/Thru/", |message| println!("pass-through: {}", message.addr))
+//! 	.fallback(|message| println!("unhandled: {message:?}"));
+//! router.run(&mut socket).await?;
+//! # Ok(()) }) }
+//! ```
+
+use std::{fmt, future::poll_fn, net::SocketAddr, pin::Pin};
+
+use futures_core::Stream;
+
+use crate::{
+	OSCPacket, VMCError, VMCMessage, VMCResult,
+	message::{BlendShape, BoneTransform, DeviceTransform, RootTransform, State, Time},
+	osc::OSCMessage,
+	parse
+};
+
+type Handler<T> = Box<dyn FnMut(&T) + Send>;
+
+/// Dispatches messages to handlers registered per message type or OSC address prefix.
+///
+/// Each received OSC message is first passed to all [address handlers](Router::on_address) whose prefix matches its
+/// address. Messages which matched no address handler are then [parsed](crate::parse) and passed to all handlers
+/// registered for their type, or to the [fallback handlers](Router::fallback) if there are none. Messages which can't
+/// be parsed because they are not part of the VMC protocol are ignored unless an address handler matched them.
+///
+/// Handlers are called in the order they were registered.
+#[derive(Default)]
+pub struct Router {
+	addresses: Vec<(String, Handler<OSCMessage>)>,
+	root: Vec<Handler<RootTransform>>,
+	bone: Vec<Handler<BoneTransform>>,
+	device: Vec<Handler<DeviceTransform>>,
+	blendshape: Vec<Handler<BlendShape>>,
+	apply_blendshapes: Vec<Box<dyn FnMut() + Send>>,
+	state: Vec<Handler<State>>,
+	time: Vec<Handler<Time>>,
+	fallback: Vec<Handler<VMCMessage>>
+}
+
+impl fmt::Debug for Router {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Router")
+			.field("addresses", &self.addresses.iter().map(|(prefix, _)| prefix).collect::<Vec<_>>())
+			.finish_non_exhaustive()
+	}
+}
+
+impl Router {
+	/// Creates a router without any handlers.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Registers a handler for OSC messages whose address starts with `prefix`.
+	///
+	/// Address handlers receive messages before they are parsed, so they can handle messages which are not part of the
+	/// VMC protocol, like `/VMC/Thru/` pass-through messages. Messages handled by an address handler are not passed to
+	/// any other handlers.
+	pub fn on_address<F: FnMut(&OSCMessage) + Send + 'static>(mut self, prefix: impl Into<String>, handler: F) -> Self {
+		self.addresses.push((prefix.into(), Box::new(handler)));
+		self
+	}
+
+	/// Registers a handler for [`RootTransform`] messages.
+	pub fn on_root<F: FnMut(&RootTransform) + Send + 'static>(mut self, handler: F) -> Self {
+		self.root.push(Box::new(handler));
+		self
+	}
+
+	/// Registers a handler for [`BoneTransform`] messages.
+	pub fn on_bone<F: FnMut(&BoneTransform) + Send + 'static>(mut self, handler: F) -> Self {
+		self.bone.push(Box::new(handler));
+		self
+	}
+
+	/// Registers a handler for [`DeviceTransform`] messages.
+	pub fn on_device<F: FnMut(&DeviceTransform) + Send + 'static>(mut self, handler: F) -> Self {
+		self.device.push(Box::new(handler));
+		self
+	}
+
+	/// Registers a handler for [`BlendShape`] messages.
+	pub fn on_blendshape<F: FnMut(&BlendShape) + Send + 'static>(mut self, handler: F) -> Self {
+		self.blendshape.push(Box::new(handler));
+		self
+	}
+
+	/// Registers a handler for [`ApplyBlendShapes`](crate::VMCApplyBlendShapes) messages.
+	pub fn on_apply_blendshapes<F: FnMut() + Send + 'static>(mut self, handler: F) -> Self {
+		self.apply_blendshapes.push(Box::new(handler));
+		self
+	}
+
+	/// Registers a handler for [`State`] messages.
+	pub fn on_state<F: FnMut(&State) + Send + 'static>(mut self, handler: F) -> Self {
+		self.state.push(Box::new(handler));
+		self
+	}
+
+	/// Registers a handler for [`Time`] messages.
+	pub fn on_time<F: FnMut(&Time) + Send + 'static>(mut self, handler: F) -> Self {
+		self.time.push(Box::new(handler));
+		self
+	}
+
+	/// Registers a handler for messages of a type which has no handlers registered.
+	pub fn fallback<F: FnMut(&VMCMessage) + Send + 'static>(mut self, handler: F) -> Self {
+		self.fallback.push(Box::new(handler));
+		self
+	}
+
+	/// Dispatches a parsed message to the handlers registered for its type.
+	pub fn dispatch(&mut self, message: &VMCMessage) {
+		fn call<T>(handlers: &mut [Handler<T>], value: &T) -> bool {
+			handlers.iter_mut().for_each(|handler| handler(value));
+			!handlers.is_empty()
+		}
+
+		let handled = match message {
+			VMCMessage::RootTransform(transform) => call(&mut self.root, transform),
+			VMCMessage::BoneTransform(transform) => call(&mut self.bone, transform),
+			VMCMessage::DeviceTransform(transform) => call(&mut self.device, transform),
+			VMCMessage::BlendShape(blendshape) => call(&mut self.blendshape, blendshape),
+			VMCMessage::ApplyBlendShapes => {
+				self.apply_blendshapes.iter_mut().for_each(|handler| handler());
+				!self.apply_blendshapes.is_empty()
+			}
+			VMCMessage::State(state) => call(&mut self.state, state),
+			VMCMessage::Time(time) => call(&mut self.time, time)
+		};
+		if !handled {
+			call(&mut self.fallback, message);
+		}
+	}
+
+	/// Dispatches all messages contained in an OSC packet.
+	///
+	/// Returns an error if a message has a known VMC address but invalid arguments; the remaining messages of the
+	/// packet are still dispatched.
+	pub fn dispatch_packet(&mut self, packet: OSCPacket) -> VMCResult<()> {
+		let mut result = Ok(());
+		for message in packet.into_messages() {
+			let mut handled = false;
+			for (prefix, handler) in &mut self.addresses {
+				if message.addr.starts_with(prefix.as_str()) {
+					handler(&message);
+					handled = true;
+				}
+			}
+			if handled {
+				continue;
+			}
+
+			match parse(OSCPacket::Message(message)) {
+				Ok(messages) => messages.iter().for_each(|message| self.dispatch(message)),
+				Err(VMCError::UnimplementedMessage(..)) => {}
+				Err(e) => {
+					if result.is_ok() {
+						result = Err(e);
+					}
+				}
+			}
+		}
+		result
+	}
+
+	/// Dispatches all packets received from a stream of packets, such as a [`VMCSocket`](crate::VMCSocket) or
+	/// [`VMCReceiver`](crate::VMCReceiver), until the stream ends or an error occurs.
+	pub async fn run<S>(&mut self, mut stream: S) -> VMCResult<()>
+	where
+		S: Stream<Item = VMCResult<(OSCPacket, SocketAddr)>> + Unpin
+	{
+		while let Some(packet) = poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await {
+			let (packet, _) = packet?;
+			self.dispatch_packet(packet)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::{
+		Arc, Mutex,
+		atomic::{AtomicUsize, Ordering}
+	};
+
+	use super::*;
+	use crate::{IntoOSCMessage, IntoOSCPacket, OSCType, Quat, VMCBlendShape, VMCBoneTransform, VMCStandardVRM0Bone, VMCTime, Vec3A, osc::OSCBundle};
+
+	#[test]
+	fn test_router() -> VMCResult<()> {
+		let bones = Arc::new(Mutex::new(Vec::new()));
+		let thru = Arc::new(AtomicUsize::new(0));
+		let fallback = Arc::new(AtomicUsize::new(0));
+		let mut router = Router::new()
+			.on_bone({
+				let bones = Arc::clone(&bones);
+				move |transform| bones.lock().unwrap().push(transform.bone.clone())
+			})
+			.on_address("/VMC/Thru/", {
+				let thru = Arc::clone(&thru);
+				move |_| {
+					thru.fetch_add(1, Ordering::Relaxed);
+				}
+			})
+			.fallback({
+				let fallback = Arc::clone(&fallback);
+				move |_| {
+					fallback.fetch_add(1, Ordering::Relaxed);
+				}
+			});
+
+		router.dispatch_packet(OSCPacket::Bundle(OSCBundle {
+			timetag: (0, 1).into(),
+			content: vec![
+				OSCPacket::Message(VMCBoneTransform::new(VMCStandardVRM0Bone::Head, Vec3A::ZERO, Quat::IDENTITY).into_osc_message()),
+				OSCPacket::Message(VMCBlendShape::new("Joy", 1.0).into_osc_message()),
+				OSCPacket::Message(OSCMessage::new("/VMC/Thru/Custom", (1.0,))),
+				OSCPacket::Message(OSCMessage::new("/Unknown", vec![OSCType::Int(1)])),
+				OSCPacket::Message(VMCTime::new(1.0).into_osc_message()),
+			]
+		}))?;
+		assert_eq!(*bones.lock().unwrap(), ["Head"]);
+		assert_eq!(thru.load(Ordering::Relaxed), 1);
+		assert_eq!(fallback.load(Ordering::Relaxed), 2);
+		Ok(())
+	}
+
+	#[test]
+	fn test_router_edge_cases() {
+		fn record<T>(calls: &Arc<Mutex<Vec<&'static str>>>, name: &'static str) -> impl FnMut(&T) + Send + 'static {
+			let calls = Arc::clone(calls);
+			move |_| calls.lock().unwrap().push(name)
+		}
+
+		let calls = Arc::new(Mutex::new(Vec::new()));
+		let mut router = Router::new()
+			.on_address("/VMC/Ext/", record(&calls, "ext"))
+			.on_address("/VMC/Ext/Blend/", record(&calls, "blend"))
+			.on_time(record(&calls, "time"));
+
+		// every matching address handler is called in order, and the message isn't parsed, even if it is malformed
+		router
+			.dispatch_packet(OSCPacket::Message(OSCMessage::new("/VMC/Ext/Blend/Val", vec![OSCType::Int(1)])))
+			.unwrap();
+		router.dispatch_packet(VMCTime::new(1.0).into_osc_packet()).unwrap();
+		assert_eq!(std::mem::take(&mut *calls.lock().unwrap()), ["ext", "blend", "ext"]);
+
+		// an empty prefix matches every message
+		let mut router = Router::new().on_address("", record(&calls, "any")).fallback(record(&calls, "fallback"));
+		router.dispatch_packet(OSCPacket::Message(OSCMessage::new("/Unknown", ()))).unwrap();
+		router.dispatch_packet(VMCTime::new(1.0).into_osc_packet()).unwrap();
+		assert_eq!(std::mem::take(&mut *calls.lock().unwrap()), ["any", "any"]);
+
+		// nested bundles are flattened; messages with invalid arguments don't stop the rest of the packet, and only the
+		// first error is returned
+		let mut router = Router::new().on_time(record(&calls, "time 1")).on_time(record(&calls, "time 2"));
+		let malformed = OSCMessage::new("/VMC/Ext/OK", (7,));
+		let expected = parse(OSCPacket::Message(malformed.clone())).unwrap_err().to_string();
+		let result = router.dispatch_packet(OSCPacket::Bundle(OSCBundle {
+			timetag: (0, 1).into(),
+			content: vec![
+				OSCPacket::Message(malformed),
+				OSCPacket::Bundle(OSCBundle {
+					timetag: (0, 1).into(),
+					content: vec![VMCTime::new(1.0).into_osc_packet()]
+				}),
+				OSCPacket::Message(OSCMessage::new("/VMC/Ext/OK", (8,))),
+			]
+		}));
+		assert_eq!(result.unwrap_err().to_string(), expected);
+		assert_eq!(std::mem::take(&mut *calls.lock().unwrap()), ["time 1", "time 2"]);
+
+		// without a matching handler or fallback, messages are dropped silently
+		router.dispatch(&VMCMessage::ApplyBlendShapes);
+		router.dispatch_packet(VMCBlendShape::new("Joy", 1.0).into_osc_packet()).unwrap();
+		assert!(calls.lock().unwrap().is_empty());
+	}
+
+	#[tokio::test]
+	async fn test_run() {
+		let times = Arc::new(AtomicUsize::new(0));
+		let mut router = Router::new().on_time({
+			let times = Arc::clone(&times);
+			move |_| {
+				times.fetch_add(1, Ordering::Relaxed);
+			}
+		});
+		let addr = SocketAddr::from(([127, 0, 0, 1], 39539));
+		let packet = || Ok((VMCTime::new(1.0).into_osc_packet(), addr));
+
+		// the stream ending is not an error
+		router.run(futures_util::stream::iter([packet(), packet()])).await.unwrap();
+		assert_eq!(times.load(Ordering::Relaxed), 2);
+
+		// receive & dispatch errors stop the router
+		let failed = router
+			.run(futures_util::stream::iter([packet(), Err(VMCError::UnknownBone("Tail".into())), packet()]))
+			.await;
+		assert!(matches!(failed, Err(VMCError::UnknownBone(_))));
+		assert_eq!(times.load(Ordering::Relaxed), 3);
+		let invalid = OSCPacket::Message(OSCMessage::new("/VMC/Ext/OK", (7,)));
+		assert!(router.run(futures_util::stream::iter([Ok((invalid, addr)), packet()])).await.is_err());
+		assert_eq!(times.load(Ordering::Relaxed), 3);
+	}
+}
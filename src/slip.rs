@@ -0,0 +1,401 @@
+//! SLIP ([RFC 1055](https://datatracker.ietf.org/doc/html/rfc1055)) framing for OSC packets.
+//!
+//! The OSC 1.1 specification recommends SLIP framing for stream-based transports like serial links, using the
+//! "double-ENDed" variant where each packet is both preceded and followed by an `END` byte.
+//!
+//! [`SlipDecoder`] & [`encode_slip`] implement the framing itself, for use with any transport; [`VMCSlipStream`] uses
+//! them to send & receive packets over an async byte stream.
+
+use std::{
+	fmt,
+	future::poll_fn,
+	io,
+	pin::Pin,
+	task::{Context, Poll}
+};
+
+use futures_core::Stream;
+use tokio::{
+	io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf},
+	sync::Mutex
+};
+
+use crate::{
+	IntoOSCPacket, OSCPacket, VMCMessage, VMCResult,
+	framed::FramedRead,
+	osc::{DecodeLimits, OSCError, OSCResult, decode_udp_with_limits, encode_into},
+	parse
+};
+
+/// Marks the end (and, in double-ENDed SLIP, the start) of a packet.
+pub const END: u8 = 0xC0;
+/// Escapes an `END` or `ESC` byte occurring in the packet data.
+pub const ESC: u8 = 0xDB;
+/// `ESC ESC_END` represents a literal `END` byte in the packet data.
+pub const ESC_END: u8 = 0xDC;
+/// `ESC ESC_ESC` represents a literal `ESC` byte in the packet data.
+pub const ESC_ESC: u8 = 0xDD;
+
+/// Encodes an OSC packet and frames it with double-ENDed SLIP.
+///
+/// # Example
+///
+/// ```
+/// use vmc::{
+/// 	osc::{OSCMessage, OSCPacket},
+/// 	slip
+/// };
+///
+/// let packet = OSCPacket::Message(OSCMessage::new("/VMC/Ext/T", (1.0f32,)));
+/// let bytes = slip::encode_slip(&packet);
+/// assert_eq!(bytes.first(), Some(&slip::END));
+/// assert_eq!(bytes.last(), Some(&slip::END));
+/// ```
+pub fn encode_slip(packet: &OSCPacket) -> Vec<u8> {
+	let mut out = Vec::new();
+	encode_slip_into(packet, &mut out);
+	out
+}
+
+/// Encodes an OSC packet and appends it to `out`, framed with double-ENDed SLIP.
+pub fn encode_slip_into(packet: &OSCPacket, out: &mut Vec<u8>) {
+	let mut bytes = Vec::new();
+	// NOTE: The Output implementation for Vec<u8> can't actually produce an error!
+	encode_into(packet, &mut bytes).expect("Failed to write encoded packet into Vec");
+	slip_escape_into(&bytes, out);
+}
+
+/// Frames arbitrary data with double-ENDed SLIP, appending the result to `out`.
+pub fn slip_escape_into(data: &[u8], out: &mut Vec<u8>) {
+	out.reserve(data.len() + 2);
+	out.push(END);
+	for &byte in data {
+		match byte {
+			END => out.extend_from_slice(&[ESC, ESC_END]),
+			ESC => out.extend_from_slice(&[ESC, ESC_ESC]),
+			byte => out.push(byte)
+		}
+	}
+	out.push(END);
+}
+
+/// The default maximum size of a frame accepted by a [`SlipDecoder`].
+const DEFAULT_MAX_FRAME_LEN: usize = 1024 * 1024;
+
+/// An incremental decoder for SLIP-framed OSC packets.
+///
+/// Data read from a stream can be fed to the decoder in chunks of any size via [`SlipDecoder::push`]; complete packets
+/// are then returned by [`SlipDecoder::next_packet`].
+///
+/// A frame which is larger than the [maximum frame size](SlipDecoder::with_max_frame_len) is dropped up to the next
+/// `END` byte, and an error is returned in its place, so the decoder stays in sync with the stream.
+///
+/// # Example
+///
+/// ```
+/// use vmc::{
+/// 	osc::{OSCMessage, OSCPacket},
+/// 	slip
+/// };
+///
+/// let packet = OSCPacket::Message(OSCMessage::new("/VMC/Ext/T", (1.0f32,)));
+/// let bytes = slip::encode_slip(&packet);
+///
+/// let mut decoder = slip::SlipDecoder::new();
+/// decoder.push(&bytes[..5]);
+/// assert_eq!(decoder.next_packet()?, None);
+/// decoder.push(&bytes[5..]);
+/// assert_eq!(decoder.next_packet()?, Some(packet));
+/// # Ok::<(), vmc::osc::OSCError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct SlipDecoder {
+	buf: Vec<u8>,
+	/// Whether incoming data is discarded up to the next `END`, because it belongs to an oversized frame.
+	discarding: bool,
+	/// The offset in `buf` at which an oversized frame was dropped, so its error is returned in order.
+	dropped_at: Option<usize>,
+	limits: DecodeLimits,
+	max_frame_len: usize
+}
+
+impl Default for SlipDecoder {
+	fn default() -> Self {
+		Self {
+			buf: Vec::new(),
+			discarding: false,
+			dropped_at: None,
+			limits: DecodeLimits::default(),
+			max_frame_len: DEFAULT_MAX_FRAME_LEN
+		}
+	}
+}
+
+impl SlipDecoder {
+	/// Creates a new decoder with the [default limits](DecodeLimits::default).
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Sets the limits applied when decoding each packet.
+	pub fn with_limits(mut self, limits: DecodeLimits) -> Self {
+		self.limits = limits;
+		self
+	}
+
+	/// Sets the maximum size in bytes of a frame, including SLIP escapes, which bounds how much data is buffered for a
+	/// single frame. Defaults to 1 MiB.
+	pub fn with_max_frame_len(mut self, len: usize) -> Self {
+		self.max_frame_len = len;
+		self
+	}
+
+	/// Returns the limits applied when decoding each packet.
+	pub fn limits(&self) -> &DecodeLimits {
+		&self.limits
+	}
+
+	/// Returns the maximum size of a frame.
+	pub fn max_frame_len(&self) -> usize {
+		self.max_frame_len
+	}
+
+	/// Feeds raw bytes from the stream into the decoder.
+	pub fn push(&mut self, mut data: &[u8]) {
+		if self.discarding {
+			match data.iter().position(|&b| b == END) {
+				Some(end) => {
+					self.discarding = false;
+					data = &data[end + 1..];
+				}
+				None => return
+			}
+		}
+		self.buf.extend_from_slice(data);
+
+		// drop the incomplete frame at the end of the buffer once it grows too large
+		let partial = self.buf.iter().rposition(|&b| b == END).map_or(0, |end| end + 1);
+		if self.buf.len() - partial > self.max_frame_len {
+			self.buf.truncate(partial);
+			self.discarding = true;
+			if self.dropped_at.is_none() {
+				self.dropped_at = Some(partial);
+			}
+		}
+	}
+
+	/// Returns the next complete frame, with SLIP escapes removed, if one is available.
+	///
+	/// Empty frames (i.e. consecutive `END` bytes) are skipped. If a frame was larger than the [maximum frame
+	/// size](SlipDecoder::with_max_frame_len), an error is returned in its place.
+	pub fn next_frame(&mut self) -> OSCResult<Option<Vec<u8>>> {
+		loop {
+			if self.dropped_at == Some(0) {
+				self.dropped_at = None;
+				return Err(self.frame_too_large());
+			}
+
+			let Some(end) = self.buf.iter().position(|&b| b == END) else {
+				return Ok(None);
+			};
+			if let Some(dropped_at) = &mut self.dropped_at {
+				*dropped_at -= end + 1;
+			}
+			if end > self.max_frame_len {
+				self.buf.drain(..=end);
+				return Err(self.frame_too_large());
+			}
+
+			let mut frame = Vec::with_capacity(end);
+			let mut escaped = false;
+			for &byte in &self.buf[..end] {
+				if escaped {
+					// RFC 1055 recommends leaving protocol violations (ESC followed by anything other than ESC_END or
+					// ESC_ESC) in the packet as-is.
+					frame.push(match byte {
+						ESC_END => END,
+						ESC_ESC => ESC,
+						byte => byte
+					});
+					escaped = false;
+				} else if byte == ESC {
+					escaped = true;
+				} else {
+					frame.push(byte);
+				}
+			}
+			self.buf.drain(..=end);
+			if !frame.is_empty() {
+				return Ok(Some(frame));
+			}
+		}
+	}
+
+	/// Decodes the next complete OSC packet, if one is available.
+	pub fn next_packet(&mut self) -> OSCResult<Option<OSCPacket>> {
+		match self.next_frame()? {
+			Some(frame) => decode_udp_with_limits(&frame, &self.limits).map(|(_, packet)| Some(packet)),
+			None => Ok(None)
+		}
+	}
+
+	/// Returns `true` if the decoder holds data for an incomplete frame.
+	pub fn has_partial(&self) -> bool {
+		self.discarding || self.buf.iter().any(|&b| b != END)
+	}
+
+	fn frame_too_large(&self) -> OSCError {
+		// the frame is dropped as a whole
+		OSCError::Decode {
+			offset: 0,
+			addr: None,
+			error: Box::new(OSCError::LimitExceeded(format!("frame larger than {} bytes", self.max_frame_len)))
+		}
+	}
+}
+
+/// A VMC connection over any byte stream, framed with SLIP as recommended by the OSC 1.1 specification.
+///
+/// This is intended for serial links, e.g. microcontroller-based trackers connected over USB, but works with any type
+/// implementing [`AsyncRead`] + [`AsyncWrite`].
+///
+/// Like [`VMCTcpSocket`](crate::VMCTcpSocket), this type implements [`Stream`] to receive packets, and packets can be
+/// sent with [`VMCSlipStream::send`].
+///
+/// # Examples
+///
+/// ```no_run
+/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+/// use vmc::{VMCBlendShape, VMCSlipStream, VMCStandardVRMBlendShape};
+///
+/// // any `AsyncRead + AsyncWrite`, e.g. a serial port
+/// let (io, _) = tokio::io::duplex(1024);
+/// let mut stream = VMCSlipStream::new(io);
+/// stream.send(VMCBlendShape::new(VMCStandardVRMBlendShape::Joy, 1.0)).await?;
+/// for message in stream.recv_message().await? {
+/// 	println!("{:?}", message);
+/// }
+/// # Ok(()) }) }
+/// ```
+pub struct VMCSlipStream<T> {
+	reader: FramedRead<ReadHalf<T>, SlipDecoder>,
+	writer: Mutex<WriteHalf<T>>
+}
+
+impl<T> fmt::Debug for VMCSlipStream<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("VMCSlipStream").finish_non_exhaustive()
+	}
+}
+
+impl<T: AsyncRead + AsyncWrite> VMCSlipStream<T> {
+	/// Creates a new VMC stream over the given I/O object.
+	pub fn new(io: T) -> Self {
+		Self::with_decoder(io, SlipDecoder::new())
+	}
+
+	/// Creates a new VMC stream over the given I/O object, receiving packets with `decoder`, e.g. to configure its
+	/// [limits](SlipDecoder::with_limits).
+	pub fn with_decoder(io: T, decoder: SlipDecoder) -> Self {
+		let (reader, writer) = tokio::io::split(io);
+		Self {
+			reader: FramedRead::new(reader, decoder),
+			writer: Mutex::new(writer)
+		}
+	}
+
+	/// Sends an OSC packet over the stream.
+	pub async fn send<P: IntoOSCPacket>(&self, packet: P) -> VMCResult<()> {
+		let buf = encode_slip(&packet.into_osc_packet());
+		let mut writer = self.writer.lock().await;
+		writer.write_all(&buf).await?;
+		writer.flush().await?;
+		Ok(())
+	}
+
+	/// Receives a single OSC packet from the stream.
+	pub async fn recv(&mut self) -> VMCResult<OSCPacket> {
+		match poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await {
+			Some(res) => res,
+			None => Err(io::Error::from(io::ErrorKind::UnexpectedEof).into())
+		}
+	}
+
+	/// Receives a single OSC packet from the stream and [parses](crate::parse) it into its contained [`VMCMessage`]s.
+	pub async fn recv_message(&mut self) -> VMCResult<Vec<VMCMessage>> {
+		parse(self.recv().await?)
+	}
+
+	/// Shuts down the write half of the stream.
+	pub async fn shutdown(&self) -> VMCResult<()> {
+		self.writer.lock().await.shutdown().await?;
+		Ok(())
+	}
+}
+
+impl<T: AsyncRead + AsyncWrite> Stream for VMCSlipStream<T> {
+	type Item = VMCResult<OSCPacket>;
+
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		self.reader.poll_next_packet(cx)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{VMCBlendShape, VMCStandardVRMBlendShape, osc::OSCMessage};
+
+	#[tokio::test]
+	async fn test_slip_roundtrip() -> VMCResult<()> {
+		let (a, b) = tokio::io::duplex(64);
+		let a = VMCSlipStream::new(a);
+		let mut b = VMCSlipStream::new(b);
+
+		let sender = tokio::spawn(async move {
+			for i in 0..100 {
+				a.send(VMCBlendShape::new(VMCStandardVRMBlendShape::Joy, i as f32 / 100.0)).await?;
+			}
+			a.shutdown().await
+		});
+
+		for i in 0..100 {
+			let messages = b.recv_message().await?;
+			assert_eq!(messages.len(), 1);
+			match &messages[0] {
+				VMCMessage::BlendShape(shape) => assert_eq!(shape.value, i as f32 / 100.0),
+				message => panic!("unexpected message {message:?}")
+			}
+		}
+		assert!(b.recv().await.is_err());
+		sender.await.unwrap()
+	}
+
+	#[test]
+	fn test_oversized_frame() -> OSCResult<()> {
+		let packets: Vec<OSCPacket> = (0..2).map(|i| OSCPacket::Message(OSCMessage::new("/test", (i,)))).collect();
+		let oversized = encode_slip(&OSCPacket::Message(OSCMessage::new("/test", ("x".repeat(64),))));
+
+		// an oversized frame is dropped up to the next END, even if it arrives over multiple pushes
+		let mut decoder = SlipDecoder::new().with_max_frame_len(32);
+		decoder.push(&encode_slip(&packets[0]));
+		decoder.push(&oversized[..40]);
+		assert!(decoder.has_partial());
+		decoder.push(&oversized[40..]);
+		decoder.push(&encode_slip(&packets[1]));
+		assert_eq!(decoder.next_packet()?, Some(packets[0].clone()));
+		let err = decoder.next_packet().unwrap_err();
+		assert!(matches!(err, OSCError::Decode { offset: 0, .. }));
+		assert!(matches!(err.inner(), OSCError::LimitExceeded(_)));
+		assert_eq!(decoder.next_packet()?, Some(packets[1].clone()));
+		assert_eq!(decoder.next_packet()?, None);
+		assert!(!decoder.has_partial());
+
+		// as is one which arrives in a single push
+		let mut decoder = SlipDecoder::new().with_max_frame_len(32);
+		decoder.push(&[oversized, encode_slip(&packets[0])].concat());
+		assert!(decoder.next_packet().is_err());
+		assert_eq!(decoder.next_packet()?, Some(packets[0].clone()));
+		Ok(())
+	}
+}
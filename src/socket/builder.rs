@@ -0,0 +1,537 @@
+use std::{
+	io,
+	net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs},
+	ops::RangeInclusive
+};
+
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::UdpSocket;
+
+use super::VMCSocket;
+use crate::{
+	VMCError, VMCOptionString, VMCReceiveEnable, VMCResult,
+	osc::{DecodeLimits, EncodeOptions}
+};
+
+/// The role a [`VMCSocket`] plays in a VMC connection, which determines the defaults of a [`VMCSocketBuilder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VMCRole {
+	/// A performer sends motion data to a marionette. By default, performers bind to a port assigned by the OS and send
+	/// to `127.0.0.1:39539`, like [`performer!`](crate::performer).
+	Performer,
+	/// A marionette receives motion data from performers. By default, marionettes bind to port 39539 and have no
+	/// target, like [`marionette!`](crate::marionette).
+	Marionette
+}
+
+impl VMCRole {
+	fn default_port(&self) -> u16 {
+		match self {
+			VMCRole::Performer => 0,
+			VMCRole::Marionette => 39539
+		}
+	}
+}
+
+/// A builder used to configure a [`VMCSocket`] and its OS-level socket options, as a non-macro alternative to
+/// [`performer!`](crate::performer) & [`marionette!`](crate::marionette) which works with addresses configured at
+/// runtime and with pre-bound sockets.
+///
+/// Created via [`VMCSocket::builder`].
+///
+/// When no bind address is given, the socket binds to `127.0.0.1`, which only other programs on the same machine can
+/// reach. It binds to the unspecified address (`0.0.0.0` or `::`) instead, accepting packets on every network
+/// interface, with [`bind_any`](VMCSocketBuilder::bind_any), or if a [role](VMCSocketBuilder::role) is set and packets
+/// must leave or reach the local machine anyway: when the socket broadcasts, joins a multicast group, or targets an
+/// address which isn't a loopback address. The port defaults to the role's port, or 0 without a role.
+///
+/// # Examples
+///
+/// ```no_run
+/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+/// use vmc::{VMCRole, VMCSocket};
+///
+/// // equivalent to `vmc::performer!("192.168.1.255:39539", broadcast = true)`, with the target read from a config
+/// let target = std::env::var("VMC_TARGET").unwrap_or_else(|_| "192.168.1.255:39539".to_string());
+/// let performer = VMCSocket::builder()
+/// 	.role(VMCRole::Performer)
+/// 	.target(target)
+/// 	.broadcast(true)
+/// 	.build()
+/// 	.await?;
+/// # Ok(()) }) }
+/// ```
+///
+/// ```no_run
+/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+/// use std::net::Ipv4Addr;
+///
+/// use vmc::VMCSocket;
+///
+/// // A marionette receiving full-body tracking data from a multicast group.
+/// let socket = VMCSocket::builder()
+/// 	.bind("0.0.0.0:39539")
+/// 	.recv_buffer_size(4 * 1024 * 1024)
+/// 	.reuse_address(true)
+/// 	.join_multicast_v4(Ipv4Addr::new(239, 0, 0, 1), Ipv4Addr::UNSPECIFIED)
+/// 	.build()
+/// 	.await?;
+/// # Ok(()) }) }
+/// ```
+#[derive(Debug, Default)]
+pub struct VMCSocketBuilder {
+	role: Option<VMCRole>,
+	bind: Option<io::Result<Vec<SocketAddr>>>,
+	bind_port: Option<u16>,
+	bind_any: bool,
+	bind_any_v6: bool,
+	fallback_ports: Option<RangeInclusive<u16>>,
+	announce: Vec<io::Result<Vec<SocketAddr>>>,
+	announce_options: Option<String>,
+	target: Option<io::Result<Vec<SocketAddr>>>,
+	recv_buffer_size: Option<usize>,
+	send_buffer_size: Option<usize>,
+	reuse_address: bool,
+	#[cfg_attr(not(unix), allow(dead_code))]
+	reuse_port: bool,
+	broadcast: bool,
+	multicast_ttl: Option<u32>,
+	multicast_loop: Option<bool>,
+	multicast_interface_v4: Option<Ipv4Addr>,
+	multicast_interface_v6: Option<u32>,
+	multicast_groups_v4: Vec<(Ipv4Addr, Ipv4Addr)>,
+	multicast_groups_v6: Vec<(Ipv6Addr, u32)>,
+	allowed_peers: Vec<IpAddr>,
+	decode_limits: Option<DecodeLimits>,
+	encode_options: Option<EncodeOptions>,
+	#[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+	device: Option<Vec<u8>>,
+	#[cfg(any(target_os = "android", target_os = "linux"))]
+	kernel_timestamps: bool
+}
+
+impl VMCSocketBuilder {
+	/// Creates a new builder with the default socket options.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Sets the role of the socket, which determines the default bind address & target. See [`VMCRole`].
+	pub fn role(mut self, role: VMCRole) -> Self {
+		self.role = Some(role);
+		self
+	}
+
+	/// Sets the address to bind the socket to, overriding the default described [above](VMCSocketBuilder).
+	///
+	/// Binding with a port number of 0 will request that the OS assigns a port to this socket. If the address resolves
+	/// to multiple addresses, each will be tried in order until one succeeds.
+	///
+	/// Note that host names are resolved immediately (and thus may block); prefer passing IP addresses.
+	pub fn bind<A: ToSocketAddrs>(mut self, addr: A) -> Self {
+		self.bind = Some(addr.to_socket_addrs().map(Iterator::collect));
+		self
+	}
+
+	/// Sets the port to bind the socket to on the default address, like the `bind_port` option of
+	/// [`performer!`](crate::performer). Ignored if a [bind address](VMCSocketBuilder::bind) is given.
+	pub fn bind_port(mut self, port: u16) -> Self {
+		self.bind_port = Some(port);
+		self
+	}
+
+	/// Binds the socket to the unspecified address on the default port, so it can be reached from other machines, e.g.
+	/// phone trackers on the local network. The IPv4 address `0.0.0.0` is used, unless the
+	/// [target](VMCSocketBuilder::target) is an IPv6 address. Ignored if a [bind address](VMCSocketBuilder::bind) is
+	/// given.
+	pub fn bind_any(mut self) -> Self {
+		self.bind_any = true;
+		self
+	}
+
+	/// Like [`bind_any`](VMCSocketBuilder::bind_any), but always binds to the IPv6 unspecified address `::`. Depending
+	/// on the OS, the socket may also accept IPv4 packets.
+	pub fn bind_any_v6(mut self) -> Self {
+		self.bind_any_v6 = true;
+		self
+	}
+
+	/// Sets a range of ports to try in order if the bind port is already in use, e.g. by another marionette running on
+	/// the same machine. The port actually bound can be queried with [`VMCSocket::local_addr`].
+	///
+	/// If every port is in use, building fails with [`VMCError::PortsInUse`].
+	///
+	/// # Examples
+	///
+	/// ```no_run
+	/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+	/// use vmc::{VMCRole, VMCSocket};
+	///
+	/// let marionette = VMCSocket::builder()
+	/// 	.role(VMCRole::Marionette)
+	/// 	.fallback_ports(39540..=39549)
+	/// 	// tell the performer where to send to if 39539 was taken
+	/// 	.announce_port("192.168.1.182:39539")
+	/// 	.build()
+	/// 	.await?;
+	/// println!("receiving on port {}", marionette.local_addr()?.port());
+	/// # Ok(()) }) }
+	/// ```
+	pub fn fallback_ports(mut self, ports: RangeInclusive<u16>) -> Self {
+		self.fallback_ports = Some(ports);
+		self
+	}
+
+	/// Announces the bound port to an address once the socket is built, by sending it a
+	/// [receive enable message](VMCReceiveEnable) (`/VMC/Ext/Rcv`) from the socket, and an
+	/// [option string](VMCSocketBuilder::announce_options) if set. Senders which support these messages can follow the
+	/// socket to whichever [fallback port](VMCSocketBuilder::fallback_ports) it ended up on.
+	///
+	/// Can be called multiple times to announce to multiple addresses. Note that host names are resolved immediately
+	/// (and thus may block); prefer passing IP addresses.
+	pub fn announce_port<A: ToSocketAddrs>(mut self, addr: A) -> Self {
+		self.announce.push(addr.to_socket_addrs().map(Iterator::collect));
+		self
+	}
+
+	/// Sets an [option string](VMCOptionString) (`/VMC/Ext/Opt`) sent along with [port
+	/// announcements](VMCSocketBuilder::announce_port).
+	pub fn announce_options(mut self, options: impl Into<String>) -> Self {
+		self.announce_options = Some(options.into());
+		self
+	}
+
+	/// Sets the address the socket sends to, by [connecting](VMCSocket::connect) it once bound. Defaults to
+	/// `127.0.0.1:39539` for [performers](VMCRole::Performer), and no target otherwise.
+	///
+	/// Note that host names are resolved immediately (and thus may block); prefer passing IP addresses.
+	pub fn target<A: ToSocketAddrs>(mut self, addr: A) -> Self {
+		self.target = Some(addr.to_socket_addrs().map(Iterator::collect));
+		self
+	}
+
+	/// Sets the size of the OS receive buffer (`SO_RCVBUF`) in bytes.
+	///
+	/// High-rate full-body tracking can overflow the default receive buffer, causing packets to be dropped; increasing
+	/// this can help if the application can't always keep up with incoming traffic. Note that the OS may clamp or
+	/// adjust the requested size.
+	pub fn recv_buffer_size(mut self, size: usize) -> Self {
+		self.recv_buffer_size = Some(size);
+		self
+	}
+
+	/// Sets the size of the OS send buffer (`SO_SNDBUF`) in bytes.
+	pub fn send_buffer_size(mut self, size: usize) -> Self {
+		self.send_buffer_size = Some(size);
+		self
+	}
+
+	/// Sets the value of the `SO_REUSEADDR` option, allowing multiple sockets to bind to the same address & port.
+	pub fn reuse_address(mut self, reuse: bool) -> Self {
+		self.reuse_address = reuse;
+		self
+	}
+
+	/// Sets the value of the `SO_REUSEPORT` option, allowing multiple sockets (potentially in different processes) to
+	/// bind to the same port.
+	///
+	/// This option is only supported on Unix platforms, and is ignored elsewhere.
+	pub fn reuse_port(mut self, reuse: bool) -> Self {
+		self.reuse_port = reuse;
+		self
+	}
+
+	/// Sets the value of the `SO_BROADCAST` option. See [`VMCSocket::set_broadcast`].
+	pub fn broadcast(mut self, broadcast: bool) -> Self {
+		self.broadcast = broadcast;
+		self
+	}
+
+	/// Sets the time-to-live of outgoing multicast packets (`IP_MULTICAST_TTL`, or `IPV6_MULTICAST_HOPS` for IPv6
+	/// sockets), i.e. how many network hops multicast packets can travel. Defaults to 1, which keeps packets within the
+	/// local network.
+	pub fn multicast_ttl(mut self, ttl: u32) -> Self {
+		self.multicast_ttl = Some(ttl);
+		self
+	}
+
+	/// Sets whether outgoing multicast packets are looped back to the local machine (`IP_MULTICAST_LOOP`, or
+	/// `IPV6_MULTICAST_LOOP` for IPv6 sockets).
+	pub fn multicast_loop(mut self, enabled: bool) -> Self {
+		self.multicast_loop = Some(enabled);
+		self
+	}
+
+	/// Sets the address of the local interface used to send outgoing IPv4 multicast packets (`IP_MULTICAST_IF`).
+	pub fn multicast_interface_v4(mut self, interface: Ipv4Addr) -> Self {
+		self.multicast_interface_v4 = Some(interface);
+		self
+	}
+
+	/// Sets the index of the local interface used to send outgoing IPv6 multicast packets (`IPV6_MULTICAST_IF`).
+	pub fn multicast_interface_v6(mut self, interface: u32) -> Self {
+		self.multicast_interface_v6 = Some(interface);
+		self
+	}
+
+	/// Joins an IPv4 multicast group after binding. See [`VMCSocket::join_multicast_v4`].
+	pub fn join_multicast_v4(mut self, multiaddr: Ipv4Addr, interface: Ipv4Addr) -> Self {
+		self.multicast_groups_v4.push((multiaddr, interface));
+		self
+	}
+
+	/// Joins an IPv6 multicast group after binding. See [`VMCSocket::join_multicast_v6`].
+	pub fn join_multicast_v6(mut self, multiaddr: Ipv6Addr, interface: u32) -> Self {
+		self.multicast_groups_v6.push((multiaddr, interface));
+		self
+	}
+
+	/// Only accept packets sent from the given IP address. See [`VMCSocket::allow_peer`].
+	pub fn allow_peer(mut self, addr: impl Into<IpAddr>) -> Self {
+		self.allowed_peers.push(addr.into());
+		self
+	}
+
+	/// Sets the limits applied when decoding received packets. See [`VMCSocket::set_decode_limits`].
+	pub fn decode_limits(mut self, limits: DecodeLimits) -> Self {
+		self.decode_limits = Some(limits);
+		self
+	}
+
+	/// Sets the options used to encode packets sent on the socket. See [`VMCSocket::set_encode_options`].
+	pub fn encode_options(mut self, options: EncodeOptions) -> Self {
+		self.encode_options = Some(options);
+		self
+	}
+
+	/// Binds the socket to a particular network interface by name, e.g. `"eth0"` (`SO_BINDTODEVICE`), so that only
+	/// packets received on that interface are processed.
+	#[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+	pub fn device(mut self, interface: impl AsRef<[u8]>) -> Self {
+		self.device = Some(interface.as_ref().to_vec());
+		self
+	}
+
+	/// Enables kernel receive timestamps (`SO_TIMESTAMP`), which are reported in [`VMCRecvTimestamp::kernel`].
+	///
+	/// [`VMCRecvTimestamp::kernel`]: crate::VMCRecvTimestamp::kernel
+	#[cfg(any(target_os = "android", target_os = "linux"))]
+	pub fn kernel_timestamps(mut self, enabled: bool) -> Self {
+		self.kernel_timestamps = enabled;
+		self
+	}
+
+	/// Creates and binds the socket with the configured options, then connects it to the target, if any.
+	pub async fn build(mut self) -> VMCResult<VMCSocket> {
+		let addrs = match self.bind.take() {
+			Some(addrs) => addrs?,
+			None => vec![self.default_bind()]
+		};
+
+		let mut last_err = None;
+		for addr in addrs {
+			let fallbacks = self.fallback_ports.clone().into_iter().flatten().filter(|port| *port != addr.port());
+			for port in std::iter::once(addr.port()).chain(fallbacks) {
+				match self.bind_addr(SocketAddr::new(addr.ip(), port)) {
+					Ok(socket) => return self.finish(socket).await,
+					Err(e) if e.kind() == io::ErrorKind::AddrInUse => last_err = Some(e),
+					Err(e) => {
+						last_err = Some(e);
+						break;
+					}
+				}
+			}
+		}
+		match (last_err, self.fallback_ports) {
+			(Some(e), Some(ports)) if e.kind() == io::ErrorKind::AddrInUse => Err(VMCError::PortsInUse(ports)),
+			(last_err, _) => Err(last_err
+				.unwrap_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "could not resolve to any address"))
+				.into())
+		}
+	}
+
+	/// Creates the socket from an already bound UDP socket, applying the configured options, then connects it to the
+	/// target, if any.
+	///
+	/// The bind address is ignored, as are [`reuse_address`](VMCSocketBuilder::reuse_address) &
+	/// [`reuse_port`](VMCSocketBuilder::reuse_port), which only take effect before binding. A
+	/// [`tokio::net::UdpSocket`] can be passed by first converting it with
+	/// [`into_std`](tokio::net::UdpSocket::into_std).
+	///
+	/// # Examples
+	///
+	/// ```no_run
+	/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+	/// use vmc::{VMCRole, VMCSocket};
+	///
+	/// let udp = std::net::UdpSocket::bind("0.0.0.0:0")?;
+	/// let performer = VMCSocket::builder()
+	/// 	.role(VMCRole::Performer)
+	/// 	.target("192.168.1.193:39539")
+	/// 	.send_buffer_size(1024 * 1024)
+	/// 	.from_socket(udp)
+	/// 	.await?;
+	/// # Ok(()) }) }
+	/// ```
+	pub async fn from_socket(self, socket: std::net::UdpSocket) -> VMCResult<VMCSocket> {
+		let socket = Socket::from(socket);
+		let addr = socket
+			.local_addr()?
+			.as_socket()
+			.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "socket is not an IP socket"))?;
+		self.configure(&socket, addr)?;
+		socket.set_nonblocking(true)?;
+		self.join_groups(&socket)?;
+		let socket = UdpSocket::from_std(socket.into())?;
+		self.finish(socket).await
+	}
+
+	fn default_bind(&self) -> SocketAddr {
+		let port = self.bind_port.unwrap_or_else(|| self.role.map_or(0, |role| role.default_port()));
+		let target = self.target.as_ref().and_then(|addrs| addrs.as_ref().ok()).and_then(|addrs| addrs.first());
+		let public = self.broadcast
+			|| !self.multicast_groups_v4.is_empty()
+			|| !self.multicast_groups_v6.is_empty()
+			|| target.is_some_and(|addr| !addr.ip().is_loopback());
+		let v6 = self.bind_any_v6 || target.map_or(self.multicast_groups_v4.is_empty() && !self.multicast_groups_v6.is_empty(), SocketAddr::is_ipv6);
+		let ip = match (self.bind_any || self.bind_any_v6 || (self.role.is_some() && public), v6) {
+			(true, true) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+			(true, false) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+			(false, _) => IpAddr::V4(Ipv4Addr::LOCALHOST)
+		};
+		SocketAddr::new(ip, port)
+	}
+
+	async fn finish(self, socket: UdpSocket) -> VMCResult<VMCSocket> {
+		let mut socket = VMCSocket::new(socket);
+		for peer in self.allowed_peers {
+			socket.allow_peer(peer);
+		}
+		if let Some(limits) = self.decode_limits {
+			socket.set_decode_limits(limits);
+		}
+		if let Some(options) = self.encode_options {
+			socket.set_encode_options(options);
+		}
+		let target = match (self.target, self.role) {
+			(Some(target), _) => Some(target?),
+			(None, Some(VMCRole::Performer)) => Some(vec![SocketAddr::from((Ipv4Addr::LOCALHOST, 39539))]),
+			(None, _) => None
+		};
+		if let Some(target) = target {
+			socket.connect(&target[..]).await?;
+		}
+
+		if !self.announce.is_empty() {
+			let local = socket.local_addr()?;
+			let mut announcement = VMCReceiveEnable::new(true, local.port());
+			if !local.ip().is_unspecified() {
+				announcement = announcement.with_addr(local.ip());
+			}
+			for addrs in self.announce {
+				// announce to the first address the socket can reach
+				let Some(addr) = addrs?.into_iter().find(|addr| addr.is_ipv4() == local.is_ipv4()) else {
+					continue;
+				};
+				socket.send_to(announcement.clone(), addr).await?;
+				if let Some(options) = &self.announce_options {
+					socket.send_to(VMCOptionString::new(options), addr).await?;
+				}
+			}
+		}
+		Ok(socket)
+	}
+
+	fn bind_addr(&self, addr: SocketAddr) -> io::Result<UdpSocket> {
+		let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+		if self.reuse_address {
+			socket.set_reuse_address(true)?;
+		}
+		#[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos", target_os = "cygwin", target_os = "nuttx"))))]
+		if self.reuse_port {
+			socket.set_reuse_port(true)?;
+		}
+		self.configure(&socket, addr)?;
+
+		socket.set_nonblocking(true)?;
+		socket.bind(&addr.into())?;
+		self.join_groups(&socket)?;
+
+		UdpSocket::from_std(socket.into())
+	}
+
+	/// Applies the options which can be set either before or after binding.
+	fn configure(&self, socket: &Socket, addr: SocketAddr) -> io::Result<()> {
+		if let Some(size) = self.recv_buffer_size {
+			socket.set_recv_buffer_size(size)?;
+		}
+		if let Some(size) = self.send_buffer_size {
+			socket.set_send_buffer_size(size)?;
+		}
+		if self.broadcast {
+			socket.set_broadcast(true)?;
+		}
+		#[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+		if let Some(device) = &self.device {
+			socket.bind_device(Some(device))?;
+		}
+		#[cfg(any(target_os = "android", target_os = "linux"))]
+		if self.kernel_timestamps {
+			set_timestamp(socket)?;
+		}
+
+		match addr {
+			SocketAddr::V4(_) => {
+				if let Some(ttl) = self.multicast_ttl {
+					socket.set_multicast_ttl_v4(ttl)?;
+				}
+				if let Some(enabled) = self.multicast_loop {
+					socket.set_multicast_loop_v4(enabled)?;
+				}
+				if let Some(interface) = self.multicast_interface_v4 {
+					socket.set_multicast_if_v4(&interface)?;
+				}
+			}
+			SocketAddr::V6(_) => {
+				if let Some(hops) = self.multicast_ttl {
+					socket.set_multicast_hops_v6(hops)?;
+				}
+				if let Some(enabled) = self.multicast_loop {
+					socket.set_multicast_loop_v6(enabled)?;
+				}
+				if let Some(interface) = self.multicast_interface_v6 {
+					socket.set_multicast_if_v6(interface)?;
+				}
+			}
+		}
+		Ok(())
+	}
+
+	fn join_groups(&self, socket: &Socket) -> io::Result<()> {
+		for (multiaddr, interface) in &self.multicast_groups_v4 {
+			socket.join_multicast_v4(multiaddr, interface)?;
+		}
+		for (multiaddr, interface) in &self.multicast_groups_v6 {
+			socket.join_multicast_v6(multiaddr, *interface)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(any(target_os = "android", target_os = "linux"))]
+fn set_timestamp(socket: &Socket) -> io::Result<()> {
+	use std::os::fd::AsRawFd;
+
+	let enabled: libc::c_int = 1;
+	// SAFETY: `enabled` is a valid `c_int` for the duration of the call.
+	let res = unsafe {
+		libc::setsockopt(
+			socket.as_raw_fd(),
+			libc::SOL_SOCKET,
+			libc::SO_TIMESTAMP,
+			(&enabled as *const libc::c_int).cast(),
+			std::mem::size_of::<libc::c_int>() as libc::socklen_t
+		)
+	};
+	if res < 0 { Err(io::Error::last_os_error()) } else { Ok(()) }
+}
@@ -0,0 +1,163 @@
+use super::VMCSender;
+use crate::{IntoOSCMessage, OSCPacket, VMCResult, osc, osc::OSCMessage, pose::pack_bundles};
+
+/// Wraps a [`VMCSender`] to pack all messages of a frame into as few bundles as possible.
+///
+/// Sending each bone transform in its own datagram wastes bandwidth on packet headers, and datagrams of a single frame
+/// may be reordered or partially dropped by the network. Instead, a `VMCBundledSender` queues messages until the frame
+/// is completed by a [`Time`](crate::VMCTime) message or an explicit [`flush`](VMCBundledSender::flush), then sends
+/// them packed into bundles which each fit within the configured maximum datagram size.
+///
+/// # Examples
+///
+/// ```no_run
+/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+/// use vmc::{Quat, VMCBoneTransform, VMCBundledSender, VMCStandardVRM0Bone, VMCTime, Vec3A};
+///
+/// let socket = vmc::performer!().await?;
+/// let mut sender = VMCBundledSender::new(socket.sender());
+/// sender
+/// 	.send(VMCBoneTransform::new(VMCStandardVRM0Bone::Hips, Vec3A::ZERO, Quat::IDENTITY))
+/// 	.await?;
+/// sender
+/// 	.send(VMCBoneTransform::new(VMCStandardVRM0Bone::Head, Vec3A::ZERO, Quat::IDENTITY))
+/// 	.await?;
+/// // sends both bones & the time in a single bundle
+/// sender.send(VMCTime::elapsed()).await?;
+/// # Ok(()) }) }
+/// ```
+#[derive(Debug)]
+pub struct VMCBundledSender {
+	sender: VMCSender,
+	max_size: usize,
+	pending: Vec<OSCMessage>
+}
+
+impl VMCBundledSender {
+	/// Wraps a sender, packing bundles within the [maximum UDP payload](osc::MAX_UDP_PAYLOAD) of an Ethernet frame.
+	pub fn new(sender: VMCSender) -> Self {
+		Self {
+			sender,
+			max_size: osc::MAX_UDP_PAYLOAD,
+			pending: Vec::new()
+		}
+	}
+
+	/// Sets the maximum size of each sent datagram, in bytes.
+	///
+	/// Messages which are larger than this on their own are still sent, in a datagram of their own.
+	pub fn with_mtu(mut self, max_size: usize) -> Self {
+		self.max_size = max_size;
+		self
+	}
+
+	/// Returns the maximum size of each sent datagram.
+	pub fn mtu(&self) -> usize {
+		self.max_size
+	}
+
+	/// Returns the number of queued messages.
+	pub fn pending(&self) -> usize {
+		self.pending.len()
+	}
+
+	/// Get a reference to the underlying sender.
+	pub fn get_ref(&self) -> &VMCSender {
+		&self.sender
+	}
+
+	/// Consumes the bundled sender, returning the underlying sender. Any queued messages are discarded.
+	pub fn into_inner(self) -> VMCSender {
+		self.sender
+	}
+
+	/// Queues a message. If the message is a [`Time`](crate::VMCTime) message, all queued messages are sent.
+	pub async fn send(&mut self, message: impl IntoOSCMessage) -> VMCResult<()> {
+		let message = message.into_osc_message();
+		let is_time = message.addr == "/VMC/Ext/T";
+		self.pending.push(message);
+		if is_time {
+			self.flush().await?;
+		}
+		Ok(())
+	}
+
+	/// Sends all queued messages.
+	///
+	/// If sending fails, the messages which weren't sent yet stay queued, so they are sent by the next flush.
+	pub async fn flush(&mut self) -> VMCResult<()> {
+		let mut packets = pack_bundles(std::mem::take(&mut self.pending), self.max_size).into_iter();
+		while let Some(packet) = packets.next() {
+			if let Err(e) = self.sender.send_packet(&packet).await {
+				self.pending = std::iter::once(packet).chain(packets).flat_map(OSCPacket::into_messages).collect();
+				return Err(e);
+			}
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{Quat, VMCBoneTransform, VMCMessage, VMCSocket, VMCStandardVRM0Bone, VMCTime, Vec3A};
+
+	#[tokio::test]
+	async fn test_bundled_sender() -> VMCResult<()> {
+		let mut marionette = VMCSocket::bind("127.0.0.1:0").await?;
+		let performer = VMCSocket::bind("127.0.0.1:0").await?;
+		performer.connect(marionette.local_addr()?).await?;
+
+		let mut sender = VMCBundledSender::new(performer.sender()).with_mtu(256);
+		for bone in [
+			VMCStandardVRM0Bone::Hips,
+			VMCStandardVRM0Bone::Spine,
+			VMCStandardVRM0Bone::Chest,
+			VMCStandardVRM0Bone::Neck,
+			VMCStandardVRM0Bone::Head
+		] {
+			sender.send(VMCBoneTransform::new(bone, Vec3A::ZERO, Quat::IDENTITY)).await?;
+		}
+		assert_eq!(sender.pending(), 5);
+		sender.send(VMCTime::new(1.0)).await?;
+		assert_eq!(sender.pending(), 0);
+
+		let packets = performer.stats().packets_sent();
+		assert!(packets > 1 && packets < 6);
+		let mut messages = Vec::new();
+		for _ in 0..packets {
+			messages.extend(marionette.recv_message().await?);
+		}
+		assert_eq!(messages.len(), 6);
+		assert!(matches!(&messages[5], VMCMessage::Time(time) if time.0 == 1.0));
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_bundled_sender_keeps_unsent() -> VMCResult<()> {
+		let mut marionette = VMCSocket::bind("127.0.0.1:0").await?;
+		let performer = VMCSocket::bind("127.0.0.1:0").await?;
+
+		// without a target, sending fails
+		let mut sender = VMCBundledSender::new(performer.sender()).with_mtu(128);
+		sender
+			.send(VMCBoneTransform::new(VMCStandardVRM0Bone::Hips, Vec3A::ZERO, Quat::IDENTITY))
+			.await?;
+		sender
+			.send(VMCBoneTransform::new(VMCStandardVRM0Bone::Head, Vec3A::ZERO, Quat::IDENTITY))
+			.await?;
+		assert!(sender.send(VMCTime::new(1.0)).await.is_err());
+		assert_eq!(sender.pending(), 3);
+
+		performer.connect(marionette.local_addr()?).await?;
+		sender.flush().await?;
+		assert_eq!(sender.pending(), 0);
+		let mut messages = Vec::new();
+		while messages.len() < 3 {
+			messages.extend(marionette.recv_message().await?);
+		}
+		assert!(matches!(&messages[0], VMCMessage::BoneTransform(transform) if transform.bone == VMCStandardVRM0Bone::Hips));
+		assert!(matches!(&messages[2], VMCMessage::Time(time) if time.0 == 1.0));
+		Ok(())
+	}
+}
@@ -0,0 +1,169 @@
+use std::{
+	future::{Future, poll_fn},
+	pin::pin,
+	task::Poll,
+	time::Duration
+};
+
+use tokio::{
+	sync::watch,
+	time::{self, MissedTickBehavior}
+};
+
+use super::VMCSender;
+use crate::{
+	message::{State, Time},
+	osc::OSCBundle
+};
+
+/// The default interval between keepalive packets.
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Keeps receivers informed that a performer is available by sending its [`State`](crate::VMCState) along with a
+/// [`Time`](crate::VMCTime) message at a regular interval, independently of the motion data.
+///
+/// Receivers treat a performer which stops sending `/VMC/Ext/OK` as gone. With a keepalive task running, application
+/// code only has to send bones & blend shapes, and receivers still see the performer as available while tracking is
+/// paused or lost. The state is sent as soon as it changes, and then again at every interval.
+///
+/// # Examples
+///
+/// ```no_run
+/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+/// use vmc::{VMCKeepalive, VMCModelState, VMCState};
+///
+/// let socket = vmc::performer!().await?;
+/// let (keepalive, task) = VMCKeepalive::new(socket.sender(), VMCState::new(VMCModelState::NotLoaded));
+/// tokio::spawn(task.run());
+///
+/// // ...load the avatar
+/// keepalive.set_state(VMCState::new(VMCModelState::Loaded));
+/// loop {
+/// 	// ...send bones & blend shapes
+/// }
+/// # Ok(()) }) }
+/// ```
+#[derive(Debug)]
+pub struct VMCKeepalive {
+	state: watch::Sender<State>
+}
+
+impl VMCKeepalive {
+	/// Creates a keepalive reporting `state`, returning it along with the task that sends it.
+	pub fn new(sender: VMCSender, state: State) -> (Self, VMCKeepaliveTask) {
+		let (tx, rx) = watch::channel(state);
+		(
+			Self { state: tx },
+			VMCKeepaliveTask {
+				sender,
+				state: rx,
+				interval: DEFAULT_INTERVAL
+			}
+		)
+	}
+
+	/// Replaces the reported state. The new state is sent right away.
+	pub fn set_state(&self, state: State) {
+		self.state.send_replace(state);
+	}
+
+	/// Returns the reported state.
+	pub fn state(&self) -> State {
+		self.state.borrow().clone()
+	}
+}
+
+/// Sends the state reported by a [`VMCKeepalive`], created by [`VMCKeepalive::new`].
+///
+/// The task does nothing until [`run`](VMCKeepaliveTask::run) is spawned on the runtime.
+#[derive(Debug)]
+pub struct VMCKeepaliveTask {
+	sender: VMCSender,
+	state: watch::Receiver<State>,
+	interval: Duration
+}
+
+impl VMCKeepaliveTask {
+	/// Sets the interval between keepalive packets. Defaults to one second.
+	///
+	/// # Panics
+	///
+	/// Panics if `interval` is zero.
+	pub fn with_interval(mut self, interval: Duration) -> Self {
+		assert!(!interval.is_zero(), "keepalive interval must not be zero");
+		self.interval = interval;
+		self
+	}
+
+	/// Returns the interval between keepalive packets.
+	pub fn interval(&self) -> Duration {
+		self.interval
+	}
+
+	/// Sends the state & time in a bundle at every interval, and whenever the state changes, until the
+	/// [`VMCKeepalive`] is dropped.
+	///
+	/// Sending errors don't stop the task, so that it recovers once the target becomes reachable again.
+	pub async fn run(mut self) {
+		let mut interval = time::interval(self.interval);
+		interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+		loop {
+			let changed = {
+				let mut changed = pin!(self.state.changed());
+				poll_fn(|cx| {
+					if let Poll::Ready(changed) = changed.as_mut().poll(cx) {
+						return Poll::Ready(Some(changed));
+					}
+					interval.poll_tick(cx).map(|_| None)
+				})
+				.await
+			};
+			match changed {
+				Some(Err(_)) => return,
+				Some(Ok(())) => interval.reset(),
+				None => {}
+			}
+			let state = self.state.borrow_and_update().clone();
+			let _ = self.sender.send(OSCBundle::builder().push(state).push(Time::elapsed()).build()).await;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{VMCMessage, VMCResult, VMCSocket, message::ModelState, osc::OSCPacket};
+
+	fn parse_state(packet: OSCPacket) -> (State, bool) {
+		match &crate::parse(packet).unwrap()[..] {
+			[VMCMessage::State(state), time] => (state.clone(), matches!(time, VMCMessage::Time(_))),
+			messages => panic!("unexpected keepalive: {messages:?}")
+		}
+	}
+
+	#[tokio::test]
+	async fn test_keepalive() -> VMCResult<()> {
+		let mut marionette = VMCSocket::bind("127.0.0.1:0").await?;
+		let performer = VMCSocket::bind("127.0.0.1:0").await?;
+		performer.connect(marionette.local_addr()?).await?;
+
+		let (keepalive, task) = VMCKeepalive::new(performer.sender(), State::new(ModelState::NotLoaded));
+		let task = tokio::spawn(task.with_interval(Duration::from_millis(20)).run());
+		for _ in 0..2 {
+			assert_eq!(parse_state(marionette.recv().await?), (State::new(ModelState::NotLoaded), true));
+		}
+
+		keepalive.set_state(State::new(ModelState::Loaded));
+		let mut state = parse_state(marionette.recv().await?).0;
+		// a tick may already have been in flight
+		if state.model_state == ModelState::NotLoaded {
+			state = parse_state(marionette.recv().await?).0;
+		}
+		assert_eq!(state, State::new(ModelState::Loaded));
+
+		// dropping the keepalive stops the task
+		drop(keepalive);
+		task.await.unwrap();
+		Ok(())
+	}
+}
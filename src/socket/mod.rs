@@ -0,0 +1,1858 @@
+use std::{
+	collections::HashSet,
+	future::poll_fn,
+	io,
+	net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+	pin::Pin,
+	sync::Arc,
+	task::{Context, Poll, ready},
+	time::{Duration, Instant}
+};
+
+use bytes::Bytes;
+use futures_core::Stream;
+use futures_sink::Sink;
+use smallvec::SmallVec;
+use socket2::SockRef;
+#[cfg(any(target_os = "android", target_os = "linux"))]
+use tokio::io::Interest;
+use tokio::net::{ToSocketAddrs, UdpSocket};
+
+mod builder;
+mod bundled;
+mod keepalive;
+mod peer;
+mod pool;
+mod queued;
+mod stats;
+mod status;
+mod throttle;
+mod timestamp;
+
+pub use self::{
+	builder::{VMCRole, VMCSocketBuilder},
+	bundled::VMCBundledSender,
+	keepalive::{VMCKeepalive, VMCKeepaliveTask},
+	queued::{VMCQueuedSender, VMCSendQueueStats, VMCSendTask},
+	stats::VMCSocketStats,
+	status::VMCPeerStatus,
+	throttle::VMCThrottledSender,
+	timestamp::VMCRecvTimestamp
+};
+use self::{peer::LastPeer, pool::BufferPool};
+use crate::{
+	IntoOSCPacket, OSCPacket, VMCError, VMCFrames, VMCMessage, VMCMessages, VMCModelState, VMCPose, VMCRequestInfo, VMCResult, VMCState,
+	osc::{self, DecodeLimits, EncodeOptions},
+	parse_datagram_with_limits,
+	stream::{Scheduled, Timestamped},
+	udp::{Datagram, UDPSocketStream}
+};
+
+/// A UDP socket to send and receive VMC messages.
+#[derive(Debug)]
+pub struct VMCSocket {
+	receiver: VMCReceiver,
+	sender: VMCSender
+}
+
+impl VMCSocket {
+	/// Creates a new OSC socket from a [`tokio::net::UdpSocket`].
+	pub fn new(socket: UdpSocket) -> Self {
+		let socket = UDPSocketStream::new(socket);
+		let stats = Arc::new(VMCSocketStats::default());
+		let last_peer = Arc::new(LastPeer::default());
+		let sender = VMCSender::new(socket.clone_inner(), Arc::clone(&stats), Arc::clone(&last_peer));
+		Self {
+			receiver: VMCReceiver {
+				socket,
+				allowed_peers: None,
+				decode_limits: DecodeLimits::default(),
+				raw_batch: Vec::new(),
+				stats,
+				last_peer
+			},
+			sender
+		}
+	}
+
+	/// Creates an VMC socket from the given address.
+	///
+	/// Binding with a port number of 0 will request that the OS assigns a port to this socket.
+	/// The port allocated can be queried via [`local_addr`] method.
+	///
+	/// [`local_addr`]: #method.local_addr
+	pub async fn bind<A: ToSocketAddrs>(addr: A) -> VMCResult<Self> {
+		let socket = UdpSocket::bind(addr).await?;
+		Ok(Self::new(socket))
+	}
+
+	/// Creates a [`VMCSocketBuilder`], which can be used to configure OS-level socket options before binding.
+	///
+	/// # Examples
+	///
+	/// ```no_run
+	/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+	/// use vmc::VMCSocket;
+	///
+	/// let socket = VMCSocket::builder()
+	/// 	.bind("0.0.0.0:39539")
+	/// 	.recv_buffer_size(4 * 1024 * 1024)
+	/// 	.reuse_address(true)
+	/// 	.build()
+	/// 	.await?;
+	/// # Ok(()) }) }
+	/// ```
+	pub fn builder() -> VMCSocketBuilder {
+		VMCSocketBuilder::new()
+	}
+
+	/// Connects the UDP socket to a remote address.
+	///
+	/// When connected, only messages from this address will be received and the [`send`] method
+	/// will use the specified address for sending.
+	///
+	/// [`send`]: #method.send
+	///
+	/// # Examples
+	///
+	/// ```no_run
+	/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+	/// use vmc::VMCSocket;
+	///
+	/// let socket = VMCSocket::bind("127.0.0.1:0").await?;
+	/// socket.connect("127.0.0.1:8080").await?;
+	/// # Ok(()) }) }
+	/// ```
+	pub async fn connect<A: ToSocketAddrs>(&self, addrs: A) -> VMCResult<()> {
+		let mut last_err = None;
+		for addr in tokio::net::lookup_host(addrs).await? {
+			check_broadcast(SockRef::from(self.socket()), addr)?;
+			match self.socket().connect(addr).await {
+				Ok(()) => return Ok(()),
+				Err(e) => last_err = Some(map_broadcast_err(SockRef::from(self.socket()), addr, e))
+			}
+		}
+		Err(last_err.unwrap_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "could not resolve to any address").into()))
+	}
+
+	/// Checks whether the target the socket is [connected](VMCSocket::connect) to is running, since sending over UDP
+	/// succeeds whether or not anything receives it.
+	///
+	/// Sends a [request for information](VMCRequestInfo) (`/VMC/Ext/Set/Req`) and waits up to `timeout` for any
+	/// traffic from the target. Applications which answer requests or are already sending (like a performer sending
+	/// motion data) are reported as [responding](VMCPeerStatus::Responding); packets received after the request is sent
+	/// are left to be received as usual. If the target's host reports that nothing listens on the target port, which is
+	/// typical when the target application isn't running on a reachable host, the target is reported as
+	/// [refused](VMCPeerStatus::Refused).
+	///
+	/// Packets already waiting to be received when the probe starts may have been sent long before the target stopped,
+	/// so they are discarded rather than being taken as a sign of life. They aren't counted in the socket's
+	/// [statistics](VMCSocket::stats).
+	///
+	/// Fails if the socket isn't connected.
+	///
+	/// # Examples
+	///
+	/// ```no_run
+	/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+	/// use std::time::Duration;
+	///
+	/// use vmc::VMCPeerStatus;
+	///
+	/// let socket = vmc::performer!("192.168.1.193:39539").await?;
+	/// match socket.probe(Duration::from_millis(500)).await? {
+	/// 	VMCPeerStatus::Responding { .. } => println!("connected"),
+	/// 	VMCPeerStatus::NotResponding => println!("not responding"),
+	/// 	VMCPeerStatus::Refused => println!("marionette isn't running")
+	/// }
+	/// # Ok(()) }) }
+	/// ```
+	pub async fn probe(&self, timeout: Duration) -> VMCResult<VMCPeerStatus> {
+		self.socket().peer_addr()?;
+		// discard stale packets, along with any error reported for an earlier send
+		let mut buf = [0; 1];
+		while !matches!(self.socket().try_recv(&mut buf), Err(e) if e.kind() == io::ErrorKind::WouldBlock) {}
+
+		let start = Instant::now();
+		match self.send(VMCRequestInfo).await {
+			Err(VMCError::Io(e)) if is_refused(&e) => return Ok(VMCPeerStatus::Refused),
+			res => res?
+		}
+		match tokio::time::timeout(timeout, self.socket().peek_sender()).await {
+			Ok(Ok(_)) => Ok(VMCPeerStatus::Responding { elapsed: start.elapsed() }),
+			Ok(Err(e)) if is_refused(&e) => {
+				// the error is reported once; clear it so it doesn't surface on the next receive
+				let _ = self.socket().take_error();
+				Ok(VMCPeerStatus::Refused)
+			}
+			Ok(Err(e)) => Err(e.into()),
+			Err(_) => Ok(VMCPeerStatus::NotResponding)
+		}
+	}
+
+	/// Sets the value of the `SO_BROADCAST` option for this socket.
+	///
+	/// When enabled, this socket is allowed to send packets to a broadcast address, e.g. `255.255.255.255:39539` or a
+	/// subnet broadcast address like `192.168.1.255:39539`. Attempting to send to a broadcast address without enabling
+	/// this option will return [`VMCError::BroadcastNotEnabled`].
+	///
+	/// Note that the socket must not be bound to a loopback address in order to reach other machines; see the
+	/// `broadcast` option of [`performer!`](crate::performer) to set this up automatically.
+	///
+	/// # Examples
+	///
+	/// ```no_run
+	/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+	/// use vmc::{VMCSocket, VMCTime};
+	///
+	/// let socket = VMCSocket::bind("0.0.0.0:0").await?;
+	/// socket.set_broadcast(true)?;
+	/// socket.send_to(VMCTime::elapsed(), "255.255.255.255:39539").await?;
+	/// # Ok(()) }) }
+	/// ```
+	pub fn set_broadcast(&self, on: bool) -> VMCResult<()> {
+		self.socket().set_broadcast(on)?;
+		Ok(())
+	}
+
+	/// Gets the value of the `SO_BROADCAST` option for this socket.
+	///
+	/// See [`VMCSocket::set_broadcast`].
+	pub fn broadcast(&self) -> VMCResult<bool> {
+		Ok(self.socket().broadcast()?)
+	}
+
+	/// Joins a multicast group using the `IP_ADD_MEMBERSHIP` option on this socket.
+	///
+	/// `interface` is the address of the local interface with which the system should join the multicast group; if it
+	/// is [`Ipv4Addr::UNSPECIFIED`], an appropriate interface is chosen by the system.
+	///
+	/// To receive multicast traffic, the socket should be bound to the unspecified address (`0.0.0.0`) rather than
+	/// localhost; see the `multicast` option of [`marionette!`](crate::marionette) to set this up automatically.
+	///
+	/// # Examples
+	///
+	/// ```no_run
+	/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+	/// use std::net::Ipv4Addr;
+	///
+	/// use vmc::VMCSocket;
+	///
+	/// let socket = VMCSocket::bind("0.0.0.0:39539").await?;
+	/// socket.join_multicast_v4(Ipv4Addr::new(239, 0, 0, 1), Ipv4Addr::UNSPECIFIED)?;
+	/// # Ok(()) }) }
+	/// ```
+	pub fn join_multicast_v4(&self, multiaddr: Ipv4Addr, interface: Ipv4Addr) -> VMCResult<()> {
+		self.socket().join_multicast_v4(multiaddr, interface)?;
+		Ok(())
+	}
+
+	/// Joins a multicast group using the `IPV6_ADD_MEMBERSHIP` option on this socket.
+	///
+	/// `interface` is the index of the interface to join/leave (or 0 to indicate any interface).
+	pub fn join_multicast_v6(&self, multiaddr: &Ipv6Addr, interface: u32) -> VMCResult<()> {
+		self.socket().join_multicast_v6(multiaddr, interface)?;
+		Ok(())
+	}
+
+	/// Leaves a multicast group previously joined with [`VMCSocket::join_multicast_v4`].
+	pub fn leave_multicast_v4(&self, multiaddr: Ipv4Addr, interface: Ipv4Addr) -> VMCResult<()> {
+		self.socket().leave_multicast_v4(multiaddr, interface)?;
+		Ok(())
+	}
+
+	/// Leaves a multicast group previously joined with [`VMCSocket::join_multicast_v6`].
+	pub fn leave_multicast_v6(&self, multiaddr: &Ipv6Addr, interface: u32) -> VMCResult<()> {
+		self.socket().leave_multicast_v6(multiaddr, interface)?;
+		Ok(())
+	}
+
+	/// Sends an OSC packet on the socket to the given address.
+	///
+	/// If `addrs` resolves to multiple addresses, each is tried in turn until the packet is sent to one of them.
+	///
+	/// # Examples
+	///
+	/// ```no_run
+	/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+	/// use vmc::{VMCBlendShape, VMCSocket, VMCStandardVRMBlendShape};
+	///
+	/// let socket = VMCSocket::bind("127.0.0.1:0").await?;
+	/// let addr = "127.0.0.1:39539";
+	/// let message = VMCBlendShape::new(VMCStandardVRMBlendShape::Joy, 1.0);
+	/// socket.send_to(message, &addr).await?;
+	/// # Ok(()) }) }
+	/// ```
+	pub async fn send_to<A: ToSocketAddrs, P: IntoOSCPacket>(&self, packet: P, addrs: A) -> VMCResult<()> {
+		self.sender.send_to(packet, addrs).await
+	}
+
+	/// Sends a packet on the socket to the remote address to which it is connected.
+	///
+	/// The [`connect`] method will connect this socket to a remote address.
+	/// This method will fail if the socket is not connected.
+	///
+	/// [`connect`]: #method.connect
+	///
+	/// # Examples
+	///
+	/// ```no_run
+	/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+	/// use vmc::{VMCBlendShape, VMCSocket, VMCStandardVRMBlendShape};
+	///
+	/// let socket = VMCSocket::bind("127.0.0.1:2434").await?;
+	/// socket.connect("127.0.0.1:39539").await?;
+	/// socket.send(VMCBlendShape::new(VMCStandardVRMBlendShape::Joy, 1.0)).await?;
+	/// #
+	/// # Ok(()) }) }
+	/// ```
+	pub async fn send<P: IntoOSCPacket>(&self, packet: P) -> VMCResult<()> {
+		self.sender.send(packet).await
+	}
+
+	/// Sends a packet on the connected socket, encoding it into the given buffer.
+	///
+	/// The buffer is cleared before encoding. Reusing the same buffer across calls means sending won't allocate once
+	/// the buffer has grown to fit the largest packet, even when many tasks share the socket.
+	///
+	/// # Examples
+	///
+	/// ```no_run
+	/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+	/// use vmc::{Quat, VMCBoneTransform, VMCSocket, VMCStandardVRM0Bone, Vec3A};
+	///
+	/// let socket = VMCSocket::bind("127.0.0.1:0").await?;
+	/// socket.connect("127.0.0.1:39539").await?;
+	/// let mut buf = Vec::new();
+	/// loop {
+	/// 	socket
+	/// 		.send_with_buf(VMCBoneTransform::new(VMCStandardVRM0Bone::Head, Vec3A::ZERO, Quat::IDENTITY), &mut buf)
+	/// 		.await?;
+	/// }
+	/// # Ok(()) }) }
+	/// ```
+	pub async fn send_with_buf<P: IntoOSCPacket>(&self, packet: P, buf: &mut Vec<u8>) -> VMCResult<()> {
+		self.sender.send_with_buf(packet, buf).await
+	}
+
+	/// Sends multiple packets on the connected socket, each as its own datagram.
+	///
+	/// On Linux, the packets are submitted with as few `sendmmsg` calls as possible, which is considerably cheaper than
+	/// one [`send`] per packet when sending e.g. every bone of an avatar each frame. On other platforms, the packets
+	/// are sent one after another.
+	///
+	/// If sending fails partway through, the packets before the failed one will have been sent.
+	///
+	/// [`send`]: #method.send
+	///
+	/// # Examples
+	///
+	/// ```no_run
+	/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+	/// use vmc::{Quat, VMCBoneTransform, VMCSocket, VMCStandardVRM0Bone, Vec3A};
+	///
+	/// let socket = VMCSocket::bind("127.0.0.1:0").await?;
+	/// socket.connect("127.0.0.1:39539").await?;
+	/// let bones = [VMCStandardVRM0Bone::Hips, VMCStandardVRM0Bone::Spine, VMCStandardVRM0Bone::Head];
+	/// socket
+	/// 	.send_batch(bones.map(|bone| VMCBoneTransform::new(bone, Vec3A::ZERO, Quat::IDENTITY)))
+	/// 	.await?;
+	/// # Ok(()) }) }
+	/// ```
+	pub async fn send_batch<I>(&self, packets: I) -> VMCResult<()>
+	where
+		I: IntoIterator,
+		I::Item: IntoOSCPacket
+	{
+		self.sender.send_batch(packets).await
+	}
+
+	/// Sends a complete [`VMCPose`] on the connected socket.
+	///
+	/// See [`VMCPose::to_packets`] for details on how the pose is encoded. Like [`send`], this method will fail if the
+	/// socket is not connected.
+	///
+	/// [`send`]: #method.send
+	pub async fn send_pose(&self, pose: &VMCPose) -> VMCResult<()> {
+		self.sender.send_pose(pose).await
+	}
+
+	/// Sends a packet to the peer which sent the most recently received packet, e.g. to answer a request on an
+	/// unconnected marionette socket which receives from any performer.
+	///
+	/// The peer is tracked across all receivers & senders of this socket, so a sender split off with
+	/// [`into_split`](VMCSocket::into_split) replies to the peer of the last packet its receiver received. Fails if no
+	/// packet has been received yet.
+	///
+	/// # Examples
+	///
+	/// ```no_run
+	/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+	/// use vmc::{OSCPacket, VMCReceiveEnable};
+	///
+	/// let mut socket = vmc::marionette!(bind_any).await?;
+	/// loop {
+	/// 	if let OSCPacket::Message(message) = socket.recv().await? {
+	/// 		if message.addr == "/VMC/Ext/Set/Req" {
+	/// 			socket.reply(VMCReceiveEnable::new(true, 39539)).await?;
+	/// 		}
+	/// 	}
+	/// }
+	/// # Ok(()) }) }
+	/// ```
+	pub async fn reply<P: IntoOSCPacket>(&self, packet: P) -> VMCResult<()> {
+		self.sender.reply(packet).await
+	}
+
+	/// Returns the address of the peer which sent the most recently received packet, which [`reply`](VMCSocket::reply)
+	/// sends to.
+	pub fn last_peer(&self) -> Option<SocketAddr> {
+		self.receiver.last_peer()
+	}
+
+	/// Receives a single OSC packet on the socket.
+	///
+	/// # Examples
+	///
+	/// ```no_run
+	/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+	/// let mut socket = vmc::marionette!().await?;
+	/// let packet = socket.recv().await?;
+	/// println!("{:?}", packet);
+	/// # Ok(()) }) }
+	/// ```
+	pub async fn recv(&mut self) -> VMCResult<OSCPacket> {
+		self.receiver.recv().await
+	}
+
+	/// Receives a single OSC packet on the socket, returning the packet and the address of the peer that sent it.
+	pub async fn recv_from(&mut self) -> VMCResult<(OSCPacket, SocketAddr)> {
+		self.receiver.recv_from().await
+	}
+
+	/// Receives a single OSC packet on the socket, returning the packet, the address of the peer that sent it, and the
+	/// time at which it was received.
+	///
+	/// The receive time is taken as soon as the packet is read from the socket, so it can be used to precisely measure
+	/// latency & jitter. For even more precision, kernel timestamps can be enabled on Linux via
+	/// [`VMCSocketBuilder::kernel_timestamps`].
+	///
+	/// # Examples
+	///
+	/// ```no_run
+	/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+	/// let mut socket = vmc::marionette!().await?;
+	/// let mut last = None;
+	/// loop {
+	/// 	let (_, _, timestamp) = socket.recv_timestamped().await?;
+	/// 	if let Some(last) = last.replace(timestamp.received) {
+	/// 		println!("interval: {:?}", timestamp.received - last);
+	/// 	}
+	/// }
+	/// # Ok(()) }) }
+	/// ```
+	pub async fn recv_timestamped(&mut self) -> VMCResult<(OSCPacket, SocketAddr, VMCRecvTimestamp)> {
+		self.receiver.recv_timestamped().await
+	}
+
+	/// Receives a single datagram on the socket without decoding it, returning the raw datagram and the address of the
+	/// peer that sent it.
+	///
+	/// The [peer allowlist](VMCSocket::allow_peer) still applies. The returned [`Bytes`] can be cheaply cloned, which
+	/// makes this useful for forwarding packets to multiple destinations without re-encoding them. Use
+	/// [`osc::decode_udp_bytes`] to decode the datagram.
+	///
+	/// Datagrams are received directly into a buffer owned by the socket, which is reused for later datagrams once all
+	/// handles to it are dropped. Holding on to a datagram for a long time keeps that buffer alive, so copy it out with
+	/// [`Bytes::copy_from_slice`] if it needs to be kept around.
+	pub async fn recv_raw(&mut self) -> VMCResult<(Bytes, SocketAddr)> {
+		self.receiver.recv_raw().await
+	}
+
+	/// Receives up to `max` OSC packets at once, appending them to `batch` along with the address of the peer that sent
+	/// each and the time at which it was received. Returns the number of packets received.
+	///
+	/// This waits until at least one packet is available, then takes every packet that is already queued on the socket
+	/// (up to `max`) without waiting further. On Linux, they are received with as few `recvmmsg` calls as possible,
+	/// which saves a lot of wakeups & syscalls when receiving from many performers at once. Reusing `batch` across
+	/// calls avoids reallocating it.
+	///
+	/// Unlike [`recv`], packets which fail to decode are skipped rather than returned as an error; they are still
+	/// counted in [`VMCSocketStats::decode_errors`]. Use [`recv_raw_batch`] to handle them yourself.
+	///
+	/// [`recv`]: #method.recv
+	/// [`recv_raw_batch`]: #method.recv_raw_batch
+	///
+	/// # Examples
+	///
+	/// ```no_run
+	/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+	/// let mut socket = vmc::marionette!().await?;
+	/// let mut batch = Vec::new();
+	/// loop {
+	/// 	socket.recv_batch(&mut batch, 64).await?;
+	/// 	for (packet, peer, _) in batch.drain(..) {
+	/// 		println!("{peer}: {packet:?}");
+	/// 	}
+	/// }
+	/// # Ok(()) }) }
+	/// ```
+	pub async fn recv_batch(&mut self, batch: &mut Vec<(OSCPacket, SocketAddr, VMCRecvTimestamp)>, max: usize) -> VMCResult<usize> {
+		self.receiver.recv_batch(batch, max).await
+	}
+
+	/// Receives up to `max` datagrams at once without decoding them, appending them to `batch`. Returns the number of
+	/// datagrams received.
+	///
+	/// See [`recv_batch`] & [`recv_raw`].
+	///
+	/// [`recv_batch`]: #method.recv_batch
+	/// [`recv_raw`]: #method.recv_raw
+	pub async fn recv_raw_batch(&mut self, batch: &mut Vec<(Bytes, SocketAddr, VMCRecvTimestamp)>, max: usize) -> VMCResult<usize> {
+		self.receiver.recv_raw_batch(batch, max).await
+	}
+
+	/// Returns a stream of packets received on this socket, each paired with the address of the peer that sent it and
+	/// the time at which it was received.
+	///
+	/// See [`VMCSocket::recv_timestamped`].
+	pub fn timestamped(&mut self) -> Timestamped<'_> {
+		self.receiver.timestamped()
+	}
+
+	/// Returns a stream of packets received on this socket which holds back bundles until the time given by their time
+	/// tag.
+	///
+	/// See [`Scheduled`] for details.
+	pub fn scheduled(&mut self) -> Scheduled<&mut Self> {
+		Scheduled::new(self)
+	}
+
+	/// Receives a single OSC packet on the socket and [parses](crate::parse) it into its contained [`VMCMessage`]s.
+	///
+	/// The packet is parsed straight from the received datagram with [`parse_datagram`](crate::parse_datagram), so
+	/// standard & previously seen bone and blend shape names are received without allocating.
+	///
+	/// # Examples
+	///
+	/// ```no_run
+	/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+	/// use vmc::{VMCMessage, VMCModelState, VMCState};
+	///
+	/// let mut socket = vmc::performer!().await?;
+	/// socket.send(VMCState::new(VMCModelState::Loaded)).await?;
+	/// for message in socket.recv_message().await? {
+	/// 	if let VMCMessage::State(state) = message {
+	/// 		println!("{:?}", state.model_state);
+	/// 	}
+	/// }
+	/// # Ok(()) }) }
+	/// ```
+	pub async fn recv_message(&mut self) -> VMCResult<Vec<VMCMessage>> {
+		self.receiver.recv_message().await
+	}
+
+	/// Returns a stream of parsed [`VMCMessage`]s received on this socket, along with the address of the peer that sent
+	/// each message.
+	///
+	/// This is equivalent to decoding each packet received by this socket's [`Stream`] implementation and passing it to
+	/// [`parse`](crate::parse).
+	///
+	/// # Examples
+	///
+	/// ```no_run
+	/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+	/// use futures_util::StreamExt;
+	/// use vmc::VMCMessage;
+	///
+	/// let mut socket = vmc::marionette!().await?;
+	/// let mut messages = socket.messages();
+	/// while let Some(message) = messages.next().await {
+	/// 	let (message, _) = message?;
+	/// 	if let VMCMessage::Time(time) = message {
+	/// 		println!("Render all (time: {})", time.0);
+	/// 	}
+	/// }
+	/// # Ok(()) }) }
+	/// ```
+	pub fn messages(&mut self) -> VMCMessages<&mut Self> {
+		VMCMessages::new(self)
+	}
+
+	/// Returns a stream of complete [frames](crate::VMCFrame) received on this socket, each containing all messages
+	/// sent by a peer before a [`Time`](crate::VMCTime) message.
+	///
+	/// See [`VMCFrames`] for details.
+	pub fn frames(&mut self) -> VMCFrames<VMCMessages<&mut Self>> {
+		self.messages().frames()
+	}
+
+	/// Only accept packets sent from the given IP address.
+	///
+	/// By default, a socket accepts packets from any peer. Once a peer is added to the allowlist, packets from any
+	/// address not in the allowlist are silently dropped before they are decoded. This is useful for unconnected
+	/// marionette sockets on a shared network, where anyone on the LAN could otherwise send motion data.
+	///
+	/// # Examples
+	///
+	/// ```no_run
+	/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+	/// use std::net::Ipv4Addr;
+	///
+	/// let mut socket = vmc::marionette!("0.0.0.0:39539").await?;
+	/// socket.allow_peer(Ipv4Addr::new(192, 168, 1, 20));
+	/// socket.allow_peer(Ipv4Addr::new(192, 168, 1, 21));
+	/// # Ok(()) }) }
+	/// ```
+	pub fn allow_peer(&mut self, addr: impl Into<IpAddr>) {
+		self.receiver.allow_peer(addr);
+	}
+
+	/// Returns the set of peers this socket accepts packets from, or `None` if packets from any peer are accepted.
+	///
+	/// See [`VMCSocket::allow_peer`].
+	pub fn allowed_peers(&self) -> Option<&HashSet<IpAddr>> {
+		self.receiver.allowed_peers()
+	}
+
+	/// Clears the peer allowlist, accepting packets from any peer again.
+	///
+	/// See [`VMCSocket::allow_peer`].
+	pub fn clear_allowed_peers(&mut self) {
+		self.receiver.clear_allowed_peers();
+	}
+
+	/// Sets the limits applied when decoding received packets, such as the maximum bundle nesting depth or blob size.
+	///
+	/// Packets exceeding the limits are rejected with [`OSCError::LimitExceeded`](crate::osc::OSCError::LimitExceeded)
+	/// (wrapped in [`OSCError::Decode`](crate::osc::OSCError::Decode)) and counted as decode errors. The [default
+	/// limits](DecodeLimits::default) are suitable for sockets exposed to untrusted peers.
+	pub fn set_decode_limits(&mut self, limits: DecodeLimits) {
+		self.receiver.set_decode_limits(limits);
+	}
+
+	/// Returns the limits applied when decoding received packets.
+	pub fn decode_limits(&self) -> &DecodeLimits {
+		self.receiver.decode_limits()
+	}
+
+	/// Sets the options used to encode packets sent on this socket, e.g. how non-finite floats are handled.
+	///
+	/// Senders created afterwards with [`VMCSocket::sender`] inherit these options.
+	///
+	/// # Examples
+	///
+	/// ```no_run
+	/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+	/// use vmc::osc::{EncodeOptions, NonFinitePolicy};
+	///
+	/// let mut socket = vmc::performer!().await?;
+	/// socket.set_encode_options(EncodeOptions::new().with_non_finite(NonFinitePolicy::Zero));
+	/// # Ok(()) }) }
+	/// ```
+	pub fn set_encode_options(&mut self, options: EncodeOptions) {
+		self.sender.set_encode_options(options);
+	}
+
+	/// Returns the options used to encode packets sent on this socket.
+	pub fn encode_options(&self) -> &EncodeOptions {
+		self.sender.encode_options()
+	}
+
+	/// Sets the state sent by [`VMCSocket::close`] to tell receivers that the performer is exiting, or `None` to send
+	/// nothing. Defaults to [`VMCModelState::NotLoaded`].
+	///
+	/// Senders created afterwards with [`VMCSocket::sender`] inherit the final state.
+	pub fn set_final_state(&mut self, state: Option<VMCState>) {
+		self.sender.set_final_state(state);
+	}
+
+	/// Returns the state sent by [`VMCSocket::close`].
+	pub fn final_state(&self) -> Option<&VMCState> {
+		self.sender.final_state()
+	}
+
+	/// Closes the socket, first sending any packet still buffered by the [`Sink`] implementation, followed by the
+	/// [final state](VMCSocket::set_final_state) so receivers notice right away that the performer is exiting instead
+	/// of waiting for it to time out.
+	///
+	/// The final state is only sent if the socket is [connected](VMCSocket::connect). Other senders of the socket, like
+	/// a running [`VMCKeepalive`] task, should be stopped beforehand, or they may announce the performer again.
+	///
+	/// Simply dropping the socket never blocks, so it can't wait to send the final state; a buffered packet is still
+	/// sent if the socket can take it immediately.
+	///
+	/// # Examples
+	///
+	/// ```no_run
+	/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+	/// let socket = vmc::performer!().await?;
+	/// // ...send bones & blend shapes
+	/// socket.close().await?;
+	/// # Ok(()) }) }
+	/// ```
+	pub async fn close(self) -> VMCResult<()> {
+		self.sender.close().await
+	}
+
+	/// Returns the statistics counters for this socket, which are shared with all of its senders and receivers.
+	pub fn stats(&self) -> &VMCSocketStats {
+		self.receiver.stats()
+	}
+
+	/// Create a standalone sender for this socket.
+	///
+	/// The sender can be moved to other threads or tasks.
+	pub fn sender(&self) -> VMCSender {
+		self.sender.clone()
+	}
+
+	/// Splits this socket into owned receive and send halves, which can be moved to separate tasks.
+	///
+	/// Both halves share the same underlying [`UdpSocket`]; further senders can be created from the returned
+	/// [`VMCSender`] by cloning it.
+	///
+	/// # Examples
+	///
+	/// ```no_run
+	/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+	/// use futures_util::StreamExt;
+	/// use vmc::{VMCModelState, VMCState};
+	///
+	/// let socket = vmc::performer!().await?;
+	/// let (mut receiver, sender) = socket.into_split();
+	/// tokio::spawn(async move {
+	/// 	while let Some(message) = receiver.messages().next().await {
+	/// 		println!("{:?}", message);
+	/// 	}
+	/// });
+	/// sender.send(VMCState::new(VMCModelState::Loaded)).await?;
+	/// # Ok(()) }) }
+	/// ```
+	pub fn into_split(self) -> (VMCReceiver, VMCSender) {
+		(self.receiver, self.sender)
+	}
+
+	/// Get a reference to the underling [`UdpSocket`].
+	pub fn socket(&self) -> &UdpSocket {
+		self.receiver.socket()
+	}
+
+	/// Returns the local address that this socket is bound to.
+	///
+	/// This can be useful, for example, when binding to port 0 to figure out which port was
+	/// actually bound.
+	pub fn local_addr(&self) -> VMCResult<SocketAddr> {
+		let addr = self.socket().local_addr()?;
+		Ok(addr)
+	}
+}
+
+impl Stream for VMCSocket {
+	type Item = VMCResult<(OSCPacket, SocketAddr)>;
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		Pin::new(&mut self.receiver).poll_next(cx)
+	}
+}
+
+/// Sends packets to the remote address to which the socket is connected.
+///
+/// See [`VMCSender`]'s implementation of [`Sink`] for details.
+impl<P: IntoOSCPacket> Sink<P> for VMCSocket {
+	type Error = VMCError;
+
+	fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<VMCResult<()>> {
+		self.sender.poll_send_pending(cx)
+	}
+
+	fn start_send(mut self: Pin<&mut Self>, item: P) -> VMCResult<()> {
+		Pin::new(&mut self.sender).start_send(item)
+	}
+
+	fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<VMCResult<()>> {
+		self.sender.poll_send_pending(cx)
+	}
+
+	fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<VMCResult<()>> {
+		self.sender.poll_send_pending(cx)
+	}
+}
+
+/// The receiving half of a VMC socket.
+///
+/// See [`VMCSocket::into_split`].
+#[derive(Debug)]
+pub struct VMCReceiver {
+	socket: UDPSocketStream,
+	allowed_peers: Option<HashSet<IpAddr>>,
+	decode_limits: DecodeLimits,
+	raw_batch: Vec<Datagram>,
+	stats: Arc<VMCSocketStats>,
+	last_peer: Arc<LastPeer>
+}
+
+impl VMCReceiver {
+	/// Receives a single OSC packet on the socket.
+	///
+	/// See [`VMCSocket::recv`].
+	pub async fn recv(&mut self) -> VMCResult<OSCPacket> {
+		self.recv_from().await.map(|(packet, _)| packet)
+	}
+
+	/// Receives a single OSC packet on the socket, returning the packet and the address of the peer that sent it.
+	///
+	/// See [`VMCSocket::recv_from`].
+	pub async fn recv_from(&mut self) -> VMCResult<(OSCPacket, SocketAddr)> {
+		match poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await {
+			Some(res) => res,
+			None => Err(io::Error::from(io::ErrorKind::UnexpectedEof).into())
+		}
+	}
+
+	/// Receives a single OSC packet on the socket, along with the address of the peer that sent it and the time at
+	/// which it was received.
+	///
+	/// See [`VMCSocket::recv_timestamped`].
+	pub async fn recv_timestamped(&mut self) -> VMCResult<(OSCPacket, SocketAddr, VMCRecvTimestamp)> {
+		match poll_fn(|cx| self.poll_recv_timestamped(cx)).await {
+			Some(res) => res,
+			None => Err(io::Error::from(io::ErrorKind::UnexpectedEof).into())
+		}
+	}
+
+	/// Receives a single datagram on the socket without decoding it.
+	///
+	/// See [`VMCSocket::recv_raw`].
+	pub async fn recv_raw(&mut self) -> VMCResult<(Bytes, SocketAddr)> {
+		match poll_fn(|cx| self.poll_recv_raw(cx)).await {
+			Some(res) => res.map(|(buf, peer_addr, _)| (buf, peer_addr)),
+			None => Err(io::Error::from(io::ErrorKind::UnexpectedEof).into())
+		}
+	}
+
+	/// Receives up to `max` OSC packets at once.
+	///
+	/// See [`VMCSocket::recv_batch`].
+	pub async fn recv_batch(&mut self, batch: &mut Vec<(OSCPacket, SocketAddr, VMCRecvTimestamp)>, max: usize) -> VMCResult<usize> {
+		let mut raw_batch = std::mem::take(&mut self.raw_batch);
+		let result = self.recv_raw_batch(&mut raw_batch, max).await;
+		let start = batch.len();
+		for (buf, peer_addr, timestamp) in raw_batch.drain(..) {
+			if let Ok(packet) = self.decode(&buf) {
+				batch.push((packet, peer_addr, timestamp));
+			}
+		}
+		self.raw_batch = raw_batch;
+		result.map(|_| batch.len() - start)
+	}
+
+	/// Receives up to `max` datagrams at once without decoding them.
+	///
+	/// See [`VMCSocket::recv_raw_batch`].
+	pub async fn recv_raw_batch(&mut self, batch: &mut Vec<(Bytes, SocketAddr, VMCRecvTimestamp)>, max: usize) -> VMCResult<usize> {
+		let start = batch.len();
+		poll_fn(|cx| self.poll_recv_raw_batch(cx, batch, max)).await?;
+		Ok(batch.len() - start)
+	}
+
+	/// Returns a stream of packets received on this socket, along with their sender address & receive time.
+	///
+	/// See [`VMCSocket::timestamped`].
+	pub fn timestamped(&mut self) -> Timestamped<'_> {
+		Timestamped::new(self)
+	}
+
+	/// Returns a stream of packets received on this socket which holds back bundles until their time tag.
+	///
+	/// See [`VMCSocket::scheduled`].
+	pub fn scheduled(&mut self) -> Scheduled<&mut Self> {
+		Scheduled::new(self)
+	}
+
+	/// Receives a single OSC packet on the socket and [parses](crate::parse) it into its contained [`VMCMessage`]s.
+	///
+	/// See [`VMCSocket::recv_message`].
+	pub async fn recv_message(&mut self) -> VMCResult<Vec<VMCMessage>> {
+		let (buf, _) = self.recv_raw().await?;
+		match parse_datagram_with_limits(&buf, &self.decode_limits) {
+			Err(err @ VMCError::Osc(_)) => {
+				self.stats.record_decode_error();
+				Err(err)
+			}
+			res => res
+		}
+	}
+
+	/// Returns a stream of parsed [`VMCMessage`]s received on this socket.
+	///
+	/// See [`VMCSocket::messages`].
+	pub fn messages(&mut self) -> VMCMessages<&mut Self> {
+		VMCMessages::new(self)
+	}
+
+	/// Returns a stream of complete frames received on this socket.
+	///
+	/// See [`VMCSocket::frames`].
+	pub fn frames(&mut self) -> VMCFrames<VMCMessages<&mut Self>> {
+		self.messages().frames()
+	}
+
+	/// Only accept packets sent from the given IP address.
+	///
+	/// See [`VMCSocket::allow_peer`].
+	pub fn allow_peer(&mut self, addr: impl Into<IpAddr>) {
+		self.allowed_peers.get_or_insert_with(HashSet::new).insert(addr.into());
+	}
+
+	/// Returns the set of peers this receiver accepts packets from, or `None` if packets from any peer are accepted.
+	pub fn allowed_peers(&self) -> Option<&HashSet<IpAddr>> {
+		self.allowed_peers.as_ref()
+	}
+
+	/// Clears the peer allowlist, accepting packets from any peer again.
+	pub fn clear_allowed_peers(&mut self) {
+		self.allowed_peers = None;
+	}
+
+	/// Sets the limits applied when decoding received packets.
+	///
+	/// See [`VMCSocket::set_decode_limits`].
+	pub fn set_decode_limits(&mut self, limits: DecodeLimits) {
+		self.decode_limits = limits;
+	}
+
+	/// Returns the limits applied when decoding received packets.
+	pub fn decode_limits(&self) -> &DecodeLimits {
+		&self.decode_limits
+	}
+
+	/// Returns the statistics counters for this socket.
+	///
+	/// See [`VMCSocket::stats`].
+	pub fn stats(&self) -> &VMCSocketStats {
+		&self.stats
+	}
+
+	/// Returns the address of the peer which sent the most recently received packet.
+	///
+	/// See [`VMCSocket::last_peer`].
+	pub fn last_peer(&self) -> Option<SocketAddr> {
+		self.last_peer.get()
+	}
+
+	fn is_allowed(&self, addr: &SocketAddr) -> bool {
+		match &self.allowed_peers {
+			Some(peers) => peers.contains(&addr.ip()) || peers.contains(&to_canonical(addr.ip())),
+			None => true
+		}
+	}
+
+	/// Get a reference to the underling [`UdpSocket`].
+	pub fn socket(&self) -> &UdpSocket {
+		self.socket.get_ref()
+	}
+
+	/// Returns the local address that this socket is bound to.
+	pub fn local_addr(&self) -> VMCResult<SocketAddr> {
+		let addr = self.socket().local_addr()?;
+		Ok(addr)
+	}
+}
+
+impl VMCReceiver {
+	pub(crate) fn poll_recv_raw(&mut self, cx: &mut Context<'_>) -> Poll<Option<VMCResult<(Bytes, SocketAddr, VMCRecvTimestamp)>>> {
+		loop {
+			let (buf, peer_addr, timestamp) = match ready!(Pin::new(&mut self.socket).poll_next(cx)) {
+				Some(Ok(packet)) => packet,
+				Some(Err(err)) => return Poll::Ready(Some(Err(err.into()))),
+				None => return Poll::Ready(None)
+			};
+			self.stats.record_received(buf.len());
+			if !self.is_allowed(&peer_addr) {
+				self.stats.record_dropped();
+				continue;
+			}
+			self.last_peer.set(peer_addr);
+			return Poll::Ready(Some(Ok((buf, peer_addr, timestamp))));
+		}
+	}
+
+	pub(crate) fn poll_recv_raw_batch(&mut self, cx: &mut Context<'_>, batch: &mut Vec<Datagram>, max: usize) -> Poll<VMCResult<()>> {
+		let (start, max) = (batch.len(), max.max(1));
+		loop {
+			ready!(self.socket.poll_recv_batch(cx, max, batch))?;
+			// drop datagrams from peers that aren't allowed, keeping the rest in order
+			let mut kept = start;
+			for i in start..batch.len() {
+				let (buf, peer_addr, _) = &batch[i];
+				self.stats.record_received(buf.len());
+				if self.is_allowed(peer_addr) {
+					batch.swap(kept, i);
+					kept += 1;
+				} else {
+					self.stats.record_dropped();
+				}
+			}
+			batch.truncate(kept);
+			if let Some((_, peer_addr, _)) = batch[start..].last() {
+				self.last_peer.set(*peer_addr);
+				return Poll::Ready(Ok(()));
+			}
+		}
+	}
+
+	pub(crate) fn poll_recv_timestamped(&mut self, cx: &mut Context<'_>) -> Poll<Option<VMCResult<(OSCPacket, SocketAddr, VMCRecvTimestamp)>>> {
+		let (buf, peer_addr, timestamp) = match ready!(self.poll_recv_raw(cx)) {
+			Some(Ok(datagram)) => datagram,
+			Some(Err(err)) => return Poll::Ready(Some(Err(err))),
+			None => return Poll::Ready(None)
+		};
+		Poll::Ready(Some(self.decode(&buf).map(|packet| (packet, peer_addr, timestamp))))
+	}
+
+	/// Decodes a datagram received on this socket, counting decode errors in the socket's statistics.
+	pub(crate) fn decode(&self, buf: &[u8]) -> VMCResult<OSCPacket> {
+		match osc::decode_udp_with_limits(buf, &self.decode_limits) {
+			Ok((_, packet)) => Ok(packet),
+			Err(err) => {
+				self.stats.record_decode_error();
+				Err(err.into())
+			}
+		}
+	}
+}
+
+impl Stream for VMCReceiver {
+	type Item = VMCResult<(OSCPacket, SocketAddr)>;
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		self.poll_recv_timestamped(cx)
+			.map(|packet| packet.map(|packet| packet.map(|(packet, peer_addr, _)| (packet, peer_addr))))
+	}
+}
+
+/// A sender to send messages over a VMC socket.
+///
+/// See [`VMCSocket::sender`] and [`VMCSocket::into_split`].
+#[derive(Debug)]
+pub struct VMCSender {
+	socket: Arc<UdpSocket>,
+	pending: Option<Vec<u8>>,
+	encode_options: EncodeOptions,
+	final_state: Option<VMCState>,
+	pool: Arc<BufferPool>,
+	stats: Arc<VMCSocketStats>,
+	last_peer: Arc<LastPeer>
+}
+
+impl Clone for VMCSender {
+	fn clone(&self) -> Self {
+		Self {
+			socket: Arc::clone(&self.socket),
+			pending: None,
+			encode_options: self.encode_options,
+			final_state: self.final_state.clone(),
+			pool: Arc::clone(&self.pool),
+			stats: Arc::clone(&self.stats),
+			last_peer: Arc::clone(&self.last_peer)
+		}
+	}
+}
+
+impl VMCSender {
+	pub(crate) fn new(socket: Arc<UdpSocket>, stats: Arc<VMCSocketStats>, last_peer: Arc<LastPeer>) -> Self {
+		Self {
+			socket,
+			pending: None,
+			encode_options: EncodeOptions::default(),
+			final_state: Some(VMCState::new(VMCModelState::NotLoaded)),
+			pool: Arc::default(),
+			stats,
+			last_peer
+		}
+	}
+
+	/// Sends a VMC packet on the socket to the given address.
+	///
+	/// See [`VMCSocket::send_to`].
+	pub async fn send_to<A: ToSocketAddrs, P: IntoOSCPacket>(&self, packet: P, addrs: A) -> VMCResult<()> {
+		let buf = self.pool.encode(&packet.into_osc_packet(), &self.encode_options)?;
+		let mut last_err = None;
+		for addr in tokio::net::lookup_host(addrs).await? {
+			match self.send_buf_to(&buf, addr).await {
+				Ok(()) => return Ok(()),
+				Err(e) => last_err = Some(e)
+			}
+		}
+		Err(last_err.unwrap_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no addresses to send data to").into()))
+	}
+
+	/// Sends a VMC packet to the peer which sent the most recently received packet.
+	///
+	/// See [`VMCSocket::reply`].
+	pub async fn reply<P: IntoOSCPacket>(&self, packet: P) -> VMCResult<()> {
+		let Some(addr) = self.last_peer.get() else {
+			return Err(io::Error::new(io::ErrorKind::NotConnected, "no packet has been received to reply to").into());
+		};
+		let buf = self.pool.encode(&packet.into_osc_packet(), &self.encode_options)?;
+		self.send_buf_to(&buf, addr).await
+	}
+
+	/// Returns the address of the peer which sent the most recently received packet.
+	///
+	/// See [`VMCSocket::last_peer`].
+	pub fn last_peer(&self) -> Option<SocketAddr> {
+		self.last_peer.get()
+	}
+
+	/// Sends an already encoded packet to the given address.
+	pub(crate) async fn send_buf_to(&self, buf: &[u8], addr: SocketAddr) -> VMCResult<()> {
+		check_broadcast(SockRef::from(self.socket()), addr)?;
+		let n = self
+			.socket()
+			.send_to(buf, addr)
+			.await
+			.map_err(|e| map_broadcast_err(SockRef::from(self.socket()), addr, e))?;
+		self.finish_send(buf, n)
+	}
+
+	/// Sends a VMC packet on the connected socket.
+	///
+	/// See [`VMCSocket::send`].
+	pub async fn send<P: IntoOSCPacket>(&self, packet: P) -> VMCResult<()> {
+		self.send_packet(&packet.into_osc_packet()).await
+	}
+
+	/// Sends a packet on the connected socket without taking ownership of it.
+	pub(crate) async fn send_packet(&self, packet: &OSCPacket) -> VMCResult<()> {
+		let buf = self.pool.encode(packet, &self.encode_options)?;
+		let n = self.socket().send(&buf[..]).await?;
+		self.finish_send(&buf[..], n)
+	}
+
+	/// Sends multiple VMC packets on the connected socket.
+	///
+	/// See [`VMCSocket::send_batch`].
+	pub async fn send_batch<I>(&self, packets: I) -> VMCResult<()>
+	where
+		I: IntoIterator,
+		I::Item: IntoOSCPacket
+	{
+		let (buf, ends) = self.pool.encode_batch(packets, &self.encode_options)?;
+		let mut start = 0;
+		let datagrams: SmallVec<[&[u8]; 64]> = ends
+			.iter()
+			.map(|&end| {
+				let datagram = &buf[start..end];
+				start = end;
+				datagram
+			})
+			.collect();
+		self.send_datagrams(&datagrams).await
+	}
+
+	#[cfg(any(target_os = "android", target_os = "linux"))]
+	async fn send_datagrams(&self, mut datagrams: &[&[u8]]) -> VMCResult<()> {
+		let mut sent = [0; crate::udp::MAX_BATCH];
+		while !datagrams.is_empty() {
+			let n = self
+				.socket()
+				.async_io(Interest::WRITABLE, || crate::udp::sendmmsg(self.socket(), datagrams, &mut sent))
+				.await?;
+			for (datagram, len) in datagrams.iter().zip(&sent[..n]) {
+				self.finish_send(datagram, *len)?;
+			}
+			datagrams = &datagrams[n..];
+		}
+		Ok(())
+	}
+
+	#[cfg(not(any(target_os = "android", target_os = "linux")))]
+	async fn send_datagrams(&self, datagrams: &[&[u8]]) -> VMCResult<()> {
+		for datagram in datagrams {
+			let n = self.socket().send(datagram).await?;
+			self.finish_send(datagram, n)?;
+		}
+		Ok(())
+	}
+
+	/// Sends a VMC packet on the connected socket, encoding it into the given buffer.
+	///
+	/// See [`VMCSocket::send_with_buf`].
+	pub async fn send_with_buf<P: IntoOSCPacket>(&self, packet: P, buf: &mut Vec<u8>) -> VMCResult<()> {
+		let packet = packet.into_osc_packet();
+		buf.clear();
+		// NOTE: The Output implementation for Vec<u8> can't actually produce an error!
+		osc::encode_into(&*self.encode_options.prepare(&packet)?, buf).expect("Failed to write encoded packet into Vec");
+		let n = self.socket().send(buf).await?;
+		self.finish_send(buf, n)
+	}
+
+	/// Sends a complete [`VMCPose`] on the connected socket.
+	///
+	/// See [`VMCSocket::send_pose`].
+	pub async fn send_pose(&self, pose: &VMCPose) -> VMCResult<()> {
+		for packet in pose.to_packets() {
+			self.send(packet).await?;
+		}
+		Ok(())
+	}
+
+	/// Sets the options used to encode packets sent by this sender.
+	///
+	/// See [`VMCSocket::set_encode_options`].
+	pub fn set_encode_options(&mut self, options: EncodeOptions) {
+		self.encode_options = options;
+	}
+
+	/// Returns the options used to encode packets sent by this sender.
+	pub fn encode_options(&self) -> &EncodeOptions {
+		&self.encode_options
+	}
+
+	/// Sets the state sent by [`VMCSender::close`], or `None` to send nothing.
+	///
+	/// See [`VMCSocket::set_final_state`].
+	pub fn set_final_state(&mut self, state: Option<VMCState>) {
+		self.final_state = state;
+	}
+
+	/// Returns the state sent by [`VMCSender::close`].
+	pub fn final_state(&self) -> Option<&VMCState> {
+		self.final_state.as_ref()
+	}
+
+	/// Sends any packet still buffered by the [`Sink`] implementation, followed by the final state if the socket is
+	/// connected, then drops the sender.
+	///
+	/// See [`VMCSocket::close`].
+	pub async fn close(mut self) -> VMCResult<()> {
+		poll_fn(|cx| self.poll_send_pending(cx)).await?;
+		if let Some(state) = self.final_state.take() {
+			if self.socket().peer_addr().is_ok() {
+				self.send(state).await?;
+			}
+		}
+		Ok(())
+	}
+
+	/// Returns the statistics counters for this socket.
+	///
+	/// See [`VMCSocket::stats`].
+	pub fn stats(&self) -> &VMCSocketStats {
+		&self.stats
+	}
+
+	/// Get a reference to the underling [`UdpSocket`].
+	pub fn socket(&self) -> &UdpSocket {
+		&self.socket
+	}
+
+	fn poll_send_pending(&mut self, cx: &mut Context<'_>) -> Poll<VMCResult<()>> {
+		if let Some(buf) = &self.pending {
+			let res = ready!(self.socket.poll_send(cx, &buf[..]));
+			let buf = self.pending.take().unwrap();
+			let res = res.map_err(VMCError::from).and_then(|n| self.finish_send(&buf[..], n));
+			self.pool.give(buf);
+			res?;
+		}
+		Poll::Ready(Ok(()))
+	}
+
+	fn finish_send(&self, buf: &[u8], len: usize) -> VMCResult<()> {
+		check_len(buf, len)?;
+		self.stats.record_sent(len);
+		Ok(())
+	}
+}
+
+impl Drop for VMCSender {
+	fn drop(&mut self) {
+		// dropping may happen on an async task, so rather than waiting for the socket, the buffered packet is only sent if
+		// it can be right away. the socket is non-blocking, so this goes straight to the OS instead of through tokio's
+		// readiness tracking, which wouldn't know the socket is writable if this sender never waited on it
+		if let Some(buf) = self.pending.take() {
+			if let Ok(n) = SockRef::from(self.socket()).send(&buf[..]) {
+				let _ = self.finish_send(&buf[..], n);
+			}
+			self.pool.give(buf);
+		}
+	}
+}
+
+/// Sends packets to the remote address to which the socket is connected.
+///
+/// Each item is encoded when it is passed to [`Sink::start_send`], and the sink only buffers a single packet at a time,
+/// so [`Sink::poll_ready`] will wait until the previous packet has been sent. Like [`VMCSender::send`], sending will
+/// fail if the socket is not connected.
+///
+/// # Examples
+///
+/// ```no_run
+/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+/// use futures_util::{SinkExt, stream};
+/// use vmc::{VMCApplyBlendShapes, VMCBlendShape, VMCMessage, VMCStandardVRMBlendShape};
+///
+/// let mut sender = vmc::performer!().await?.sender();
+/// let mut messages = stream::iter([
+/// 	Ok(VMCMessage::from(VMCBlendShape::new(VMCStandardVRMBlendShape::Joy, 1.0))),
+/// 	Ok(VMCMessage::from(VMCApplyBlendShapes))
+/// ]);
+/// sender.send_all(&mut messages).await?;
+/// # Ok(()) }) }
+/// ```
+impl<P: IntoOSCPacket> Sink<P> for VMCSender {
+	type Error = VMCError;
+
+	fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<VMCResult<()>> {
+		self.poll_send_pending(cx)
+	}
+
+	fn start_send(mut self: Pin<&mut Self>, item: P) -> VMCResult<()> {
+		let buf = self.pool.encode(&item.into_osc_packet(), &self.encode_options)?.into_inner();
+		self.pending = Some(buf);
+		Ok(())
+	}
+
+	fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<VMCResult<()>> {
+		self.poll_send_pending(cx)
+	}
+
+	fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<VMCResult<()>> {
+		self.poll_send_pending(cx)
+	}
+}
+
+/// Returns whether an error reports an ICMP port unreachable response; Windows reports these as connection resets.
+fn is_refused(err: &io::Error) -> bool {
+	matches!(err.kind(), io::ErrorKind::ConnectionRefused | io::ErrorKind::ConnectionReset)
+}
+
+/// Returns an error if `addr` is the limited broadcast address and `SO_BROADCAST` is not enabled on `socket`.
+pub(crate) fn check_broadcast(socket: SockRef<'_>, addr: SocketAddr) -> VMCResult<()> {
+	match addr.ip() {
+		IpAddr::V4(ip) if ip.is_broadcast() && !socket.broadcast()? => Err(VMCError::BroadcastNotEnabled(addr)),
+		_ => Ok(())
+	}
+}
+
+/// Subnet broadcast addresses can't be identified without knowing the netmask, but sending to one without
+/// `SO_BROADCAST` fails with `EACCES`, so we can map that into a more descriptive error.
+pub(crate) fn map_broadcast_err(socket: SockRef<'_>, addr: SocketAddr, err: io::Error) -> VMCError {
+	if err.kind() == io::ErrorKind::PermissionDenied && addr.is_ipv4() && matches!(socket.broadcast(), Ok(false)) {
+		VMCError::BroadcastNotEnabled(addr)
+	} else {
+		err.into()
+	}
+}
+
+/// Maps IPv4-mapped IPv6 addresses (`::ffff:a.b.c.d`), as reported by dual-stack sockets, to their IPv4 equivalent.
+fn to_canonical(ip: IpAddr) -> IpAddr {
+	match ip {
+		IpAddr::V6(ip) => ip.to_ipv4_mapped().map_or(IpAddr::V6(ip), IpAddr::V4),
+		ip => ip
+	}
+}
+
+pub(crate) fn check_len(buf: &[u8], len: usize) -> VMCResult<()> {
+	if len != buf.len() {
+		Err(io::Error::new(io::ErrorKind::Interrupted, "UDP packet not fully sent").into())
+	} else {
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		Quat, VMCApplyBlendShapes, VMCBlendShape, VMCBoneTransform, VMCOptionString, VMCReceiveEnable, VMCTime, Vec3A,
+		osc::{OSCBundle, OSCTime}
+	};
+
+	#[tokio::test]
+	async fn test_send_with_buf() -> VMCResult<()> {
+		let mut marionette = VMCSocket::bind("127.0.0.1:0").await?;
+		let performer = VMCSocket::bind("127.0.0.1:0").await?;
+		performer.connect(marionette.local_addr()?).await?;
+
+		let mut buf = Vec::new();
+		performer.send_with_buf(VMCBlendShape::new("Joy", 1.0), &mut buf).await?;
+		let ptr = buf.as_ptr();
+		performer.send_with_buf(VMCTime::new(1.0), &mut buf).await?;
+		// the smaller packet fits in the existing allocation
+		assert_eq!(buf.as_ptr(), ptr);
+		assert_eq!(buf, osc::encode(&VMCTime::new(1.0).into_osc_packet())?);
+
+		assert!(matches!(&marionette.recv_message().await?[..], [VMCMessage::BlendShape(blend)] if blend.key == "Joy"));
+		assert!(matches!(&marionette.recv_message().await?[..], [VMCMessage::Time(VMCTime(1.0))]));
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_send_batch() -> VMCResult<()> {
+		let mut marionette = VMCSocket::builder().recv_buffer_size(1024 * 1024).build().await?;
+		let performer = VMCSocket::bind("127.0.0.1:0").await?;
+		performer.connect(marionette.local_addr()?).await?;
+
+		// more than fit in a single `sendmmsg` call
+		performer
+			.send_batch((0..100).map(|i| VMCBoneTransform::new("Hips", Vec3A::X * i as f32, Quat::IDENTITY)))
+			.await?;
+		assert_eq!(performer.stats().packets_sent(), 100);
+		for i in 0..100 {
+			let messages = marionette.recv_message().await?;
+			assert!(matches!(&messages[..], [VMCMessage::BoneTransform(transform)] if transform.position.x == i as f32));
+		}
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_recv_batch() -> VMCResult<()> {
+		let mut marionette = VMCSocket::builder().recv_buffer_size(1024 * 1024).build().await?;
+		let performer = VMCSocket::bind("127.0.0.1:0").await?;
+		performer.connect(marionette.local_addr()?).await?;
+
+		for i in 0..40 {
+			performer.send(VMCTime::new(i as f32)).await?;
+		}
+		// a garbage datagram is skipped, but counted
+		performer.socket().send(b"garbage").await?;
+
+		let mut batch = Vec::new();
+		while marionette.stats().packets_received() < 41 {
+			let n = marionette.recv_batch(&mut batch, 64).await?;
+			assert!(n > 0);
+		}
+		assert_eq!(batch.len(), 40);
+		assert_eq!(marionette.stats().decode_errors(), 1);
+		for (i, (packet, peer, _)) in batch.into_iter().enumerate() {
+			assert_eq!(peer, performer.local_addr()?);
+			assert_eq!(packet, VMCTime::new(i as f32).into_osc_packet());
+		}
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_recv_raw_reuses_buffer() -> VMCResult<()> {
+		let mut marionette = VMCSocket::bind("127.0.0.1:0").await?;
+		let performer = VMCSocket::bind("127.0.0.1:0").await?;
+		performer.connect(marionette.local_addr()?).await?;
+
+		for i in 0..3 {
+			performer.send(VMCTime::new(i as f32)).await?;
+		}
+
+		// datagrams are handed out without copying, so clones (e.g. when forwarding to several targets) share the buffer
+		let (first, _) = marionette.recv_raw().await?;
+		let ptr = first.as_ptr();
+		let forwarded = first.clone();
+		assert_eq!(forwarded.as_ptr(), ptr);
+		assert_eq!(osc::decode_udp(&forwarded)?.1, VMCTime::new(0.0).into_osc_packet());
+
+		// the buffer is still in use, so the next datagram is received into a new one
+		let (second, _) = marionette.recv_raw().await?;
+		assert_ne!(second.as_ptr(), ptr);
+		let ptr = second.as_ptr();
+		drop((first, forwarded, second));
+
+		// once all handles are dropped, the space is reclaimed
+		let (third, _) = marionette.recv_raw().await?;
+		assert_eq!(third.as_ptr(), ptr);
+		assert_eq!(osc::decode_udp(&third)?.1, VMCTime::new(2.0).into_osc_packet());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_builder_roles() -> VMCResult<()> {
+		let mut marionette = VMCSocket::builder().role(VMCRole::Marionette).bind("127.0.0.1:0").build().await?;
+		let performer = VMCSocket::builder()
+			.role(VMCRole::Performer)
+			.target(marionette.local_addr()?)
+			.from_socket(std::net::UdpSocket::bind("127.0.0.1:0")?)
+			.await?;
+		assert_eq!(performer.socket().peer_addr()?, marionette.local_addr()?);
+		performer.send(VMCTime::new(1.0)).await?;
+		assert!(matches!(&marionette.recv_message().await?[..], [VMCMessage::Time(VMCTime(1.0))]));
+
+		// performers send to the default port
+		let performer = VMCSocket::builder().role(VMCRole::Performer).bind_port(0).build().await?;
+		assert_eq!(performer.socket().peer_addr()?, SocketAddr::from((Ipv4Addr::LOCALHOST, 39539)));
+		assert!(performer.local_addr()?.ip().is_loopback());
+
+		// receiving from other machines binds to every interface
+		let marionette = VMCSocket::builder().role(VMCRole::Marionette).bind_any().bind_port(0).build().await?;
+		assert_eq!(marionette.local_addr()?.ip(), Ipv4Addr::UNSPECIFIED);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_builder_fallback_ports() -> VMCResult<()> {
+		let mut performer = VMCSocket::bind("127.0.0.1:0").await?;
+		let taken = VMCSocket::bind("127.0.0.1:0").await?;
+		let port = taken.local_addr()?.port();
+
+		let marionette = VMCSocket::builder()
+			.bind(taken.local_addr()?)
+			.fallback_ports(port.saturating_sub(10)..=port.saturating_add(10))
+			.announce_port(performer.local_addr()?)
+			.announce_options("fallback")
+			.build()
+			.await?;
+		let bound = marionette.local_addr()?.port();
+		assert_ne!(bound, port);
+		let OSCPacket::Message(message) = performer.recv().await? else {
+			panic!()
+		};
+		assert_eq!(VMCReceiveEnable::from_osc_message(&message), Some(VMCReceiveEnable::new(true, bound).with_addr("127.0.0.1")));
+		let OSCPacket::Message(message) = performer.recv().await? else {
+			panic!()
+		};
+		assert_eq!(VMCOptionString::from_osc_message(&message), Some(VMCOptionString::new("fallback")));
+
+		let err = VMCSocket::builder()
+			.bind(taken.local_addr()?)
+			.fallback_ports(port..=port)
+			.build()
+			.await
+			.unwrap_err();
+		assert!(matches!(err, VMCError::PortsInUse(ports) if ports == (port..=port)));
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_send_to_tries_each_addr() -> VMCResult<()> {
+		let mut marionette = VMCSocket::bind("127.0.0.1:0").await?;
+		let performer = VMCSocket::bind("127.0.0.1:0").await?;
+
+		// an IPv4 socket can't send to an IPv6 address, so the packet goes to the next address
+		let unreachable = SocketAddr::from((Ipv6Addr::LOCALHOST, marionette.local_addr()?.port()));
+		performer.send_to(VMCTime::new(1.0), &[unreachable, marionette.local_addr()?][..]).await?;
+		assert!(matches!(&marionette.recv_message().await?[..], [VMCMessage::Time(VMCTime(1.0))]));
+
+		assert!(performer.send_to(VMCTime::new(1.0), &[unreachable][..]).await.is_err());
+		assert!(performer.send_to(VMCTime::new(1.0), &[][..]).await.is_err());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_reply() -> VMCResult<()> {
+		let mut marionette = VMCSocket::bind("127.0.0.1:0").await?;
+		let mut first = VMCSocket::bind("127.0.0.1:0").await?;
+		let mut second = VMCSocket::bind("127.0.0.1:0").await?;
+		assert!(marionette.reply(VMCTime::new(0.0)).await.is_err());
+
+		first.send_to(VMCTime::new(1.0), marionette.local_addr()?).await?;
+		second.send_to(VMCTime::new(2.0), marionette.local_addr()?).await?;
+		marionette.recv().await?;
+		assert_eq!(marionette.last_peer(), Some(first.local_addr()?));
+		marionette.reply(VMCTime::new(1.5)).await?;
+		assert!(matches!(&first.recv_message().await?[..], [VMCMessage::Time(VMCTime(1.5))]));
+
+		// split halves share the last peer
+		let (mut receiver, sender) = marionette.into_split();
+		receiver.recv().await?;
+		sender.reply(VMCTime::new(2.5)).await?;
+		assert!(matches!(&second.recv_message().await?[..], [VMCMessage::Time(VMCTime(2.5))]));
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_probe() -> VMCResult<()> {
+		let mut marionette = VMCSocket::bind("127.0.0.1:0").await?;
+		let performer = VMCSocket::bind("127.0.0.1:0").await?;
+		assert!(performer.probe(Duration::from_millis(10)).await.is_err());
+		performer.connect(marionette.local_addr()?).await?;
+		assert_eq!(performer.probe(Duration::from_millis(50)).await?, VMCPeerStatus::NotResponding);
+
+		let responder = tokio::spawn(async move {
+			loop {
+				if let Ok(OSCPacket::Message(message)) = marionette.recv().await {
+					if VMCRequestInfo::from_osc_message(&message).is_some() {
+						marionette.reply(VMCTime::new(1.0)).await.unwrap();
+						break marionette;
+					}
+				}
+			}
+		});
+		assert!(performer.probe(Duration::from_secs(5)).await?.is_responding());
+		responder.await.unwrap();
+
+		let closed = VMCSocket::bind("127.0.0.1:0").await?.local_addr()?;
+		let performer = VMCSocket::bind("127.0.0.1:0").await?;
+		performer.connect(closed).await?;
+		assert_eq!(performer.probe(Duration::from_secs(5)).await?, VMCPeerStatus::Refused);
+
+		// packets queued before the probe don't count as a response
+		let target = VMCSocket::bind("127.0.0.1:0").await?;
+		let mut performer = VMCSocket::bind("127.0.0.1:0").await?;
+		performer.connect(target.local_addr()?).await?;
+		target.send_to(VMCTime::new(1.0), performer.local_addr()?).await?;
+		target.send_to(VMCTime::new(2.0), performer.local_addr()?).await?;
+		tokio::time::timeout(Duration::from_secs(5), performer.socket().peek_sender())
+			.await
+			.unwrap()?;
+		assert_eq!(performer.probe(Duration::from_millis(50)).await?, VMCPeerStatus::NotResponding);
+		target.send_to(VMCTime::new(3.0), performer.local_addr()?).await?;
+		assert!(matches!(&performer.recv_message().await?[..], [VMCMessage::Time(VMCTime(3.0))]));
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_close() -> VMCResult<()> {
+		let mut marionette = VMCSocket::bind("127.0.0.1:0").await?;
+		let mut performer = VMCSocket::bind("127.0.0.1:0").await?;
+		performer.connect(marionette.local_addr()?).await?;
+
+		// the packet buffered by the sink is sent before the final state
+		futures_util::SinkExt::feed(&mut performer, VMCTime::new(1.0)).await?;
+		performer.close().await?;
+		assert!(matches!(&marionette.recv_message().await?[..], [VMCMessage::Time(VMCTime(1.0))]));
+		assert!(matches!(
+			&marionette.recv_message().await?[..],
+			[VMCMessage::State(state)] if state.model_state == VMCModelState::NotLoaded
+		));
+
+		// dropping a sender still sends its buffered packet
+		let mut sender = VMCSocket::bind("127.0.0.1:0").await?.sender();
+		sender.socket().connect(marionette.local_addr()?).await?;
+		sender.set_final_state(Some(VMCState::new(VMCModelState::Loaded)));
+		futures_util::SinkExt::feed(&mut sender, VMCTime::new(2.0)).await?;
+		drop(sender);
+		assert!(matches!(&marionette.recv_message().await?[..], [VMCMessage::Time(VMCTime(2.0))]));
+		// ...but not the final state, which only `close` sends
+		assert!(tokio::time::timeout(Duration::from_millis(50), marionette.recv()).await.is_err());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_multicast() -> VMCResult<()> {
+		let group = Ipv4Addr::new(239, 255, 42, 99);
+		// join on the loopback interface so the test doesn't depend on the machine's network setup
+		let mut first = VMCSocket::builder()
+			.bind("0.0.0.0:0")
+			.reuse_address(true)
+			.reuse_port(true)
+			.join_multicast_v4(group, Ipv4Addr::LOCALHOST)
+			.build()
+			.await?;
+		let port = first.local_addr()?.port();
+		// multiple marionettes on the same machine can receive the same group
+		let mut second = VMCSocket::builder()
+			.bind(("0.0.0.0", port))
+			.reuse_address(true)
+			.reuse_port(true)
+			.join_multicast_v4(group, Ipv4Addr::LOCALHOST)
+			.build()
+			.await?;
+
+		let performer = VMCSocket::builder()
+			.bind("0.0.0.0:0")
+			.multicast_interface_v4(Ipv4Addr::LOCALHOST)
+			.multicast_loop(true)
+			.build()
+			.await?;
+		performer.send_to(VMCTime::new(1.0), (group, port)).await?;
+		assert!(matches!(&first.recv_message().await?[..], [VMCMessage::Time(VMCTime(1.0))]));
+		assert!(matches!(&second.recv_message().await?[..], [VMCMessage::Time(VMCTime(1.0))]));
+
+		second.leave_multicast_v4(group, Ipv4Addr::LOCALHOST)?;
+		// the membership is gone, so leaving again fails
+		assert!(second.leave_multicast_v4(group, Ipv4Addr::LOCALHOST).is_err());
+
+		// the macro binds to the unspecified address with address reuse, so several marionettes can share a port
+		let marionette = crate::marionette!("0.0.0.0:0", multicast = "239.255.42.99").await?;
+		let addr = marionette.local_addr()?;
+		assert_eq!(addr.ip(), Ipv4Addr::UNSPECIFIED);
+		let shared = crate::marionette!(bind = addr, multicast = group).await?;
+		assert_eq!(shared.local_addr()?, addr);
+		assert!(crate::marionette!("0.0.0.0:0", multicast = "not a group").await.is_err());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_broadcast() -> VMCResult<()> {
+		let mut marionette = VMCSocket::bind("0.0.0.0:0").await?;
+		let port = marionette.local_addr()?.port();
+		let performer = VMCSocket::bind("0.0.0.0:0").await?;
+		assert!(!performer.broadcast()?);
+
+		// the limited broadcast address is caught before sending...
+		let limited = SocketAddr::from((Ipv4Addr::BROADCAST, port));
+		assert!(matches!(performer.send_to(VMCTime::new(1.0), limited).await, Err(VMCError::BroadcastNotEnabled(addr)) if addr == limited));
+		// ...and subnet broadcast addresses, here the one for 127.0.0.0/8, are caught when the kernel refuses to send
+		let subnet = SocketAddr::from((Ipv4Addr::new(127, 255, 255, 255), port));
+		assert!(matches!(performer.send_to(VMCTime::new(1.0), subnet).await, Err(VMCError::BroadcastNotEnabled(addr)) if addr == subnet));
+		assert!(matches!(performer.connect(subnet).await, Err(VMCError::BroadcastNotEnabled(addr)) if addr == subnet));
+
+		performer.set_broadcast(true)?;
+		assert!(performer.broadcast()?);
+		performer.send_to(VMCTime::new(1.0), subnet).await?;
+		assert!(matches!(&marionette.recv_message().await?[..], [VMCMessage::Time(VMCTime(1.0))]));
+
+		// the macro enables broadcast before connecting to the target
+		let performer = crate::performer!(subnet, broadcast = true).await?;
+		assert!(performer.broadcast()?);
+		performer.send(VMCTime::new(2.0)).await?;
+		assert!(matches!(&marionette.recv_message().await?[..], [VMCMessage::Time(VMCTime(2.0))]));
+		assert!(matches!(crate::performer!(subnet, broadcast = false).await, Err(VMCError::BroadcastNotEnabled(_))));
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_allowed_peers() -> VMCResult<()> {
+		let mut marionette = VMCSocket::builder().bind("127.0.0.1:0").allow_peer(Ipv4Addr::LOCALHOST).build().await?;
+		let addr = marionette.local_addr()?;
+		let allowed = VMCSocket::bind("127.0.0.1:0").await?;
+		// any address in 127.0.0.0/8 is local, so this acts as a second machine
+		let blocked = VMCSocket::bind("127.0.0.2:0").await?;
+
+		blocked.send_to(VMCTime::new(0.0), addr).await?;
+		allowed.send_to(VMCTime::new(1.0), addr).await?;
+		assert_eq!(marionette.recv_from().await?, (VMCTime::new(1.0).into_osc_packet(), allowed.local_addr()?));
+		// the blocked packet was received, but dropped before decoding
+		assert_eq!(marionette.stats().packets_received(), 2);
+		assert_eq!(marionette.stats().dropped_packets(), 1);
+		assert_eq!(marionette.last_peer(), Some(allowed.local_addr()?));
+
+		// batch receives are filtered too
+		for i in 0..4 {
+			blocked.send_to(VMCTime::new(i as f32), addr).await?;
+			allowed.send_to(VMCTime::new(i as f32), addr).await?;
+		}
+		let mut batch = Vec::new();
+		while batch.len() < 4 {
+			marionette.recv_batch(&mut batch, 8).await?;
+		}
+		assert!(
+			batch
+				.iter()
+				.enumerate()
+				.all(|(i, (packet, peer, _))| *peer == allowed.local_addr().unwrap() && *packet == VMCTime::new(i as f32).into_osc_packet())
+		);
+		assert_eq!(marionette.stats().dropped_packets(), 5);
+
+		marionette.clear_allowed_peers();
+		assert!(marionette.allowed_peers().is_none());
+		blocked.send_to(VMCTime::new(2.0), addr).await?;
+		assert_eq!(marionette.recv_from().await?.1, blocked.local_addr()?);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_allowed_peers_dual_stack() -> VMCResult<()> {
+		// dual-stack sockets report IPv4 senders as IPv4-mapped IPv6 addresses (`::ffff:127.0.0.1`)
+		let mut marionette = VMCSocket::bind("[::]:0").await?;
+		marionette.allow_peer(Ipv4Addr::LOCALHOST);
+		let port = marionette.local_addr()?.port();
+		let allowed = VMCSocket::bind("127.0.0.1:0").await?;
+		let blocked = VMCSocket::bind("127.0.0.2:0").await?;
+
+		blocked.send_to(VMCTime::new(0.0), ("127.0.0.1", port)).await?;
+		allowed.send_to(VMCTime::new(1.0), ("127.0.0.1", port)).await?;
+		let (packet, peer) = marionette.recv_from().await?;
+		assert_eq!(packet, VMCTime::new(1.0).into_osc_packet());
+		assert_eq!(peer.ip(), Ipv4Addr::LOCALHOST.to_ipv6_mapped());
+		assert_eq!(marionette.stats().dropped_packets(), 1);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_recv_timestamps() -> VMCResult<()> {
+		let mut marionette = VMCSocket::bind("127.0.0.1:0").await?;
+		let performer = VMCSocket::bind("127.0.0.1:0").await?;
+		performer.connect(marionette.local_addr()?).await?;
+
+		let before = Instant::now();
+		performer.send(VMCTime::new(1.0)).await?;
+		let (packet, peer, timestamp) = marionette.recv_timestamped().await?;
+		assert_eq!((packet, peer), (VMCTime::new(1.0).into_osc_packet(), performer.local_addr()?));
+		assert!(timestamp.received >= before && timestamp.received <= Instant::now());
+		// kernel timestamps are opt-in
+		assert_eq!(timestamp.kernel, None);
+		Ok(())
+	}
+
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	#[tokio::test]
+	async fn test_kernel_timestamps() -> VMCResult<()> {
+		let mut marionette = VMCSocket::builder().bind("127.0.0.1:0").kernel_timestamps(true).build().await?;
+		let performer = VMCSocket::bind("127.0.0.1:0").await?;
+		performer.connect(marionette.local_addr()?).await?;
+		// the kernel enables timestamping in the background, so the first packet may be stamped when it is read
+		performer.send(VMCTime::new(0.0)).await?;
+		marionette.recv_timestamped().await?;
+		tokio::time::sleep(Duration::from_millis(10)).await;
+
+		let before = std::time::SystemTime::now();
+		performer.send(VMCTime::new(1.0)).await?;
+		// delay the read, which shouldn't affect the time the kernel received the packet
+		tokio::time::sleep(Duration::from_millis(50)).await;
+		let (_, _, timestamp) = marionette.recv_timestamped().await?;
+		let kernel = timestamp.kernel.expect("kernel timestamp");
+		// allow some slack, since the kernel timestamp may be taken from a coarser clock
+		assert!(kernel + Duration::from_millis(5) >= before, "{kernel:?} < {before:?}");
+		assert!(kernel + Duration::from_millis(40) <= std::time::SystemTime::now());
+
+		// batch receives get their own timestamp per datagram
+		for i in 0..3 {
+			performer.send(VMCTime::new(i as f32)).await?;
+		}
+		let mut batch = Vec::new();
+		while batch.len() < 3 {
+			marionette.recv_batch(&mut batch, 8).await?;
+		}
+		let stamps: Vec<_> = batch
+			.iter()
+			.map(|(_, _, timestamp)| timestamp.kernel.expect("kernel timestamp"))
+			.collect();
+		assert!(stamps[0] >= kernel && stamps.windows(2).all(|pair| pair[0] <= pair[1]));
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_send_bundle_builder() -> VMCResult<()> {
+		let mut marionette = VMCSocket::bind("127.0.0.1:0").await?;
+		let performer = VMCSocket::bind("127.0.0.1:0").await?;
+		performer.connect(marionette.local_addr()?).await?;
+
+		let builder = OSCBundle::builder();
+		assert!(builder.is_empty());
+		let builder = builder
+			.timetag((1, 2))
+			.extend([VMCBlendShape::new("Joy", 1.0), VMCBlendShape::new("Angry", 0.5)])
+			.push_bundle(OSCBundle::builder().push(VMCApplyBlendShapes))
+			.push(VMCTime::new(1.0));
+		assert_eq!(builder.len(), 4);
+
+		let expected = OSCBundle {
+			timetag: OSCTime { seconds: 1, fractional: 2 },
+			content: vec![
+				VMCBlendShape::new("Joy", 1.0).into_osc_packet(),
+				VMCBlendShape::new("Angry", 0.5).into_osc_packet(),
+				OSCPacket::Bundle(OSCBundle {
+					timetag: OSCTime::IMMEDIATE,
+					content: vec![VMCApplyBlendShapes.into_osc_packet()]
+				}),
+				VMCTime::new(1.0).into_osc_packet(),
+			]
+		};
+		assert_eq!(builder.clone().build(), expected);
+
+		// builders can be sent directly, and encode to the same bytes as the equivalent bundle
+		let mut buf = Vec::new();
+		performer.send_with_buf(builder, &mut buf).await?;
+		assert_eq!(buf, osc::encode(&OSCPacket::Bundle(expected.clone()))?);
+		assert_eq!(marionette.recv().await?, OSCPacket::Bundle(expected));
+
+		// nested bundles are flattened into messages in order
+		performer
+			.send(
+				OSCBundle::builder()
+					.push_bundle(OSCBundle::builder().push(VMCTime::new(2.0)))
+					.push(VMCApplyBlendShapes)
+			)
+			.await?;
+		assert!(matches!(&marionette.recv_message().await?[..], [VMCMessage::Time(VMCTime(2.0)), VMCMessage::ApplyBlendShapes]));
+		Ok(())
+	}
+}
@@ -0,0 +1,19 @@
+use std::{
+	net::SocketAddr,
+	sync::{Mutex, PoisonError}
+};
+
+/// The address of the peer which sent the most recently received packet, shared between a socket's receivers & senders
+/// so that senders can [reply](super::VMCSender::reply) to it.
+#[derive(Debug, Default)]
+pub(crate) struct LastPeer(Mutex<Option<SocketAddr>>);
+
+impl LastPeer {
+	pub fn get(&self) -> Option<SocketAddr> {
+		*self.0.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+
+	pub fn set(&self, addr: SocketAddr) {
+		*self.0.lock().unwrap_or_else(PoisonError::into_inner) = Some(addr);
+	}
+}
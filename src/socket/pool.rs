@@ -0,0 +1,146 @@
+use std::{
+	fmt,
+	ops::Deref,
+	sync::{Mutex, PoisonError}
+};
+
+use smallvec::SmallVec;
+
+use crate::{
+	IntoOSCPacket, OSCPacket,
+	osc::{self, EncodeOptions, OSCResult}
+};
+
+/// The maximum number of idle buffers kept by a pool.
+const MAX_BUFFERS: usize = 8;
+/// Buffers which grew larger than this (e.g. to encode a huge bundle) are freed instead of being returned to the pool.
+const MAX_CAPACITY: usize = 64 * 1024;
+
+/// A pool of reusable encode buffers, shared between a [`VMCSender`](super::VMCSender) and its clones, so that sending
+/// a packet doesn't allocate a fresh buffer every time.
+#[derive(Default)]
+pub(crate) struct BufferPool {
+	buffers: Mutex<Vec<Vec<u8>>>
+}
+
+impl fmt::Debug for BufferPool {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("BufferPool").field("idle", &self.idle()).finish()
+	}
+}
+
+impl BufferPool {
+	/// Takes an empty buffer from the pool, or allocates a new one if the pool is empty.
+	pub fn take(&self) -> Vec<u8> {
+		let buf = self.buffers.lock().unwrap_or_else(PoisonError::into_inner).pop();
+		buf.unwrap_or_else(|| Vec::with_capacity(osc::MTU))
+	}
+
+	/// Returns a buffer to the pool.
+	pub fn give(&self, mut buf: Vec<u8>) {
+		if buf.capacity() > MAX_CAPACITY {
+			return;
+		}
+		buf.clear();
+		let mut buffers = self.buffers.lock().unwrap_or_else(PoisonError::into_inner);
+		if buffers.len() < MAX_BUFFERS {
+			buffers.push(buf);
+		}
+	}
+
+	/// Returns the number of idle buffers in the pool.
+	pub fn idle(&self) -> usize {
+		self.buffers.lock().unwrap_or_else(PoisonError::into_inner).len()
+	}
+
+	/// Encodes a packet into a pooled buffer, which is returned to the pool when dropped.
+	pub fn encode(&self, packet: &OSCPacket, options: &EncodeOptions) -> OSCResult<PooledBuffer<'_>> {
+		let packet = options.prepare(packet)?;
+		let mut buf = self.take();
+		// NOTE: The Output implementation for Vec<u8> can't actually produce an error!
+		osc::encode_into(&packet, &mut buf).expect("Failed to write encoded packet into Vec");
+		Ok(PooledBuffer { pool: self, buf })
+	}
+
+	/// Encodes multiple packets back-to-back into a single pooled buffer, returning the buffer along with the offset at
+	/// which each packet ends.
+	pub fn encode_batch<I>(&self, packets: I, options: &EncodeOptions) -> OSCResult<(PooledBuffer<'_>, SmallVec<[usize; 64]>)>
+	where
+		I: IntoIterator,
+		I::Item: IntoOSCPacket
+	{
+		let mut buf = PooledBuffer { pool: self, buf: self.take() };
+		let mut ends = SmallVec::new();
+		for packet in packets {
+			let packet = packet.into_osc_packet();
+			// NOTE: The Output implementation for Vec<u8> can't actually produce an error!
+			osc::encode_into(&*options.prepare(&packet)?, &mut buf.buf).expect("Failed to write encoded packet into Vec");
+			ends.push(buf.len());
+		}
+		Ok((buf, ends))
+	}
+}
+
+/// An encoded packet in a buffer borrowed from a [`BufferPool`].
+pub(crate) struct PooledBuffer<'a> {
+	pool: &'a BufferPool,
+	buf: Vec<u8>
+}
+
+impl PooledBuffer<'_> {
+	/// Detaches the buffer from the pool. It can be returned later with [`BufferPool::give`].
+	pub fn into_inner(mut self) -> Vec<u8> {
+		std::mem::take(&mut self.buf)
+	}
+}
+
+impl Deref for PooledBuffer<'_> {
+	type Target = [u8];
+
+	fn deref(&self) -> &[u8] {
+		&self.buf
+	}
+}
+
+impl Drop for PooledBuffer<'_> {
+	fn drop(&mut self) {
+		// buffers detached with `into_inner` leave an unallocated `Vec` behind, which isn't worth pooling
+		if self.buf.capacity() > 0 {
+			self.pool.give(std::mem::take(&mut self.buf));
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{IntoOSCPacket, VMCTime};
+
+	#[test]
+	fn test_buffer_pool() -> OSCResult<()> {
+		let pool = BufferPool::default();
+		let packet = VMCTime::new(1.0).into_osc_packet();
+
+		let buf = pool.encode(&packet, &EncodeOptions::default())?;
+		assert_eq!(&buf[..], &osc::encode(&packet)?[..]);
+		let ptr = buf.as_ptr();
+		drop(buf);
+		assert_eq!(pool.idle(), 1);
+
+		// the same allocation is reused for the next packet
+		let buf = pool.encode(&packet, &EncodeOptions::default())?;
+		assert_eq!(buf.as_ptr(), ptr);
+		assert_eq!(pool.idle(), 0);
+
+		let buf = buf.into_inner();
+		assert_eq!(pool.idle(), 0);
+		pool.give(buf);
+		assert_eq!(pool.idle(), 1);
+
+		let (buf, ends) = pool.encode_batch([packet.clone(), packet.clone()], &EncodeOptions::default())?;
+		let size = osc::encoded_size(&packet);
+		assert_eq!(&ends[..], &[size, size * 2]);
+		assert_eq!(buf.len(), size * 2);
+		Ok(())
+	}
+}
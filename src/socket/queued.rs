@@ -0,0 +1,270 @@
+use std::sync::{
+	Arc,
+	atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering}
+};
+
+use crossbeam_queue::ArrayQueue;
+use tokio::sync::Notify;
+
+use super::VMCSender;
+use crate::{IntoOSCPacket, OSCPacket, stream::OverflowPolicy};
+
+/// The maximum number of queued packets sent with a single [`VMCSender::send_batch`] call.
+const BATCH_SIZE: usize = 32;
+
+/// Statistics counters for a [`VMCQueuedSender`].
+///
+/// Packets which were sent successfully are counted by the [socket's stats](super::VMCSocketStats).
+#[derive(Debug, Default)]
+pub struct VMCSendQueueStats {
+	queued: AtomicU64,
+	dropped: AtomicU64,
+	send_errors: AtomicU64
+}
+
+impl VMCSendQueueStats {
+	/// Returns the number of packets added to the queue.
+	pub fn queued(&self) -> u64 {
+		self.queued.load(Ordering::Relaxed)
+	}
+
+	/// Returns the number of packets discarded because the queue was full.
+	pub fn dropped(&self) -> u64 {
+		self.dropped.load(Ordering::Relaxed)
+	}
+
+	/// Returns the number of times sending failed. Queued packets are sent in batches, and a failure may lose the rest
+	/// of its batch.
+	pub fn send_errors(&self) -> u64 {
+		self.send_errors.load(Ordering::Relaxed)
+	}
+}
+
+#[derive(Debug)]
+struct Shared {
+	queue: ArrayQueue<OSCPacket>,
+	notify: Notify,
+	stats: VMCSendQueueStats,
+	/// The number of live [`VMCQueuedSender`] handles; the task stops once this drops to zero and the queue is drained.
+	senders: AtomicUsize,
+	task_dropped: AtomicBool
+}
+
+/// Sends packets from a dedicated task, so that sending never has to wait for the socket.
+///
+/// [`VMCQueuedSender::send`] is a synchronous, non-blocking push onto a lock-free queue, which makes it suitable for
+/// calling from a render thread or any other code that can't `await`. Queued packets are sent by a [`VMCSendTask`],
+/// which must be spawned on the runtime.
+///
+/// When the queue is full, the [`OverflowPolicy`] decides what happens to new packets. The default,
+/// [`OverflowPolicy::DropOldest`], discards the oldest queued packet, so receivers always get the latest data. With
+/// [`OverflowPolicy::Wait`], the new packet is handed back to the caller instead.
+///
+/// # Examples
+///
+/// ```no_run
+/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+/// use vmc::{VMCQueuedSender, VMCTime};
+///
+/// let socket = vmc::performer!().await?;
+/// let (sender, task) = VMCQueuedSender::new(socket.sender(), 256);
+/// tokio::spawn(task.run());
+///
+/// std::thread::spawn(move || {
+/// 	loop {
+/// 		// ...render & send bones
+/// 		let _ = sender.send(VMCTime::elapsed());
+/// 	}
+/// });
+/// # Ok(()) }) }
+/// ```
+#[derive(Debug)]
+pub struct VMCQueuedSender {
+	shared: Arc<Shared>,
+	policy: OverflowPolicy
+}
+
+impl VMCQueuedSender {
+	/// Wraps a sender with a queue holding up to `capacity` packets, returning the queued sender along with the task
+	/// that sends its packets.
+	///
+	/// # Panics
+	///
+	/// Panics if `capacity` is zero.
+	pub fn new(sender: VMCSender, capacity: usize) -> (Self, VMCSendTask) {
+		let shared = Arc::new(Shared {
+			queue: ArrayQueue::new(capacity),
+			notify: Notify::new(),
+			stats: VMCSendQueueStats::default(),
+			senders: AtomicUsize::new(1),
+			task_dropped: AtomicBool::new(false)
+		});
+		(
+			Self {
+				shared: Arc::clone(&shared),
+				policy: OverflowPolicy::default()
+			},
+			VMCSendTask { sender, shared }
+		)
+	}
+
+	/// Sets what happens to new packets when the queue is full. Defaults to [`OverflowPolicy::DropOldest`].
+	pub fn with_overflow_policy(mut self, policy: OverflowPolicy) -> Self {
+		self.policy = policy;
+		self
+	}
+
+	/// Returns the policy applied when the queue is full.
+	pub fn overflow_policy(&self) -> OverflowPolicy {
+		self.policy
+	}
+
+	/// Queues a packet to be sent, without waiting.
+	///
+	/// Returns the packet back if it couldn't be queued, which happens if the queue is full and the overflow policy is
+	/// [`OverflowPolicy::Wait`], or if the [`VMCSendTask`] has been dropped. Packets discarded because of any other
+	/// overflow policy are counted by [`VMCSendQueueStats::dropped`].
+	// the packet is handed back as-is, like `ArrayQueue::push` does, rather than boxed
+	#[allow(clippy::result_large_err)]
+	pub fn send<P: IntoOSCPacket>(&self, packet: P) -> Result<(), OSCPacket> {
+		let packet = packet.into_osc_packet();
+		if self.shared.task_dropped.load(Ordering::Acquire) {
+			return Err(packet);
+		}
+		let stats = &self.shared.stats;
+		match self.policy {
+			OverflowPolicy::Wait => self.shared.queue.push(packet)?,
+			OverflowPolicy::DropNewest => {
+				if self.shared.queue.push(packet).is_err() {
+					stats.dropped.fetch_add(1, Ordering::Relaxed);
+					return Ok(());
+				}
+			}
+			OverflowPolicy::DropOldest => {
+				if self.shared.queue.force_push(packet).is_some() {
+					stats.dropped.fetch_add(1, Ordering::Relaxed);
+				}
+			}
+		}
+		stats.queued.fetch_add(1, Ordering::Relaxed);
+		self.shared.notify.notify_one();
+		Ok(())
+	}
+
+	/// Returns the number of packets waiting to be sent.
+	pub fn len(&self) -> usize {
+		self.shared.queue.len()
+	}
+
+	/// Returns `true` if no packets are waiting to be sent.
+	pub fn is_empty(&self) -> bool {
+		self.shared.queue.is_empty()
+	}
+
+	/// Returns the maximum number of packets the queue can hold.
+	pub fn capacity(&self) -> usize {
+		self.shared.queue.capacity()
+	}
+
+	/// Returns the queue's statistics counters.
+	pub fn stats(&self) -> &VMCSendQueueStats {
+		&self.shared.stats
+	}
+}
+
+impl Clone for VMCQueuedSender {
+	fn clone(&self) -> Self {
+		self.shared.senders.fetch_add(1, Ordering::Relaxed);
+		Self {
+			shared: Arc::clone(&self.shared),
+			policy: self.policy
+		}
+	}
+}
+
+impl Drop for VMCQueuedSender {
+	fn drop(&mut self) {
+		if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+			self.shared.notify.notify_one();
+		}
+	}
+}
+
+/// Sends the packets queued by a [`VMCQueuedSender`], created by [`VMCQueuedSender::new`].
+///
+/// The task does nothing until [`run`](VMCSendTask::run) is spawned on the runtime.
+#[derive(Debug)]
+pub struct VMCSendTask {
+	sender: VMCSender,
+	shared: Arc<Shared>
+}
+
+impl VMCSendTask {
+	/// Sends queued packets until all [`VMCQueuedSender`]s have been dropped and the queue is empty.
+	///
+	/// Packets which are queued while a previous batch is being sent are sent together in the next batch. Sending
+	/// errors don't stop the task; they are counted by [`VMCSendQueueStats::send_errors`].
+	pub async fn run(self) {
+		let mut batch = Vec::with_capacity(BATCH_SIZE);
+		loop {
+			while batch.len() < BATCH_SIZE {
+				match self.shared.queue.pop() {
+					Some(packet) => batch.push(packet),
+					None => break
+				}
+			}
+			if !batch.is_empty() {
+				if self.sender.send_batch(batch.drain(..)).await.is_err() {
+					self.shared.stats.send_errors.fetch_add(1, Ordering::Relaxed);
+				}
+				continue;
+			}
+			if self.shared.senders.load(Ordering::Acquire) == 0 {
+				if self.shared.queue.is_empty() {
+					return;
+				}
+				continue;
+			}
+			self.shared.notify.notified().await;
+		}
+	}
+}
+
+impl Drop for VMCSendTask {
+	fn drop(&mut self) {
+		self.shared.task_dropped.store(true, Ordering::Release);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{VMCResult, VMCSocket, VMCTime};
+
+	#[tokio::test]
+	async fn test_queued_sender() -> VMCResult<()> {
+		let mut marionette = VMCSocket::bind("127.0.0.1:0").await?;
+		let performer = VMCSocket::bind("127.0.0.1:0").await?;
+		performer.connect(marionette.local_addr()?).await?;
+
+		let (sender, task) = VMCQueuedSender::new(performer.sender(), 4);
+		// the task isn't running yet, so the oldest packets are dropped to make room
+		for i in 0..6 {
+			sender.send(VMCTime::new(i as f32)).unwrap();
+		}
+		assert_eq!(sender.len(), 4);
+		assert_eq!(sender.stats().dropped(), 2);
+
+		let waiting = sender.clone().with_overflow_policy(OverflowPolicy::Wait);
+		assert_eq!(waiting.send(VMCTime::new(6.0)), Err(VMCTime::new(6.0).into_osc_packet()));
+		drop((sender, waiting));
+
+		// the task sends the remaining packets, then stops since all senders are gone
+		task.run().await;
+		for i in 2..6 {
+			assert_eq!(marionette.recv().await?, VMCTime::new(i as f32).into_osc_packet());
+		}
+		assert_eq!(performer.stats().packets_sent(), 4);
+		Ok(())
+	}
+}
@@ -0,0 +1,95 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Statistics counters for a [`VMCSocket`](super::VMCSocket).
+///
+/// Counters are updated atomically as packets are sent & received, and are shared between a socket and all of its
+/// [senders](super::VMCSender) and [receivers](super::VMCReceiver).
+///
+/// # Examples
+///
+/// ```no_run
+/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+/// let socket = vmc::marionette!().await?;
+/// let stats = socket.stats();
+/// println!(
+/// 	"received {} packets ({} bytes), {} decode errors",
+/// 	stats.packets_received(),
+/// 	stats.bytes_received(),
+/// 	stats.decode_errors()
+/// );
+/// # Ok(()) }) }
+/// ```
+#[derive(Debug, Default)]
+pub struct VMCSocketStats {
+	packets_sent: AtomicU64,
+	bytes_sent: AtomicU64,
+	packets_received: AtomicU64,
+	bytes_received: AtomicU64,
+	decode_errors: AtomicU64,
+	dropped_packets: AtomicU64
+}
+
+impl VMCSocketStats {
+	/// Returns the number of packets successfully sent.
+	pub fn packets_sent(&self) -> u64 {
+		self.packets_sent.load(Ordering::Relaxed)
+	}
+
+	/// Returns the number of bytes successfully sent.
+	pub fn bytes_sent(&self) -> u64 {
+		self.bytes_sent.load(Ordering::Relaxed)
+	}
+
+	/// Returns the number of packets received, including packets that failed to decode or were dropped.
+	pub fn packets_received(&self) -> u64 {
+		self.packets_received.load(Ordering::Relaxed)
+	}
+
+	/// Returns the number of bytes received, including packets that failed to decode or were dropped.
+	pub fn bytes_received(&self) -> u64 {
+		self.bytes_received.load(Ordering::Relaxed)
+	}
+
+	/// Returns the number of received packets that could not be decoded as OSC.
+	pub fn decode_errors(&self) -> u64 {
+		self.decode_errors.load(Ordering::Relaxed)
+	}
+
+	/// Returns the number of received packets that were dropped because their sender was not in the
+	/// [peer allowlist](super::VMCSocket::allow_peer).
+	pub fn dropped_packets(&self) -> u64 {
+		self.dropped_packets.load(Ordering::Relaxed)
+	}
+
+	/// Resets all counters to zero.
+	pub fn reset(&self) {
+		for counter in [
+			&self.packets_sent,
+			&self.bytes_sent,
+			&self.packets_received,
+			&self.bytes_received,
+			&self.decode_errors,
+			&self.dropped_packets
+		] {
+			counter.store(0, Ordering::Relaxed);
+		}
+	}
+
+	pub(crate) fn record_sent(&self, len: usize) {
+		self.packets_sent.fetch_add(1, Ordering::Relaxed);
+		self.bytes_sent.fetch_add(len as u64, Ordering::Relaxed);
+	}
+
+	pub(crate) fn record_received(&self, len: usize) {
+		self.packets_received.fetch_add(1, Ordering::Relaxed);
+		self.bytes_received.fetch_add(len as u64, Ordering::Relaxed);
+	}
+
+	pub(crate) fn record_decode_error(&self) {
+		self.decode_errors.fetch_add(1, Ordering::Relaxed);
+	}
+
+	pub(crate) fn record_dropped(&self) {
+		self.dropped_packets.fetch_add(1, Ordering::Relaxed);
+	}
+}
@@ -0,0 +1,25 @@
+use std::time::Duration;
+
+/// Whether the target of a connected socket appears to be running, as determined by
+/// [`VMCSocket::probe`](super::VMCSocket::probe).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VMCPeerStatus {
+	/// The target sent something back within the timeout.
+	Responding {
+		/// The time from sending the probe until traffic from the target arrived. If the target was already sending,
+		/// this is close to zero.
+		elapsed: Duration
+	},
+	/// Nothing arrived from the target within the timeout. The target may not be running, may not respond to requests,
+	/// or may be unreachable through a firewall.
+	NotResponding,
+	/// The target's host reported that nothing is listening on the target port.
+	Refused
+}
+
+impl VMCPeerStatus {
+	/// Returns `true` if the target responded.
+	pub fn is_responding(&self) -> bool {
+		matches!(self, VMCPeerStatus::Responding { .. })
+	}
+}
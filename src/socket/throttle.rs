@@ -0,0 +1,247 @@
+use std::{
+	collections::HashMap,
+	time::{Duration, Instant}
+};
+
+use super::VMCSender;
+use crate::{
+	IntoOSCMessage, VMCMessage, VMCResult,
+	message::{DeviceType, Time},
+	name::Name,
+	osc,
+	pose::pack_bundles
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Channel {
+	Root,
+	Bone(Name),
+	Device(DeviceType, String, bool),
+	BlendShape(Name),
+	ApplyBlendShapes,
+	State
+}
+
+/// Wraps a [`VMCSender`] to limit the rate at which frames & messages are sent.
+///
+/// Messages are buffered until a [`Time`](crate::VMCTime) message completes the frame. If the frame is due according
+/// to the configured limits, all buffered messages are sent, packed into as few bundles as possible; otherwise, they
+/// are kept until the next frame, with newer updates of the same bone, device, or blend shape replacing older ones.
+/// This way, no bone is ever left out of date, but a performer producing frames faster than receivers need them doesn't
+/// waste bandwidth.
+///
+/// Note that `/VMC/Ext/Set/Period` requests from receivers are not supported yet, so limits must be configured
+/// manually.
+///
+/// # Examples
+///
+/// ```no_run
+/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+/// use vmc::{VMCThrottledSender, VMCTime};
+///
+/// let socket = vmc::performer!().await?;
+/// let mut sender = VMCThrottledSender::new(socket.sender()).with_max_fps(30.0);
+/// loop {
+/// 	// ...send bones at whatever rate tracking runs at
+/// 	sender.send(VMCTime::elapsed()).await?;
+/// }
+/// # Ok(()) }) }
+/// ```
+#[derive(Debug)]
+pub struct VMCThrottledSender {
+	sender: VMCSender,
+	frame_interval: Option<Duration>,
+	message_rate: Option<f64>,
+	tokens: f64,
+	refilled: Instant,
+	last_frame: Option<Instant>,
+	pending: Vec<VMCMessage>,
+	slots: HashMap<Channel, usize>,
+	time: Option<Time>
+}
+
+impl VMCThrottledSender {
+	/// Wraps a sender without any limits; use [`VMCThrottledSender::with_max_fps`] and
+	/// [`VMCThrottledSender::with_max_message_rate`] to configure them.
+	pub fn new(sender: VMCSender) -> Self {
+		Self {
+			sender,
+			frame_interval: None,
+			message_rate: None,
+			tokens: 0.0,
+			refilled: Instant::now(),
+			last_frame: None,
+			pending: Vec::new(),
+			slots: HashMap::new(),
+			time: None
+		}
+	}
+
+	/// Limits the number of frames sent per second.
+	///
+	/// # Panics
+	///
+	/// Panics if `fps` is not positive & finite.
+	pub fn with_max_fps(mut self, fps: f64) -> Self {
+		self.set_max_fps(Some(fps));
+		self
+	}
+
+	/// Limits the number of messages sent per second, counting each message within a bundle.
+	///
+	/// Up to one second's worth of messages can be sent in a burst. A frame is only sent once enough budget is
+	/// available for all of its messages, so frames are never split.
+	///
+	/// # Panics
+	///
+	/// Panics if `rate` is not positive & finite.
+	pub fn with_max_message_rate(mut self, rate: f64) -> Self {
+		self.set_max_message_rate(Some(rate));
+		self
+	}
+
+	/// Sets or removes the frame rate limit. See [`VMCThrottledSender::with_max_fps`].
+	///
+	/// # Panics
+	///
+	/// Panics if `fps` is not positive & finite.
+	pub fn set_max_fps(&mut self, fps: Option<f64>) {
+		self.frame_interval = fps.map(|fps| {
+			assert!(fps > 0.0 && fps.is_finite(), "frame rate must be positive");
+			Duration::from_secs_f64(1.0 / fps)
+		});
+	}
+
+	/// Sets or removes the message rate limit. See [`VMCThrottledSender::with_max_message_rate`].
+	///
+	/// # Panics
+	///
+	/// Panics if `rate` is not positive & finite.
+	pub fn set_max_message_rate(&mut self, rate: Option<f64>) {
+		if let Some(rate) = rate {
+			assert!(rate > 0.0 && rate.is_finite(), "message rate must be positive");
+			self.tokens = rate;
+			self.refilled = Instant::now();
+		}
+		self.message_rate = rate;
+	}
+
+	/// Returns the frame rate limit.
+	pub fn max_fps(&self) -> Option<f64> {
+		self.frame_interval.map(|interval| 1.0 / interval.as_secs_f64())
+	}
+
+	/// Returns the message rate limit.
+	pub fn max_message_rate(&self) -> Option<f64> {
+		self.message_rate
+	}
+
+	/// Returns the number of messages waiting to be sent with the next frame.
+	pub fn pending(&self) -> usize {
+		self.pending.len()
+	}
+
+	/// Get a reference to the underlying sender.
+	pub fn get_ref(&self) -> &VMCSender {
+		&self.sender
+	}
+
+	/// Consumes the throttled sender, returning the underlying sender. Any pending messages are discarded.
+	pub fn into_inner(self) -> VMCSender {
+		self.sender
+	}
+
+	/// Queues a message, sending the pending frame if it is completed by this message and due.
+	pub async fn send(&mut self, message: impl Into<VMCMessage>) -> VMCResult<()> {
+		let message = message.into();
+		let channel = match &message {
+			VMCMessage::RootTransform(_) => Channel::Root,
+			VMCMessage::BoneTransform(transform) => Channel::Bone(transform.bone.clone()),
+			VMCMessage::DeviceTransform(transform) => Channel::Device(transform.device, transform.joint.clone(), transform.local),
+			VMCMessage::BlendShape(blendshape) => Channel::BlendShape(blendshape.key.clone()),
+			VMCMessage::ApplyBlendShapes => Channel::ApplyBlendShapes,
+			VMCMessage::State(_) => Channel::State,
+			VMCMessage::Time(time) => {
+				self.time = Some(time.clone());
+				if self.is_due(Instant::now()) {
+					self.flush().await?;
+				}
+				return Ok(());
+			}
+		};
+		match self.slots.get(&channel) {
+			Some(&index) => self.pending[index] = message,
+			None => {
+				self.slots.insert(channel, self.pending.len());
+				self.pending.push(message);
+			}
+		}
+		Ok(())
+	}
+
+	fn is_due(&mut self, now: Instant) -> bool {
+		if let (Some(interval), Some(last_frame)) = (self.frame_interval, self.last_frame) {
+			if now.saturating_duration_since(last_frame) < interval {
+				return false;
+			}
+		}
+		if let Some(rate) = self.message_rate {
+			self.tokens = (self.tokens + now.saturating_duration_since(self.refilled).as_secs_f64() * rate).min(rate);
+			self.refilled = now;
+			// a frame larger than the burst size would never be sent otherwise
+			let cost = (self.pending.len() + 1) as f64;
+			if self.tokens < cost.min(rate) {
+				return false;
+			}
+		}
+		true
+	}
+
+	/// Sends all pending messages immediately, regardless of the configured limits, followed by the latest
+	/// [`Time`](crate::VMCTime) message if one was queued.
+	pub async fn flush(&mut self) -> VMCResult<()> {
+		let now = Instant::now();
+		self.last_frame = Some(now);
+		let mut messages = std::mem::take(&mut self.pending);
+		self.slots.clear();
+		messages.extend(self.time.take().map(VMCMessage::Time));
+		if self.message_rate.is_some() {
+			self.tokens -= messages.len() as f64;
+		}
+		let messages = messages.into_iter().map(IntoOSCMessage::into_osc_message).collect();
+		for packet in pack_bundles(messages, osc::MAX_UDP_PAYLOAD) {
+			self.sender.send(packet).await?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{Quat, VMCBoneTransform, VMCSocket, VMCStandardVRM0Bone, VMCTime, Vec3A};
+
+	#[tokio::test]
+	async fn test_throttle_coalesces() -> VMCResult<()> {
+		let mut marionette = VMCSocket::bind("127.0.0.1:0").await?;
+		let performer = VMCSocket::bind("127.0.0.1:0").await?;
+		performer.connect(marionette.local_addr()?).await?;
+
+		let mut sender = VMCThrottledSender::new(performer.sender()).with_max_fps(1.0);
+		for i in 0..5 {
+			sender
+				.send(VMCBoneTransform::new(VMCStandardVRM0Bone::Head, Vec3A::X * i as f32, Quat::IDENTITY))
+				.await?;
+			sender.send(VMCTime::new(i as f32)).await?;
+		}
+		assert_eq!(sender.pending(), 1);
+		sender.flush().await?;
+
+		let first = marionette.recv_message().await?;
+		assert!(matches!(&first[..], [VMCMessage::BoneTransform(transform), VMCMessage::Time(time)] if transform.position.x == 0.0 && time.0 == 0.0));
+		let second = marionette.recv_message().await?;
+		assert!(matches!(&second[..], [VMCMessage::BoneTransform(transform), VMCMessage::Time(time)] if transform.position.x == 4.0 && time.0 == 4.0));
+		assert_eq!(performer.stats().packets_sent(), 2);
+		Ok(())
+	}
+}
@@ -0,0 +1,24 @@
+use std::time::{Instant, SystemTime};
+
+/// The time at which a packet was received.
+///
+/// See [`VMCSocket::recv_timestamped`](super::VMCSocket::recv_timestamped).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VMCRecvTimestamp {
+	/// Monotonic time taken immediately after the packet was read from the socket.
+	pub received: Instant,
+	/// The time the packet was received by the OS network stack, as reported by the kernel via `SO_TIMESTAMP`.
+	///
+	/// This is unaffected by task scheduling delays, but is only available on Linux & Android if enabled with
+	/// [`VMCSocketBuilder::kernel_timestamps`](super::VMCSocketBuilder::kernel_timestamps); otherwise, it is `None`.
+	///
+	/// If no other socket on the system had timestamps enabled, Linux only starts timestamping packets shortly after
+	/// the socket is created; packets received before then are stamped with the time they were read instead.
+	pub kernel: Option<SystemTime>
+}
+
+impl VMCRecvTimestamp {
+	pub(crate) fn now(kernel: Option<SystemTime>) -> Self {
+		Self { received: Instant::now(), kernel }
+	}
+}
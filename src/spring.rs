@@ -0,0 +1,291 @@
+//! Procedural secondary motion for auxiliary bones like hair, tails, & accessories.
+//!
+//! Trackers only drive the humanoid bones, so hair & tails would stay rigid. [`SpringBones`] simulates chains of custom
+//! bones hanging off a humanoid bone with the same verlet integration as VRM spring bones: each joint's tail keeps its
+//! inertia (reduced by drag), is pulled back towards its rest direction by the stiffness, and is pulled down by
+//! gravity. The simulated rotations are emitted as extra [`BoneTransform`]s, or added to a
+//! received pose with [`SpringBones::apply`].
+//!
+//! Like the humanoid bones, the rest pose of a chain is described in model space with identity rotations: +X is the
+//! avatar's right, +Y is up, and +Z is forward. Emitted rotations are relative to that rest pose.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use vmc::{
+//! 	Quat, VMCPose, VMCStandardVRM0Bone, Vec3A,
+//! 	spring::{SpringBones, SpringChain}
+//! };
+//!
+//! // a ponytail hanging from the back of the head
+//! let mut springs = SpringBones::new().with_chain(
+//! 	SpringChain::new(VMCStandardVRM0Bone::Head)
+//! 		.with_joint("Ponytail1", Vec3A::new(0.0, 0.08, -0.1))
+//! 		.with_joint("Ponytail2", Vec3A::new(0.0, -0.1, -0.02))
+//! 		.with_tail(Vec3A::new(0.0, -0.1, 0.0))
+//! 		.with_gravity(Vec3A::NEG_Y * 0.5)
+//! );
+//!
+//! let mut pose = VMCPose::new();
+//! pose.set_bone(VMCStandardVRM0Bone::Hips, Vec3A::new(0.0, 1.0, 0.0), Quat::IDENTITY);
+//! pose.set_bone(VMCStandardVRM0Bone::Head, Vec3A::new(0.0, 0.6, 0.0), Quat::from_rotation_y(0.5));
+//! springs.apply(&mut pose, Duration::from_millis(16));
+//! assert!(pose.bone("Ponytail2").is_some());
+//! ```
+
+use std::time::Duration;
+
+use glam::{Quat, Vec3A};
+
+use crate::{VMCBoneTransform as BoneTransform, VMCPose as Pose, VMCStandardVRM0Bone as StandardVRM0Bone, name::Name};
+
+/// The longest time step simulated at once; longer gaps between updates, e.g. from packet loss, are shortened so the
+/// simulation stays stable.
+const MAX_STEP: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, PartialEq)]
+struct Joint {
+	bone: Name,
+	offset: Vec3A
+}
+
+/// A chain of custom bones simulated by [`SpringBones`], attached to a humanoid bone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpringChain {
+	parent: StandardVRM0Bone,
+	joints: Vec<Joint>,
+	tail: Option<Vec3A>,
+	stiffness: f32,
+	drag: f32,
+	gravity: Vec3A
+}
+
+impl SpringChain {
+	/// Creates an empty chain attached to a humanoid bone, with a stiffness of `1.0`, a drag of `0.4`, and no gravity.
+	pub fn new(parent: StandardVRM0Bone) -> Self {
+		Self {
+			parent,
+			joints: Vec::new(),
+			tail: None,
+			stiffness: 1.0,
+			drag: 0.4,
+			gravity: Vec3A::ZERO
+		}
+	}
+
+	/// Appends a joint to the chain, at the given rest offset from the previous joint (or the parent bone, for the
+	/// first joint). The offset is also the position of the emitted bone transform.
+	pub fn with_joint(mut self, bone: impl Into<Name>, offset: impl Into<Vec3A>) -> Self {
+		self.joints.push(Joint {
+			bone: bone.into(),
+			offset: offset.into()
+		});
+		self
+	}
+
+	/// Sets the rest offset from the last joint to the end of the chain. Without a tail, the last joint is extended by
+	/// its own offset, or 7cm downwards if that is zero.
+	pub fn with_tail(mut self, tail: impl Into<Vec3A>) -> Self {
+		self.tail = Some(tail.into());
+		self
+	}
+
+	/// Sets how strongly joints return to their rest direction, in the same units as VRM spring bones.
+	pub fn with_stiffness(mut self, stiffness: f32) -> Self {
+		self.stiffness = stiffness;
+		self
+	}
+
+	/// Sets how much of their velocity joints lose each update, from `0.0` to `1.0`.
+	pub fn with_drag(mut self, drag: f32) -> Self {
+		self.drag = drag.clamp(0.0, 1.0);
+		self
+	}
+
+	/// Sets the gravity acting on the chain, as a direction scaled by its strength, in model space.
+	pub fn with_gravity(mut self, gravity: impl Into<Vec3A>) -> Self {
+		self.gravity = gravity.into();
+		self
+	}
+
+	/// Returns the rest offset from each joint to the next, and from the last joint to the tail.
+	fn lengths(&self) -> impl Iterator<Item = Vec3A> + '_ {
+		let tail = self.tail.unwrap_or_else(|| {
+			let last = self.joints.last().map_or(Vec3A::ZERO, |joint| joint.offset);
+			if last.length_squared() > 0.0 { last } else { Vec3A::NEG_Y * 0.07 }
+		});
+		self.joints.iter().skip(1).map(|joint| joint.offset).chain(std::iter::once(tail))
+	}
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TailState {
+	current: Vec3A,
+	previous: Vec3A
+}
+
+/// Simulates [`SpringChain`]s on top of received poses; see the [module documentation](self).
+#[derive(Debug, Clone, Default)]
+pub struct SpringBones {
+	chains: Vec<(SpringChain, Option<Vec<TailState>>)>
+}
+
+impl SpringBones {
+	/// Creates a simulator without any chains.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Adds a chain to the simulation.
+	pub fn with_chain(mut self, chain: SpringChain) -> Self {
+		self.chains.push((chain, None));
+		self
+	}
+
+	/// Returns the chains in the simulation.
+	pub fn chains(&self) -> impl Iterator<Item = &SpringChain> + '_ {
+		self.chains.iter().map(|(chain, _)| chain)
+	}
+
+	/// Advances the simulation by `dt` following the motion of a pose, and returns the transforms of all simulated
+	/// bones.
+	///
+	/// Chains whose parent bone is missing from the pose are skipped, and restart from their rest pose once it returns.
+	/// The first update of each chain also starts from the rest pose.
+	pub fn update(&mut self, pose: &Pose, dt: Duration) -> Vec<BoneTransform> {
+		let dt = dt.min(MAX_STEP).as_secs_f32();
+		let mut transforms = Vec::new();
+		for (chain, state) in &mut self.chains {
+			let Some((mut position, mut parent_rotation)) = pose.world_transform(chain.parent) else {
+				*state = None;
+				continue;
+			};
+			let state = state.get_or_insert_with(|| {
+				// lay the chain out in its rest pose
+				let mut position = position;
+				chain
+					.joints
+					.iter()
+					.zip(chain.lengths())
+					.map(|(joint, length)| {
+						position += parent_rotation * joint.offset;
+						let tail = position + parent_rotation * length;
+						TailState { current: tail, previous: tail }
+					})
+					.collect()
+			});
+
+			for ((joint, length), tail) in chain.joints.iter().zip(chain.lengths()).zip(state.iter_mut()) {
+				position += parent_rotation * joint.offset;
+				let rest = parent_rotation * length;
+				let inertia = (tail.current - tail.previous) * (1.0 - chain.drag);
+				let mut next = tail.current + inertia + rest.normalize_or_zero() * chain.stiffness * dt + chain.gravity * dt;
+				// keep the bone at its length
+				next = position + (next - position).try_normalize().unwrap_or(rest.normalize_or_zero()) * length.length();
+				tail.previous = tail.current;
+				tail.current = next;
+
+				let rotation = match (rest.try_normalize(), (next - position).try_normalize()) {
+					(Some(from), Some(to)) => Quat::from_rotation_arc(from.into(), to.into()) * parent_rotation,
+					_ => parent_rotation
+				};
+				let local = (parent_rotation.inverse() * rotation).normalize();
+				transforms.push(BoneTransform::new(joint.bone.clone(), joint.offset, local));
+				parent_rotation = rotation;
+			}
+		}
+		transforms
+	}
+
+	/// Advances the simulation like [`SpringBones::update`], and adds the simulated bones to the pose.
+	pub fn apply(&mut self, pose: &mut Pose, dt: Duration) {
+		for transform in self.update(pose, dt) {
+			pose.bones.insert(transform.bone.clone(), transform);
+		}
+	}
+
+	/// Resets all chains to their rest pose.
+	pub fn reset(&mut self) {
+		for (_, state) in &mut self.chains {
+			*state = None;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use approx::assert_relative_eq;
+
+	use super::*;
+	use crate::VMCMessage;
+
+	#[test]
+	fn test_spring_bones() {
+		let chain = SpringChain::new(StandardVRM0Bone::Head)
+			.with_joint("Tail1", Vec3A::new(0.0, 0.0, -0.1))
+			.with_joint("Tail2", Vec3A::new(0.0, 0.0, -0.2))
+			.with_stiffness(2.0)
+			.with_drag(0.2);
+		let mut springs = SpringBones::new().with_chain(chain.clone());
+		let dt = Duration::from_millis(16);
+
+		let mut pose = Pose::new();
+		pose.set_bone(StandardVRM0Bone::Hips, Vec3A::Y, Quat::IDENTITY);
+		pose.set_bone(StandardVRM0Bone::Head, Vec3A::Y * 0.6, Quat::IDENTITY);
+
+		// at rest, the chain stays in its rest pose
+		let transforms = springs.update(&pose, dt);
+		assert_eq!(transforms.len(), 2);
+		assert_eq!(transforms[0].bone, "Tail1");
+		assert_eq!(transforms[1].position, Vec3A::new(0.0, 0.0, -0.2));
+		for transform in &transforms {
+			assert_relative_eq!(transform.rotation, Quat::IDENTITY, epsilon = 1e-5);
+		}
+
+		// turning the head quickly makes the tail lag behind, then settle
+		pose.set_bone(StandardVRM0Bone::Head, Vec3A::Y * 0.6, Quat::from_rotation_y(1.0));
+		let lagging = springs.update(&pose, dt);
+		assert!(lagging[0].rotation.angle_between(Quat::IDENTITY) > 0.5);
+		for _ in 0..500 {
+			springs.update(&pose, dt);
+		}
+		for transform in springs.update(&pose, dt) {
+			assert!(transform.rotation.angle_between(Quat::IDENTITY) < 0.01);
+		}
+
+		// gravity bends the chain down
+		let mut springs = SpringBones::new().with_chain(chain.with_gravity(Vec3A::NEG_Y * 2.0));
+		for _ in 0..500 {
+			springs.update(&pose, dt);
+		}
+		let mut with_tail = pose.clone();
+		springs.apply(&mut with_tail, dt);
+		let (_, rotation) = with_tail.world_transform(StandardVRM0Bone::Head).unwrap();
+		let tail = rotation * with_tail.bone("Tail1").unwrap().rotation * Vec3A::NEG_Z;
+		assert!(tail.y < -0.1);
+
+		// the simulated bones survive being sent & parsed along with the humanoid bones
+		let packets = with_tail.to_packets();
+		let messages: Vec<_> = packets
+			.into_iter()
+			.map(crate::parse)
+			.collect::<Result<Vec<_>, _>>()
+			.unwrap()
+			.into_iter()
+			.flatten()
+			.collect();
+		for bone in ["Head", "Tail1", "Tail2"] {
+			assert!(
+				messages
+					.iter()
+					.any(|message| matches!(message, VMCMessage::BoneTransform(transform) if transform.bone == bone))
+			);
+		}
+
+		// chains without their parent are skipped
+		pose.bones.remove("Head");
+		assert!(springs.update(&pose, dt).is_empty());
+	}
+}
@@ -0,0 +1,405 @@
+//! Stream adapters for VMC sockets.
+
+use std::{
+	collections::{HashMap, VecDeque},
+	net::SocketAddr,
+	pin::Pin,
+	task::{Context, Poll, ready},
+	time::{Duration, Instant}
+};
+
+use futures_core::Stream;
+use tokio::time::{Interval, MissedTickBehavior};
+
+use crate::{OSCPacket, VMCAvatarState, VMCMessage, VMCPose, VMCReceiver, VMCRecvTimestamp, VMCResult, VMCTime, parse};
+
+mod latest;
+mod merge;
+mod scheduled;
+mod split;
+mod watchdog;
+
+pub use self::{
+	latest::Latest,
+	merge::{ConflictPolicy, Merge},
+	scheduled::Scheduled,
+	split::{OverflowPolicy, Split, SplitReceiver, SplitStreams},
+	watchdog::{LivenessEvent, LivenessSignal, Watchdog}
+};
+
+/// A stream of parsed [`VMCMessage`]s, created by [`VMCSocket::messages`](crate::VMCSocket::messages).
+///
+/// Packets received from the inner stream are decoded and [parsed](crate::parse); bundles are flattened so that each
+/// item of the stream is a single message, paired with the address of the peer that sent it.
+#[derive(Debug)]
+pub struct Messages<S> {
+	stream: S,
+	pending: VecDeque<(VMCMessage, SocketAddr)>
+}
+
+impl<S> Messages<S> {
+	/// Wraps a stream of OSC packets.
+	pub fn new(stream: S) -> Self {
+		Self { stream, pending: VecDeque::new() }
+	}
+
+	/// Get a reference to the inner stream.
+	pub fn get_ref(&self) -> &S {
+		&self.stream
+	}
+
+	/// Get a mutable reference to the inner stream.
+	pub fn get_mut(&mut self) -> &mut S {
+		&mut self.stream
+	}
+
+	/// Consumes this adapter, returning the inner stream.
+	///
+	/// Any messages parsed from the last packet which have not yet been yielded are discarded.
+	pub fn into_inner(self) -> S {
+		self.stream
+	}
+
+	/// Groups the messages of this stream into [`Frame`]s. See [`Frames`].
+	pub fn frames(self) -> Frames<Self> {
+		Frames::new(self)
+	}
+
+	/// Resamples the messages of this stream into poses emitted at a fixed rate. See [`Resampler`].
+	pub fn resample(self, rate: f64, mode: ResampleMode) -> Resampler<Self> {
+		Resampler::new(self, rate, mode)
+	}
+
+	/// Splits the messages of this stream into independent sub-streams by kind. See [`Split`].
+	pub fn split(self, capacity: usize, policy: OverflowPolicy) -> (Split<Self>, SplitStreams) {
+		Split::new(self, capacity, policy)
+	}
+
+	/// Keeps only the latest message of each bone, device, blend shape, etc. when the consumer falls behind. See
+	/// [`Latest`].
+	pub fn latest(self) -> Latest<Self> {
+		Latest::new(self)
+	}
+
+	/// Reports when the performer stops sending for longer than `timeout`, and when it resumes. See [`Watchdog`].
+	pub fn watchdog(self, timeout: Duration) -> Watchdog<Self> {
+		Watchdog::new(self, timeout)
+	}
+}
+
+impl<S> Stream for Messages<S>
+where
+	S: Stream<Item = VMCResult<(OSCPacket, SocketAddr)>> + Unpin
+{
+	type Item = VMCResult<(VMCMessage, SocketAddr)>;
+
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		loop {
+			if let Some(message) = self.pending.pop_front() {
+				return Poll::Ready(Some(Ok(message)));
+			}
+
+			match ready!(Pin::new(&mut self.stream).poll_next(cx)) {
+				Some(Ok((packet, addr))) => match parse(packet) {
+					Ok(messages) => self.pending.extend(messages.into_iter().map(|message| (message, addr))),
+					Err(e) => return Poll::Ready(Some(Err(e)))
+				},
+				Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+				None => return Poll::Ready(None)
+			}
+		}
+	}
+}
+
+/// A complete frame of messages, terminated by a [`Time`](crate::VMCTime) message.
+#[derive(Debug, Clone)]
+pub struct Frame {
+	/// The time message which ended this frame.
+	pub time: VMCTime,
+	/// All messages received since the previous frame, in order, excluding the time message.
+	pub messages: Vec<VMCMessage>
+}
+
+/// A stream of [`Frame`]s, created by [`VMCSocket::frames`](crate::VMCSocket::frames) or [`Messages::frames`].
+///
+/// In VMC, the [`Time`](crate::VMCTime) message marks the end of a frame, signaling that the marionette should render.
+/// This adapter buffers incoming messages and yields them all at once when a time message arrives, so that consumers
+/// can apply each frame atomically instead of handling interleaved messages.
+///
+/// Messages are buffered separately for each peer, so frames sent by multiple performers are not mixed. Messages that
+/// were not followed by a time message when the stream ends are discarded.
+///
+/// # Examples
+///
+/// ```no_run
+/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+/// use futures_util::StreamExt;
+///
+/// let mut socket = vmc::marionette!().await?;
+/// let mut frames = socket.frames();
+/// while let Some(frame) = frames.next().await {
+/// 	let (frame, addr) = frame?;
+/// 	println!("{} messages from {} at t={}", frame.messages.len(), addr, frame.time.0);
+/// }
+/// # Ok(()) }) }
+/// ```
+#[derive(Debug)]
+pub struct Frames<S> {
+	stream: S,
+	pending: HashMap<SocketAddr, Vec<VMCMessage>>
+}
+
+impl<S> Frames<S> {
+	/// Wraps a stream of [`VMCMessage`]s.
+	pub fn new(stream: S) -> Self {
+		Self { stream, pending: HashMap::new() }
+	}
+
+	/// Get a reference to the inner stream.
+	pub fn get_ref(&self) -> &S {
+		&self.stream
+	}
+
+	/// Get a mutable reference to the inner stream.
+	pub fn get_mut(&mut self) -> &mut S {
+		&mut self.stream
+	}
+
+	/// Consumes this adapter, returning the inner stream.
+	///
+	/// Any buffered messages belonging to incomplete frames are discarded.
+	pub fn into_inner(self) -> S {
+		self.stream
+	}
+}
+
+impl<S> Stream for Frames<S>
+where
+	S: Stream<Item = VMCResult<(VMCMessage, SocketAddr)>> + Unpin
+{
+	type Item = VMCResult<(Frame, SocketAddr)>;
+
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		loop {
+			match ready!(Pin::new(&mut self.stream).poll_next(cx)) {
+				Some(Ok((VMCMessage::Time(time), addr))) => {
+					let messages = self.pending.remove(&addr).unwrap_or_default();
+					return Poll::Ready(Some(Ok((Frame { time, messages }, addr))));
+				}
+				Some(Ok((message, addr))) => self.pending.entry(addr).or_default().push(message),
+				Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+				None => return Poll::Ready(None)
+			}
+		}
+	}
+}
+
+/// How a [`Resampler`] produces poses between incoming frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResampleMode {
+	/// Emit the latest complete frame as-is.
+	#[default]
+	Hold,
+	/// Interpolate between the two latest complete frames with [`VMCPose::lerp`].
+	///
+	/// This adds latency of roughly one incoming frame interval in exchange for smooth motion.
+	Interpolate
+}
+
+/// A stream of [`VMCPose`]s emitted at a fixed rate, created by [`Messages::resample`].
+///
+/// Incoming messages are applied to a [`VMCAvatarState`], and a snapshot is taken each time a frame is completed by a
+/// [`Time`](crate::VMCTime) message. Poses are then emitted at a fixed rate regardless of the timing of incoming
+/// frames, either holding the latest frame or interpolating between the latest two frames. This is useful for renderers
+/// & recorders which require uniform timing.
+///
+/// No poses are emitted until the first frame is complete. If ticks are missed because the stream isn't polled in time,
+/// they are skipped rather than emitted in a burst.
+///
+/// # Examples
+///
+/// ```no_run
+/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+/// use futures_util::StreamExt;
+/// use vmc::stream::ResampleMode;
+///
+/// let mut socket = vmc::marionette!().await?;
+/// // upsample a 30 Hz tracker to a 144 Hz renderer
+/// let mut poses = socket.messages().resample(144.0, ResampleMode::Interpolate);
+/// while let Some(pose) = poses.next().await {
+/// 	let pose = pose?;
+/// 	println!("{} bones", pose.bones.len());
+/// }
+/// # Ok(()) }) }
+/// ```
+#[derive(Debug)]
+pub struct Resampler<S> {
+	stream: S,
+	period: Duration,
+	mode: ResampleMode,
+	interval: Option<Interval>,
+	state: VMCAvatarState,
+	previous: Option<(VMCPose, Instant)>,
+	current: Option<(VMCPose, Instant)>,
+	done: bool
+}
+
+impl<S> Resampler<S> {
+	/// Wraps a stream of [`VMCMessage`]s, emitting poses at `rate` Hz.
+	///
+	/// # Panics
+	///
+	/// Panics if `rate` is not positive & finite.
+	pub fn new(stream: S, rate: f64, mode: ResampleMode) -> Self {
+		assert!(rate > 0.0 && rate.is_finite(), "resample rate must be positive");
+		Self {
+			stream,
+			period: Duration::from_secs_f64(1.0 / rate),
+			mode,
+			interval: None,
+			state: VMCAvatarState::new(),
+			previous: None,
+			current: None,
+			done: false
+		}
+	}
+
+	/// Get a reference to the inner stream.
+	pub fn get_ref(&self) -> &S {
+		&self.stream
+	}
+
+	/// Get a mutable reference to the inner stream.
+	pub fn get_mut(&mut self) -> &mut S {
+		&mut self.stream
+	}
+
+	/// Consumes this adapter, returning the inner stream.
+	pub fn into_inner(self) -> S {
+		self.stream
+	}
+
+	fn sample(&self, now: Instant) -> Option<VMCPose> {
+		let (current, t1) = self.current.as_ref()?;
+		match (&self.previous, self.mode) {
+			(Some((previous, t0)), ResampleMode::Interpolate) => {
+				let span = t1.duration_since(*t0).as_secs_f32();
+				let t = if span > 0.0 { now.saturating_duration_since(*t1).as_secs_f32() / span } else { 1.0 };
+				Some(previous.lerp(current, t.clamp(0.0, 1.0)))
+			}
+			_ => Some(current.clone())
+		}
+	}
+}
+
+impl<S, A> Stream for Resampler<S>
+where
+	S: Stream<Item = VMCResult<(VMCMessage, A)>> + Unpin,
+	A: Unpin
+{
+	type Item = VMCResult<VMCPose>;
+
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		while !self.done {
+			match Pin::new(&mut self.stream).poll_next(cx) {
+				Poll::Ready(Some(Ok((message, _)))) => {
+					let is_frame_end = matches!(message, VMCMessage::Time(_));
+					self.state.apply(message);
+					if is_frame_end {
+						let snapshot = (self.state.to_pose(), Instant::now());
+						self.previous = self.current.replace(snapshot);
+					}
+				}
+				Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+				Poll::Ready(None) => self.done = true,
+				Poll::Pending => break
+			}
+		}
+		if self.done {
+			return Poll::Ready(None);
+		}
+
+		let period = self.period;
+		loop {
+			let interval = self.interval.get_or_insert_with(|| {
+				let mut interval = tokio::time::interval(period);
+				interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+				interval
+			});
+			let now = ready!(interval.poll_tick(cx)).into_std();
+			if let Some(pose) = self.sample(now) {
+				return Poll::Ready(Some(Ok(pose)));
+			}
+		}
+	}
+}
+
+/// A stream of packets paired with the address of the peer that sent them and the time at which they were received,
+/// created by [`VMCSocket::timestamped`](crate::VMCSocket::timestamped).
+#[derive(Debug)]
+pub struct Timestamped<'a> {
+	receiver: &'a mut VMCReceiver
+}
+
+impl<'a> Timestamped<'a> {
+	pub(crate) fn new(receiver: &'a mut VMCReceiver) -> Self {
+		Self { receiver }
+	}
+}
+
+impl Stream for Timestamped<'_> {
+	type Item = VMCResult<(OSCPacket, SocketAddr, VMCRecvTimestamp)>;
+
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		self.receiver.poll_recv_timestamped(cx)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use futures_util::{StreamExt, stream};
+
+	use super::*;
+	use crate::{
+		IntoOSCPacket, VMCApplyBlendShapes, VMCBlendShape, VMCTime,
+		osc::{OSCBundle, OSCTime}
+	};
+
+	#[tokio::test]
+	async fn test_messages_flatten_bundles() -> VMCResult<()> {
+		let addr: SocketAddr = "127.0.0.1:39539".parse().unwrap();
+		let bundle = OSCBundle {
+			timetag: OSCTime::from((0, 1)),
+			content: vec![VMCBlendShape::new("Joy", 1.0).into_osc_packet(), VMCApplyBlendShapes.into_osc_packet()]
+		};
+		let packets = stream::iter([Ok((bundle.into_osc_packet(), addr)), Ok((VMCTime::new(1.0).into_osc_packet(), addr))]);
+
+		let messages: Vec<_> = Messages::new(packets).collect().await;
+		assert_eq!(messages.len(), 3);
+		assert!(matches!(messages[0], Ok((VMCMessage::BlendShape(_), a)) if a == addr));
+		assert!(matches!(messages[1], Ok((VMCMessage::ApplyBlendShapes, _))));
+		assert!(matches!(messages[2], Ok((VMCMessage::Time(_), _))));
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_frames_per_peer() -> VMCResult<()> {
+		let a: SocketAddr = "127.0.0.1:39539".parse().unwrap();
+		let b: SocketAddr = "127.0.0.1:39540".parse().unwrap();
+		let messages = stream::iter([
+			Ok((VMCBlendShape::new("Joy", 1.0).into(), a)),
+			Ok((VMCBlendShape::new("Angry", 1.0).into(), b)),
+			Ok((VMCApplyBlendShapes.into(), a)),
+			Ok((VMCTime::new(1.0).into(), a)),
+			Ok((VMCTime::new(2.0).into(), b))
+		]);
+
+		let frames: Vec<_> = Frames::new(messages).collect().await;
+		assert_eq!(frames.len(), 2);
+		let (frame, addr) = frames[0].as_ref().unwrap();
+		assert_eq!((*addr, frame.time.0, frame.messages.len()), (a, 1.0, 2));
+		let (frame, addr) = frames[1].as_ref().unwrap();
+		assert_eq!((*addr, frame.time.0, frame.messages.len()), (b, 2.0, 1));
+		Ok(())
+	}
+}
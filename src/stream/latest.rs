@@ -0,0 +1,176 @@
+use std::{
+	collections::{BTreeMap, HashMap},
+	net::SocketAddr,
+	pin::Pin,
+	task::{Context, Poll}
+};
+
+use futures_core::Stream;
+
+use crate::{VMCDeviceType, VMCMessage, VMCResult, name::Name};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+	Root,
+	Bone(Name),
+	Device(VMCDeviceType, String, bool),
+	BlendShape(Name),
+	ApplyBlendShapes,
+	State,
+	Time
+}
+
+impl Key {
+	fn of(message: &VMCMessage) -> Self {
+		match message {
+			VMCMessage::RootTransform(_) => Key::Root,
+			VMCMessage::BoneTransform(transform) => Key::Bone(transform.bone.clone()),
+			VMCMessage::DeviceTransform(transform) => Key::Device(transform.device, transform.joint.clone(), transform.local),
+			VMCMessage::BlendShape(blendshape) => Key::BlendShape(blendshape.key.clone()),
+			VMCMessage::ApplyBlendShapes => Key::ApplyBlendShapes,
+			VMCMessage::State(_) => Key::State,
+			VMCMessage::Time(_) => Key::Time
+		}
+	}
+}
+
+/// A stream adapter which only keeps the latest value of each bone, device, blend shape, etc. when the consumer falls
+/// behind, created by [`Messages::latest`](super::Messages::latest).
+///
+/// Each time it is polled, `Latest` reads all messages which are immediately available from the inner stream into a
+/// cache holding one message per key, replacing older messages with the same key. Messages are then yielded from the
+/// cache in the order their keys were last updated. A consumer which keeps up sees every message, while a slow consumer
+/// skips straight to the newest data instead of working through an ever-growing backlog of stale transforms; the
+/// amount of buffered data is bounded by the number of distinct keys.
+///
+/// Errors from the inner stream are yielded immediately; cached messages are kept.
+#[derive(Debug)]
+pub struct Latest<S> {
+	stream: S,
+	cache: HashMap<Key, (u64, VMCMessage, SocketAddr)>,
+	order: BTreeMap<u64, Key>,
+	seq: u64,
+	superseded: u64,
+	done: bool
+}
+
+impl<S> Latest<S> {
+	/// Wraps a stream of messages.
+	pub fn new(stream: S) -> Self {
+		Self {
+			stream,
+			cache: HashMap::new(),
+			order: BTreeMap::new(),
+			seq: 0,
+			superseded: 0,
+			done: false
+		}
+	}
+
+	/// Get a reference to the inner stream.
+	pub fn get_ref(&self) -> &S {
+		&self.stream
+	}
+
+	/// Get a mutable reference to the inner stream.
+	pub fn get_mut(&mut self) -> &mut S {
+		&mut self.stream
+	}
+
+	/// Consumes this adapter, returning the inner stream. Any cached messages are discarded.
+	pub fn into_inner(self) -> S {
+		self.stream
+	}
+
+	/// Returns the number of messages discarded so far because a newer message with the same key arrived before they
+	/// were yielded.
+	pub fn superseded(&self) -> u64 {
+		self.superseded
+	}
+
+	/// Returns the number of cached messages waiting to be yielded.
+	pub fn len(&self) -> usize {
+		self.cache.len()
+	}
+
+	/// Returns `true` if no messages are cached.
+	pub fn is_empty(&self) -> bool {
+		self.cache.is_empty()
+	}
+
+	fn insert(&mut self, message: VMCMessage, addr: SocketAddr) {
+		let seq = self.seq;
+		self.seq += 1;
+		let key = Key::of(&message);
+		if let Some((old_seq, ..)) = self.cache.get(&key) {
+			self.order.remove(old_seq);
+			self.superseded += 1;
+		}
+		self.order.insert(seq, key.clone());
+		self.cache.insert(key, (seq, message, addr));
+	}
+
+	fn pop(&mut self) -> Option<(VMCMessage, SocketAddr)> {
+		let (_, key) = self.order.pop_first()?;
+		self.cache.remove(&key).map(|(_, message, addr)| (message, addr))
+	}
+}
+
+impl<S> Stream for Latest<S>
+where
+	S: Stream<Item = VMCResult<(VMCMessage, SocketAddr)>> + Unpin
+{
+	type Item = VMCResult<(VMCMessage, SocketAddr)>;
+
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		while !self.done {
+			match Pin::new(&mut self.stream).poll_next(cx) {
+				Poll::Ready(Some(Ok((message, addr)))) => self.insert(message, addr),
+				Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+				Poll::Ready(None) => self.done = true,
+				Poll::Pending => break
+			}
+		}
+		match self.pop() {
+			Some(item) => Poll::Ready(Some(Ok(item))),
+			None if self.done => Poll::Ready(None),
+			None => Poll::Pending
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use futures_util::{StreamExt, stream};
+
+	use super::*;
+	use crate::{Quat, VMCBlendShape, VMCBoneTransform, VMCStandardVRM0Bone, VMCTime, Vec3A};
+
+	#[tokio::test]
+	async fn test_latest() -> VMCResult<()> {
+		let addr: SocketAddr = "127.0.0.1:39539".parse().unwrap();
+		let bone = |bone: VMCStandardVRM0Bone, x: f32| VMCMessage::from(VMCBoneTransform::new(bone, Vec3A::X * x, Quat::IDENTITY));
+		let messages = vec![
+			Ok((bone(VMCStandardVRM0Bone::Hips, 0.0), addr)),
+			Ok((bone(VMCStandardVRM0Bone::Head, 0.0), addr)),
+			Ok((VMCBlendShape::new("Joy", 0.0).into(), addr)),
+			Ok((VMCTime::new(0.0).into(), addr)),
+			Ok((bone(VMCStandardVRM0Bone::Head, 1.0), addr)),
+			Ok((VMCTime::new(1.0).into(), addr)),
+		];
+
+		// all messages are available at once, as if the consumer fell behind by a frame
+		let mut latest = Latest::new(stream::iter(messages));
+		let mut out = Vec::new();
+		while let Some(message) = latest.next().await {
+			out.push(message?.0);
+		}
+		assert_eq!(latest.superseded(), 2);
+		assert!(matches!(&out[0], VMCMessage::BoneTransform(transform) if transform.bone == "Hips"));
+		assert!(matches!(&out[1], VMCMessage::BlendShape(_)));
+		assert!(matches!(&out[2], VMCMessage::BoneTransform(transform) if transform.bone == "Head" && transform.position.x == 1.0));
+		assert!(matches!(&out[3], VMCMessage::Time(time) if time.0 == 1.0));
+		assert_eq!(out.len(), 4);
+		Ok(())
+	}
+}
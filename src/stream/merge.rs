@@ -0,0 +1,289 @@
+use std::{
+	collections::HashMap,
+	net::SocketAddr,
+	pin::Pin,
+	task::{Context, Poll},
+	time::{Duration, Instant}
+};
+
+use futures_core::Stream;
+
+use crate::{VMCMessage, VMCResult, name::Name};
+
+/// How a [`Merge`] resolves messages from multiple sources which update the same bone, device, or blend shape.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+	/// Forward all updates; the most recent update wins.
+	LastWriterWins,
+	/// Forward updates from the highest-priority source which has updated the channel within `timeout`, dropping
+	/// updates from lower-priority sources. If the owning source stops sending updates for longer than `timeout`, other
+	/// sources take over.
+	Priority {
+		/// How long a source keeps ownership of a channel without updating it.
+		timeout: Duration
+	}
+}
+
+impl Default for ConflictPolicy {
+	fn default() -> Self {
+		ConflictPolicy::Priority { timeout: Duration::from_millis(500) }
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Channel {
+	Root,
+	Bone(Name),
+	Device(String),
+	BlendShape(Name)
+}
+
+#[derive(Debug)]
+struct Source<S> {
+	stream: S,
+	priority: i32,
+	done: bool
+}
+
+/// Merges messages from multiple sources, such as body tracking from one application & face tracking from another, into
+/// a single stream.
+///
+/// Transforms & blend shapes are forwarded according to the [`ConflictPolicy`], unless a channel is explicitly
+/// [assigned](Merge::assign_bone) to a source, in which case only that source's updates are forwarded. To keep the
+/// merged stream coherent, [`Time`](crate::VMCTime) & [`State`](crate::VMCState) messages are only forwarded from the
+/// [clock source](Merge::with_clock), and [`ApplyBlendShapes`](crate::VMCApplyBlendShapes) is only forwarded when a
+/// blend shape has been forwarded since the last one.
+///
+/// Messages keep the address of the peer that sent them. Sources are polled in turn so that no source can starve the
+/// others. The merged stream ends when all sources have ended.
+///
+/// # Examples
+///
+/// ```no_run
+/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+/// use futures_util::StreamExt;
+/// use vmc::{
+/// 	VMCAvatarState, VMCSocket,
+/// 	stream::{ConflictPolicy, Merge}
+/// };
+///
+/// let mut body = VMCSocket::bind("0.0.0.0:39539").await?;
+/// let mut face = VMCSocket::bind("0.0.0.0:39540").await?;
+/// let mut merged = Merge::new(ConflictPolicy::default())
+/// 	.with_source(body.messages(), 0)
+/// 	.with_source(face.messages(), 0)
+/// 	// facial expressions always come from the face tracker
+/// 	.assign_blendshapes(1);
+///
+/// let mut avatar = VMCAvatarState::new();
+/// while let Some(message) = merged.next().await {
+/// 	let (message, _) = message?;
+/// 	avatar.apply(message);
+/// }
+/// # Ok(()) }) }
+/// ```
+#[derive(Debug)]
+pub struct Merge<S> {
+	sources: Vec<Source<S>>,
+	policy: ConflictPolicy,
+	clock: usize,
+	assigned: HashMap<Channel, usize>,
+	blendshapes_source: Option<usize>,
+	owners: HashMap<Channel, (usize, Instant)>,
+	blendshapes_pending: bool,
+	next: usize
+}
+
+impl<S> Merge<S> {
+	/// Creates a merger without any sources.
+	pub fn new(policy: ConflictPolicy) -> Self {
+		Self {
+			sources: Vec::new(),
+			policy,
+			clock: 0,
+			assigned: HashMap::new(),
+			blendshapes_source: None,
+			owners: HashMap::new(),
+			blendshapes_pending: false,
+			next: 0
+		}
+	}
+
+	/// Adds a source with the given priority; higher values take precedence. Sources are numbered in the order they are
+	/// added, starting at 0.
+	pub fn with_source(mut self, stream: S, priority: i32) -> Self {
+		self.sources.push(Source { stream, priority, done: false });
+		self
+	}
+
+	/// Sets the source whose [`Time`](crate::VMCTime) & [`State`](crate::VMCState) messages are forwarded. Defaults to
+	/// source 0.
+	pub fn with_clock(mut self, source: usize) -> Self {
+		self.clock = source;
+		self
+	}
+
+	/// Only forwards transforms of the given bone from `source`, regardless of the [`ConflictPolicy`].
+	///
+	/// `bone` can be either a [`StandardVRM0Bone`](crate::VMCStandardVRM0Bone) or the name of a bone.
+	pub fn assign_bone(mut self, bone: impl Into<Name>, source: usize) -> Self {
+		self.assigned.insert(Channel::Bone(bone.into()), source);
+		self
+	}
+
+	/// Only forwards root transforms from `source`, regardless of the [`ConflictPolicy`].
+	pub fn assign_root(mut self, source: usize) -> Self {
+		self.assigned.insert(Channel::Root, source);
+		self
+	}
+
+	/// Only forwards blend shapes from `source`, regardless of the [`ConflictPolicy`].
+	pub fn assign_blendshapes(mut self, source: usize) -> Self {
+		self.blendshapes_source = Some(source);
+		self
+	}
+
+	/// Returns the number of sources.
+	pub fn len(&self) -> usize {
+		self.sources.len()
+	}
+
+	/// Returns `true` if there are no sources.
+	pub fn is_empty(&self) -> bool {
+		self.sources.is_empty()
+	}
+
+	/// Returns `true` if a message from `source` should be forwarded.
+	fn accept(&mut self, source: usize, message: &VMCMessage) -> bool {
+		let channel = match message {
+			VMCMessage::RootTransform(_) => Channel::Root,
+			VMCMessage::BoneTransform(transform) => Channel::Bone(transform.bone.clone()),
+			VMCMessage::DeviceTransform(transform) => Channel::Device(transform.joint.clone()),
+			VMCMessage::BlendShape(blendshape) => {
+				if self.blendshapes_source.is_some_and(|assigned| assigned != source) {
+					return false;
+				}
+				Channel::BlendShape(blendshape.key.clone())
+			}
+			VMCMessage::ApplyBlendShapes => return std::mem::take(&mut self.blendshapes_pending),
+			VMCMessage::State(_) | VMCMessage::Time(_) => return source == self.clock
+		};
+		if let Some(&assigned) = self.assigned.get(&channel) {
+			return assigned == source;
+		}
+
+		let accepted = match self.policy {
+			ConflictPolicy::LastWriterWins => true,
+			ConflictPolicy::Priority { timeout } => {
+				let now = Instant::now();
+				match self.owners.get(&channel) {
+					Some(&(owner, updated)) if owner != source => {
+						self.sources[source].priority > self.sources[owner].priority || now.duration_since(updated) > timeout
+					}
+					_ => true
+				}
+			}
+		};
+		if accepted {
+			if let ConflictPolicy::Priority { .. } = self.policy {
+				self.owners.insert(channel.clone(), (source, Instant::now()));
+			}
+			if let Channel::BlendShape(_) = channel {
+				self.blendshapes_pending = true;
+			}
+		}
+		accepted
+	}
+}
+
+impl<S> Stream for Merge<S>
+where
+	S: Stream<Item = VMCResult<(VMCMessage, SocketAddr)>> + Unpin
+{
+	type Item = VMCResult<(VMCMessage, SocketAddr)>;
+
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		let len = self.sources.len();
+		loop {
+			let mut progressed = false;
+			for offset in 0..len {
+				let index = (self.next + offset) % len;
+				if self.sources[index].done {
+					continue;
+				}
+				match Pin::new(&mut self.sources[index].stream).poll_next(cx) {
+					Poll::Ready(Some(Ok((message, addr)))) => {
+						progressed = true;
+						if self.accept(index, &message) {
+							// continue with the next source next time so that a busy source can't starve the others
+							self.next = (index + 1) % len;
+							return Poll::Ready(Some(Ok((message, addr))));
+						}
+					}
+					Poll::Ready(Some(Err(e))) => {
+						self.next = (index + 1) % len;
+						return Poll::Ready(Some(Err(e)));
+					}
+					Poll::Ready(None) => {
+						self.sources[index].done = true;
+						progressed = true;
+					}
+					Poll::Pending => {}
+				}
+			}
+			if self.sources.iter().all(|source| source.done) {
+				return Poll::Ready(None);
+			}
+			if !progressed {
+				return Poll::Pending;
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use futures_util::{StreamExt, stream};
+
+	use super::*;
+	use crate::{Quat, VMCApplyBlendShapes, VMCBlendShape, VMCBoneTransform, VMCStandardVRM0Bone, VMCTime, Vec3A};
+
+	#[tokio::test]
+	async fn test_merge() -> VMCResult<()> {
+		let body: SocketAddr = "127.0.0.1:39539".parse().unwrap();
+		let face: SocketAddr = "127.0.0.1:39540".parse().unwrap();
+		let bone = |bone: VMCStandardVRM0Bone, x: f32| VMCMessage::from(VMCBoneTransform::new(bone, Vec3A::X * x, Quat::IDENTITY));
+		let body_messages = stream::iter(vec![
+			Ok((bone(VMCStandardVRM0Bone::Hips, 0.0), body)),
+			Ok((bone(VMCStandardVRM0Bone::Head, 0.0), body)),
+			Ok((VMCBlendShape::new("Joy", 0.0).into(), body)),
+			Ok((VMCApplyBlendShapes.into(), body)),
+			Ok((VMCTime::new(1.0).into(), body)),
+		]);
+		let face_messages = stream::iter(vec![
+			Ok((bone(VMCStandardVRM0Bone::Head, 1.0), face)),
+			Ok((VMCBlendShape::new("Joy", 1.0).into(), face)),
+			Ok((VMCApplyBlendShapes.into(), face)),
+			Ok((VMCTime::new(100.0).into(), face)),
+		]);
+
+		let merged: Vec<_> = Merge::new(ConflictPolicy::default())
+			.with_source(body_messages, 0)
+			.with_source(face_messages, 1)
+			.assign_blendshapes(1)
+			.collect::<Vec<_>>()
+			.await
+			.into_iter()
+			.map(|message| message.map(|(message, addr)| (message, addr == face)))
+			.collect::<VMCResult<_>>()?;
+
+		// the higher-priority face tracker takes over the head once it has sent it, but not the hips
+		assert!(matches!(&merged[0], (VMCMessage::BoneTransform(transform), false) if transform.bone == "Hips"));
+		assert!(matches!(&merged[1], (VMCMessage::BoneTransform(transform), true) if transform.bone == "Head"));
+		assert!(matches!(&merged[2], (VMCMessage::BlendShape(_), true)));
+		assert!(matches!(&merged[3], (VMCMessage::ApplyBlendShapes, true)));
+		assert!(matches!(&merged[4], (VMCMessage::Time(time), false) if time.0 == 1.0));
+		assert_eq!(merged.len(), 5);
+		Ok(())
+	}
+}
@@ -0,0 +1,225 @@
+use std::{
+	cmp::{Ordering, Reverse},
+	collections::BinaryHeap,
+	future::Future,
+	net::SocketAddr,
+	pin::Pin,
+	task::{Context, Poll},
+	time::{Duration, SystemTime}
+};
+
+use futures_core::Stream;
+use tokio::time::{Instant, Sleep};
+
+use crate::{OSCPacket, VMCResult};
+
+#[derive(Debug)]
+struct Entry {
+	deadline: Instant,
+	seq: u64,
+	packet: OSCPacket,
+	addr: SocketAddr
+}
+
+impl PartialEq for Entry {
+	fn eq(&self, other: &Self) -> bool {
+		self.cmp(other) == Ordering::Equal
+	}
+}
+
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl Ord for Entry {
+	fn cmp(&self, other: &Self) -> Ordering {
+		(self.deadline, self.seq).cmp(&(other.deadline, other.seq))
+	}
+}
+
+/// A stream adapter which delays bundles until the time given by their time tag, created by
+/// [`VMCSocket::scheduled`](crate::VMCSocket::scheduled).
+///
+/// Per OSC semantics, a bundle whose time tag lies in the future should only be applied at that time. This allows a
+/// performer to drive multiple render machines in sync, by sending each frame slightly ahead of time to all of them.
+/// Messages which aren't part of a bundle, and bundles whose time tag is *immediately* (`(0, 1)`) or in the past, are
+/// yielded as soon as they are received. Delayed bundles are yielded in order of their time tags; bundles with the same
+/// time tag are yielded in the order they were received. Nested bundles are yielded along with their enclosing bundle.
+///
+/// Time tags are compared against the local system clock, so the clocks of all machines involved should be
+/// synchronized, e.g. with NTP. To protect against misconfigured clocks, bundles are never delayed for longer than the
+/// [maximum delay](Scheduled::with_max_delay).
+///
+/// # Examples
+///
+/// ```no_run
+/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+/// use futures_util::StreamExt;
+/// use vmc::VMCMessages;
+///
+/// let mut socket = vmc::marionette!().await?;
+/// let mut messages = VMCMessages::new(socket.scheduled());
+/// while let Some(message) = messages.next().await {
+/// 	let (message, _) = message?;
+/// 	// apply the message right away; it has already been held back until its time tag
+/// }
+/// # Ok(()) }) }
+/// ```
+#[derive(Debug)]
+pub struct Scheduled<S> {
+	stream: S,
+	max_delay: Duration,
+	queue: BinaryHeap<Reverse<Entry>>,
+	seq: u64,
+	sleep: Pin<Box<Sleep>>,
+	done: bool
+}
+
+impl<S> Scheduled<S> {
+	/// Wraps a stream of packets. Bundles are delayed by at most 1 second by default.
+	pub fn new(stream: S) -> Self {
+		Self {
+			stream,
+			max_delay: Duration::from_secs(1),
+			queue: BinaryHeap::new(),
+			seq: 0,
+			sleep: Box::pin(tokio::time::sleep(Duration::ZERO)),
+			done: false
+		}
+	}
+
+	/// Sets the maximum time a bundle is held back. Bundles whose time tag is further in the future are yielded after
+	/// this delay.
+	pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+		self.max_delay = max_delay;
+		self
+	}
+
+	/// Returns the number of bundles currently held back.
+	pub fn len(&self) -> usize {
+		self.queue.len()
+	}
+
+	/// Returns `true` if no bundles are currently held back.
+	pub fn is_empty(&self) -> bool {
+		self.queue.is_empty()
+	}
+
+	/// Get a reference to the inner stream.
+	pub fn get_ref(&self) -> &S {
+		&self.stream
+	}
+
+	/// Get a mutable reference to the inner stream.
+	pub fn get_mut(&mut self) -> &mut S {
+		&mut self.stream
+	}
+
+	/// Consumes this adapter, returning the inner stream. Any bundles which are being held back are discarded.
+	pub fn into_inner(self) -> S {
+		self.stream
+	}
+
+	/// Returns the time at which `packet` should be yielded, or `None` if it should be yielded immediately.
+	fn deadline(&self, packet: &OSCPacket) -> Option<Instant> {
+		let OSCPacket::Bundle(bundle) = packet else {
+			return None;
+		};
+		if bundle.timetag == (0, 1).into() {
+			return None;
+		}
+		let delay = SystemTime::from(bundle.timetag).duration_since(SystemTime::now()).ok()?;
+		Some(Instant::now() + delay.min(self.max_delay))
+	}
+
+	fn pop_due(&mut self, now: Instant) -> Option<(OSCPacket, SocketAddr)> {
+		if self.queue.peek()?.0.deadline > now {
+			return None;
+		}
+		self.queue.pop().map(|Reverse(entry)| (entry.packet, entry.addr))
+	}
+}
+
+impl<S> Stream for Scheduled<S>
+where
+	S: Stream<Item = VMCResult<(OSCPacket, SocketAddr)>> + Unpin
+{
+	type Item = VMCResult<(OSCPacket, SocketAddr)>;
+
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		if let Some(item) = self.pop_due(Instant::now()) {
+			return Poll::Ready(Some(Ok(item)));
+		}
+
+		while !self.done {
+			match Pin::new(&mut self.stream).poll_next(cx) {
+				Poll::Ready(Some(Ok((packet, addr)))) => match self.deadline(&packet) {
+					Some(deadline) => {
+						let seq = self.seq;
+						self.seq += 1;
+						self.queue.push(Reverse(Entry { deadline, seq, packet, addr }));
+					}
+					None => return Poll::Ready(Some(Ok((packet, addr))))
+				},
+				Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+				Poll::Ready(None) => self.done = true,
+				Poll::Pending => break
+			}
+		}
+
+		let Some(deadline) = self.queue.peek().map(|entry| entry.0.deadline) else {
+			return if self.done { Poll::Ready(None) } else { Poll::Pending };
+		};
+		if self.sleep.deadline() != deadline {
+			self.sleep.as_mut().reset(deadline);
+		}
+		match self.sleep.as_mut().poll(cx) {
+			Poll::Ready(()) => Poll::Ready(self.queue.pop().map(|Reverse(entry)| Ok((entry.packet, entry.addr)))),
+			Poll::Pending => Poll::Pending
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use futures_util::{StreamExt, stream};
+
+	use super::*;
+	use crate::{
+		IntoOSCPacket, VMCMessage, VMCMessages, VMCTime,
+		osc::{OSCBundle, OSCTime}
+	};
+
+	#[tokio::test]
+	async fn test_scheduled() -> VMCResult<()> {
+		let addr: SocketAddr = "127.0.0.1:39539".parse().unwrap();
+		let bundle = |delay: Duration, time: f32| {
+			let timetag = OSCTime::try_from(SystemTime::now() + delay).unwrap();
+			OSCPacket::Bundle(OSCBundle {
+				timetag,
+				content: vec![VMCTime::new(time).into_osc_packet()]
+			})
+		};
+		let packets = vec![
+			Ok((bundle(Duration::from_millis(100), 2.0), addr)),
+			Ok((bundle(Duration::from_millis(50), 1.0), addr)),
+			Ok((VMCTime::new(0.0).into_osc_packet(), addr)),
+		];
+
+		let start = Instant::now();
+		let times: Vec<_> = VMCMessages::new(Scheduled::new(stream::iter(packets)))
+			.map(|message| message.map(|(message, _)| (message, start.elapsed())))
+			.collect::<Vec<_>>()
+			.await
+			.into_iter()
+			.collect::<VMCResult<_>>()?;
+		assert!(matches!(&times[0], (VMCMessage::Time(time), _) if time.0 == 0.0));
+		assert!(matches!(&times[1], (VMCMessage::Time(time), elapsed) if time.0 == 1.0 && *elapsed >= Duration::from_millis(40)));
+		assert!(matches!(&times[2], (VMCMessage::Time(time), elapsed) if time.0 == 2.0 && *elapsed >= Duration::from_millis(90)));
+		Ok(())
+	}
+}
@@ -0,0 +1,328 @@
+use std::{
+	collections::VecDeque,
+	future::{Future, poll_fn},
+	net::SocketAddr,
+	pin::Pin,
+	sync::{Arc, Mutex, MutexGuard},
+	task::{Context, Poll, Waker, ready}
+};
+
+use futures_core::Stream;
+
+use crate::{VMCMessage, VMCResult};
+
+/// What a [`Split`] does when a sub-stream's buffer is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+	/// Stop reading the source stream until the consumer catches up. This never loses messages, but a slow consumer
+	/// stalls all other sub-streams.
+	Wait,
+	/// Discard the incoming message.
+	DropNewest,
+	/// Discard the oldest buffered message to make room for the incoming one, so that consumers which fall behind
+	/// always skip ahead to the latest data.
+	#[default]
+	DropOldest
+}
+
+type Item = (VMCMessage, SocketAddr);
+
+#[derive(Debug)]
+struct State {
+	queue: VecDeque<Item>,
+	closed: bool,
+	receiver_dropped: bool,
+	dropped: u64,
+	recv_waker: Option<Waker>,
+	send_waker: Option<Waker>
+}
+
+#[derive(Debug)]
+struct Channel {
+	state: Mutex<State>,
+	capacity: usize
+}
+
+impl Channel {
+	fn lock(&self) -> MutexGuard<'_, State> {
+		self.state.lock().unwrap_or_else(|e| e.into_inner())
+	}
+
+	/// Pushes an item according to `policy`, returning it back if the channel is full & the policy is to wait.
+	fn push(&self, item: Item, policy: OverflowPolicy, cx: &mut Context<'_>) -> Option<Item> {
+		let mut state = self.lock();
+		if state.receiver_dropped {
+			return None;
+		}
+		if state.queue.len() >= self.capacity {
+			match policy {
+				OverflowPolicy::Wait => {
+					state.send_waker = Some(cx.waker().clone());
+					return Some(item);
+				}
+				OverflowPolicy::DropNewest => {
+					state.dropped += 1;
+					return None;
+				}
+				OverflowPolicy::DropOldest => {
+					state.queue.pop_front();
+					state.dropped += 1;
+				}
+			}
+		}
+		state.queue.push_back(item);
+		if let Some(waker) = state.recv_waker.take() {
+			waker.wake();
+		}
+		None
+	}
+
+	fn close(&self) {
+		let mut state = self.lock();
+		state.closed = true;
+		if let Some(waker) = state.recv_waker.take() {
+			waker.wake();
+		}
+	}
+}
+
+/// One of the sub-streams of a [`Split`].
+///
+/// Messages are paired with the address of the peer that sent them. The stream ends once the [`Split`] finishes or is
+/// dropped and all buffered messages have been received.
+#[derive(Debug)]
+pub struct SplitReceiver {
+	channel: Arc<Channel>
+}
+
+impl SplitReceiver {
+	/// Receives the next message, or `None` if the stream has ended.
+	pub async fn recv(&mut self) -> Option<(VMCMessage, SocketAddr)> {
+		poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+	}
+
+	/// Returns the number of messages discarded so far because this sub-stream's buffer was full.
+	pub fn dropped(&self) -> u64 {
+		self.channel.lock().dropped
+	}
+
+	/// Returns the number of messages currently buffered.
+	pub fn len(&self) -> usize {
+		self.channel.lock().queue.len()
+	}
+
+	/// Returns `true` if no messages are currently buffered.
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+}
+
+impl Stream for SplitReceiver {
+	type Item = (VMCMessage, SocketAddr);
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		let mut state = self.channel.lock();
+		if let Some(item) = state.queue.pop_front() {
+			if let Some(waker) = state.send_waker.take() {
+				waker.wake();
+			}
+			return Poll::Ready(Some(item));
+		}
+		if state.closed {
+			return Poll::Ready(None);
+		}
+		state.recv_waker = Some(cx.waker().clone());
+		Poll::Pending
+	}
+}
+
+impl Drop for SplitReceiver {
+	fn drop(&mut self) {
+		let mut state = self.channel.lock();
+		state.receiver_dropped = true;
+		state.queue.clear();
+		if let Some(waker) = state.send_waker.take() {
+			waker.wake();
+		}
+	}
+}
+
+/// The sub-streams of a [`Split`], each of which can be consumed from a different task.
+#[derive(Debug)]
+pub struct SplitStreams {
+	/// Root & bone transforms.
+	pub bones: SplitReceiver,
+	/// Blend shapes & [`ApplyBlendShapes`](crate::VMCApplyBlendShapes).
+	pub blendshapes: SplitReceiver,
+	/// Device transforms.
+	pub devices: SplitReceiver,
+	/// [`State`](crate::VMCState) messages.
+	pub status: SplitReceiver
+}
+
+/// Splits a stream of messages into independent sub-streams by kind, created by
+/// [`Messages::split`](super::Messages::split).
+///
+/// A `Split` is a future which reads the source stream and distributes its messages to the [`SplitStreams`]; it must
+/// be polled (usually by spawning it) for the sub-streams to receive anything. It completes when the source stream
+/// ends, or with the first error it yields; the sub-streams end once their remaining messages have been received.
+///
+/// [`Time`](crate::VMCTime) messages are delivered to every sub-stream, so that each consumer can tell where frames
+/// end. Sub-streams which have been dropped are skipped.
+///
+/// # Examples
+///
+/// ```no_run
+/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+/// use vmc::stream::OverflowPolicy;
+///
+/// let socket = vmc::marionette!().await?;
+/// let (receiver, _) = socket.into_split();
+/// let (split, mut streams) = vmc::VMCMessages::new(receiver).split(256, OverflowPolicy::DropOldest);
+/// tokio::spawn(split);
+///
+/// tokio::spawn(async move {
+/// 	while let Some((message, _)) = streams.blendshapes.recv().await {
+/// 		// drive face animation
+/// 	}
+/// });
+/// while let Some((message, _)) = streams.bones.recv().await {
+/// 	// render
+/// }
+/// # Ok(()) }) }
+/// ```
+#[derive(Debug)]
+pub struct Split<S> {
+	stream: S,
+	policy: OverflowPolicy,
+	channels: [Arc<Channel>; 4],
+	/// Messages waiting to be delivered, with the index of the channel they're destined for.
+	pending: VecDeque<(usize, Item)>,
+	done: bool
+}
+
+impl<S> Split<S> {
+	/// Wraps a stream of messages. Each sub-stream buffers up to `capacity` messages, after which `policy` applies.
+	///
+	/// # Panics
+	///
+	/// Panics if `capacity` is 0.
+	pub fn new(stream: S, capacity: usize, policy: OverflowPolicy) -> (Self, SplitStreams) {
+		assert!(capacity > 0, "split capacity must be positive");
+		let channels = [(); 4].map(|_| {
+			Arc::new(Channel {
+				state: Mutex::new(State {
+					queue: VecDeque::new(),
+					closed: false,
+					receiver_dropped: false,
+					dropped: 0,
+					recv_waker: None,
+					send_waker: None
+				}),
+				capacity
+			})
+		});
+		let [bones, blendshapes, devices, status] = channels.clone().map(|channel| SplitReceiver { channel });
+		let split = Self {
+			stream,
+			policy,
+			channels,
+			pending: VecDeque::new(),
+			done: false
+		};
+		(split, SplitStreams { bones, blendshapes, devices, status })
+	}
+
+	fn close(&mut self) {
+		self.done = true;
+		for channel in &self.channels {
+			channel.close();
+		}
+	}
+}
+
+impl<S> Drop for Split<S> {
+	fn drop(&mut self) {
+		self.close();
+	}
+}
+
+impl<S> Future for Split<S>
+where
+	S: Stream<Item = VMCResult<(VMCMessage, SocketAddr)>> + Unpin
+{
+	type Output = VMCResult<()>;
+
+	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+		if self.done {
+			return Poll::Ready(Ok(()));
+		}
+		loop {
+			while let Some((index, item)) = self.pending.pop_front() {
+				if let Some(item) = self.channels[index].push(item, self.policy, cx) {
+					self.pending.push_front((index, item));
+					return Poll::Pending;
+				}
+			}
+
+			match ready!(Pin::new(&mut self.stream).poll_next(cx)) {
+				Some(Ok((message, addr))) => {
+					let index = match &message {
+						VMCMessage::RootTransform(_) | VMCMessage::BoneTransform(_) => 0,
+						VMCMessage::BlendShape(_) | VMCMessage::ApplyBlendShapes => 1,
+						VMCMessage::DeviceTransform(_) => 2,
+						VMCMessage::State(_) => 3,
+						VMCMessage::Time(_) => {
+							self.pending.extend((0..4).map(|index| (index, (message.clone(), addr))));
+							continue;
+						}
+					};
+					self.pending.push_back((index, (message, addr)));
+				}
+				Some(Err(e)) => {
+					self.close();
+					return Poll::Ready(Err(e));
+				}
+				None => {
+					self.close();
+					return Poll::Ready(Ok(()));
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use futures_util::stream;
+
+	use super::*;
+	use crate::{Quat, VMCBlendShape, VMCBoneTransform, VMCStandardVRM0Bone, VMCTime, Vec3A};
+
+	#[tokio::test]
+	async fn test_split() -> VMCResult<()> {
+		let addr: SocketAddr = "127.0.0.1:39539".parse().unwrap();
+		let mut messages = Vec::new();
+		for i in 0..4 {
+			messages.push(Ok((VMCBoneTransform::new(VMCStandardVRM0Bone::Head, Vec3A::ZERO, Quat::IDENTITY).into(), addr)));
+			messages.push(Ok((VMCBlendShape::new("Joy", i as f32).into(), addr)));
+			messages.push(Ok((VMCTime::new(i as f32).into(), addr)));
+		}
+
+		let (split, mut streams) = Split::new(stream::iter(messages), 4, OverflowPolicy::DropOldest);
+		drop(streams.devices);
+		split.await?;
+
+		// 4 bones & 4 times didn't fit into the bone stream's buffer, so only the latest 2 frames remain
+		assert_eq!(streams.bones.dropped(), 4);
+		let mut bones = Vec::new();
+		while let Some((message, _)) = streams.bones.recv().await {
+			bones.push(message);
+		}
+		assert_eq!(bones.len(), 4);
+		assert!(matches!(&bones[3], VMCMessage::Time(time) if time.0 == 3.0));
+		assert!(matches!(streams.blendshapes.recv().await, Some((VMCMessage::BlendShape(blendshape), _)) if blendshape.value == 2.0));
+		assert!(matches!(streams.status.recv().await, Some((VMCMessage::Time(_), _))));
+		Ok(())
+	}
+}
@@ -0,0 +1,193 @@
+use std::{
+	future::Future,
+	net::SocketAddr,
+	pin::Pin,
+	task::{Context, Poll},
+	time::Duration
+};
+
+use futures_core::Stream;
+use tokio::time::{Instant, Sleep};
+
+use crate::{VMCMessage, VMCResult};
+
+/// Which messages a [`Watchdog`] considers a sign of life from the performer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LivenessSignal {
+	/// Any message.
+	#[default]
+	AnyMessage,
+	/// Only [`State`](crate::VMCState) (`/VMC/Ext/OK`) messages. Useful to detect performers which keep sending stale
+	/// transforms after their tracking has stopped, as long as they stop reporting their state too.
+	State
+}
+
+/// An item of a [`Watchdog`] stream.
+#[derive(Debug, Clone)]
+pub enum LivenessEvent {
+	/// A message received from the performer.
+	Message(VMCMessage, SocketAddr),
+	/// The performer was lost: no liveness signal arrived within the timeout.
+	Lost,
+	/// A liveness signal arrived from the given address after the performer was lost, or for the first time.
+	Resumed(SocketAddr)
+}
+
+/// A stream adapter which reports when the performer stops & resumes sending, created by
+/// [`Messages::watchdog`](super::Messages::watchdog).
+///
+/// Messages from the inner stream are passed through as [`LivenessEvent::Message`]. When no [liveness
+/// signal](LivenessSignal) has arrived within the timeout, [`LivenessEvent::Lost`] is emitted; when a signal arrives
+/// again, [`LivenessEvent::Resumed`] is emitted before the message which carried it. The performer is considered lost
+/// until the first signal arrives, so the first signal also emits `Resumed`.
+///
+/// # Examples
+///
+/// ```no_run
+/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+/// use std::time::Duration;
+///
+/// use futures_util::StreamExt;
+/// use vmc::stream::LivenessEvent;
+///
+/// let mut socket = vmc::marionette!().await?;
+/// let mut events = socket.messages().watchdog(Duration::from_secs(2));
+/// while let Some(event) = events.next().await {
+/// 	match event? {
+/// 		LivenessEvent::Message(message, _) => {}
+/// 		LivenessEvent::Lost => println!("performer disconnected"),
+/// 		LivenessEvent::Resumed(addr) => println!("performer connected from {addr}")
+/// 	}
+/// }
+/// # Ok(()) }) }
+/// ```
+#[derive(Debug)]
+pub struct Watchdog<S> {
+	stream: S,
+	timeout: Duration,
+	signal: LivenessSignal,
+	deadline: Pin<Box<Sleep>>,
+	alive: bool,
+	pending: Option<(VMCMessage, SocketAddr)>
+}
+
+impl<S> Watchdog<S> {
+	/// Wraps a stream of messages, considering the performer lost if no message arrives within `timeout`.
+	pub fn new(stream: S, timeout: Duration) -> Self {
+		Self {
+			stream,
+			timeout,
+			signal: LivenessSignal::default(),
+			deadline: Box::pin(tokio::time::sleep(timeout)),
+			alive: false,
+			pending: None
+		}
+	}
+
+	/// Sets which messages count as a sign of life. Defaults to [`LivenessSignal::AnyMessage`].
+	pub fn with_signal(mut self, signal: LivenessSignal) -> Self {
+		self.signal = signal;
+		self
+	}
+
+	/// Returns `true` if a liveness signal has arrived within the timeout.
+	pub fn is_alive(&self) -> bool {
+		self.alive
+	}
+
+	/// Get a reference to the inner stream.
+	pub fn get_ref(&self) -> &S {
+		&self.stream
+	}
+
+	/// Get a mutable reference to the inner stream.
+	pub fn get_mut(&mut self) -> &mut S {
+		&mut self.stream
+	}
+
+	/// Consumes this adapter, returning the inner stream.
+	pub fn into_inner(self) -> S {
+		self.stream
+	}
+}
+
+impl<S> Stream for Watchdog<S>
+where
+	S: Stream<Item = VMCResult<(VMCMessage, SocketAddr)>> + Unpin
+{
+	type Item = VMCResult<LivenessEvent>;
+
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		if let Some((message, addr)) = self.pending.take() {
+			return Poll::Ready(Some(Ok(LivenessEvent::Message(message, addr))));
+		}
+
+		// check the deadline first, so that a performer which floods non-signal messages is still detected as lost
+		if self.alive && self.deadline.as_mut().poll(cx).is_ready() {
+			self.alive = false;
+			return Poll::Ready(Some(Ok(LivenessEvent::Lost)));
+		}
+
+		match Pin::new(&mut self.stream).poll_next(cx) {
+			Poll::Ready(Some(Ok((message, addr)))) => {
+				let is_signal = match self.signal {
+					LivenessSignal::AnyMessage => true,
+					LivenessSignal::State => matches!(message, VMCMessage::State(_))
+				};
+				if !is_signal {
+					return Poll::Ready(Some(Ok(LivenessEvent::Message(message, addr))));
+				}
+
+				let deadline = Instant::now() + self.timeout;
+				self.deadline.as_mut().reset(deadline);
+				if self.alive {
+					Poll::Ready(Some(Ok(LivenessEvent::Message(message, addr))))
+				} else {
+					self.alive = true;
+					self.pending = Some((message, addr));
+					Poll::Ready(Some(Ok(LivenessEvent::Resumed(addr))))
+				}
+			}
+			Poll::Ready(Some(Err(e))) => Poll::Ready(Some(Err(e))),
+			Poll::Ready(None) => Poll::Ready(None),
+			Poll::Pending => Poll::Pending
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use futures_util::{StreamExt, stream};
+	use tokio::sync::mpsc;
+
+	use super::*;
+	use crate::{VMCModelState, VMCState, VMCTime};
+
+	#[tokio::test]
+	async fn test_watchdog() -> VMCResult<()> {
+		let addr: SocketAddr = "127.0.0.1:39539".parse().unwrap();
+		let (tx, rx) = mpsc::unbounded_channel::<VMCMessage>();
+		let messages = stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|message| (Ok((message, addr)), rx)) });
+		let mut watchdog = Watchdog::new(Box::pin(messages), Duration::from_millis(50)).with_signal(LivenessSignal::State);
+
+		tx.send(VMCTime::new(0.0).into()).unwrap();
+		tx.send(VMCState::new(VMCModelState::Loaded).into()).unwrap();
+		assert!(matches!(watchdog.next().await, Some(Ok(LivenessEvent::Message(VMCMessage::Time(_), _)))));
+		assert!(matches!(watchdog.next().await, Some(Ok(LivenessEvent::Resumed(_)))));
+		assert!(matches!(watchdog.next().await, Some(Ok(LivenessEvent::Message(VMCMessage::State(_), _)))));
+		assert!(watchdog.is_alive());
+
+		// times don't count as a signal, so the performer is lost once the timeout elapses
+		tx.send(VMCTime::new(1.0).into()).unwrap();
+		assert!(matches!(watchdog.next().await, Some(Ok(LivenessEvent::Message(VMCMessage::Time(_), _)))));
+		assert!(matches!(watchdog.next().await, Some(Ok(LivenessEvent::Lost))));
+		assert!(!watchdog.is_alive());
+
+		tx.send(VMCState::new(VMCModelState::Loaded).into()).unwrap();
+		assert!(matches!(watchdog.next().await, Some(Ok(LivenessEvent::Resumed(_)))));
+		drop(tx);
+		assert!(matches!(watchdog.next().await, Some(Ok(LivenessEvent::Message(VMCMessage::State(_), _)))));
+		assert!(watchdog.next().await.is_none());
+		Ok(())
+	}
+}
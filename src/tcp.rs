@@ -0,0 +1,341 @@
+use std::{
+	fmt,
+	future::poll_fn,
+	io,
+	net::SocketAddr,
+	pin::Pin,
+	task::{Context, Poll, ready}
+};
+
+use futures_core::Stream;
+use tokio::{
+	io::AsyncWriteExt,
+	net::{
+		TcpListener, TcpStream, ToSocketAddrs,
+		tcp::{OwnedReadHalf, OwnedWriteHalf}
+	},
+	sync::Mutex
+};
+
+use crate::{
+	IntoOSCPacket, OSCPacket, VMCFrames, VMCMessage, VMCMessages, VMCPose, VMCResult,
+	framed::{FramedRead, encode_length_prefixed},
+	osc::{EncodeOptions, StreamDecoder},
+	parse
+};
+
+/// A TCP connection to send and receive VMC messages.
+///
+/// Packets are framed as specified by the OSC 1.0 specification for stream-based protocols: each packet is preceded by
+/// its size as a big-endian 32-bit integer.
+///
+/// Like [`VMCSocket`](crate::VMCSocket), this type implements [`Stream`] to receive packets, and packets can be sent
+/// with [`VMCTcpSocket::send`].
+pub struct VMCTcpSocket {
+	reader: FramedRead<OwnedReadHalf, StreamDecoder>,
+	writer: Mutex<OwnedWriteHalf>,
+	peer_addr: SocketAddr,
+	encode_options: EncodeOptions
+}
+
+impl fmt::Debug for VMCTcpSocket {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("VMCTcpSocket").field("peer_addr", &self.peer_addr).finish_non_exhaustive()
+	}
+}
+
+impl VMCTcpSocket {
+	/// Creates a new VMC socket from a connected [`tokio::net::TcpStream`].
+	pub fn new(stream: TcpStream) -> VMCResult<Self> {
+		let peer_addr = stream.peer_addr()?;
+		let (reader, writer) = stream.into_split();
+		Ok(Self {
+			reader: FramedRead::new(reader, StreamDecoder::new()),
+			writer: Mutex::new(writer),
+			peer_addr,
+			encode_options: EncodeOptions::default()
+		})
+	}
+
+	/// Opens a TCP connection to a remote VMC application.
+	///
+	/// # Examples
+	///
+	/// ```no_run
+	/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+	/// use vmc::{VMCBlendShape, VMCStandardVRMBlendShape, VMCTcpSocket};
+	///
+	/// let socket = VMCTcpSocket::connect("127.0.0.1:39539").await?;
+	/// socket.send(VMCBlendShape::new(VMCStandardVRMBlendShape::Joy, 1.0)).await?;
+	/// # Ok(()) }) }
+	/// ```
+	pub async fn connect<A: ToSocketAddrs>(addr: A) -> VMCResult<Self> {
+		let stream = TcpStream::connect(addr).await?;
+		stream.set_nodelay(true)?;
+		Self::new(stream)
+	}
+
+	/// Sets the options used to encode packets sent on this connection, e.g. how non-finite floats are handled.
+	///
+	/// See [`VMCSocket::set_encode_options`](crate::VMCSocket::set_encode_options).
+	pub fn set_encode_options(&mut self, options: EncodeOptions) {
+		self.encode_options = options;
+	}
+
+	/// Returns the options used to encode packets sent on this connection.
+	pub fn encode_options(&self) -> &EncodeOptions {
+		&self.encode_options
+	}
+
+	/// Sends an OSC packet over the connection.
+	pub async fn send<P: IntoOSCPacket>(&self, packet: P) -> VMCResult<()> {
+		let mut buf = Vec::new();
+		encode_length_prefixed(&packet.into_osc_packet(), &self.encode_options, &mut buf)?;
+		let mut writer = self.writer.lock().await;
+		writer.write_all(&buf).await?;
+		Ok(())
+	}
+
+	/// Sends a complete [`VMCPose`] over the connection.
+	///
+	/// See [`VMCSocket::send_pose`](crate::VMCSocket::send_pose).
+	pub async fn send_pose(&self, pose: &VMCPose) -> VMCResult<()> {
+		let mut buf = Vec::new();
+		for packet in pose.to_packets() {
+			encode_length_prefixed(&packet, &self.encode_options, &mut buf)?;
+		}
+		let mut writer = self.writer.lock().await;
+		writer.write_all(&buf).await?;
+		Ok(())
+	}
+
+	/// Receives a single OSC packet from the connection.
+	pub async fn recv(&mut self) -> VMCResult<OSCPacket> {
+		match poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await {
+			Some(res) => res.map(|(packet, _)| packet),
+			None => Err(io::Error::from(io::ErrorKind::UnexpectedEof).into())
+		}
+	}
+
+	/// Receives a single OSC packet from the connection and [parses](crate::parse) it into its contained
+	/// [`VMCMessage`]s.
+	pub async fn recv_message(&mut self) -> VMCResult<Vec<VMCMessage>> {
+		parse(self.recv().await?)
+	}
+
+	/// Returns a stream of parsed [`VMCMessage`]s received on this connection.
+	///
+	/// See [`VMCSocket::messages`](crate::VMCSocket::messages).
+	pub fn messages(&mut self) -> VMCMessages<&mut Self> {
+		VMCMessages::new(self)
+	}
+
+	/// Returns a stream of complete frames received on this connection.
+	///
+	/// See [`VMCSocket::frames`](crate::VMCSocket::frames).
+	pub fn frames(&mut self) -> VMCFrames<VMCMessages<&mut Self>> {
+		self.messages().frames()
+	}
+
+	/// Returns the address of the remote peer of this connection.
+	pub fn peer_addr(&self) -> SocketAddr {
+		self.peer_addr
+	}
+
+	/// Returns the local address of this connection.
+	pub fn local_addr(&self) -> VMCResult<SocketAddr> {
+		Ok(self.reader.get_ref().local_addr()?)
+	}
+
+	/// Shuts down the write half of the connection.
+	pub async fn shutdown(&self) -> VMCResult<()> {
+		self.writer.lock().await.shutdown().await?;
+		Ok(())
+	}
+}
+
+impl Stream for VMCTcpSocket {
+	type Item = VMCResult<(OSCPacket, SocketAddr)>;
+
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		let peer_addr = self.peer_addr;
+		self.reader
+			.poll_next_packet(cx)
+			.map(|packet| packet.map(|packet| packet.map(|packet| (packet, peer_addr))))
+	}
+}
+
+/// A TCP listener accepting connections from VMC applications.
+///
+/// Accepted connections are returned as [`VMCTcpSocket`]s. This type also implements [`Stream`], yielding each accepted
+/// connection.
+///
+/// # Examples
+///
+/// ```no_run
+/// # fn main() -> vmc::VMCResult<()> { tokio_test::block_on(async {
+/// use futures_util::StreamExt;
+/// use vmc::VMCTcpListener;
+///
+/// let listener = VMCTcpListener::bind("127.0.0.1:39539").await?;
+/// loop {
+/// 	let (mut socket, _) = listener.accept().await?;
+/// 	tokio::spawn(async move {
+/// 		let mut messages = socket.messages();
+/// 		while let Some(Ok((message, _))) = messages.next().await {
+/// 			println!("{:?}", message);
+/// 		}
+/// 	});
+/// }
+/// # Ok(()) }) }
+/// ```
+#[derive(Debug)]
+pub struct VMCTcpListener {
+	listener: TcpListener
+}
+
+impl VMCTcpListener {
+	/// Creates a new listener from a [`tokio::net::TcpListener`].
+	pub fn new(listener: TcpListener) -> Self {
+		Self { listener }
+	}
+
+	/// Creates a new listener bound to the given address.
+	pub async fn bind<A: ToSocketAddrs>(addr: A) -> VMCResult<Self> {
+		Ok(Self::new(TcpListener::bind(addr).await?))
+	}
+
+	/// Accepts a new incoming connection from this listener.
+	pub async fn accept(&self) -> VMCResult<(VMCTcpSocket, SocketAddr)> {
+		let (stream, addr) = self.listener.accept().await?;
+		stream.set_nodelay(true)?;
+		Ok((VMCTcpSocket::new(stream)?, addr))
+	}
+
+	/// Get a reference to the underlying [`TcpListener`].
+	pub fn listener(&self) -> &TcpListener {
+		&self.listener
+	}
+
+	/// Returns the local address that this listener is bound to.
+	pub fn local_addr(&self) -> VMCResult<SocketAddr> {
+		Ok(self.listener.local_addr()?)
+	}
+}
+
+impl Stream for VMCTcpListener {
+	type Item = VMCResult<VMCTcpSocket>;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		let (stream, _) = ready!(self.listener.poll_accept(cx))?;
+		stream.set_nodelay(true)?;
+		Poll::Ready(Some(VMCTcpSocket::new(stream)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use futures_util::StreamExt;
+	use glam::{Quat, Vec3A};
+	use tokio::{io::AsyncWriteExt, net::TcpStream};
+
+	use super::*;
+	use crate::{
+		VMCBlendShape, VMCBoneTransform, VMCError, VMCStandardVRM0Bone, VMCStandardVRMBlendShape, VMCTime,
+		osc::{NonFinitePolicy, OSCError, OSCMessage}
+	};
+
+	fn length_prefixed(packet: &OSCPacket) -> Vec<u8> {
+		let mut buf = Vec::new();
+		encode_length_prefixed(packet, &EncodeOptions::default(), &mut buf).unwrap();
+		buf
+	}
+
+	#[tokio::test]
+	async fn test_tcp_roundtrip() -> VMCResult<()> {
+		let listener = crate::marionette!("127.0.0.1:0", transport = tcp).await?;
+		let performer = crate::performer!(listener.local_addr()?, transport = tcp).await?;
+		let (mut marionette, addr) = listener.accept().await?;
+		assert_eq!(addr, performer.local_addr()?);
+		assert_eq!(marionette.peer_addr(), performer.local_addr()?);
+
+		performer.send(VMCBlendShape::new(VMCStandardVRMBlendShape::Joy, 0.5)).await?;
+		assert!(matches!(&marionette.recv_message().await?[..], [VMCMessage::BlendShape(shape)] if shape.value == 0.5));
+
+		let mut pose = VMCPose::new();
+		pose.set_bone(VMCStandardVRM0Bone::Head, Vec3A::Y, Quat::IDENTITY);
+		pose.time = Some(VMCTime::new(1.0));
+		performer.send_pose(&pose).await?;
+		let mut frames = marionette.frames();
+		let (frame, _) = frames.next().await.unwrap()?;
+		assert_eq!(frame.time, VMCTime::new(1.0));
+		assert!(
+			frame
+				.messages
+				.iter()
+				.any(|message| matches!(message, VMCMessage::BoneTransform(transform) if transform.bone == VMCStandardVRM0Bone::Head))
+		);
+
+		// either side can send, and shutting down ends the other side's stream
+		marionette.send(VMCTime::new(2.0)).await?;
+		let mut performer = performer;
+		assert!(matches!(&performer.recv_message().await?[..], [VMCMessage::Time(VMCTime(t))] if *t == 2.0));
+		marionette.shutdown().await?;
+		assert!(performer.next().await.is_none());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_tcp_framing() -> VMCResult<()> {
+		let mut listener = VMCTcpListener::bind("127.0.0.1:0").await?;
+		let mut stream = TcpStream::connect(listener.local_addr()?).await?;
+		let mut marionette = listener.next().await.unwrap()?;
+
+		let packets: Vec<OSCPacket> = (0..3).map(|i| OSCPacket::Message(OSCMessage::new("/VMC/Ext/T", (i as f32,)))).collect();
+		let bytes: Vec<u8> = packets.iter().flat_map(length_prefixed).collect();
+
+		// packets split across reads at arbitrary points, including inside the size prefix
+		for chunk in [&bytes[..2], &bytes[2..13], &bytes[13..]] {
+			stream.write_all(chunk).await?;
+			stream.flush().await?;
+			tokio::time::sleep(Duration::from_millis(10)).await;
+		}
+		for packet in &packets {
+			assert_eq!(&marionette.recv().await?, packet);
+		}
+
+		// the stream closing in the middle of a packet is an error, rather than a clean end of stream
+		stream.write_all(&bytes[..6]).await?;
+		stream.shutdown().await?;
+		let err = marionette.recv().await.unwrap_err();
+		assert!(matches!(err, VMCError::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof));
+		assert!(marionette.next().await.is_none());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_tcp_encode_options() -> VMCResult<()> {
+		let listener = VMCTcpListener::bind("127.0.0.1:0").await?;
+		let mut performer = VMCTcpSocket::connect(listener.local_addr()?).await?;
+		let (mut marionette, _) = listener.accept().await?;
+
+		// invalid strings are rejected by default, as on UDP sockets
+		let err = performer.send(VMCBlendShape::new("Joy\0", 1.0)).await.unwrap_err();
+		assert!(matches!(err, VMCError::Osc(OSCError::InvalidString(_))));
+
+		performer.set_encode_options(EncodeOptions::new().with_non_finite(NonFinitePolicy::Zero));
+		performer
+			.send(VMCBoneTransform::new(VMCStandardVRM0Bone::Hips, Vec3A::NAN, Quat::IDENTITY))
+			.await?;
+		let packet = marionette.recv().await?;
+		assert!(matches!(&parse(packet)?[..], [VMCMessage::BoneTransform(transform)] if transform.position == Vec3A::ZERO));
+
+		performer.set_encode_options(EncodeOptions::strict());
+		let mut pose = VMCPose::new();
+		pose.set_bone(VMCStandardVRM0Bone::Hips, Vec3A::NAN, Quat::IDENTITY);
+		assert!(matches!(performer.send_pose(&pose).await.unwrap_err(), VMCError::Osc(OSCError::NonFiniteFloat(_))));
+		Ok(())
+	}
+}
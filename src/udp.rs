@@ -1,26 +1,59 @@
+#[cfg(any(target_os = "android", target_os = "linux"))]
+use std::{cell::RefCell, time::SystemTime};
 use std::{
-	fmt,
-	future::Future,
-	io,
+	fmt, io,
+	mem::MaybeUninit,
 	net::SocketAddr,
 	pin::Pin,
 	sync::Arc,
 	task::{Context, Poll}
 };
 
+use bytes::{Bytes, BytesMut};
 use futures_core::Stream;
+#[cfg(any(target_os = "android", target_os = "linux"))]
+use smallvec::SmallVec;
 use tokio::net::UdpSocket;
 
-pub(crate) type RecvFuture = Pin<Box<dyn Future<Output = io::Result<(Vec<u8>, usize, SocketAddr)>> + Send + Sync>>;
+use crate::VMCRecvTimestamp;
 
+/// The maximum size of a UDP datagram.
+const MAX_DATAGRAM_SIZE: usize = 64 * 1024;
+/// The maximum number of datagrams sent with a single `sendmmsg(2)` call.
+#[cfg(any(target_os = "android", target_os = "linux"))]
+pub(crate) const MAX_BATCH: usize = 32;
+/// The maximum number of datagrams received with a single `recvmmsg(2)` call. Space for a full datagram is reserved for
+/// each in the thread's [scratch buffer](SCRATCH), so this is kept lower than [`MAX_BATCH`]; each thread which receives
+/// batches keeps up to 1 MiB (`MAX_RECV_BATCH * MAX_DATAGRAM_SIZE`) allocated for as long as the thread lives.
+#[cfg(any(target_os = "android", target_os = "linux"))]
+const MAX_RECV_BATCH: usize = 16;
+
+#[cfg(any(target_os = "android", target_os = "linux"))]
+thread_local! {
+	/// The buffer batches are received into before being copied into a stream's buffer. It is shared by all streams
+	/// on a thread, since it only holds datagrams for the duration of a single receive call.
+	static SCRATCH: RefCell<Vec<MaybeUninit<u8>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A datagram received on a [`UDPSocketStream`].
+pub(crate) type Datagram = (Bytes, SocketAddr, VMCRecvTimestamp);
+/// The length, sender, and kernel receive timestamp of a datagram received with `recvmsg(2)` or `recvmmsg(2)`.
+#[cfg(any(target_os = "android", target_os = "linux"))]
+type Received = (usize, SocketAddr, Option<SystemTime>);
+
+/// A stream of datagrams received on a UDP socket.
+///
+/// Datagrams are received directly into a shared buffer and handed out as [`Bytes`] without copying. Once all handles
+/// to previously received datagrams are dropped, their space in the buffer is reused, so a consumer which processes
+/// each datagram before receiving the next never allocates.
+///
+/// Batches are received into a per-thread scratch buffer with room for a full datagram per slot, then copied into the
+/// shared buffer, so retained datagrams only hold on to as much memory as they actually use.
 pub(crate) struct UDPSocketStream {
 	pub(crate) socket: Arc<UdpSocket>,
-	future: Option<RecvFuture>,
-	buf: Option<Vec<u8>>
+	buf: BytesMut
 }
 
-unsafe impl Send for UDPSocketStream {}
-
 impl Clone for UDPSocketStream {
 	fn clone(&self) -> Self {
 		Self::from_arc(self.socket.clone())
@@ -40,8 +73,7 @@ impl UDPSocketStream {
 	}
 
 	pub fn from_arc(socket: Arc<UdpSocket>) -> Self {
-		let buf = vec![0u8; 1024 * 64];
-		Self { socket, future: None, buf: Some(buf) }
+		Self { socket, buf: BytesMut::new() }
 	}
 
 	pub fn get_ref(&self) -> &UdpSocket {
@@ -51,42 +83,274 @@ impl UDPSocketStream {
 	pub fn clone_inner(&self) -> Arc<UdpSocket> {
 		Arc::clone(&self.socket)
 	}
+
+	#[cfg(not(any(target_os = "android", target_os = "linux")))]
+	fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<(usize, SocketAddr, VMCRecvTimestamp)>> {
+		let mut buf = tokio::io::ReadBuf::uninit(spare_capacity(&mut self.buf));
+		let addr = std::task::ready!(self.socket.poll_recv_from(cx, &mut buf))?;
+		Poll::Ready(Ok((buf.filled().len(), addr, VMCRecvTimestamp::now(None))))
+	}
+
+	#[cfg(any(target_os = "android", target_os = "linux"))]
+	fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<(usize, SocketAddr, VMCRecvTimestamp)>> {
+		loop {
+			std::task::ready!(self.socket.poll_recv_ready(cx))?;
+			let buf = spare_capacity(&mut self.buf);
+			match self.socket.try_io(tokio::io::Interest::READABLE, || recvmsg(&self.socket, buf)) {
+				Ok((n, addr, kernel)) => return Poll::Ready(Ok((n, addr, VMCRecvTimestamp::now(kernel)))),
+				// readiness was cleared by `try_io`, so the next `poll_recv_ready` registers for a wakeup
+				Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+				Err(e) => return Poll::Ready(Err(e))
+			}
+		}
+	}
+}
+
+impl UDPSocketStream {
+	/// Receives up to `max` datagrams into `batch`, waiting until at least one is available.
+	///
+	/// On Linux, all datagrams which are already queued on the socket are received with a single `recvmmsg(2)` call.
+	/// Elsewhere, datagrams are received one at a time until the socket would block.
+	#[cfg(not(any(target_os = "android", target_os = "linux")))]
+	pub fn poll_recv_batch(&mut self, cx: &mut Context<'_>, max: usize, batch: &mut Vec<Datagram>) -> Poll<io::Result<()>> {
+		let start = batch.len();
+		while batch.len() - start < max {
+			match self.poll_recv(cx) {
+				Poll::Ready(Ok((n, addr, timestamp))) => batch.push((self.take_datagram(n), addr, timestamp)),
+				// report errors on the next call if some datagrams were already received
+				Poll::Ready(Err(e)) if batch.len() == start => return Poll::Ready(Err(e)),
+				Poll::Pending if batch.len() == start => return Poll::Pending,
+				Poll::Ready(Err(_)) | Poll::Pending => break
+			}
+		}
+		Poll::Ready(Ok(()))
+	}
+
+	/// Receives up to `max` datagrams into `batch`, waiting until at least one is available.
+	///
+	/// On Linux, all datagrams which are already queued on the socket are received with a single `recvmmsg(2)` call.
+	/// Elsewhere, datagrams are received one at a time until the socket would block.
+	#[cfg(any(target_os = "android", target_os = "linux"))]
+	pub fn poll_recv_batch(&mut self, cx: &mut Context<'_>, max: usize, batch: &mut Vec<Datagram>) -> Poll<io::Result<()>> {
+		let count = max.clamp(1, MAX_RECV_BATCH);
+		let received = loop {
+			std::task::ready!(self.socket.poll_recv_ready(cx))?;
+			let received = SCRATCH.with(|scratch| {
+				let mut scratch = scratch.borrow_mut();
+				if scratch.len() < count * MAX_DATAGRAM_SIZE {
+					scratch.resize(count * MAX_DATAGRAM_SIZE, MaybeUninit::uninit());
+				}
+				let scratch = &mut scratch[..count * MAX_DATAGRAM_SIZE];
+				let received = self
+					.socket
+					.try_io(tokio::io::Interest::READABLE, || recvmmsg(&self.socket, scratch, count))?;
+				// copy the datagrams out of their slots to directly follow each other, only reserving the space they use
+				self.buf.reserve(received.iter().map(|&(n, ..)| n).sum());
+				for (i, &(n, ..)) in received.iter().enumerate() {
+					let slot = &scratch[i * MAX_DATAGRAM_SIZE..i * MAX_DATAGRAM_SIZE + n];
+					// SAFETY: the first `n` bytes of the slot were initialized by `recvmmsg`
+					self.buf
+						.extend_from_slice(unsafe { std::slice::from_raw_parts(slot.as_ptr().cast::<u8>(), n) });
+				}
+				Ok::<_, io::Error>(received)
+			});
+			match received {
+				Ok(received) => break received,
+				Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+				Err(e) => return Poll::Ready(Err(e))
+			}
+		};
+		for (n, addr, kernel) in received {
+			batch.push((self.buf.split_to(n).freeze(), addr, VMCRecvTimestamp::now(kernel)));
+		}
+		Poll::Ready(Ok(()))
+	}
+
+	/// Splits off the first `n` bytes of the spare capacity, which were initialized by a receive call.
+	fn take_datagram(&mut self, n: usize) -> Bytes {
+		// SAFETY: the first `n` bytes of the spare capacity were initialized by the receive call
+		unsafe { self.buf.set_len(n) };
+		self.buf.split().freeze()
+	}
+}
+
+/// Makes room for a full datagram in `buf`, returning the uninitialized space to receive it into.
+fn spare_capacity(buf: &mut BytesMut) -> &mut [MaybeUninit<u8>] {
+	// reclaims the space of previously received datagrams if they have all been dropped
+	buf.reserve(MAX_DATAGRAM_SIZE);
+	&mut buf.spare_capacity_mut()[..MAX_DATAGRAM_SIZE]
 }
 
 impl Stream for UDPSocketStream {
-	type Item = io::Result<(Vec<u8>, SocketAddr)>;
+	type Item = io::Result<Datagram>;
 
 	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-		loop {
-			if self.future.is_none() {
-				let buf = self.buf.take().unwrap();
-				let future = recv_next(Arc::clone(&self.socket), buf);
-				self.future = Some(Box::pin(future));
-			}
+		let (n, addr, timestamp) = match std::task::ready!(self.poll_recv(cx)) {
+			Ok(received) => received,
+			Err(e) => return Poll::Ready(Some(Err(e)))
+		};
+		Poll::Ready(Some(Ok((self.take_datagram(n), addr, timestamp))))
+	}
+}
+
+/// Receives a datagram with `recvmsg(2)`, returning the kernel receive timestamp if `SO_TIMESTAMP` is enabled on the
+/// socket.
+#[cfg(any(target_os = "android", target_os = "linux"))]
+fn recvmsg(socket: &UdpSocket, buf: &mut [MaybeUninit<u8>]) -> io::Result<Received> {
+	use std::{mem, os::fd::AsRawFd};
+
+	use socket2::SockAddr;
+
+	// u64 to satisfy the alignment of `cmsghdr`
+	let mut control = [0u64; 8];
+	let mut iov = libc::iovec {
+		iov_base: buf.as_mut_ptr().cast(),
+		iov_len: buf.len()
+	};
+	// SAFETY: `try_init` provides valid storage for a socket address; `recvmsg` initializes it and its length.
+	let ((n, kernel), addr) = unsafe {
+		SockAddr::try_init(|storage, len| {
+			let mut msg: libc::msghdr = mem::zeroed();
+			msg.msg_name = storage.cast();
+			msg.msg_namelen = *len;
+			msg.msg_iov = &mut iov;
+			msg.msg_iovlen = 1;
+			msg.msg_control = control.as_mut_ptr().cast();
+			msg.msg_controllen = mem::size_of_val(&control) as _;
 
-			if let Some(f) = &mut self.future {
-				let res = match f.as_mut().poll(cx) {
-					Poll::Ready(t) => t,
-					Poll::Pending => return Poll::Pending
-				};
-				self.future = None;
-				return match res {
-					Err(e) => Poll::Ready(Some(Err(e))),
-					Ok((buf, n, addr)) => {
-						let res_buf = buf[..n].to_vec();
-						self.buf = Some(buf);
-						Poll::Ready(Some(Ok((res_buf, addr))))
-					}
-				};
+			let n = libc::recvmsg(socket.as_raw_fd(), &mut msg, 0);
+			if n < 0 {
+				return Err(io::Error::last_os_error());
 			}
+			*len = msg.msg_namelen;
+
+			Ok((n as usize, kernel_timestamp(&msg)))
+		})?
+	};
+	let addr = addr
+		.as_socket()
+		.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "received packet from non-IP address"))?;
+	Ok((n, addr, kernel))
+}
+
+/// Receives up to `count` datagrams with a single `recvmmsg(2)` call, each into its own `MAX_DATAGRAM_SIZE` slot of
+/// `buf`. Returns the length, sender, and kernel receive timestamp of each received datagram.
+#[cfg(any(target_os = "android", target_os = "linux"))]
+fn recvmmsg(socket: &UdpSocket, buf: &mut [MaybeUninit<u8>], count: usize) -> io::Result<SmallVec<[Received; MAX_RECV_BATCH]>> {
+	use std::{mem, os::fd::AsRawFd, ptr};
+
+	use socket2::{SockAddr, SockAddrStorage};
+
+	let count = count.min(MAX_RECV_BATCH);
+	let mut addrs: [SockAddrStorage; MAX_RECV_BATCH] = std::array::from_fn(|_| SockAddrStorage::zeroed());
+	// u64 to satisfy the alignment of `cmsghdr`
+	let mut controls = [[0u64; 8]; MAX_RECV_BATCH];
+	// SAFETY: `iovec` & `mmsghdr` are plain C structs for which all zeroes is a valid (empty) value.
+	let (mut iovs, mut msgs): ([libc::iovec; MAX_RECV_BATCH], [libc::mmsghdr; MAX_RECV_BATCH]) = unsafe { (mem::zeroed(), mem::zeroed()) };
+	let slots = buf.chunks_exact_mut(MAX_DATAGRAM_SIZE).zip(&mut iovs).zip(&mut msgs);
+	for (((slot, iov), msg), (addr, control)) in slots.zip(addrs.iter_mut().zip(&mut controls)).take(count) {
+		iov.iov_base = slot.as_mut_ptr().cast();
+		iov.iov_len = slot.len();
+		msg.msg_hdr.msg_namelen = addr.size_of();
+		msg.msg_hdr.msg_name = (addr as *mut SockAddrStorage).cast();
+		msg.msg_hdr.msg_iov = iov;
+		msg.msg_hdr.msg_iovlen = 1;
+		msg.msg_hdr.msg_control = control.as_mut_ptr().cast();
+		msg.msg_hdr.msg_controllen = mem::size_of_val(control) as _;
+	}
+
+	// SAFETY: each message points to valid storage for its address, control messages, and data.
+	let n = unsafe { libc::recvmmsg(socket.as_raw_fd(), msgs.as_mut_ptr(), count as _, 0, ptr::null_mut()) };
+	if n < 0 {
+		return Err(io::Error::last_os_error());
+	}
+	let mut received = SmallVec::new();
+	for (msg, addr) in msgs.iter().zip(addrs).take(n as usize) {
+		// SAFETY: `recvmmsg` initialized the address and its length.
+		let addr = unsafe { SockAddr::new(addr, msg.msg_hdr.msg_namelen) }
+			.as_socket()
+			.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "received packet from non-IP address"))?;
+		// SAFETY: `recvmmsg` initialized the control messages.
+		received.push((msg.msg_len as usize, addr, unsafe { kernel_timestamp(&msg.msg_hdr) }));
+	}
+	Ok(received)
+}
+
+/// Reads the `SO_TIMESTAMP` receive timestamp from the control messages of a received message, if present.
+///
+/// # Safety
+/// `msg` must have been filled in by a successful receive call.
+#[cfg(any(target_os = "android", target_os = "linux"))]
+unsafe fn kernel_timestamp(msg: &libc::msghdr) -> Option<SystemTime> {
+	use std::{ptr, time::Duration};
+
+	let mut kernel = None;
+	let mut cmsg = libc::CMSG_FIRSTHDR(msg);
+	while !cmsg.is_null() {
+		if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_TIMESTAMP {
+			let tv = ptr::read_unaligned(libc::CMSG_DATA(cmsg).cast::<libc::timeval>());
+			kernel = Some(std::time::UNIX_EPOCH + Duration::new(tv.tv_sec as u64, tv.tv_usec as u32 * 1000));
 		}
+		cmsg = libc::CMSG_NXTHDR(msg, cmsg);
+	}
+	kernel
+}
+
+/// Sends up to [`MAX_BATCH`] datagrams on a connected socket with a single `sendmmsg(2)` call, writing the number of
+/// bytes sent for each datagram into `sent`. Returns the number of datagrams which were sent.
+#[cfg(any(target_os = "android", target_os = "linux"))]
+pub(crate) fn sendmmsg(socket: &UdpSocket, datagrams: &[&[u8]], sent: &mut [usize; MAX_BATCH]) -> io::Result<usize> {
+	use std::{mem, os::fd::AsRawFd};
+
+	let len = datagrams.len().min(MAX_BATCH);
+	// SAFETY: `iovec` & `mmsghdr` are plain C structs for which all zeroes is a valid (empty) value.
+	let (mut iovs, mut msgs): ([libc::iovec; MAX_BATCH], [libc::mmsghdr; MAX_BATCH]) = unsafe { (mem::zeroed(), mem::zeroed()) };
+	for ((datagram, iov), msg) in datagrams.iter().zip(&mut iovs).zip(&mut msgs) {
+		iov.iov_base = datagram.as_ptr() as *mut _;
+		iov.iov_len = datagram.len();
+		msg.msg_hdr.msg_iov = iov;
+		msg.msg_hdr.msg_iovlen = 1;
+	}
+
+	// SAFETY: each message points to a single iovec, which in turn points to a datagram that outlives the call.
+	let n = unsafe { libc::sendmmsg(socket.as_raw_fd(), msgs.as_mut_ptr(), len as _, 0) };
+	if n < 0 {
+		return Err(io::Error::last_os_error());
 	}
+	let n = n as usize;
+	for (sent, msg) in sent.iter_mut().zip(&msgs[..n]) {
+		*sent = msg.msg_len as usize;
+	}
+	Ok(n)
 }
 
-async fn recv_next(socket: Arc<UdpSocket>, mut buf: Vec<u8>) -> io::Result<(Vec<u8>, usize, SocketAddr)> {
-	let res = socket.recv_from(&mut buf).await;
-	match res {
-		Err(e) => Err(e),
-		Ok((n, addr)) => Ok((buf, n, addr))
+#[cfg(all(test, any(target_os = "android", target_os = "linux")))]
+mod tests {
+	use std::future::poll_fn;
+
+	use super::*;
+
+	#[tokio::test]
+	async fn test_batch_datagrams_are_right_sized() -> io::Result<()> {
+		let socket = UdpSocket::bind("127.0.0.1:0").await?;
+		let sender = UdpSocket::bind("127.0.0.1:0").await?;
+		sender.connect(socket.local_addr()?).await?;
+		for i in 0..4u8 {
+			sender.send(&[i; 32]).await?;
+		}
+
+		let mut stream = UDPSocketStream::new(socket);
+		let mut batch = Vec::new();
+		while batch.len() < 4 {
+			poll_fn(|cx| stream.poll_recv_batch(cx, 4, &mut batch)).await?;
+		}
+		assert!(batch.iter().enumerate().all(|(i, (datagram, ..))| datagram[..] == [i as u8; 32]));
+
+		// a retained datagram only keeps the space of the batch alive, not a full slot per datagram
+		let datagram = batch.swap_remove(0).0;
+		drop((batch, stream));
+		let datagram = datagram.try_into_mut().expect("datagram should be the last handle to its buffer");
+		assert!(datagram.capacity() < MAX_DATAGRAM_SIZE);
+		Ok(())
 	}
 }
@@ -0,0 +1,66 @@
+//! Checks that receiving standard & previously seen bone and blend shape names doesn't allocate.
+//!
+//! This lives in its own test binary, since it installs a global allocator counting the allocations of each thread.
+
+use std::{
+	alloc::{GlobalAlloc, Layout, System},
+	cell::Cell
+};
+
+use vmc::{
+	IntoOSCPacket, Quat, VMCBlendShape, VMCBoneTransform, VMCMessage, VMCStandardVRM0Bone, VMCStandardVRMBlendShape, Vec3A,
+	osc::{self, OSCBundle}
+};
+
+struct CountingAlloc;
+
+thread_local! {
+	static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+		System.alloc(layout)
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		System.dealloc(ptr, layout)
+	}
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+	let before = ALLOCATIONS.with(Cell::get);
+	let res = f();
+	(res, ALLOCATIONS.with(Cell::get) - before)
+}
+
+#[test]
+fn test_names_are_not_allocated() {
+	let datagram = osc::encode(
+		&OSCBundle::builder()
+			.push(VMCBoneTransform::new(VMCStandardVRM0Bone::LeftUpperArm, Vec3A::ZERO, Quat::IDENTITY))
+			.push(VMCBlendShape::new(VMCStandardVRMBlendShape::Joy, 1.0))
+			.push(VMCBlendShape::new("TestAllocCustomShape", 0.5))
+			.build()
+			.into_osc_packet()
+	)
+	.unwrap();
+
+	// the first time a custom name is seen, it is interned
+	vmc::parse_datagram(&datagram).unwrap();
+
+	// afterwards, the only allocation is the returned `Vec`
+	let (messages, count) = allocations(|| vmc::parse_datagram(&datagram).unwrap());
+	assert_eq!(count, 1);
+	assert!(matches!(&messages[0], VMCMessage::BoneTransform(transform) if transform.bone == VMCStandardVRM0Bone::LeftUpperArm));
+	assert!(matches!(&messages[1], VMCMessage::BlendShape(shape) if shape.key == VMCStandardVRMBlendShape::Joy));
+	assert!(matches!(&messages[2], VMCMessage::BlendShape(shape) if shape.key == "TestAllocCustomShape"));
+
+	// decoding into an `OSCPacket` first copies every string
+	let (_, count) = allocations(|| vmc::parse(osc::decode_udp(&datagram).unwrap().1).unwrap());
+	assert!(count > 1);
+}
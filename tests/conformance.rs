@@ -0,0 +1,70 @@
+//! Conformance tests against the VMC protocol specification.
+//!
+//! So far, the only fixture is reconstructed from the specification; captures from real senders (VirtualMotionCapture,
+//! VSeeFace, VMagicMirror, Warudo) are still missing, and are picked up from the same directory once added.
+//!
+//! Each file in `tests/fixtures` contains one datagram per line as a hex string, as copied from Wireshark with "Copy as
+//! Hex Stream"; lines starting with `#` are comments. Every message in every datagram must either parse, or be one of
+//! the extension messages this crate deliberately doesn't implement. See `tests/fixtures/README.md`.
+
+use std::{fs, path::Path};
+
+use vmc::{OSCPacket, VMCError, osc};
+
+/// Messages of the VMC protocol which don't describe the avatar, and which `parse` reports as unimplemented.
+const UNIMPLEMENTED: &[&str] = &[
+	"/VMC/Ext/Cam",
+	"/VMC/Ext/Con",
+	"/VMC/Ext/Key",
+	"/VMC/Ext/Midi/Note",
+	"/VMC/Ext/Midi/CC/Val",
+	"/VMC/Ext/Midi/CC/Bit",
+	"/VMC/Ext/Light",
+	"/VMC/Ext/Rcv",
+	"/VMC/Ext/VRM",
+	"/VMC/Ext/Remote",
+	"/VMC/Ext/Opt",
+	"/VMC/Ext/Setting/Color",
+	"/VMC/Ext/Setting/Win",
+	"/VMC/Ext/Config"
+];
+
+fn parse_hex(line: &str) -> Vec<u8> {
+	assert!(line.len() % 2 == 0, "odd number of hex digits");
+	(0..line.len())
+		.step_by(2)
+		.map(|i| u8::from_str_radix(&line[i..i + 2], 16).expect("invalid hex digit"))
+		.collect()
+}
+
+#[test]
+fn test_conformance_fixtures() {
+	let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+	let mut fixtures: Vec<_> = fs::read_dir(&dir)
+		.unwrap()
+		.map(|entry| entry.unwrap().path())
+		.filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+		.collect();
+	fixtures.sort();
+	assert!(!fixtures.is_empty());
+
+	for fixture in fixtures {
+		let name = fixture.file_name().unwrap().to_string_lossy().into_owned();
+		let source = fs::read_to_string(&fixture).unwrap();
+		let mut parsed = 0;
+		for (i, line) in source.lines().enumerate().filter(|(_, line)| !line.is_empty() && !line.starts_with('#')) {
+			let datagram = parse_hex(line.trim());
+			let (_, packet) = osc::decode_udp(&datagram).unwrap_or_else(|e| panic!("{name}:{}: failed to decode: {e}\n{}", i + 1, osc::debug_dump(&datagram)));
+
+			for message in packet.into_messages() {
+				let addr = message.addr.clone();
+				match vmc::parse(OSCPacket::Message(message)) {
+					Ok(_) => parsed += 1,
+					Err(VMCError::UnimplementedMessage(..)) if UNIMPLEMENTED.contains(&addr.as_str()) || addr.starts_with("/VMC/Thru/") => {}
+					Err(e) => panic!("{name}:{}: failed to parse {addr}: {e}", i + 1)
+				}
+			}
+		}
+		assert!(parsed > 0, "{name} contains no avatar data");
+	}
+}